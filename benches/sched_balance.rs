@@ -0,0 +1,169 @@
+//! Scheduler Load-Balancing Benchmark
+//!
+//! Boots MelloOS in QEMU with 4 and 8 CPUs and runs the in-kernel
+//! load-balancing workload (`sched_bench_task` in `kernel/src/main.rs`):
+//! two CPU-bound workers per CPU with uneven amounts of work and periodic
+//! short sleeps. Reports the makespan against the ideal (perfectly balanced)
+//! makespan, plus how often idle stealing, periodic pulls, idle kicks and
+//! wake-affine placement kicked in.
+//!
+//! Performance Target:
+//! - Balancing efficiency (ideal / makespan): ≥ 70% on 4 and 8 CPUs
+//!
+//! Setup:
+//! 1. Set `SCHED_BENCH_ENABLED = true` in `kernel/src/config.rs`
+//! 2. `make iso`
+//! 3. `cargo test --release sched_balance -- --ignored --nocapture`
+
+#![cfg(test)]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Performance target
+const TARGET_EFFICIENCY: f64 = 0.70;
+
+/// How long to wait for the benchmark to report
+const QEMU_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// ISO built by `make iso`
+const ISO_PATH: &str = "mellos.iso";
+
+/// Benchmark result structure
+#[derive(Debug)]
+struct BalanceResult {
+    cpus: usize,
+    workers: usize,
+    makespan_ms: u64,
+    ideal_ms: u64,
+    idle_steals: u64,
+    pulls: u64,
+    idle_kicks: u64,
+    wake_affine: u64,
+}
+
+impl BalanceResult {
+    /// Parse a `[SCHED-BENCH] key=value ...` line from the serial console
+    fn parse(line: &str) -> Option<Self> {
+        let fields = line.split("[SCHED-BENCH]").nth(1)?;
+        let value = |key: &str| -> Option<u64> {
+            fields
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix(key)?.strip_prefix('='))?
+                .parse()
+                .ok()
+        };
+
+        Some(Self {
+            cpus: value("cpus")? as usize,
+            workers: value("workers")? as usize,
+            makespan_ms: value("makespan_ms")?,
+            ideal_ms: value("ideal_ms")?,
+            idle_steals: value("idle_steals")?,
+            pulls: value("pulls")?,
+            idle_kicks: value("idle_kicks")?,
+            wake_affine: value("wake_affine")?,
+        })
+    }
+
+    fn efficiency(&self) -> f64 {
+        self.ideal_ms as f64 / self.makespan_ms.max(1) as f64
+    }
+
+    fn passed(&self) -> bool {
+        self.efficiency() >= TARGET_EFFICIENCY
+    }
+
+    fn print(&self) {
+        println!("\n========================================");
+        println!("Load Balancing Benchmark ({} CPUs)", self.cpus);
+        println!("========================================");
+        println!("Workers:      {}", self.workers);
+        println!("Makespan:     {} ms", self.makespan_ms);
+        println!("Ideal:        {} ms", self.ideal_ms);
+        println!();
+
+        println!("Balancing Activity:");
+        println!("  Idle steals:  {}", self.idle_steals);
+        println!("  Pulls:        {}", self.pulls);
+        println!("  Idle kicks:   {}", self.idle_kicks);
+        println!("  Wake-affine:  {}", self.wake_affine);
+        println!();
+
+        let status = if self.passed() { "✓ PASS" } else { "✗ FAIL" };
+        println!(
+            "{} Efficiency: {:.1}% (target: ≥ {:.0}%)",
+            status,
+            self.efficiency() * 100.0,
+            TARGET_EFFICIENCY * 100.0
+        );
+        println!("========================================\n");
+    }
+}
+
+/// Boot MelloOS with `cpus` CPUs and wait for the benchmark result
+fn bench_balance(cpus: usize) -> BalanceResult {
+    println!("Booting MelloOS with {} CPUs...", cpus);
+
+    let mut qemu = Command::new("qemu-system-x86_64")
+        .args(["-cdrom", ISO_PATH])
+        .args(["-smp", &cpus.to_string()])
+        .args(["-m", "2G"])
+        .args(["-serial", "stdio"])
+        .args(["-display", "none"])
+        .arg("-no-reboot")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to start qemu-system-x86_64");
+
+    // Scan the serial console on another thread so the timeout still works
+    let stdout = qemu.stdout.take().expect("No QEMU stdout");
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(result) = BalanceResult::parse(&line) {
+                let _ = tx.send(result);
+                return;
+            }
+        }
+    });
+
+    let result = rx.recv_timeout(QEMU_TIMEOUT);
+    let _ = qemu.kill();
+    let _ = qemu.wait();
+
+    result.expect("No [SCHED-BENCH] result (is SCHED_BENCH_ENABLED set?)")
+}
+
+#[test]
+fn test_parse_result_line() {
+    let line = "[SCHED-BENCH] cpus=4 workers=8 makespan_ms=900 ideal_ms=800 \
+                idle_steals=12 pulls=3 idle_kicks=5 wake_affine=40";
+    let result = BalanceResult::parse(line).expect("Failed to parse");
+    assert_eq!(result.cpus, 4);
+    assert_eq!(result.workers, 8);
+    assert_eq!(result.idle_steals, 12);
+    assert!(result.passed());
+
+    assert!(BalanceResult::parse("[SCHED] unrelated").is_none());
+}
+
+#[test]
+#[ignore] // Ignore by default - requires QEMU and a benchmark-enabled ISO
+fn test_balance_smp4() {
+    let result = bench_balance(4);
+    result.print();
+    assert!(result.passed(), "4-CPU balancing efficiency below target");
+}
+
+#[test]
+#[ignore] // Ignore by default - requires QEMU and a benchmark-enabled ISO
+fn test_balance_smp8() {
+    let result = bench_balance(8);
+    result.print();
+    assert!(result.passed(), "8-CPU balancing efficiency below target");
+}
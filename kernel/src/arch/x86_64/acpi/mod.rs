@@ -1,15 +1,20 @@
 use crate::config::MAX_CPUS;
 /// ACPI (Advanced Configuration and Power Interface) support
 /// This module provides ACPI table parsing, specifically the MADT
-/// (Multiple APIC Description Table) for CPU and APIC discovery.
+/// (Multiple APIC Description Table) for CPU and APIC discovery and the
+/// SRAT (System Resource Affinity Table) for the NUMA topology.
 use crate::{serial_print, serial_println};
 use core::slice;
 use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Once;
 
 /// Global MADT information
 static mut MADT_INFO: Option<MadtInfo> = None;
 static MADT_INITIALIZED: AtomicBool = AtomicBool::new(false);
 
+/// Global SRAT information (absent on non-NUMA machines)
+static SRAT_INFO: Once<SratInfo> = Once::new();
+
 /// RSDP (Root System Description Pointer) structure
 /// This is the first ACPI structure we need to find
 #[repr(C, packed)]
@@ -81,6 +86,58 @@ struct MadtIoApic {
     gsi_base: u32,
 }
 
+/// SRAT table structure
+#[repr(C, packed)]
+struct Srat {
+    header: SdtHeader,
+    table_revision: u32,
+    reserved: u64,
+    // Followed by variable-length affinity structures
+}
+
+/// SRAT Entry Type 0: Processor Local APIC Affinity
+#[repr(C, packed)]
+struct SratLocalApic {
+    header: MadtEntryHeader,
+    proximity_domain_lo: u8,
+    apic_id: u8,
+    flags: u32,
+    local_sapic_eid: u8,
+    proximity_domain_hi: [u8; 3],
+    clock_domain: u32,
+}
+
+/// SRAT Entry Type 1: Memory Affinity
+#[repr(C, packed)]
+struct SratMemory {
+    header: MadtEntryHeader,
+    proximity_domain: u32,
+    reserved1: u16,
+    base_address: u64,
+    length: u64,
+    reserved2: u32,
+    flags: u32,
+    reserved3: u64,
+}
+
+/// SRAT Entry Type 2: Processor Local x2APIC Affinity
+#[repr(C, packed)]
+struct SratX2Apic {
+    header: MadtEntryHeader,
+    reserved1: u16,
+    proximity_domain: u32,
+    x2apic_id: u32,
+    flags: u32,
+    clock_domain: u32,
+    reserved2: u32,
+}
+
+/// SRAT affinity entry flag: entry is enabled
+const SRAT_ENABLED: u32 = 1 << 0;
+
+/// Maximum number of SRAT memory ranges kept
+const MAX_SRAT_MEMORY: usize = crate::mm::pmm::MAX_NODE_RANGES;
+
 /// CPU information extracted from MADT
 #[derive(Debug, Clone, Copy)]
 pub struct CpuInfo {
@@ -106,6 +163,51 @@ pub struct MadtInfo {
     pub ioapic_count: usize,
 }
 
+/// Memory range and its NUMA node, extracted from SRAT
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryAffinity {
+    pub base: u64,
+    pub length: u64,
+    pub node: u8,
+}
+
+/// Parsed SRAT information
+///
+/// Proximity domains are renumbered densely (0..node_count) in the order
+/// they first appear in the table.
+pub struct SratInfo {
+    pub memory: [Option<MemoryAffinity>; MAX_SRAT_MEMORY],
+    pub memory_count: usize,
+    /// (APIC ID, node) for every enabled CPU
+    pub cpus: [Option<(u32, u8)>; MAX_CPUS],
+    pub cpu_count: usize,
+    pub node_count: usize,
+    domains: [u32; crate::mm::pmm::MAX_NUMA_NODES],
+}
+
+impl SratInfo {
+    /// Map a proximity domain to a dense node id
+    fn node_for_domain(&mut self, domain: u32) -> u8 {
+        for node in 0..self.node_count {
+            if self.domains[node] == domain {
+                return node as u8;
+            }
+        }
+
+        if self.node_count < self.domains.len() {
+            self.domains[self.node_count] = domain;
+            self.node_count += 1;
+            (self.node_count - 1) as u8
+        } else {
+            serial_println!(
+                "[ACPI] Warning: too many NUMA nodes, folding domain {} into node 0",
+                domain
+            );
+            0
+        }
+    }
+}
+
 /// ACPI parsing errors
 #[derive(Debug)]
 pub enum AcpiError {
@@ -113,6 +215,8 @@ pub enum AcpiError {
     InvalidChecksum,
     MadtNotFound,
     InvalidMadt,
+    TableNotFound,
+    InvalidSrat,
 }
 
 /// Validate ACPI table checksum
@@ -163,6 +267,24 @@ pub fn init_acpi(rsdp_addr: u64) -> Result<(), AcpiError> {
     }
     MADT_INITIALIZED.store(true, Ordering::Release);
 
+    // The SRAT is optional: without it the machine is a single NUMA node
+    match find_table(rsdp_addr, b"SRAT").and_then(parse_srat_table) {
+        Ok(srat_info) => {
+            serial_println!(
+                "[ACPI] NUMA: {} nodes, {} memory ranges",
+                srat_info.node_count,
+                srat_info.memory_count
+            );
+            SRAT_INFO.call_once(|| srat_info);
+        }
+        Err(AcpiError::TableNotFound) => {
+            serial_println!("[ACPI] No SRAT, assuming a single NUMA node");
+        }
+        Err(e) => {
+            serial_println!("[ACPI] Ignoring SRAT: {:?}", e);
+        }
+    }
+
     Ok(())
 }
 
@@ -176,6 +298,25 @@ pub fn get_madt_info() -> Option<&'static MadtInfo> {
     }
 }
 
+/// Get reference to SRAT information
+/// Returns None if there is no SRAT or ACPI has not been initialized
+pub fn get_srat_info() -> Option<&'static SratInfo> {
+    SRAT_INFO.get()
+}
+
+/// NUMA node of the CPU with the given APIC ID (0 without an SRAT)
+pub fn numa_node_for_apic(apic_id: u8) -> u8 {
+    get_srat_info()
+        .and_then(|srat| {
+            srat.cpus[..srat.cpu_count]
+                .iter()
+                .flatten()
+                .find(|(id, _)| *id == apic_id as u32)
+                .map(|&(_, node)| node)
+        })
+        .unwrap_or(0)
+}
+
 /// Parse MADT table and extract CPU and APIC information
 ///
 /// # Arguments
@@ -185,6 +326,23 @@ pub fn get_madt_info() -> Option<&'static MadtInfo> {
 /// * `Ok(MadtInfo)` - Parsed MADT information with CPU list and APIC addresses
 /// * `Err(AcpiError)` - Error if parsing fails
 fn parse_madt(rsdp_addr: u64) -> Result<MadtInfo, AcpiError> {
+    let madt_addr = find_table(rsdp_addr, b"APIC").map_err(|e| match e {
+        AcpiError::TableNotFound => AcpiError::MadtNotFound,
+        e => e,
+    })?;
+
+    serial_println!("[ACPI] MADT found at 0x{:x}", madt_addr);
+
+    // Parse MADT
+    parse_madt_table(madt_addr)
+}
+
+/// Find an ACPI table by signature through the RSDT or XSDT
+///
+/// # Arguments
+/// * `rsdp_addr` - Physical address of the RSDP structure
+/// * `signature` - Four-byte table signature (e.g. `b"APIC"`)
+fn find_table(rsdp_addr: u64, signature: &[u8; 4]) -> Result<u64, AcpiError> {
     serial_println!("[ACPI] RSDP found at 0x{:x}", rsdp_addr);
 
     // Read RSDP structure
@@ -208,7 +366,7 @@ fn parse_madt(rsdp_addr: u64) -> Result<MadtInfo, AcpiError> {
 
     // Determine which table to use (RSDT or XSDT)
     serial_println!("[ACPI] Determining table type...");
-    let madt_addr = if rsdp.revision >= 2 {
+    if rsdp.revision >= 2 {
         // ACPI 2.0+: Use XSDT
         serial_println!("[ACPI] Using XSDT (ACPI 2.0+)");
         let xsdt_addr = unsafe {
@@ -216,10 +374,7 @@ fn parse_madt(rsdp_addr: u64) -> Result<MadtInfo, AcpiError> {
             core::ptr::read_unaligned(core::ptr::addr_of!((*rsdp_ext_ptr).xsdt_address))
         };
         serial_println!("[ACPI] XSDT address: 0x{:x}", xsdt_addr);
-        serial_println!("[ACPI] Calling find_madt_in_xsdt...");
-        let addr = find_madt_in_xsdt(xsdt_addr)?;
-        serial_println!("[ACPI] find_madt_in_xsdt returned: 0x{:x}", addr);
-        addr
+        find_table_in_xsdt(xsdt_addr, signature)
     } else {
         // ACPI 1.0: Use RSDT
         serial_println!("[ACPI] Using RSDT (ACPI 1.0)");
@@ -227,17 +382,12 @@ fn parse_madt(rsdp_addr: u64) -> Result<MadtInfo, AcpiError> {
             let rsdp_ptr = rsdp_addr as *const Rsdp;
             core::ptr::read_unaligned(core::ptr::addr_of!((*rsdp_ptr).rsdt_address))
         };
-        find_madt_in_rsdt(rsdt_addr as u64)?
-    };
-
-    serial_println!("[ACPI] MADT found at 0x{:x}", madt_addr);
-
-    // Parse MADT
-    parse_madt_table(madt_addr)
+        find_table_in_rsdt(rsdt_addr as u64, signature)
+    }
 }
 
-/// Find MADT in RSDT (ACPI 1.0)
-fn find_madt_in_rsdt(rsdt_addr: u64) -> Result<u64, AcpiError> {
+/// Find a table in the RSDT (ACPI 1.0)
+fn find_table_in_rsdt(rsdt_addr: u64, signature: &[u8; 4]) -> Result<u64, AcpiError> {
     let header = unsafe { &*(rsdt_addr as *const SdtHeader) };
 
     // Validate RSDT signature
@@ -264,33 +414,38 @@ fn find_madt_in_rsdt(rsdt_addr: u64) -> Result<u64, AcpiError> {
     let entries_ptr = unsafe { (rsdt_addr as *const u8).add(entries_offset) as *const u32 };
     let entries = unsafe { slice::from_raw_parts(entries_ptr, entry_count) };
 
-    // Search for MADT
+    // Search for the table
     for &entry_addr in entries {
         let entry_header = unsafe { &*(entry_addr as u64 as *const SdtHeader) };
         let entry_sig = unsafe { core::ptr::read_unaligned(&entry_header.signature) };
-        if &entry_sig == b"APIC" {
+        if &entry_sig == signature {
             return Ok(entry_addr as u64);
         }
     }
 
-    serial_println!("[ACPI] MADT not found in RSDT");
-    Err(AcpiError::MadtNotFound)
+    serial_println!(
+        "[ACPI] {} not found in RSDT",
+        core::str::from_utf8(signature).unwrap_or("???")
+    );
+    Err(AcpiError::TableNotFound)
 }
 
-/// Find MADT in XSDT (ACPI 2.0+)
-fn find_madt_in_xsdt(xsdt_addr: u64) -> Result<u64, AcpiError> {
+/// Find a table in the XSDT (ACPI 2.0+)
+fn find_table_in_xsdt(xsdt_addr: u64, signature: &[u8; 4]) -> Result<u64, AcpiError> {
+    let sig_str = core::str::from_utf8(signature).unwrap_or("???");
     serial_println!(
-        "[ACPI] find_madt_in_xsdt: Reading header at 0x{:x}",
+        "[ACPI] find_table_in_xsdt({}): Reading header at 0x{:x}",
+        sig_str,
         xsdt_addr
     );
 
-    serial_println!("[ACPI] find_madt_in_xsdt: Validating XSDT signature");
+    serial_println!("[ACPI] find_table_in_xsdt: Validating XSDT signature");
     // Validate XSDT signature
-    let signature = unsafe {
+    let xsdt_signature = unsafe {
         let header_ptr = xsdt_addr as *const SdtHeader;
         core::ptr::read_unaligned(core::ptr::addr_of!((*header_ptr).signature))
     };
-    if &signature != b"XSDT" {
+    if &xsdt_signature != b"XSDT" {
         serial_println!("[ACPI] Invalid XSDT signature");
         return Err(AcpiError::InvalidRsdp);
     }
@@ -300,10 +455,10 @@ fn find_madt_in_xsdt(xsdt_addr: u64) -> Result<u64, AcpiError> {
         core::ptr::read_unaligned(core::ptr::addr_of!((*header_ptr).length))
     };
     serial_println!(
-        "[ACPI] find_madt_in_xsdt: XSDT signature valid, length={}",
+        "[ACPI] find_table_in_xsdt: XSDT signature valid, length={}",
         header_length
     );
-    serial_println!("[ACPI] find_madt_in_xsdt: Validating checksum");
+    serial_println!("[ACPI] find_table_in_xsdt: Validating checksum");
     // Validate checksum
     let xsdt_bytes =
         unsafe { slice::from_raw_parts(xsdt_addr as *const u8, header_length as usize) };
@@ -312,42 +467,42 @@ fn find_madt_in_xsdt(xsdt_addr: u64) -> Result<u64, AcpiError> {
         return Err(AcpiError::InvalidChecksum);
     }
 
-    serial_println!("[ACPI] find_madt_in_xsdt: Checksum valid");
+    serial_println!("[ACPI] find_table_in_xsdt: Checksum valid");
     // Calculate number of entries
     let entries_offset = core::mem::size_of::<SdtHeader>();
     let entries_size = header_length as usize - entries_offset;
     let entry_count = entries_size / 8; // 64-bit pointers
 
     serial_println!(
-        "[ACPI] find_madt_in_xsdt: {} entries to search",
+        "[ACPI] find_table_in_xsdt: {} entries to search",
         entry_count
     );
     // Get pointer to entries array
     let entries_ptr = unsafe { (xsdt_addr as *const u8).add(entries_offset) as *const u64 };
     let entries = unsafe { slice::from_raw_parts(entries_ptr, entry_count) };
 
-    // Search for MADT
-    serial_println!("[ACPI] find_madt_in_xsdt: Searching for MADT...");
+    // Search for the table
+    serial_println!("[ACPI] find_table_in_xsdt: Searching for {}...", sig_str);
     for (i, &entry_addr) in entries.iter().enumerate() {
         serial_println!(
-            "[ACPI] find_madt_in_xsdt: Checking entry {} at 0x{:x}",
+            "[ACPI] find_table_in_xsdt: Checking entry {} at 0x{:x}",
             i,
             entry_addr
         );
         let entry_header = unsafe { &*(entry_addr as *const SdtHeader) };
         let entry_sig = unsafe { core::ptr::read_unaligned(&entry_header.signature) };
         serial_println!(
-            "[ACPI] find_madt_in_xsdt: Entry signature: {:?}",
+            "[ACPI] find_table_in_xsdt: Entry signature: {:?}",
             core::str::from_utf8(&entry_sig).unwrap_or("???")
         );
-        if &entry_sig == b"APIC" {
-            serial_println!("[ACPI] find_madt_in_xsdt: Found MADT!");
+        if &entry_sig == signature {
+            serial_println!("[ACPI] find_table_in_xsdt: Found {}!", sig_str);
             return Ok(entry_addr);
         }
     }
 
-    serial_println!("[ACPI] MADT not found in XSDT");
-    Err(AcpiError::MadtNotFound)
+    serial_println!("[ACPI] {} not found in XSDT", sig_str);
+    Err(AcpiError::TableNotFound)
 }
 
 /// Parse MADT table and extract CPU and APIC information
@@ -471,3 +626,136 @@ fn parse_madt_table(madt_addr: u64) -> Result<MadtInfo, AcpiError> {
         ioapic_count,
     })
 }
+
+/// Parse SRAT table and extract CPU and memory NUMA affinity
+fn parse_srat_table(srat_addr: u64) -> Result<SratInfo, AcpiError> {
+    let srat = unsafe { &*(srat_addr as *const Srat) };
+
+    // Validate SRAT signature
+    let srat_sig = unsafe { core::ptr::read_unaligned(&srat.header.signature) };
+    if &srat_sig != b"SRAT" {
+        serial_println!("[ACPI] Invalid SRAT signature");
+        return Err(AcpiError::InvalidSrat);
+    }
+
+    // Validate checksum
+    let srat_bytes =
+        unsafe { slice::from_raw_parts(srat_addr as *const u8, srat.header.length as usize) };
+    if !validate_checksum(srat_bytes) {
+        serial_println!("[ACPI] Invalid SRAT checksum");
+        return Err(AcpiError::InvalidChecksum);
+    }
+
+    let mut info = SratInfo {
+        memory: [None; MAX_SRAT_MEMORY],
+        memory_count: 0,
+        cpus: [None; MAX_CPUS],
+        cpu_count: 0,
+        node_count: 0,
+        domains: [0; crate::mm::pmm::MAX_NUMA_NODES],
+    };
+
+    // Parse SRAT entries
+    let entries_offset = core::mem::size_of::<Srat>();
+    let entries_size = srat.header.length as usize - entries_offset;
+    let entries_start = unsafe { (srat_addr as *const u8).add(entries_offset) };
+
+    let mut offset = 0;
+    while offset < entries_size {
+        let entry_ptr = unsafe { entries_start.add(offset) };
+        let entry_header = unsafe { &*(entry_ptr as *const MadtEntryHeader) };
+
+        if entry_header.length == 0 {
+            serial_println!("[ACPI] Zero-length SRAT entry");
+            return Err(AcpiError::InvalidSrat);
+        }
+
+        match entry_header.entry_type {
+            0 => {
+                // Processor Local APIC Affinity
+                let cpu_ptr = entry_ptr as *const SratLocalApic;
+
+                // Read values using raw pointers to avoid alignment issues
+                let flags = unsafe { core::ptr::addr_of!((*cpu_ptr).flags).read_unaligned() };
+                let apic_id = unsafe { core::ptr::addr_of!((*cpu_ptr).apic_id).read() };
+                let lo = unsafe { core::ptr::addr_of!((*cpu_ptr).proximity_domain_lo).read() };
+                let hi = unsafe { core::ptr::addr_of!((*cpu_ptr).proximity_domain_hi).read() };
+                let domain = u32::from_le_bytes([lo, hi[0], hi[1], hi[2]]);
+
+                if flags & SRAT_ENABLED != 0 {
+                    let node = info.node_for_domain(domain);
+                    if info.cpu_count < MAX_CPUS {
+                        info.cpus[info.cpu_count] = Some((apic_id as u32, node));
+                        info.cpu_count += 1;
+                        serial_println!("[ACPI] SRAT CPU: apic_id={}, node={}", apic_id, node);
+                    }
+                }
+            }
+            1 => {
+                // Memory Affinity
+                let mem_ptr = entry_ptr as *const SratMemory;
+
+                // Read values using raw pointers to avoid alignment issues
+                let flags = unsafe { core::ptr::addr_of!((*mem_ptr).flags).read_unaligned() };
+                let domain =
+                    unsafe { core::ptr::addr_of!((*mem_ptr).proximity_domain).read_unaligned() };
+                let base = unsafe { core::ptr::addr_of!((*mem_ptr).base_address).read_unaligned() };
+                let length = unsafe { core::ptr::addr_of!((*mem_ptr).length).read_unaligned() };
+
+                if flags & SRAT_ENABLED != 0 && length != 0 {
+                    let node = info.node_for_domain(domain);
+                    if info.memory_count < MAX_SRAT_MEMORY {
+                        info.memory[info.memory_count] =
+                            Some(MemoryAffinity { base, length, node });
+                        info.memory_count += 1;
+                        serial_println!(
+                            "[ACPI] SRAT memory: 0x{:x}-0x{:x}, node={}",
+                            base,
+                            base + length,
+                            node
+                        );
+                    } else {
+                        serial_println!(
+                            "[ACPI] Warning: SRAT memory range limit reached, ignoring additional ranges"
+                        );
+                    }
+                }
+            }
+            2 => {
+                // Processor Local x2APIC Affinity
+                let cpu_ptr = entry_ptr as *const SratX2Apic;
+
+                // Read values using raw pointers to avoid alignment issues
+                let flags = unsafe { core::ptr::addr_of!((*cpu_ptr).flags).read_unaligned() };
+                let x2apic_id =
+                    unsafe { core::ptr::addr_of!((*cpu_ptr).x2apic_id).read_unaligned() };
+                let domain =
+                    unsafe { core::ptr::addr_of!((*cpu_ptr).proximity_domain).read_unaligned() };
+
+                if flags & SRAT_ENABLED != 0 {
+                    let node = info.node_for_domain(domain);
+                    if info.cpu_count < MAX_CPUS {
+                        info.cpus[info.cpu_count] = Some((x2apic_id, node));
+                        info.cpu_count += 1;
+                        serial_println!("[ACPI] SRAT CPU: x2apic_id={}, node={}", x2apic_id, node);
+                    }
+                }
+            }
+            _ => {
+                // Other entry types (GICC, generic initiator) are not relevant on x86_64
+                serial_println!(
+                    "[ACPI] Skipping SRAT entry type {}",
+                    entry_header.entry_type
+                );
+            }
+        }
+
+        offset += entry_header.length as usize;
+    }
+
+    if info.node_count == 0 {
+        return Err(AcpiError::InvalidSrat);
+    }
+
+    Ok(info)
+}
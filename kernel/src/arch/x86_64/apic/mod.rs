@@ -262,6 +262,8 @@ impl LocalApic {
     /// LAPIC timer frequency. It programs the PIT for a 10ms one-shot,
     /// sets the LAPIC timer to maximum count, waits for the PIT to fire,
     /// and calculates the LAPIC frequency from the remaining count.
+    /// The TSC is measured over the same window and its frequency handed
+    /// to `tsc::set_frequency`, which starts the monotonic clock.
     ///
     /// # Returns
     ///
@@ -306,6 +308,7 @@ impl LocalApic {
 
         // Set LAPIC timer to maximum count
         self.write(LAPIC_TIMER_INIT_COUNT, 0xFFFFFFFF);
+        let tsc_start = crate::arch::x86_64::tsc::rdtsc();
 
         // Enable PIT channel 2 gate to start counting
         let gate_value = pit_gate.read();
@@ -322,6 +325,7 @@ impl LocalApic {
 
         // Read the current LAPIC timer count
         let final_count = self.read(LAPIC_TIMER_CURRENT_COUNT);
+        let tsc_end = crate::arch::x86_64::tsc::rdtsc();
 
         // Stop LAPIC timer
         self.write(LAPIC_TIMER_INIT_COUNT, 0);
@@ -332,6 +336,8 @@ impl LocalApic {
         // Calculate frequency: ticks_per_10ms * 100 = ticks_per_second
         let frequency = (ticks_elapsed as u64) * 100;
 
+        crate::arch::x86_64::tsc::set_frequency((tsc_end - tsc_start) * 100);
+
         frequency
     }
 
@@ -369,4 +375,38 @@ impl LocalApic {
         // Set initial count to start the timer
         self.write(LAPIC_TIMER_INIT_COUNT, initial_count as u32);
     }
+
+    /// Put the APIC timer in one-shot mode (divide by 16), disarmed
+    ///
+    /// Arm it with `arm_oneshot`.
+    ///
+    /// # Safety
+    ///
+    /// Must be called on the CPU that owns this LAPIC.
+    pub unsafe fn init_oneshot_timer(&mut self) {
+        self.write(LAPIC_TIMER_DIVIDE, 0x3);
+        self.write(LAPIC_TIMER_LVT, TIMER_VECTOR as u32);
+        self.write(LAPIC_TIMER_INIT_COUNT, 0);
+    }
+
+    /// Put the APIC timer in TSC-deadline mode, disarmed
+    ///
+    /// Arm it by writing IA32_TSC_DEADLINE (see `tsc::write_deadline`).
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support TSC-deadline mode, and this must be called on
+    /// the CPU that owns this LAPIC.
+    pub unsafe fn init_tsc_deadline_timer(&mut self) {
+        // Bits 17-18: Timer mode (10b = TSC-deadline)
+        self.write(LAPIC_TIMER_LVT, (0b10 << 17) | (TIMER_VECTOR as u32));
+        // The LVT write must be ordered before the first MSR write
+        core::arch::asm!("mfence", options(nostack, preserves_flags));
+        crate::arch::x86_64::tsc::write_deadline(0);
+    }
+
+    /// Fire the one-shot timer after `count` timer ticks (0 disarms it)
+    pub fn arm_oneshot(&mut self, count: u32) {
+        self.write(LAPIC_TIMER_INIT_COUNT, count);
+    }
 }
@@ -3,10 +3,14 @@
 //! This module implements the page fault handler for memory protection
 //! in user-mode processes. It detects user space faults and terminates
 //! processes that access invalid memory.
+//!
+//! The debug (#DB) and breakpoint (#BP) traps are handled here too: in user
+//! mode they raise SIGTRAP, which is how a tracer learns of a single step
+//! or an `int3` breakpoint (see `user::ptrace`).
 
 use crate::sched;
 use crate::serial_println;
-use crate::user::process::{ProcessManager, ProcessState};
+use crate::sys::syscall::SyscallFrame;
 
 /// Page fault error code bits
 const PF_PRESENT: u64 = 1 << 0; // Page was present
@@ -15,6 +19,34 @@ const PF_USER: u64 = 1 << 2; // Fault occurred in user mode
 const PF_RESERVED: u64 = 1 << 3; // Reserved bit was set
 const PF_INSTR: u64 = 1 << 4; // Fault was caused by instruction fetch
 
+/// Registers saved by `page_fault_wrapper` and `trap_wrapper`
+///
+/// The general-purpose registers are in `SyscallFrame` order, followed by
+/// the error code and the frame pushed by the CPU.
+#[repr(C)]
+pub struct FaultFrame {
+    regs: [u64; 15],
+    _error_code: u64,
+    iret: [u64; 5],
+}
+
+impl FaultFrame {
+    /// The interrupted user registers
+    fn user_regs(&self) -> SyscallFrame {
+        let mut words = [0u64; 20];
+        words[..15].copy_from_slice(&self.regs);
+        words[15..].copy_from_slice(&self.iret);
+        unsafe { core::mem::transmute::<[u64; 20], SyscallFrame>(words) }
+    }
+
+    /// Replace the registers to return to
+    fn set_user_regs(&mut self, regs: &SyscallFrame) {
+        let words = unsafe { core::mem::transmute::<SyscallFrame, [u64; 20]>(*regs) };
+        self.regs.copy_from_slice(&words[..15]);
+        self.iret.copy_from_slice(&words[15..]);
+    }
+}
+
 /// Page fault handler entry point
 ///
 /// This function is called when a page fault occurs. It analyzes the fault
@@ -26,11 +58,17 @@ const PF_INSTR: u64 = 1 << 4; // Fault was caused by instruction fetch
 /// * `error_code` - Page fault error code from CPU
 /// * `fault_addr` - Faulting virtual address (from CR2 register)
 /// * `rip` - Instruction pointer where fault occurred
+/// * `frame` - Registers saved on entry
 ///
 /// # Safety
 /// This function is called from interrupt context and must be interrupt-safe.
 #[no_mangle]
-pub extern "C" fn page_fault_handler(error_code: u64, fault_addr: u64, rip: u64) {
+pub extern "C" fn page_fault_handler(
+    error_code: u64,
+    fault_addr: u64,
+    rip: u64,
+    frame: *mut FaultFrame,
+) {
     let cpu_id = unsafe { crate::arch::x86_64::smp::percpu::percpu_current().id };
 
     // Read CR2 register to get the faulting address
@@ -73,7 +111,7 @@ pub extern "C" fn page_fault_handler(error_code: u64, fault_addr: u64, rip: u64)
 
     // Check if this is a user space fault
     if user_mode {
-        handle_user_page_fault(actual_fault_addr, error_code, rip);
+        handle_user_page_fault(actual_fault_addr, error_code, rip, frame);
     } else {
         handle_kernel_page_fault(actual_fault_addr, error_code, rip);
     }
@@ -91,15 +129,29 @@ pub extern "C" fn page_fault_handler(error_code: u64, fault_addr: u64, rip: u64)
 /// # Returns
 /// Ok(()) if COW fault was handled successfully, Err otherwise
 fn handle_cow_fault(fault_addr: u64) -> Result<(), &'static str> {
-    use crate::mm::paging::{get_current_cr3, PageTable, PageTableFlags};
+    handle_cow_fault_in(crate::mm::paging::get_current_cr3(), fault_addr)
+}
+
+/// Break Copy-on-Write for a page in an arbitrary address space
+///
+/// Same as `handle_cow_fault`, but walks the page table rooted at
+/// `pml4_phys`. Used by `copy_to_user` to break COW before the kernel writes
+/// to a user page through the direct map.
+///
+/// # Arguments
+/// * `pml4_phys` - Physical address of the target PML4
+/// * `fault_addr` - User virtual address to make writable
+///
+/// # Returns
+/// Ok(()) if the page is now privately writable, Err otherwise
+pub(crate) fn handle_cow_fault_in(pml4_phys: usize, fault_addr: u64) -> Result<(), &'static str> {
+    use crate::mm::paging::{PageTable, PageTableFlags};
     use crate::mm::phys_to_virt;
     use crate::mm::pmm::get_global_pmm;
     use crate::mm::refcount::PAGE_REFCOUNT;
 
     let fault_addr_usize = fault_addr as usize;
 
-    // Get current page table
-    let pml4_phys = get_current_cr3();
     let pml4_virt = phys_to_virt(pml4_phys);
     let pml4 = unsafe { &mut *(pml4_virt as *mut PageTable) };
 
@@ -123,6 +175,9 @@ fn handle_cow_fault(fault_addr: u64) -> Result<(), &'static str> {
     if !pdpt_entry.is_present() {
         return Err("PDPT entry not present");
     }
+    if pdpt_entry.is_huge() {
+        return Err("1GB copy-on-write pages are not supported");
+    }
 
     let pd_phys = pdpt_entry.addr();
     let pd_virt = phys_to_virt(pd_phys);
@@ -133,6 +188,13 @@ fn handle_cow_fault(fault_addr: u64) -> Result<(), &'static str> {
         return Err("PD entry not present");
     }
 
+    // 2MB pages are copied as a whole
+    if pd_entry.is_huge() {
+        break_huge_page_cow(pd.get_entry_mut(pd_index), fault_addr)?;
+        flush_cow_page(pml4_phys, fault_addr_usize);
+        return Ok(());
+    }
+
     let pt_phys = pd_entry.addr();
     let pt_virt = phys_to_virt(pt_phys);
     let pt = unsafe { &mut *(pt_virt as *mut PageTable) };
@@ -208,15 +270,89 @@ fn handle_cow_fault(fault_addr: u64) -> Result<(), &'static str> {
         );
     }
 
-    // Flush TLB for this page
+    flush_cow_page(pml4_phys, fault_addr_usize);
+
+    Ok(())
+}
+
+/// Flush the TLB entry of a page whose COW state just changed
+fn flush_cow_page(pml4_phys: usize, addr: usize) {
+    use crate::mm::paging::get_current_cr3;
+
+    // Other PCIDs pick up the change lazily
+    crate::mm::pcid::invalidate_user_tlb();
+    if pml4_phys == get_current_cr3() {
+        unsafe {
+            core::arch::asm!(
+                "invlpg [{}]",
+                in(reg) addr,
+                options(nostack, preserves_flags)
+            );
+        }
+    }
+}
+
+/// Break Copy-on-Write for a 2MB page
+///
+/// # Arguments
+/// * `pd_entry` - PD entry mapping the huge page
+/// * `fault_addr` - Faulting address inside the huge page
+fn break_huge_page_cow(
+    pd_entry: &mut crate::mm::paging::PageTableEntry,
+    fault_addr: u64,
+) -> Result<(), &'static str> {
+    use crate::mm::paging::{PageTableFlags, PAGE_SIZE_2M};
+    use crate::mm::phys_to_virt;
+    use crate::mm::pmm::{get_global_pmm, FRAME_SIZE};
+    use crate::mm::refcount::PAGE_REFCOUNT;
+
+    if !pd_entry.is_cow() {
+        return Err("Page is not marked as COW");
+    }
+
+    let old_page_phys = pd_entry.addr() & !(PAGE_SIZE_2M - 1);
+    let refcount = PAGE_REFCOUNT.get_refcount(old_page_phys);
+
+    serial_println!(
+        "[COW] Handling 2MB COW fault at addr=0x{:x}, page_phys=0x{:x}, refcount={}",
+        fault_addr,
+        old_page_phys,
+        refcount
+    );
+
+    if refcount == 1 {
+        pd_entry.clear_cow();
+        pd_entry.set_writable(true);
+        return Ok(());
+    }
+
+    let mut pmm_guard = get_global_pmm();
+    let pmm = pmm_guard.as_mut().ok_or("PMM not initialized")?;
+
+    let new_page_phys = pmm
+        .alloc_contiguous(PAGE_SIZE_2M / FRAME_SIZE, PAGE_SIZE_2M)
+        .ok_or("Out of physical memory")?;
+
     unsafe {
-        core::arch::asm!(
-            "invlpg [{}]",
-            in(reg) fault_addr_usize,
-            options(nostack, preserves_flags)
+        core::ptr::copy_nonoverlapping(
+            phys_to_virt(old_page_phys) as *const u8,
+            phys_to_virt(new_page_phys) as *mut u8,
+            PAGE_SIZE_2M,
         );
     }
 
+    let old_flags = pd_entry.raw() & !0x000F_FFFF_FFFF_F000;
+    let new_flags = (old_flags & !PageTableFlags::COW.bits()) | PageTableFlags::WRITABLE.bits();
+    pd_entry.set(new_page_phys, PageTableFlags(new_flags));
+
+    PAGE_REFCOUNT.dec_refcount(old_page_phys, pmm);
+
+    serial_println!(
+        "[COW] Copied 2MB page: old=0x{:x} -> new=0x{:x}",
+        old_page_phys,
+        new_page_phys
+    );
+
     Ok(())
 }
 
@@ -233,7 +369,7 @@ fn handle_cow_fault(fault_addr: u64) -> Result<(), &'static str> {
 /// * `fault_addr` - Faulting virtual address
 /// * `error_code` - Page fault error code
 /// * `rip` - Instruction pointer where fault occurred
-fn handle_user_page_fault(fault_addr: u64, error_code: u64, rip: u64) {
+fn handle_user_page_fault(fault_addr: u64, error_code: u64, rip: u64, frame: *mut FaultFrame) {
     let cpu_id = unsafe { crate::arch::x86_64::smp::percpu::percpu_current().id };
 
     // Check if this is a COW fault (write to present page)
@@ -245,6 +381,7 @@ fn handle_user_page_fault(fault_addr: u64, error_code: u64, rip: u64) {
         match handle_cow_fault(fault_addr) {
             Ok(()) => {
                 // COW fault handled successfully - return to user space
+                account_user_fault(false);
                 serial_println!(
                     "[FAULT][cpu{}] COW fault handled successfully at 0x{:x}",
                     cpu_id,
@@ -275,53 +412,13 @@ fn handle_user_page_fault(fault_addr: u64, error_code: u64, rip: u64) {
                 fault_addr
             );
 
-            // Check if this is a MAP_GROWSDOWN mapping
-            if mapping.flags.is_growsdown() {
-                // Try to handle stack expansion
-                use crate::mm::mmap::handle_growsdown_fault;
-
-                // We need a mutable reference, so we'll need to get it from the table
-                let pid = crate::sched::get_current_task_info()
-                    .map(|info| info.0 as u64)
-                    .unwrap_or(1);
-
-                let manager = crate::mm::mmap::get_mmap_manager();
-                if let Some(table) = manager.get_table(pid) {
-                    // Find the mapping
-                    for lock in table.mappings.iter() {
-                        let mut m = lock.write();
-                        if m.contains(fault_addr)
-                            || (m.flags.is_growsdown() && fault_addr < m.vaddr)
-                        {
-                            match handle_growsdown_fault(fault_addr, &mut m) {
-                                Ok(()) => {
-                                    serial_println!(
-                                        "[FAULT][cpu{}] MAP_GROWSDOWN fault handled at 0x{:x}",
-                                        cpu_id,
-                                        fault_addr
-                                    );
-                                    return;
-                                }
-                                Err(e) => {
-                                    serial_println!(
-                                        "[FAULT][cpu{}] MAP_GROWSDOWN fault failed: {}",
-                                        cpu_id,
-                                        e
-                                    );
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
             // Check if this is a file-backed mapping
             if mapping.is_file_backed() {
                 use crate::mm::mmap::handle_file_mapping_fault;
 
                 match handle_file_mapping_fault(fault_addr, &mapping) {
                     Ok(()) => {
+                        account_user_fault(true);
                         serial_println!(
                             "[FAULT][cpu{}] File-backed mapping fault handled at 0x{:x}",
                             cpu_id,
@@ -338,6 +435,41 @@ fn handle_user_page_fault(fault_addr: u64, error_code: u64, rip: u64) {
                         // Fall through to terminate process
                     }
                 }
+            } else {
+                // Anonymous mapping touched for the first time
+                use crate::mm::mmap::handle_anonymous_fault;
+
+                match handle_anonymous_fault(fault_addr, &mapping) {
+                    Ok(()) => {
+                        account_user_fault(false);
+                        return;
+                    }
+                    Err(e) => {
+                        serial_println!(
+                            "[FAULT][cpu{}] Anonymous mapping fault failed: {}",
+                            cpu_id,
+                            e
+                        );
+                        // Fall through to terminate process
+                    }
+                }
+            }
+        } else {
+            // Just below a MAP_GROWSDOWN stack: grow it
+            match crate::mm::mmap::grow_stack_mapping(fault_addr) {
+                Ok(true) => {
+                    account_user_fault(false);
+                    serial_println!(
+                        "[FAULT][cpu{}] MAP_GROWSDOWN fault handled at 0x{:x}",
+                        cpu_id,
+                        fault_addr
+                    );
+                    return;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    serial_println!("[FAULT][cpu{}] MAP_GROWSDOWN fault failed: {}", cpu_id, e);
+                }
             }
         }
     }
@@ -429,33 +561,117 @@ fn handle_user_page_fault(fault_addr: u64, error_code: u64, rip: u64) {
         }
     }
 
+    let signal = crate::signal::signals::SIGSEGV;
+    if !frame.is_null() {
+        use crate::signal::{SigSource, SEGV_ACCERR, SEGV_MAPERR};
+
+        let source = SigSource::Fault {
+            code: if error_code & PF_PRESENT != 0 {
+                SEGV_ACCERR
+            } else {
+                SEGV_MAPERR
+            },
+            addr: fault_addr,
+        };
+        raise_fault_signal(unsafe { &mut *frame }, signal, source);
+        return;
+    }
+
     // Terminate the process
     serial_println!(
         "[FAULT] Terminating process {} due to page fault",
         current_task_id
     );
+    crate::user::thread::exit_group(crate::user::process::WaitStatus::Signaled {
+        signal,
+        core_dumped: false,
+    })
+}
 
-    // Mark process as terminated in process table
-    if let Some(mut process_guard) = ProcessManager::get_process(current_task_id) {
-        if let Some(process) = process_guard.get_mut() {
-            process.state = ProcessState::Terminated;
-            serial_println!("[FAULT] Process {} marked as terminated", process.pid);
+/// Raise a signal for a fault or trap of the current task in user mode
+///
+/// A tracer sees the signal first and may cancel or replace it (a
+/// replacement is queued like any signal). Otherwise a handler runs if the
+/// process has one, or the whole process dies of the signal, leaving a
+/// core file.
+///
+/// # Arguments
+/// * `frame` - Registers saved on entry
+/// * `signal` - Signal for the fault
+/// * `source` - Fault code and address
+fn raise_fault_signal(
+    frame: &mut FaultFrame,
+    signal: crate::signal::Signal,
+    source: crate::signal::SigSource,
+) {
+    let mut regs = frame.user_regs();
+    let raised = crate::user::ptrace::signal_stop(&mut regs, signal, source);
+    frame.set_user_regs(&regs);
+    let source = match raised {
+        Some((raised, source)) if raised == signal => source,
+        Some((raised, _)) => {
+            if let Some(task) = sched::current_task_mut() {
+                let _ = crate::signal::send_signal(task, raised);
+            }
+            return;
         }
-    }
+        None => return,
+    };
 
-    // Mark task as terminated in scheduler
-    if let Some(current_task) = sched::get_task_mut(current_task_id) {
-        current_task.state = crate::sched::task::TaskState::Ready; // Will be cleaned up
-        serial_println!("[FAULT] Task {} marked for cleanup", current_task_id);
+    if crate::signal::frame::force_fault_signal(&mut regs, signal, source) {
+        frame.set_user_regs(&regs);
+        return;
     }
 
-    serial_println!("[FAULT] Yielding to scheduler after process termination");
+    serial_println!(
+        "[FAULT] Terminating process {} with signal {}",
+        sched::current_task().map_or(0, |task| task.pid),
+        signal
+    );
+    let core_dumped = crate::user::coredump::dump_core(signal, &regs);
+    crate::user::thread::exit_group(crate::user::process::WaitStatus::Signaled {
+        signal,
+        core_dumped,
+    })
+}
 
-    // Yield to scheduler - this process should never run again
-    sched::yield_now();
+/// Debug (#DB) and breakpoint (#BP) trap handler
+///
+/// A trap in user mode raises SIGTRAP: after a single step with the trap
+/// flag set, or after an `int3` (RIP then points past the instruction).
+/// Kernel-mode traps are logged and ignored.
+///
+/// # Arguments
+/// * `vector` - 1 for #DB, 3 for #BP
+/// * `frame` - Registers saved on entry (with a dummy error code)
+#[no_mangle]
+extern "C" fn trap_handler(vector: u64, frame: *mut FaultFrame) {
+    use crate::signal::{signals::SIGTRAP, SigSource, TRAP_BRKPT, TRAP_TRACE};
 
-    // Should never reach here
-    panic!("[FAULT] Returned from yield after process termination");
+    let frame = unsafe { &mut *frame };
+    let rip = frame.iret[0];
+    if frame.iret[1] & 3 != 3 {
+        serial_println!(
+            "[FAULT] Ignoring kernel-mode trap {} at RIP=0x{:x}",
+            vector,
+            rip
+        );
+        return;
+    }
+
+    let code = if vector == 3 { TRAP_BRKPT } else { TRAP_TRACE };
+    raise_fault_signal(frame, SIGTRAP, SigSource::Fault { code, addr: rip });
+}
+
+/// Count a user page fault that was served; `major` if it read a file
+fn account_user_fault(major: bool) {
+    if let Some(task) = sched::current_task_mut() {
+        if major {
+            task.rusage.majflt += 1;
+        } else {
+            task.rusage.minflt += 1;
+        }
+    }
 }
 
 /// Handle page fault in kernel space
@@ -526,18 +742,18 @@ fn handle_kernel_page_fault(fault_addr: u64, error_code: u64, rip: u64) -> ! {
 #[no_mangle]
 pub extern "C" fn page_fault_wrapper() {
     core::arch::naked_asm!(
-        // Save all registers
+        // Save all registers (in SyscallFrame order, see FaultFrame)
         "push rax",
         "push rcx",
         "push rdx",
-        "push rbx",
-        "push rbp",
         "push rsi",
         "push rdi",
         "push r8",
         "push r9",
         "push r10",
         "push r11",
+        "push rbx",
+        "push rbp",
         "push r12",
         "push r13",
         "push r14",
@@ -554,6 +770,7 @@ pub extern "C" fn page_fault_wrapper() {
         // Get RIP (return address, pushed by CPU)
         // It's at [rsp + 16*8] (after error code and 15 registers)
         "mov rdx, [rsp + 16*8]",  // rip -> third argument
+        "mov rcx, rsp",           // saved registers -> fourth argument
 
         // Call the Rust handler
         "call {handler}",
@@ -563,14 +780,14 @@ pub extern "C" fn page_fault_wrapper() {
         "pop r14",
         "pop r13",
         "pop r12",
+        "pop rbp",
+        "pop rbx",
         "pop r11",
         "pop r10",
         "pop r9",
         "pop r8",
         "pop rdi",
         "pop rsi",
-        "pop rbp",
-        "pop rbx",
         "pop rdx",
         "pop rcx",
         "pop rax",
@@ -585,6 +802,81 @@ pub extern "C" fn page_fault_wrapper() {
     )
 }
 
+/// Assembly entry of the debug (#DB) trap
+///
+/// Neither trap pushes an error code. The vector goes in its place, which
+/// keeps the saved registers in `FaultFrame` layout.
+#[unsafe(naked)]
+#[no_mangle]
+pub extern "C" fn debug_trap_wrapper() {
+    core::arch::naked_asm!(
+        "push 1",
+        "jmp {trap}",
+        trap = sym trap_wrapper,
+    )
+}
+
+/// Assembly entry of the breakpoint (#BP) trap
+#[unsafe(naked)]
+#[no_mangle]
+pub extern "C" fn breakpoint_wrapper() {
+    core::arch::naked_asm!(
+        "push 3",
+        "jmp {trap}",
+        trap = sym trap_wrapper,
+    )
+}
+
+/// Common part of the trap entries, with the vector pushed
+#[unsafe(naked)]
+extern "C" fn trap_wrapper() {
+    core::arch::naked_asm!(
+        // Save all registers (in SyscallFrame order, see FaultFrame)
+        "push rax",
+        "push rcx",
+        "push rdx",
+        "push rsi",
+        "push rdi",
+        "push r8",
+        "push r9",
+        "push r10",
+        "push r11",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+
+        "mov rdi, [rsp + 15*8]",  // vector -> first argument
+        "mov rsi, rsp",           // saved registers -> second argument
+        "call {handler}",
+
+        // Restore all registers
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "pop r11",
+        "pop r10",
+        "pop r9",
+        "pop r8",
+        "pop rdi",
+        "pop rsi",
+        "pop rdx",
+        "pop rcx",
+        "pop rax",
+
+        // Remove the vector
+        "add rsp, 8",
+        "iretq",
+
+        handler = sym trap_handler,
+    )
+}
+
 /// Initialize page fault handler in IDT
 ///
 /// This function should be called during kernel initialization to set up
@@ -0,0 +1,220 @@
+//! FPU/SSE State
+//!
+//! Every task keeps its user floating-point state in an `FpuState`. The
+//! scheduler saves the outgoing task's state and loads the incoming one's
+//! on each switch, so while a task runs the CPU holds its state; the
+//! kernel itself is built soft-float and never touches it. Signal frames
+//! carry a copy of the task's state.
+//!
+//! When the OS has enabled XSAVE (CR4.OSXSAVE) the state is kept in the
+//! standard XSAVE format, covering every feature enabled in XCR0;
+//! otherwise the 512-byte FXSAVE format is used. Both start with the same
+//! legacy region and are kept 64-byte aligned.
+
+use alloc::vec::Vec;
+
+/// CR4.OSXSAVE - XSAVE and XGETBV are enabled
+const CR4_OSXSAVE: u64 = 1 << 18;
+
+/// Size of the FXSAVE area (the legacy region of an XSAVE area)
+pub const FXSAVE_SIZE: usize = 512;
+
+/// Alignment required by XSAVE (FXSAVE needs 16)
+pub const STATE_ALIGN: usize = 64;
+
+/// Offset of the x87 control word in the legacy region
+const FCW_OFFSET: usize = 0;
+
+/// x87 control word after FNINIT: all exceptions masked, 64-bit precision
+const DEFAULT_FCW: u16 = 0x037F;
+
+/// Offset of MXCSR in the legacy region
+const MXCSR_OFFSET: usize = 24;
+
+/// Offset of MXCSR_MASK in the legacy region
+const MXCSR_MASK_OFFSET: usize = 28;
+
+/// MXCSR at reset: all exceptions masked, round to nearest
+const DEFAULT_MXCSR: u32 = 0x1F80;
+
+/// MXCSR_MASK to assume when the CPU stores 0 there
+const DEFAULT_MXCSR_MASK: u32 = 0xFFBF;
+
+/// Size of the XSAVE header that follows the legacy region
+const XSAVE_HEADER_SIZE: usize = 64;
+
+/// Whether the state is saved with XSAVE
+fn xsave_enabled() -> bool {
+    let cr4: u64;
+    unsafe {
+        core::arch::asm!("mov {}, cr4", out(reg) cr4, options(nomem, nostack, preserves_flags));
+    }
+    cr4 & CR4_OSXSAVE != 0
+}
+
+/// State components enabled in XCR0
+fn xcr0() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        core::arch::asm!(
+            "xgetbv",
+            in("ecx") 0,
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    ((high as u64) << 32) | low as u64
+}
+
+/// Size in bytes of the state saved by `FpuState::save`
+pub fn state_size() -> usize {
+    if xsave_enabled() {
+        // EBX: size of the XSAVE area for the features enabled in XCR0
+        let leaf = core::arch::x86_64::__cpuid_count(0xD, 0);
+        leaf.ebx as usize
+    } else {
+        FXSAVE_SIZE
+    }
+}
+
+/// FPU/SSE state in a kernel buffer
+pub struct FpuState {
+    buf: Vec<u8>,
+    offset: usize,
+    len: usize,
+}
+
+impl core::fmt::Debug for FpuState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FpuState")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+impl FpuState {
+    /// Create a state area of `state_size()` bytes holding the initial
+    /// state a new program starts with
+    pub fn new() -> Self {
+        let len = state_size();
+        let buf = alloc::vec![0u8; len + STATE_ALIGN];
+        let offset = buf.as_ptr().align_offset(STATE_ALIGN);
+        let mut state = Self { buf, offset, len };
+        init_legacy(state.as_bytes_mut());
+        state
+    }
+
+    /// The saved state
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[self.offset..self.offset + self.len]
+    }
+
+    /// The saved state, to be filled in from user memory
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.buf[self.offset..self.offset + self.len]
+    }
+
+    /// Save the CPU's current state
+    pub fn save(&mut self) {
+        let area = self.as_bytes_mut().as_mut_ptr();
+        unsafe {
+            if xsave_enabled() {
+                let mask = xcr0();
+                core::arch::asm!(
+                    "xsave64 [{}]",
+                    in(reg) area,
+                    in("eax") mask as u32,
+                    in("edx") (mask >> 32) as u32,
+                    options(nostack, preserves_flags)
+                );
+            } else {
+                core::arch::asm!("fxsave64 [{}]", in(reg) area, options(nostack, preserves_flags));
+            }
+        }
+    }
+
+    /// Check that the state can be loaded without faulting
+    ///
+    /// MXCSR must not set bits the CPU does not support and, for XSAVE, the
+    /// header must describe standard-form state for enabled features only.
+    pub fn is_valid(&self) -> bool {
+        let bytes = self.as_bytes();
+
+        // The supported MXCSR bits, from a save of our own
+        let mut current = FpuState::new();
+        current.save();
+        let mask = match read_u32_at(current.as_bytes(), MXCSR_MASK_OFFSET) {
+            0 => DEFAULT_MXCSR_MASK,
+            mask => mask,
+        };
+        if read_u32_at(bytes, MXCSR_OFFSET) & !mask != 0 {
+            return false;
+        }
+
+        if !xsave_enabled() {
+            return true;
+        }
+        let xstate_bv = read_u64_at(bytes, FXSAVE_SIZE);
+        let xcomp_bv = read_u64_at(bytes, FXSAVE_SIZE + 8);
+        let reserved = &bytes[FXSAVE_SIZE + 16..FXSAVE_SIZE + XSAVE_HEADER_SIZE];
+        xstate_bv & !xcr0() == 0 && xcomp_bv == 0 && reserved.iter().all(|&b| b == 0)
+    }
+
+    /// Load the state into the CPU
+    ///
+    /// The state must have passed `is_valid`, or the load faults.
+    pub fn restore(&self) {
+        let area = self.as_bytes().as_ptr();
+        unsafe {
+            if xsave_enabled() {
+                let mask = xcr0();
+                core::arch::asm!(
+                    "xrstor64 [{}]",
+                    in(reg) area,
+                    in("eax") mask as u32,
+                    in("edx") (mask >> 32) as u32,
+                    options(nostack, preserves_flags)
+                );
+            } else {
+                core::arch::asm!("fxrstor64 [{}]", in(reg) area, options(nostack, preserves_flags));
+            }
+        }
+    }
+}
+
+/// Write the initial control words into a zeroed legacy region
+///
+/// Everything else starts zeroed: an empty x87 stack and, for XSAVE, a
+/// header that puts every other component in its initial state.
+fn init_legacy(bytes: &mut [u8]) {
+    bytes[FCW_OFFSET..FCW_OFFSET + 2].copy_from_slice(&DEFAULT_FCW.to_le_bytes());
+    bytes[MXCSR_OFFSET..MXCSR_OFFSET + 4].copy_from_slice(&DEFAULT_MXCSR.to_le_bytes());
+}
+
+/// Read a little-endian u32 at `at`
+fn read_u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+/// Read a little-endian u64 at `at`
+fn read_u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initial_state() {
+        let mut bytes = [0u8; FXSAVE_SIZE + XSAVE_HEADER_SIZE];
+        init_legacy(&mut bytes);
+        assert_eq!(bytes[FCW_OFFSET..FCW_OFFSET + 2], DEFAULT_FCW.to_le_bytes());
+        assert_eq!(read_u32_at(&bytes, MXCSR_OFFSET), DEFAULT_MXCSR);
+        // Loadable: no reserved MXCSR bits, empty XSAVE header
+        assert_eq!(DEFAULT_MXCSR & !DEFAULT_MXCSR_MASK, 0);
+        assert_eq!(read_u64_at(&bytes, FXSAVE_SIZE), 0);
+    }
+}
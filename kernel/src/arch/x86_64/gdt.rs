@@ -571,8 +571,9 @@ pub struct GdtInfo {
 /// User space address limit
 pub const USER_LIMIT: usize = 0x0000_8000_0000_0000;
 
-/// User stack configuration
-pub const USER_STACK_TOP: usize = 0x0000_7FFF_FFFF_0000;
+/// User stack configuration (each stack top is randomized below
+/// `USER_STACK_TOP`, see `mm::aslr::stack_top`)
+pub const USER_STACK_TOP: usize = crate::mm::aslr::STACK_TOP_MAX as usize;
 pub const USER_STACK_SIZE: usize = 8192; // 8KB
 
 /// Memory region types for process tracking
@@ -1035,7 +1036,7 @@ pub fn setup_user_stack_with_size(stack_size: usize) -> Result<u64, &'static str
         return Err("Invalid stack size");
     }
 
-    let stack_top = USER_STACK_TOP;
+    let stack_top = crate::mm::aslr::stack_top() as usize;
     let stack_bottom = stack_top - stack_size;
     let guard_page = stack_bottom - 4096;
 
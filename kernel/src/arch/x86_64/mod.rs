@@ -2,9 +2,13 @@
 pub mod acpi;
 pub mod apic;
 pub mod fault;
+pub mod fpu;
 pub mod gdt;
+pub mod rtc;
 pub mod smp;
 pub mod syscall;
+pub mod tls;
+pub mod tsc;
 
 // Re-export user_entry_trampoline for external use
 pub use gdt::user_entry_trampoline;
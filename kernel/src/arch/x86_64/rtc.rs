@@ -0,0 +1,108 @@
+//! CMOS Real-Time Clock
+//!
+//! Read once at boot to anchor CLOCK_REALTIME; afterwards wall time advances
+//! with the TSC-based monotonic clock.
+
+use x86_64::instructions::port::Port;
+
+const CMOS_ADDRESS: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+
+/// Status A: update in progress
+const STATUS_A_UIP: u8 = 0x80;
+/// Status B: 24-hour mode
+const STATUS_B_24H: u8 = 0x02;
+/// Status B: binary (not BCD) values
+const STATUS_B_BINARY: u8 = 0x04;
+
+unsafe fn read_register(reg: u8) -> u8 {
+    let mut address = Port::<u8>::new(CMOS_ADDRESS);
+    let mut data = Port::<u8>::new(CMOS_DATA);
+    // Keep NMIs enabled (bit 7 clear)
+    address.write(reg & 0x7F);
+    data.read()
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value & 0x0F) + (value >> 4) * 10
+}
+
+/// Days since 1970-01-01 for a civil date (proleptic Gregorian)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Read the current wall-clock time as seconds since the Unix epoch
+///
+/// Assumes the RTC keeps UTC and the year is in the 2000s.
+pub fn read_unix_time() -> u64 {
+    unsafe {
+        // Wait for any update in progress to finish
+        while read_register(REG_STATUS_A) & STATUS_A_UIP != 0 {
+            core::hint::spin_loop();
+        }
+
+        let mut seconds = read_register(REG_SECONDS);
+        let mut minutes = read_register(REG_MINUTES);
+        let mut hours = read_register(REG_HOURS);
+        let mut day = read_register(REG_DAY);
+        let mut month = read_register(REG_MONTH);
+        let mut year = read_register(REG_YEAR);
+        let status_b = read_register(REG_STATUS_B);
+
+        let pm = hours & 0x80 != 0;
+        hours &= 0x7F;
+
+        if status_b & STATUS_B_BINARY == 0 {
+            seconds = bcd_to_binary(seconds);
+            minutes = bcd_to_binary(minutes);
+            hours = bcd_to_binary(hours);
+            day = bcd_to_binary(day);
+            month = bcd_to_binary(month);
+            year = bcd_to_binary(year);
+        }
+
+        if status_b & STATUS_B_24H == 0 {
+            hours %= 12;
+            if pm {
+                hours += 12;
+            }
+        }
+
+        let days = days_from_civil(2000 + year as i64, month as u32, day as u32);
+        (days * 86400 + hours as i64 * 3600 + minutes as i64 * 60 + seconds as i64) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_days_from_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(2024, 2, 29), 19782);
+    }
+
+    #[test]
+    fn test_bcd_to_binary() {
+        assert_eq!(bcd_to_binary(0x59), 59);
+        assert_eq!(bcd_to_binary(0x00), 0);
+    }
+}
@@ -2,6 +2,7 @@
 /// This module provides CPU core discovery, AP (Application Processor) bringup,
 /// and per-CPU data structures.
 pub mod percpu;
+pub mod topology;
 
 use crate::arch::x86_64::acpi::get_madt_info;
 use crate::arch::x86_64::apic::LocalApic;
@@ -95,6 +96,7 @@ pub extern "C" fn ap_entry64(cpu_id: usize, apic_id: u8, lapic_address: u64) ->
     // Match BSP feature setup so NX-marked pages are valid on this core
     crate::mm::enable_nx_bit();
     crate::mm::enable_write_protect();
+    crate::mm::pcid::init_pcid_on_cpu();
 
     // Initialize PerCpu structure for this AP
     unsafe {
@@ -243,10 +245,10 @@ pub extern "C" fn ap_entry64(cpu_id: usize, apic_id: u8, lapic_address: u64) ->
     // Timer will be started after kernel initialization is complete
     // to prevent deadlocks during init
     unsafe {
-        lapic.init_timer(lapic_frequency, crate::config::SCHED_HZ);
+        crate::sched::hrtimer::init_cpu_timer(&mut lapic);
     }
     serial_println!(
-        "[APIC] core{} timer configured @{}Hz (not started)",
+        "[APIC] core{} tick configured @{}Hz (not started)",
         saved_cpu_id,
         crate::config::SCHED_HZ
     );
@@ -380,6 +382,17 @@ const AP_STACK_SIZE: usize = 16 * 1024;
 /// This is within the identity-mapped region (0x0-0x1FFFFF)
 const AP_STACK_PHYS_BASE: usize = 0x100000;
 
+/// Physical range of the boot stack used by an AP
+///
+/// # Returns
+/// `(base, size)` of the stack for `cpu_id` (must be 1..N)
+pub fn ap_stack_range(cpu_id: usize) -> (usize, usize) {
+    (
+        AP_STACK_PHYS_BASE + (cpu_id - 1) * AP_STACK_SIZE,
+        AP_STACK_SIZE,
+    )
+}
+
 /// Trampoline binary data
 /// This is the compiled AP boot code that will be copied to 0x8000
 static TRAMPOLINE_CODE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/boot_ap.bin"));
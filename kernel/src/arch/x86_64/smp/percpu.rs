@@ -5,77 +5,91 @@
 ///
 /// Each CPU core has its own PerCpu structure that is cache-line aligned
 /// to prevent false sharing between cores.
+use crate::arch::x86_64::smp::topology::CpuTopology;
 use crate::config::MAX_CPUS;
-use crate::sched::task::TaskId;
-use crate::sync::SpinLock;
+use crate::sched::balance::CpuLoad;
+use crate::sched::cputime::CpuTime;
+use crate::sched::fair::{FairRunQueue, NICE_0_LOAD};
+use crate::sched::hrtimer::TimerQueue;
+use crate::sched::rt::RtRunQueue;
+use crate::sched::task::{Task, TaskId};
+use crate::sync::{IrqSpinLock, SpinLock};
 use core::sync::atomic::{AtomicU64, Ordering};
 
-/// Maximum number of tasks per CPU runqueue
-const MAX_RUNQUEUE_SIZE: usize = 64;
-
-/// Simple circular queue for task IDs in per-CPU runqueue
+/// Per-CPU runqueue holding every scheduling class
 ///
-/// This is a fixed-size queue that uses a circular buffer to store task IDs.
-/// It's more efficient than a dynamic collection for kernel use.
+/// Real-time tasks always run before fair tasks, unless the real-time class
+/// has used up its budget for the current throttling period.
 pub struct RunQueue {
-    tasks: [TaskId; MAX_RUNQUEUE_SIZE],
-    head: usize,
-    tail: usize,
-    count: usize,
+    /// SCHED_FIFO / SCHED_RR tasks
+    pub rt: RtRunQueue,
+
+    /// SCHED_OTHER tasks, ordered by vruntime
+    pub fair: FairRunQueue,
 }
 
 impl RunQueue {
     /// Create a new empty runqueue
     pub const fn new() -> Self {
         Self {
-            tasks: [0; MAX_RUNQUEUE_SIZE],
-            head: 0,
-            tail: 0,
-            count: 0,
+            rt: RtRunQueue::new(),
+            fair: FairRunQueue::new(),
         }
     }
 
-    /// Add a task to the back of the queue
+    /// Add a task to the queue of its current class
     ///
-    /// Returns true if successful, false if queue is full
-    pub fn push_back(&mut self, task_id: TaskId) -> bool {
-        if self.count >= MAX_RUNQUEUE_SIZE {
+    /// Returns false if the task is already queued
+    pub fn enqueue(&mut self, task: &mut Task, wakeup: bool) -> bool {
+        if task.rt.is_queued() || task.se.is_queued() {
             return false;
         }
 
-        self.tasks[self.tail] = task_id;
-        self.tail = (self.tail + 1) % MAX_RUNQUEUE_SIZE;
-        self.count += 1;
-        true
+        if task.rt.is_rt() {
+            self.rt.enqueue(task, wakeup)
+        } else {
+            self.fair.enqueue(task, wakeup)
+        }
     }
 
-    /// Remove and return the task from the front of the queue
+    /// Remove a task from whichever class queue holds it
     ///
-    /// Returns None if queue is empty
-    pub fn pop_front(&mut self) -> Option<TaskId> {
-        if self.count == 0 {
-            return None;
+    /// Returns false if the task is not queued on this CPU
+    pub fn dequeue(&mut self, task: &mut Task) -> bool {
+        self.rt.dequeue(task) || self.fair.dequeue(task)
+    }
+
+    /// Check whether the task is queued on this CPU
+    pub fn contains(&self, task: &Task) -> bool {
+        self.rt.contains(task) || self.fair.contains(task)
+    }
+
+    /// Pick the next task to run and take it off the queue
+    pub fn pick_next(&mut self) -> Option<&'static mut Task> {
+        match self.rt.pick_next() {
+            Some(task) => Some(task),
+            None => self.fair.pick_next(),
         }
+    }
 
-        let task_id = self.tasks[self.head];
-        self.head = (self.head + 1) % MAX_RUNQUEUE_SIZE;
-        self.count -= 1;
-        Some(task_id)
+    /// Check whether any queued task may run now
+    pub fn has_runnable(&self) -> bool {
+        self.rt.has_runnable() || !self.fair.is_empty()
     }
 
-    /// Get the number of tasks in the queue
+    /// Get the number of queued tasks
     pub fn len(&self) -> usize {
-        self.count
+        self.rt.len() + self.fair.len()
     }
 
-    /// Check if the queue is empty
-    pub fn is_empty(&self) -> bool {
-        self.count == 0
+    /// Total weight of queued tasks (real-time tasks count as nice 0)
+    pub fn load(&self) -> u64 {
+        self.rt.len() as u64 * NICE_0_LOAD + self.fair.load()
     }
 
-    /// Check if the queue is full
-    pub fn is_full(&self) -> bool {
-        self.count >= MAX_RUNQUEUE_SIZE
+    /// Check if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.rt.is_empty() && self.fair.is_empty()
     }
 }
 
@@ -121,12 +135,18 @@ impl PerCpuStats {
 /// # Fields
 /// * `id` - Logical CPU ID (0 for BSP, 1..N for APs)
 /// * `apic_id` - APIC ID from MADT (may not be sequential)
-/// * `node_id` - NUMA node ID (for future NUMA support)
-/// * `runqueue` - Queue of tasks ready to execute on this core
+/// * `node_id` - NUMA node ID from the ACPI SRAT (0 on non-NUMA machines)
+/// * `topology` - Package, core and SMT thread decoded from `apic_id`
+/// * `runqueue` - Tasks ready to execute on this core (real-time and fair)
 /// * `current_task` - Currently executing task (None if idle)
 /// * `idle_task` - Idle task for this core (runs when no other tasks are ready)
+/// * `migrate_pending` - Switched-out task that must be queued on another core
+/// * `prev_task` - Switched-out task whose context is still being saved
+/// * `load` - Decaying average of the runqueue weight, for load balancing
+/// * `cputime` - Time spent in user, system, idle and interrupt context
 /// * `lapic_timer_hz` - Calibrated LAPIC timer frequency in Hz
 /// * `ticks` - Number of timer ticks since boot
+/// * `timers` - Pending high-resolution timers and the next tick deadline
 /// * `in_interrupt` - True if currently executing an interrupt handler
 /// * `stats` - Per-CPU statistics counters
 #[repr(C, align(64))]
@@ -137,9 +157,12 @@ pub struct PerCpu {
     /// APIC ID from MADT
     pub apic_id: u8,
 
-    /// NUMA node ID (for future NUMA support)
+    /// NUMA node ID (from the ACPI SRAT, 0 without one)
     pub node_id: u8,
 
+    /// Package, core and SMT thread of this CPU
+    pub topology: CpuTopology,
+
     /// Runqueue for this CPU core
     pub runqueue: SpinLock<RunQueue>,
 
@@ -149,12 +172,31 @@ pub struct PerCpu {
     /// Idle task for this core
     pub idle_task: TaskId,
 
+    /// Task that was switched out because it may no longer run here
+    ///
+    /// It is queued elsewhere once the switch has saved its context.
+    pub migrate_pending: Option<TaskId>,
+
+    /// Task switched out by the last context switch on this core
+    ///
+    /// Its `on_cpu` flag is cleared once the switch has saved its context.
+    pub prev_task: Option<TaskId>,
+
+    /// Load average of this CPU
+    pub load: CpuLoad,
+
+    /// Time this CPU has spent in each state (see `sched::cputime`)
+    pub cputime: CpuTime,
+
     /// Calibrated LAPIC timer frequency in Hz
     pub lapic_timer_hz: u64,
 
     /// Number of timer ticks since boot
     pub ticks: AtomicU64,
 
+    /// High-resolution timers armed on this CPU
+    pub timers: IrqSpinLock<TimerQueue>,
+
     /// True if currently executing an interrupt handler
     pub in_interrupt: bool,
 
@@ -172,11 +214,17 @@ impl PerCpu {
             id: 0,
             apic_id: 0,
             node_id: 0,
+            topology: CpuTopology::unknown(),
             runqueue: SpinLock::new(RunQueue::new()),
             current_task: None,
             idle_task: 0,
+            migrate_pending: None,
+            prev_task: None,
+            load: CpuLoad::new(),
+            cputime: CpuTime::new(),
             lapic_timer_hz: 0,
             ticks: AtomicU64::new(0),
+            timers: IrqSpinLock::new(TimerQueue::new()),
             in_interrupt: false,
             stats: PerCpuStats::new(),
         }
@@ -308,7 +356,8 @@ pub unsafe fn init_percpu(cpu_id: usize, apic_id: u8) {
         options(nostack, nomem)
     );
 
-    percpu.node_id = 0;
+    percpu.node_id = crate::arch::x86_64::acpi::numa_node_for_apic(apic_id);
+    percpu.topology = CpuTopology::from_apic_id(apic_id);
     core::arch::asm!(
         "mov al, 'G'",
         "mov dx, 0x3F8",
@@ -499,6 +548,14 @@ pub unsafe fn setup_gs_base(cpu_id: usize) {
     wrmsr(MSR_GS_BASE, percpu_ptr);
 }
 
+/// Check whether GS.BASE has been set up on the current CPU
+///
+/// Code that can run during early boot (before `setup_gs_base`) must check
+/// this before calling `percpu_current`.
+pub fn percpu_ready() -> bool {
+    unsafe { rdmsr(MSR_GS_BASE) != 0 }
+}
+
 /// Get a reference to the current CPU's PerCpu structure
 ///
 /// This function reads the GS.BASE MSR to get a pointer to the current
@@ -0,0 +1,159 @@
+//! CPU topology detection
+//!
+//! Splits each CPU's APIC ID (taken from the ACPI MADT) into package, core
+//! and SMT thread numbers. The number of APIC ID bits used by each level
+//! comes from CPUID: the extended topology leaf (0x0B) when available,
+//! otherwise the legacy leaves 0x01 and 0x04. Together with the NUMA node
+//! from the ACPI SRAT this tells the scheduler which CPUs share a core
+//! (and its L1/L2 caches) or a package (and its last-level cache).
+
+use core::arch::x86_64::{__cpuid, __cpuid_count};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// CPUID.01H:EDX bit 28 - more than one logical processor per package
+const CPUID_EDX_HTT: u32 = 1 << 28;
+
+/// Extended topology level types (CPUID.0BH:ECX[15:8])
+const LEVEL_TYPE_SMT: u32 = 1;
+const LEVEL_TYPE_CORE: u32 = 2;
+
+/// APIC ID bit widths, packed as `smt_shift | package_shift << 8`
+///
+/// Detected once and shared by every CPU; `u32::MAX` until then.
+static SHIFTS: AtomicU32 = AtomicU32::new(u32::MAX);
+
+/// Position of a CPU in the machine
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CpuTopology {
+    /// Physical package (socket)
+    pub package: u32,
+    /// Core within the package
+    pub core: u32,
+    /// SMT thread within the core
+    pub thread: u32,
+}
+
+impl CpuTopology {
+    /// Placeholder until `init_percpu` decodes the APIC ID
+    pub const fn unknown() -> Self {
+        Self {
+            package: 0,
+            core: 0,
+            thread: 0,
+        }
+    }
+
+    /// Decode an APIC ID
+    pub fn from_apic_id(apic_id: u8) -> Self {
+        let (smt_shift, package_shift) = shifts();
+        Self::decode(apic_id as u32, smt_shift, package_shift)
+    }
+
+    fn decode(apic_id: u32, smt_shift: u32, package_shift: u32) -> Self {
+        let core_bits = package_shift - smt_shift;
+        Self {
+            package: apic_id >> package_shift,
+            core: (apic_id >> smt_shift) & ((1 << core_bits) - 1),
+            thread: apic_id & ((1 << smt_shift) - 1),
+        }
+    }
+
+    /// Check whether two CPUs are SMT siblings (same physical core)
+    pub fn same_core(&self, other: &Self) -> bool {
+        self.package == other.package && self.core == other.core
+    }
+
+    /// Check whether two CPUs share a package (and its last-level cache)
+    pub fn same_package(&self, other: &Self) -> bool {
+        self.package == other.package
+    }
+}
+
+/// APIC ID bit widths `(smt_shift, package_shift)`, detected on first use
+fn shifts() -> (u32, u32) {
+    let mut packed = SHIFTS.load(Ordering::Relaxed);
+    if packed == u32::MAX {
+        let (smt_shift, package_shift) = detect_shifts();
+        packed = smt_shift | package_shift << 8;
+        SHIFTS.store(packed, Ordering::Relaxed);
+    }
+    (packed & 0xFF, packed >> 8)
+}
+
+/// Number of bits needed to number `count` items
+fn bits_for(count: u32) -> u32 {
+    if count <= 1 {
+        0
+    } else {
+        32 - (count - 1).leading_zeros()
+    }
+}
+
+fn detect_shifts() -> (u32, u32) {
+    let max_leaf = __cpuid(0).eax;
+
+    // Extended topology: each sub-leaf gives the shift to the next level up
+    if max_leaf >= 0x0B && __cpuid_count(0x0B, 0).ebx != 0 {
+        let mut smt_shift = 0;
+        let mut package_shift = 0;
+
+        for sub_leaf in 0..8 {
+            let level = __cpuid_count(0x0B, sub_leaf);
+            let level_type = (level.ecx >> 8) & 0xFF;
+            let shift = level.eax & 0x1F;
+
+            match level_type {
+                0 => break,
+                LEVEL_TYPE_SMT => smt_shift = shift,
+                LEVEL_TYPE_CORE => package_shift = shift,
+                _ => package_shift = package_shift.max(shift),
+            }
+        }
+
+        return (smt_shift, package_shift.max(smt_shift));
+    }
+
+    // Legacy: logical processors per package and cores per package
+    let leaf1 = __cpuid(1);
+    if leaf1.edx & CPUID_EDX_HTT == 0 {
+        return (0, 0);
+    }
+
+    let logical = (leaf1.ebx >> 16) & 0xFF;
+    let cores = if max_leaf >= 4 {
+        (__cpuid_count(4, 0).eax >> 26) + 1
+    } else {
+        1
+    };
+
+    let package_shift = bits_for(logical);
+    let smt_shift = bits_for(logical / cores.max(1)).min(package_shift);
+    (smt_shift, package_shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_apic_id() {
+        // 2 threads per core, 4 cores per package
+        let topo = CpuTopology::decode(0b1_011_1, 1, 3);
+        assert_eq!(topo.package, 1);
+        assert_eq!(topo.core, 3);
+        assert_eq!(topo.thread, 1);
+
+        let sibling = CpuTopology::decode(0b1_011_0, 1, 3);
+        assert!(topo.same_core(&sibling));
+        assert!(!topo.same_core(&CpuTopology::decode(0b1_010_1, 1, 3)));
+        assert!(topo.same_package(&CpuTopology::decode(0b1_000_0, 1, 3)));
+    }
+
+    #[test]
+    fn test_bits_for() {
+        assert_eq!(bits_for(1), 0);
+        assert_eq!(bits_for(2), 1);
+        assert_eq!(bits_for(6), 3);
+        assert_eq!(bits_for(8), 3);
+    }
+}
@@ -5,7 +5,6 @@
 //! and assembly entry points.
 
 use crate::arch::x86_64::gdt::{KERNEL_CODE_SEG, USER_CODE_SEG};
-use crate::user::process::ProcessId;
 use crate::{serial_print, serial_println};
 
 /// Model Specific Registers for syscall/sysret
@@ -169,10 +168,6 @@ pub const SYS_EXEC: usize = 9;
 /// Error codes (POSIX-compatible)
 pub const ENOSYS: isize = -38; // Function not implemented
 pub const EFAULT: isize = -14; // Bad address
-pub const ENOMEM: isize = -12; // Out of memory
-pub const ECHILD: isize = -10; // No child processes
-pub const ESRCH: isize = -3; // No such process
-pub const EAGAIN: isize = -11; // Try again
 pub const EINVAL: isize = -22; // Invalid argument
 pub const EPERM: isize = -1; // Operation not permitted
 
@@ -204,16 +199,8 @@ pub fn copy_from_user(dst: &mut [u8], src_ptr: usize, len: usize) -> Result<(),
         return Err(EINVAL);
     }
 
-    // TODO: When implementing full page table separation, replace direct pointer
-    // access with temporary kernel mapping (kmap_user_page()) for safety
-
-    // Perform copy with page fault handling (current shared address space)
-    unsafe {
-        let src = core::slice::from_raw_parts(src_ptr as *const u8, len);
-        dst[..len].copy_from_slice(src);
-    }
-
-    Ok(())
+    // Walk the current task's page tables and copy through the direct map
+    crate::user::process::copy_from_user(dst, src_ptr, len).map_err(|_| EFAULT)
 }
 
 /// Copy data from kernel space to user space
@@ -233,16 +220,8 @@ pub fn copy_to_user(dst_ptr: usize, src: &[u8]) -> Result<(), isize> {
         return Err(EFAULT);
     }
 
-    // TODO: When implementing full page table separation, replace direct pointer
-    // access with temporary kernel mapping (kmap_user_page()) for safety
-
-    // Perform copy with page fault handling (current shared address space)
-    unsafe {
-        let dst = core::slice::from_raw_parts_mut(dst_ptr as *mut u8, src.len());
-        dst.copy_from_slice(src);
-    }
-
-    Ok(())
+    // Walk the current task's page tables and copy through the direct map
+    crate::user::process::copy_to_user(dst_ptr, src).map_err(|_| EFAULT)
 }
 
 /// Enhanced syscall dispatcher with detailed logging and new syscalls
@@ -267,10 +246,22 @@ extern "C" fn syscall_dispatcher_enhanced(
     arg1: usize,
     arg2: usize,
     arg3: usize,
-    _arg4: usize,
-    _arg5: usize,
-    _arg6: usize,
+    arg4: usize,
+    arg5: usize,
+    arg6: usize,
 ) -> isize {
+    crate::sched::cputime::syscall_enter();
+
+    // Syscalls answered here skip `syscall_dispatcher`, so the task's
+    // syscall filter is applied to them here
+    if matches!(syscall_id, SYS_WRITE | SYS_YIELD | SYS_GETPID) {
+        let args = [arg1, arg2, arg3, arg4, arg5, arg6].map(|arg| arg as u64);
+        if let Some(result) = crate::user::seccomp::filter_syscall(syscall_id, &args) {
+            crate::sched::cputime::syscall_exit();
+            return result;
+        }
+    }
+
     // Get current CPU and process for detailed logging
     let cpu_id = unsafe { crate::arch::x86_64::smp::percpu::percpu_current().id };
     let pid = get_current_process_id().unwrap_or(0);
@@ -299,12 +290,13 @@ extern "C" fn syscall_dispatcher_enhanced(
             if !is_user_pointer_valid(arg1) {
                 EFAULT
             } else {
-                sys_exec_stub(arg1, arg2)
+                crate::sys::syscall::syscall_dispatcher(syscall_id, arg1, arg2, arg3)
             }
         }
-        SYS_EXIT => sys_exit_enhanced(arg1),
-        SYS_FORK => sys_fork_stub(),
-        SYS_WAIT => sys_wait_stub(arg1),
+        // Process lifecycle lives with the task model in `user::process`
+        SYS_EXIT | SYS_FORK | SYS_WAIT => {
+            crate::sys::syscall::syscall_dispatcher(syscall_id, arg1, arg2, arg3)
+        }
         SYS_YIELD => sys_yield_enhanced(),
         SYS_GETPID => sys_getpid_enhanced(),
 
@@ -353,6 +345,7 @@ extern "C" fn syscall_dispatcher_enhanced(
         );
     }
 
+    crate::sched::cputime::syscall_exit();
     result
 }
 
@@ -505,187 +498,6 @@ fn sys_write_enhanced(fd: usize, buf_ptr: usize, len: usize) -> isize {
     len as isize
 }
 
-/// Enhanced sys_exit handler - Mark process as zombie and clean up
-///
-/// This function performs complete process termination:
-/// 1. Closes all file descriptors
-/// 2. Frees memory regions
-/// 3. Marks process as zombie with exit code
-/// 4. Notifies parent process
-/// 5. Removes task from scheduler
-///
-/// # Arguments
-/// * `code` - Exit code for the process
-///
-/// # Returns
-/// Never returns (process is terminated)
-fn sys_exit_enhanced(code: usize) -> ! {
-    use crate::sched;
-    use crate::user::process::ProcessManager;
-
-    let cpu_id = unsafe { crate::arch::x86_64::smp::percpu::percpu_current().id };
-    let pid = get_current_process_id().unwrap_or(0);
-
-    serial_println!(
-        "[SYSCALL][cpu{} pid={}] Process exiting with code {}",
-        cpu_id,
-        pid,
-        code
-    );
-
-    // Get current task information
-    let current_task_info = match sched::get_current_task_info() {
-        Some(info) => info,
-        None => {
-            serial_println!("[SYSCALL] SYS_EXIT: No current task found");
-            // Fall back to infinite loop
-            loop {
-                unsafe {
-                    core::arch::asm!("hlt");
-                }
-            }
-        }
-    };
-
-    let current_task_id = current_task_info.0;
-
-    // Step 1: Close all file descriptors
-    serial_println!("[SYSCALL] SYS_EXIT: Closing all file descriptors");
-    crate::sys::syscall::close_all_fds_on_exit();
-
-    // Step 2: Get process and prepare for cleanup
-    let parent_pid = if let Some(mut process_guard) = ProcessManager::get_process(current_task_id) {
-        if let Some(process) = process_guard.get_mut() {
-            let parent = process.parent_pid;
-
-            // Mark process as zombie with exit code
-            process.mark_zombie(code as i32);
-
-            serial_println!(
-                "[SYSCALL] SYS_EXIT: Process {} marked as zombie with exit code {}",
-                process.pid,
-                code
-            );
-
-            // Memory regions will be freed when process is reaped by parent
-            // For now, we keep them so parent can inspect the zombie state
-
-            parent
-        } else {
-            None
-        }
-    } else {
-        None
-    };
-
-    // Step 3: Notify parent process if it exists
-    if let Some(parent_pid) = parent_pid {
-        serial_println!(
-            "[SYSCALL] SYS_EXIT: Process {} has parent {}, waking parent if waiting",
-            pid,
-            parent_pid
-        );
-
-        // Wake up parent if it's blocked waiting for this child
-        // The parent will clean up the zombie and remove it from its children list
-        wake_parent_if_waiting(parent_pid, current_task_id);
-    } else {
-        serial_println!(
-            "[SYSCALL] SYS_EXIT: Process {} has no parent (orphan or init)",
-            pid
-        );
-
-        // If no parent, this is an orphaned process
-        // In a full implementation, orphaned processes would be reparented to init
-        // For now, we just ensure the process is not in any children list
-        // Since there's no parent, there's no children list to remove from
-    }
-
-    // Step 4: Remove current task from scheduler
-    // Mark task as blocked so it won't be rescheduled
-    // The zombie process will be cleaned up when parent calls wait()
-    if let Some(current_task) = sched::get_task_mut(current_task_id) {
-        current_task.state = crate::sched::task::TaskState::Blocked;
-        serial_println!(
-            "[SYSCALL] SYS_EXIT: Task {} marked as blocked (zombie)",
-            current_task_id
-        );
-    }
-
-    serial_println!(
-        "[SYSCALL] SYS_EXIT: Process {} terminating, yielding to scheduler",
-        pid
-    );
-
-    // Step 5: Yield to scheduler - this task should never be scheduled again
-    sched::yield_now();
-
-    // Should never reach here, but provide fallback
-    serial_println!("[SYSCALL] SYS_EXIT: ERROR - Returned from yield after exit!");
-    loop {
-        unsafe {
-            core::arch::asm!("hlt");
-        }
-    }
-}
-
-/// Wake up parent process if it's waiting for this child
-///
-/// This function checks if the parent process is blocked on waitpid
-/// and wakes it up if so. It uses the scheduler's wakeup mechanism
-/// to properly transition the parent from Sleeping to Ready state
-/// and re-enqueue it to the appropriate CPU runqueue.
-///
-/// # Arguments
-/// * `parent_pid` - Parent process ID
-/// * `child_pid` - Child process ID that just exited
-fn wake_parent_if_waiting(parent_pid: ProcessId, child_pid: ProcessId) {
-    use crate::sched;
-    use crate::sched::task::TaskState;
-
-    serial_println!(
-        "[SYSCALL] wake_parent: Checking if parent {} is waiting for child {}",
-        parent_pid,
-        child_pid
-    );
-
-    // Check if parent task is blocked waiting for children
-    if let Some(parent_task) = sched::get_task_mut(parent_pid) {
-        // Check if parent is in Sleeping state (used for blocking syscalls like waitpid)
-        if parent_task.state == TaskState::Sleeping {
-            // Wake up the parent - it will check for zombie children when it resumes
-            parent_task.state = TaskState::Ready;
-            parent_task.wake_tick = None;
-
-            serial_println!(
-                "[SYSCALL] wake_parent: Woke up parent process {} waiting for child {}",
-                parent_pid,
-                child_pid
-            );
-
-            // Re-enqueue the parent task to the scheduler
-            // This ensures it will be scheduled to run again
-            sched::enqueue_task(parent_pid, None);
-
-            serial_println!(
-                "[SYSCALL] wake_parent: Parent {} re-enqueued to scheduler",
-                parent_pid
-            );
-        } else {
-            serial_println!(
-                "[SYSCALL] wake_parent: Parent process {} not waiting (state: {:?})",
-                parent_pid,
-                parent_task.state
-            );
-        }
-    } else {
-        serial_println!(
-            "[SYSCALL] wake_parent: Parent process {} not found in task table",
-            parent_pid
-        );
-    }
-}
-
 /// Enhanced sys_yield handler
 ///
 /// This version marks the current task as Ready and calls the scheduler
@@ -721,645 +533,6 @@ fn sys_getpid_enhanced() -> isize {
     pid as isize
 }
 
-/// sys_fork implementation - Create a child process
-///
-/// Creates a copy of the current process with a new PID.
-/// Returns child PID to parent, 0 to child.
-///
-/// # Returns
-/// * Child PID (positive) in parent process
-/// * 0 in child process  
-/// * Negative error code on failure
-fn sys_fork_stub() -> isize {
-    use crate::mm::paging::PageTable;
-
-    use crate::sched::{self};
-    use crate::user::process::{ProcessError, ProcessManager};
-
-    serial_println!("[SYSCALL] SYS_FORK: Creating child process");
-
-    // Get current task/process information
-    let current_task_info = match sched::get_current_task_info() {
-        Some(info) => info,
-        None => {
-            serial_println!("[SYSCALL] SYS_FORK: No current task found");
-            return ESRCH; // No such process
-        }
-    };
-
-    let parent_task_id = current_task_info.0;
-
-    // Get parent task to copy its context and memory regions
-    let parent_task = match sched::get_task_mut(parent_task_id) {
-        Some(task) => task,
-        None => {
-            serial_println!("[SYSCALL] SYS_FORK: Parent task not found");
-            return ESRCH;
-        }
-    };
-
-    // Create a new process with the current task as parent
-    let child_pid = match ProcessManager::create_process(Some(parent_task_id), "forked_process") {
-        Ok(pid) => pid,
-        Err(ProcessError::ProcessTableFull) => {
-            serial_println!("[SYSCALL] SYS_FORK: Process table full");
-            return EAGAIN; // Try again later
-        }
-        Err(_) => {
-            serial_println!("[SYSCALL] SYS_FORK: Failed to create process");
-            return ENOMEM; // Out of memory
-        }
-    };
-
-    // Get the child process to set up its memory and context
-    let mut child_process_guard = match ProcessManager::get_process(child_pid) {
-        Some(guard) => guard,
-        None => {
-            serial_println!("[SYSCALL] SYS_FORK: Child process not found after creation");
-            return ENOMEM;
-        }
-    };
-
-    let child_process = match child_process_guard.get_mut() {
-        Some(process) => process,
-        None => {
-            serial_println!("[SYSCALL] SYS_FORK: Child process slot empty");
-            return ENOMEM;
-        }
-    };
-
-    // Copy parent's memory regions to child
-    // TODO: Implement copy-on-write optimization in the future
-    for i in 0..parent_task.region_count {
-        if let Some(region) = &parent_task.memory_regions[i] {
-            match child_process.add_memory_region(region.clone()) {
-                Ok(()) => {
-                    serial_println!(
-                        "[SYSCALL] SYS_FORK: Copied memory region {:?}",
-                        region.region_type
-                    );
-                }
-                Err(e) => {
-                    serial_println!("[SYSCALL] SYS_FORK: Failed to copy memory region: {:?}", e);
-                    // Continue with other regions
-                }
-            }
-        }
-    }
-
-    // TODO: Copy parent's page table (mark as TODO for copy-on-write)
-    // For now, we'll create a new empty page table
-    // In a full implementation, we would duplicate the parent's page table
-    // and mark pages as copy-on-write
-    child_process.page_table = Some(PageTable::new());
-
-    // Copy parent's CPU context for the child
-    child_process.context = parent_task.context.clone();
-
-    // Note: Child's return value (0) will be set up during context switch
-    // The rax register is caller-saved and not part of CpuContext
-
-    // Set child process priority to match parent
-    child_process.priority = parent_task.priority;
-
-    // Create a corresponding Task for the scheduler
-    // We need to create a task entry point that will restore the child's context
-    fn child_task_entry() -> ! {
-        // This function should never be called directly
-        // The child will resume from the fork point with context switching
-        panic!("[FORK] Child task entry called directly - this should not happen");
-    }
-
-    // Create child task with same priority as parent
-    let child_task_id =
-        match sched::spawn_task("forked_task", child_task_entry, parent_task.priority) {
-            Ok(id) => id,
-            Err(e) => {
-                serial_println!("[SYSCALL] SYS_FORK: Failed to create child task: {:?}", e);
-                // Clean up the process we created
-                let _ = ProcessManager::remove_process(child_pid);
-                return ENOMEM;
-            }
-        };
-
-    // Get the child task and set up its context to match the child process
-    if let Some(child_task) = sched::get_task_mut(child_task_id) {
-        // Copy the child process context to the child task
-        child_task.context = child_process.context.clone();
-
-        // Copy memory regions from child process to child task
-        child_task.region_count = 0;
-        for i in 0..child_process.region_count {
-            if let Some(region) = &child_process.memory_regions[i] {
-                if let Err(e) = child_task.add_memory_region(region.clone()) {
-                    serial_println!(
-                        "[SYSCALL] SYS_FORK: Failed to copy region to child task: {:?}",
-                        e
-                    );
-                }
-            }
-        }
-
-        serial_println!(
-            "[SYSCALL] SYS_FORK: Created child process PID {} with task ID {}",
-            child_pid,
-            child_task_id
-        );
-    } else {
-        serial_println!("[SYSCALL] SYS_FORK: Failed to get child task after creation");
-        // Clean up
-        let _ = ProcessManager::remove_process(child_pid);
-        return ENOMEM;
-    }
-
-    // Drop the child process guard to release the lock
-    drop(child_process_guard);
-
-    // Add child to parent's children list
-    if let Some(parent_task) = sched::get_task_mut(parent_task_id) {
-        parent_task.children.push(child_pid);
-        serial_println!(
-            "[SYSCALL] SYS_FORK: Added child {} to parent {}'s children list",
-            child_pid,
-            parent_task_id
-        );
-    } else {
-        serial_println!(
-            "[SYSCALL] SYS_FORK: Warning: Could not add child to parent's children list"
-        );
-    }
-
-    serial_println!(
-        "[SYSCALL] SYS_FORK: Fork completed successfully - parent gets PID {}",
-        child_pid
-    );
-
-    // Return child PID to parent process
-    // The child process will get 0 when it's scheduled (due to context.rax = 0)
-    child_pid as isize
-}
-
-/// Close all file descriptors with FD_CLOEXEC flag set
-///
-/// This is called during exec to close file descriptors that should not
-/// be inherited by the new program.
-fn close_cloexec_fds() {
-    use crate::sys::syscall::close_fds_with_cloexec;
-
-    serial_println!("[SYSCALL] Closing FDs with FD_CLOEXEC flag");
-    close_fds_with_cloexec();
-}
-
-/// sys_exec implementation - Replace current process with new ELF binary
-///
-/// Clears the current process memory space and loads a new ELF binary.
-/// This replaces the current process image entirely.
-///
-/// # Arguments
-/// * `path_ptr` - Pointer to null-terminated path string in user space
-/// * `argv_ptr` - Pointer to argument array (currently unused)
-///
-/// # Returns
-/// * Does not return on success (process is replaced)
-/// * Negative error code on failure
-fn sys_exec_stub(path_ptr: usize, _argv_ptr: usize) -> isize {
-    use crate::sched;
-
-    use crate::user::process::{copy_from_user, is_user_pointer_valid, ProcessManager};
-
-    serial_println!("[SYSCALL] SYS_EXEC: Replacing current process");
-
-    // Validate path pointer
-    if !is_user_pointer_valid(path_ptr) {
-        serial_println!("[SYSCALL] SYS_EXEC: Invalid path pointer");
-        return EFAULT;
-    }
-
-    // Get current task/process information
-    let current_task_info = match sched::get_current_task_info() {
-        Some(info) => info,
-        None => {
-            serial_println!("[SYSCALL] SYS_EXEC: No current task found");
-            return ESRCH;
-        }
-    };
-
-    let current_task_id = current_task_info.0;
-
-    // Copy path string from user space (limit to 256 bytes)
-    let mut path_buffer = [0u8; 256];
-    let path_str = unsafe {
-        // Find null terminator in user space
-        let mut len = 0;
-        let user_ptr = path_ptr as *const u8;
-        while len < 255 {
-            let byte = *user_ptr.add(len);
-            if byte == 0 {
-                break;
-            }
-            len += 1;
-        }
-
-        // Copy the string
-        match copy_from_user(&mut path_buffer[..len], path_ptr, len) {
-            Ok(()) => {
-                path_buffer[len] = 0; // Null terminate
-                match core::str::from_utf8(&path_buffer[..len]) {
-                    Ok(s) => s,
-                    Err(_) => {
-                        serial_println!("[SYSCALL] SYS_EXEC: Invalid UTF-8 in path");
-                        return EINVAL;
-                    }
-                }
-            }
-            Err(_) => {
-                serial_println!("[SYSCALL] SYS_EXEC: Failed to copy path from user space");
-                return EFAULT;
-            }
-        }
-    };
-
-    serial_println!("[SYSCALL] SYS_EXEC: Loading ELF binary: {}", path_str);
-
-    // Get current process to clear its memory space
-    let mut process_guard = match ProcessManager::get_process(current_task_id) {
-        Some(guard) => guard,
-        None => {
-            // If no process exists, create one for this task
-            match ProcessManager::create_process(None, "exec_process") {
-                Ok(pid) => match ProcessManager::get_process(pid) {
-                    Some(guard) => guard,
-                    None => {
-                        serial_println!("[SYSCALL] SYS_EXEC: Failed to get newly created process");
-                        return ENOMEM;
-                    }
-                },
-                Err(_) => {
-                    serial_println!("[SYSCALL] SYS_EXEC: Failed to create process for exec");
-                    return ENOMEM;
-                }
-            }
-        }
-    };
-
-    let process = match process_guard.get_mut() {
-        Some(p) => p,
-        None => {
-            serial_println!("[SYSCALL] SYS_EXEC: Process slot empty");
-            return ESRCH;
-        }
-    };
-
-    // Clear current memory space
-    process.clear_memory_regions();
-
-    // Close file descriptors with FD_CLOEXEC flag set
-    close_cloexec_fds();
-
-    // TODO: In a full implementation, we would:
-    // 1. Load the ELF binary from the file system
-    // 2. Parse ELF headers and program headers
-    // 3. Map PT_LOAD segments to virtual memory
-    // 4. Set up new user stack
-    // 5. Set entry point in CPU context
-
-    // For now, we'll simulate loading a simple "hello world" program
-    // This is a placeholder until we have a full ELF loader and file system
-
-    serial_println!(
-        "[SYSCALL] SYS_EXEC: Simulating ELF load for path: {}",
-        path_str
-    );
-
-    // Simulate setting up a new program
-    // In reality, this would involve:
-    // - Reading the ELF file from storage
-    // - Parsing ELF headers
-    // - Mapping program segments
-    // - Setting up the stack
-
-    // For demonstration, we'll set up a minimal memory layout
-    use crate::mm::paging::PageTableFlags;
-    use crate::sched::task::{MemoryRegion, MemoryRegionType};
-    use crate::user::process::{USER_STACK_SIZE, USER_STACK_TOP};
-
-    // Add a code region (simulated)
-    let code_region = MemoryRegion::new(
-        0x400000, // Standard ELF load address
-        0x401000, // 4KB code segment
-        PageTableFlags::PRESENT | PageTableFlags::USER,
-        MemoryRegionType::Code,
-    );
-
-    if let Err(e) = process.add_memory_region(code_region) {
-        serial_println!("[SYSCALL] SYS_EXEC: Failed to add code region: {:?}", e);
-        return ENOMEM;
-    }
-
-    // Add a stack region
-    let stack_region = MemoryRegion::new(
-        USER_STACK_TOP - USER_STACK_SIZE,
-        USER_STACK_TOP,
-        PageTableFlags::PRESENT
-            | PageTableFlags::WRITABLE
-            | PageTableFlags::USER
-            | PageTableFlags::NO_EXECUTE,
-        MemoryRegionType::Stack,
-    );
-
-    if let Err(e) = process.add_memory_region(stack_region) {
-        serial_println!("[SYSCALL] SYS_EXEC: Failed to add stack region: {:?}", e);
-        return ENOMEM;
-    }
-
-    // Reset CPU context for new program
-    // Set entry point to simulated code address
-    process.context.rsp = USER_STACK_TOP as u64;
-    process.context.rbx = 0;
-    process.context.rbp = 0;
-    process.context.r12 = 0x400000; // Entry point
-    process.context.r13 = 0;
-    process.context.r14 = 0;
-    process.context.r15 = 0;
-    // Note: Return values are handled by caller-saved registers, not in CpuContext
-
-    // Update process name
-    process.set_name(path_str);
-
-    // Get current task and update its memory regions and context
-    if let Some(current_task) = sched::get_task_mut(current_task_id) {
-        // Clear task memory regions
-        current_task.clear_memory_regions();
-
-        // Copy new memory regions from process to task
-        for i in 0..process.region_count {
-            if let Some(region) = &process.memory_regions[i] {
-                if let Err(e) = current_task.add_memory_region(region.clone()) {
-                    serial_println!("[SYSCALL] SYS_EXEC: Failed to copy region to task: {:?}", e);
-                }
-            }
-        }
-
-        // Update task context
-        current_task.context = process.context.clone();
-        current_task.creds.uid = process.uid;
-        current_task.creds.gid = process.gid;
-        current_task.creds.is_kernel_thread = false;
-        current_task.user_stack_pointer = process.context.rsp;
-
-        serial_println!("[SYSCALL] SYS_EXEC: Successfully replaced process image");
-        serial_println!(
-            "[SYSCALL] SYS_EXEC: New entry point: 0x{:x}",
-            current_task.context.r12
-        );
-        serial_println!(
-            "[SYSCALL] SYS_EXEC: New stack pointer: 0x{:x}",
-            current_task.context.rsp
-        );
-    } else {
-        serial_println!("[SYSCALL] SYS_EXEC: Failed to get current task");
-        return ESRCH;
-    }
-
-    // Drop process guard
-    drop(process_guard);
-
-    // exec() does not return on success - the process image is replaced
-    // We need to trigger a context switch to start executing the new program
-    // The new program will start from the entry point we set
-
-    serial_println!("[SYSCALL] SYS_EXEC: Triggering context switch to new program");
-
-    // Yield to scheduler to start executing the new program
-    sched::yield_now();
-
-    // Should never reach here if exec succeeded
-    panic!("[SYSCALL] SYS_EXEC: Returned from yield_now after exec - this should not happen");
-}
-
-/// sys_wait implementation - Wait for child process to exit
-///
-/// Waits for a child process to terminate and returns its PID and exit code.
-/// If no child has exited yet, the parent process blocks until one does.
-///
-/// # Arguments
-/// * `child_pid` - Specific child PID to wait for, or 0 to wait for any child
-///
-/// # Returns
-/// * Positive value: (child_pid << 8) | exit_code on success
-/// * Negative error code on failure
-fn sys_wait_stub(child_pid: usize) -> isize {
-    use crate::sched;
-    use crate::user::process::ProcessManager;
-
-    let cpu_id = unsafe { crate::arch::x86_64::smp::percpu::percpu_current().id };
-    let parent_pid = get_current_process_id().unwrap_or(0);
-
-    serial_println!(
-        "[SYSCALL][cpu{} pid={}] SYS_WAIT: Waiting for child {}",
-        cpu_id,
-        parent_pid,
-        if child_pid == 0 { "any" } else { "specific" }
-    );
-
-    // Get current task information
-    let current_task_info = match sched::get_current_task_info() {
-        Some(info) => info,
-        None => {
-            serial_println!("[SYSCALL] SYS_WAIT: No current task found");
-            return ESRCH;
-        }
-    };
-
-    let parent_task_id = current_task_info.0;
-
-    // Look for zombie children
-    let zombie_child = if child_pid == 0 {
-        // Wait for any child
-        ProcessManager::find_zombie_child(parent_task_id)
-    } else {
-        // Wait for specific child
-        if let Some(child_guard) = ProcessManager::get_process(child_pid) {
-            if let Some(child_process) = child_guard.get() {
-                if child_process.is_child_of(parent_task_id)
-                    && child_process.state == crate::user::process::ProcessState::Zombie
-                {
-                    child_process.exit_code.map(|code| (child_pid, code))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    };
-
-    if let Some((dead_child_pid, exit_code)) = zombie_child {
-        // Found a zombie child - clean it up and return
-        serial_println!(
-            "[SYSCALL] SYS_WAIT: Found zombie child {} with exit code {}",
-            dead_child_pid,
-            exit_code
-        );
-
-        // Remove the zombie process from the process table
-        match ProcessManager::remove_process(dead_child_pid) {
-            Ok(removed_process) => {
-                serial_println!(
-                    "[SYSCALL] SYS_WAIT: Cleaned up zombie process {} ({})",
-                    removed_process.pid,
-                    removed_process.get_name()
-                );
-            }
-            Err(e) => {
-                serial_println!(
-                    "[SYSCALL] SYS_WAIT: Failed to remove zombie process {}: {:?}",
-                    dead_child_pid,
-                    e
-                );
-            }
-        }
-
-        // Remove child from parent's children list
-        if let Some(parent_task) = sched::get_task_mut(parent_task_id) {
-            parent_task.children.retain(|&pid| pid != dead_child_pid);
-            serial_println!(
-                "[SYSCALL] SYS_WAIT: Removed child {} from parent's children list",
-                dead_child_pid
-            );
-        }
-
-        // Return child PID and exit code
-        // Encode as (child_pid << 8) | (exit_code & 0xFF)
-        let result = ((dead_child_pid & 0xFFFFFF) << 8) | ((exit_code as usize) & 0xFF);
-        serial_println!(
-            "[SYSCALL] SYS_WAIT: Returning result 0x{:x} (pid={}, code={})",
-            result,
-            dead_child_pid,
-            exit_code
-        );
-        return result as isize;
-    }
-
-    // No zombie children found - we should block the parent
-    serial_println!("[SYSCALL] SYS_WAIT: No zombie children found");
-
-    // Check if the parent has any children at all by checking the children list
-    let has_children = if let Some(parent_task) = sched::get_task_mut(parent_task_id) {
-        !parent_task.children.is_empty()
-    } else {
-        false
-    };
-
-    if !has_children {
-        serial_println!("[SYSCALL] SYS_WAIT: Parent has no children");
-        return ECHILD; // No child processes
-    }
-
-    // Parent has children but none are zombies yet
-    // Block the parent until a child exits
-    serial_println!(
-        "[SYSCALL] SYS_WAIT: Blocking parent {} until child exits",
-        parent_task_id
-    );
-
-    // Mark the parent task as Sleeping (blocked on waitpid)
-    if let Some(parent_task) = sched::get_task_mut(parent_task_id) {
-        parent_task.state = crate::sched::task::TaskState::Sleeping;
-        parent_task.wake_tick = None; // No timeout - wait indefinitely
-
-        serial_println!(
-            "[SYSCALL] SYS_WAIT: Parent {} marked as Sleeping, yielding to scheduler",
-            parent_task_id
-        );
-    } else {
-        serial_println!("[SYSCALL] SYS_WAIT: Failed to get parent task");
-        return ESRCH;
-    }
-
-    // Yield to scheduler - parent will be woken up when a child exits
-    sched::yield_now();
-
-    // When we resume here, a child has exited - check for zombies again
-    serial_println!(
-        "[SYSCALL] SYS_WAIT: Parent {} resumed, checking for zombies again",
-        parent_task_id
-    );
-
-    // Retry the zombie check after waking up
-    let zombie_child = if child_pid == 0 {
-        ProcessManager::find_zombie_child(parent_task_id)
-    } else {
-        if let Some(child_guard) = ProcessManager::get_process(child_pid) {
-            if let Some(child_process) = child_guard.get() {
-                if child_process.is_child_of(parent_task_id)
-                    && child_process.state == crate::user::process::ProcessState::Zombie
-                {
-                    child_process.exit_code.map(|code| (child_pid, code))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    };
-
-    if let Some((dead_child_pid, exit_code)) = zombie_child {
-        // Found a zombie child after waking up - clean it up and return
-        serial_println!(
-            "[SYSCALL] SYS_WAIT: After wakeup, found zombie child {} with exit code {}",
-            dead_child_pid,
-            exit_code
-        );
-
-        // Remove the zombie process from the process table
-        match ProcessManager::remove_process(dead_child_pid) {
-            Ok(removed_process) => {
-                serial_println!(
-                    "[SYSCALL] SYS_WAIT: Cleaned up zombie process {} ({})",
-                    removed_process.pid,
-                    removed_process.get_name()
-                );
-            }
-            Err(e) => {
-                serial_println!(
-                    "[SYSCALL] SYS_WAIT: Failed to remove zombie process {}: {:?}",
-                    dead_child_pid,
-                    e
-                );
-            }
-        }
-
-        // Remove child from parent's children list
-        if let Some(parent_task) = sched::get_task_mut(parent_task_id) {
-            parent_task.children.retain(|&pid| pid != dead_child_pid);
-            serial_println!(
-                "[SYSCALL] SYS_WAIT: Removed child {} from parent's children list",
-                dead_child_pid
-            );
-        }
-
-        // Return child PID and exit code
-        let result = ((dead_child_pid & 0xFFFFFF) << 8) | ((exit_code as usize) & 0xFF);
-        serial_println!(
-            "[SYSCALL] SYS_WAIT: Returning result 0x{:x} (pid={}, code={})",
-            result,
-            dead_child_pid,
-            exit_code
-        );
-        return result as isize;
-    }
-
-    // Still no zombie after waking up - this shouldn't happen but handle gracefully
-    serial_println!("[SYSCALL] SYS_WAIT: No zombie found after wakeup, returning EAGAIN");
-    return EAGAIN;
-}
-
 /// Integration tests for syscall mechanism
 #[cfg(test)]
 mod integration_tests {
@@ -1469,19 +642,10 @@ mod integration_tests {
         let result = syscall_dispatcher_enhanced(SYS_WRITE, 1, 0, 5, 0, 0, 0);
         assert_eq!(result, EFAULT);
 
-        // Test unimplemented syscalls
-        assert_eq!(
-            syscall_dispatcher_enhanced(SYS_FORK, 0, 0, 0, 0, 0, 0),
-            ENOSYS
-        );
-        assert_eq!(
-            syscall_dispatcher_enhanced(SYS_EXEC, 0x1000, 0, 0, 0, 0, 0),
-            ENOSYS
-        );
-        assert_eq!(
-            syscall_dispatcher_enhanced(SYS_WAIT, 1, 0, 0, 0, 0, 0),
-            ENOSYS
-        );
+        // Process syscalls need a user task to act on
+        assert!(syscall_dispatcher_enhanced(SYS_FORK, 0, 0, 0, 0, 0, 0) < 0);
+        assert!(syscall_dispatcher_enhanced(SYS_EXEC, 0x1000, 0, 0, 0, 0, 0) < 0);
+        assert!(syscall_dispatcher_enhanced(SYS_WAIT, 1, 0, 0, 0, 0, 0) < 0);
     }
 
     /// Test syscall performance and register preservation
@@ -1500,7 +664,6 @@ mod integration_tests {
         // Test error code constants
         assert_eq!(ENOSYS, -38);
         assert_eq!(EFAULT, -14);
-        assert_eq!(ENOMEM, -12);
         assert_eq!(EINVAL, -22);
         assert_eq!(EPERM, -1);
     }
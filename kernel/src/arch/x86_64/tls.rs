@@ -0,0 +1,45 @@
+//! Thread-Local Storage Base
+//!
+//! User threads find their TLS block through the FS segment base, which
+//! lives in the IA32_FS_BASE MSR. Each task keeps its value in
+//! `CpuContext::fs_base`; the scheduler loads it on every switch and
+//! `arch_prctl(ARCH_SET_FS)` changes it.
+
+/// IA32_FS_BASE MSR
+const IA32_FS_BASE: u32 = 0xC000_0100;
+
+/// `arch_prctl` code: set the FS base
+pub const ARCH_SET_FS: usize = 0x1002;
+
+/// `arch_prctl` code: read the FS base
+pub const ARCH_GET_FS: usize = 0x1003;
+
+/// Read the FS base of the current CPU
+pub fn read_fs_base() -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        core::arch::asm!(
+            "rdmsr",
+            in("ecx") IA32_FS_BASE,
+            out("eax") low,
+            out("edx") high,
+            options(nostack, preserves_flags)
+        );
+    }
+    ((high as u64) << 32) | low as u64
+}
+
+/// Set the FS base of the current CPU
+///
+/// # Safety
+/// `base` must be canonical, or the write faults.
+pub unsafe fn write_fs_base(base: u64) {
+    core::arch::asm!(
+        "wrmsr",
+        in("ecx") IA32_FS_BASE,
+        in("eax") base as u32,
+        in("edx") (base >> 32) as u32,
+        options(nostack, preserves_flags)
+    );
+}
@@ -0,0 +1,78 @@
+//! Time Stamp Counter (TSC) clocksource
+//!
+//! The TSC is calibrated against the PIT together with the LAPIC timer (see
+//! `LocalApic::calibrate_timer`) and then serves as the kernel's nanosecond
+//! clock. All CPUs are assumed to share a synchronized, invariant TSC, which
+//! holds on QEMU and on any CPU advertising an invariant TSC.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// IA32_TSC_DEADLINE MSR
+const IA32_TSC_DEADLINE: u32 = 0x6E0;
+
+/// CPUID.01H:ECX bit 24 - TSC-deadline timer mode supported
+const CPUID_ECX_TSC_DEADLINE: u32 = 1 << 24;
+
+/// Calibrated TSC frequency in Hz (0 until calibrated)
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+/// TSC value at calibration, used as time zero for the monotonic clock
+static TSC_BASE: AtomicU64 = AtomicU64::new(0);
+
+/// Read the time stamp counter
+#[inline]
+pub fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Record the calibrated TSC frequency and start the monotonic clock
+pub fn set_frequency(hz: u64) {
+    TSC_BASE.store(rdtsc(), Ordering::SeqCst);
+    TSC_HZ.store(hz, Ordering::SeqCst);
+}
+
+/// Calibrated TSC frequency in Hz (0 if not calibrated)
+pub fn frequency() -> u64 {
+    TSC_HZ.load(Ordering::Relaxed)
+}
+
+/// Check whether the TSC can be used as a clock
+pub fn is_calibrated() -> bool {
+    frequency() != 0
+}
+
+/// Check whether the LAPIC timer supports TSC-deadline mode
+pub fn has_deadline_mode() -> bool {
+    let cpuid = core::arch::x86_64::__cpuid(1);
+    cpuid.ecx & CPUID_ECX_TSC_DEADLINE != 0
+}
+
+/// Nanoseconds elapsed since calibration (0 if not calibrated)
+pub fn nanos() -> u64 {
+    let hz = frequency();
+    if hz == 0 {
+        return 0;
+    }
+    let delta = rdtsc().saturating_sub(TSC_BASE.load(Ordering::Relaxed));
+    (delta as u128 * 1_000_000_000 / hz as u128) as u64
+}
+
+/// Convert a time in nanoseconds since calibration to a TSC value
+pub fn nanos_to_tsc(ns: u64) -> u64 {
+    let hz = frequency();
+    TSC_BASE.load(Ordering::Relaxed) + (ns as u128 * hz as u128 / 1_000_000_000) as u64
+}
+
+/// Program the TSC-deadline MSR (0 disarms the timer)
+///
+/// # Safety
+/// The LAPIC timer must be in TSC-deadline mode.
+pub unsafe fn write_deadline(tsc: u64) {
+    core::arch::asm!(
+        "wrmsr",
+        in("ecx") IA32_TSC_DEADLINE,
+        in("eax") tsc as u32,
+        in("edx") (tsc >> 32) as u32,
+        options(nostack, preserves_flags)
+    );
+}
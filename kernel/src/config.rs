@@ -4,6 +4,70 @@
 /// Higher frequency = more responsive scheduling and faster sleep/wake
 pub const SCHED_HZ: u64 = 100;
 
+/// Fair scheduler latency target in nanoseconds - every runnable task
+/// should get a turn within this period (stretched when there are many)
+pub const SCHED_LATENCY_NS: u64 = 40_000_000;
+
+/// Minimum time a task runs before the fair scheduler preempts it
+/// At 100 Hz this is one tick
+pub const SCHED_MIN_GRANULARITY_NS: u64 = 10_000_000;
+
+/// SCHED_RR time slice in nanoseconds
+pub const SCHED_RR_TIMESLICE_NS: u64 = 100_000_000;
+
+/// Real-time throttling period in nanoseconds
+pub const SCHED_RT_PERIOD_NS: u64 = 1_000_000_000;
+
+/// Real-time runtime allowed per throttling period in nanoseconds
+/// The remaining 5% is left for fair-class tasks
+pub const SCHED_RT_RUNTIME_NS: u64 = 950_000_000;
+
+/// Ticks between periodic load balancing runs on each busy CPU
+/// At 100 Hz: 4 ticks = 40ms
+pub const SCHED_BALANCE_INTERVAL_TICKS: u64 = 4;
+
+/// Load imbalance (in percent of the local CPU's load average) above which
+/// periodic balancing pulls tasks from a busier CPU
+pub const SCHED_IMBALANCE_PCT: u64 = 125;
+
+/// Run the scheduler load-balancing benchmark at boot (see
+/// `benches/sched_balance.rs`). Off by default; it keeps every CPU busy
+/// for several seconds
+pub const SCHED_BENCH_ENABLED: bool = false;
+
+/// Stack size for kernel threads created with `sched::kthread`
+/// (plain `spawn_task` tasks get 8 KiB)
+pub const KTHREAD_STACK_SIZE: usize = 16 * 1024;
+
+/// Kernel stack size for threads and processes created by `clone`
+pub const CLONE_STACK_SIZE: usize = 16 * 1024;
+
+/// Ticks between wake-ups of the writeback flusher thread
+/// At 100 Hz: 500 ticks = 5 seconds
+pub const WRITEBACK_INTERVAL_TICKS: u64 = 500;
+
+/// Default upper bound for task IDs (`/proc/sys/kernel/pid_max`)
+/// Tasks are limited by memory and this bound, not by a table size
+pub const PID_MAX_DEFAULT: usize = 32768;
+
+/// Default path core dumps are written to (`/proc/sys/kernel/core_pattern`)
+/// `%e` is replaced by the command name, `%p` by the PID and `%s` by the signal
+pub const CORE_PATTERN_DEFAULT: &str = "/var/core/core.%e.%p";
+
+/// Default size limit for core dumps in bytes (soft `RLIMIT_CORE`, 0 disables them)
+/// Memory that does not fit under the limit is left out of the dump
+pub const CORE_SIZE_LIMIT_DEFAULT: u64 = 64 * 1024 * 1024;
+
+/// Default soft limit on open file descriptors (`RLIMIT_NOFILE`)
+pub const NOFILE_LIMIT_DEFAULT: usize = 256;
+
+/// Highest file descriptor limit a process can be given (hard `RLIMIT_NOFILE`)
+/// The file descriptor table grows on demand up to this size
+pub const NR_OPEN: usize = 1024;
+
+/// Default soft limit on the size of a growing stack in bytes (`RLIMIT_STACK`)
+pub const STACK_LIMIT_DEFAULT: u64 = 8 * 1024 * 1024;
+
 /// Maximum number of CPUs supported by the kernel
 pub const MAX_CPUS: usize = 16;
 
@@ -18,3 +82,16 @@ pub const FAST_BOOT_TIMEOUT_TICKS: usize = 500;
 /// Normal boot timeout in ticks
 /// At 100 Hz: 3000 ticks = 30 seconds
 pub const NORMAL_BOOT_TIMEOUT_TICKS: usize = 3000;
+
+/// Kernel memory leak detector (kmemleak) - tracks every heap allocation
+/// and periodically scans for unreferenced blocks. Costs memory and time;
+/// enable only when chasing leaks
+pub const KMEMLEAK_ENABLED: bool = false;
+
+/// Ticks between kmemleak scans
+/// At 100 Hz: 1000 ticks = 10 seconds
+pub const KMEMLEAK_SCAN_INTERVAL_TICKS: u64 = 1000;
+
+/// Minimum age in ticks before an unreferenced block is reported
+/// At 100 Hz: 500 ticks = 5 seconds
+pub const KMEMLEAK_MIN_AGE_TICKS: usize = 500;
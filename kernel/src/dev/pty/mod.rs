@@ -304,6 +304,8 @@ pub struct PtyMaster {
     pub winsize: Winsize,
     /// Whether the slave side is open
     pub slave_open: bool,
+    /// Number of open file descriptors for the master side
+    pub open_count: usize,
 }
 
 impl PtyMaster {
@@ -314,6 +316,7 @@ impl PtyMaster {
             termios: Termios::default(),
             winsize: Winsize::default(),
             slave_open: false,
+            open_count: 0,
         }
     }
 }
@@ -373,6 +376,7 @@ impl PtyPair {
     pub fn allocate(&mut self) {
         self.allocated = true;
         self.master.slave_open = false;
+        self.master.open_count = 1;
         self.slave.session = None;
         self.slave.foreground_pgid = None;
     }
@@ -385,6 +389,7 @@ impl PtyPair {
     }
 }
 
+use crate::sync::waitqueue::WaitQueue;
 use crate::sync::SpinLock;
 
 /// Global PTY table
@@ -475,6 +480,22 @@ impl PtyTable {
 /// Global PTY table instance
 static PTY_TABLE: SpinLock<PtyTable> = SpinLock::new(PtyTable::new());
 
+/// Tasks polling either side of each PTY pair
+static POLLERS: [WaitQueue; MAX_PTY_PAIRS] = [const { WaitQueue::new() }; MAX_PTY_PAIRS];
+
+/// Wait queue of a PTY pair, woken when data arrives on either side, the
+/// line discipline changes or the pair is closed
+pub fn poll_queue(number: PtyNumber) -> Option<&'static WaitQueue> {
+    POLLERS.get(number as usize)
+}
+
+/// Wake the tasks polling a PTY pair
+fn wake_pollers(number: PtyNumber) {
+    if let Some(queue) = poll_queue(number) {
+        queue.wake_all();
+    }
+}
+
 /// Send a signal to the foreground process group of a PTY
 ///
 /// This is called when special characters (Ctrl-C, Ctrl-Z, Ctrl-\) are detected
@@ -582,12 +603,38 @@ pub fn deallocate_pty(number: PtyNumber) -> bool {
     }
 
     let result = table.deallocate_pty(number);
+    drop(table);
     if result {
         crate::serial_println!("[PTY] Deallocated PTY pair {}", number);
+        wake_pollers(number);
     }
     result
 }
 
+/// Count another file descriptor for a PTY master (after dup or fork)
+pub fn hold_master(number: PtyNumber) {
+    let mut table = PTY_TABLE.lock();
+    if let Some(pair) = table.get_pty_mut(number) {
+        pair.master.open_count += 1;
+    }
+}
+
+/// Close a file descriptor for a PTY master
+///
+/// The pair is deallocated when its last master descriptor closes.
+pub fn release_master(number: PtyNumber) {
+    let last = match PTY_TABLE.lock().get_pty_mut(number) {
+        Some(pair) => {
+            pair.master.open_count = pair.master.open_count.saturating_sub(1);
+            pair.master.open_count == 0
+        }
+        None => false,
+    };
+    if last {
+        deallocate_pty(number);
+    }
+}
+
 /// Get the slave number for a PTY (for TIOCGPTN ioctl)
 ///
 /// Returns the PTY number if it's allocated, or None otherwise.
@@ -611,6 +658,9 @@ pub fn set_termios(number: PtyNumber, termios: Termios) -> bool {
     let mut table = PTY_TABLE.lock();
     if let Some(pair) = table.get_pty_mut(number) {
         pair.master.termios = termios;
+        drop(table);
+        // Switching out of canonical mode can make a partial line readable
+        wake_pollers(number);
         true
     } else {
         false
@@ -730,12 +780,14 @@ pub fn write_master(number: PtyNumber, data: &[u8]) -> usize {
             }
         }
 
+        drop(table);
         if bytes_written > 0 {
             crate::serial_println!(
                 "[PTY] Master wrote {} bytes to PTY {}",
                 bytes_written,
                 number
             );
+            wake_pollers(number);
         }
         bytes_written
     } else {
@@ -819,6 +871,26 @@ pub fn read_slave(number: PtyNumber, buf: &mut [u8]) -> usize {
     }
 }
 
+/// Check whether a read from one side of a PTY would return data
+///
+/// For the slave in canonical mode that takes a complete line.
+pub fn has_input(number: PtyNumber, master: bool) -> bool {
+    let table = PTY_TABLE.lock();
+    let pair = match table.get_pty(number) {
+        Some(pair) => pair,
+        None => return false,
+    };
+    if master {
+        return !pair.master.output_buffer.is_empty();
+    }
+    if pair.master.termios.c_lflag & lflag::ICANON == 0 {
+        return !pair.slave.input_buffer.is_empty();
+    }
+    let mut temp_buf = [0u8; PTY_BUFFER_SIZE];
+    let available = pair.slave.input_buffer.peek(&mut temp_buf);
+    temp_buf[..available].contains(&b'\n')
+}
+
 /// Write to PTY slave (writes to master output buffer)
 ///
 /// Returns the number of bytes written.
@@ -870,12 +942,14 @@ pub fn write_slave(number: PtyNumber, data: &[u8]) -> usize {
             }
         }
 
+        drop(table);
         if bytes_written > 0 {
             crate::serial_println!(
                 "[PTY] Slave wrote {} bytes to PTY {}",
                 bytes_written,
                 number
             );
+            wake_pollers(number);
         }
         bytes_written
     } else {
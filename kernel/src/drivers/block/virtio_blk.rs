@@ -1,6 +1,7 @@
 // virtio-blk block device driver
 
 use crate::drivers::{Device, Driver, DriverError};
+use crate::sync::rtmutex::RtMutex;
 use crate::sync::SpinLock;
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
@@ -113,14 +114,23 @@ struct VirtqUsed {
     avail_event: u16, // Only if VIRTIO_F_EVENT_IDX
 }
 
+/// Frames backing one virtqueue: descriptor table and available ring in the
+/// first, used ring (page aligned, as legacy devices require) in the second
+const VIRTQUEUE_FRAMES: usize = 2;
+
 /// Complete virtqueue structure
+///
+/// The rings live in DMA32 frames so that devices limited to 32-bit DMA
+/// addresses can reach them; they are accessed through the HHDM.
 struct Virtqueue {
     /// Descriptor table
-    desc: Box<[VirtqDesc; QUEUE_SIZE as usize]>,
+    desc: &'static mut [VirtqDesc; QUEUE_SIZE as usize],
     /// Available ring
-    avail: Box<VirtqAvail>,
+    avail: &'static mut VirtqAvail,
     /// Used ring
-    used: Box<VirtqUsed>,
+    used: &'static mut VirtqUsed,
+    /// Physical address of the first backing frame
+    ring_paddr: u64,
     /// Free descriptor list
     free_desc: Vec<u16>,
     /// Last seen used index
@@ -130,16 +140,17 @@ struct Virtqueue {
 }
 
 impl Virtqueue {
+    /// Offset of the available ring within the first frame
+    const AVAIL_OFFSET: usize = mem::size_of::<[VirtqDesc; QUEUE_SIZE as usize]>();
+    /// Offset of the used ring from the first frame
+    const USED_OFFSET: usize = crate::mm::pmm::FRAME_SIZE;
+
     /// Create a new virtqueue
-    fn new() -> Self {
-        let desc = Box::new(
-            [VirtqDesc {
-                addr: 0,
-                len: 0,
-                flags: 0,
-                next: 0,
-            }; QUEUE_SIZE as usize],
-        );
+    ///
+    /// Returns None if no DMA32 memory is available for the rings.
+    fn new() -> Option<Self> {
+        let ring_paddr = crate::mm::pmm::alloc_dma32_frames(VIRTQUEUE_FRAMES)?;
+        let ring_vaddr = crate::mm::phys_to_virt(ring_paddr);
 
         // Initialize descriptor free list
         let mut free_desc = Vec::with_capacity(QUEUE_SIZE as usize);
@@ -147,28 +158,26 @@ impl Virtqueue {
             free_desc.push(i);
         }
 
-        let avail = Box::new(VirtqAvail {
-            flags: 0,
-            idx: 0,
-            ring: [0; QUEUE_SIZE as usize],
-            used_event: 0,
-        });
-
-        let used = Box::new(VirtqUsed {
-            flags: 0,
-            idx: 0,
-            ring: [VirtqUsedElem { id: 0, len: 0 }; QUEUE_SIZE as usize],
-            avail_event: 0,
-        });
+        // Safety: the frames are exclusively owned by this queue until Drop,
+        // `alloc_dma32_frames` zeroes them and zeroed memory is a valid value for every ring type, and the three
+        // regions do not overlap
+        let (desc, avail, used) = unsafe {
+            (
+                &mut *(ring_vaddr as *mut [VirtqDesc; QUEUE_SIZE as usize]),
+                &mut *((ring_vaddr + Self::AVAIL_OFFSET) as *mut VirtqAvail),
+                &mut *((ring_vaddr + Self::USED_OFFSET) as *mut VirtqUsed),
+            )
+        };
 
-        Virtqueue {
+        Some(Virtqueue {
             desc,
             avail,
             used,
+            ring_paddr: ring_paddr as u64,
             free_desc,
             last_used_idx: 0,
             queue_size: QUEUE_SIZE,
-        }
+        })
     }
 
     /// Allocate a descriptor from the free list
@@ -185,17 +194,17 @@ impl Virtqueue {
 
     /// Get physical address of descriptor table
     fn desc_paddr(&self) -> u64 {
-        self.desc.as_ptr() as u64
+        self.ring_paddr
     }
 
     /// Get physical address of available ring
     fn avail_paddr(&self) -> u64 {
-        self.avail.as_ref() as *const VirtqAvail as u64
+        self.ring_paddr + Self::AVAIL_OFFSET as u64
     }
 
     /// Get physical address of used ring
     fn used_paddr(&self) -> u64 {
-        self.used.as_ref() as *const VirtqUsed as u64
+        self.ring_paddr + Self::USED_OFFSET as u64
     }
 
     /// Add a descriptor chain to the available ring
@@ -228,6 +237,12 @@ impl Virtqueue {
     }
 }
 
+impl Drop for Virtqueue {
+    fn drop(&mut self) {
+        crate::mm::pmm::free_dma32_frames(self.ring_paddr as usize, VIRTQUEUE_FRAMES);
+    }
+}
+
 /// virtio-blk device structure
 struct VirtioBlkDevice {
     base_addr: usize,
@@ -311,7 +326,7 @@ impl VirtioBlkDevice {
 
     /// Initialize the virtqueue
     fn init_virtqueue(&mut self) -> Result<(), DriverError> {
-        let vq = Virtqueue::new();
+        let vq = Virtqueue::new().ok_or(DriverError::ResourceUnavailable)?;
 
         // Configure virtqueue in device
         // Queue select (queue 0 for virtio-blk)
@@ -618,7 +633,11 @@ impl BlockDevice for VirtioBlkDevice {
 }
 
 /// Global virtio-blk device instance
-static VIRTIO_BLK: SpinLock<Option<VirtioBlkDevice>> = SpinLock::new(None);
+///
+/// Held for the whole of a polled request, so it is an `RtMutex`: a
+/// real-time task waiting for the disk blocks and boosts the task doing
+/// I/O rather than spinning behind it.
+static VIRTIO_BLK: RtMutex<Option<VirtioBlkDevice>> = RtMutex::new(None);
 
 /// Probe function for virtio-blk driver
 pub fn virtio_blk_probe(device: &Device) -> bool {
@@ -285,3 +285,8 @@ static BUFFER_CACHE: Once<BufferCache> = Once::new();
 pub fn get_buffer_cache() -> &'static BufferCache {
     BUFFER_CACHE.call_once(|| BufferCache::new())
 }
+
+/// Get the number of cached buffers, without setting up the cache
+pub fn cached_buffers() -> usize {
+    BUFFER_CACHE.get().map_or(0, |cache| cache.buffer_count())
+}
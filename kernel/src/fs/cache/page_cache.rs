@@ -202,6 +202,8 @@ pub struct FilePageCache {
     pages: [RwLock<PageCacheEntry>; MAX_PAGES_PER_FILE],
     /// Read-ahead window for this file
     readahead: ReadAheadWindow,
+    /// Number of cached pages
+    page_count: AtomicUsize,
     /// Number of dirty pages
     dirty_count: AtomicUsize,
     /// File size in bytes
@@ -217,6 +219,7 @@ impl FilePageCache {
             in_use: AtomicBool::new(false),
             pages: [INIT_ENTRY; MAX_PAGES_PER_FILE],
             readahead: ReadAheadWindow::new(),
+            page_count: AtomicUsize::new(0),
             dirty_count: AtomicUsize::new(0),
             file_size: AtomicU64::new(0),
         }
@@ -226,6 +229,7 @@ impl FilePageCache {
     pub fn init(&self, inode: u64) {
         self.inode.store(inode, Ordering::Release);
         self.in_use.store(true, Ordering::Release);
+        self.page_count.store(0, Ordering::Release);
         self.dirty_count.store(0, Ordering::Release);
         self.file_size.store(0, Ordering::Release);
         self.readahead.reset();
@@ -272,6 +276,7 @@ impl FilePageCache {
             let mut page = page_lock.write();
             if !page.is_valid() {
                 page.init(page_num, data, timestamp);
+                self.page_count.fetch_add(1, Ordering::Relaxed);
                 return Some(idx);
             }
         }
@@ -342,6 +347,11 @@ impl FilePageCache {
         self.dirty_count.load(Ordering::Relaxed)
     }
 
+    /// Get number of cached pages
+    pub fn page_count(&self) -> usize {
+        self.page_count.load(Ordering::Relaxed)
+    }
+
     /// Get dirty pages in a range
     ///
     /// Returns a vector of (page_num, data) tuples for all dirty pages
@@ -413,6 +423,7 @@ impl FilePageCache {
                 page.invalidate();
             }
         }
+        self.page_count.store(0, Ordering::Relaxed);
         self.dirty_count.store(0, Ordering::Relaxed);
         self.in_use.store(false, Ordering::Release);
     }
@@ -482,6 +493,15 @@ impl PageCache {
     pub fn next_timestamp(&self) -> u64 {
         self.timestamp.fetch_add(1, Ordering::Relaxed)
     }
+
+    /// Get total number of cached pages across all files
+    pub fn page_count(&self) -> usize {
+        self.file_caches
+            .iter()
+            .filter(|cache| cache.is_in_use())
+            .map(|cache| cache.page_count())
+            .sum()
+    }
 }
 
 use spin::Once;
@@ -493,3 +513,8 @@ static PAGE_CACHE: Once<PageCache> = Once::new();
 pub fn get_page_cache() -> &'static PageCache {
     PAGE_CACHE.call_once(|| PageCache::new())
 }
+
+/// Get the number of cached pages, without setting up the cache
+pub fn cached_pages() -> usize {
+    PAGE_CACHE.get().map_or(0, |cache| cache.page_count())
+}
@@ -1,11 +1,13 @@
 //! Write-back coalescing and dirty page flushing
 //!
 //! This module implements:
-//! - Background flusher thread for writeback
+//! - Background flusher thread for writeback (a kernel thread, see `start`)
 //! - Batching of adjacent dirty pages (128-1024 KiB)
 //! - Deadline-based scheduling (default: 30 seconds)
 //! - Sync-triggered immediate flush
 
+use crate::sched::kthread::{self, KThread};
+use crate::sync::SpinLock;
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use spin::RwLock;
 
@@ -119,6 +121,8 @@ pub struct WritebackScheduler {
     last_flush: AtomicU64,
     /// Force immediate flush flag
     force_flush: AtomicBool,
+    /// Background flusher thread
+    flusher: SpinLock<Option<KThread>>,
 }
 
 impl WritebackScheduler {
@@ -130,20 +134,38 @@ impl WritebackScheduler {
             running: AtomicBool::new(false),
             last_flush: AtomicU64::new(0),
             force_flush: AtomicBool::new(false),
+            flusher: SpinLock::new(None),
         }
     }
 
-    /// Start the writeback scheduler
-    #[allow(dead_code)]
-    pub fn start(&self) {
-        self.running.store(true, Ordering::Release);
-        // TODO: Spawn background flusher thread when task scheduler supports it
+    /// Start the writeback scheduler and its background flusher thread
+    pub fn start(&'static self) {
+        if self.running.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        match kthread::kthread_create("writeback", move || flusher_thread(self)) {
+            Ok(thread) => *self.flusher.lock() = Some(thread),
+            Err(e) => {
+                crate::serial_println!("[WRITEBACK] Failed to start flusher thread: {:?}", e);
+                self.running.store(false, Ordering::Release);
+            }
+        }
     }
 
     /// Stop the writeback scheduler
+    ///
+    /// Waits for the flusher thread to finish its current pass.
     #[allow(dead_code)]
     pub fn stop(&self) {
         self.running.store(false, Ordering::Release);
+
+        let flusher = self.flusher.lock().take();
+        if let Some(flusher) = flusher {
+            if !flusher.is_current() {
+                flusher.stop();
+            }
+        }
     }
 
     /// Check if scheduler is running
@@ -156,6 +178,10 @@ impl WritebackScheduler {
     #[allow(dead_code)]
     pub fn trigger_flush(&self) {
         self.force_flush.store(true, Ordering::Release);
+
+        if let Some(flusher) = self.flusher.lock().as_ref() {
+            flusher.wake_up();
+        }
     }
 
     /// Check if immediate flush is requested
@@ -209,6 +235,30 @@ impl WritebackScheduler {
     }
 }
 
+/// Body of the background flusher thread
+///
+/// Wakes every `WRITEBACK_INTERVAL_TICKS` (or when `trigger_flush` is
+/// called) and writes out all dirty pages once the deadline has passed.
+fn flusher_thread(scheduler: &'static WritebackScheduler) -> i32 {
+    let interval_ns = crate::config::WRITEBACK_INTERVAL_TICKS * crate::sched::hrtimer::TICK_NS;
+
+    while !kthread::kthread_should_stop() {
+        let now = crate::sched::hrtimer::monotonic_ns() / 1_000_000;
+
+        if scheduler.should_flush_now() || scheduler.is_deadline_passed(now) {
+            scheduler.clear_flush_flag();
+            if let Err(e) = flush_all_pages() {
+                crate::serial_println!("[WRITEBACK] Background flush failed: {}", e);
+            }
+            scheduler.update_last_flush(now);
+        }
+
+        kthread::kthread_sleep_ns(interval_ns);
+    }
+
+    0
+}
+
 use spin::Once;
 
 /// Global writeback scheduler
@@ -11,9 +11,10 @@ use super::super_::MfsSuperblock;
 use super::txg::{TxgConfig, TxgManager};
 
 use crate::fs::block_dev::{BlockDevice, BlockError};
+use crate::sched::kthread::{self, KThread};
 use crate::sync::SpinLock;
 use alloc::string::String;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 
 /// MelloFS Disk filesystem type
@@ -44,6 +45,7 @@ impl MfsDiskType {
                 alloc
             }),
             txg_mgr: TxgManager::new(TxgConfig::default()),
+            txg_sync: SpinLock::new(None),
             block_device: None, // No block device in simple mount
         });
 
@@ -63,6 +65,8 @@ pub struct MfsDiskFs {
     allocator: SpinLock<SpaceAllocator>,
     /// Transaction group manager
     txg_mgr: TxgManager,
+    /// Background transaction group committer
+    txg_sync: SpinLock<Option<KThread>>,
     /// Block device (optional for now)
     block_device: Option<Arc<dyn BlockDevice>>,
 }
@@ -226,6 +230,58 @@ impl MfsDiskFs {
     }
 }
 
+impl MfsDiskFs {
+    /// Start the kernel thread that commits transaction groups
+    ///
+    /// The thread only holds a weak reference, so it winds down by itself
+    /// once the filesystem is dropped.
+    fn start_txg_sync(self: &Arc<Self>) {
+        let fs = Arc::downgrade(self);
+        match kthread::kthread_create("txg_sync", move || txg_sync_thread(fs)) {
+            Ok(thread) => *self.txg_sync.lock() = Some(thread),
+            Err(e) => {
+                crate::serial_println!("[MFS_DISK] Failed to start txg_sync thread: {:?}", e)
+            }
+        }
+    }
+}
+
+impl Drop for MfsDiskFs {
+    fn drop(&mut self) {
+        // The last reference may be the committer's own; it exits on its own
+        if let Some(thread) = self.txg_sync.lock().take() {
+            if !thread.is_current() {
+                thread.stop();
+            }
+        }
+    }
+}
+
+/// Body of the txg_sync thread
+///
+/// Checks the open transaction group every `max_age_ms` and commits it
+/// once it is old or large enough.
+fn txg_sync_thread(fs: Weak<MfsDiskFs>) -> i32 {
+    while !kthread::kthread_should_stop() {
+        let interval_ms = match fs.upgrade() {
+            Some(fs) => {
+                let now_ms = current_time_ns() / 1_000_000;
+                if fs.txg_mgr.should_commit(now_ms) {
+                    if let Err(e) = fs.sync() {
+                        crate::serial_println!("[MFS_DISK] Background txg commit failed: {}", e);
+                    }
+                }
+                fs.txg_mgr.config().max_age_ms
+            }
+            None => break,
+        };
+
+        kthread::kthread_sleep_ns(interval_ms * 1_000_000);
+    }
+
+    0
+}
+
 // Tests would go here but are omitted for kernel code
 
 impl MfsDiskType {
@@ -262,9 +318,12 @@ impl MfsDiskType {
                 alloc
             }),
             txg_mgr: TxgManager::new(TxgConfig::default()),
+            txg_sync: SpinLock::new(None),
             block_device: Some(device),
         });
 
+        fs.start_txg_sync();
+
         Ok(fs)
     }
 }
@@ -174,6 +174,11 @@ impl TxgManager {
         Ok(())
     }
 
+    /// Get the configuration
+    pub fn config(&self) -> TxgConfig {
+        self.config
+    }
+
     /// Check if current transaction group should be committed
     pub fn should_commit(&self, current_time: u64) -> bool {
         let txg = self.current_txg.lock();
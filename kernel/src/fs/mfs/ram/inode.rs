@@ -215,9 +215,30 @@ impl Inode for RamInode {
         })
     }
 
-    fn set_attr(&self, _attr: SetAttr) -> Result<(), FsError> {
-        // TODO: Implement attribute setting
-        Err(FsError::NotSupported)
+    fn set_attr(&self, attr: SetAttr) -> Result<(), FsError> {
+        if let Some(size) = attr.size {
+            self.truncate(size)?;
+        }
+
+        let mut data = self.data.lock();
+        if let Some(mode) = attr.mode {
+            // The file type never changes
+            data.mode = FileMode::new(data.mode.file_type() | mode.permissions());
+        }
+        if let Some(uid) = attr.uid {
+            data.uid = uid;
+        }
+        if let Some(gid) = attr.gid {
+            data.gid = gid;
+        }
+        if let Some(atime) = attr.atime {
+            data.atime = atime;
+        }
+        if let Some(mtime) = attr.mtime {
+            data.mtime = mtime;
+        }
+        data.ctime = Self::current_time();
+        Ok(())
     }
 
     fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
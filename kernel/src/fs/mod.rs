@@ -42,10 +42,11 @@ fn mount_root_filesystem() {
 
     // Mount as root with default options
     let opts = MountOpts::default();
+    let flags = opts.flags;
     match fs_type.mount(opts) {
         Ok(superblock) => {
             // Register the mount
-            match mount::register_mount("/", superblock, "mfs_ram") {
+            match mount::register_mount("/", superblock, "mfs_ram", flags) {
                 Ok(_) => {
                     serial_println!("[VFS] Successfully mounted mfs_ram as root filesystem");
                 }
@@ -16,6 +16,12 @@
 //! concurrent modifications and retry if necessary. This ensures consistent
 //! reads without blocking writers.
 
+use crate::fs::vfs::inode::{DirCookie, DirEnt, FileMode, Inode, SetAttr, Stat};
+use crate::fs::vfs::superblock::FsError;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
 /// Maximum command name length
 const MAX_COMM_LEN: usize = 16;
 
@@ -74,6 +80,10 @@ pub struct ProcInfo {
     pub tty_nr: usize,
     /// Foreground process group of controlling terminal
     pub tpgid: Option<usize>,
+    /// User ID
+    pub uid: u32,
+    /// Group ID
+    pub gid: u32,
     /// Process state
     pub state: ProcState,
     /// Command name (fixed size)
@@ -84,14 +94,38 @@ pub struct ProcInfo {
     pub cmdline: [[u8; MAX_ARG_LEN]; MAX_CMDLINE_ARGS],
     /// Number of command line arguments
     pub cmdline_count: usize,
+    /// Page faults served without reading a file
+    pub minflt: u64,
+    /// Minor faults of collected children
+    pub cminflt: u64,
+    /// Page faults that read from a file
+    pub majflt: u64,
+    /// Major faults of collected children
+    pub cmajflt: u64,
     /// User time (clock ticks)
     pub utime: u64,
     /// System time (clock ticks)
     pub stime: u64,
+    /// User time of collected children (clock ticks)
+    pub cutime: u64,
+    /// System time of collected children (clock ticks)
+    pub cstime: u64,
+    /// Number of threads in the process
+    pub num_threads: usize,
+    /// Time the process started, in clock ticks since boot
+    pub starttime: u64,
     /// Virtual memory size (bytes)
     pub vsize: usize,
     /// Resident set size (pages)
     pub rss: usize,
+    /// Times the process waited for something
+    pub nvcsw: u64,
+    /// Times the process was preempted or yielded
+    pub nivcsw: u64,
+    /// CPUs the process may run on
+    pub cpus_allowed: crate::sched::cpuset::CpuMask,
+    /// Capability sets
+    pub caps: crate::user::capability::CapSets,
 }
 
 impl ProcInfo {
@@ -104,15 +138,29 @@ impl ProcInfo {
             sid: pid,
             tty_nr: 0,
             tpgid: None,
+            uid: 0,
+            gid: 0,
             state: ProcState::Running,
             comm: [0u8; MAX_COMM_LEN],
             comm_len: 0,
             cmdline: [[0u8; MAX_ARG_LEN]; MAX_CMDLINE_ARGS],
             cmdline_count: 0,
+            minflt: 0,
+            cminflt: 0,
+            majflt: 0,
+            cmajflt: 0,
             utime: 0,
             stime: 0,
+            cutime: 0,
+            cstime: 0,
+            num_threads: 1,
+            starttime: 0,
             vsize: 0,
             rss: 0,
+            nvcsw: 0,
+            nivcsw: 0,
+            cpus_allowed: crate::sched::cpuset::CpuMask::empty(),
+            caps: crate::user::capability::CapSets::empty(),
         }
     }
 
@@ -144,7 +192,9 @@ impl ProcInfo {
 
     /// Format as /proc/<pid>/stat content
     ///
-    /// Format: pid (comm) state ppid pgrp session tty_nr tpgid flags ...
+    /// Format: pid (comm) state ppid pgrp session tty_nr tpgid flags minflt
+    /// cminflt majflt cmajflt utime stime cutime cstime priority nice
+    /// num_threads itrealvalue starttime vsize rss ...
     /// Returns the number of bytes written to the buffer
     pub fn format_stat(&self, buf: &mut [u8]) -> usize {
         use core::fmt::Write;
@@ -168,7 +218,7 @@ impl ProcInfo {
         let mut writer = BufWriter { buf, pos: 0 };
         let _ = write!(
             writer,
-            "{} ({}) {} {} {} {} {} {} 0 0 0 0 0 {} {} 0 0 0 0 0 0 0 {} {} 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n",
+            "{} ({}) {} {} {} {} {} {} 0 {} {} {} {} {} {} {} {} 0 0 {} 0 {} {} {} 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n",
             self.pid,
             self.get_comm(),
             self.state.to_char(),
@@ -177,8 +227,16 @@ impl ProcInfo {
             self.sid,
             self.tty_nr,
             self.tpgid.unwrap_or(0),
+            self.minflt,
+            self.cminflt,
+            self.majflt,
+            self.cmajflt,
             self.utime,
             self.stime,
+            self.cutime,
+            self.cstime,
+            self.num_threads,
+            self.starttime,
             self.vsize,
             self.rss,
         );
@@ -217,8 +275,19 @@ impl ProcInfo {
              PPid:\t{}\n\
              Pgid:\t{}\n\
              Sid:\t{}\n\
+             Uid:\t{}\n\
+             Gid:\t{}\n\
+             CapInh:\t{:016x}\n\
+             CapPrm:\t{:016x}\n\
+             CapEff:\t{:016x}\n\
+             CapBnd:\t{:016x}\n\
              VmSize:\t{} kB\n\
-             VmRSS:\t{} kB\n",
+             VmRSS:\t{} kB\n\
+             Threads:\t{}\n\
+             Cpus_allowed:\t{:x}\n\
+             Cpus_allowed_list:\t{}\n\
+             voluntary_ctxt_switches:\t{}\n\
+             nonvoluntary_ctxt_switches:\t{}\n",
             self.get_comm(),
             self.state.to_char(),
             match self.state {
@@ -231,8 +300,19 @@ impl ProcInfo {
             self.ppid,
             self.pgid,
             self.sid,
+            self.uid,
+            self.gid,
+            self.caps.inheritable.bits(),
+            self.caps.permitted.bits(),
+            self.caps.effective.bits(),
+            self.caps.bounding.bits(),
             self.vsize / 1024,
             self.rss * 4, // Assuming 4KB pages
+            self.num_threads,
+            self.cpus_allowed.bits(),
+            self.cpus_allowed,
+            self.nvcsw,
+            self.nivcsw,
         );
         writer.pos
     }
@@ -424,10 +504,10 @@ impl CpuInfo {
 /// System uptime information for /proc/uptime
 #[derive(Debug, Clone, Copy)]
 pub struct Uptime {
-    /// System uptime in seconds
-    pub uptime_secs: u64,
-    /// Idle time in seconds (sum across all CPUs)
-    pub idle_secs: u64,
+    /// System uptime in hundredths of a second
+    pub uptime_centisecs: u64,
+    /// Idle time in hundredths of a second (sum across all CPUs)
+    pub idle_centisecs: u64,
 }
 
 impl Uptime {
@@ -455,7 +535,14 @@ impl Uptime {
         }
 
         let mut writer = BufWriter { buf, pos: 0 };
-        let _ = write!(writer, "{}.00 {}.00\n", self.uptime_secs, self.idle_secs);
+        let _ = write!(
+            writer,
+            "{}.{:02} {}.{:02}\n",
+            self.uptime_centisecs / 100,
+            self.uptime_centisecs % 100,
+            self.idle_centisecs / 100,
+            self.idle_centisecs % 100,
+        );
         writer.pos
     }
 }
@@ -483,6 +570,12 @@ pub enum ProcPath {
     Uptime,
     /// /proc/stat file (system-wide statistics)
     Stat,
+    /// /proc/kthreads file (kernel threads)
+    KThreads,
+    /// /proc/sys/kernel/pid_max file
+    SysPidMax,
+    /// /proc/sys/kernel/core_pattern file
+    SysCorePattern,
     /// /proc/debug directory
     DebugDir,
     /// /proc/debug/pty file
@@ -491,6 +584,8 @@ pub enum ProcPath {
     DebugSessions,
     /// /proc/debug/locks file
     DebugLocks,
+    /// /proc/debug/kmemleak file
+    DebugKmemleak,
     /// Unknown/invalid path
     Invalid,
 }
@@ -531,6 +626,13 @@ pub fn parse_proc_path(path: &str) -> ProcPath {
                 "pty" => ProcPath::DebugPty,
                 "sessions" => ProcPath::DebugSessions,
                 "locks" => ProcPath::DebugLocks,
+                "kmemleak" => ProcPath::DebugKmemleak,
+                _ => ProcPath::Invalid,
+            }
+        } else if first == "sys" {
+            match second {
+                "kernel/pid_max" => ProcPath::SysPidMax,
+                "kernel/core_pattern" => ProcPath::SysCorePattern,
                 _ => ProcPath::Invalid,
             }
         } else if let Ok(pid) = first.parse::<usize>() {
@@ -551,6 +653,7 @@ pub fn parse_proc_path(path: &str) -> ProcPath {
             "cpuinfo" => ProcPath::CpuInfo,
             "uptime" => ProcPath::Uptime,
             "stat" => ProcPath::Stat,
+            "kthreads" => ProcPath::KThreads,
             "debug" => ProcPath::DebugDir,
             pid_str => {
                 // Try to parse as PID
@@ -587,6 +690,9 @@ pub fn proc_read(path: &str, buf: &mut [u8], offset: usize) -> Result<usize, i32
         ProcPath::CpuInfo => read_cpuinfo(buf, offset),
         ProcPath::Uptime => read_uptime(buf, offset),
         ProcPath::Stat => read_stat(buf, offset),
+        ProcPath::KThreads => read_kthreads(buf, offset),
+        ProcPath::SysPidMax => read_sys_pid_max(buf, offset),
+        ProcPath::SysCorePattern => read_sys_core_pattern(buf, offset),
         ProcPath::Self_ => {
             // /proc/self should be handled as a symlink by the caller
             Err(-22) // EINVAL
@@ -594,10 +700,207 @@ pub fn proc_read(path: &str, buf: &mut [u8], offset: usize) -> Result<usize, i32
         ProcPath::DebugPty => read_debug_pty(buf, offset),
         ProcPath::DebugSessions => read_debug_sessions(buf, offset),
         ProcPath::DebugLocks => read_debug_locks(buf, offset),
+        ProcPath::DebugKmemleak => read_debug_kmemleak(buf, offset),
         _ => Err(-2), // ENOENT
     }
 }
 
+/// Write data to a /proc file
+///
+/// Only the tunables under /proc/sys can be written, and only by a task
+/// with CAP_SYS_ADMIN. Each write replaces the whole value.
+///
+/// # Arguments
+/// * `path` - The /proc path to write
+/// * `data` - The new value; one trailing newline is ignored
+///
+/// # Returns
+/// The number of bytes consumed, or an error code
+pub fn proc_write(path: &str, data: &[u8]) -> Result<usize, i32> {
+    use crate::user::capability::{capable, Capabilities};
+
+    let proc_path = parse_proc_path(path);
+    match proc_path {
+        ProcPath::SysPidMax | ProcPath::SysCorePattern => {}
+        ProcPath::Invalid => return Err(-2), // ENOENT
+        _ => return Err(-13),                // EACCES
+    }
+    if !capable(Capabilities::CAP_SYS_ADMIN) {
+        return Err(-1); // EPERM
+    }
+    let value = data.strip_suffix(b"\n").unwrap_or(data);
+    let value = core::str::from_utf8(value).map_err(|_| -22)?; // EINVAL
+
+    if proc_path == ProcPath::SysPidMax {
+        let pid_max = value.trim().parse::<usize>().map_err(|_| -22)?; // EINVAL
+        if !crate::sched::pid::set_pid_max(pid_max) {
+            return Err(-22); // EINVAL: outside PID_MAX_MIN..=PID_MAX_LIMIT
+        }
+    } else {
+        if value.len() >= crate::user::coredump::CORE_PATTERN_MAX {
+            return Err(-22); // EINVAL
+        }
+        crate::user::coredump::set_core_pattern(value);
+    }
+    Ok(data.len())
+}
+
+/// Open a /proc file
+///
+/// /proc is not mounted in the VFS: `sys_open` hands paths under it here,
+/// and reads and writes on the returned inode go to `proc_read` and
+/// `proc_write`, so the content is generated afresh on every read.
+///
+/// # Returns
+/// The file's inode, or an error code if it does not exist or is a
+/// directory
+pub fn open(path: &str) -> Result<Arc<dyn Inode>, i32> {
+    // Generating nothing checks that the file (and its process) exists
+    match proc_read(path, &mut [], 0) {
+        Ok(_) => Ok(Arc::new(ProcInode {
+            path: String::from(path),
+            writable: matches!(
+                parse_proc_path(path),
+                ProcPath::SysPidMax | ProcPath::SysCorePattern
+            ),
+        })),
+        Err(-3) => Err(-2), // ENOENT: the process is gone
+        Err(err) => Err(err),
+    }
+}
+
+/// An open /proc file
+struct ProcInode {
+    path: String,
+    /// A tunable under /proc/sys
+    writable: bool,
+}
+
+impl ProcInode {
+    /// Map a /proc error code to a VFS error
+    fn error(code: i32) -> FsError {
+        match code {
+            -1 => FsError::NotPermitted,
+            -2 | -3 => FsError::NotFound,
+            -13 => FsError::PermissionDenied,
+            -22 => FsError::InvalidArgument,
+            _ => FsError::IoError,
+        }
+    }
+}
+
+impl Inode for ProcInode {
+    fn ino(&self) -> u64 {
+        0
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+
+    fn mode(&self) -> FileMode {
+        let permissions = if self.writable { 0o644 } else { 0o444 };
+        FileMode::new(FileMode::S_IFREG | permissions)
+    }
+
+    fn nlink(&self) -> u32 {
+        1
+    }
+
+    fn uid_gid(&self) -> (u32, u32) {
+        (0, 0)
+    }
+
+    fn size(&self) -> u64 {
+        // Generated on read, as on Linux
+        0
+    }
+
+    fn stat(&self) -> Result<Stat, FsError> {
+        Ok(Stat {
+            st_dev: 0,
+            st_ino: self.ino(),
+            st_mode: self.mode().0 as u32,
+            st_nlink: 1,
+            st_uid: 0,
+            st_gid: 0,
+            st_rdev: 0,
+            st_size: 0,
+            st_blksize: 4096,
+            st_blocks: 0,
+            st_atime_sec: 0,
+            st_atime_nsec: 0,
+            st_mtime_sec: 0,
+            st_mtime_nsec: 0,
+            st_ctime_sec: 0,
+            st_ctime_nsec: 0,
+        })
+    }
+
+    fn set_attr(&self, _attr: SetAttr) -> Result<(), FsError> {
+        Err(FsError::NotPermitted)
+    }
+
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    fn create(
+        &self,
+        _name: &str,
+        _mode: FileMode,
+        _uid: u32,
+        _gid: u32,
+    ) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    fn link(&self, _name: &str, _target: Arc<dyn Inode>) -> Result<(), FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    fn readdir(&self, _cookie: &mut DirCookie, _entries: &mut Vec<DirEnt>) -> Result<(), FsError> {
+        Err(FsError::NotADirectory)
+    }
+
+    fn read_at(&self, off: u64, dst: &mut [u8]) -> Result<usize, FsError> {
+        proc_read(&self.path, dst, off as usize).map_err(Self::error)
+    }
+
+    fn write_at(&self, _off: u64, src: &[u8]) -> Result<usize, FsError> {
+        proc_write(&self.path, src).map_err(Self::error)
+    }
+
+    fn truncate(&self, _new_size: u64) -> Result<(), FsError> {
+        // Writes replace the whole value, so O_TRUNC has nothing to do
+        Ok(())
+    }
+
+    fn readlink(&self) -> Result<String, FsError> {
+        Err(FsError::InvalidArgument)
+    }
+
+    fn set_xattr(&self, _name: &str, _value: &[u8]) -> Result<(), FsError> {
+        Err(FsError::NotSupported)
+    }
+
+    fn get_xattr(&self, _name: &str) -> Result<Vec<u8>, FsError> {
+        Err(FsError::NotSupported)
+    }
+
+    fn list_xattr(&self) -> Result<Vec<String>, FsError> {
+        Err(FsError::NotSupported)
+    }
+}
+
 /// Read /proc/<pid>/stat file
 fn read_pid_stat(pid: usize, buf: &mut [u8], offset: usize) -> Result<usize, i32> {
     // Get process info from scheduler
@@ -670,6 +973,7 @@ fn read_uptime(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
 /// Read /proc/stat file (system-wide statistics)
 fn read_stat(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
     use crate::metrics;
+    use crate::sched::cputime::{self, CpuState};
     use core::fmt::Write;
 
     struct BufWriter<'a> {
@@ -696,18 +1000,49 @@ fn read_stat(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
 
     let m = metrics::metrics();
 
-    // CPU statistics (simplified - per-CPU stats would go here)
+    // CPU time in clock ticks: user nice system idle iowait irq softirq
+    // steal guest guest_nice (iowait and the virtualization columns are
+    // not tracked), summed over all CPUs and then per CPU
     let cpu_count = crate::arch::x86_64::smp::get_cpu_count();
-    let _ = write!(writer, "cpu  0 0 0 0 0 0 0 0 0 0\n");
+    let cpu_times = |cpus: core::ops::Range<usize>| {
+        let sum = |state| {
+            let ns: u64 = cpus
+                .clone()
+                .map(|cpu_id| cputime::cpu_time(cpu_id, state))
+                .sum();
+            clock_ticks(ns)
+        };
+        [
+            sum(CpuState::User),
+            sum(CpuState::Nice),
+            sum(CpuState::System),
+            sum(CpuState::Idle),
+            sum(CpuState::Irq),
+            sum(CpuState::Softirq),
+        ]
+    };
+    let [user, nice, system, idle, irq, softirq] = cpu_times(0..cpu_count);
+    let _ = write!(
+        writer,
+        "cpu  {} {} {} {} 0 {} {} 0 0 0\n",
+        user, nice, system, idle, irq, softirq
+    );
     for cpu_id in 0..cpu_count {
-        let _ = write!(writer, "cpu{}  0 0 0 0 0 0 0 0 0 0\n", cpu_id);
+        let [user, nice, system, idle, irq, softirq] = cpu_times(cpu_id..cpu_id + 1);
+        let _ = write!(
+            writer,
+            "cpu{} {} {} {} {} 0 {} {} 0 0 0\n",
+            cpu_id, user, nice, system, idle, irq, softirq
+        );
     }
 
     // Context switches
     let _ = write!(writer, "ctxt {}\n", m.get_context_switches());
 
-    // Boot time (placeholder)
-    let _ = write!(writer, "btime 0\n");
+    // Boot time (seconds since the epoch)
+    let boot_ns =
+        crate::sched::hrtimer::realtime_ns().saturating_sub(crate::sched::hrtimer::monotonic_ns());
+    let _ = write!(writer, "btime {}\n", boot_ns / 1_000_000_000);
 
     // Processes (placeholder - would need to count tasks)
     let _ = write!(writer, "processes 0\n");
@@ -747,10 +1082,66 @@ fn read_stat(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
         }
     }
 
+    // Syscalls refused by syscall filters, by number
+    let _ = write!(
+        writer,
+        "syscalls_denied {}\n",
+        m.get_total_syscalls_denied()
+    );
+    for i in 0..metrics::MAX_SYSCALLS {
+        let count = m.get_syscall_denied(i);
+        if count > 0 {
+            let _ = write!(writer, "  syscall_{}: {}\n", i, count);
+        }
+    }
+
     let len = writer.pos;
     copy_with_offset(&temp_buf[..len], buf, offset)
 }
 
+/// Read /proc/kthreads file
+fn read_kthreads(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
+    use core::fmt::Write;
+
+    struct BufWriter<'a> {
+        buf: &'a mut [u8],
+        pos: usize,
+    }
+
+    impl<'a> Write for BufWriter<'a> {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            let bytes = s.as_bytes();
+            let remaining = self.buf.len() - self.pos;
+            let to_write = bytes.len().min(remaining);
+            self.buf[self.pos..self.pos + to_write].copy_from_slice(&bytes[..to_write]);
+            self.pos += to_write;
+            Ok(())
+        }
+    }
+
+    let mut temp_buf = [0u8; 4096];
+    let mut writer = BufWriter {
+        buf: &mut temp_buf,
+        pos: 0,
+    };
+    let _ = crate::sched::kthread::write_list(&mut writer);
+
+    let len = writer.pos;
+    copy_with_offset(&temp_buf[..len], buf, offset)
+}
+
+/// Read /proc/sys/kernel/pid_max file (upper bound for task IDs)
+fn read_sys_pid_max(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
+    let content = alloc::format!("{}\n", crate::sched::pid::pid_max());
+    copy_with_offset(content.as_bytes(), buf, offset)
+}
+
+/// Read /proc/sys/kernel/core_pattern file (where core dumps go)
+fn read_sys_core_pattern(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
+    let content = alloc::format!("{}\n", crate::user::coredump::core_pattern());
+    copy_with_offset(content.as_bytes(), buf, offset)
+}
+
 /// Read /proc/debug/pty file
 fn read_debug_pty(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
     use crate::metrics;
@@ -838,12 +1229,10 @@ fn read_debug_sessions(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
     );
 
     // Iterate through all tasks and display session info
-    // Count tasks by iterating through the task table
     let mut task_count = 0;
-    const MAX_TASKS: usize = 1024;
 
-    for task_id in 1..MAX_TASKS {
-        if let Some(task) = sched::get_task_by_id(task_id) {
+    sched::for_each_task(|task| {
+        if task.id != 0 {
             task_count += 1;
 
             let tty_str = if let Some(tty) = task.tty {
@@ -857,6 +1246,7 @@ fn read_debug_sessions(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
                 crate::sched::task::TaskState::Ready => 'R',
                 crate::sched::task::TaskState::Sleeping => 'S',
                 crate::sched::task::TaskState::Blocked => 'D',
+                crate::sched::task::TaskState::Zombie => 'Z',
             };
 
             let _ = write!(
@@ -865,7 +1255,7 @@ fn read_debug_sessions(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
                 task.pid, task.ppid, task.pgid, task.sid, tty_str, state_char, task.name
             );
         }
-    }
+    });
 
     let _ = write!(writer, "\nTotal processes: {}\n", task_count);
 
@@ -924,6 +1314,44 @@ fn read_debug_locks(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
     copy_with_offset(&temp_buf[..len], buf, offset)
 }
 
+/// Read /proc/debug/kmemleak file
+///
+/// The report can be longer than any fixed temporary buffer, so it is
+/// formatted straight into `buf`, skipping the first `offset` bytes.
+fn read_debug_kmemleak(buf: &mut [u8], offset: usize) -> Result<usize, i32> {
+    use core::fmt::Write;
+
+    struct OffsetWriter<'a> {
+        buf: &'a mut [u8],
+        skip: usize,
+        pos: usize,
+    }
+
+    impl<'a> Write for OffsetWriter<'a> {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            let mut bytes = s.as_bytes();
+            let skipped = bytes.len().min(self.skip);
+            self.skip -= skipped;
+            bytes = &bytes[skipped..];
+
+            let remaining = self.buf.len() - self.pos;
+            let to_write = bytes.len().min(remaining);
+            self.buf[self.pos..self.pos + to_write].copy_from_slice(&bytes[..to_write]);
+            self.pos += to_write;
+            Ok(())
+        }
+    }
+
+    let mut writer = OffsetWriter {
+        buf,
+        skip: offset,
+        pos: 0,
+    };
+    let _ = crate::mm::kmemleak::write_report(&mut writer);
+
+    Ok(writer.pos)
+}
+
 /// Helper function to copy data with offset
 ///
 /// # Arguments
@@ -961,9 +1389,14 @@ fn get_proc_info(pid: usize) -> Option<ProcInfo> {
     proc_info.sid = task.sid;
     proc_info.tty_nr = task.tty.unwrap_or(0);
     proc_info.tpgid = None; // TODO: Get from TTY when available
+    proc_info.uid = task.creds.uid;
+    proc_info.gid = task.creds.gid;
+    proc_info.caps = task.creds.caps;
 
     // Set state based on task state
     proc_info.state = match task.state {
+        crate::sched::task::TaskState::Zombie => ProcState::Zombie,
+        _ if task.stopped => ProcState::Stopped,
         crate::sched::task::TaskState::Running => ProcState::Running,
         crate::sched::task::TaskState::Ready => ProcState::Running,
         crate::sched::task::TaskState::Sleeping => ProcState::Sleeping,
@@ -977,17 +1410,45 @@ fn get_proc_info(pid: usize) -> Option<ProcInfo> {
     // For now, just use the task name
     proc_info.add_cmdline_arg(task.name);
 
-    // TODO: Get actual timing and memory info
-    proc_info.utime = 0;
-    proc_info.stime = 0;
-    proc_info.vsize = task.total_memory_usage();
-    proc_info.rss = task.total_memory_usage() / 4096; // Convert to pages
+    // A process counts the time and faults of all its threads
+    let usage = crate::user::process::thread_group_usage(task);
+    proc_info.minflt = usage.minflt;
+    proc_info.majflt = usage.majflt;
+    proc_info.utime = clock_ticks(usage.utime_ns);
+    proc_info.stime = clock_ticks(usage.stime_ns);
+    proc_info.nvcsw = usage.nvcsw;
+    proc_info.nivcsw = usage.nivcsw;
+    proc_info.cminflt = task.child_rusage.minflt;
+    proc_info.cmajflt = task.child_rusage.majflt;
+    proc_info.cutime = clock_ticks(task.child_rusage.utime_ns);
+    proc_info.cstime = clock_ticks(task.child_rusage.stime_ns);
+    proc_info.num_threads = task
+        .thread_group
+        .as_ref()
+        .map_or(1, |group| group.members().len().max(1));
+    proc_info.starttime = clock_ticks(task.start_ns);
+
+    proc_info.vsize = crate::mm::mmap::vm_size(task);
+    proc_info.rss = task.resident_memory_usage() / 4096; // Convert to pages
+    proc_info.cpus_allowed = crate::sched::task_cpus(task);
 
     Some(proc_info)
 }
 
+/// Convert nanoseconds to clock ticks (`SCHED_HZ` per second)
+fn clock_ticks(ns: u64) -> u64 {
+    ns / crate::sched::fair::TICK_NS
+}
+
 /// Get system memory information
 fn get_meminfo() -> MemInfo {
+    use crate::fs::cache::{buffer_cache, page_cache};
+
+    // The caches are static arrays, so they never hold memory the PMM
+    // could hand out: they are reported but not counted as available
+    let buffers = buffer_cache::cached_buffers() * buffer_cache::MAX_BUFFER_SIZE / 1024;
+    let cached = page_cache::cached_pages() * page_cache::CACHE_PAGE_SIZE / 1024;
+
     // Get memory statistics from memory manager
     let result = crate::mm::with_memory_managers(|pmm, _mapper| {
         let mem_total = pmm.total_memory_mb() * 1024; // Convert MB to kB
@@ -997,18 +1458,18 @@ fn get_meminfo() -> MemInfo {
             mem_total,
             mem_free,
             mem_available: mem_free, // Simplified for now
-            buffers: 0,              // TODO: Track buffer cache
-            cached: 0,               // TODO: Track page cache
+            buffers,
+            cached,
         })
     });
 
-    // If memory manager not initialized, return zeros
+    // If memory manager not initialized, report only the caches
     result.unwrap_or(MemInfo {
         mem_total: 0,
         mem_free: 0,
         mem_available: 0,
-        buffers: 0,
-        cached: 0,
+        buffers,
+        cached,
     })
 }
 
@@ -1061,20 +1522,19 @@ fn get_cpuinfo(cpu_id: usize) -> CpuInfo {
 
 /// Get system uptime
 fn get_uptime() -> Uptime {
-    use crate::sched::timer;
+    use crate::sched::cputime::{cpu_time, CpuState};
 
-    // Get current tick count
-    let ticks = timer::get_tick_count();
+    const NS_PER_CENTISEC: u64 = 10_000_000;
 
-    // Convert ticks to seconds (assuming 100 Hz timer)
-    let uptime_secs = (ticks / 100) as u64;
-
-    // TODO: Calculate actual idle time
-    let idle_secs = 0;
+    let uptime_ns = crate::sched::hrtimer::monotonic_ns();
+    let cpu_count = crate::arch::x86_64::smp::get_cpu_count();
+    let idle_ns: u64 = (0..cpu_count)
+        .map(|cpu_id| cpu_time(cpu_id, CpuState::Idle))
+        .sum();
 
     Uptime {
-        uptime_secs,
-        idle_secs,
+        uptime_centisecs: uptime_ns / NS_PER_CENTISEC,
+        idle_centisecs: idle_ns / NS_PER_CENTISEC,
     }
 }
 
@@ -1170,6 +1630,7 @@ impl ProcSnapshot {
             crate::sched::task::TaskState::Ready => ProcState::Running,
             crate::sched::task::TaskState::Sleeping => ProcState::Sleeping,
             crate::sched::task::TaskState::Blocked => ProcState::Sleeping,
+            crate::sched::task::TaskState::Zombie => ProcState::Zombie,
         };
 
         Self {
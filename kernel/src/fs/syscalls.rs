@@ -56,6 +56,7 @@
 //!
 //! All syscalls return standard POSIX error codes:
 //! - `ENOENT` (-2) - No such file or directory
+//! - `EPERM` (-1) - Operation not permitted
 //! - `EACCES` (-13) - Permission denied
 //! - `EEXIST` (-17) - File exists
 //! - `ENOTDIR` (-20) - Not a directory
@@ -81,11 +82,12 @@
 //!
 //! - All user pointers are validated before dereferencing
 //! - Path traversal attacks are prevented by VFS path resolution
-//! - Permission checks are enforced at the VFS inode level
+//! - Permission checks (`vfs::permission`) use the caller's effective IDs
 //! - Buffer overflows are prevented by length validation
 
 use crate::fs::vfs::inode::{DirCookie, DirEnt, FileMode, Inode, SetAttr, Stat};
 use crate::fs::vfs::path::{resolve_parent, resolve_path};
+use crate::fs::vfs::permission;
 use crate::fs::vfs::superblock::FsError;
 use crate::serial_println;
 use alloc::string::String;
@@ -255,6 +257,7 @@ fn map_vfs_error(error: FsError) -> i32 {
     match error {
         FsError::NotFound => -2,              // ENOENT
         FsError::PermissionDenied => -13,     // EACCES
+        FsError::NotPermitted => -1,          // EPERM
         FsError::AlreadyExists => -17,        // EEXIST
         FsError::NotADirectory => -20,        // ENOTDIR
         FsError::IsADirectory => -21,         // EISDIR
@@ -310,6 +313,8 @@ pub fn sys_open(path_ptr: usize, flags: u32, mode: u32) -> i32 {
     let nofollow = (flags & open_flags::O_NOFOLLOW) != 0;
     
     // Resolve path to inode
+    let creds = crate::user::creds::current();
+    let mut created = false;
     let inode = if create {
         // Try to resolve existing file first
         match resolve_path(&path, None) {
@@ -331,10 +336,17 @@ pub fn sys_open(path_ptr: usize, flags: u32, mode: u32) -> i32 {
                     }
                 };
                 
+                if let Err(e) = permission::may_create(&*parent_inode, &creds) {
+                    serial_println!("[FS] sys_open: cannot create in parent: {:?}", e);
+                    return map_vfs_error(e);
+                }
+                
                 let file_mode = FileMode::new((FileMode::S_IFREG | (mode as u16 & 0o7777)) as u16);
-                match parent_inode.create(&filename, file_mode, 0, 0) {
+                let (uid, gid) = permission::new_owner(&*parent_inode, &creds);
+                match parent_inode.create(&filename, file_mode, uid, gid) {
                     Ok(new_inode) => {
                         serial_println!("[FS] sys_open: created new file \"{}\"", filename);
+                        created = true;
                         new_inode
                     }
                     Err(e) => {
@@ -359,6 +371,14 @@ pub fn sys_open(path_ptr: usize, flags: u32, mode: u32) -> i32 {
         }
     };
     
+    // A file just created may be opened whatever its mode says
+    if !created {
+        if let Err(e) = permission::permission(&*inode, &creds, permission::open_mask(flags)) {
+            serial_println!("[FS] sys_open: permission denied");
+            return map_vfs_error(e);
+        }
+    }
+    
     // Check file type constraints
     let file_mode = inode.mode();
     if directory && !file_mode.is_dir() {
@@ -671,7 +691,8 @@ pub fn sys_mkdir(path_ptr: usize, mode: u32) -> i32 {
     
     // Create directory
     let dir_mode = FileMode::new((FileMode::S_IFDIR | (mode as u16 & 0o7777)) as u16);
-    match parent_inode.create(&dirname, dir_mode, 0, 0) {
+    let creds = crate::user::creds::current();
+    match permission::create(&*parent_inode, &creds, &dirname, dir_mode) {
         Ok(_) => {
             serial_println!("[FS] sys_mkdir: created directory \"{}\"", dirname);
             0
@@ -710,8 +731,9 @@ pub fn sys_unlink(path_ptr: usize) -> i32 {
         }
     };
     
-    // Remove file
-    match parent_inode.unlink(&filename) {
+    // Remove file, checking the parent and the sticky-directory rule
+    let creds = crate::user::creds::current();
+    match permission::unlink(&*parent_inode, &creds, &filename) {
         Ok(()) => {
             serial_println!("[FS] sys_unlink: removed file \"{}\"", filename);
             0
@@ -758,7 +780,8 @@ pub fn sys_symlink(target_ptr: usize, linkpath_ptr: usize) -> i32 {
     };
     
     // Create symbolic link
-    match parent_inode.symlink(&linkname, &target) {
+    let creds = crate::user::creds::current();
+    match permission::symlink(&*parent_inode, &creds, &linkname, &target) {
         Ok(_) => {
             serial_println!("[FS] sys_symlink: created symlink \"{}\" -> \"{}\"", linkname, target);
             0
@@ -11,6 +11,7 @@ pub mod file;
 pub mod inode;
 pub mod mount;
 pub mod path;
+pub mod permission;
 pub mod registry;
 pub mod superblock;
 
@@ -5,7 +5,7 @@
 //!
 //! Implementation uses static arrays to avoid heap allocation during early boot.
 
-use crate::fs::vfs::superblock::SuperBlock;
+use crate::fs::vfs::superblock::{MountFlags, SuperBlock};
 use alloc::string::String;
 use alloc::sync::Arc;
 use spin::Mutex as SpinLock;
@@ -22,6 +22,8 @@ pub struct MountPoint {
     pub superblock: Arc<dyn SuperBlock>,
     /// Filesystem type name (e.g., "mfs_ram", "mfs_disk")
     pub fs_type: String,
+    /// Flags the filesystem was mounted with (e.g. MS_NOSUID)
+    pub flags: MountFlags,
     /// Is this mount point active?
     pub active: bool,
 }
@@ -49,6 +51,7 @@ impl MountTable {
     /// * `path` - Mount path (must start with '/')
     /// * `sb` - Superblock of the filesystem
     /// * `fs_type` - Filesystem type name
+    /// * `flags` - Mount flags
     ///
     /// # Returns
     /// Ok(()) on success, Err if mount table is full or path already mounted
@@ -57,6 +60,7 @@ impl MountTable {
         path: String,
         sb: Arc<dyn SuperBlock>,
         fs_type: String,
+        flags: MountFlags,
     ) -> Result<(), &'static str> {
         // Validate path
         if !path.starts_with('/') {
@@ -77,6 +81,7 @@ impl MountTable {
                     path,
                     superblock: sb,
                     fs_type,
+                    flags,
                     active: true,
                 });
                 self.count += 1;
@@ -177,9 +182,10 @@ pub fn register_mount(
     path: &str,
     sb: Arc<dyn SuperBlock>,
     fs_type: &str,
+    flags: MountFlags,
 ) -> Result<(), &'static str> {
     let mut table = MOUNT_TABLE.lock();
-    table.register_mount(path.into(), sb, fs_type.into())
+    table.register_mount(path.into(), sb, fs_type.into(), flags)
 }
 
 /// Lookup a mount point by path (public API)
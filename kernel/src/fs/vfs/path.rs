@@ -9,6 +9,7 @@
 use crate::fs::vfs::dentry::Dentry;
 use crate::fs::vfs::inode::Inode;
 use crate::fs::vfs::mount;
+use crate::fs::vfs::permission;
 use crate::fs::vfs::superblock::FsError;
 use alloc::format;
 use alloc::string::String;
@@ -35,6 +36,7 @@ const MAX_PATH_LEN: usize = 4096;
 ///
 /// # Errors
 /// * ENOENT - Path component not found
+/// * EACCES - No search permission on a directory on the path
 /// * ENOTDIR - Path component is not a directory
 /// * ELOOP - Too many symlink hops
 /// * ENAMETOOLONG - Path or component too long
@@ -91,6 +93,7 @@ fn resolve_path_internal(
     }
 
     // Walk through path components
+    let creds = crate::user::creds::current();
     for (idx, component) in components.iter().enumerate() {
         // Validate component length
         if component.len() > MAX_NAME_LEN {
@@ -122,6 +125,9 @@ fn resolve_path_internal(
             return Err(FsError::NotADirectory);
         }
 
+        // Walking through a directory needs search permission
+        permission::permission(&*current, &creds, permission::MAY_EXEC)?;
+
         // Lookup component in current directory
         let next = current.lookup(component).map_err(|_| FsError::NotFound)?;
 
@@ -182,6 +188,7 @@ fn get_root_dentry() -> Result<Arc<Dentry>, FsError> {
 ///
 /// # Errors
 /// * ENOENT - Path component not found
+/// * EACCES - No search permission on a directory on the path
 /// * ENOTDIR - Path component is not a directory
 /// * ELOOP - Too many symlink hops
 /// * ENAMETOOLONG - Path or component too long
@@ -238,6 +245,7 @@ fn resolve_path_dentry_internal(
     }
 
     // Walk through path components
+    let creds = crate::user::creds::current();
     for (idx, component) in components.iter().enumerate() {
         // Validate component length
         if component.len() > MAX_NAME_LEN {
@@ -259,6 +267,9 @@ fn resolve_path_dentry_internal(
             return Err(FsError::NotADirectory);
         }
 
+        // Walking through a directory needs search permission
+        permission::permission(&**current.inode(), &creds, permission::MAY_EXEC)?;
+
         // Lookup component in current directory
         let next_inode = current
             .inode()
@@ -0,0 +1,406 @@
+//! File Permission Checks
+//!
+//! Discretionary access control on inodes. The caller's effective UID
+//! picks the owner, group or other permission bits of a file: the owner
+//! bits if it owns the file, the group bits if the file's group is its
+//! effective or a supplementary group, and the other bits otherwise.
+//! `CAP_DAC_OVERRIDE` skips the checks, except that it only allows
+//! executing a file that has an execute bit set; `CAP_DAC_READ_SEARCH`
+//! skips them for reading files and searching directories. `CAP_FOWNER`,
+//! `CAP_FSETID` and `CAP_CHOWN` lift the owner rules of `chmod` and
+//! `chown`.
+//!
+//! Directories need search (execute) permission to be walked through, and
+//! write and search permission to add or remove entries. In a sticky
+//! directory (`S_ISVTX`, like `/tmp`) an entry may only be removed by the
+//! owner of the entry or of the directory.
+
+use crate::fs::vfs::file::FdFlags;
+use crate::fs::vfs::inode::{FileMode, Inode, SetAttr};
+use crate::fs::vfs::superblock::FsError;
+use crate::sched::task::Credentials;
+use crate::user::capability::Capabilities;
+use alloc::sync::Arc;
+
+/// Execute a file or search a directory
+pub const MAY_EXEC: u16 = 1;
+/// Write a file or change a directory's entries
+pub const MAY_WRITE: u16 = 2;
+/// Read a file or list a directory
+pub const MAY_READ: u16 = 4;
+
+/// Check `mask` against a file's mode and owner
+///
+/// # Arguments
+/// * `mode` - File mode
+/// * `owner` - File owner and group
+/// * `creds` - Caller's credentials
+/// * `mask` - `MAY_*` bits the caller needs
+///
+/// # Errors
+/// * PermissionDenied - A requested permission is missing (EACCES)
+pub fn check_mode(
+    mode: FileMode,
+    owner: (u32, u32),
+    creds: &Credentials,
+    mask: u16,
+) -> Result<(), FsError> {
+    if creds.capable(Capabilities::CAP_DAC_OVERRIDE) {
+        let executable = mode.is_dir()
+            || mode.0 & (FileMode::S_IXUSR | FileMode::S_IXGRP | FileMode::S_IXOTH) != 0;
+        if mask & MAY_EXEC == 0 || executable {
+            return Ok(());
+        }
+    }
+    if creds.capable(Capabilities::CAP_DAC_READ_SEARCH) {
+        let search = if mode.is_dir() { MAY_EXEC } else { 0 };
+        if mask & !(MAY_READ | search) == 0 {
+            return Ok(());
+        }
+    }
+
+    let bits = if creds.uid == owner.0 {
+        mode.0 >> 6
+    } else if creds.in_group(owner.1) {
+        mode.0 >> 3
+    } else {
+        mode.0
+    } & 0o7;
+
+    if bits & mask == mask {
+        Ok(())
+    } else {
+        Err(FsError::PermissionDenied)
+    }
+}
+
+/// Permissions needed to open a file with `flags` (by its O_ACCMODE bits)
+pub fn open_mask(flags: u32) -> u16 {
+    match flags & 0x3 {
+        FdFlags::O_RDONLY => MAY_READ,
+        FdFlags::O_WRONLY => MAY_WRITE,
+        _ => MAY_READ | MAY_WRITE,
+    }
+}
+
+/// Check `mask` against an inode
+pub fn permission(inode: &dyn Inode, creds: &Credentials, mask: u16) -> Result<(), FsError> {
+    check_mode(inode.mode(), inode.uid_gid(), creds, mask)
+}
+
+/// Check that the caller may add an entry to `dir`
+pub fn may_create(dir: &dyn Inode, creds: &Credentials) -> Result<(), FsError> {
+    permission(dir, creds, MAY_WRITE | MAY_EXEC)
+}
+
+/// Check that the caller may remove `victim` from `dir`
+///
+/// # Errors
+/// * PermissionDenied - No write and search permission on `dir` (EACCES)
+/// * NotPermitted - `dir` is sticky and the caller owns neither it nor
+///   `victim` (EPERM)
+pub fn may_delete(dir: &dyn Inode, victim: &dyn Inode, creds: &Credentials) -> Result<(), FsError> {
+    permission(dir, creds, MAY_WRITE | MAY_EXEC)?;
+    check_sticky(dir.mode(), dir.uid_gid().0, victim.uid_gid().0, creds)
+}
+
+/// Check the sticky-directory rule for removing an entry
+pub fn check_sticky(
+    dir_mode: FileMode,
+    dir_owner: u32,
+    victim_owner: u32,
+    creds: &Credentials,
+) -> Result<(), FsError> {
+    if dir_mode.0 & FileMode::S_ISVTX == 0
+        || creds.capable(Capabilities::CAP_FOWNER)
+        || creds.uid == dir_owner
+        || creds.uid == victim_owner
+    {
+        Ok(())
+    } else {
+        Err(FsError::NotPermitted)
+    }
+}
+
+/// Owner and group of a file the caller creates in `dir`
+///
+/// The file belongs to the caller's effective IDs, except that a file in a
+/// set-group-ID directory takes the directory's group.
+pub fn new_owner(dir: &dyn Inode, creds: &Credentials) -> (u32, u32) {
+    if dir.mode().0 & FileMode::S_ISGID != 0 {
+        (creds.uid, dir.uid_gid().1)
+    } else {
+        (creds.uid, creds.gid)
+    }
+}
+
+/// Mode a `chmod` to `perm` gives a file
+///
+/// Only the owner (or a caller with `CAP_FOWNER`) may change the mode.
+/// The set-group-ID bit is dropped if the caller is not in the file's
+/// group and lacks `CAP_FSETID`.
+///
+/// # Errors
+/// * NotPermitted - The caller does not own the file (EPERM)
+pub fn chmod_mode(
+    mode: FileMode,
+    owner: (u32, u32),
+    creds: &Credentials,
+    perm: u16,
+) -> Result<FileMode, FsError> {
+    if !creds.capable(Capabilities::CAP_FOWNER) && creds.uid != owner.0 {
+        return Err(FsError::NotPermitted);
+    }
+
+    let mut perm = perm & 0o7777;
+    if !creds.capable(Capabilities::CAP_FSETID) && !creds.in_group(owner.1) {
+        perm &= !FileMode::S_ISGID;
+    }
+    Ok(FileMode::new(mode.file_type() | perm))
+}
+
+/// Attributes a `chown` to `uid`/`gid` sets on a file (`None` keeps one)
+///
+/// `CAP_CHOWN` may give a file to anyone. The owner may only change its group,
+/// to one of the owner's groups. Changing the owner or group of a
+/// non-directory clears its set-user-ID bit, and its set-group-ID bit if
+/// it is group-executable.
+///
+/// # Errors
+/// * NotPermitted - The change is not allowed (EPERM)
+pub fn chown_attr(
+    mode: FileMode,
+    owner: (u32, u32),
+    creds: &Credentials,
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> Result<SetAttr, FsError> {
+    if !creds.capable(Capabilities::CAP_CHOWN) {
+        let keeps_owner = uid.map_or(true, |uid| uid == owner.0);
+        let own_group = gid.map_or(true, |gid| gid == owner.1 || creds.in_group(gid));
+        if creds.uid != owner.0 || !keeps_owner || !own_group {
+            return Err(FsError::NotPermitted);
+        }
+    }
+
+    let mut attr = SetAttr {
+        uid,
+        gid,
+        ..SetAttr::default()
+    };
+    if (uid.is_some() || gid.is_some()) && !mode.is_dir() {
+        let mut bits = mode.0 & !FileMode::S_ISUID;
+        if bits & FileMode::S_IXGRP != 0 {
+            bits &= !FileMode::S_ISGID;
+        }
+        if bits != mode.0 {
+            attr.mode = Some(FileMode::new(bits));
+        }
+    }
+    Ok(attr)
+}
+
+/// Change an inode's mode as the caller
+pub fn chmod(inode: &dyn Inode, creds: &Credentials, perm: u16) -> Result<(), FsError> {
+    let mode = chmod_mode(inode.mode(), inode.uid_gid(), creds, perm)?;
+    inode.set_attr(SetAttr {
+        mode: Some(mode),
+        ..SetAttr::default()
+    })
+}
+
+/// Change an inode's owner and group as the caller
+pub fn chown(
+    inode: &dyn Inode,
+    creds: &Credentials,
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> Result<(), FsError> {
+    let attr = chown_attr(inode.mode(), inode.uid_gid(), creds, uid, gid)?;
+    inode.set_attr(attr)
+}
+
+/// Create `name` in `dir` as the caller
+///
+/// The new inode is owned as `new_owner` says.
+pub fn create(
+    dir: &dyn Inode,
+    creds: &Credentials,
+    name: &str,
+    mode: FileMode,
+) -> Result<Arc<dyn Inode>, FsError> {
+    may_create(dir, creds)?;
+    let (uid, gid) = new_owner(dir, creds);
+    dir.create(name, mode, uid, gid)
+}
+
+/// Create a symbolic link `name` to `target` in `dir` as the caller
+pub fn symlink(
+    dir: &dyn Inode,
+    creds: &Credentials,
+    name: &str,
+    target: &str,
+) -> Result<Arc<dyn Inode>, FsError> {
+    may_create(dir, creds)?;
+    dir.symlink(name, target)
+}
+
+/// Remove the non-directory entry `name` from `dir` as the caller
+///
+/// # Errors
+/// * IsADirectory - `name` is a directory (EISDIR)
+/// * PermissionDenied, NotPermitted - See `may_delete`
+pub fn unlink(dir: &dyn Inode, creds: &Credentials, name: &str) -> Result<(), FsError> {
+    let victim = dir.lookup(name)?;
+    if victim.mode().is_dir() {
+        return Err(FsError::IsADirectory);
+    }
+    may_delete(dir, &*victim, creds)?;
+    dir.unlink(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: u32 = 1000;
+    const BOB: u32 = 1001;
+    const STAFF: u32 = 50;
+
+    fn file(perm: u16) -> FileMode {
+        FileMode::new(FileMode::S_IFREG | perm)
+    }
+
+    #[test]
+    fn test_check_mode_classes() {
+        let alice = Credentials::user(ALICE, ALICE);
+        let bob = Credentials::user(BOB, BOB);
+        let owner = (ALICE, STAFF);
+
+        assert!(check_mode(file(0o640), owner, &alice, MAY_READ | MAY_WRITE).is_ok());
+        assert!(check_mode(file(0o640), owner, &bob, MAY_READ).is_err());
+
+        // Group bits apply to supplementary groups too
+        let mut staff = bob;
+        staff.groups[0] = STAFF;
+        staff.ngroups = 1;
+        assert!(check_mode(file(0o640), owner, &staff, MAY_READ).is_ok());
+        assert!(check_mode(file(0o640), owner, &staff, MAY_WRITE).is_err());
+
+        // The owner class wins even when it grants less
+        assert!(check_mode(file(0o044), owner, &alice, MAY_READ).is_err());
+    }
+
+    #[test]
+    fn test_check_mode_root() {
+        let root = Credentials::user(0, 0);
+        assert!(check_mode(file(0o000), (ALICE, ALICE), &root, MAY_READ | MAY_WRITE).is_ok());
+        assert!(check_mode(file(0o644), (ALICE, ALICE), &root, MAY_EXEC).is_err());
+        assert!(check_mode(file(0o744), (ALICE, ALICE), &root, MAY_EXEC).is_ok());
+        let dir = FileMode::new(FileMode::S_IFDIR);
+        assert!(check_mode(dir, (ALICE, ALICE), &root, MAY_EXEC).is_ok());
+
+        // A backup service that may read everything, but not write
+        let mut backup = Credentials::user(BOB, BOB);
+        backup.caps.effective = Capabilities::CAP_DAC_READ_SEARCH;
+        assert!(check_mode(file(0o000), (ALICE, ALICE), &backup, MAY_READ).is_ok());
+        assert!(check_mode(dir, (ALICE, ALICE), &backup, MAY_READ | MAY_EXEC).is_ok());
+        assert!(check_mode(file(0o000), (ALICE, ALICE), &backup, MAY_WRITE).is_err());
+        assert!(check_mode(file(0o000), (ALICE, ALICE), &backup, MAY_EXEC).is_err());
+    }
+
+    #[test]
+    fn test_sticky() {
+        let tmp = FileMode::new(FileMode::S_IFDIR | 0o1777);
+        let alice = Credentials::user(ALICE, ALICE);
+        assert!(check_sticky(tmp, 0, ALICE, &alice).is_ok());
+        assert_eq!(
+            check_sticky(tmp, 0, BOB, &alice),
+            Err(FsError::NotPermitted)
+        );
+        assert!(check_sticky(tmp, 0, BOB, &Credentials::user(0, 0)).is_ok());
+
+        let plain = FileMode::new(FileMode::S_IFDIR | 0o777);
+        assert!(check_sticky(plain, 0, BOB, &alice).is_ok());
+    }
+
+    #[test]
+    fn test_chmod_chown() {
+        let alice = Credentials::user(ALICE, ALICE);
+        let owner = (ALICE, STAFF);
+        assert!(chmod_mode(file(0o644), owner, &Credentials::user(BOB, BOB), 0o777).is_err());
+        // Not in the file's group: setgid is dropped
+        let mode = chmod_mode(file(0o644), owner, &alice, 0o2755).unwrap();
+        assert_eq!(mode, file(0o755));
+
+        assert!(chown_attr(file(0o644), owner, &alice, Some(BOB), None).is_err());
+        assert!(chown_attr(file(0o644), owner, &alice, None, Some(STAFF)).is_ok());
+        assert!(chown_attr(file(0o644), owner, &alice, None, Some(BOB)).is_err());
+
+        let attr = chown_attr(
+            file(0o6755),
+            owner,
+            &Credentials::user(0, 0),
+            Some(BOB),
+            None,
+        );
+        assert_eq!(attr.unwrap().mode, Some(file(0o755)));
+    }
+
+    fn dir(perm: u16, owner: (u32, u32)) -> Arc<dyn Inode> {
+        let mode = FileMode::new(FileMode::S_IFDIR | perm);
+        crate::fs::mfs::ram::inode::RamInode::new_dir(1, mode, owner.0, owner.1).unwrap()
+    }
+
+    #[test]
+    fn test_unlink_in_sticky_dir() {
+        let tmp = dir(0o1777, (0, 0));
+        let alice = Credentials::user(ALICE, ALICE);
+        let bob = Credentials::user(BOB, BOB);
+        create(&*tmp, &alice, "a", file(0o644)).unwrap();
+        create(&*tmp, &bob, "b", file(0o644)).unwrap();
+
+        assert_eq!(unlink(&*tmp, &alice, "b"), Err(FsError::NotPermitted));
+        assert!(unlink(&*tmp, &alice, "a").is_ok());
+        assert!(tmp.lookup("a").is_err());
+        assert!(unlink(&*tmp, &Credentials::user(0, 0), "b").is_ok());
+    }
+
+    #[test]
+    fn test_unlink_needs_write_on_dir() {
+        let home = dir(0o755, (ALICE, ALICE));
+        let alice = Credentials::user(ALICE, ALICE);
+        create(&*home, &alice, "notes", file(0o666)).unwrap();
+        let subdir = FileMode::new(FileMode::S_IFDIR | 0o755);
+        create(&*home, &alice, "sub", subdir).unwrap();
+
+        let bob = Credentials::user(BOB, BOB);
+        assert_eq!(
+            unlink(&*home, &bob, "notes"),
+            Err(FsError::PermissionDenied)
+        );
+        assert_eq!(unlink(&*home, &alice, "sub"), Err(FsError::IsADirectory));
+        assert!(unlink(&*home, &alice, "notes").is_ok());
+    }
+
+    #[test]
+    fn test_create_checks_dir_and_sets_owner() {
+        let alice = Credentials::user(ALICE, ALICE);
+        let bob = Credentials::user(BOB, BOB);
+        let home = dir(0o755, (ALICE, ALICE));
+        assert_eq!(
+            create(&*home, &bob, "x", file(0o644)).err(),
+            Some(FsError::PermissionDenied)
+        );
+        assert_eq!(
+            symlink(&*home, &bob, "y", "/etc").err(),
+            Some(FsError::PermissionDenied)
+        );
+        assert!(create(&*home, &alice, "x", file(0o644)).is_ok());
+
+        // A set-group-ID directory hands its group to new entries
+        let shared = dir(0o2777, (ALICE, STAFF));
+        let made = create(&*shared, &bob, "report", file(0o644)).unwrap();
+        assert_eq!(made.uid_gid(), (BOB, STAFF));
+    }
+}
@@ -110,6 +110,8 @@ pub enum FsError {
     AlreadyExists,
     /// Permission denied
     PermissionDenied,
+    /// Operation not permitted (e.g. not the owner)
+    NotPermitted,
     /// Not a directory
     NotADirectory,
     /// Is a directory
@@ -2,7 +2,7 @@
 ///
 /// This module handles loading and spawning the userland init process.
 /// Phase 6.3 implementation uses ELF loading and user-mode execution.
-use crate::mm::paging::PageMapper;
+use crate::mm::paging::{alloc_page_table_with_kernel_mappings, PageMapper};
 use crate::mm::pmm::PhysicalMemoryManager;
 use crate::sched::{priority::TaskPriority, spawn_task, Task};
 use crate::serial_println;
@@ -61,6 +61,7 @@ pub fn load_init_process() -> Result<(), &'static str> {
                 "[INIT] Init process launcher scheduled (task_id={})",
                 task_id
             );
+            crate::user::process::set_child_reaper(task_id);
             Ok(())
         }
         Err(_e) => {
@@ -101,8 +102,9 @@ fn load_init_process_phase4() -> Result<(), &'static str> {
     serial_println!("[INIT] Init binary address: {:p}", INIT_BINARY.as_ptr());
 
     // Spawn the init task wrapper with Normal priority
-    spawn_task("init", init_task_wrapper, TaskPriority::Normal)
+    let task_id = spawn_task("init", init_task_wrapper, TaskPriority::Normal)
         .map_err(|_| "Failed to spawn init task")?;
+    crate::user::process::set_child_reaper(task_id);
 
     serial_println!("[INIT] Init process task spawned successfully");
 
@@ -119,7 +121,6 @@ fn load_init_process_phase4() -> Result<(), &'static str> {
 #[allow(dead_code)]
 pub fn load_init_process_elf(
     pmm: &mut PhysicalMemoryManager,
-    mapper: &mut PageMapper,
     task: &mut Task,
 ) -> Result<(u64, u64), ElfError> {
     serial_println!("[INIT] Loading init process using ELF loader...");
@@ -129,8 +130,16 @@ pub fn load_init_process_elf(
         return Err(ElfError::BufferTooSmall);
     }
 
+    // Init gets its own PML4; never load user segments into the kernel template
+    if !task.has_private_address_space() {
+        let pml4_phys =
+            alloc_page_table_with_kernel_mappings(pmm).map_err(|_| ElfError::OutOfMemory)?;
+        task.context.cr3 = pml4_phys as u64;
+    }
+    let mut mapper = PageMapper::for_address_space(task.address_space());
+
     // Create ELF loader
-    let mut elf_loader = ElfLoader::new(pmm, mapper);
+    let mut elf_loader = ElfLoader::new(pmm, &mut mapper);
 
     // Load the ELF binary
     let (entry_point, user_stack_top) = elf_loader.load_elf(INIT_ELF_BINARY, task)?;
@@ -336,6 +336,7 @@ pub fn unregister_irq_handler(irq: u8) {
 /// This function must be called from interrupt context. The caller is
 /// responsible for sending EOI to the LAPIC after this function returns.
 pub fn handle_irq(irq: u8) {
+    crate::sched::cputime::irq_enter();
     let handlers = IRQ_HANDLERS.lock();
 
     if let Some(handler) = handlers[irq as usize] {
@@ -362,6 +363,7 @@ pub fn handle_irq(irq: u8) {
     } else {
         serial_println!("[IRQ] Warning: Unhandled IRQ {}", irq);
     }
+    crate::sched::cputime::irq_exit();
 }
 
 // ============================================================================
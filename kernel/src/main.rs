@@ -703,6 +703,111 @@ fn smp_test_task_d() -> ! {
     }
 }
 
+// ============================================================================
+// Scheduler Load-Balancing Benchmark (config::SCHED_BENCH_ENABLED)
+// ============================================================================
+
+/// Number of workers started so far (each takes the next index)
+static SCHED_BENCH_STARTED: core::sync::atomic::AtomicUsize =
+    core::sync::atomic::AtomicUsize::new(0);
+
+/// Number of workers that have finished
+static SCHED_BENCH_DONE: core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+/// Monotonic time at which the last worker finished (nanoseconds)
+static SCHED_BENCH_END_NS: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(0);
+
+/// Iterations of the busy loop per chunk of work
+const SCHED_BENCH_CHUNK_ITERS: u64 = 200_000;
+
+/// Chunks of work done by the shortest worker (others do 2x and 3x)
+const SCHED_BENCH_CHUNKS: usize = 100;
+
+/// Chunks of work a worker does (uneven, so queues drift out of balance)
+fn sched_bench_chunks(index: usize) -> usize {
+    SCHED_BENCH_CHUNKS * (1 + index % 3)
+}
+
+/// One chunk of CPU-bound work
+fn sched_bench_spin() {
+    let mut acc: u64 = 0;
+    for i in 0..SCHED_BENCH_CHUNK_ITERS {
+        acc = core::hint::black_box(acc.wrapping_mul(31).wrapping_add(i));
+    }
+    core::hint::black_box(acc);
+}
+
+/// Benchmark worker: CPU-bound chunks with a short sleep every 8 chunks
+///
+/// The sleeps exercise wakeup placement; the uneven amounts of work leave
+/// CPUs idle early unless work is stolen or pulled.
+fn sched_bench_worker() -> ! {
+    use core::sync::atomic::Ordering;
+
+    let index = SCHED_BENCH_STARTED.fetch_add(1, Ordering::Relaxed);
+    for chunk in 0..sched_bench_chunks(index) {
+        sched_bench_spin();
+        if chunk % 8 == 7 {
+            sched::sleep_current_task(1, TaskPriority::Normal);
+        }
+    }
+
+    SCHED_BENCH_END_NS.fetch_max(sched::hrtimer::monotonic_ns(), Ordering::Relaxed);
+    SCHED_BENCH_DONE.fetch_add(1, Ordering::Release);
+
+    loop {
+        sched::sleep_current_task(1000, TaskPriority::Normal);
+    }
+}
+
+/// Benchmark driver: starts 2 workers per CPU and reports the makespan
+///
+/// Prints one `[SCHED-BENCH]` line that `benches/sched_balance.rs` parses.
+/// `ideal_ms` is the makespan with perfect balancing and no sleeps.
+fn sched_bench_task() -> ! {
+    use core::sync::atomic::Ordering;
+
+    let cpus = arch::x86_64::smp::get_cpu_count();
+    let workers = cpus * 2;
+
+    // Let boot-time tasks settle, then time one chunk on this CPU
+    sched::sleep_current_task(200, TaskPriority::Normal);
+    let t0 = sched::hrtimer::monotonic_ns();
+    sched_bench_spin();
+    let chunk_ns = sched::hrtimer::monotonic_ns() - t0;
+
+    let total_chunks: usize = (0..workers).map(sched_bench_chunks).sum();
+    let ideal_ns = total_chunks as u64 * chunk_ns / cpus as u64;
+
+    let start = sched::hrtimer::monotonic_ns();
+    for _ in 0..workers {
+        spawn_task("Bench-Worker", sched_bench_worker, TaskPriority::Normal)
+            .expect("Failed to spawn Bench-Worker");
+    }
+
+    while SCHED_BENCH_DONE.load(Ordering::Acquire) < workers {
+        sched::sleep_current_task(10, TaskPriority::Normal);
+    }
+
+    let makespan_ns = SCHED_BENCH_END_NS.load(Ordering::Relaxed) - start;
+    let stats = &sched::balance::STATS;
+    serial_println!(
+        "[SCHED-BENCH] cpus={} workers={} makespan_ms={} ideal_ms={} idle_steals={} pulls={} idle_kicks={} wake_affine={}",
+        cpus,
+        workers,
+        makespan_ns / 1_000_000,
+        ideal_ns / 1_000_000,
+        stats.idle_steals.load(Ordering::Relaxed),
+        stats.pulls.load(Ordering::Relaxed),
+        stats.idle_kicks.load(Ordering::Relaxed),
+        stats.wake_affine.load(Ordering::Relaxed)
+    );
+
+    loop {
+        sched::sleep_current_task(1000, TaskPriority::Normal);
+    }
+}
+
 /// Extract embedded userspace binaries to the filesystem
 ///
 /// This function creates the /bin directory and extracts the embedded
@@ -891,6 +996,17 @@ fn print_test_results_delayed() -> ! {
 /// Kernel entry point called by the Limine bootloader
 #[no_mangle]
 pub extern "C" fn _start() -> ! {
+    // Remember the boot stack for kmemleak before anything is allocated
+    let boot_rsp: usize;
+    unsafe {
+        core::arch::asm!(
+            "mov {}, rsp",
+            out(reg) boot_rsp,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+    mm::kmemleak::set_boot_stack(boot_rsp);
+
     // Initialize serial port for debugging
     serial::SERIAL.lock().init();
     serial_println!("[KERNEL] MelloOS starting...");
@@ -970,6 +1086,9 @@ pub extern "C" fn _start() -> ! {
         bsp_apic_id
     );
 
+    // Split the free lists per NUMA node now that the SRAT has been parsed
+    mm::init_numa();
+
     serial_println!("[KERNEL] Calibrating APIC timer...");
     // Calibrate APIC timer using PIT
     let lapic_frequency = unsafe { bsp_lapic.calibrate_timer() };
@@ -981,12 +1100,15 @@ pub extern "C" fn _start() -> ! {
         percpu.lapic_timer_hz = lapic_frequency;
     }
 
+    // The TSC was calibrated alongside the LAPIC timer; anchor wall time to it
+    sched::hrtimer::init_clock();
+
     serial_println!("[KERNEL] Initializing BSP APIC timer...");
-    // Initialize APIC timer at SCHED_HZ (100 Hz)
+    // Program the first tick (TSC-deadline or one-shot, periodic as fallback)
     unsafe {
-        bsp_lapic.init_timer(lapic_frequency, config::SCHED_HZ);
+        sched::hrtimer::init_cpu_timer(&mut bsp_lapic);
     }
-    serial_println!("[APIC] core0 timer @{}Hz", config::SCHED_HZ);
+    serial_println!("[APIC] core0 tick @{}Hz", config::SCHED_HZ);
 
     serial_println!("[KERNEL] Initializing SMP (bringing up Application Processors)...");
 
@@ -1046,11 +1168,19 @@ pub extern "C" fn _start() -> ! {
     serial_println!("[KERNEL] Extracting userspace binaries to filesystem...");
     // Extract embedded binaries to /bin directory (Phase 6 - exec/shell launch)
     extract_binaries_to_fs();
+    // Directory for core dumps of crashed processes
+    user::coredump::init();
 
     serial_println!("[KERNEL] Initializing scheduler...");
     // Initialize the task scheduler
     init_scheduler();
 
+    // Start the periodic leak scanner (only when kmemleak is enabled)
+    mm::kmemleak::start_scanner();
+
+    // Start the background dirty page flusher
+    fs::cache::writeback::get_writeback_scheduler().start();
+
     serial_println!("[KERNEL] Initializing timer interrupt...");
     // Initialize IDT and syscall handler early so kernel tests can use syscalls safely
     unsafe {
@@ -1127,6 +1257,12 @@ pub extern "C" fn _start() -> ! {
     spawn_task("SMP-C", smp_test_task_c, TaskPriority::Normal).expect("Failed to spawn SMP-C");
     spawn_task("SMP-D", smp_test_task_d, TaskPriority::Low).expect("Failed to spawn SMP-D");
 
+    if config::SCHED_BENCH_ENABLED {
+        serial_println!("[KERNEL] Spawning scheduler load-balancing benchmark...");
+        spawn_task("Sched-Bench", sched_bench_task, TaskPriority::Normal)
+            .expect("Failed to spawn Sched-Bench");
+    }
+
     serial_println!("[KERNEL] ========================================");
     serial_println!("[KERNEL] All SMP test tasks spawned successfully!");
     serial_println!("[KERNEL] CPU count: {}", cpu_count);
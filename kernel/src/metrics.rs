@@ -23,6 +23,9 @@ pub struct SystemMetrics {
     /// Per-syscall counters (indexed by syscall number)
     pub syscalls: [AtomicU64; MAX_SYSCALLS],
 
+    /// Per-syscall counts of calls refused by a syscall filter
+    pub syscalls_denied: [AtomicU64; MAX_SYSCALLS],
+
     /// Total interrupts handled
     pub interrupts: AtomicU64,
 
@@ -46,6 +49,7 @@ impl SystemMetrics {
             pty_bytes_in: AtomicU64::new(0),
             pty_bytes_out: AtomicU64::new(0),
             syscalls: [ZERO; MAX_SYSCALLS],
+            syscalls_denied: [ZERO; MAX_SYSCALLS],
             interrupts: AtomicU64::new(0),
             page_faults: AtomicU64::new(0),
             ipc_messages_sent: AtomicU64::new(0),
@@ -85,6 +89,14 @@ impl SystemMetrics {
         }
     }
 
+    /// Increment the refused-by-filter counter for a syscall number
+    #[inline]
+    pub fn inc_syscall_denied(&self, syscall_num: usize) {
+        if syscall_num < MAX_SYSCALLS {
+            self.syscalls_denied[syscall_num].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
     /// Increment interrupt counter
     #[inline]
     pub fn inc_interrupts(&self) {
@@ -147,6 +159,23 @@ impl SystemMetrics {
         total
     }
 
+    /// Get the refused-by-filter count for a specific syscall number
+    pub fn get_syscall_denied(&self, syscall_num: usize) -> u64 {
+        if syscall_num < MAX_SYSCALLS {
+            self.syscalls_denied[syscall_num].load(Ordering::Relaxed)
+        } else {
+            0
+        }
+    }
+
+    /// Get total count of syscalls refused by filters
+    pub fn get_total_syscalls_denied(&self) -> u64 {
+        self.syscalls_denied
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .sum()
+    }
+
     /// Get interrupt count
     pub fn get_interrupts(&self) -> u64 {
         self.interrupts.load(Ordering::Relaxed)
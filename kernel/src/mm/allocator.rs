@@ -290,6 +290,9 @@ pub fn kmalloc(size: usize) -> *mut u8 {
             // kprintln!("[MM] Allocated {} bytes at 0x{:p}", size, ptr);
         }
 
+        drop(allocator_guard);
+        super::kmemleak::track(ptr, size);
+
         ptr
     } else {
         // Allocator not initialized
@@ -303,6 +306,8 @@ pub fn kfree(ptr: *mut u8, size: usize) {
         return;
     }
 
+    super::kmemleak::untrack(ptr);
+
     let mut allocator_guard = ALLOCATOR.lock();
 
     if let Some(allocator) = allocator_guard.as_mut() {
@@ -0,0 +1,264 @@
+//! Address Space Layout Randomization
+//!
+//! The kernel image itself is relocated by Limine to a random base (the
+//! kernel is linked as a PIE, see `linker.ld`); this module only reports the
+//! resulting slide. For user space it picks a random stack top, mmap base and
+//! load base for position-independent executables on every exec.
+//!
+//! Entropy comes from RDSEED, then RDRAND, and finally from TSC jitter on
+//! CPUs that implement neither instruction.
+
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Address the kernel is linked at (see `linker.ld`)
+pub const KERNEL_LINK_BASE: usize = 0xFFFF_FFFF_8000_0000;
+
+/// Highest user stack top (the pre-ASLR fixed value)
+pub const STACK_TOP_MAX: u64 = 0x0000_7FFF_FFFF_0000;
+
+/// Bits of page-granular entropy in the stack top (256MB range)
+const STACK_RANDOM_BITS: u32 = 16;
+
+/// Highest mmap base
+pub const MMAP_BASE_MAX: u64 = 0x0000_7000_0000_0000;
+
+/// Bits of page-granular entropy in the mmap base (64GB range)
+const MMAP_RANDOM_BITS: u32 = 24;
+
+/// Lowest load base for position-independent executables
+pub const PIE_BASE_MIN: u64 = 0x0000_5555_0000_0000;
+
+/// Bits of page-granular entropy in the PIE load base (1TB range)
+const PIE_RANDOM_BITS: u32 = 28;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// CPUID.01H:ECX bit advertising RDRAND
+const CPUID_ECX_RDRAND: u32 = 1 << 30;
+
+/// CPUID.(EAX=07H,ECX=0):EBX bit advertising RDSEED
+const CPUID_EBX_RDSEED: u32 = 1 << 18;
+
+/// Hardware retries before giving up on RDSEED/RDRAND
+const HW_RETRIES: usize = 10;
+
+/// Entropy source in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntropySource {
+    Rdseed,
+    Rdrand,
+    TscJitter,
+}
+
+impl EntropySource {
+    pub fn name(self) -> &'static str {
+        match self {
+            EntropySource::Rdseed => "RDSEED",
+            EntropySource::Rdrand => "RDRAND",
+            EntropySource::TscJitter => "TSC jitter",
+        }
+    }
+}
+
+const SOURCE_UNPROBED: u8 = 0;
+const SOURCE_RDSEED: u8 = 1;
+const SOURCE_RDRAND: u8 = 2;
+const SOURCE_TSC: u8 = 3;
+
+/// Cached result of `probe_entropy_source`
+static ENTROPY_SOURCE: AtomicU8 = AtomicU8::new(SOURCE_UNPROBED);
+
+/// Slide reported by `init_aslr`
+static KERNEL_SLIDE: AtomicU64 = AtomicU64::new(0);
+
+/// Mixing state, so consecutive fallback draws never repeat
+static MIX_STATE: AtomicU64 = AtomicU64::new(0x9E37_79B9_7F4A_7C15);
+
+fn probe_entropy_source() -> EntropySource {
+    let leaf0 = core::arch::x86_64::__cpuid(0);
+    if leaf0.eax >= 7 {
+        let leaf7 = core::arch::x86_64::__cpuid_count(7, 0);
+        if leaf7.ebx & CPUID_EBX_RDSEED != 0 {
+            return EntropySource::Rdseed;
+        }
+    }
+
+    let leaf1 = core::arch::x86_64::__cpuid(1);
+    if leaf1.ecx & CPUID_ECX_RDRAND != 0 {
+        return EntropySource::Rdrand;
+    }
+
+    EntropySource::TscJitter
+}
+
+/// Get the entropy source, probing CPUID on first use
+pub fn entropy_source() -> EntropySource {
+    match ENTROPY_SOURCE.load(Ordering::Relaxed) {
+        SOURCE_RDSEED => EntropySource::Rdseed,
+        SOURCE_RDRAND => EntropySource::Rdrand,
+        SOURCE_TSC => EntropySource::TscJitter,
+        _ => {
+            let source = probe_entropy_source();
+            let raw = match source {
+                EntropySource::Rdseed => SOURCE_RDSEED,
+                EntropySource::Rdrand => SOURCE_RDRAND,
+                EntropySource::TscJitter => SOURCE_TSC,
+            };
+            ENTROPY_SOURCE.store(raw, Ordering::Relaxed);
+            source
+        }
+    }
+}
+
+fn rdseed64() -> Option<u64> {
+    for _ in 0..HW_RETRIES {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!(
+                "rdseed {value}",
+                "setc {ok}",
+                value = out(reg) value,
+                ok = out(reg_byte) ok,
+                options(nomem, nostack),
+            );
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+        core::hint::spin_loop();
+    }
+    None
+}
+
+fn rdrand64() -> Option<u64> {
+    for _ in 0..HW_RETRIES {
+        let value: u64;
+        let ok: u8;
+        unsafe {
+            core::arch::asm!(
+                "rdrand {value}",
+                "setc {ok}",
+                value = out(reg) value,
+                ok = out(reg_byte) ok,
+                options(nomem, nostack),
+            );
+        }
+        if ok != 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Gather entropy from the timing jitter of short busy loops
+///
+/// Each round's duration varies with cache, interrupt and SMT noise; only
+/// the low bits of each delta are folded in.
+fn tsc_jitter64() -> u64 {
+    let mut acc = 0u64;
+    for round in 0..64u64 {
+        let start = unsafe { core::arch::x86_64::_rdtsc() };
+        let mut spin = (start & 0x3F) + 16;
+        while spin > 0 {
+            spin -= 1;
+            core::hint::spin_loop();
+        }
+        let delta = unsafe { core::arch::x86_64::_rdtsc() }.wrapping_sub(start);
+        acc = acc.rotate_left(7) ^ delta ^ round;
+    }
+    acc
+}
+
+/// SplitMix64 finalizer
+fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Get 64 random bits
+///
+/// Not suitable for key material when running on the TSC fallback.
+pub fn random_u64() -> u64 {
+    let raw = match entropy_source() {
+        EntropySource::Rdseed => rdseed64().or_else(rdrand64),
+        EntropySource::Rdrand => rdrand64(),
+        EntropySource::TscJitter => None,
+    }
+    .unwrap_or_else(tsc_jitter64);
+
+    let state = MIX_STATE.fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed);
+    mix64(raw ^ state)
+}
+
+/// Random page-aligned offset below `2^bits` pages
+fn random_page_offset(bits: u32) -> u64 {
+    (random_u64() & ((1u64 << bits) - 1)) * PAGE_SIZE
+}
+
+/// Pick a user stack top for a new program image
+pub fn stack_top() -> u64 {
+    STACK_TOP_MAX - random_page_offset(STACK_RANDOM_BITS)
+}
+
+/// Pick the base of a new process's mmap area
+pub fn mmap_base() -> u64 {
+    MMAP_BASE_MAX - random_page_offset(MMAP_RANDOM_BITS)
+}
+
+/// Pick the load bias of a position-independent executable
+pub fn pie_load_base() -> u64 {
+    PIE_BASE_MIN + random_page_offset(PIE_RANDOM_BITS)
+}
+
+/// Kernel slide chosen by the bootloader
+pub fn kernel_slide(kernel_virt_base: usize) -> usize {
+    kernel_virt_base.wrapping_sub(KERNEL_LINK_BASE)
+}
+
+/// Slide the running kernel was loaded with (0 before `init_aslr`)
+pub fn current_kernel_slide() -> usize {
+    KERNEL_SLIDE.load(Ordering::Relaxed) as usize
+}
+
+/// Probe the entropy source and report the layout randomization in use
+///
+/// # Arguments
+/// * `kernel_virt_base` - Virtual base the kernel was loaded at
+pub fn init_aslr(kernel_virt_base: usize) {
+    let source = entropy_source();
+    KERNEL_SLIDE.store(kernel_slide(kernel_virt_base) as u64, Ordering::Relaxed);
+    crate::serial_println!(
+        "[MM] KASLR: kernel at 0x{:016x} (slide 0x{:x})",
+        kernel_virt_base,
+        kernel_slide(kernel_virt_base)
+    );
+    crate::serial_println!("[MM] ASLR enabled (entropy: {})", source.name());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges() {
+        for _ in 0..64 {
+            let top = stack_top();
+            assert_eq!(top % PAGE_SIZE, 0);
+            assert!(top <= STACK_TOP_MAX);
+            assert!(top > STACK_TOP_MAX - (PAGE_SIZE << STACK_RANDOM_BITS));
+
+            let base = pie_load_base();
+            assert_eq!(base % PAGE_SIZE, 0);
+            assert!(base >= PIE_BASE_MIN);
+            assert!(base < mmap_base());
+        }
+    }
+
+    #[test]
+    fn test_kernel_slide() {
+        assert_eq!(kernel_slide(KERNEL_LINK_BASE), 0);
+        assert_eq!(kernel_slide(KERNEL_LINK_BASE + 0x20_0000), 0x20_0000);
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! This provides basic mmap functionality for Phase 8.
 
+use crate::mm::paging::PAGE_SIZE_2M;
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use spin::RwLock;
 
@@ -161,6 +162,8 @@ pub struct MmapTable {
     in_use: AtomicBool,
     pub mappings: [RwLock<MemoryMapping>; MAX_MAPPINGS],
     count: AtomicUsize,
+    /// Randomized start of the area used for mappings without a hint
+    mmap_base: AtomicU64,
 }
 
 impl MmapTable {
@@ -171,6 +174,7 @@ impl MmapTable {
             in_use: AtomicBool::new(false),
             mappings: [INIT; MAX_MAPPINGS],
             count: AtomicUsize::new(0),
+            mmap_base: AtomicU64::new(0),
         }
     }
 
@@ -178,6 +182,8 @@ impl MmapTable {
         self.pid.store(pid, Ordering::Release);
         self.in_use.store(true, Ordering::Release);
         self.count.store(0, Ordering::Release);
+        self.mmap_base
+            .store(crate::mm::aslr::mmap_base(), Ordering::Release);
     }
 
     pub fn is_for_process(&self, pid: u64) -> bool {
@@ -213,6 +219,93 @@ impl MmapTable {
     pub fn count(&self) -> usize {
         self.count.load(Ordering::Relaxed)
     }
+
+    /// Total length of the mappings for which `filter` holds
+    pub fn mapped_bytes<F>(&self, filter: F) -> usize
+    where
+        F: Fn(&MemoryMapping) -> bool,
+    {
+        self.mappings
+            .iter()
+            .map(|lock| *lock.read())
+            .filter(|m| m.valid && filter(m))
+            .map(|m| m.length)
+            .sum()
+    }
+
+    /// Base address for mappings placed by the kernel
+    pub fn mmap_base(&self) -> u64 {
+        self.mmap_base.load(Ordering::Acquire)
+    }
+
+    /// Find a free range of `length` bytes at or above the mmap base
+    ///
+    /// # Arguments
+    /// * `length` - Size of the range (page aligned)
+    /// * `align` - Alignment of the start address (power of two)
+    pub fn find_free_range(&self, length: usize, align: u64) -> u64 {
+        let mut candidate = (self.mmap_base() + align - 1) & !(align - 1);
+        'search: loop {
+            for lock in &self.mappings {
+                let m = lock.read();
+                let m_end = m.vaddr + m.length as u64;
+                if m.valid && m.vaddr < candidate + length as u64 && candidate < m_end {
+                    candidate = (m_end + align - 1) & !(align - 1);
+                    continue 'search;
+                }
+            }
+            return candidate;
+        }
+    }
+
+    /// Drop every mapping
+    fn clear(&self) {
+        for lock in &self.mappings {
+            lock.write().valid = false;
+        }
+        self.count.store(0, Ordering::Release);
+    }
+
+    /// Remove `[start, end)` from the table
+    ///
+    /// Mappings partly inside the range are trimmed; a mapping with the range
+    /// in its middle is split in two.
+    pub fn remove_range(&self, start: u64, end: u64) -> Result<(), &'static str> {
+        let mut tail = None;
+
+        for lock in &self.mappings {
+            let mut m = lock.write();
+            let m_end = m.vaddr + m.length as u64;
+            if !m.valid || m_end <= start || m.vaddr >= end {
+                continue;
+            }
+
+            if start <= m.vaddr && end >= m_end {
+                m.valid = false;
+                self.count.fetch_sub(1, Ordering::Relaxed);
+            } else if start <= m.vaddr {
+                let cut = end - m.vaddr;
+                m.vaddr = end;
+                m.length -= cut as usize;
+                m.offset += cut;
+            } else if end >= m_end {
+                m.length = (start - m.vaddr) as usize;
+            } else {
+                let mut upper = *m;
+                upper.vaddr = end;
+                upper.length = (m_end - end) as usize;
+                upper.offset += end - m.vaddr;
+                m.length = (start - m.vaddr) as usize;
+                tail = Some(upper);
+            }
+        }
+
+        if let Some(upper) = tail {
+            self.add_mapping(upper).ok_or("Too many mappings")?;
+        }
+
+        Ok(())
+    }
 }
 
 const MAX_TABLES: usize = 256;
@@ -243,6 +336,54 @@ impl MmapManager {
         }
         None
     }
+
+    /// The table of process `pid`, without setting one up if it has none
+    pub fn find_table(&self, pid: u64) -> Option<&MmapTable> {
+        self.tables.iter().find(|table| table.is_for_process(pid))
+    }
+
+    /// Give a forked child a copy of its parent's mappings
+    ///
+    /// The child's pages are copy-on-write copies of the parent's, so the
+    /// mappings describe them unchanged.
+    pub fn copy_table(&self, parent: u64, child: u64) -> Result<(), &'static str> {
+        let Some(source) = self.find_table(parent) else {
+            return Ok(());
+        };
+        let target = self.get_table(child).ok_or("No mmap table")?;
+        target.clear();
+        target
+            .mmap_base
+            .store(source.mmap_base(), Ordering::Release);
+        for lock in &source.mappings {
+            let mapping = *lock.read();
+            if mapping.valid {
+                target.add_mapping(mapping).ok_or("Too many mappings")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Release the table of process `pid` once its address space is gone
+    pub fn release(&self, pid: u64) {
+        if let Some(table) = self.find_table(pid) {
+            table.clear();
+            table.in_use.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Process whose mappings the current task uses (threads share them)
+pub fn current_pid() -> u64 {
+    crate::sched::current_task().map_or(1, |task| task.pid as u64)
+}
+
+/// Whether `[addr, addr + length)` lies in user space
+fn in_user_space(addr: u64, length: usize) -> bool {
+    use crate::sched::task::USER_LIMIT;
+
+    addr.checked_add(length as u64)
+        .is_some_and(|end| end <= USER_LIMIT as u64)
 }
 
 use spin::Once;
@@ -270,9 +411,7 @@ pub fn sys_mmap(
     let page_aligned_length = (length + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
 
     // Get current process ID
-    let pid = crate::sched::get_current_task_info()
-        .map(|info| info.0 as u64)
-        .unwrap_or(1);
+    let pid = current_pid();
 
     let table = get_mmap_manager().get_table(pid).ok_or("No mmap table")?;
 
@@ -282,13 +421,24 @@ pub fn sys_mmap(
     } else if addr != 0 {
         addr
     } else {
-        0x7000_0000_0000 + (table.count() * 0x10000) as u64
+        // 2MB-align large mappings so anonymous memory can use huge pages
+        let align = if page_aligned_length >= PAGE_SIZE_2M {
+            PAGE_SIZE_2M as u64
+        } else {
+            PAGE_SIZE as u64
+        };
+        table.find_free_range(page_aligned_length, align)
     };
 
     // Ensure page alignment
     if vaddr & (PAGE_SIZE as u64 - 1) != 0 {
         return Err("Address not page aligned");
     }
+    if !in_user_space(vaddr, page_aligned_length) {
+        return Err("ENOMEM: Range outside user space");
+    }
+
+    check_limits(page_aligned_length, prot, flags)?;
 
     // Store mapping info
     let fd_opt = if fd >= 0 { Some(fd as u32) } else { None };
@@ -305,6 +455,99 @@ pub fn sys_mmap(
     Ok(vaddr)
 }
 
+/// Size of the caller's address space: its memory regions (code, data and
+/// stack segments) and its mappings in `table`
+///
+/// Must not be called with a mapping of `table` locked.
+pub fn address_space_bytes(table: &MmapTable) -> usize {
+    let regions = crate::sched::current_task().map_or(0, region_bytes);
+    regions + table.mapped_bytes(|_| true)
+}
+
+/// Size of `task`'s address space, measured like `address_space_bytes`
+pub fn vm_size(task: &crate::sched::task::Task) -> usize {
+    let mapped = get_mmap_manager()
+        .find_table(task.pid as u64)
+        .map_or(0, |table| table.mapped_bytes(|_| true));
+    region_bytes(task) + mapped
+}
+
+/// Bytes covered by a task's memory regions
+fn region_bytes(task: &crate::sched::task::Task) -> usize {
+    task.memory_regions
+        .iter()
+        .flatten()
+        .map(|r| r.end - r.start)
+        .sum()
+}
+
+/// Whether a mapping counts towards `RLIMIT_DATA` (private and writable)
+fn is_data_mapping(prot: ProtFlags, flags: MmapFlags) -> bool {
+    prot.is_writable() && !flags.is_shared() && !flags.is_growsdown()
+}
+
+/// Bytes of `task`'s data, BSS and heap segments
+fn data_region_bytes(task: &crate::sched::task::Task) -> usize {
+    use crate::sched::task::MemoryRegionType;
+
+    task.memory_regions
+        .iter()
+        .flatten()
+        .filter(|r| {
+            matches!(
+                r.region_type,
+                MemoryRegionType::Data | MemoryRegionType::Bss | MemoryRegionType::Heap
+            )
+        })
+        .map(|r| r.end - r.start)
+        .sum()
+}
+
+/// Address space and data size of `task`, as `RLIMIT_AS` and
+/// `RLIMIT_DATA` count them
+///
+/// Must not be called with a mapping of the task's table locked.
+pub fn memory_usage(task: &crate::sched::task::Task) -> crate::user::rlimit::MemoryUsage {
+    let table = get_mmap_manager().find_table(task.pid as u64);
+    let mapped = table.map_or(0, |table| table.mapped_bytes(|_| true));
+    let data_mapped = table.map_or(0, |table| {
+        table.mapped_bytes(|m| is_data_mapping(m.prot, m.flags))
+    });
+    crate::user::rlimit::MemoryUsage {
+        address_space: region_bytes(task) + mapped,
+        data: data_region_bytes(task) + data_mapped,
+        stack: 0,
+    }
+}
+
+/// Check that `length` more bytes of mappings keep the caller under its
+/// `RLIMIT_AS` and, for a data mapping, its `RLIMIT_DATA`
+///
+/// The data size counts the data, BSS and heap segments as well as the
+/// private writable mappings.
+fn check_limits(length: usize, prot: ProtFlags, flags: MmapFlags) -> Result<(), &'static str> {
+    use crate::user::rlimit::{MemoryUsage, RLIMIT_AS};
+
+    let Some(task) = crate::sched::current_task() else {
+        return Ok(());
+    };
+    let usage = memory_usage(task);
+    let grown = MemoryUsage {
+        address_space: usage.address_space + length,
+        data: if is_data_mapping(prot, flags) {
+            usage.data + length
+        } else {
+            0
+        },
+        stack: 0,
+    };
+    match task.rlimits.memory_over_limit(&grown) {
+        None => Ok(()),
+        Some(RLIMIT_AS) => Err("ENOMEM: RLIMIT_AS exceeded"),
+        Some(_) => Err("ENOMEM: RLIMIT_DATA exceeded"),
+    }
+}
+
 /// msync syscall implementation
 ///
 /// Synchronizes a file-backed memory mapping with the underlying file.
@@ -326,9 +569,7 @@ pub fn sys_msync(addr: u64, length: usize, flags: MsyncFlags) -> Result<(), &'st
         return Ok(());
     }
 
-    let pid = crate::sched::get_current_task_info()
-        .map(|info| info.0 as u64)
-        .unwrap_or(1);
+    let pid = current_pid();
 
     let table = get_mmap_manager().get_table(pid).ok_or("No mmap table")?;
     let mapping = table.find_mapping(addr).ok_or("Address not mapped")?;
@@ -430,8 +671,8 @@ pub fn sys_msync(addr: u64, length: usize, flags: MsyncFlags) -> Result<(), &'st
 /// # Returns
 /// Ok(()) on success, or an error string
 pub fn sys_mprotect(addr: u64, length: usize, prot: ProtFlags) -> Result<(), &'static str> {
-    use crate::mm::paging::{get_current_cr3, PageTable, PageTableFlags};
-    use crate::mm::phys_to_virt;
+    use crate::mm::paging::{PageMapper, PageTableFlags};
+    use crate::mm::pmm::get_global_pmm;
 
     if length == 0 {
         return Ok(());
@@ -441,10 +682,11 @@ pub fn sys_mprotect(addr: u64, length: usize, prot: ProtFlags) -> Result<(), &'s
     if addr & (PAGE_SIZE as u64 - 1) != 0 {
         return Err("EINVAL: Address not page aligned");
     }
+    if !in_user_space(addr, length) {
+        return Err("ENOMEM: Range outside user space");
+    }
 
-    let pid = crate::sched::get_current_task_info()
-        .map(|info| info.0 as u64)
-        .unwrap_or(1);
+    let pid = current_pid();
 
     // Validate the address range is within a valid mapping
     let table = get_mmap_manager().get_table(pid).ok_or("No mmap table")?;
@@ -463,11 +705,6 @@ pub fn sys_mprotect(addr: u64, length: usize, prot: ProtFlags) -> Result<(), &'s
         prot
     );
 
-    // Get current page table
-    let pml4_phys = get_current_cr3();
-    let pml4_virt = phys_to_virt(pml4_phys);
-    let pml4 = unsafe { &mut *(pml4_virt as *mut PageTable) };
-
     // Calculate page range
     let start_page = addr as usize;
     let end_page = (addr as usize + length + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
@@ -483,78 +720,229 @@ pub fn sys_mprotect(addr: u64, length: usize, prot: ProtFlags) -> Result<(), &'s
         pt_flags |= PageTableFlags::NO_EXECUTE;
     }
 
-    // Update page table entries for each page in the range (Requirement 4.1)
-    let mut current_page = start_page;
-    while current_page < end_page {
-        // Extract indices from virtual address
-        let pml4_index = (current_page >> 39) & 0x1FF;
-        let pdpt_index = (current_page >> 30) & 0x1FF;
-        let pd_index = (current_page >> 21) & 0x1FF;
-        let pt_index = (current_page >> 12) & 0x1FF;
+    // Update page table entries in the range (Requirement 4.1); huge pages
+    // straddling the range boundaries are split, and the TLB is shot down
+    // on all CPUs (Requirement 4.2)
+    let mut pmm_guard = get_global_pmm();
+    let pmm = pmm_guard.as_mut().ok_or("PMM not initialized")?;
+    let mut mapper = PageMapper::new();
+    let updated = mapper.protect_range(start_page, end_page, pt_flags, pmm)?;
 
-        // Walk page tables to find the PTE
-        let pml4_entry = pml4.get_entry(pml4_index);
-        if !pml4_entry.is_present() {
-            current_page += PAGE_SIZE;
-            continue;
-        }
+    crate::serial_println!(
+        "[MMAP] mprotect completed: updated {} page table entries",
+        updated
+    );
 
-        let pdpt_phys = pml4_entry.addr();
-        let pdpt_virt = phys_to_virt(pdpt_phys);
-        let pdpt = unsafe { &mut *(pdpt_virt as *mut PageTable) };
+    Ok(())
+}
 
-        let pdpt_entry = pdpt.get_entry(pdpt_index);
-        if !pdpt_entry.is_present() {
-            current_page += PAGE_SIZE;
-            continue;
+/// munmap syscall implementation
+///
+/// Removes the mappings for the specified range and frees the backing
+/// frames. Huge pages only partly inside the range are split first.
+///
+/// # Arguments
+/// * `addr` - Starting address (must be page-aligned)
+/// * `length` - Length of the region in bytes
+///
+/// # Returns
+/// Ok(()) on success, or an error string
+pub fn sys_munmap(addr: u64, length: usize) -> Result<(), &'static str> {
+    use crate::mm::paging::PageMapper;
+    use crate::mm::pmm::get_global_pmm;
+
+    if length == 0 {
+        return Err("EINVAL: Invalid length");
+    }
+
+    if addr & (PAGE_SIZE as u64 - 1) != 0 {
+        return Err("EINVAL: Address not page aligned");
+    }
+
+    if !in_user_space(addr, length) {
+        return Err("EINVAL: Range outside user space");
+    }
+
+    let end = (addr + length as u64 + PAGE_SIZE as u64 - 1) & !(PAGE_SIZE as u64 - 1);
+
+    let pid = current_pid();
+
+    let table = get_mmap_manager().get_table(pid).ok_or("No mmap table")?;
+    table.remove_range(addr, end)?;
+
+    let mut pmm_guard = get_global_pmm();
+    let pmm = pmm_guard.as_mut().ok_or("PMM not initialized")?;
+    let mut mapper = PageMapper::new();
+    let unmapped = mapper.unmap_range(addr as usize, end as usize, pmm)?;
+    free_unmapped(&unmapped, pmm);
+
+    crate::serial_println!(
+        "[MMAP] munmap at {:#x}, {} bytes ({} pages freed)",
+        addr,
+        end - addr,
+        unmapped.len()
+    );
+
+    Ok(())
+}
+
+/// Free the pages `unmap_range` took out of an address space
+///
+/// Pages still shared copy-on-write only lose a reference.
+fn free_unmapped(
+    unmapped: &[(crate::mm::PhysAddr, crate::mm::paging::PageSize)],
+    pmm: &mut crate::mm::pmm::PhysicalMemoryManager,
+) {
+    use crate::mm::pmm::FRAME_SIZE;
+    use crate::mm::refcount::PAGE_REFCOUNT;
+
+    for &(phys, size) in unmapped {
+        if PAGE_REFCOUNT.is_shared(phys) {
+            PAGE_REFCOUNT.dec_refcount(phys, pmm);
+        } else {
+            pmm.free_contiguous(phys, size.bytes() / FRAME_SIZE);
         }
+    }
+}
 
-        let pd_phys = pdpt_entry.addr();
-        let pd_virt = phys_to_virt(pd_phys);
-        let pd = unsafe { &mut *(pd_virt as *mut PageTable) };
+/// Unmap and drop every mapping of process `pid`, whose address space is
+/// `cr3`, when exec replaces its image
+pub fn release_mappings(
+    pid: u64,
+    cr3: crate::mm::PhysAddr,
+    pmm: &mut crate::mm::pmm::PhysicalMemoryManager,
+) {
+    let manager = get_mmap_manager();
+    let Some(table) = manager.find_table(pid) else {
+        return;
+    };
 
-        let pd_entry = pd.get_entry(pd_index);
-        if !pd_entry.is_present() {
-            current_page += PAGE_SIZE;
+    let mut mapper = crate::mm::paging::PageMapper::for_address_space(cr3);
+    for lock in &table.mappings {
+        let mapping = *lock.read();
+        if !mapping.valid {
             continue;
         }
+        let end = mapping.vaddr as usize + mapping.length;
+        if let Ok(unmapped) = mapper.unmap_range(mapping.vaddr as usize, end, pmm) {
+            free_unmapped(&unmapped, pmm);
+        }
+    }
+    manager.release(pid);
+}
 
-        let pt_phys = pd_entry.addr();
-        let pt_virt = phys_to_virt(pt_phys);
-        let pt = unsafe { &mut *(pt_virt as *mut PageTable) };
+/// Handle page fault in an anonymous mapping
+///
+/// Populates the faulting page with zeroed memory. When the 2MB region
+/// around the fault lies entirely inside the mapping and nothing in it is
+/// mapped yet, a single 2MB page is used instead of a 4KB page.
+///
+/// # Arguments
+/// * `fault_addr` - Faulting virtual address
+/// * `mapping` - Memory mapping descriptor
+///
+/// # Returns
+/// Ok(()) if the fault was handled, or an error string
+pub fn handle_anonymous_fault(
+    fault_addr: u64,
+    mapping: &MemoryMapping,
+) -> Result<(), &'static str> {
+    let pml4_phys = crate::mm::paging::get_current_cr3();
+    handle_anonymous_fault_in(pml4_phys, fault_addr, mapping)
+}
 
-        let pt_entry = pt.get_entry_mut(pt_index);
-        if pt_entry.is_present() {
-            // Update the page table entry with new flags
-            let phys_addr = pt_entry.addr();
-            pt_entry.set(phys_addr, pt_flags);
+/// Populate a page of an anonymous mapping in an arbitrary address space
+///
+/// Same as `handle_anonymous_fault`, but maps into the page table rooted
+/// at `pml4_phys`.
+fn handle_anonymous_fault_in(
+    pml4_phys: crate::mm::PhysAddr,
+    fault_addr: u64,
+    mapping: &MemoryMapping,
+) -> Result<(), &'static str> {
+    use crate::mm::paging::{PageMapper, PageSize, PageTableFlags};
+    use crate::mm::pmm::{get_global_pmm, FRAME_SIZE};
 
-            // Flush TLB for this page (local CPU)
-            unsafe {
-                core::arch::asm!(
-                    "invlpg [{}]",
-                    in(reg) current_page,
-                    options(nostack, preserves_flags)
-                );
-            }
-        }
+    if !mapping.contains(fault_addr) {
+        return Err("Fault address outside mapping");
+    }
 
-        current_page += PAGE_SIZE;
+    let mut flags = PageTableFlags::PRESENT | PageTableFlags::USER;
+    if mapping.prot.is_writable() {
+        flags |= PageTableFlags::WRITABLE;
+    }
+    if !mapping.prot.is_executable() {
+        flags |= PageTableFlags::NO_EXECUTE;
     }
 
-    // Perform TLB shootdown on all other CPUs (Requirement 4.2)
-    unsafe {
-        crate::mm::tlb::tlb_shootdown(start_page, end_page);
+    let mut pmm_guard = get_global_pmm();
+    let pmm = pmm_guard.as_mut().ok_or("PMM not initialized")?;
+    let mut mapper = PageMapper::for_address_space(pml4_phys);
+
+    let huge_base = fault_addr & !(PAGE_SIZE_2M as u64 - 1);
+    let huge_fits = huge_base >= mapping.vaddr
+        && huge_base + PAGE_SIZE_2M as u64 <= mapping.vaddr + mapping.length as u64;
+
+    if huge_fits && mapper.is_unmapped(huge_base as usize, PageSize::Size2M) {
+        if let Some(phys) = pmm.alloc_contiguous(PAGE_SIZE_2M / FRAME_SIZE, PAGE_SIZE_2M) {
+            mapper.map_huge_page(huge_base as usize, phys, PageSize::Size2M, flags, pmm)?;
+
+            crate::serial_println!(
+                "[MMAP] Anonymous fault: mapped 2MB page at 0x{:x}",
+                huge_base
+            );
+            return Ok(());
+        }
     }
 
-    crate::serial_println!(
-        "[MMAP] mprotect completed: updated {} pages",
-        (end_page - start_page) / PAGE_SIZE
-    );
+    let page_addr = fault_addr & !(PAGE_SIZE as u64 - 1);
+    let phys = pmm.alloc_frame().ok_or("Out of physical memory")?;
+    mapper.map_page(page_addr as usize, phys, flags, pmm)?;
+
+    crate::serial_println!("[MMAP] Anonymous fault: mapped page at 0x{:x}", page_addr);
 
     Ok(())
 }
 
+/// Populate a user page the kernel is about to access
+///
+/// The kernel reaches user memory by walking the page tables (see
+/// `kmap_user_page_in`), so it never takes the fault that would map a page
+/// of an mmap region that was not touched yet. This does what that fault
+/// would. Anonymous pages are populated in any address space. File-backed
+/// pages are read through the current process's file descriptor, so they
+/// are only populated in the current address space.
+///
+/// # Arguments
+/// * `pml4_phys` - PML4 of the address space
+/// * `pid` - Process whose mmap table describes the address space
+/// * `addr` - User address to populate
+///
+/// # Returns
+/// Ok(()) once the page is mapped, or an error string if there is no
+/// accessible mapping at `addr` or it could not be populated
+pub fn fault_in_page(
+    pml4_phys: crate::mm::PhysAddr,
+    pid: u64,
+    addr: u64,
+) -> Result<(), &'static str> {
+    let mapping = get_mmap_manager()
+        .find_table(pid)
+        .and_then(|table| table.find_mapping(addr))
+        .ok_or("No mapping at address")?;
+    if !mapping.prot.is_readable() {
+        return Err("Mapping is not accessible");
+    }
+
+    if !mapping.is_file_backed() {
+        handle_anonymous_fault_in(pml4_phys, addr, &mapping)
+    } else if pml4_phys & !0xFFF == crate::mm::paging::get_current_cr3() {
+        handle_file_mapping_fault(addr, &mapping)
+    } else {
+        Err("File-backed page of another address space")
+    }
+}
+
 /// Handle page fault in file-backed mapping
 ///
 /// This function handles page faults in file-backed memory mappings by:
@@ -578,7 +966,6 @@ pub fn handle_file_mapping_fault(
     use crate::mm::phys_to_virt;
     use crate::mm::pmm::get_global_pmm;
     use crate::mm::refcount::PAGE_REFCOUNT;
-    use crate::sys::syscall::FD_TABLE;
 
     crate::serial_println!(
         "[MMAP] File-backed fault at addr=0x{:x}, mapping=[0x{:x}-0x{:x}], fd={:?}",
@@ -609,18 +996,13 @@ pub fn handle_file_mapping_fault(
     );
 
     // Get the inode from the file descriptor
-    let fd_table = FD_TABLE.lock();
-    let fd_entry = fd_table.get(fd as usize).ok_or("Invalid file descriptor")?;
+    let fd_entry = crate::sys::syscall::get_fd(fd as usize).ok_or("Invalid file descriptor")?;
 
     // Extract inode from FdType
     let (inode, inode_id) = match fd_entry.fd_type() {
         crate::sys::syscall::FdType::VfsFile { inode, .. } => (inode.clone(), inode.ino()),
-        _ => {
-            drop(fd_table);
-            return Err("File descriptor is not a VFS file");
-        }
+        _ => return Err("File descriptor is not a VFS file"),
     };
-    drop(fd_table);
 
     // Try to get page from cache first
     let page_cache = get_page_cache();
@@ -821,6 +1203,53 @@ pub fn handle_file_mapping_fault(
     Ok(())
 }
 
+/// Grow the `MAP_GROWSDOWN` mapping just above `fault_addr` down over it
+///
+/// The grown stack must stay a guard page clear of the mapping below it.
+///
+/// # Returns
+/// Ok(true) if the stack grew, Ok(false) if the nearest mapping above the
+/// address is not a growable stack, or an error string
+pub fn grow_stack_mapping(fault_addr: u64) -> Result<bool, &'static str> {
+    const STACK_GUARD_SIZE: u64 = 4096; // 1 page guard
+
+    let Some(table) = get_mmap_manager().find_table(current_pid()) else {
+        return Ok(false);
+    };
+    // Measured before any mapping is locked
+    let address_space = address_space_bytes(table);
+
+    let mut above: Option<(usize, u64)> = None;
+    let mut below_end = 0;
+    for (idx, lock) in table.mappings.iter().enumerate() {
+        let m = lock.read();
+        if !m.valid {
+            continue;
+        }
+        if m.vaddr > fault_addr {
+            if above.map_or(true, |(_, vaddr)| m.vaddr < vaddr) {
+                above = Some((idx, m.vaddr));
+            }
+        } else {
+            below_end = below_end.max(m.vaddr + m.length as u64);
+        }
+    }
+
+    let Some((idx, _)) = above else {
+        return Ok(false);
+    };
+    let mut mapping = table.mappings[idx].write();
+    if !mapping.valid || !mapping.flags.is_growsdown() {
+        return Ok(false);
+    }
+    if fault_addr & !(PAGE_SIZE as u64 - 1) < below_end + STACK_GUARD_SIZE {
+        return Err("Stack overflow (guard page)");
+    }
+
+    handle_growsdown_fault(fault_addr, &mut mapping, address_space)?;
+    Ok(true)
+}
+
 /// Handle MAP_GROWSDOWN stack expansion
 ///
 /// This function handles page faults in MAP_GROWSDOWN mappings by automatically
@@ -829,19 +1258,20 @@ pub fn handle_file_mapping_fault(
 /// # Arguments
 /// * `fault_addr` - Faulting virtual address
 /// * `mapping` - Memory mapping descriptor (will be updated)
+/// * `address_space` - Size of the process's address space (see
+///   `address_space_bytes`), for `RLIMIT_AS`
 ///
 /// # Returns
 /// Ok(()) on success, or an error string
 pub fn handle_growsdown_fault(
     fault_addr: u64,
     mapping: &mut MemoryMapping,
+    address_space: usize,
 ) -> Result<(), &'static str> {
     use crate::mm::paging::{get_current_cr3, PageTable, PageTableFlags};
     use crate::mm::phys_to_virt;
     use crate::mm::pmm::get_global_pmm;
 
-    const STACK_GUARD_SIZE: u64 = 4096; // 1 page guard
-
     crate::serial_println!(
         "[MMAP] GROWSDOWN fault at addr=0x{:x}, mapping=[0x{:x}-0x{:x}]",
         fault_addr,
@@ -849,11 +1279,6 @@ pub fn handle_growsdown_fault(
         mapping.vaddr + mapping.length as u64
     );
 
-    // Check if fault is within guard page distance
-    if fault_addr < mapping.vaddr && fault_addr >= mapping.vaddr - STACK_GUARD_SIZE {
-        return Err("Stack overflow (guard page)");
-    }
-
     // Check if we should extend the mapping
     if fault_addr >= mapping.vaddr {
         return Err("Fault address not below mapping");
@@ -863,6 +1288,23 @@ pub fn handle_growsdown_fault(
     let new_start = fault_addr & !(PAGE_SIZE as u64 - 1);
     let extension_size = (mapping.vaddr - new_start) as usize;
 
+    // The stack may not grow past RLIMIT_STACK, nor the address space past
+    // RLIMIT_AS
+    if let Some(task) = crate::sched::current_task() {
+        use crate::user::rlimit::{MemoryUsage, RLIMIT_STACK};
+
+        let grown = MemoryUsage {
+            address_space: address_space + extension_size,
+            data: 0,
+            stack: (mapping.vaddr + mapping.length as u64 - new_start) as usize,
+        };
+        match task.rlimits.memory_over_limit(&grown) {
+            None => {}
+            Some(RLIMIT_STACK) => return Err("Stack limit exceeded (RLIMIT_STACK)"),
+            Some(_) => return Err("Address space limit exceeded (RLIMIT_AS)"),
+        }
+    }
+
     crate::serial_println!(
         "[MMAP] Extending stack: new_start=0x{:x}, extension={} bytes",
         new_start,
@@ -1005,11 +1447,158 @@ pub fn handle_growsdown_fault(
 /// # Returns
 /// Some(mapping) if found, None otherwise
 pub fn find_mapping_for_addr(addr: u64) -> Option<MemoryMapping> {
-    let pid = crate::sched::get_current_task_info()
-        .map(|info| info.0 as u64)
-        .unwrap_or(1);
+    let pid = current_pid();
 
     let manager = get_mmap_manager();
     let table = manager.get_table(pid)?;
     table.find_mapping(addr)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: u64 = 0x4000_0000;
+    const SIZE_2M: u64 = PAGE_SIZE_2M as u64;
+
+    fn table_with(vaddr: u64, length: usize) -> MmapTable {
+        let table = MmapTable::new();
+        let mapping = MemoryMapping::create(
+            vaddr,
+            length,
+            ProtFlags::PROT_READ,
+            MmapFlags::MAP_PRIVATE,
+            Some(3),
+            0,
+        );
+        table.add_mapping(mapping).unwrap();
+        table
+    }
+
+    fn mappings(table: &MmapTable) -> alloc::vec::Vec<(u64, usize, u64)> {
+        let mut found: alloc::vec::Vec<_> = table
+            .mappings
+            .iter()
+            .map(|lock| *lock.read())
+            .filter(|m| m.valid)
+            .map(|m| (m.vaddr, m.length, m.offset))
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_unmap_page_splits_2m_mapping() {
+        let table = table_with(BASE, 2 * PAGE_SIZE_2M);
+
+        let hole = BASE + SIZE_2M;
+        table.remove_range(hole, hole + PAGE_SIZE as u64).unwrap();
+
+        assert_eq!(
+            mappings(&table),
+            [
+                (BASE, PAGE_SIZE_2M, 0),
+                (
+                    hole + PAGE_SIZE as u64,
+                    PAGE_SIZE_2M - PAGE_SIZE,
+                    SIZE_2M + PAGE_SIZE as u64
+                ),
+            ]
+        );
+        assert_eq!(table.count(), 2);
+    }
+
+    #[test]
+    fn test_unmap_head_of_2m_mapping() {
+        let table = table_with(BASE, 2 * PAGE_SIZE_2M);
+
+        table.remove_range(BASE, BASE + SIZE_2M).unwrap();
+        assert_eq!(mappings(&table), [(BASE + SIZE_2M, PAGE_SIZE_2M, SIZE_2M)]);
+    }
+
+    #[test]
+    fn test_unmap_whole_2m_mapping() {
+        let table = table_with(BASE, PAGE_SIZE_2M);
+
+        table
+            .remove_range(BASE - SIZE_2M, BASE + 2 * SIZE_2M)
+            .unwrap();
+        assert!(mappings(&table).is_empty());
+        assert_eq!(table.count(), 0);
+    }
+
+    #[test]
+    fn test_free_range_is_2m_aligned() {
+        let table = table_with(BASE, PAGE_SIZE);
+        table.mmap_base.store(BASE, Ordering::Release);
+
+        // The range skips the existing mapping and stays 2MB aligned
+        assert_eq!(table.find_free_range(PAGE_SIZE_2M, SIZE_2M), BASE + SIZE_2M);
+        assert_eq!(
+            table.find_free_range(PAGE_SIZE, PAGE_SIZE as u64),
+            BASE + PAGE_SIZE as u64
+        );
+    }
+
+    #[test]
+    fn test_copy_table_and_release() {
+        // Process IDs no task will have
+        let (parent_pid, child_pid) = (u64::MAX - 1, u64::MAX - 2);
+        let manager = get_mmap_manager();
+        let parent = manager.get_table(parent_pid).unwrap();
+        parent
+            .add_mapping(MemoryMapping::create(
+                BASE,
+                PAGE_SIZE_2M,
+                ProtFlags::PROT_READ,
+                MmapFlags::MAP_ANONYMOUS,
+                None,
+                0,
+            ))
+            .unwrap();
+
+        manager.copy_table(parent_pid, child_pid).unwrap();
+        let child = manager.find_table(child_pid).unwrap();
+        assert_eq!(mappings(child), [(BASE, PAGE_SIZE_2M, 0)]);
+
+        manager.release(parent_pid);
+        assert!(manager.find_table(parent_pid).is_none());
+        assert_eq!(child.count(), 1);
+        manager.release(child_pid);
+    }
+
+    #[test]
+    fn test_fault_in_needs_accessible_mapping() {
+        // A process ID no task will have
+        let pid = u64::MAX - 3;
+        let table = get_mmap_manager().get_table(pid).unwrap();
+        table
+            .add_mapping(MemoryMapping::create(
+                BASE,
+                PAGE_SIZE,
+                ProtFlags::PROT_NONE,
+                MmapFlags::MAP_ANONYMOUS,
+                None,
+                0,
+            ))
+            .unwrap();
+
+        // Neither touches the page tables
+        assert!(fault_in_page(0, pid, BASE).is_err());
+        assert!(fault_in_page(0, pid, BASE + SIZE_2M).is_err());
+        get_mmap_manager().release(pid);
+    }
+
+    #[test]
+    fn test_user_space_ranges() {
+        use crate::sched::task::USER_LIMIT;
+
+        assert!(in_user_space(BASE, PAGE_SIZE_2M));
+        assert!(in_user_space(USER_LIMIT as u64 - SIZE_2M, PAGE_SIZE_2M));
+        assert!(!in_user_space(
+            USER_LIMIT as u64 - PAGE_SIZE as u64,
+            PAGE_SIZE_2M
+        ));
+        assert!(!in_user_space(u64::MAX - PAGE_SIZE as u64, PAGE_SIZE_2M));
+    }
+}
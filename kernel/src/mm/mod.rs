@@ -42,19 +42,21 @@ use limine::request::{ExecutableAddressRequest, HhdmRequest, MemoryMapRequest};
 use spin::Mutex;
 
 pub mod allocator;
+pub mod aslr;
+pub mod kmemleak;
 pub mod mmap;
 pub mod paging;
+pub mod pcid;
 pub mod pmm;
 pub mod refcount;
 pub mod security;
 pub mod tlb;
 
-struct MemoryManagerState {
-    pmm: pmm::PhysicalMemoryManager,
-    mapper: paging::PageMapper,
-}
-
-static MEMORY_MANAGER: Mutex<Option<MemoryManagerState>> = Mutex::new(None);
+/// Kernel page mapper, stored once memory management is initialised
+///
+/// The physical memory manager it allocates page tables from is owned by
+/// `pmm::GLOBAL_PMM`.
+static KERNEL_MAPPER: Mutex<Option<paging::PageMapper>> = Mutex::new(None);
 
 /// Limine HHDM (Higher Half Direct Map) request
 /// This provides the offset for direct physical memory mapping
@@ -121,9 +123,13 @@ pub fn with_memory_managers<R, F>(f: F) -> Result<R, &'static str>
 where
     F: FnOnce(&mut pmm::PhysicalMemoryManager, &mut paging::PageMapper) -> Result<R, &'static str>,
 {
-    let mut guard = MEMORY_MANAGER.lock();
-    let state = guard.as_mut().ok_or("Memory manager not initialized")?;
-    f(&mut state.pmm, &mut state.mapper)
+    let mut pmm_guard = pmm::get_global_pmm();
+    let pmm = pmm_guard.as_mut().ok_or("Memory manager not initialized")?;
+    let mut mapper_guard = KERNEL_MAPPER.lock();
+    let mapper = mapper_guard
+        .as_mut()
+        .ok_or("Memory manager not initialized")?;
+    f(pmm, mapper)
 }
 
 /// Enable NX (No Execute) bit support in the CPU
@@ -350,11 +356,11 @@ pub fn run_memory_tests(pmm: &mut pmm::PhysicalMemoryManager, mapper: &mut pagin
 ///
 /// This function coordinates the initialization of all memory management components:
 /// 1. HHDM offset from Limine
-/// 2. CPU memory protection features (NX bit, write protection)
+/// 2. CPU memory protection features (NX bit, write protection, ASLR)
 /// 3. Physical Memory Manager (PMM)
-/// 4. Paging system with kernel section mapping
+/// 4. Paging system with kernel section mapping (huge pages where aligned)
 /// 5. Guard pages for stack/heap protection
-/// 6. Kernel heap allocator
+/// 6. Kernel heap allocator (and kmemleak tracking, if enabled)
 ///
 /// This should be called early in kernel initialization, after framebuffer setup
 /// but before any dynamic memory allocation is needed.
@@ -387,28 +393,43 @@ pub fn init_memory() {
     // Enable CPU memory protection features
     enable_nx_bit();
     enable_write_protect();
+    pcid::init_pcid_on_cpu();
+    aslr::init_aslr(kernel_virt_base);
 
     // Initialize Physical Memory Manager
-    let mut pmm = pmm::PhysicalMemoryManager::init(memory_map_response, kernel_start, kernel_end);
+    // The global PMM is the allocator's only owner; boot-time mapping
+    // below allocates through it like everything else
+    pmm::init_global_pmm(pmm::PhysicalMemoryManager::init(
+        memory_map_response,
+        kernel_start,
+        kernel_end,
+    ));
+    let mut pmm_guard = pmm::get_global_pmm();
+    let pmm = pmm_guard
+        .as_mut()
+        .expect("[MM] ERROR: Global PMM not initialized");
 
     let _total_mb = pmm.total_memory_mb();
     let _free_mb = pmm.free_memory_mb();
 
-    // Initialize global PMM for syscall access
-    // Note: We create a second PMM instance for global access
-    // This is a temporary solution until we refactor to use a single global PMM
-    let global_pmm =
-        pmm::PhysicalMemoryManager::init(memory_map_response, kernel_start, kernel_end);
-    pmm::init_global_pmm(global_pmm);
-
     // Initialize paging system
     let mut mapper = paging::PageMapper::new();
 
     // Map kernel sections with appropriate permissions
     mapper
-        .map_kernel_sections(kernel_addr_response, &mut pmm)
+        .map_kernel_sections(kernel_addr_response, pmm)
         .expect("[MM] ERROR: Failed to map kernel sections");
 
+    // Back the direct map with huge pages (fewer TLB misses and table walks)
+    match mapper.map_hhdm(memory_map_response, pmm) {
+        Ok((gb_pages, mb_pages)) => crate::serial_println!(
+            "[MM] HHDM remapped with {} x 1GB + {} x 2MB pages",
+            gb_pages,
+            mb_pages
+        ),
+        Err(e) => crate::serial_println!("[MM] HHDM huge page mapping failed: {}", e),
+    }
+
     // Define heap region (16MB heap starting at 0xFFFF_A000_0000_0000)
     let heap_start = 0xFFFF_A000_0000_0000usize;
     let heap_size = 16 * 1024 * 1024; // 16MB
@@ -428,7 +449,7 @@ pub fn init_memory() {
                 paging::PageTableFlags::PRESENT
                     | paging::PageTableFlags::WRITABLE
                     | paging::PageTableFlags::NO_EXECUTE,
-                &mut pmm,
+                pmm,
             )
             .expect("[MM] ERROR: Failed to map heap page");
 
@@ -446,12 +467,19 @@ pub fn init_memory() {
 
     // Initialize kernel heap allocator
     allocator::init_allocator(heap_start, heap_size);
+    kmemleak::init(heap_start, heap_size);
+
+    // The boot page table now holds every kernel mapping; process page
+    // tables copy their upper half from it and kernel threads run on it
+    paging::init_kernel_template(paging::get_current_cr3())
+        .expect("[MM] ERROR: Failed to initialize kernel page table template");
 
     // Run memory management tests
-    run_memory_tests(&mut pmm, &mut mapper);
+    run_memory_tests(pmm, &mut mapper);
+    drop(pmm_guard);
 
-    // Store memory managers for later use (user-mode ELF loading, etc.)
-    *MEMORY_MANAGER.lock() = Some(MemoryManagerState { pmm, mapper });
+    // Store the mapper for later use (user-mode ELF loading, etc.)
+    *KERNEL_MAPPER.lock() = Some(mapper);
 
     // Log initialization summary
     // TODO: Replace with proper logging once available
@@ -465,3 +493,44 @@ pub fn init_memory() {
     // [MM] Kernel heap: 0xFFFF_A000_0000_0000 - 0xFFFF_A000_0100_0000 (16 MB)
     // [MM] Memory management initialized successfully
 }
+
+/// Apply the ACPI SRAT memory affinity to the physical memory manager
+///
+/// Must run after `init_acpi()`. Without an SRAT every frame stays on node 0.
+pub fn init_numa() {
+    let srat = match crate::arch::x86_64::acpi::get_srat_info() {
+        Some(srat) if srat.node_count > 1 => srat,
+        _ => return,
+    };
+
+    let mut ranges = [pmm::NodeRange {
+        base: 0,
+        length: 0,
+        node: 0,
+    }; pmm::MAX_NODE_RANGES];
+    let mut count = 0;
+    for affinity in srat.memory[..srat.memory_count].iter().flatten() {
+        if count < ranges.len() {
+            ranges[count] = pmm::NodeRange {
+                base: affinity.base as PhysAddr,
+                length: affinity.length as usize,
+                node: affinity.node,
+            };
+            count += 1;
+        }
+    }
+
+    let mut global = pmm::get_global_pmm();
+    if let Some(pmm) = global.as_mut() {
+        pmm.set_numa_layout(&ranges[..count], srat.node_count);
+
+        for node in 0..pmm.node_count() {
+            crate::serial_println!(
+                "[MM] NUMA node {}: DMA32 {} MB free, Normal {} MB free",
+                node,
+                pmm.zone_free_frames(node, pmm::Zone::Dma32) * pmm::FRAME_SIZE / (1024 * 1024),
+                pmm.zone_free_frames(node, pmm::Zone::Normal) * pmm::FRAME_SIZE / (1024 * 1024)
+            );
+        }
+    }
+}
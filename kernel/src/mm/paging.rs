@@ -4,7 +4,8 @@
 #![allow(dead_code)]
 
 use crate::mm::pmm::PhysicalMemoryManager;
-use crate::mm::{phys_to_virt, PhysAddr, VirtAddr};
+use crate::mm::{phys_to_virt, virt_to_phys, PhysAddr, VirtAddr};
+use alloc::vec::Vec;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 /// Page table entry flags
@@ -68,6 +69,52 @@ impl PageTableFlags {
     }
 }
 
+/// Size of a 2MB huge page
+pub const PAGE_SIZE_2M: usize = 0x20_0000;
+
+/// Size of a 1GB huge page
+pub const PAGE_SIZE_1G: usize = 0x4000_0000;
+
+/// Mask for the physical address bits of a page table entry
+const ENTRY_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Size of a leaf mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSize {
+    /// 4KB page (PT entry)
+    Size4K,
+    /// 2MB page (PD entry with HUGE set)
+    Size2M,
+    /// 1GB page (PDPT entry with HUGE set)
+    Size1G,
+}
+
+impl PageSize {
+    /// Size in bytes
+    pub const fn bytes(self) -> usize {
+        match self {
+            PageSize::Size4K => 4096,
+            PageSize::Size2M => PAGE_SIZE_2M,
+            PageSize::Size1G => PAGE_SIZE_1G,
+        }
+    }
+
+    /// Page size a huge page is split into
+    pub const fn split_size(self) -> Option<PageSize> {
+        match self {
+            PageSize::Size4K => None,
+            PageSize::Size2M => Some(PageSize::Size4K),
+            PageSize::Size1G => Some(PageSize::Size2M),
+        }
+    }
+}
+
+/// Check whether the CPU supports 1GB pages (CPUID.80000001H:EDX.Page1GB)
+pub fn supports_1g_pages() -> bool {
+    let max_extended = core::arch::x86_64::__cpuid(0x8000_0000).eax;
+    max_extended >= 0x8000_0001 && core::arch::x86_64::__cpuid(0x8000_0001).edx & (1 << 26) != 0
+}
+
 /// Page table entry
 /// Represents a single entry in a page table
 #[repr(transparent)]
@@ -134,6 +181,11 @@ impl PageTableEntry {
         self.0
     }
 
+    /// Check if entry maps a huge page (only meaningful in a PDPT or PD)
+    pub fn is_huge(&self) -> bool {
+        (self.0 & PageTableFlags::HUGE.bits()) != 0
+    }
+
     /// Set COW flag and clear WRITABLE flag
     ///
     /// This marks the page as copy-on-write, making it read-only until
@@ -216,6 +268,25 @@ impl PageMapper {
         PageMapper { pml4 }
     }
 
+    /// Create a page mapper for an arbitrary address space
+    ///
+    /// Used to populate or inspect a process page table that is not the one
+    /// currently loaded in CR3 (exec, fork, copy_to_user on another process).
+    ///
+    /// # Arguments
+    /// * `pml4_phys` - Physical address of the target PML4
+    pub fn for_address_space(pml4_phys: PhysAddr) -> Self {
+        let pml4_virt = phys_to_virt(pml4_phys & !0xFFF);
+        let pml4 = unsafe { &mut *(pml4_virt as *mut PageTable) };
+
+        PageMapper { pml4 }
+    }
+
+    /// Physical address of the PML4 this mapper operates on
+    pub fn pml4_phys(&self) -> PhysAddr {
+        virt_to_phys(self.pml4 as *const PageTable as VirtAddr)
+    }
+
     /// Map a virtual address to a physical address with specified flags
     /// Creates intermediate page tables as needed
     ///
@@ -269,35 +340,17 @@ impl PageMapper {
             new_table
         };
 
-        // Get or create PD from PDPT
+        // Get or create PD from PDPT (splitting a 1GB page if one covers the address)
         let pdpt_virt = phys_to_virt(pdpt_phys);
         let pdpt = unsafe { &mut *(pdpt_virt as *mut PageTable) };
         let pdpt_entry = pdpt.get_entry_mut(pdpt_index);
-        let pd_phys = if pdpt_entry.is_present() {
-            pdpt_entry.addr()
-        } else {
-            let new_table = pmm.alloc_frame().ok_or("Out of physical memory")?;
-            let table_virt = phys_to_virt(new_table);
-            let table = unsafe { &mut *(table_virt as *mut PageTable) };
-            table.zero();
-            pdpt_entry.set(new_table, table_flags);
-            new_table
-        };
+        let pd_phys = next_table(pdpt_entry, PageSize::Size1G, virt_addr, table_flags, pmm)?;
 
-        // Get or create PT from PD
+        // Get or create PT from PD (splitting a 2MB page if one covers the address)
         let pd_virt = phys_to_virt(pd_phys);
         let pd = unsafe { &mut *(pd_virt as *mut PageTable) };
         let pd_entry = pd.get_entry_mut(pd_index);
-        let pt_phys = if pd_entry.is_present() {
-            pd_entry.addr()
-        } else {
-            let new_table = pmm.alloc_frame().ok_or("Out of physical memory")?;
-            let table_virt = phys_to_virt(new_table);
-            let table = unsafe { &mut *(table_virt as *mut PageTable) };
-            table.zero();
-            pd_entry.set(new_table, table_flags);
-            new_table
-        };
+        let pt_phys = next_table(pd_entry, PageSize::Size2M, virt_addr, table_flags, pmm)?;
 
         // Set final PT entry
         let pt_virt = phys_to_virt(pt_phys);
@@ -320,6 +373,140 @@ impl PageMapper {
     }
 }
 
+/// Get the table below a PDPT or PD entry, creating it if missing
+///
+/// A huge page in the way is split so the caller can map at a finer
+/// granularity inside it.
+///
+/// # Arguments
+/// * `entry` - PDPT or PD entry
+/// * `entry_size` - Size of the region the entry covers
+/// * `virt_addr` - Any address inside that region
+/// * `table_flags` - Flags for a newly created table
+/// * `pmm` - Physical memory manager for allocating tables
+fn next_table(
+    entry: &mut PageTableEntry,
+    entry_size: PageSize,
+    virt_addr: VirtAddr,
+    table_flags: PageTableFlags,
+    pmm: &mut PhysicalMemoryManager,
+) -> Result<PhysAddr, &'static str> {
+    if entry.is_present() {
+        if entry.is_huge() {
+            let base = virt_addr & !(entry_size.bytes() - 1);
+            return split_huge_entry(entry, entry_size, base, pmm);
+        }
+        return Ok(entry.addr());
+    }
+
+    let new_table = pmm.alloc_frame().ok_or("Out of physical memory")?;
+    let table_virt = phys_to_virt(new_table);
+    let table = unsafe { &mut *(table_virt as *mut PageTable) };
+    table.zero();
+    entry.set(new_table, table_flags);
+    Ok(new_table)
+}
+
+/// Free a page table detached from an entry mapping `size`, and the page
+/// tables below it
+///
+/// Only page tables are freed, never the frames they map.
+fn free_detached_table(table_phys: PhysAddr, size: PageSize, pmm: &mut PhysicalMemoryManager) {
+    if size == PageSize::Size1G {
+        let pd = unsafe { &*(phys_to_virt(table_phys) as *const PageTable) };
+        for i in 0..512 {
+            let entry = pd.get_entry(i);
+            if entry.is_present() && !entry.is_huge() {
+                pmm.free_frame(entry.addr());
+            }
+        }
+    }
+    pmm.free_frame(table_phys);
+}
+
+/// Allocate a table of the next smaller pages mapping the same memory, with
+/// the same flags, as the huge page entry `raw` of size `size`
+fn build_split_table(
+    raw: u64,
+    size: PageSize,
+    pmm: &mut PhysicalMemoryManager,
+) -> Result<PhysAddr, &'static str> {
+    let child = size.split_size().ok_or("Cannot split a 4KB page")?;
+    let phys_base = (raw & ENTRY_ADDR_MASK) as PhysAddr & !(size.bytes() - 1);
+
+    let mut child_flags = raw & !ENTRY_ADDR_MASK;
+    if child == PageSize::Size4K {
+        child_flags &= !PageTableFlags::HUGE.bits();
+    }
+
+    let table_phys = pmm.alloc_frame().ok_or("Out of physical memory")?;
+    let table = unsafe { &mut *(phys_to_virt(table_phys) as *mut PageTable) };
+    for i in 0..512 {
+        table
+            .get_entry_mut(i)
+            .set(phys_base + i * child.bytes(), PageTableFlags(child_flags));
+    }
+
+    Ok(table_phys)
+}
+
+/// Replace a huge page with a table of smaller pages mapping the same memory
+///
+/// The smaller pages inherit the huge page's flags, so access rights do not
+/// change. Copy-on-write sharing is tracked per head frame; the share count
+/// of the huge page is copied to the head frame of every new page.
+///
+/// # Arguments
+/// * `entry` - PDPT or PD entry holding the huge page
+/// * `size` - Size of the huge page
+/// * `virt_base` - Virtual address the huge page maps
+/// * `pmm` - Physical memory manager for allocating the new table
+///
+/// # Returns
+/// Physical address of the new table
+fn split_huge_entry(
+    entry: &mut PageTableEntry,
+    size: PageSize,
+    virt_base: VirtAddr,
+    pmm: &mut PhysicalMemoryManager,
+) -> Result<PhysAddr, &'static str> {
+    use crate::mm::refcount::PAGE_REFCOUNT;
+
+    let child = size.split_size().ok_or("Cannot split a 4KB page")?;
+    let raw = entry.raw();
+    let phys_base = entry.addr() & !(size.bytes() - 1);
+    let table_phys = build_split_table(raw, size, pmm)?;
+
+    if raw & PageTableFlags::COW.bits() != 0 {
+        let shares = PAGE_REFCOUNT.get_refcount(phys_base);
+        for i in 1..512 {
+            for _ in 1..shares {
+                PAGE_REFCOUNT.inc_refcount(phys_base + i * child.bytes());
+            }
+        }
+    }
+
+    // Permissions are enforced by the new leaves
+    let user = raw & PageTableFlags::USER.bits();
+    entry.set(
+        table_phys,
+        PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags(user),
+    );
+
+    unsafe {
+        crate::mm::tlb::tlb_shootdown(virt_base, virt_base + size.bytes());
+    }
+
+    crate::serial_println!(
+        "[PAGING] Split {}KB page at {:#x} into {}KB pages",
+        size.bytes() / 1024,
+        virt_base,
+        child.bytes() / 1024
+    );
+
+    Ok(table_phys)
+}
+
 /// Invalidate TLB entry for a single page
 /// Uses the invlpg instruction to flush the TLB entry for the given virtual address
 fn invlpg(virt_addr: VirtAddr) {
@@ -364,6 +551,9 @@ impl PageMapper {
         if !pdpt_entry.is_present() {
             return Err("Page not mapped (PDPT)");
         }
+        if pdpt_entry.is_huge() {
+            return Err("Page is part of a huge page (use unmap_range)");
+        }
 
         let pd_phys = pdpt_entry.addr();
         let pd_virt = phys_to_virt(pd_phys);
@@ -373,6 +563,9 @@ impl PageMapper {
         if !pd_entry.is_present() {
             return Err("Page not mapped (PD)");
         }
+        if pd_entry.is_huge() {
+            return Err("Page is part of a huge page (use unmap_range)");
+        }
 
         let pt_phys = pd_entry.addr();
         let pt_virt = phys_to_virt(pt_phys);
@@ -396,6 +589,271 @@ impl PageMapper {
     }
 }
 
+/// Result of looking up the leaf entry for an address
+enum Leaf {
+    /// Nothing is mapped; the hole spans the aligned region of this size
+    Missing(PageSize),
+    /// Leaf entry and the size of the page it maps
+    Mapped(&'static mut PageTableEntry, PageSize),
+}
+
+impl PageMapper {
+    /// Map a 2MB or 1GB page
+    ///
+    /// Any existing mapping of the range is replaced. Page tables that were
+    /// mapping the range at a finer granularity are freed; the frames they
+    /// map are not. Tables the bootloader built are not the PMM's to hand
+    /// out, so freeing them is a no-op.
+    ///
+    /// # Arguments
+    /// * `virt_addr` - Virtual address (aligned to `size`)
+    /// * `phys_addr` - Physical address (aligned to `size`)
+    /// * `size` - `PageSize::Size2M` or `PageSize::Size1G`
+    /// * `flags` - Page table flags (HUGE is added automatically)
+    /// * `pmm` - Physical memory manager for allocating page tables
+    pub fn map_huge_page(
+        &mut self,
+        virt_addr: VirtAddr,
+        phys_addr: PhysAddr,
+        size: PageSize,
+        flags: PageTableFlags,
+        pmm: &mut PhysicalMemoryManager,
+    ) -> Result<(), &'static str> {
+        if size == PageSize::Size4K {
+            return self.map_page(virt_addr, phys_addr, flags, pmm);
+        }
+        if size == PageSize::Size1G && !supports_1g_pages() {
+            return Err("1GB pages not supported by this CPU");
+        }
+        if !virt_addr.is_multiple_of(size.bytes()) || !phys_addr.is_multiple_of(size.bytes()) {
+            return Err("Address not aligned to huge page size");
+        }
+
+        let pml4_index = (virt_addr >> 39) & 0x1FF;
+        let pdpt_index = (virt_addr >> 30) & 0x1FF;
+        let pd_index = (virt_addr >> 21) & 0x1FF;
+
+        let user_flag = PageTableFlags(flags.bits() & PageTableFlags::USER.bits());
+        let table_flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | user_flag;
+
+        let pml4_entry = self.pml4.get_entry_mut(pml4_index);
+        let pdpt_phys = if pml4_entry.is_present() {
+            pml4_entry.addr()
+        } else {
+            let new_table = alloc_page_table(pmm)?;
+            pml4_entry.set(new_table, table_flags);
+            new_table
+        };
+
+        let pdpt = unsafe { &mut *(phys_to_virt(pdpt_phys) as *mut PageTable) };
+        let entry = if size == PageSize::Size1G {
+            pdpt.get_entry_mut(pdpt_index)
+        } else {
+            let pdpt_entry = pdpt.get_entry_mut(pdpt_index);
+            let pd_phys = next_table(pdpt_entry, PageSize::Size1G, virt_addr, table_flags, pmm)?;
+            let pd = unsafe { &mut *(phys_to_virt(pd_phys) as *mut PageTable) };
+            pd.get_entry_mut(pd_index)
+        };
+
+        let was_present = entry.is_present();
+        let detached = (was_present && !entry.is_huge()).then(|| entry.addr());
+        entry.set(phys_addr, flags | PageTableFlags::HUGE);
+
+        if was_present {
+            unsafe {
+                crate::mm::tlb::tlb_shootdown(virt_addr, virt_addr + size.bytes());
+            }
+        }
+
+        // No CPU can walk the old tables any more
+        if let Some(table) = detached {
+            free_detached_table(table, size, pmm);
+        }
+
+        Ok(())
+    }
+
+    /// Split the huge page covering an address, if any
+    ///
+    /// A 1GB page is split all the way down to 4KB pages for the 2MB region
+    /// containing `virt_addr`.
+    ///
+    /// # Returns
+    /// `true` if a huge page was split
+    pub fn split_huge_page(
+        &mut self,
+        virt_addr: VirtAddr,
+        pmm: &mut PhysicalMemoryManager,
+    ) -> Result<bool, &'static str> {
+        let mut split = false;
+        while let Leaf::Mapped(entry, size) = self.leaf(virt_addr) {
+            if size == PageSize::Size4K {
+                break;
+            }
+            let base = virt_addr & !(size.bytes() - 1);
+            split_huge_entry(entry, size, base, pmm)?;
+            split = true;
+        }
+        Ok(split)
+    }
+
+    /// Unmap every page in a range
+    ///
+    /// Huge pages that are only partly covered are split first.
+    ///
+    /// # Arguments
+    /// * `start` - Start of the range (4KB aligned)
+    /// * `end` - End of the range (exclusive, 4KB aligned)
+    /// * `pmm` - Physical memory manager for allocating split tables
+    ///
+    /// # Returns
+    /// The physical frames that were mapped in the range, with their size
+    pub fn unmap_range(
+        &mut self,
+        start: VirtAddr,
+        end: VirtAddr,
+        pmm: &mut PhysicalMemoryManager,
+    ) -> Result<Vec<(PhysAddr, PageSize)>, &'static str> {
+        let mut unmapped = Vec::new();
+        self.for_each_leaf(start, end, pmm, |entry, size| {
+            unmapped.push((entry.addr() & !(size.bytes() - 1), size));
+            entry.clear();
+        })?;
+        Ok(unmapped)
+    }
+
+    /// Change the flags of every mapped page in a range
+    ///
+    /// Huge pages that are only partly covered are split first. Pages that
+    /// are copy-on-write stay read-only and keep their COW marking.
+    ///
+    /// # Arguments
+    /// * `start` - Start of the range (4KB aligned)
+    /// * `end` - End of the range (exclusive, 4KB aligned)
+    /// * `flags` - New leaf flags
+    /// * `pmm` - Physical memory manager for allocating split tables
+    ///
+    /// # Returns
+    /// Number of leaf entries updated
+    pub fn protect_range(
+        &mut self,
+        start: VirtAddr,
+        end: VirtAddr,
+        flags: PageTableFlags,
+        pmm: &mut PhysicalMemoryManager,
+    ) -> Result<usize, &'static str> {
+        let mut updated = 0;
+        self.for_each_leaf(start, end, pmm, |entry, size| {
+            let mut new_flags = flags.bits();
+            if size != PageSize::Size4K {
+                new_flags |= PageTableFlags::HUGE.bits();
+            }
+            if entry.is_cow() {
+                new_flags =
+                    (new_flags & !PageTableFlags::WRITABLE.bits()) | PageTableFlags::COW.bits();
+            }
+            let phys = entry.addr() & !(size.bytes() - 1);
+            entry.set(phys, PageTableFlags(new_flags));
+            updated += 1;
+        })?;
+        Ok(updated)
+    }
+
+    /// Apply `f` to every leaf entry lying entirely inside `[start, end)`
+    ///
+    /// Partly covered huge pages are split so the range boundaries fall on
+    /// leaf boundaries. The TLB is flushed for the whole range afterwards.
+    fn for_each_leaf<F>(
+        &mut self,
+        start: VirtAddr,
+        end: VirtAddr,
+        pmm: &mut PhysicalMemoryManager,
+        mut f: F,
+    ) -> Result<(), &'static str>
+    where
+        F: FnMut(&mut PageTableEntry, PageSize),
+    {
+        if start % 4096 != 0 || end % 4096 != 0 {
+            return Err("Address not aligned to 4KB");
+        }
+
+        let mut virt = start;
+        while virt < end {
+            match self.leaf(virt) {
+                Leaf::Missing(hole) => {
+                    virt = (virt & !(hole.bytes() - 1)) + hole.bytes();
+                }
+                Leaf::Mapped(entry, size) => {
+                    let base = virt & !(size.bytes() - 1);
+                    if base < start || base + size.bytes() > end {
+                        // Only part of this huge page is affected
+                        split_huge_entry(entry, size, base, pmm)?;
+                        continue;
+                    }
+                    f(entry, size);
+                    virt = base + size.bytes();
+                }
+            }
+        }
+
+        unsafe {
+            crate::mm::tlb::tlb_shootdown(start, end);
+        }
+
+        Ok(())
+    }
+
+    /// Check that nothing is mapped in the aligned region of `size` at `virt_addr`
+    ///
+    /// A `true` result means a page of `size` can be mapped there without
+    /// replacing existing page tables.
+    pub fn is_unmapped(&mut self, virt_addr: VirtAddr, size: PageSize) -> bool {
+        match self.leaf(virt_addr & !(size.bytes() - 1)) {
+            Leaf::Missing(hole) => hole.bytes() >= size.bytes(),
+            Leaf::Mapped(..) => false,
+        }
+    }
+
+    /// Find the leaf entry mapping an address
+    fn leaf(&mut self, virt_addr: VirtAddr) -> Leaf {
+        let pml4_index = (virt_addr >> 39) & 0x1FF;
+        let pdpt_index = (virt_addr >> 30) & 0x1FF;
+        let pd_index = (virt_addr >> 21) & 0x1FF;
+        let pt_index = (virt_addr >> 12) & 0x1FF;
+
+        let pml4_entry = self.pml4.get_entry(pml4_index);
+        if !pml4_entry.is_present() {
+            // A missing PML4 entry is a 512GB hole; step in 1GB units
+            return Leaf::Missing(PageSize::Size1G);
+        }
+
+        let pdpt = unsafe { &mut *(phys_to_virt(pml4_entry.addr()) as *mut PageTable) };
+        let pdpt_entry = pdpt.get_entry_mut(pdpt_index);
+        if !pdpt_entry.is_present() {
+            return Leaf::Missing(PageSize::Size1G);
+        }
+        if pdpt_entry.is_huge() {
+            return Leaf::Mapped(pdpt_entry, PageSize::Size1G);
+        }
+
+        let pd = unsafe { &mut *(phys_to_virt(pdpt_entry.addr()) as *mut PageTable) };
+        let pd_entry = pd.get_entry_mut(pd_index);
+        if !pd_entry.is_present() {
+            return Leaf::Missing(PageSize::Size2M);
+        }
+        if pd_entry.is_huge() {
+            return Leaf::Mapped(pd_entry, PageSize::Size2M);
+        }
+
+        let pt = unsafe { &mut *(phys_to_virt(pd_entry.addr()) as *mut PageTable) };
+        let pt_entry = pt.get_entry_mut(pt_index);
+        if !pt_entry.is_present() {
+            return Leaf::Missing(PageSize::Size4K);
+        }
+        Leaf::Mapped(pt_entry, PageSize::Size4K)
+    }
+}
+
 impl PageMapper {
     /// Translate virtual address to physical address
     /// Walks the page tables to find the physical address
@@ -607,6 +1065,9 @@ impl PageMapper {
 
     /// Map a range of virtual addresses to physical addresses
     /// Helper function for mapping kernel sections
+    ///
+    /// 2MB pages are used wherever the virtual and physical addresses are
+    /// both 2MB aligned and the whole huge page lies inside the range.
     fn map_range(
         &mut self,
         start_virt: VirtAddr,
@@ -628,6 +1089,15 @@ impl PageMapper {
             let offset = virt - kernel_base_virt;
             let phys = kernel_base_phys + offset;
 
+            if virt.is_multiple_of(PAGE_SIZE_2M)
+                && phys.is_multiple_of(PAGE_SIZE_2M)
+                && virt + PAGE_SIZE_2M <= end
+            {
+                self.map_huge_page(virt, phys, PageSize::Size2M, flags, pmm)?;
+                virt += PAGE_SIZE_2M;
+                continue;
+            }
+
             // Map the page
             self.map_page(virt, phys, flags, pmm)?;
 
@@ -636,6 +1106,77 @@ impl PageMapper {
 
         Ok(())
     }
+
+    /// Map usable RAM in the higher-half direct map with huge pages
+    ///
+    /// Limine builds the HHDM itself; this upgrades every 1GB (if supported)
+    /// or 2MB aligned chunk of usable memory to a single huge page. The
+    /// unaligned edges keep the bootloader's mappings.
+    ///
+    /// # Arguments
+    /// * `memory_map` - Limine memory map
+    /// * `pmm` - Physical memory manager for allocating page tables
+    ///
+    /// # Returns
+    /// `(gigabyte_pages, two_megabyte_pages)` mapped
+    pub fn map_hhdm(
+        &mut self,
+        memory_map: &limine::response::MemoryMapResponse,
+        pmm: &mut PhysicalMemoryManager,
+    ) -> Result<(usize, usize), &'static str> {
+        use limine::memory_map::EntryType;
+
+        let flags = PageTableFlags::PRESENT
+            | PageTableFlags::WRITABLE
+            | PageTableFlags::NO_EXECUTE
+            | PageTableFlags::GLOBAL;
+        let use_1g = supports_1g_pages();
+        let mut counts = (0, 0);
+
+        for entry in memory_map.entries() {
+            if entry.entry_type != EntryType::USABLE {
+                continue;
+            }
+
+            let region_end = entry.base as usize + entry.length as usize;
+            let mut phys = (entry.base as usize + PAGE_SIZE_2M - 1) & !(PAGE_SIZE_2M - 1);
+
+            while phys + PAGE_SIZE_2M <= region_end {
+                let size = if use_1g
+                    && phys.is_multiple_of(PAGE_SIZE_1G)
+                    && phys + PAGE_SIZE_1G <= region_end
+                {
+                    PageSize::Size1G
+                } else {
+                    PageSize::Size2M
+                };
+                let virt = phys_to_virt(phys);
+
+                // Leave chunks the bootloader already mapped with a large enough page
+                let already_huge = match self.leaf(virt) {
+                    Leaf::Mapped(entry, current) => {
+                        current.bytes() >= size.bytes()
+                            && entry.addr() & !(current.bytes() - 1)
+                                == phys & !(current.bytes() - 1)
+                    }
+                    Leaf::Missing(_) => false,
+                };
+
+                if !already_huge {
+                    self.map_huge_page(virt, phys, size, flags, pmm)?;
+                    if size == PageSize::Size1G {
+                        counts.0 += 1;
+                    } else {
+                        counts.1 += 1;
+                    }
+                }
+
+                phys += size.bytes();
+            }
+        }
+
+        Ok(counts)
+    }
 }
 
 impl PageMapper {
@@ -971,6 +1512,19 @@ pub fn free_page_table_hierarchy(pml4_phys: PhysAddr, pmm: &mut PhysicalMemoryMa
         pml4_phys
     );
 
+    if Some(pml4_phys) == get_kernel_template() {
+        crate::serial_println!("[PAGING] Refusing to free the kernel page table template");
+        return;
+    }
+
+    // Never pull the page table out from under the running CPU
+    if get_current_cr3() == pml4_phys {
+        if let Some(template) = get_kernel_template() {
+            crate::mm::pcid::switch_address_space(template);
+        }
+    }
+    crate::mm::pcid::release_address_space(pml4_phys);
+
     let pml4_virt = phys_to_virt(pml4_phys);
     let pml4 = unsafe { &*(pml4_virt as *const PageTable) };
 
@@ -1137,9 +1691,84 @@ pub fn mark_user_pages_cow(pml4_phys: PhysAddr) -> Result<usize, &'static str> {
         pml4_phys
     );
 
+    // Pages that were writable are now read-only: stale writable TLB entries
+    // would let writes bypass the COW fault
+    if cow_count > 0 {
+        crate::mm::pcid::invalidate_user_tlb();
+        if get_current_cr3() == pml4_phys {
+            unsafe { crate::mm::tlb::flush_all() };
+        }
+    }
+
     Ok(cow_count)
 }
 
+/// Count the bytes of user memory present in a page table hierarchy
+///
+/// Walks the lower half of the PML4 and adds up every present user page,
+/// counting huge pages at their full size. This is the resident set of the
+/// address space: pages that were never touched or were unmapped are not
+/// counted.
+///
+/// The caller must keep the page tables from being freed during the walk,
+/// e.g. by holding the PMM lock (see `Task::resident_memory_usage`).
+///
+/// # Arguments
+/// * `pml4_phys` - Physical address of the PML4 (root page table)
+pub fn user_resident_bytes(pml4_phys: PhysAddr) -> usize {
+    let is_user_page = |entry: &PageTableEntry| {
+        entry.is_present() && (entry.raw() & PageTableFlags::USER.bits()) != 0
+    };
+    let is_huge = |entry: &PageTableEntry| (entry.raw() & PageTableFlags::HUGE.bits()) != 0;
+
+    let pml4 = unsafe { &*(phys_to_virt(pml4_phys) as *const PageTable) };
+    let mut bytes = 0;
+
+    // Only user space (lower half): indices 0-255
+    for pml4_idx in 0..256 {
+        let pml4_entry = pml4.get_entry(pml4_idx);
+        if !pml4_entry.is_present() {
+            continue;
+        }
+        let pdpt = unsafe { &*(phys_to_virt(pml4_entry.addr()) as *const PageTable) };
+
+        for pdpt_idx in 0..512 {
+            let pdpt_entry = pdpt.get_entry(pdpt_idx);
+            if !pdpt_entry.is_present() {
+                continue;
+            }
+            if is_huge(pdpt_entry) {
+                if is_user_page(pdpt_entry) {
+                    bytes += PAGE_SIZE_1G;
+                }
+                continue;
+            }
+            let pd = unsafe { &*(phys_to_virt(pdpt_entry.addr()) as *const PageTable) };
+
+            for pd_idx in 0..512 {
+                let pd_entry = pd.get_entry(pd_idx);
+                if !pd_entry.is_present() {
+                    continue;
+                }
+                if is_huge(pd_entry) {
+                    if is_user_page(pd_entry) {
+                        bytes += PAGE_SIZE_2M;
+                    }
+                    continue;
+                }
+                let pt = unsafe { &*(phys_to_virt(pd_entry.addr()) as *const PageTable) };
+
+                bytes += (0..512)
+                    .filter(|&pt_idx| is_user_page(pt.get_entry(pt_idx)))
+                    .count()
+                    * PageSize::Size4K.bytes();
+            }
+        }
+    }
+
+    bytes
+}
+
 /// Set CR3 to switch to a different page table
 ///
 /// # Arguments
@@ -1282,3 +1911,71 @@ impl PageMapper {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mm::pmm::{test_pmm, Zone};
+
+    const HUGE_BASE: PhysAddr = 0x4000_0000;
+
+    fn table_at(phys: PhysAddr) -> &'static PageTable {
+        unsafe { &*(phys_to_virt(phys) as *const PageTable) }
+    }
+
+    #[test]
+    fn test_split_2m_page_into_4k_pages() {
+        let (_guard, mut pmm) = test_pmm();
+        let flags = PageTableFlags::PRESENT
+            | PageTableFlags::WRITABLE
+            | PageTableFlags::USER
+            | PageTableFlags::NO_EXECUTE;
+        let raw = HUGE_BASE as u64 | (flags | PageTableFlags::HUGE).bits();
+
+        let table = table_at(build_split_table(raw, PageSize::Size2M, &mut pmm).unwrap());
+        for i in 0..512 {
+            let entry = table.get_entry(i);
+            assert_eq!(entry.addr(), HUGE_BASE + i * 4096);
+            assert_eq!(entry.raw() & !ENTRY_ADDR_MASK, flags.bits());
+        }
+    }
+
+    #[test]
+    fn test_split_1g_page_into_2m_pages() {
+        let (_guard, mut pmm) = test_pmm();
+        let flags = PageTableFlags::PRESENT | PageTableFlags::HUGE;
+        let raw = HUGE_BASE as u64 | flags.bits();
+
+        let table = table_at(build_split_table(raw, PageSize::Size1G, &mut pmm).unwrap());
+        for i in 0..512 {
+            let entry = table.get_entry(i);
+            assert!(entry.is_huge());
+            assert_eq!(entry.addr(), HUGE_BASE + i * PAGE_SIZE_2M);
+        }
+    }
+
+    #[test]
+    fn test_split_4k_page_fails() {
+        let (_guard, mut pmm) = test_pmm();
+        let raw = PageTableFlags::PRESENT.bits();
+        assert!(build_split_table(raw, PageSize::Size4K, &mut pmm).is_err());
+    }
+
+    #[test]
+    fn test_detached_tables_are_freed() {
+        let (_guard, mut pmm) = test_pmm();
+        let free = pmm.zone_free_frames(0, Zone::Dma32);
+
+        // A page directory with one page table and one 2MB page under it
+        let pd_phys = alloc_page_table(&mut pmm).unwrap();
+        let pt_phys = alloc_page_table(&mut pmm).unwrap();
+        let pd = unsafe { &mut *(phys_to_virt(pd_phys) as *mut PageTable) };
+        pd.get_entry_mut(0)
+            .set(pt_phys, PageTableFlags::PRESENT | PageTableFlags::WRITABLE);
+        pd.get_entry_mut(1)
+            .set(HUGE_BASE, PageTableFlags::PRESENT | PageTableFlags::HUGE);
+
+        free_detached_table(pd_phys, PageSize::Size1G, &mut pmm);
+        assert_eq!(pmm.zone_free_frames(0, Zone::Dma32), free);
+    }
+}
@@ -0,0 +1,294 @@
+//! Address Space Switching and PCID Management
+//!
+//! Every user process owns a private PML4 whose upper half is copied from the
+//! kernel template, so switching tasks means loading a different CR3. Without
+//! help from the CPU each CR3 write flushes every non-global TLB entry. When
+//! the CPU supports Process-Context Identifiers (PCIDs), each CPU hands out a
+//! small set of PCIDs to recently used address spaces and reloads CR3 with the
+//! "no flush" bit set, so translations survive a round trip through another
+//! process.
+//!
+//! Cached translations in an inactive PCID are not reached by `invlpg` or by
+//! shootdown IPIs (both only affect the active PCID). Any change to user
+//! mappings therefore bumps a global TLB generation; a PCID whose recorded
+//! generation is older than the global one is flushed the next time it is
+//! loaded.
+
+use crate::config::MAX_CPUS;
+use crate::mm::PhysAddr;
+use crate::sync::SpinLock;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// CR4.PCIDE - enables process-context identifiers
+const CR4_PCIDE: u64 = 1 << 17;
+
+/// CPUID.01H:ECX bit advertising PCID support
+const CPUID_ECX_PCID: u32 = 1 << 17;
+
+/// CR3 bit 63 - when PCIDE is set, do not flush the TLB entries of the PCID
+const CR3_NOFLUSH: u64 = 1 << 63;
+
+/// Mask for the PCID field in CR3 (bits 0-11)
+const CR3_PCID_MASK: u64 = 0xFFF;
+
+/// Mask for the PML4 physical address in CR3
+const CR3_ADDR_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Number of PCIDs handed out per CPU (PCID 0 is never used for a process)
+const PCIDS_PER_CPU: usize = 8;
+
+/// Whether PCIDs are enabled (all CPUs share the same setting)
+static PCID_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Global generation of user-space mappings
+///
+/// Incremented whenever user mappings change in a way that requires TLB
+/// invalidation (unmap, permission downgrade, remap).
+static USER_TLB_GEN: AtomicU64 = AtomicU64::new(1);
+
+/// Number of CR3 loads that kept the TLB (no-flush)
+static NOFLUSH_SWITCHES: AtomicU64 = AtomicU64::new(0);
+
+/// Number of CR3 loads that flushed the TLB of the target PCID
+static FLUSH_SWITCHES: AtomicU64 = AtomicU64::new(0);
+
+/// A PCID slot owned by one address space on one CPU
+#[derive(Debug, Clone, Copy)]
+struct PcidSlot {
+    /// PML4 physical address of the address space (0 = free)
+    pml4: PhysAddr,
+    /// Value of USER_TLB_GEN when this PCID was last flushed
+    tlb_gen: u64,
+}
+
+/// Per-CPU PCID assignment table
+///
+/// Slot `i` corresponds to PCID `i + 1`.
+#[derive(Debug)]
+pub(crate) struct PcidCache {
+    slots: [PcidSlot; PCIDS_PER_CPU],
+    /// Next slot to evict when no slot matches
+    next_victim: usize,
+}
+
+impl PcidCache {
+    pub(crate) const fn new() -> Self {
+        Self {
+            slots: [PcidSlot {
+                pml4: 0,
+                tlb_gen: 0,
+            }; PCIDS_PER_CPU],
+            next_victim: 0,
+        }
+    }
+
+    /// Pick a PCID for an address space
+    ///
+    /// # Arguments
+    /// * `pml4` - PML4 physical address of the address space being loaded
+    /// * `current_gen` - Current global TLB generation
+    ///
+    /// # Returns
+    /// `(pcid, needs_flush)` - the PCID to load and whether its cached
+    /// translations must be discarded
+    pub(crate) fn assign(&mut self, pml4: PhysAddr, current_gen: u64) -> (u16, bool) {
+        if let Some(idx) = self.slots.iter().position(|s| s.pml4 == pml4) {
+            let slot = &mut self.slots[idx];
+            let stale = slot.tlb_gen != current_gen;
+            slot.tlb_gen = current_gen;
+            return ((idx + 1) as u16, stale);
+        }
+
+        let idx = self.next_victim;
+        self.next_victim = (self.next_victim + 1) % PCIDS_PER_CPU;
+        self.slots[idx] = PcidSlot {
+            pml4,
+            tlb_gen: current_gen,
+        };
+        ((idx + 1) as u16, true)
+    }
+
+    /// Drop any slot belonging to an address space
+    pub(crate) fn forget(&mut self, pml4: PhysAddr) {
+        for slot in self.slots.iter_mut() {
+            if slot.pml4 == pml4 {
+                slot.pml4 = 0;
+                slot.tlb_gen = 0;
+            }
+        }
+    }
+}
+
+/// Per-CPU PCID tables
+static CPU_PCIDS: [SpinLock<PcidCache>; MAX_CPUS] =
+    [const { SpinLock::new(PcidCache::new()) }; MAX_CPUS];
+
+/// Read the raw CR3 value
+#[inline]
+fn read_cr3() -> u64 {
+    let cr3: u64;
+    unsafe {
+        core::arch::asm!("mov {}, cr3", out(reg) cr3, options(nostack, preserves_flags));
+    }
+    cr3
+}
+
+/// Write a raw CR3 value
+#[inline]
+unsafe fn write_cr3(value: u64) {
+    core::arch::asm!("mov cr3, {}", in(reg) value, options(nostack, preserves_flags));
+}
+
+/// Enable PCIDs on the calling CPU if supported
+///
+/// Must be called on every CPU (BSP from `init_memory`, APs from their entry
+/// path) while CR3 still carries PCID 0, before the first task switch.
+pub fn init_pcid_on_cpu() {
+    let cpuid = core::arch::x86_64::__cpuid(1);
+    if cpuid.ecx & CPUID_ECX_PCID == 0 {
+        return;
+    }
+
+    // CR4.PCIDE may only be set while CR3[11:0] == 0
+    let cr3 = read_cr3();
+    if cr3 & CR3_PCID_MASK != 0 {
+        unsafe { write_cr3(cr3 & CR3_ADDR_MASK) };
+    }
+
+    unsafe {
+        let mut cr4: u64;
+        core::arch::asm!("mov {}, cr4", out(reg) cr4, options(nostack, preserves_flags));
+        cr4 |= CR4_PCIDE;
+        core::arch::asm!("mov cr4, {}", in(reg) cr4, options(nostack, preserves_flags));
+    }
+
+    if !PCID_ENABLED.swap(true, Ordering::AcqRel) {
+        crate::serial_println!("[MM] PCID enabled ({} per CPU)", PCIDS_PER_CPU);
+    }
+}
+
+/// Check whether PCIDs are in use
+pub fn pcid_enabled() -> bool {
+    PCID_ENABLED.load(Ordering::Acquire)
+}
+
+/// Record that user-space mappings changed
+///
+/// Inactive PCIDs on every CPU are flushed the next time they are loaded.
+/// The active PCID must still be flushed by the caller (invlpg/shootdown).
+pub fn invalidate_user_tlb() {
+    USER_TLB_GEN.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Forget an address space that is about to be freed
+///
+/// Its PML4 frame may be reused for a new address space, so any PCID still
+/// tagged with it must not be loaded without a flush.
+pub fn release_address_space(pml4: PhysAddr) {
+    for cache in CPU_PCIDS.iter() {
+        cache.lock().forget(pml4);
+    }
+    invalidate_user_tlb();
+}
+
+/// Load an address space on the current CPU
+///
+/// Called by the scheduler right before `context_switch`. A zero `pml4`
+/// keeps the current address space.
+///
+/// # Arguments
+/// * `pml4` - Physical address of the PML4 to load
+pub fn switch_address_space(pml4: PhysAddr) {
+    if pml4 == 0 {
+        return;
+    }
+
+    let current = read_cr3();
+
+    if !pcid_enabled() {
+        if (current & CR3_ADDR_MASK) as usize != pml4 {
+            unsafe { write_cr3(pml4 as u64) };
+        }
+        return;
+    }
+
+    let cpu_id = crate::arch::x86_64::smp::percpu::percpu_current().id;
+    if cpu_id >= MAX_CPUS {
+        unsafe { write_cr3(pml4 as u64) };
+        return;
+    }
+
+    let tlb_gen = USER_TLB_GEN.load(Ordering::Acquire);
+    let (pcid, needs_flush) = CPU_PCIDS[cpu_id].lock().assign(pml4, tlb_gen);
+    let target = pml4 as u64 | pcid as u64;
+
+    if !needs_flush && current & !CR3_NOFLUSH == target {
+        return;
+    }
+
+    if needs_flush {
+        FLUSH_SWITCHES.fetch_add(1, Ordering::Relaxed);
+        unsafe { write_cr3(target) };
+    } else {
+        NOFLUSH_SWITCHES.fetch_add(1, Ordering::Relaxed);
+        unsafe { write_cr3(target | CR3_NOFLUSH) };
+    }
+}
+
+/// Get (no-flush, flushing) CR3 switch counters
+pub fn switch_stats() -> (u64, u64) {
+    (
+        NOFLUSH_SWITCHES.load(Ordering::Relaxed),
+        FLUSH_SWITCHES.load(Ordering::Relaxed),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assign_reuses_slot() {
+        let mut cache = PcidCache::new();
+        let (a, flush_a) = cache.assign(0x1000, 1);
+        assert!(flush_a);
+        let (b, flush_b) = cache.assign(0x1000, 1);
+        assert_eq!(a, b);
+        assert!(!flush_b);
+    }
+
+    #[test]
+    fn test_stale_generation_flushes() {
+        let mut cache = PcidCache::new();
+        let (a, _) = cache.assign(0x1000, 1);
+        let (b, flush) = cache.assign(0x1000, 2);
+        assert_eq!(a, b);
+        assert!(flush);
+    }
+
+    #[test]
+    fn test_eviction_round_robin() {
+        let mut cache = PcidCache::new();
+        for i in 0..PCIDS_PER_CPU {
+            let (pcid, _) = cache.assign((i + 1) * 0x1000, 1);
+            assert_eq!(pcid as usize, i + 1);
+            assert_ne!(pcid, 0);
+        }
+        // Ninth address space evicts PCID 1
+        let (pcid, flush) = cache.assign(0x100_0000, 1);
+        assert_eq!(pcid, 1);
+        assert!(flush);
+        // Evicted address space needs a fresh flush
+        let (_, flush) = cache.assign(0x1000, 1);
+        assert!(flush);
+    }
+
+    #[test]
+    fn test_forget() {
+        let mut cache = PcidCache::new();
+        cache.assign(0x1000, 1);
+        cache.forget(0x1000);
+        let (_, flush) = cache.assign(0x1000, 1);
+        assert!(flush);
+    }
+}
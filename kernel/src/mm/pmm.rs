@@ -1,5 +1,10 @@
 // Physical Memory Manager
 // Manages physical memory frames (4KB blocks)
+//
+// Frames are grouped by NUMA node and by zone (DMA32 below 4 GiB, Normal
+// above) and handed out by a binary buddy allocator with one set of free
+// lists per (node, zone). A bitmap still records the state of every frame,
+// so single frames can be freed without knowing the block they came from.
 
 #![allow(dead_code)]
 
@@ -10,22 +15,122 @@ use limine::response::MemoryMapResponse;
 /// Size of a physical frame (4KB page)
 pub const FRAME_SIZE: usize = 4096;
 
+/// Number of buddy orders (order 18 = 2^18 frames = 1 GiB)
+pub const NUM_ORDERS: usize = 19;
+
+/// Maximum number of NUMA nodes tracked by the allocator
+pub const MAX_NUMA_NODES: usize = 8;
+
+/// Maximum number of node memory ranges (from the SRAT)
+pub const MAX_NODE_RANGES: usize = 32;
+
+/// First physical address above the DMA32 zone
+pub const DMA32_LIMIT: PhysAddr = 0x1_0000_0000;
+
+/// Number of memory zones
+const NUM_ZONES: usize = 2;
+
+/// Free list terminator
+const NO_FRAME: usize = usize::MAX;
+
+/// Order map value of a frame the allocator does not manage
+///
+/// Frames that were not free when the allocator was built (firmware and
+/// bootloader memory, the kernel image, the allocator metadata) are never
+/// handed out, so attempts to free them are ignored.
+const RESERVED: u8 = u8::MAX;
+
+/// Memory zone of a physical frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// Below 4 GiB - reachable by 32-bit DMA (virtio queues, legacy devices)
+    Dma32,
+    /// Everything above 4 GiB
+    Normal,
+}
+
+impl Zone {
+    /// Zone containing the given frame
+    fn of(frame: usize) -> Zone {
+        if frame < DMA32_LIMIT / FRAME_SIZE {
+            Zone::Dma32
+        } else {
+            Zone::Normal
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Zone::Dma32 => 0,
+            Zone::Normal => 1,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Zone::Dma32 => "DMA32",
+            Zone::Normal => "Normal",
+        }
+    }
+
+    /// Zones an allocation limited to `self` may use, in order of preference
+    ///
+    /// Normal allocations only fall back to DMA32 once Normal is exhausted,
+    /// so low memory is kept for devices that need it.
+    fn fallback_list(self) -> &'static [Zone] {
+        match self {
+            Zone::Dma32 => &[Zone::Dma32],
+            Zone::Normal => &[Zone::Normal, Zone::Dma32],
+        }
+    }
+}
+
+/// Physical memory range owned by a NUMA node
+#[derive(Debug, Clone, Copy)]
+pub struct NodeRange {
+    pub base: PhysAddr,
+    pub length: usize,
+    pub node: u8,
+}
+
+/// Link stored in the first frame of every free block
+#[repr(C)]
+struct FreeLink {
+    next: usize,
+    prev: usize,
+}
+
+/// Allocator state
+///
+/// Lives in the metadata area after the kernel image, with the bitmap and
+/// the order map.
+struct PmmState {
+    /// Total number of frames in the system
+    total_frames: usize,
+    /// Number of free frames available
+    free_frames: usize,
+    /// Heads of the free lists, per node, zone and order
+    free_lists: [[[usize; NUM_ORDERS]; NUM_ZONES]; MAX_NUMA_NODES],
+    /// Free frames per node and zone
+    zone_free: [[usize; NUM_ZONES]; MAX_NUMA_NODES],
+    /// Number of NUMA nodes (1 until an SRAT layout is applied)
+    node_count: usize,
+    /// Memory ranges per node; frames outside every range belong to node 0
+    node_ranges: [NodeRange; MAX_NODE_RANGES],
+    node_range_count: usize,
+}
+
 /// Physical Memory Manager
-/// Uses a bitmap allocator to track free and used frames
+/// Buddy allocator over per-node, per-zone free lists
 pub struct PhysicalMemoryManager {
+    /// Shared allocator state
+    state: &'static mut PmmState,
     /// Bitmap where each bit represents one frame
     /// 0 = free, 1 = used
     bitmap: &'static mut [u8],
-    /// Total number of frames in the system
-    total_frames: usize,
-    /// Number of free frames available
-    free_frames: usize,
-    /// Start of usable memory (physical address)
-    memory_start: PhysAddr,
-    /// End of usable memory (physical address)
-    memory_end: PhysAddr,
-    /// Last allocated frame index (for faster sequential allocation)
-    last_alloc: usize,
+    /// Per-frame order map: order + 1 if the frame heads a free block,
+    /// `RESERVED` if the allocator does not manage it, else 0
+    block_order: &'static mut [u8],
 }
 
 impl PhysicalMemoryManager {
@@ -33,38 +138,67 @@ impl PhysicalMemoryManager {
     ///
     /// This function:
     /// - Parses the memory map and filters for Usable memory only
-    /// - Calculates total frames and allocates bitmap
-    /// - Marks kernel image and page tables as used
-    /// - Logs total and usable memory in MB
+    /// - Places the allocator state, bitmap and order map after the kernel
+    /// - Marks kernel image and allocator metadata as used
+    /// - Builds the buddy free lists (single node until `set_numa_layout`)
     pub fn init(
         memory_map: &MemoryMapResponse,
         kernel_start: PhysAddr,
         kernel_end: PhysAddr,
     ) -> Self {
+        // Only consider Usable memory regions
+        let usable = memory_map
+            .entries()
+            .iter()
+            .filter(|entry| entry.entry_type == EntryType::USABLE)
+            .map(|entry| (entry.base as PhysAddr, entry.length as usize));
+
+        Self::init_from_ranges(usable, kernel_start, kernel_end)
+    }
+
+    /// Initialize the allocator over `(base, length)` ranges of usable memory
+    fn init_from_ranges<I>(usable: I, kernel_start: PhysAddr, kernel_end: PhysAddr) -> Self
+    where
+        I: Iterator<Item = (PhysAddr, usize)> + Clone,
+    {
         // Find the highest usable memory address and calculate total frames
-        let mut highest_addr = 0usize;
-
-        for entry in memory_map.entries() {
-            // Only consider Usable memory regions
-            if entry.entry_type == EntryType::USABLE {
-                let entry_end = entry.base as usize + entry.length as usize;
-                if entry_end > highest_addr {
-                    highest_addr = entry_end;
-                }
-            }
-        }
+        let highest_addr = usable
+            .clone()
+            .map(|(base, length)| base + length)
+            .max()
+            .unwrap_or(0);
 
         let total_frames = highest_addr / FRAME_SIZE;
         let bitmap_size = (total_frames + 7) / 8; // Round up to nearest byte
 
-        // Find a suitable location for the bitmap in usable memory
-        // We'll place it after the kernel
-        let bitmap_start = (kernel_end + FRAME_SIZE - 1) & !(FRAME_SIZE - 1); // Align to frame
-        let bitmap_virt = phys_to_virt(bitmap_start);
+        // Place the metadata after the kernel: state, bitmap, order map
+        let meta_start = (kernel_end + FRAME_SIZE - 1) & !(FRAME_SIZE - 1); // Align to frame
+        let state_size = core::mem::size_of::<PmmState>();
+        let bitmap_start = meta_start + state_size;
+        let order_map_start = bitmap_start + bitmap_size;
+        let meta_end = order_map_start + total_frames;
 
-        // Create bitmap slice
-        let bitmap =
-            unsafe { core::slice::from_raw_parts_mut(bitmap_virt as *mut u8, bitmap_size) };
+        let state = unsafe { &mut *(phys_to_virt(meta_start) as *mut PmmState) };
+        let bitmap = unsafe {
+            core::slice::from_raw_parts_mut(phys_to_virt(bitmap_start) as *mut u8, bitmap_size)
+        };
+        let block_order = unsafe {
+            core::slice::from_raw_parts_mut(phys_to_virt(order_map_start) as *mut u8, total_frames)
+        };
+
+        *state = PmmState {
+            total_frames,
+            free_frames: 0,
+            free_lists: [[[NO_FRAME; NUM_ORDERS]; NUM_ZONES]; MAX_NUMA_NODES],
+            zone_free: [[0; NUM_ZONES]; MAX_NUMA_NODES],
+            node_count: 1,
+            node_ranges: [NodeRange {
+                base: 0,
+                length: 0,
+                node: 0,
+            }; MAX_NODE_RANGES],
+            node_range_count: 0,
+        };
 
         // Initialize bitmap - mark all frames as used initially
         for byte in bitmap.iter_mut() {
@@ -72,149 +206,350 @@ impl PhysicalMemoryManager {
         }
 
         let mut pmm = PhysicalMemoryManager {
+            state,
             bitmap,
-            total_frames,
-            free_frames: 0,
-            memory_start: 0,
-            memory_end: highest_addr,
-            last_alloc: 0,
+            block_order,
         };
 
         // Mark usable memory regions as free
-        for entry in memory_map.entries() {
-            if entry.entry_type == EntryType::USABLE {
-                let start_frame = (entry.base as usize) / FRAME_SIZE;
-                let end_frame =
-                    ((entry.base as usize + entry.length as usize) + FRAME_SIZE - 1) / FRAME_SIZE;
-
-                for frame in start_frame..end_frame {
-                    if frame < total_frames {
-                        pmm.mark_frame_free(frame);
-                    }
-                }
+        for (base, length) in usable {
+            let start_frame = base / FRAME_SIZE;
+            let end_frame = (base + length).div_ceil(FRAME_SIZE);
+
+            for frame in start_frame..end_frame.min(total_frames) {
+                pmm.set_frame_bit(frame, false);
             }
         }
 
         // Mark kernel image as used
         let kernel_start_frame = kernel_start / FRAME_SIZE;
-        let kernel_end_frame = (kernel_end + FRAME_SIZE - 1) / FRAME_SIZE;
+        let kernel_end_frame = kernel_end.div_ceil(FRAME_SIZE);
 
-        for frame in kernel_start_frame..kernel_end_frame {
-            if frame < total_frames {
-                pmm.mark_frame_used(frame);
-            }
+        for frame in kernel_start_frame..kernel_end_frame.min(total_frames) {
+            pmm.set_frame_bit(frame, true);
         }
 
-        // Mark bitmap itself as used
-        let bitmap_end = bitmap_start + bitmap_size;
-        let bitmap_start_frame = bitmap_start / FRAME_SIZE;
-        let bitmap_end_frame = (bitmap_end + FRAME_SIZE - 1) / FRAME_SIZE;
+        // Mark the allocator metadata itself as used
+        let meta_start_frame = meta_start / FRAME_SIZE;
+        let meta_end_frame = meta_end.div_ceil(FRAME_SIZE);
 
-        for frame in bitmap_start_frame..bitmap_end_frame {
-            if frame < total_frames {
-                pmm.mark_frame_used(frame);
-            }
+        for frame in meta_start_frame..meta_end_frame.min(total_frames) {
+            pmm.set_frame_bit(frame, true);
+        }
+
+        // Only frames that are free now are ever managed
+        for frame in 0..total_frames {
+            pmm.block_order[frame] = if pmm.is_frame_free(frame) {
+                0
+            } else {
+                RESERVED
+            };
         }
 
-        // TODO: Log memory information once logging is available
-        // let total_mb = (total_frames * FRAME_SIZE) / (1024 * 1024);
-        // let usable_mb = total_usable_bytes / (1024 * 1024);
-        // let free_mb = (pmm.free_frames * FRAME_SIZE) / (1024 * 1024);
+        pmm.rebuild_free_lists();
 
         pmm
     }
 
-    /// Mark a frame as free in the bitmap
-    fn mark_frame_free(&mut self, frame: usize) {
+    /// Set or clear a frame's bit in the bitmap
+    fn set_frame_bit(&mut self, frame: usize, used: bool) {
         let byte_index = frame / 8;
         let bit_index = frame % 8;
 
         if byte_index < self.bitmap.len() {
-            let was_used = (self.bitmap[byte_index] & (1 << bit_index)) != 0;
-            self.bitmap[byte_index] &= !(1 << bit_index);
-
-            if was_used {
-                self.free_frames += 1;
+            if used {
+                self.bitmap[byte_index] |= 1 << bit_index;
+            } else {
+                self.bitmap[byte_index] &= !(1 << bit_index);
             }
         }
     }
 
-    /// Mark a frame as used in the bitmap
-    fn mark_frame_used(&mut self, frame: usize) {
+    /// Check if a frame is free
+    fn is_frame_free(&self, frame: usize) -> bool {
         let byte_index = frame / 8;
         let bit_index = frame % 8;
 
         if byte_index < self.bitmap.len() {
-            let was_free = (self.bitmap[byte_index] & (1 << bit_index)) == 0;
-            self.bitmap[byte_index] |= 1 << bit_index;
+            (self.bitmap[byte_index] & (1 << bit_index)) == 0
+        } else {
+            false
+        }
+    }
+
+    /// Node owning a frame, and the first frame past that node's range
+    fn node_segment(&self, frame: usize) -> (usize, usize) {
+        let mut next_start = self.state.total_frames;
 
-            if was_free {
-                self.free_frames -= 1;
+        for range in &self.state.node_ranges[..self.state.node_range_count] {
+            let start = range.base / FRAME_SIZE;
+            let end = (range.base + range.length) / FRAME_SIZE;
+            if frame >= start && frame < end {
+                return (range.node as usize, end);
+            }
+            if start > frame && start < next_start {
+                next_start = start;
             }
         }
+
+        // Not covered by any range: node 0 up to the next range
+        (0, next_start)
     }
 
-    /// Check if a frame is free
-    fn is_frame_free(&self, frame: usize) -> bool {
-        let byte_index = frame / 8;
-        let bit_index = frame % 8;
+    /// First frame past the (node, zone) segment containing `frame`
+    fn segment_end(&self, frame: usize) -> usize {
+        let (_, node_end) = self.node_segment(frame);
+        let zone_end = match Zone::of(frame) {
+            Zone::Dma32 => DMA32_LIMIT / FRAME_SIZE,
+            Zone::Normal => usize::MAX,
+        };
+        node_end.min(zone_end).min(self.state.total_frames)
+    }
 
-        if byte_index < self.bitmap.len() {
-            (self.bitmap[byte_index] & (1 << bit_index)) == 0
+    fn link(&self, frame: usize) -> *mut FreeLink {
+        phys_to_virt(frame * FRAME_SIZE) as *mut FreeLink
+    }
+
+    /// Put a free block on the head of its free list
+    fn push_block(&mut self, frame: usize, order: usize) {
+        let (node, _) = self.node_segment(frame);
+        let zone = Zone::of(frame).index();
+        let head = self.state.free_lists[node][zone][order];
+
+        unsafe {
+            (*self.link(frame)).next = head;
+            (*self.link(frame)).prev = NO_FRAME;
+            if head != NO_FRAME {
+                (*self.link(head)).prev = frame;
+            }
+        }
+
+        self.state.free_lists[node][zone][order] = frame;
+        self.block_order[frame] = order as u8 + 1;
+        self.state.zone_free[node][zone] += 1 << order;
+        self.state.free_frames += 1 << order;
+    }
+
+    /// Take a free block off its free list
+    fn remove_block(&mut self, frame: usize, order: usize) {
+        let (node, _) = self.node_segment(frame);
+        let zone = Zone::of(frame).index();
+        let (next, prev) = unsafe { ((*self.link(frame)).next, (*self.link(frame)).prev) };
+
+        unsafe {
+            if prev != NO_FRAME {
+                (*self.link(prev)).next = next;
+            } else {
+                self.state.free_lists[node][zone][order] = next;
+            }
+            if next != NO_FRAME {
+                (*self.link(next)).prev = prev;
+            }
+        }
+
+        self.block_order[frame] = 0;
+        self.state.zone_free[node][zone] -= 1 << order;
+        self.state.free_frames -= 1 << order;
+    }
+
+    /// Rebuild every free list from the bitmap
+    ///
+    /// Runs of free frames are split into the largest aligned blocks that
+    /// do not cross a zone or node boundary.
+    fn rebuild_free_lists(&mut self) {
+        self.state.free_lists = [[[NO_FRAME; NUM_ORDERS]; NUM_ZONES]; MAX_NUMA_NODES];
+        self.state.zone_free = [[0; NUM_ZONES]; MAX_NUMA_NODES];
+        self.state.free_frames = 0;
+        for order in self.block_order.iter_mut() {
+            if *order != RESERVED {
+                *order = 0;
+            }
+        }
+
+        let total_frames = self.state.total_frames;
+        let mut frame = 0;
+
+        while frame < total_frames {
+            if !self.is_frame_free(frame) {
+                frame += 1;
+                continue;
+            }
+
+            let limit = self.segment_end(frame);
+            let mut order = 0;
+            while order + 1 < NUM_ORDERS
+                && frame % (1 << (order + 1)) == 0
+                && frame + (1 << (order + 1)) <= limit
+                && (frame..frame + (1 << (order + 1))).all(|f| self.is_frame_free(f))
+            {
+                order += 1;
+            }
+
+            self.push_block(frame, order);
+            frame += 1 << order;
+        }
+    }
+
+    /// Replace the node layout (from the ACPI SRAT) and rebuild the free lists
+    ///
+    /// # Arguments
+    /// * `ranges` - Memory ranges and the node owning each
+    /// * `node_count` - Number of nodes (node ids must be below this)
+    pub fn set_numa_layout(&mut self, ranges: &[NodeRange], node_count: usize) {
+        let count = ranges.len().min(MAX_NODE_RANGES);
+        self.state.node_ranges[..count].copy_from_slice(&ranges[..count]);
+        self.state.node_range_count = count;
+        self.state.node_count = node_count.clamp(1, MAX_NUMA_NODES);
+
+        self.rebuild_free_lists();
+    }
+
+    /// Node the current CPU belongs to
+    fn preferred_node(&self) -> usize {
+        use crate::arch::x86_64::smp::percpu;
+
+        if self.state.node_count > 1 && percpu::percpu_ready() {
+            (percpu::percpu_current().node_id as usize).min(self.state.node_count - 1)
         } else {
-            false
+            0
         }
     }
-}
 
-impl PhysicalMemoryManager {
-    /// Allocate a physical frame
+    /// Allocate a block of 2^order frames, preferring the current CPU's node
     ///
-    /// Returns the physical address of the allocated frame, or None if out of memory.
-    /// The allocated frame is zeroed for security.
-    pub fn alloc_frame(&mut self) -> Option<PhysAddr> {
-        // Check if we have any free frames
-        if self.free_frames == 0 {
-            // TODO: Log error once logging is available
+    /// Returns the first frame of the block; all its frames are marked used.
+    fn alloc_block(&mut self, order: usize, zone: Zone) -> Option<usize> {
+        if order >= NUM_ORDERS {
             return None;
         }
 
-        // Scan bitmap starting from last_alloc for faster sequential allocation
-        let start_frame = self.last_alloc;
+        let local = self.preferred_node();
+        let node_count = self.state.node_count;
+
+        for step in 0..node_count {
+            let node = (local + step) % node_count;
+            for &candidate in zone.fallback_list() {
+                for found in order..NUM_ORDERS {
+                    let frame = self.state.free_lists[node][candidate.index()][found];
+                    if frame == NO_FRAME {
+                        continue;
+                    }
 
-        for offset in 0..self.total_frames {
-            let frame = (start_frame + offset) % self.total_frames;
+                    self.remove_block(frame, found);
 
-            if self.is_frame_free(frame) {
-                // Mark frame as used
-                self.mark_frame_used(frame);
-                self.last_alloc = frame;
+                    // Return the unused upper halves to the free lists
+                    let mut split = found;
+                    while split > order {
+                        split -= 1;
+                        self.push_block(frame + (1 << split), split);
+                    }
 
-                // Calculate physical address
-                let phys_addr = frame * FRAME_SIZE;
+                    for f in frame..frame + (1 << order) {
+                        self.set_frame_bit(f, true);
+                    }
 
-                // Zero the frame for security
-                let virt_addr = phys_to_virt(phys_addr);
-                unsafe {
-                    core::ptr::write_bytes(virt_addr as *mut u8, 0, FRAME_SIZE);
+                    return Some(frame);
                 }
-
-                return Some(phys_addr);
             }
         }
 
-        // Should never reach here if free_frames > 0
         None
     }
 
+    /// Return one used frame to the free lists, merging with free buddies
+    fn release_frame(&mut self, frame: usize) {
+        self.set_frame_bit(frame, false);
+
+        let mut block = frame;
+        let mut order = 0;
+
+        while order + 1 < NUM_ORDERS {
+            let buddy = block ^ (1 << order);
+            if buddy >= self.state.total_frames || self.block_order[buddy] as usize != order + 1 {
+                break;
+            }
+
+            // Never merge across a zone or node boundary
+            let merged = block.min(buddy);
+            if self.segment_end(merged) < merged + (1 << (order + 1))
+                || self.node_segment(buddy).0 != self.node_segment(block).0
+            {
+                break;
+            }
+
+            self.remove_block(buddy, order);
+            block = merged;
+            order += 1;
+        }
+
+        self.push_block(block, order);
+    }
+
+    /// Zero frames through the direct map
+    fn zero_frames(frame: usize, count: usize) {
+        let virt_addr = phys_to_virt(frame * FRAME_SIZE);
+        unsafe {
+            core::ptr::write_bytes(virt_addr as *mut u8, 0, count * FRAME_SIZE);
+        }
+    }
+}
+
+impl PhysicalMemoryManager {
+    /// Allocate a physical frame
+    ///
+    /// Returns the physical address of the allocated frame, or None if out of memory.
+    /// The allocated frame is zeroed for security.
+    pub fn alloc_frame(&mut self) -> Option<PhysAddr> {
+        self.alloc_frame_zone(Zone::Normal)
+    }
+
+    /// Allocate a physical frame from `zone` or a lower zone
+    ///
+    /// Use `Zone::Dma32` for memory a device addresses with 32-bit DMA.
+    pub fn alloc_frame_zone(&mut self, zone: Zone) -> Option<PhysAddr> {
+        let frame = self.alloc_block(0, zone)?;
+        Self::zero_frames(frame, 1);
+        Some(frame * FRAME_SIZE)
+    }
+
     /// Get total memory in MB
     pub fn total_memory_mb(&self) -> usize {
-        (self.total_frames * FRAME_SIZE) / (1024 * 1024)
+        (self.state.total_frames * FRAME_SIZE) / (1024 * 1024)
     }
 
     /// Get free memory in MB
     pub fn free_memory_mb(&self) -> usize {
-        (self.free_frames * FRAME_SIZE) / (1024 * 1024)
+        (self.state.free_frames * FRAME_SIZE) / (1024 * 1024)
+    }
+
+    /// Number of NUMA nodes in the current layout
+    pub fn node_count(&self) -> usize {
+        self.state.node_count
+    }
+
+    /// Free frames in one zone of one node
+    pub fn zone_free_frames(&self, node: usize, zone: Zone) -> usize {
+        if node >= MAX_NUMA_NODES {
+            return 0;
+        }
+        self.state.zone_free[node][zone.index()]
+    }
+
+    /// Number of free blocks of each order in one zone of one node
+    pub fn free_blocks_per_order(&self, node: usize, zone: Zone) -> [usize; NUM_ORDERS] {
+        let mut counts = [0; NUM_ORDERS];
+        if node >= MAX_NUMA_NODES {
+            return counts;
+        }
+
+        for (order, count) in counts.iter_mut().enumerate() {
+            let mut frame = self.state.free_lists[node][zone.index()][order];
+            while frame != NO_FRAME {
+                *count += 1;
+                frame = unsafe { (*self.link(frame)).next };
+            }
+        }
+        counts
     }
 }
 
@@ -231,17 +566,16 @@ impl PhysicalMemoryManager {
         let frame = phys_addr / FRAME_SIZE;
 
         // Validate frame is within bounds
-        if frame >= self.total_frames {
+        if frame >= self.state.total_frames {
             return;
         }
 
-        // Check if frame is already free
-        if self.is_frame_free(frame) {
+        // Check if frame is already free or was never ours to hand out
+        if self.is_frame_free(frame) || self.block_order[frame] == RESERVED {
             return;
         }
 
-        // Mark frame as free
-        self.mark_frame_free(frame);
+        self.release_frame(frame);
     }
 }
 
@@ -255,60 +589,48 @@ impl PhysicalMemoryManager {
     /// * `count` - Number of contiguous frames to allocate
     /// * `align` - Alignment requirement in bytes (must be power of 2)
     pub fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<PhysAddr> {
-        // Validate alignment is power of 2
-        if align == 0 || (align & (align - 1)) != 0 {
-            return None;
-        }
+        self.alloc_contiguous_zone(count, align, Zone::Normal)
+    }
 
-        // Check if we have enough free frames
-        if self.free_frames < count {
+    /// Allocate contiguous physical frames from `zone` or a lower zone
+    ///
+    /// The buddy block is rounded up to a power of two frames (and to the
+    /// alignment); frames past `count` go straight back to the free lists.
+    pub fn alloc_contiguous_zone(
+        &mut self,
+        count: usize,
+        align: usize,
+        zone: Zone,
+    ) -> Option<PhysAddr> {
+        // Validate alignment is power of 2
+        if count == 0 || align == 0 || (align & (align - 1)) != 0 {
             return None;
         }
 
-        let align_frames = align / FRAME_SIZE;
-
-        // Scan for contiguous free frames with proper alignment
-        let mut start_frame = 0;
-
-        while start_frame < self.total_frames {
-            // Align start_frame
-            if align_frames > 1 {
-                start_frame = (start_frame + align_frames - 1) & !(align_frames - 1);
-            }
-
-            if start_frame + count > self.total_frames {
-                break;
-            }
+        // Buddy blocks of order N are aligned to 2^N frames
+        let align_frames = (align / FRAME_SIZE).max(1);
+        let order = (count.next_power_of_two().max(align_frames)).trailing_zeros() as usize;
 
-            // Check if all frames in range are free
-            let mut all_free = true;
-            for offset in 0..count {
-                if !self.is_frame_free(start_frame + offset) {
-                    all_free = false;
-                    start_frame = start_frame + offset + 1;
-                    break;
-                }
-            }
+        let frame = self.alloc_block(order, zone)?;
 
-            if all_free {
-                // Allocate all frames in the range
-                for offset in 0..count {
-                    self.mark_frame_used(start_frame + offset);
-                }
+        for tail in frame + count..frame + (1 << order) {
+            self.release_frame(tail);
+        }
 
-                let phys_addr = start_frame * FRAME_SIZE;
+        Self::zero_frames(frame, count);
 
-                // Zero all frames for security
-                let virt_addr = phys_to_virt(phys_addr);
-                unsafe {
-                    core::ptr::write_bytes(virt_addr as *mut u8, 0, count * FRAME_SIZE);
-                }
+        Some(frame * FRAME_SIZE)
+    }
 
-                return Some(phys_addr);
-            }
+    /// Free a block allocated with `alloc_contiguous`
+    ///
+    /// # Arguments
+    /// * `phys_addr` - Physical address of the first frame
+    /// * `count` - Number of frames in the block
+    pub fn free_contiguous(&mut self, phys_addr: PhysAddr, count: usize) {
+        for i in 0..count {
+            self.free_frame(phys_addr + i * FRAME_SIZE);
         }
-
-        None
     }
 }
 
@@ -345,3 +667,151 @@ pub fn init_global_pmm(pmm: PhysicalMemoryManager) {
 pub fn get_global_pmm() -> crate::sync::SpinLockGuard<'static, Option<PhysicalMemoryManager>> {
     GLOBAL_PMM.lock()
 }
+
+/// Allocate frames below 4 GiB for device DMA (virtio queues, legacy devices)
+///
+/// # Arguments
+/// * `count` - Number of contiguous frames to allocate
+///
+/// # Returns
+/// Physical address of the first (zeroed) frame, or None if DMA32 is exhausted
+pub fn alloc_dma32_frames(count: usize) -> Option<PhysAddr> {
+    get_global_pmm()
+        .as_mut()?
+        .alloc_contiguous_zone(count, FRAME_SIZE, Zone::Dma32)
+}
+
+/// Free frames allocated with `alloc_dma32_frames`
+pub fn free_dma32_frames(phys_addr: PhysAddr, count: usize) {
+    if let Some(pmm) = get_global_pmm().as_mut() {
+        pmm.free_contiguous(phys_addr, count);
+    }
+}
+
+/// Frames backing the test allocator
+#[cfg(test)]
+const TEST_FRAMES: usize = 128;
+
+/// First usable frame of the test allocator; frames below hold its metadata
+#[cfg(test)]
+const FIRST_USABLE: usize = 64;
+
+/// Serializes tests using `test_pmm`, which all move the HHDM offset
+#[cfg(test)]
+static TEST_HHDM_LOCK: spin::Mutex<()> = spin::Mutex::new(());
+
+/// Allocator over a host buffer standing in for physical memory
+///
+/// The allocator metadata takes frame 0 and frames `FIRST_USABLE..` are one
+/// free order-6 block. The HHDM offset points at the buffer until the
+/// returned guard is dropped.
+#[cfg(test)]
+pub(crate) fn test_pmm() -> (spin::MutexGuard<'static, ()>, PhysicalMemoryManager) {
+    let guard = TEST_HHDM_LOCK.lock();
+    let memory = alloc::vec![0u64; TEST_FRAMES * FRAME_SIZE / 8].leak();
+    crate::mm::init_hhdm(memory.as_mut_ptr() as usize);
+
+    let usable = [(
+        FIRST_USABLE * FRAME_SIZE,
+        (TEST_FRAMES - FIRST_USABLE) * FRAME_SIZE,
+    )];
+    let pmm = PhysicalMemoryManager::init_from_ranges(usable.iter().copied(), 0, 0);
+    (guard, pmm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn free_blocks(pmm: &PhysicalMemoryManager) -> [usize; NUM_ORDERS] {
+        pmm.free_blocks_per_order(0, Zone::Dma32)
+    }
+
+    #[test]
+    fn test_init_builds_largest_block() {
+        let (_guard, pmm) = test_pmm();
+
+        let mut expected = [0; NUM_ORDERS];
+        expected[6] = 1;
+        assert_eq!(free_blocks(&pmm), expected);
+        assert_eq!(
+            pmm.zone_free_frames(0, Zone::Dma32),
+            TEST_FRAMES - FIRST_USABLE
+        );
+    }
+
+    #[test]
+    fn test_alloc_splits_block() {
+        let (_guard, mut pmm) = test_pmm();
+
+        assert_eq!(pmm.alloc_frame(), Some(FIRST_USABLE * FRAME_SIZE));
+
+        // The order-6 block was split into one free buddy of each lower order
+        let mut expected = [0; NUM_ORDERS];
+        expected[..6].fill(1);
+        assert_eq!(free_blocks(&pmm), expected);
+        assert_eq!(pmm.zone_free_frames(0, Zone::Dma32), 63);
+    }
+
+    #[test]
+    fn test_free_merges_buddies() {
+        let (_guard, mut pmm) = test_pmm();
+
+        let first = pmm.alloc_frame().unwrap();
+        let second = pmm.alloc_frame().unwrap();
+        assert_eq!(second, first + FRAME_SIZE);
+
+        pmm.free_frame(first);
+        assert_eq!(free_blocks(&pmm)[0], 1);
+
+        pmm.free_frame(second);
+        let mut expected = [0; NUM_ORDERS];
+        expected[6] = 1;
+        assert_eq!(free_blocks(&pmm), expected);
+    }
+
+    #[test]
+    fn test_double_free_is_ignored() {
+        let (_guard, mut pmm) = test_pmm();
+
+        let frame = pmm.alloc_frame().unwrap();
+        pmm.free_frame(frame);
+        pmm.free_frame(frame);
+        assert_eq!(
+            pmm.zone_free_frames(0, Zone::Dma32),
+            TEST_FRAMES - FIRST_USABLE
+        );
+    }
+
+    #[test]
+    fn test_reserved_frames_are_never_freed() {
+        let (_guard, mut pmm) = test_pmm();
+
+        // Frame 0 holds the allocator metadata, frame 1 was never usable
+        pmm.free_frame(0);
+        pmm.free_frame(FRAME_SIZE);
+        assert_eq!(
+            pmm.zone_free_frames(0, Zone::Dma32),
+            TEST_FRAMES - FIRST_USABLE
+        );
+    }
+
+    #[test]
+    fn test_contiguous_returns_tail() {
+        let (_guard, mut pmm) = test_pmm();
+
+        // Three frames come from an order-2 block whose last frame is freed
+        let block = pmm.alloc_contiguous(3, FRAME_SIZE).unwrap();
+        assert_eq!(block, FIRST_USABLE * FRAME_SIZE);
+        assert_eq!(
+            pmm.zone_free_frames(0, Zone::Dma32),
+            TEST_FRAMES - FIRST_USABLE - 3
+        );
+        assert_eq!(free_blocks(&pmm)[0], 1);
+
+        pmm.free_contiguous(block, 3);
+        let mut expected = [0; NUM_ORDERS];
+        expected[6] = 1;
+        assert_eq!(free_blocks(&pmm), expected);
+    }
+}
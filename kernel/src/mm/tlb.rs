@@ -10,8 +10,8 @@ use crate::arch::x86_64::apic::ipi::{send_ipi, TLB_FLUSH_IPI_VECTOR};
 use crate::arch::x86_64::smp::percpu::{percpu_current, percpu_for};
 use crate::arch::x86_64::smp::{get_cpu_count, is_cpu_online};
 use crate::config::MAX_CPUS;
+use crate::sched::process_group::Pid;
 use crate::sync::SpinLock;
-use crate::user::process::ProcessId;
 use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
 /// Maximum number of CPUs that can be targeted in a single shootdown
@@ -40,7 +40,7 @@ impl CpuProcessTracker {
     }
 
     /// Mark a process as accessed by this CPU
-    fn mark_accessed(&self, pid: ProcessId) {
+    fn mark_accessed(&self, pid: Pid) {
         if pid < MAX_TRACKED_PROCESSES {
             let word_idx = pid / 64;
             let bit_idx = pid % 64;
@@ -49,7 +49,7 @@ impl CpuProcessTracker {
     }
 
     /// Check if a process has been accessed by this CPU
-    fn has_accessed(&self, pid: ProcessId) -> bool {
+    fn has_accessed(&self, pid: Pid) -> bool {
         if pid < MAX_TRACKED_PROCESSES {
             let word_idx = pid / 64;
             let bit_idx = pid % 64;
@@ -60,7 +60,7 @@ impl CpuProcessTracker {
     }
 
     /// Clear tracking for a process (when process exits)
-    fn clear_process(&self, pid: ProcessId) {
+    fn clear_process(&self, pid: Pid) {
         if pid < MAX_TRACKED_PROCESSES {
             let word_idx = pid / 64;
             let bit_idx = pid % 64;
@@ -418,7 +418,7 @@ pub unsafe fn handle_tlb_shootdown_ipi() {
 ///
 /// # Arguments
 /// * `pid` - Process ID that was accessed
-pub fn mark_process_accessed(pid: ProcessId) {
+pub fn mark_process_accessed(pid: Pid) {
     let cpu_id = percpu_current().id;
     if cpu_id < MAX_CPUS {
         CPU_PROCESS_MAP[cpu_id].mark_accessed(pid);
@@ -432,7 +432,7 @@ pub fn mark_process_accessed(pid: ProcessId) {
 ///
 /// # Arguments
 /// * `pid` - Process ID that is exiting
-pub fn clear_process_tracking(pid: ProcessId) {
+pub fn clear_process_tracking(pid: Pid) {
     for cpu_id in 0..MAX_CPUS {
         CPU_PROCESS_MAP[cpu_id].clear_process(pid);
     }
@@ -447,7 +447,7 @@ pub fn clear_process_tracking(pid: ProcessId) {
 ///
 /// # Returns
 /// Bitmask of CPUs that have accessed this process
-fn get_cpus_for_process(pid: ProcessId) -> u64 {
+fn get_cpus_for_process(pid: Pid) -> u64 {
     let mut mask = 0u64;
     let cpu_count = get_cpu_count().min(64);
 
@@ -495,6 +495,11 @@ fn get_cpus_for_process(pid: ProcessId) -> u64 {
 /// pmm_free(phys_addr);
 /// ```
 pub unsafe fn tlb_shootdown(start_addr: usize, end_addr: usize) -> bool {
+    // IPIs and invlpg only reach the active PCID on each CPU
+    if start_addr <= crate::mm::USER_LIMIT {
+        crate::mm::pcid::invalidate_user_tlb();
+    }
+
     let current_cpu = percpu_current().id;
     let current_tsc = core::arch::x86_64::_rdtsc();
 
@@ -546,11 +551,11 @@ pub unsafe fn tlb_shootdown(start_addr: usize, end_addr: usize) -> bool {
 ///
 /// # Returns
 /// `true` if shootdown completed successfully, `false` on timeout
-pub unsafe fn tlb_shootdown_for_process(
-    start_addr: usize,
-    end_addr: usize,
-    pid: ProcessId,
-) -> bool {
+pub unsafe fn tlb_shootdown_for_process(start_addr: usize, end_addr: usize, pid: Pid) -> bool {
+    if start_addr <= crate::mm::USER_LIMIT {
+        crate::mm::pcid::invalidate_user_tlb();
+    }
+
     // Get CPUs that have accessed this process
     let cpu_mask = get_cpus_for_process(pid);
 
@@ -574,7 +579,7 @@ unsafe fn tlb_shootdown_immediate(
     start_addr: usize,
     end_addr: usize,
     cpu_mask: u64,
-    pid: Option<ProcessId>,
+    pid: Option<Pid>,
 ) -> bool {
     let seq = TLB_SHOOTDOWN_SEQ.fetch_add(1, Ordering::Relaxed);
     let current_cpu = percpu_current().id;
@@ -0,0 +1,477 @@
+//! SMP Load Balancing
+//!
+//! Spreads fair-class work across CPUs while keeping tasks close to the
+//! caches they have warmed up. Three mechanisms cooperate:
+//!
+//! - **Wakeup placement** (`select_cpu`): a woken task goes next to its
+//!   waker when the waker's CPU is no busier than the task's previous one
+//!   (wake-affine), then moves to an idle CPU sharing that cache if there
+//!   is one. New tasks go to the least loaded CPU.
+//! - **Idle stealing** (`idle_steal`): a CPU about to go idle pulls a
+//!   queued task from the busiest CPU, trying its SMT siblings first.
+//! - **Periodic balancing** (`balance_load`): every
+//!   `SCHED_BALANCE_INTERVAL_TICKS` each busy CPU compares its load average
+//!   with the busiest CPU in each domain and pulls enough weight to even
+//!   them out. Idle CPUs have their tick stopped, so a CPU with tasks
+//!   waiting also kicks the nearest idle CPU, which then steals.
+//!
+//! # Scheduling Domains
+//!
+//! Candidate CPUs are searched nearest first: SMT siblings (same core),
+//! then the same package (shared last-level cache), then the same NUMA
+//! node, then the whole system. Topology comes from `smp::topology` and
+//! the ACPI SRAT.
+//!
+//! # Load Average
+//!
+//! Each CPU keeps a decaying average of its runnable weight (queued plus
+//! running, real-time tasks counted as nice 0) with a half-life of 32 ticks.
+//! The average is read without locks, so picking a source CPU never takes
+//! another CPU's runqueue lock. Ticks missed while the tick was stopped
+//! count as zero load.
+//!
+//! # Lock Ordering
+//!
+//! Only `pull_tasks` holds two runqueue locks, taken in CPU ID order as in
+//! `migrate_task`. Idle stealing only try-locks the source. Real-time tasks
+//! are never moved here; they stay where `enqueue_task` put them.
+
+use super::cpuset::CpuMask;
+use super::fair::{NICE_0_LOAD, TICK_NS};
+use super::task::Task;
+use super::{get_cpu_count, hrtimer, task_cpus};
+use crate::arch::x86_64::smp::percpu::{percpu_current, percpu_for};
+use crate::config::SCHED_IMBALANCE_PCT;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Per-tick decay of the load average, out of `LOAD_SCALE` (0.5^(1/32))
+const LOAD_DECAY: u64 = 1002;
+
+/// Fixed-point scale of `LOAD_DECAY`
+const LOAD_SCALE: u64 = 1024;
+
+/// Half-life of the load average in ticks
+const LOAD_HALF_LIFE: u64 = 32;
+
+/// Most tasks a single periodic balance moves
+const MAX_PULL: usize = 4;
+
+/// Balancing domains, nearest first
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Domain {
+    /// SMT siblings sharing a physical core
+    Core,
+    /// CPUs in the same package (shared last-level cache)
+    Package,
+    /// CPUs on the same NUMA node
+    Node,
+    /// Every CPU
+    System,
+}
+
+/// Counters of balancing decisions since boot
+pub struct BalanceStats {
+    /// Tasks stolen by CPUs about to go idle
+    pub idle_steals: AtomicU64,
+    /// Tasks pulled by periodic balancing
+    pub pulls: AtomicU64,
+    /// Idle CPUs woken to steal work
+    pub idle_kicks: AtomicU64,
+    /// Wakeups placed on the waker's CPU
+    pub wake_affine: AtomicU64,
+}
+
+/// Balancing counters (for benchmarks and debugging)
+pub static STATS: BalanceStats = BalanceStats {
+    idle_steals: AtomicU64::new(0),
+    pulls: AtomicU64::new(0),
+    idle_kicks: AtomicU64::new(0),
+    wake_affine: AtomicU64::new(0),
+};
+
+const DOMAINS: [Domain; 4] = [Domain::Core, Domain::Package, Domain::Node, Domain::System];
+
+/// Decaying average of a CPU's runnable weight
+pub struct CpuLoad {
+    /// Average load (weight units, nice 0 = `NICE_0_LOAD`)
+    avg: AtomicU64,
+
+    /// Tick of the last update
+    stamp: AtomicU64,
+}
+
+impl CpuLoad {
+    /// Create an empty load average
+    pub const fn new() -> Self {
+        Self {
+            avg: AtomicU64::new(0),
+            stamp: AtomicU64::new(0),
+        }
+    }
+
+    /// Fold in the load seen at tick `now`
+    ///
+    /// Only called by the owning CPU.
+    pub fn update(&self, now: u64, load: u64) {
+        let missed = now
+            .saturating_sub(self.stamp.load(Ordering::Relaxed))
+            .saturating_sub(1);
+        let avg = decay(self.avg.load(Ordering::Relaxed), missed);
+        let avg = (avg * LOAD_DECAY + load * (LOAD_SCALE - LOAD_DECAY)) / LOAD_SCALE;
+
+        self.avg.store(avg, Ordering::Relaxed);
+        self.stamp.store(now, Ordering::Relaxed);
+    }
+
+    /// Load average as of tick `now`
+    pub fn get(&self, now: u64) -> u64 {
+        let elapsed = now.saturating_sub(self.stamp.load(Ordering::Relaxed));
+        decay(self.avg.load(Ordering::Relaxed), elapsed)
+    }
+
+    /// Account for a task of `weight` arriving (`true`) or leaving
+    ///
+    /// Keeps the averages of both CPUs in line with a migration right away,
+    /// so the next balance doesn't move the same load again.
+    fn transfer(&self, weight: u64, arriving: bool) {
+        let avg = self.avg.load(Ordering::Relaxed);
+        let avg = if arriving {
+            avg + weight
+        } else {
+            avg.saturating_sub(weight)
+        };
+        self.avg.store(avg, Ordering::Relaxed);
+    }
+}
+
+/// Decay a load average by `ticks` ticks of zero load
+fn decay(mut avg: u64, ticks: u64) -> u64 {
+    if ticks >= LOAD_HALF_LIFE * 64 {
+        return 0;
+    }
+
+    avg >>= ticks / LOAD_HALF_LIFE;
+    for _ in 0..ticks % LOAD_HALF_LIFE {
+        avg = avg * LOAD_DECAY / LOAD_SCALE;
+    }
+    avg
+}
+
+/// Current time in scheduler ticks
+pub fn now_ticks() -> u64 {
+    hrtimer::monotonic_ns() / TICK_NS
+}
+
+/// Load weight a running task contributes to its CPU
+pub fn task_load(task: &Task) -> u64 {
+    if task.rt.is_rt() {
+        NICE_0_LOAD
+    } else {
+        task.se.weight as u64
+    }
+}
+
+/// Load average of a CPU
+fn cpu_load(cpu: usize, now: u64) -> u64 {
+    percpu_for(cpu).load.get(now)
+}
+
+/// Check whether a CPU is running its idle task
+fn is_idle(cpu: usize) -> bool {
+    let percpu = percpu_for(cpu);
+    percpu.current_task == Some(percpu.idle_task)
+}
+
+/// Check whether two CPUs are in the same domain
+fn in_domain(a: usize, b: usize, domain: Domain) -> bool {
+    let (pa, pb) = (percpu_for(a), percpu_for(b));
+    match domain {
+        Domain::Core => pa.topology.same_core(&pb.topology) && pa.node_id == pb.node_id,
+        Domain::Package => pa.topology.same_package(&pb.topology) && pa.node_id == pb.node_id,
+        Domain::Node => pa.node_id == pb.node_id,
+        Domain::System => true,
+    }
+}
+
+/// CPUs within `domain` of `cpu`
+fn domain_cpus(cpu: usize, domain: Domain) -> CpuMask {
+    (0..get_cpu_count())
+        .filter(|&other| in_domain(cpu, other, domain))
+        .fold(CpuMask::empty(), |mask, other| {
+            mask.or(CpuMask::single(other))
+        })
+}
+
+/// Check whether a queued task may be moved to `dst`
+///
+/// A task whose context is still being saved on its old CPU is left alone.
+fn can_migrate(task: &Task, dst: usize) -> bool {
+    task_cpus(task).contains(dst) && !task.on_cpu.load(Ordering::Acquire)
+}
+
+/// Allowed CPU with the least load, preferring idle ones
+fn least_loaded(allowed: CpuMask, now: u64) -> usize {
+    allowed
+        .iter()
+        .min_by_key(|&cpu| (!is_idle(cpu), cpu_load(cpu, now)))
+        .unwrap_or(0)
+}
+
+/// Choose the CPU to queue a task on
+///
+/// `allowed` is never empty (see `task_cpus`).
+pub fn select_cpu(task: &Task, allowed: CpuMask) -> usize {
+    let now = now_ticks();
+
+    // New tasks have no cache footprint yet: spread them out
+    let prev = match task.last_cpu {
+        Some(cpu) if allowed.contains(cpu) => cpu,
+        _ => return least_loaded(allowed, now),
+    };
+
+    // Wake-affine: join the waker if its CPU is no busier and close by
+    let this_cpu = percpu_current().id;
+    let target = if this_cpu != prev
+        && allowed.contains(this_cpu)
+        && in_domain(this_cpu, prev, Domain::Node)
+        && cpu_load(this_cpu, now) <= cpu_load(prev, now)
+    {
+        STATS.wake_affine.fetch_add(1, Ordering::Relaxed);
+        this_cpu
+    } else {
+        prev
+    };
+
+    if is_idle(target) {
+        return target;
+    }
+    if target != prev && is_idle(prev) && in_domain(prev, target, Domain::Package) {
+        return prev;
+    }
+
+    // An idle CPU sharing the target's cache, SMT siblings first
+    for domain in [Domain::Core, Domain::Package] {
+        let idle = domain_cpus(target, domain)
+            .and(allowed)
+            .iter()
+            .find(|&cpu| is_idle(cpu));
+        if let Some(cpu) = idle {
+            return cpu;
+        }
+    }
+
+    target
+}
+
+/// Move one queued fair task from `src` to `dst`
+///
+/// Gives up instead of spinning if `src`'s runqueue is busy.
+fn steal_from(src: usize, dst: usize) -> bool {
+    let (task, src_min_vruntime) = {
+        let mut runqueue = match percpu_for(src).runqueue.try_lock() {
+            Some(runqueue) => runqueue,
+            None => return false,
+        };
+        match runqueue
+            .fair
+            .pop_last_matching(|task| can_migrate(task, dst))
+        {
+            Some(task) => (task, runqueue.fair.min_vruntime()),
+            None => return false,
+        }
+    };
+
+    let weight = task.se.weight as u64;
+    let mut runqueue = percpu_for(dst).runqueue.lock();
+    runqueue.fair.place_migrated(task, src_min_vruntime);
+    runqueue.enqueue(task, false);
+    drop(runqueue);
+
+    percpu_for(src).load.transfer(weight, false);
+    percpu_for(dst).load.transfer(weight, true);
+
+    STATS.idle_steals.fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+/// Pull a task onto a CPU that is about to go idle
+///
+/// Busier CPUs are tried first within each domain, nearest domain first.
+///
+/// # Returns
+/// true if a task was queued on `this_cpu`
+pub fn idle_steal(this_cpu: usize) -> bool {
+    if get_cpu_count() <= 1 {
+        return false;
+    }
+
+    let now = now_ticks();
+    let mut tried = CpuMask::single(this_cpu);
+
+    for domain in DOMAINS {
+        loop {
+            let src = domain_cpus(this_cpu, domain)
+                .iter()
+                .filter(|&cpu| !tried.contains(cpu) && !is_idle(cpu))
+                .max_by_key(|&cpu| cpu_load(cpu, now));
+
+            let src = match src {
+                Some(cpu) => cpu,
+                None => break,
+            };
+            tried = tried.or(CpuMask::single(src));
+
+            if steal_from(src, this_cpu) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// Move up to `max_load` worth of queued fair tasks from `src` to `dst`
+///
+/// # Returns
+/// Number of tasks moved
+fn pull_tasks(src: usize, dst: usize, max_load: u64) -> usize {
+    // Lock ordering: always lock lower CPU ID first to prevent deadlocks
+    let (first, second) = if src < dst { (src, dst) } else { (dst, src) };
+    let mut first_rq = percpu_for(first).runqueue.lock();
+    let mut second_rq = percpu_for(second).runqueue.lock();
+    let (src_rq, dst_rq) = if src < dst {
+        (&mut *first_rq, &mut *second_rq)
+    } else {
+        (&mut *second_rq, &mut *first_rq)
+    };
+
+    let mut remaining = max_load;
+    let mut moved = 0;
+
+    while moved < MAX_PULL {
+        let budget = remaining;
+        let task = match src_rq
+            .fair
+            .pop_last_matching(|task| can_migrate(task, dst) && task.se.weight as u64 <= budget)
+        {
+            Some(task) => task,
+            None => break,
+        };
+
+        let weight = task.se.weight as u64;
+        dst_rq.fair.place_migrated(task, src_rq.fair.min_vruntime());
+        dst_rq.enqueue(task, false);
+
+        percpu_for(src).load.transfer(weight, false);
+        percpu_for(dst).load.transfer(weight, true);
+
+        remaining -= weight;
+        moved += 1;
+    }
+
+    STATS.pulls.fetch_add(moved as u64, Ordering::Relaxed);
+    moved
+}
+
+/// Wake the nearest idle CPU so it can steal from this one
+fn kick_idle_cpu(this_cpu: usize) {
+    let waiting = percpu_for(this_cpu).runqueue.lock().fair.len();
+    if waiting == 0 {
+        return;
+    }
+
+    for domain in DOMAINS {
+        let idle = domain_cpus(this_cpu, domain)
+            .iter()
+            .find(|&cpu| cpu != this_cpu && is_idle(cpu));
+        if let Some(cpu) = idle {
+            use crate::arch::x86_64::apic::ipi::send_reschedule_ipi;
+            send_reschedule_ipi(cpu);
+            STATS.idle_kicks.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+}
+
+/// Periodic load balancing for the current CPU
+///
+/// Pulls work from the busiest CPU of the nearest domain whose load average
+/// exceeds this CPU's by more than `SCHED_IMBALANCE_PCT` percent. If
+/// nothing needed pulling and tasks are waiting here, an idle CPU is kicked
+/// to come and steal one.
+///
+/// # Returns
+/// Number of tasks moved to this CPU
+pub fn balance_load() -> usize {
+    if get_cpu_count() <= 1 {
+        return 0;
+    }
+
+    let this_cpu = percpu_current().id;
+    let now = now_ticks();
+    let this_load = cpu_load(this_cpu, now);
+
+    for domain in DOMAINS {
+        let busiest = domain_cpus(this_cpu, domain)
+            .iter()
+            .filter(|&cpu| cpu != this_cpu)
+            .map(|cpu| (cpu, cpu_load(cpu, now)))
+            .max_by_key(|&(_, load)| load);
+
+        let (src, src_load) = match busiest {
+            Some(busiest) => busiest,
+            None => continue,
+        };
+
+        // Only pull if the difference is worth at least half a nice-0 task
+        if src_load * 100 <= this_load * SCHED_IMBALANCE_PCT
+            || src_load - this_load < NICE_0_LOAD / 2
+        {
+            continue;
+        }
+
+        let moved = pull_tasks(src, this_cpu, (src_load - this_load) / 2);
+        if moved > 0 {
+            crate::sched_log!(
+                "Load balance: CPU {} pulled {} task(s) from CPU {} (load {} vs {})",
+                this_cpu,
+                moved,
+                src,
+                src_load,
+                this_load
+            );
+            return moved;
+        }
+    }
+
+    kick_idle_cpu(this_cpu);
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_decay() {
+        assert_eq!(decay(1024, 0), 1024);
+
+        // One half-life halves the load (within rounding)
+        let half = decay(1024, LOAD_HALF_LIFE);
+        assert!((500..=512).contains(&half));
+        assert_eq!(decay(1024, LOAD_HALF_LIFE * 64), 0);
+    }
+
+    #[test]
+    fn test_load_average_converges() {
+        let load = CpuLoad::new();
+        for tick in 1..=LOAD_HALF_LIFE * 16 {
+            load.update(tick, 2 * NICE_0_LOAD);
+        }
+        let avg = load.get(LOAD_HALF_LIFE * 16);
+        assert!(avg > 2 * NICE_0_LOAD * 95 / 100);
+
+        // An idle stretch (tick stopped) decays it
+        assert!(load.get(LOAD_HALF_LIFE * 17) < avg * 51 / 100);
+    }
+}
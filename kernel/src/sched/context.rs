@@ -23,8 +23,17 @@ pub struct CpuContext {
     /// Stack pointer - points to the top of the task's stack
     pub rsp: u64,
 
-    /// CR3 register - page table physical address (for per-process page tables)
+    /// Physical address of the task's PML4
+    ///
+    /// Not touched by `context_switch`; the scheduler loads it through
+    /// `mm::pcid::switch_address_space` right before switching so that the
+    /// PCID and no-flush bits can be chosen per CPU.
     pub cr3: u64,
+
+    /// User FS base (thread-local storage pointer)
+    ///
+    /// Also loaded by the scheduler before the switch, see `arch::x86_64::tls`.
+    pub fs_base: u64,
 }
 
 impl CpuContext {
@@ -39,6 +48,7 @@ impl CpuContext {
             rbx: 0,
             rsp: 0,
             cr3: 0,
+            fs_base: 0,
         }
     }
 }
@@ -69,6 +79,9 @@ impl CpuContext {
 /// - For a new task, the return address on the stack will be entry_trampoline
 /// - For a preempted task, the return address will be where it was interrupted
 /// - This function does not return to the caller in the traditional sense
+/// - The address space is switched by the caller (see `sched::switch_mm`);
+///   kernel stacks live in the shared upper half, so it is safe to do so
+///   before the stack switch
 #[unsafe(naked)]
 pub unsafe extern "C" fn context_switch(current: *mut CpuContext, next: *const CpuContext) {
     core::arch::naked_asm!(
@@ -84,21 +97,8 @@ pub unsafe extern "C" fn context_switch(current: *mut CpuContext, next: *const C
         // RDI contains the pointer to current context (first argument)
         // We need to save RSP at offset 48 (6 registers * 8 bytes)
         "mov [rdi + 48], rsp",
-        // Save current CR3 to current.cr3
-        // CR3 is at offset 56 (7 registers * 8 bytes)
-        "mov rax, cr3",
-        "mov [rdi + 56], rax",
-        // Load next CR3 from next.cr3
-        // RSI contains the pointer to next context (second argument)
-        // Load CR3 from offset 56
-        "mov rax, [rsi + 56]",
-        // Only switch CR3 if it's different (optimization)
-        "mov rbx, cr3",
-        "cmp rax, rbx",
-        "je 1f",        // Skip CR3 write if same
-        "mov cr3, rax", // Switch page tables (automatically flushes TLB)
-        "1:",
         // Load next RSP from next.rsp
+        // RSI contains the pointer to next context (second argument)
         // Load RSP from offset 48
         "mov rsp, [rsi + 48]",
         // Restore next task's callee-saved registers from its stack
@@ -139,8 +139,8 @@ mod tests {
     fn test_context_layout() {
         use core::mem::{align_of, size_of};
 
-        // Should be 7 u64 registers = 56 bytes
-        assert_eq!(size_of::<CpuContext>(), 56);
+        // Should be 7 u64 registers + CR3 + FS base = 72 bytes
+        assert_eq!(size_of::<CpuContext>(), 72);
 
         // Should be aligned to 8 bytes (u64 alignment)
         assert_eq!(align_of::<CpuContext>(), 8);
@@ -0,0 +1,341 @@
+//! CPU Affinity and Cpusets
+//!
+//! Two constraints decide where a task may run:
+//!
+//! - **Affinity** (`Task::cpus_allowed`): the task's own mask, set with
+//!   `sched_setaffinity`.
+//! - **Cpuset** (`Task::cpuset`): the group the task belongs to. Cpusets
+//!   form a tree below the root cpuset, which spans every CPU, and a child's
+//!   CPUs are always a subset of its parent's. Confining a cpuset therefore
+//!   confines everything below it, e.g. the shell in a cpuset on CPU 0 and
+//!   builds in a sibling on CPUs 1-15.
+//!
+//! The scheduler places a task on the intersection of both. If they do not
+//! intersect (the cpuset was shrunk after the affinity was set), the cpuset
+//! wins.
+//!
+//! # Lock Ordering
+//!
+//! The cpuset table is a leaf lock: it may be taken while holding TASK_TABLE
+//! or a runqueue lock, and nothing else is locked while it is held.
+
+use crate::config::MAX_CPUS;
+use crate::sync::IrqSpinLock;
+use core::fmt;
+
+/// Maximum number of cpusets (including the root)
+pub const MAX_CPUSETS: usize = 32;
+
+/// Cpuset identifier
+pub type CpusetId = usize;
+
+/// The root cpuset, spanning every CPU
+pub const ROOT_CPUSET: CpusetId = 0;
+
+/// Set of CPUs, one bit per logical CPU ID
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CpuMask(u64);
+
+impl CpuMask {
+    /// Mask with no CPUs
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Mask with every possible CPU
+    pub const fn all() -> Self {
+        Self((1 << MAX_CPUS) - 1)
+    }
+
+    /// Mask with CPUs `0..count`
+    pub fn first_n(count: usize) -> Self {
+        Self::from_bits(
+            1u64.checked_shl(count as u32)
+                .map_or(u64::MAX, |bit| bit - 1),
+        )
+    }
+
+    /// Mask with a single CPU
+    pub fn single(cpu: usize) -> Self {
+        Self::from_bits(1u64.checked_shl(cpu as u32).unwrap_or(0))
+    }
+
+    /// Build a mask from raw bits (bits beyond MAX_CPUS are dropped)
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits & Self::all().0)
+    }
+
+    /// Raw bits
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Check whether a CPU is in the mask
+    pub fn contains(self, cpu: usize) -> bool {
+        cpu < MAX_CPUS && self.0 & (1 << cpu) != 0
+    }
+
+    /// Check if the mask has no CPUs
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// CPUs in both masks
+    pub fn and(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// CPUs in either mask
+    pub fn or(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Check whether every CPU in this mask is also in `other`
+    pub fn is_subset_of(self, other: Self) -> bool {
+        self.0 & !other.0 == 0
+    }
+
+    /// Number of CPUs in the mask
+    pub fn count(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Iterate over the CPU IDs in the mask, lowest first
+    pub fn iter(self) -> impl Iterator<Item = usize> {
+        (0..MAX_CPUS).filter(move |&cpu| self.contains(cpu))
+    }
+}
+
+/// Formats as a CPU list, e.g. `0-3,8` (the `Cpus_allowed_list` format)
+impl fmt::Display for CpuMask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        let mut cpu = 0;
+
+        while cpu < MAX_CPUS {
+            if !self.contains(cpu) {
+                cpu += 1;
+                continue;
+            }
+
+            let start = cpu;
+            while cpu + 1 < MAX_CPUS && self.contains(cpu + 1) {
+                cpu += 1;
+            }
+
+            if !first {
+                f.write_str(",")?;
+            }
+            first = false;
+
+            if start == cpu {
+                write!(f, "{}", start)?;
+            } else {
+                write!(f, "{}-{}", start, cpu)?;
+            }
+            cpu += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Cpuset operation errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpusetError {
+    /// No such cpuset
+    NotFound,
+    /// Empty mask, or CPUs outside the parent cpuset
+    InvalidMask,
+    /// The root cpuset cannot be changed or removed
+    Root,
+    /// The cpuset still has children (or a child would no longer fit)
+    Busy,
+    /// All cpuset slots are in use
+    NoSpace,
+}
+
+#[derive(Copy, Clone)]
+struct Cpuset {
+    parent: CpusetId,
+    cpus: CpuMask,
+}
+
+/// Tree of cpusets, stored by ID
+struct CpusetTable {
+    sets: [Option<Cpuset>; MAX_CPUSETS],
+}
+
+impl CpusetTable {
+    const fn new() -> Self {
+        let mut sets = [None; MAX_CPUSETS];
+        sets[ROOT_CPUSET] = Some(Cpuset {
+            parent: ROOT_CPUSET,
+            cpus: CpuMask::all(),
+        });
+        Self { sets }
+    }
+
+    fn get(&self, id: CpusetId) -> Option<&Cpuset> {
+        self.sets.get(id)?.as_ref()
+    }
+
+    fn children(&self, id: CpusetId) -> impl Iterator<Item = (CpusetId, &Cpuset)> {
+        self.sets
+            .iter()
+            .enumerate()
+            .filter_map(move |(child, set)| match set {
+                Some(set) if child != ROOT_CPUSET && set.parent == id => Some((child, set)),
+                _ => None,
+            })
+    }
+
+    fn create(&mut self, parent: CpusetId, cpus: CpuMask) -> Result<CpusetId, CpusetError> {
+        let parent_cpus = self.get(parent).ok_or(CpusetError::NotFound)?.cpus;
+        if cpus.is_empty() || !cpus.is_subset_of(parent_cpus) {
+            return Err(CpusetError::InvalidMask);
+        }
+
+        let id = self
+            .sets
+            .iter()
+            .position(|set| set.is_none())
+            .ok_or(CpusetError::NoSpace)?;
+        self.sets[id] = Some(Cpuset { parent, cpus });
+        Ok(id)
+    }
+
+    fn set_cpus(&mut self, id: CpusetId, cpus: CpuMask) -> Result<(), CpusetError> {
+        if id == ROOT_CPUSET {
+            return Err(CpusetError::Root);
+        }
+
+        let parent = self.get(id).ok_or(CpusetError::NotFound)?.parent;
+        let parent_cpus = self.get(parent).ok_or(CpusetError::NotFound)?.cpus;
+        if cpus.is_empty() || !cpus.is_subset_of(parent_cpus) {
+            return Err(CpusetError::InvalidMask);
+        }
+
+        // Children must keep fitting inside their parent
+        if self
+            .children(id)
+            .any(|(_, child)| !child.cpus.is_subset_of(cpus))
+        {
+            return Err(CpusetError::Busy);
+        }
+
+        if let Some(set) = self.sets[id].as_mut() {
+            set.cpus = cpus;
+        }
+        Ok(())
+    }
+
+    fn destroy(&mut self, id: CpusetId) -> Result<(), CpusetError> {
+        if id == ROOT_CPUSET {
+            return Err(CpusetError::Root);
+        }
+
+        self.get(id).ok_or(CpusetError::NotFound)?;
+        if self.children(id).next().is_some() {
+            return Err(CpusetError::Busy);
+        }
+
+        self.sets[id] = None;
+        Ok(())
+    }
+}
+
+static CPUSETS: IrqSpinLock<CpusetTable> = IrqSpinLock::new(CpusetTable::new());
+
+/// CPUs of a cpuset (empty if it doesn't exist)
+pub fn cpus(id: CpusetId) -> CpuMask {
+    CPUSETS
+        .lock()
+        .get(id)
+        .map_or(CpuMask::empty(), |set| set.cpus)
+}
+
+/// Check whether a cpuset exists
+pub fn exists(id: CpusetId) -> bool {
+    CPUSETS.lock().get(id).is_some()
+}
+
+/// Create a cpuset below `parent`
+///
+/// # Returns
+/// The new cpuset's ID
+pub fn create(parent: CpusetId, cpus: CpuMask) -> Result<CpusetId, CpusetError> {
+    CPUSETS.lock().create(parent, cpus)
+}
+
+/// Change the CPUs of a cpuset
+///
+/// The new mask must fit inside the parent and still contain every child.
+/// Tasks in the cpuset are moved by the caller (see `sched::set_cpuset_cpus`).
+pub fn set_cpus(id: CpusetId, cpus: CpuMask) -> Result<(), CpusetError> {
+    CPUSETS.lock().set_cpus(id, cpus)
+}
+
+/// Remove a cpuset that has no children
+///
+/// The caller makes sure no task is left in it (see `sched::destroy_cpuset`).
+pub fn destroy(id: CpusetId) -> Result<(), CpusetError> {
+    CPUSETS.lock().destroy(id)
+}
+
+/// Compute where a task may run
+///
+/// `online` is the set of CPUs that are up. Falls back to the cpuset alone
+/// when the affinity mask doesn't intersect it, and to every online CPU if
+/// even that is empty.
+pub fn effective_cpus(cpus_allowed: CpuMask, cpuset: CpusetId, online: CpuMask) -> CpuMask {
+    let set = cpus(cpuset).and(online);
+    let mask = cpus_allowed.and(set);
+
+    if !mask.is_empty() {
+        mask
+    } else if !set.is_empty() {
+        set
+    } else {
+        online
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    #[test]
+    fn test_mask_list_format() {
+        assert_eq!(format!("{}", CpuMask::from_bits(0b1011_1101)), "0,2-5,7");
+        assert_eq!(format!("{}", CpuMask::single(3)), "3");
+        assert_eq!(format!("{}", CpuMask::empty()), "");
+        assert_eq!(CpuMask::first_n(4).bits(), 0xF);
+        assert_eq!(CpuMask::first_n(64), CpuMask::all());
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let mut table = CpusetTable::new();
+        let build = table
+            .create(ROOT_CPUSET, CpuMask::from_bits(0xFFFE))
+            .unwrap();
+        let child = table.create(build, CpuMask::from_bits(0x6)).unwrap();
+
+        // Children must fit inside their parent
+        assert_eq!(
+            table.create(build, CpuMask::single(0)),
+            Err(CpusetError::InvalidMask)
+        );
+        assert_eq!(
+            table.set_cpus(build, CpuMask::from_bits(0xF0)),
+            Err(CpusetError::Busy)
+        );
+        assert_eq!(table.destroy(build), Err(CpusetError::Busy));
+
+        assert_eq!(table.destroy(child), Ok(()));
+        assert_eq!(table.set_cpus(build, CpuMask::from_bits(0xF0)), Ok(()));
+        assert_eq!(table.destroy(ROOT_CPUSET), Err(CpusetError::Root));
+    }
+}
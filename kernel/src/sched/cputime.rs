@@ -0,0 +1,256 @@
+//! CPU Time Accounting
+//!
+//! Every CPU remembers when it last accounted for its time. Whenever it
+//! changes what it is doing (syscall entry and exit, interrupt entry and
+//! exit, a context switch) the time since then is charged to what it was
+//! doing: the running task's user or system time, which also counts as the
+//! CPU's user, nice or system time, or the CPU's idle, irq or softirq time.
+//! Time spent in interrupt handlers is not charged to the interrupted task.
+//!
+//! Without a calibrated TSC the monotonic clock only advances once per tick,
+//! so instead each tick charges a whole tick to what it interrupted.
+
+use super::fair::TICK_NS;
+use super::task::Task;
+use crate::arch::x86_64::smp::percpu::{percpu_current, PerCpu};
+use crate::arch::x86_64::tsc;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// What a CPU spends its time on, in `/proc/stat` order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuState {
+    /// Running a task in user mode
+    User,
+    /// Running a task with a positive nice value in user mode
+    Nice,
+    /// Running a task in the kernel
+    System,
+    /// Running the idle task
+    Idle,
+    /// Handling an interrupt
+    Irq,
+    /// Running expired timers from the timer interrupt
+    Softirq,
+}
+
+/// Number of `CpuState`s
+pub const CPU_STATES: usize = 6;
+
+/// Time a CPU has spent in each state
+#[derive(Debug)]
+pub struct CpuTime {
+    /// Monotonic time of the last accounting point (0 before the first)
+    stamp: AtomicU64,
+    /// Nanoseconds spent in each state, indexed by `CpuState`
+    times: [AtomicU64; CPU_STATES],
+}
+
+impl CpuTime {
+    /// Create zeroed counters
+    pub const fn new() -> Self {
+        Self {
+            stamp: AtomicU64::new(0),
+            times: [const { AtomicU64::new(0) }; CPU_STATES],
+        }
+    }
+
+    /// Nanoseconds spent in `state`
+    pub fn get(&self, state: CpuState) -> u64 {
+        self.times[state as usize].load(Ordering::Relaxed)
+    }
+
+    fn add(&self, state: CpuState, ns: u64) {
+        self.times[state as usize].fetch_add(ns, Ordering::Relaxed);
+    }
+}
+
+impl Default for CpuTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The task running on `percpu`, or None while it runs its idle task
+fn running_task(percpu: &PerCpu) -> Option<&'static mut Task> {
+    match percpu.current_task {
+        Some(id) if id != percpu.idle_task => super::get_task_mut(id),
+        _ => None,
+    }
+}
+
+/// Charge `ns` of running `task` and return the CPU state it counts as
+///
+/// `kernel` says whether the task was in the kernel; None works it out
+/// from whether it is inside a syscall.
+fn charge_task(task: &mut Task, kernel: Option<bool>, ns: u64) -> CpuState {
+    let kernel =
+        kernel.unwrap_or_else(|| !task.syscall_frame.is_null() || task.creds.is_kernel_thread);
+    if kernel {
+        task.rusage.stime_ns += ns;
+        CpuState::System
+    } else {
+        task.rusage.utime_ns += ns;
+        if task.se.nice > 0 {
+            CpuState::Nice
+        } else {
+            CpuState::User
+        }
+    }
+}
+
+/// Charge `ns` to the CPU and, outside interrupt handlers, its running task
+///
+/// `state` is what the CPU was doing; None takes it from the running task.
+fn charge_ns(percpu: &PerCpu, state: Option<CpuState>, ns: u64) {
+    let state = match state {
+        Some(interrupt @ (CpuState::Irq | CpuState::Softirq)) => interrupt,
+        _ => match running_task(percpu) {
+            Some(task) => charge_task(task, state.map(|s| s == CpuState::System), ns),
+            None => CpuState::Idle,
+        },
+    };
+    percpu.cputime.add(state, ns);
+}
+
+/// Charge the time since this CPU's last accounting point
+fn charge(state: Option<CpuState>) {
+    if !tsc::is_calibrated() {
+        return;
+    }
+    let percpu = percpu_current();
+    let now = super::hrtimer::monotonic_ns();
+    let last = percpu.cputime.stamp.swap(now, Ordering::Relaxed);
+    if last != 0 && now > last {
+        charge_ns(percpu, state, now - last);
+    }
+}
+
+/// Account a syscall entry: the task was running in user mode
+pub fn syscall_enter() {
+    charge(Some(CpuState::User));
+}
+
+/// Account a syscall exit: the task was running in the kernel
+pub fn syscall_exit() {
+    charge(Some(CpuState::System));
+}
+
+/// Account an interrupt entry: charge the context it interrupted
+pub fn irq_enter() {
+    charge(None);
+}
+
+/// Account an interrupt exit: charge the handler as irq time
+pub fn irq_exit() {
+    charge(Some(CpuState::Irq));
+}
+
+/// Account the end of expired timer processing as softirq time
+pub fn softirq_exit() {
+    charge(Some(CpuState::Softirq));
+}
+
+/// Charge the running task before it is switched out
+pub fn switch_out() {
+    charge(None);
+}
+
+/// Account a scheduler tick
+///
+/// With a calibrated TSC the interrupt has already been accounted, so this
+/// only brings the running task's times up to date for `RLIMIT_CPU`.
+/// Otherwise the tick charges a whole tick to what it interrupted.
+pub fn tick() {
+    if tsc::is_calibrated() {
+        charge(None);
+    } else {
+        charge_ns(percpu_current(), None, TICK_NS);
+    }
+}
+
+/// Nanoseconds `cpu_id` has spent in `state`
+pub fn cpu_time(cpu_id: usize, state: CpuState) -> u64 {
+    crate::arch::x86_64::smp::percpu::percpu_for(cpu_id)
+        .cputime
+        .get(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_time_starts_at_zero() {
+        let time = CpuTime::new();
+        for state in [
+            CpuState::User,
+            CpuState::Nice,
+            CpuState::System,
+            CpuState::Idle,
+            CpuState::Irq,
+            CpuState::Softirq,
+        ] {
+            assert_eq!(time.get(state), 0);
+        }
+    }
+
+    #[test]
+    fn test_cpu_time_adds_per_state() {
+        let time = CpuTime::new();
+        time.add(CpuState::Idle, 10);
+        time.add(CpuState::Idle, 5);
+        time.add(CpuState::Softirq, 3);
+        assert_eq!(time.get(CpuState::Idle), 15);
+        assert_eq!(time.get(CpuState::Softirq), 3);
+        assert_eq!(time.get(CpuState::User), 0);
+    }
+
+    fn dummy_entry() -> ! {
+        loop {}
+    }
+
+    fn user_task() -> alloc::boxed::Box<Task> {
+        let mut task = alloc::boxed::Box::new(
+            Task::new(
+                1,
+                "user",
+                dummy_entry,
+                crate::sched::task::TaskPriority::Normal,
+            )
+            .expect("Failed to create test task"),
+        );
+        task.creds.is_kernel_thread = false;
+        task
+    }
+
+    #[test]
+    fn test_charge_task_user_and_system() {
+        let mut task = user_task();
+        assert_eq!(charge_task(&mut task, Some(false), 10), CpuState::User);
+        assert_eq!(charge_task(&mut task, Some(true), 4), CpuState::System);
+        assert_eq!(task.rusage.utime_ns, 10);
+        assert_eq!(task.rusage.stime_ns, 4);
+
+        task.se.nice = 5;
+        assert_eq!(charge_task(&mut task, Some(false), 1), CpuState::Nice);
+        assert_eq!(task.rusage.utime_ns, 11);
+    }
+
+    #[test]
+    fn test_charge_task_works_out_kernel_mode() {
+        let mut task = user_task();
+        assert_eq!(charge_task(&mut task, None, 3), CpuState::User);
+
+        // Inside a syscall
+        let mut frame = core::mem::MaybeUninit::<crate::sys::syscall::SyscallFrame>::uninit();
+        task.syscall_frame = frame.as_mut_ptr();
+        assert_eq!(charge_task(&mut task, None, 2), CpuState::System);
+        task.syscall_frame = core::ptr::null_mut();
+
+        // Kernel threads only ever run in the kernel
+        task.creds.is_kernel_thread = true;
+        assert_eq!(charge_task(&mut task, None, 1), CpuState::System);
+        assert_eq!(task.rusage.utime_ns, 3);
+        assert_eq!(task.rusage.stime_ns, 3);
+    }
+}
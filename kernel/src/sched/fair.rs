@@ -0,0 +1,495 @@
+//! Fair-Share Scheduling Class
+//!
+//! This module implements a proportional-share scheduler in the spirit of
+//! Linux CFS. Every task carries a virtual runtime that advances inversely to
+//! its weight, and each CPU always runs the task with the smallest virtual
+//! runtime. Weights are derived from nice values (-20..19); each nice step is
+//! worth roughly 10% of CPU time relative to a neighbour.
+//!
+//! # Architecture
+//!
+//! ```text
+//! Per-CPU FairRunQueue (ordered by vruntime, ties broken by task id):
+//!
+//!              [T3 vr=120]
+//!             /           \
+//!      [T1 vr=80]      [T7 vr=300]
+//!     /
+//! [T5 vr=40]  <- leftmost, runs next
+//! ```
+//!
+//! The ordered structure is a treap whose links live inside each task's
+//! `SchedEntity`, so enqueue and dequeue never allocate. That matters because
+//! the runqueue is manipulated from the timer interrupt, where taking the heap
+//! lock could deadlock against the interrupted task.
+//!
+//! # Accounting
+//!
+//! Runtime is charged one tick at a time by the timer interrupt. The running
+//! task is not part of its CPU's tree; it is re-inserted when it is preempted.
+
+use super::task::Task;
+use crate::config::{SCHED_HZ, SCHED_LATENCY_NS, SCHED_MIN_GRANULARITY_NS};
+
+/// Lowest (most favourable) nice value
+pub const MIN_NICE: i8 = -20;
+
+/// Highest (least favourable) nice value
+pub const MAX_NICE: i8 = 19;
+
+/// Weight of a nice-0 task
+pub const NICE_0_LOAD: u64 = 1024;
+
+/// Runtime charged to the running task per timer tick (nanoseconds)
+pub const TICK_NS: u64 = 1_000_000_000 / SCHED_HZ;
+
+/// Nice value to weight mapping (same table as Linux, nice -20 first)
+///
+/// Consecutive entries differ by ~1.25x, so a task one nice level lower gets
+/// ~10% more CPU than its neighbour.
+const SCHED_PRIO_TO_WEIGHT: [u32; 40] = [
+    88761, 71755, 56483, 46273, 36291, // -20 .. -16
+    29154, 23254, 18705, 14949, 11916, // -15 .. -11
+    9548, 7620, 6100, 4904, 3906, // -10 .. -6
+    3121, 2501, 1991, 1586, 1277, // -5 .. -1
+    1024, 820, 655, 526, 423, // 0 .. 4
+    335, 272, 215, 172, 137, // 5 .. 9
+    110, 87, 70, 56, 45, // 10 .. 14
+    36, 29, 23, 18, 15, // 15 .. 19
+];
+
+/// Convert a nice value to a load weight
+pub fn nice_to_weight(nice: i8) -> u32 {
+    let nice = nice.clamp(MIN_NICE, MAX_NICE);
+    SCHED_PRIO_TO_WEIGHT[(nice - MIN_NICE) as usize]
+}
+
+/// Per-task scheduling state for the fair class
+#[derive(Debug)]
+pub struct SchedEntity {
+    /// Nice value (-20..19)
+    pub nice: i8,
+
+    /// Load weight derived from `nice`
+    pub weight: u32,
+
+    /// Weighted virtual runtime (nanoseconds scaled by NICE_0_LOAD / weight)
+    pub vruntime: u64,
+
+    /// Total runtime ever charged to this task (nanoseconds)
+    pub sum_exec_runtime: u64,
+
+    /// Runtime charged since the task was last picked (nanoseconds)
+    pub slice_exec: u64,
+
+    /// Runqueue this entity is queued on (null when not queued)
+    rq: *const FairRunQueue,
+
+    /// Treap links
+    left: *mut Task,
+    right: *mut Task,
+}
+
+impl SchedEntity {
+    /// Create a new entity with the given nice value
+    pub fn new(nice: i8) -> Self {
+        let nice = nice.clamp(MIN_NICE, MAX_NICE);
+        Self {
+            nice,
+            weight: nice_to_weight(nice),
+            vruntime: 0,
+            sum_exec_runtime: 0,
+            slice_exec: 0,
+            rq: core::ptr::null(),
+            left: core::ptr::null_mut(),
+            right: core::ptr::null_mut(),
+        }
+    }
+
+    /// Change the nice value
+    ///
+    /// The caller must make sure the entity is not queued, since the
+    /// runqueue does not expect keys to change under it.
+    pub fn set_nice(&mut self, nice: i8) {
+        debug_assert!(self.rq.is_null());
+        self.nice = nice.clamp(MIN_NICE, MAX_NICE);
+        self.weight = nice_to_weight(self.nice);
+    }
+
+    /// Check whether the entity is on a runqueue
+    pub fn is_queued(&self) -> bool {
+        !self.rq.is_null()
+    }
+
+    /// Scale real runtime to virtual runtime for this entity's weight
+    fn calc_delta_fair(&self, delta: u64) -> u64 {
+        if self.weight as u64 == NICE_0_LOAD {
+            delta
+        } else {
+            delta * NICE_0_LOAD / self.weight as u64
+        }
+    }
+
+    /// Charge `delta` nanoseconds of runtime to this entity
+    pub fn account(&mut self, delta: u64) {
+        self.sum_exec_runtime += delta;
+        self.slice_exec += delta;
+        self.vruntime += self.calc_delta_fair(delta);
+    }
+}
+
+/// Treap priority for a task (deterministic hash of its id)
+fn treap_priority(task: *mut Task) -> u64 {
+    let id = unsafe { (*task).id } as u64;
+    id.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 16
+}
+
+/// Ordering key of a queued task
+fn key(task: *mut Task) -> (u64, usize) {
+    unsafe { ((*task).se.vruntime, (*task).id) }
+}
+
+/// Per-CPU runqueue for the fair class
+///
+/// Tasks are ordered by (vruntime, id); the leftmost task runs next.
+pub struct FairRunQueue {
+    /// Root of the treap
+    root: *mut Task,
+
+    /// Number of queued tasks
+    nr_running: usize,
+
+    /// Sum of the weights of queued tasks
+    load: u64,
+
+    /// Monotonic floor of the queue's virtual time
+    min_vruntime: u64,
+}
+
+// Safety: the raw task pointers are only dereferenced while holding the
+// per-CPU runqueue lock, and tasks are heap-allocated and never move.
+unsafe impl Send for FairRunQueue {}
+
+impl FairRunQueue {
+    /// Create a new empty runqueue
+    pub const fn new() -> Self {
+        Self {
+            root: core::ptr::null_mut(),
+            nr_running: 0,
+            load: 0,
+            min_vruntime: 0,
+        }
+    }
+
+    /// Get the number of queued tasks
+    pub fn len(&self) -> usize {
+        self.nr_running
+    }
+
+    /// Check if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.nr_running == 0
+    }
+
+    /// Sum of the weights of queued tasks
+    pub fn load(&self) -> u64 {
+        self.load
+    }
+
+    /// Current virtual time floor of this queue
+    pub fn min_vruntime(&self) -> u64 {
+        self.min_vruntime
+    }
+
+    /// Check whether the task is queued on this runqueue
+    pub fn contains(&self, task: &Task) -> bool {
+        core::ptr::eq(task.se.rq, self)
+    }
+
+    /// Insert a task
+    ///
+    /// With `wakeup` set, the task's vruntime is placed relative to the
+    /// queue: brand-new tasks start at `min_vruntime`, tasks that slept get
+    /// at most half a latency period of credit. Preempted tasks keep theirs.
+    ///
+    /// Returns false if the task is already queued somewhere.
+    pub fn enqueue(&mut self, task: &mut Task, wakeup: bool) -> bool {
+        if task.se.is_queued() {
+            return false;
+        }
+
+        if wakeup {
+            let floor = if task.se.sum_exec_runtime == 0 {
+                self.min_vruntime
+            } else {
+                self.min_vruntime.saturating_sub(SCHED_LATENCY_NS / 2)
+            };
+            task.se.vruntime = task.se.vruntime.max(floor);
+        }
+
+        task.se.left = core::ptr::null_mut();
+        task.se.right = core::ptr::null_mut();
+        task.se.rq = self;
+
+        let node = task as *mut Task;
+        self.root = unsafe { Self::insert(self.root, node) };
+        self.nr_running += 1;
+        self.load += task.se.weight as u64;
+        true
+    }
+
+    /// Remove a task queued on this runqueue
+    ///
+    /// Returns false if the task is not queued here.
+    pub fn dequeue(&mut self, task: &mut Task) -> bool {
+        if !self.contains(task) {
+            return false;
+        }
+
+        let node = task as *mut Task;
+        self.root = unsafe { Self::remove(self.root, node) };
+        self.detach(node);
+        true
+    }
+
+    /// Remove and return the task with the smallest vruntime
+    pub fn pop_first(&mut self) -> Option<&'static mut Task> {
+        let node = self.leftmost()?;
+        self.root = unsafe { Self::remove(self.root, node) };
+        self.detach(node);
+        self.update_min_vruntime(None);
+        Some(unsafe { &mut *node })
+    }
+
+    /// Remove and return the task with the largest vruntime that matches `pred`
+    ///
+    /// Used when moving work to another CPU: the task that would run last
+    /// here loses the least by leaving. `pred` filters out tasks that may
+    /// not run on the destination.
+    pub fn pop_last_matching<F>(&mut self, pred: F) -> Option<&'static mut Task>
+    where
+        F: Fn(&Task) -> bool,
+    {
+        let node = unsafe { Self::rightmost_matching(self.root, &pred) };
+        if node.is_null() {
+            return None;
+        }
+        self.root = unsafe { Self::remove(self.root, node) };
+        self.detach(node);
+        Some(unsafe { &mut *node })
+    }
+
+    /// Pick the next task to run and take it off the queue
+    pub fn pick_next(&mut self) -> Option<&'static mut Task> {
+        let task = self.pop_first()?;
+        task.se.slice_exec = 0;
+        Some(task)
+    }
+
+    /// Charge a tick to the running task and advance the queue's virtual time
+    pub fn update_curr(&mut self, curr: &mut SchedEntity, delta: u64) {
+        curr.account(delta);
+        self.update_min_vruntime(Some(curr.vruntime));
+    }
+
+    /// Decide whether the running task has used up its share
+    pub fn check_preempt_tick(&self, curr: &SchedEntity) -> bool {
+        if self.nr_running == 0 {
+            return false;
+        }
+
+        let ideal = self.sched_slice(curr);
+        if curr.slice_exec >= ideal {
+            return true;
+        }
+
+        // Don't bounce tasks faster than the minimum granularity
+        if curr.slice_exec < SCHED_MIN_GRANULARITY_NS {
+            return false;
+        }
+
+        match self.leftmost() {
+            Some(first) => curr.vruntime > key(first).0 + ideal,
+            None => false,
+        }
+    }
+
+    /// Wall-clock slice the running task is entitled to
+    ///
+    /// The scheduling period (one latency target, stretched when there are
+    /// more tasks than fit at minimum granularity) is split by weight.
+    pub fn sched_slice(&self, curr: &SchedEntity) -> u64 {
+        let nr = self.nr_running as u64 + 1;
+        let period = (nr * SCHED_MIN_GRANULARITY_NS).max(SCHED_LATENCY_NS);
+        let total = self.load + curr.weight as u64;
+        period * curr.weight as u64 / total
+    }
+
+    /// Rebase the vruntime of a task arriving from another CPU
+    ///
+    /// Keeps the task's lag relative to the queue it joins, so migrating
+    /// between CPUs neither rewards nor punishes it.
+    pub fn place_migrated(&self, task: &mut Task, src_min_vruntime: u64) {
+        let lag = task.se.vruntime.saturating_sub(src_min_vruntime);
+        task.se.vruntime = self.min_vruntime + lag;
+    }
+
+    fn detach(&mut self, node: *mut Task) {
+        let se = unsafe { &mut (*node).se };
+        se.rq = core::ptr::null();
+        se.left = core::ptr::null_mut();
+        se.right = core::ptr::null_mut();
+        self.nr_running -= 1;
+        self.load -= se.weight as u64;
+    }
+
+    fn update_min_vruntime(&mut self, curr: Option<u64>) {
+        let first = self.leftmost().map(|node| key(node).0);
+        let candidate = match (curr, first) {
+            (Some(c), Some(f)) => c.min(f),
+            (Some(c), None) => c,
+            (None, Some(f)) => f,
+            (None, None) => return,
+        };
+        self.min_vruntime = self.min_vruntime.max(candidate);
+    }
+
+    fn leftmost(&self) -> Option<*mut Task> {
+        let mut node = self.root;
+        if node.is_null() {
+            return None;
+        }
+        unsafe {
+            while !(*node).se.left.is_null() {
+                node = (*node).se.left;
+            }
+        }
+        Some(node)
+    }
+
+    /// Find the largest-keyed task in a subtree that matches `pred` (null if none)
+    unsafe fn rightmost_matching<F>(node: *mut Task, pred: &F) -> *mut Task
+    where
+        F: Fn(&Task) -> bool,
+    {
+        if node.is_null() {
+            return node;
+        }
+
+        let right = Self::rightmost_matching((*node).se.right, pred);
+        if !right.is_null() {
+            return right;
+        }
+
+        if pred(&*node) {
+            return node;
+        }
+
+        Self::rightmost_matching((*node).se.left, pred)
+    }
+
+    /// Insert `new` into the subtree rooted at `node`, returning the new root
+    unsafe fn insert(node: *mut Task, new: *mut Task) -> *mut Task {
+        if node.is_null() {
+            return new;
+        }
+
+        if key(new) < key(node) {
+            (*node).se.left = Self::insert((*node).se.left, new);
+            let left = (*node).se.left;
+            if treap_priority(left) > treap_priority(node) {
+                // Rotate right
+                (*node).se.left = (*left).se.right;
+                (*left).se.right = node;
+                return left;
+            }
+        } else {
+            (*node).se.right = Self::insert((*node).se.right, new);
+            let right = (*node).se.right;
+            if treap_priority(right) > treap_priority(node) {
+                // Rotate left
+                (*node).se.right = (*right).se.left;
+                (*right).se.left = node;
+                return right;
+            }
+        }
+
+        node
+    }
+
+    /// Remove `target` from the subtree rooted at `node`, returning the new root
+    unsafe fn remove(node: *mut Task, target: *mut Task) -> *mut Task {
+        if node.is_null() {
+            return node;
+        }
+
+        if node == target {
+            return Self::merge((*node).se.left, (*node).se.right);
+        }
+
+        if key(target) < key(node) {
+            (*node).se.left = Self::remove((*node).se.left, target);
+        } else {
+            (*node).se.right = Self::remove((*node).se.right, target);
+        }
+
+        node
+    }
+
+    /// Join two subtrees where every key in `a` is smaller than every key in `b`
+    unsafe fn merge(a: *mut Task, b: *mut Task) -> *mut Task {
+        if a.is_null() {
+            return b;
+        }
+        if b.is_null() {
+            return a;
+        }
+
+        if treap_priority(a) > treap_priority(b) {
+            (*a).se.right = Self::merge((*a).se.right, b);
+            a
+        } else {
+            (*b).se.left = Self::merge(a, (*b).se.left);
+            b
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nice_weights() {
+        assert_eq!(nice_to_weight(0), NICE_0_LOAD as u32);
+        assert_eq!(nice_to_weight(MIN_NICE), 88761);
+        assert_eq!(nice_to_weight(MAX_NICE), 15);
+        // Out-of-range values are clamped
+        assert_eq!(nice_to_weight(-40), nice_to_weight(MIN_NICE));
+        assert_eq!(nice_to_weight(40), nice_to_weight(MAX_NICE));
+    }
+
+    #[test]
+    fn test_vruntime_scales_with_weight() {
+        let mut normal = SchedEntity::new(0);
+        let mut niced = SchedEntity::new(5);
+        normal.account(TICK_NS);
+        niced.account(TICK_NS);
+
+        assert_eq!(normal.vruntime, TICK_NS);
+        assert!(niced.vruntime > normal.vruntime * 3);
+        assert_eq!(niced.sum_exec_runtime, TICK_NS);
+    }
+
+    #[test]
+    fn test_slice_split_by_weight() {
+        let mut rq = FairRunQueue::new();
+        rq.nr_running = 1;
+        rq.load = nice_to_weight(0) as u64;
+
+        let heavy = SchedEntity::new(-5);
+        let light = SchedEntity::new(5);
+        assert!(rq.sched_slice(&heavy) > rq.sched_slice(&light));
+        assert!(rq.sched_slice(&heavy) <= SCHED_LATENCY_NS);
+    }
+}
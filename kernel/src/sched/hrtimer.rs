@@ -0,0 +1,793 @@
+//! High-Resolution Timers and Tickless Idle
+//!
+//! Every CPU keeps a min-heap of timers keyed by absolute monotonic time in
+//! nanoseconds, and programs its LAPIC timer for the earliest deadline
+//! instead of interrupting at a fixed rate.
+//!
+//! # Clock Event Modes
+//!
+//! - **TSC-deadline**: The LAPIC fires when the TSC reaches the programmed
+//!   value (IA32_TSC_DEADLINE). Preferred when CPUID advertises it.
+//! - **One-shot**: The LAPIC counts down a computed number of timer ticks.
+//! - **Periodic**: Fallback when the TSC could not be calibrated; the timer
+//!   runs at `SCHED_HZ` and timers expire with tick granularity.
+//!
+//! # Scheduler Tick
+//!
+//! The scheduler tick is just another deadline (`next_tick`). It is stopped
+//! while a CPU runs its idle task, so an idle CPU only wakes for real timers
+//! or IPIs, and restarted as soon as the CPU picks a real task.
+//!
+//! # Timer Actions
+//!
+//! Expired timers either wake a sleeping task or expire an interval timer
+//! (`timer_create`-style), which signals its owner and re-arms itself.
+//! Nothing on the expiry path allocates: the heaps are fixed-size arrays.
+
+use super::task::{TaskId, TaskState};
+use crate::arch::x86_64::apic::LocalApic;
+use crate::arch::x86_64::smp::percpu::{percpu_current, percpu_for};
+use crate::arch::x86_64::{rtc, tsc};
+use crate::config::SCHED_HZ;
+use crate::sync::IrqSpinLock;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Maximum number of pending timers per CPU
+pub const MAX_CPU_TIMERS: usize = 128;
+
+/// Maximum number of interval timers system-wide
+pub const MAX_INTERVAL_TIMERS: usize = 64;
+
+/// Nanoseconds per scheduler tick
+pub const TICK_NS: u64 = 1_000_000_000 / SCHED_HZ;
+
+/// Wall-clock time since the Unix epoch
+pub const CLOCK_REALTIME: usize = 0;
+
+/// Time since boot, never adjusted
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// `timer_settime` flag: the expiry time is absolute
+pub const TIMER_ABSTIME: usize = 1;
+
+/// Shortest delay programmed into the hardware (avoids missed deadlines)
+const MIN_DELTA_NS: u64 = 1_000;
+
+/// Unix time (ns) at monotonic time zero
+static BOOT_UNIX_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Source of unique timer IDs
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// What happens when a timer expires
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TimerAction {
+    /// Wake a task sleeping in `sleep_until`
+    Wake(TaskId),
+    /// Expire the interval timer with this ID, unless it has been armed
+    /// again or disarmed since (its generation changed)
+    Interval(usize, u64),
+}
+
+/// Reference to an armed timer, used to cancel it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimerHandle {
+    cpu: usize,
+    id: u64,
+}
+
+/// How this CPU's LAPIC timer is driven
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClockEventMode {
+    Periodic,
+    OneShot,
+    TscDeadline,
+}
+
+#[derive(Copy, Clone)]
+struct HrTimer {
+    expires: u64,
+    id: u64,
+    action: TimerAction,
+}
+
+impl HrTimer {
+    const fn empty() -> Self {
+        Self {
+            expires: 0,
+            id: 0,
+            action: TimerAction::Interval(0, 0),
+        }
+    }
+}
+
+/// Per-CPU timer queue
+pub struct TimerQueue {
+    /// Binary min-heap ordered by expiry
+    heap: [HrTimer; MAX_CPU_TIMERS],
+    len: usize,
+
+    /// Deadline of the next scheduler tick (None while the tick is stopped)
+    next_tick: Option<u64>,
+
+    /// How the LAPIC timer is programmed
+    mode: ClockEventMode,
+}
+
+impl TimerQueue {
+    /// Create an empty queue (periodic until `init_cpu_timer` runs)
+    pub const fn new() -> Self {
+        Self {
+            heap: [HrTimer::empty(); MAX_CPU_TIMERS],
+            len: 0,
+            next_tick: None,
+            mode: ClockEventMode::Periodic,
+        }
+    }
+
+    /// Check whether the scheduler tick is stopped
+    pub fn tick_stopped(&self) -> bool {
+        self.next_tick.is_none()
+    }
+
+    /// Earliest pending expiry
+    fn first_expiry(&self) -> Option<u64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.heap[0].expires)
+        }
+    }
+
+    fn push(&mut self, timer: HrTimer) -> bool {
+        if self.len >= MAX_CPU_TIMERS {
+            return false;
+        }
+        self.heap[self.len] = timer;
+        self.len += 1;
+        self.sift_up(self.len - 1);
+        true
+    }
+
+    /// Remove the earliest timer if it has expired
+    fn pop_expired(&mut self, now: u64) -> Option<HrTimer> {
+        if self.len == 0 || self.heap[0].expires > now {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    /// Remove a timer by ID
+    fn remove(&mut self, id: u64) -> bool {
+        match self.heap[..self.len].iter().position(|t| t.id == id) {
+            Some(index) => {
+                self.remove_at(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) -> HrTimer {
+        let timer = self.heap[index];
+        self.len -= 1;
+        if index != self.len {
+            self.heap[index] = self.heap[self.len];
+            self.sift_down(index);
+            self.sift_up(index);
+        }
+        timer
+    }
+
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.heap[parent].expires <= self.heap[index].expires {
+                break;
+            }
+            self.heap.swap(parent, index);
+            index = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut index: usize) {
+        loop {
+            let left = 2 * index + 1;
+            let right = left + 1;
+            let mut smallest = index;
+
+            if left < self.len && self.heap[left].expires < self.heap[smallest].expires {
+                smallest = left;
+            }
+            if right < self.len && self.heap[right].expires < self.heap[smallest].expires {
+                smallest = right;
+            }
+            if smallest == index {
+                break;
+            }
+            self.heap.swap(smallest, index);
+            index = smallest;
+        }
+    }
+
+    /// Program the LAPIC for the earliest of the next tick and the next timer
+    fn program(&self, now: u64) {
+        let next = match (self.first_expiry(), self.next_tick) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        let mut lapic = local_apic();
+        match self.mode {
+            ClockEventMode::Periodic => {}
+            ClockEventMode::TscDeadline => {
+                let deadline = next.map_or(0, |ns| tsc::nanos_to_tsc(ns.max(now + MIN_DELTA_NS)));
+                unsafe { tsc::write_deadline(deadline) };
+            }
+            ClockEventMode::OneShot => {
+                let count = next.map_or(0, |ns| {
+                    let delta = ns.saturating_sub(now).max(MIN_DELTA_NS);
+                    // The one-shot timer runs at lapic_hz / 16
+                    let hz = percpu_current().lapic_timer_hz / 16;
+                    let count = delta as u128 * hz as u128 / 1_000_000_000;
+                    count.clamp(1, u32::MAX as u128) as u32
+                });
+                lapic.arm_oneshot(count);
+            }
+        }
+    }
+}
+
+fn local_apic() -> LocalApic {
+    let madt_info = crate::arch::x86_64::acpi::get_madt_info().expect("MADT info not available");
+    unsafe { LocalApic::new(madt_info.lapic_address) }
+}
+
+// ============================================================================
+// Clocks
+// ============================================================================
+
+/// Anchor CLOCK_REALTIME to the CMOS RTC
+///
+/// Must run after the TSC has been calibrated.
+pub fn init_clock() {
+    let unix_ns = rtc::read_unix_time() * 1_000_000_000;
+    BOOT_UNIX_NS.store(unix_ns.saturating_sub(monotonic_ns()), Ordering::SeqCst);
+    crate::serial_println!(
+        "[TIMER] Clock: TSC {} Hz, wall time {} s since epoch",
+        tsc::frequency(),
+        unix_ns / 1_000_000_000
+    );
+}
+
+/// Nanoseconds since boot
+///
+/// Falls back to tick granularity if the TSC is not calibrated.
+pub fn monotonic_ns() -> u64 {
+    if tsc::is_calibrated() {
+        tsc::nanos()
+    } else {
+        super::timer::get_interrupt_count() as u64 * TICK_NS
+    }
+}
+
+/// Nanoseconds since the Unix epoch
+pub fn realtime_ns() -> u64 {
+    BOOT_UNIX_NS.load(Ordering::Relaxed) + monotonic_ns()
+}
+
+/// Read a clock by ID
+pub fn clock_ns(clock: usize) -> Option<u64> {
+    match clock {
+        CLOCK_REALTIME => Some(realtime_ns()),
+        CLOCK_MONOTONIC => Some(monotonic_ns()),
+        _ => None,
+    }
+}
+
+/// Convert an absolute time on `clock` to monotonic time
+fn to_monotonic(clock: usize, ns: u64) -> u64 {
+    if clock == CLOCK_REALTIME {
+        ns.saturating_sub(BOOT_UNIX_NS.load(Ordering::Relaxed))
+    } else {
+        ns
+    }
+}
+
+// ============================================================================
+// Timer Queue Operations
+// ============================================================================
+
+/// Arm a timer on the current CPU
+///
+/// # Returns
+/// A handle for `cancel`, or None if this CPU's timer queue is full
+pub fn arm(expires: u64, action: TimerAction) -> Option<TimerHandle> {
+    let percpu = percpu_current();
+    let id = NEXT_TIMER_ID.fetch_add(1, Ordering::Relaxed);
+    let mut queue = percpu.timers.lock();
+
+    if !queue.push(HrTimer {
+        expires,
+        id,
+        action,
+    }) {
+        return None;
+    }
+
+    if queue.first_expiry() == Some(expires) {
+        queue.program(monotonic_ns());
+    }
+
+    Some(TimerHandle { cpu: percpu.id, id })
+}
+
+/// Cancel an armed timer
+///
+/// # Returns
+/// true if the timer was still pending
+pub fn cancel(handle: TimerHandle) -> bool {
+    percpu_for(handle.cpu).timers.lock().remove(handle.id)
+}
+
+/// Check whether the current CPU's scheduler tick is stopped
+pub fn tick_stopped() -> bool {
+    percpu_current().timers.lock().tick_stopped()
+}
+
+/// Stop or restart the scheduler tick on the current CPU
+///
+/// Called by the scheduler whenever it picks the next task: the tick is
+/// only needed while a real task runs, or while real-time tasks wait for
+/// a throttled period to end.
+pub fn update_tick(idle: bool) {
+    let mut queue = percpu_current().timers.lock();
+    if queue.mode == ClockEventMode::Periodic {
+        return;
+    }
+
+    let now = monotonic_ns();
+    match (idle, queue.next_tick) {
+        (true, Some(_)) => {
+            queue.next_tick = None;
+            queue.program(now);
+        }
+        (false, None) => {
+            queue.next_tick = Some(now + TICK_NS);
+            queue.program(now);
+        }
+        _ => {}
+    }
+}
+
+/// Configure the current CPU's LAPIC timer and start the scheduler tick
+///
+/// Uses TSC-deadline mode when available, one-shot mode when the TSC is
+/// calibrated, and the old periodic mode otherwise.
+///
+/// # Safety
+/// Must be called once per CPU during bring-up, with interrupts disabled.
+pub unsafe fn init_cpu_timer(lapic: &mut LocalApic) {
+    let percpu = percpu_current();
+
+    let mode = if !tsc::is_calibrated() {
+        ClockEventMode::Periodic
+    } else if tsc::has_deadline_mode() {
+        ClockEventMode::TscDeadline
+    } else {
+        ClockEventMode::OneShot
+    };
+
+    match mode {
+        ClockEventMode::Periodic => lapic.init_timer(percpu.lapic_timer_hz, SCHED_HZ),
+        ClockEventMode::OneShot => lapic.init_oneshot_timer(),
+        ClockEventMode::TscDeadline => lapic.init_tsc_deadline_timer(),
+    }
+
+    let mut queue = percpu.timers.lock();
+    queue.mode = mode;
+    let now = monotonic_ns();
+    queue.next_tick = Some(now + TICK_NS);
+    queue.program(now);
+
+    crate::serial_println!("[TIMER] core{} clock event mode: {:?}", percpu.id, mode);
+}
+
+/// Handle a LAPIC timer interrupt
+///
+/// Runs every expired timer, advances the tick and reprograms the LAPIC.
+///
+/// # Returns
+/// true if the scheduler tick is due
+pub fn timer_interrupt() -> bool {
+    let percpu = percpu_current();
+    let now = monotonic_ns();
+
+    // The handler so far counts as irq time, running timers as softirq time
+    super::cputime::irq_exit();
+    loop {
+        let timer = match percpu.timers.lock().pop_expired(now) {
+            Some(timer) => timer,
+            None => break,
+        };
+        run_action(timer.action, now);
+    }
+    super::cputime::softirq_exit();
+
+    let mut queue = percpu.timers.lock();
+    if queue.mode == ClockEventMode::Periodic {
+        return true;
+    }
+
+    let tick_due = match queue.next_tick {
+        Some(deadline) if now >= deadline => {
+            // Skip ticks that were missed rather than firing them back to back
+            let next = deadline + TICK_NS;
+            queue.next_tick = Some(if next <= now { now + TICK_NS } else { next });
+            true
+        }
+        _ => false,
+    };
+
+    queue.program(now);
+    tick_due
+}
+
+fn run_action(action: TimerAction, now: u64) {
+    match action {
+        TimerAction::Wake(task_id) => {
+            if let Some(task) = super::get_task_mut(task_id) {
+                if task.state == TaskState::Sleeping && task.sleep_timer.is_some() {
+                    task.sleep_timer = None;
+                    task.wake_tick = None;
+                    task.state = TaskState::Ready;
+                    super::enqueue_task(task_id, None);
+                }
+            }
+        }
+        TimerAction::Interval(id, generation) => expire_interval_timer(id, generation, now),
+    }
+}
+
+// ============================================================================
+// Sleeping
+// ============================================================================
+
+/// Put the current task to sleep until `deadline` (monotonic ns)
+///
+/// # Returns
+/// true if the full time elapsed, false if the sleep was interrupted or
+/// could not be armed
+pub fn sleep_until(deadline: u64) -> bool {
+    sleep_until_if(deadline, || Some(()))
+}
+
+/// Put the current task to sleep until `deadline` unless `prepare` objects
+///
+/// `prepare` runs with interrupts off just before the task is marked as
+/// sleeping. Returning None skips the sleep; otherwise the returned guard
+/// (typically a lock guard) is dropped only once the task is `Sleeping`,
+/// so a waker that takes the same lock and then calls `interrupt_sleep`
+/// cannot miss the sleeper.
+///
+/// # Returns
+/// true if the full time elapsed, false if the sleep was skipped,
+/// interrupted or could not be armed
+pub fn sleep_until_if<G>(deadline: u64, prepare: impl FnOnce() -> Option<G>) -> bool {
+    let task_id = match percpu_current().current_task {
+        Some(id) => id,
+        None => return false,
+    };
+
+    // Keep the timer from firing before we have switched away
+    let armed = x86_64::instructions::interrupts::without_interrupts(|| {
+        let task = match super::get_task_mut(task_id) {
+            Some(task) => task,
+            None => return false,
+        };
+
+        let guard = match prepare() {
+            Some(guard) => guard,
+            None => return false,
+        };
+
+        let handle = match arm(deadline, TimerAction::Wake(task_id)) {
+            Some(handle) => handle,
+            None => return false,
+        };
+
+        task.sleep_timer = Some(handle);
+        task.wake_tick = Some(deadline / TICK_NS);
+        task.state = TaskState::Sleeping;
+        drop(guard);
+        super::schedule();
+        true
+    });
+
+    armed && monotonic_ns() >= deadline
+}
+
+/// Sleep for `ns` nanoseconds
+pub fn sleep_ns(ns: u64) -> bool {
+    sleep_until(monotonic_ns() + ns)
+}
+
+/// Wake a sleeping task before its deadline (e.g. to deliver a signal)
+///
+/// # Returns
+/// true if the task was sleeping
+pub fn interrupt_sleep(task_id: TaskId) -> bool {
+    let task = match super::get_task_mut(task_id) {
+        Some(task) => task,
+        None => return false,
+    };
+
+    let handle = match task.sleep_timer {
+        Some(handle) if task.state == TaskState::Sleeping => handle,
+        _ => return false,
+    };
+
+    cancel(handle);
+    task.sleep_timer = None;
+    task.wake_tick = None;
+    task.state = TaskState::Ready;
+    super::enqueue_task(task_id, None);
+    true
+}
+
+// ============================================================================
+// Interval Timers
+// ============================================================================
+
+/// A `timer_create`-style interval timer
+#[derive(Copy, Clone)]
+struct IntervalTimer {
+    /// Owning task (None = free slot)
+    owner: Option<TaskId>,
+    /// Clock the timer was created on
+    clock: usize,
+    /// Signal sent on expiry
+    signal: u32,
+    /// Next expiry (monotonic ns, 0 = disarmed)
+    expires: u64,
+    /// Reload value (ns, 0 = one-shot)
+    interval: u64,
+    /// Expirations missed since the last signal
+    overrun: u32,
+    /// Pending hrtimer
+    handle: Option<TimerHandle>,
+    /// Generation of the current arming (0 = disarmed), carried by its
+    /// hrtimer so a stale expiry that lost the race with `cancel` is ignored
+    generation: u64,
+}
+
+/// Source of interval timer generations
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+
+impl IntervalTimer {
+    const fn free() -> Self {
+        Self {
+            owner: None,
+            clock: CLOCK_MONOTONIC,
+            signal: 0,
+            expires: 0,
+            interval: 0,
+            overrun: 0,
+            handle: None,
+            generation: 0,
+        }
+    }
+
+    fn disarm(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            cancel(handle);
+        }
+        self.expires = 0;
+        self.generation = 0;
+    }
+}
+
+/// All interval timers (lock before any per-CPU timer queue)
+static INTERVAL_TIMERS: IrqSpinLock<[IntervalTimer; MAX_INTERVAL_TIMERS]> =
+    IrqSpinLock::new([IntervalTimer::free(); MAX_INTERVAL_TIMERS]);
+
+/// Create an interval timer owned by `owner`
+///
+/// # Returns
+/// The timer ID, or None if the clock is unknown or no slot is free
+pub fn timer_create(owner: TaskId, clock: usize, signal: u32) -> Option<usize> {
+    clock_ns(clock)?;
+
+    let mut timers = INTERVAL_TIMERS.lock();
+    let id = timers.iter().position(|t| t.owner.is_none())?;
+    timers[id] = IntervalTimer {
+        owner: Some(owner),
+        clock,
+        signal,
+        ..IntervalTimer::free()
+    };
+    Some(id)
+}
+
+/// Arm or disarm an interval timer
+///
+/// `value` is the first expiry (relative, or absolute on the timer's clock
+/// with `TIMER_ABSTIME`; 0 disarms) and `interval` the reload period.
+///
+/// # Returns
+/// The previous (remaining, interval) pair, or None if `id` is not owned
+/// by `owner` or the timer queue is full
+pub fn timer_settime(
+    id: usize,
+    owner: TaskId,
+    flags: usize,
+    value: u64,
+    interval: u64,
+) -> Option<(u64, u64)> {
+    let mut timers = INTERVAL_TIMERS.lock();
+    let timer = timers.get_mut(id).filter(|t| t.owner == Some(owner))?;
+
+    let now = monotonic_ns();
+    let old = (timer.expires.saturating_sub(now), timer.interval);
+    timer.disarm();
+    timer.interval = interval;
+    timer.overrun = 0;
+
+    if value == 0 {
+        return Some(old);
+    }
+
+    let expires = if flags & TIMER_ABSTIME != 0 {
+        to_monotonic(timer.clock, value)
+    } else {
+        now + value
+    };
+
+    timer.expires = expires;
+    timer.generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    timer.handle = Some(arm(expires, TimerAction::Interval(id, timer.generation))?);
+    Some(old)
+}
+
+/// Read an interval timer
+///
+/// # Returns
+/// (remaining, interval), or None if `id` is not owned by `owner`
+pub fn timer_gettime(id: usize, owner: TaskId) -> Option<(u64, u64)> {
+    let timers = INTERVAL_TIMERS.lock();
+    let timer = timers.get(id).filter(|t| t.owner == Some(owner))?;
+    let remaining = if timer.expires == 0 {
+        0
+    } else {
+        timer.expires.saturating_sub(monotonic_ns()).max(1)
+    };
+    Some((remaining, timer.interval))
+}
+
+/// Read and reset an interval timer's overrun count
+pub fn timer_getoverrun(id: usize, owner: TaskId) -> Option<u32> {
+    let mut timers = INTERVAL_TIMERS.lock();
+    let timer = timers.get_mut(id).filter(|t| t.owner == Some(owner))?;
+    Some(core::mem::take(&mut timer.overrun))
+}
+
+/// Delete an interval timer
+///
+/// # Returns
+/// false if `id` is not owned by `owner`
+pub fn timer_delete(id: usize, owner: TaskId) -> bool {
+    let mut timers = INTERVAL_TIMERS.lock();
+    match timers.get_mut(id).filter(|t| t.owner == Some(owner)) {
+        Some(timer) => {
+            timer.disarm();
+            *timer = IntervalTimer::free();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Delete every interval timer owned by a task (on exit)
+pub fn timer_delete_all(owner: TaskId) {
+    let mut timers = INTERVAL_TIMERS.lock();
+    for timer in timers.iter_mut().filter(|t| t.owner == Some(owner)) {
+        timer.disarm();
+        *timer = IntervalTimer::free();
+    }
+}
+
+fn expire_interval_timer(id: usize, generation: u64, now: u64) {
+    let (owner, signal) = {
+        let mut timers = INTERVAL_TIMERS.lock();
+        let timer = match timers.get_mut(id) {
+            Some(timer) if timer.generation == generation => timer,
+            _ => return,
+        };
+
+        let owner = match timer.owner {
+            Some(owner) => owner,
+            None => return,
+        };
+
+        timer.handle = None;
+        if timer.interval == 0 {
+            timer.expires = 0;
+            timer.generation = 0;
+        } else {
+            // Count periods that passed while the timer was pending
+            let missed = now.saturating_sub(timer.expires) / timer.interval;
+            timer.overrun = timer.overrun.saturating_add(missed as u32);
+            timer.expires += (missed + 1) * timer.interval;
+            timer.handle = arm(timer.expires, TimerAction::Interval(id, generation));
+        }
+
+        (owner, timer.signal)
+    };
+
+    if let Some(task) = super::get_task_mut(owner) {
+        let _ = crate::signal::send_signal(task, signal);
+        interrupt_sleep(owner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(expires: u64, id: u64) -> HrTimer {
+        HrTimer {
+            expires,
+            id,
+            action: TimerAction::Interval(0, 0),
+        }
+    }
+
+    #[test]
+    fn test_heap_order() {
+        let mut queue = TimerQueue::new();
+        for (id, expires) in [50u64, 10, 40, 30, 20].iter().enumerate() {
+            assert!(queue.push(timer(*expires, id as u64)));
+        }
+
+        assert!(queue.pop_expired(5).is_none());
+        let mut order = [0u64; 5];
+        for slot in order.iter_mut() {
+            *slot = queue.pop_expired(100).unwrap().expires;
+        }
+        assert_eq!(order, [10, 20, 30, 40, 50]);
+    }
+
+    #[test]
+    fn test_heap_remove() {
+        let mut queue = TimerQueue::new();
+        queue.push(timer(30, 1));
+        queue.push(timer(10, 2));
+        queue.push(timer(20, 3));
+
+        assert!(queue.remove(2));
+        assert!(!queue.remove(2));
+        assert_eq!(queue.first_expiry(), Some(20));
+        assert_eq!(queue.len, 2);
+    }
+
+    #[test]
+    fn test_stale_interval_expiry_ignored() {
+        let id = MAX_INTERVAL_TIMERS - 1;
+        INTERVAL_TIMERS.lock()[id] = IntervalTimer {
+            owner: Some(usize::MAX),
+            expires: 1_000,
+            generation: 7,
+            ..IntervalTimer::free()
+        };
+
+        // An expiry from before the timer was re-armed changes nothing
+        expire_interval_timer(id, 6, 2_000);
+        assert_eq!(INTERVAL_TIMERS.lock()[id].expires, 1_000);
+
+        // The current one fires the one-shot timer and disarms it
+        expire_interval_timer(id, 7, 2_000);
+        let timer = INTERVAL_TIMERS.lock()[id];
+        assert_eq!((timer.expires, timer.generation), (0, 0));
+        INTERVAL_TIMERS.lock()[id] = IntervalTimer::free();
+    }
+}
@@ -0,0 +1,549 @@
+//! Kernel threads
+//!
+//! A kernel thread runs a closure on its own task with a larger stack than
+//! `spawn_task` gives. The creator keeps a `KThread` handle to control it:
+//!
+//! - `stop` asks the thread to finish and waits for its exit code. The
+//!   thread polls `kthread_should_stop` and returns from its closure.
+//! - `join` waits for the thread to return on its own.
+//! - `park` asks the thread to pause at a safe point and waits until it
+//!   has. The thread polls `kthread_should_park` and calls
+//!   `kthread_parkme`, which blocks until `unpark`.
+//! - `wake_up` cuts short a `kthread_sleep_ns`, e.g. when new work arrives.
+//!
+//! `kthread_create_on_cpu` binds the thread to one CPU; its affinity and
+//! cpuset can't be changed afterwards. Threads are listed in
+//! `/proc/kthreads`.
+//!
+//! An exited thread is a zombie until it is reaped, which frees its stack,
+//! `Task` and ID. A task cannot free itself, so the handle reaps it: when
+//! dropped (after `stop` or `join`) if the thread has exited, otherwise
+//! the thread is detached and goes on `DETACHED` when it exits, to be
+//! reaped by the next `kthread_create`, `stop` or `join`.
+//!
+//! Blocking follows the rest of the scheduler: the waiter marks itself
+//! `Blocked` and calls `schedule()`, and the waker marks it `Ready` and
+//! enqueues it. Every flag lives under one lock per thread, so a wake-up
+//! can't slip in between a waiter checking a flag and going to sleep.
+
+use super::cpuset::{CpuMask, ROOT_CPUSET};
+use super::priority::TaskPriority;
+use super::task::{SchedulerError, SchedulerResult, Task, TaskId, TaskState};
+use super::{get_task_mut, hrtimer, percpu_current};
+use crate::sync::SpinLock;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use x86_64::instructions::interrupts::without_interrupts;
+
+/// Body of a kernel thread; its return value is the exit code
+type ThreadFn = Box<dyn FnOnce() -> i32 + Send>;
+
+/// Lifecycle flags shared between a thread and its handle
+struct KThreadState {
+    /// `stop` was called
+    should_stop: bool,
+    /// `park` was called and `unpark` hasn't been yet
+    should_park: bool,
+    /// The thread is blocked in `kthread_parkme`
+    parked: bool,
+    /// The thread has returned from its closure
+    exited: bool,
+    /// Value returned by the closure (valid once `exited`)
+    exit_code: i32,
+    /// Tasks blocked in `park`, `stop` or `join`
+    waiters: Vec<TaskId>,
+    /// The handle was dropped before the thread exited
+    detached: bool,
+}
+
+/// Kernel thread control block, reachable from the thread's `Task`
+pub struct KThreadInfo {
+    /// Task running the thread
+    id: TaskId,
+    /// CPU the thread is bound to (None = may run anywhere)
+    cpu: Option<usize>,
+    /// Closure to run, taken by the thread when it starts
+    func: SpinLock<Option<ThreadFn>>,
+    /// Lifecycle flags
+    state: SpinLock<KThreadState>,
+}
+
+impl core::fmt::Debug for KThreadInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("KThreadInfo")
+            .field("id", &self.id)
+            .field("cpu", &self.cpu)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KThreadInfo {
+    fn new(id: TaskId, cpu: Option<usize>, func: Option<ThreadFn>) -> Self {
+        Self {
+            id,
+            cpu,
+            func: SpinLock::new(func),
+            state: SpinLock::new(KThreadState {
+                should_stop: false,
+                should_park: false,
+                parked: false,
+                exited: false,
+                exit_code: 0,
+                waiters: Vec::new(),
+                detached: false,
+            }),
+        }
+    }
+
+    /// Block the current task until `done` holds
+    fn wait_until(&self, done: impl Fn(&KThreadState) -> bool) {
+        let me = match percpu_current().current_task {
+            Some(id) => id,
+            None => return,
+        };
+
+        loop {
+            let finished = without_interrupts(|| {
+                let mut state = self.state.lock();
+                if done(&state) {
+                    return true;
+                }
+
+                if !state.waiters.contains(&me) {
+                    state.waiters.push(me);
+                }
+                if let Some(task) = get_task_mut(me) {
+                    task.state = TaskState::Blocked;
+                }
+                drop(state);
+
+                super::schedule();
+                false
+            });
+
+            if finished {
+                return;
+            }
+        }
+    }
+}
+
+/// Handle to a kernel thread
+///
+/// Dropping the handle reaps the thread if it has exited. Otherwise it
+/// detaches the thread, which keeps running until its closure returns.
+pub struct KThread {
+    info: Arc<KThreadInfo>,
+}
+
+impl KThread {
+    /// Task ID of the thread
+    pub fn id(&self) -> TaskId {
+        self.info.id
+    }
+
+    /// CPU the thread is bound to, if any
+    pub fn cpu(&self) -> Option<usize> {
+        self.info.cpu
+    }
+
+    /// Check whether the thread has returned from its closure
+    pub fn has_exited(&self) -> bool {
+        self.info.state.lock().exited
+    }
+
+    /// Check whether this is the thread currently running
+    pub fn is_current(&self) -> bool {
+        percpu_current().current_task == Some(self.info.id)
+    }
+
+    /// Cut short the thread's `kthread_sleep_ns`, if it is in one
+    pub fn wake_up(&self) {
+        hrtimer::interrupt_sleep(self.info.id);
+    }
+
+    /// Ask the thread to park and wait until it has
+    ///
+    /// Returns immediately if the thread has already exited. Must not be
+    /// called from the thread itself.
+    pub fn park(&self) {
+        self.info.state.lock().should_park = true;
+        self.wake_up();
+        self.info.wait_until(|state| state.parked || state.exited);
+    }
+
+    /// Let a parked thread continue
+    pub fn unpark(&self) {
+        let mut state = self.info.state.lock();
+        state.should_park = false;
+        if state.parked {
+            wake_blocked(self.info.id);
+        }
+    }
+
+    /// Ask the thread to stop and wait for it to exit
+    ///
+    /// A parked thread is unparked first so it can see the request.
+    /// Must not be called from the thread itself.
+    ///
+    /// # Returns
+    /// The thread's exit code
+    pub fn stop(self) -> i32 {
+        {
+            let mut state = self.info.state.lock();
+            state.should_stop = true;
+            state.should_park = false;
+            if state.parked {
+                wake_blocked(self.info.id);
+            }
+        }
+        self.wake_up();
+
+        self.join()
+    }
+
+    /// Wait for the thread to return from its closure
+    ///
+    /// Must not be called from the thread itself.
+    ///
+    /// # Returns
+    /// The thread's exit code
+    pub fn join(self) -> i32 {
+        self.info.wait_until(|state| state.exited);
+        reap_detached();
+        self.info.state.lock().exit_code
+    }
+}
+
+impl Drop for KThread {
+    /// Reap the thread if it has exited, otherwise detach it
+    fn drop(&mut self) {
+        let exited = {
+            let mut state = self.info.state.lock();
+            state.detached = !state.exited;
+            state.exited
+        };
+        if exited {
+            super::release_task(self.info.id);
+        }
+    }
+}
+
+/// Detached kernel threads that have exited and wait to be reaped
+static DETACHED: SpinLock<Vec<TaskId>> = SpinLock::new(Vec::new());
+
+/// Free the detached kernel threads that have exited
+fn reap_detached() {
+    let exited = core::mem::take(&mut *DETACHED.lock());
+    for id in exited {
+        super::release_task(id);
+    }
+}
+
+/// Make a `Blocked` task runnable again
+///
+/// The task goes back to the CPU it last ran on: it may have marked itself
+/// blocked without having switched away yet, and that CPU is the only one
+/// that won't run it before its context is saved.
+fn wake_blocked(task_id: TaskId) {
+    if let Some(task) = get_task_mut(task_id) {
+        if task.state == TaskState::Blocked {
+            task.state = TaskState::Ready;
+            super::enqueue_task(task_id, task.last_cpu);
+        }
+    }
+}
+
+/// Control block of the current task, if it is a kernel thread
+fn current_kthread() -> Option<Arc<KThreadInfo>> {
+    super::current_task()?.kthread.clone()
+}
+
+/// Entry point of every kernel thread
+fn kthread_entry() -> ! {
+    let info = match current_kthread() {
+        Some(info) => info,
+        None => panic!("[KTHREAD] kthread_entry run by an ordinary task"),
+    };
+
+    let func = info.func.lock().take();
+    let code = match func {
+        // A thread stopped before it first ran never calls its closure
+        Some(func) if !info.state.lock().should_stop => func(),
+        _ => 0,
+    };
+
+    exit_thread(&info, code)
+}
+
+/// Record the exit code, wake `stop` and `join` callers and leave the CPU
+/// for good as a zombie
+fn exit_thread(info: &KThreadInfo, code: i32) -> ! {
+    without_interrupts(|| {
+        if let Some(task) = get_task_mut(info.id) {
+            task.state = TaskState::Zombie;
+        }
+
+        for waiter in mark_exited(info, code) {
+            wake_blocked(waiter);
+        }
+
+        crate::sched_log!("Kernel thread {} exited with code {}", info.id, code);
+        super::schedule();
+    });
+
+    panic!(
+        "[KTHREAD] Exited kernel thread {} was scheduled again",
+        info.id
+    );
+}
+
+/// Record that the thread has exited with `code`
+///
+/// A detached thread is queued for reaping.
+///
+/// # Returns
+/// The tasks waiting for it
+fn mark_exited(info: &KThreadInfo, code: i32) -> Vec<TaskId> {
+    let mut state = info.state.lock();
+    state.exited = true;
+    state.exit_code = code;
+    if state.detached {
+        DETACHED.lock().push(info.id);
+    }
+    core::mem::take(&mut state.waiters)
+}
+
+/// Create a kernel thread and start it
+///
+/// # Arguments
+/// * `name` - Name shown in /proc
+/// * `func` - Thread body; its return value is the exit code
+///
+/// # Returns
+/// A handle to the running thread
+///
+/// # Errors
+/// Same as `spawn_task`
+pub fn kthread_create<F>(name: &'static str, func: F) -> SchedulerResult<KThread>
+where
+    F: FnOnce() -> i32 + Send + 'static,
+{
+    create(name, None, Box::new(func))
+}
+
+/// Create a kernel thread bound to one CPU and start it
+///
+/// The thread's affinity is that CPU alone, outside any cpuset, and can't
+/// be changed.
+///
+/// # Arguments
+/// * `name` - Name shown in /proc
+/// * `cpu` - CPU to run on
+/// * `func` - Thread body; its return value is the exit code
+///
+/// # Errors
+/// `InvalidCpu` if `cpu` is not online, otherwise the same as `spawn_task`
+pub fn kthread_create_on_cpu<F>(name: &'static str, cpu: usize, func: F) -> SchedulerResult<KThread>
+where
+    F: FnOnce() -> i32 + Send + 'static,
+{
+    if cpu >= super::get_cpu_count() {
+        return Err(SchedulerError::InvalidCpu);
+    }
+
+    create(name, Some(cpu), Box::new(func))
+}
+
+fn create(name: &'static str, cpu: Option<usize>, func: ThreadFn) -> SchedulerResult<KThread> {
+    reap_detached();
+
+    let mut func = Some(func);
+    let mut info = None;
+
+    super::spawn_task_in(
+        name,
+        kthread_entry,
+        TaskPriority::Normal,
+        crate::config::KTHREAD_STACK_SIZE,
+        |task: &mut Task| {
+            let new = Arc::new(KThreadInfo::new(task.id, cpu, func.take()));
+
+            // Kernel threads don't inherit their creator's confinement
+            task.cpuset = ROOT_CPUSET;
+            task.cpus_allowed = match cpu {
+                Some(cpu) => CpuMask::single(cpu),
+                None => CpuMask::all(),
+            };
+            task.kthread = Some(new.clone());
+            info = Some(new);
+        },
+    )?;
+
+    Ok(KThread {
+        info: info.expect("kernel thread spawned without its control block"),
+    })
+}
+
+// ============================================================================
+// Called from the thread
+// ============================================================================
+
+/// Check whether the current kernel thread has been asked to stop
+///
+/// The thread should clean up and return from its closure.
+pub fn kthread_should_stop() -> bool {
+    current_kthread().is_some_and(|info| info.state.lock().should_stop)
+}
+
+/// Check whether the current kernel thread has been asked to park
+pub fn kthread_should_park() -> bool {
+    current_kthread().is_some_and(|info| info.state.lock().should_park)
+}
+
+/// Park the current kernel thread until it is unparked (or stopped)
+///
+/// Returns immediately if no park was requested.
+pub fn kthread_parkme() {
+    let info = match current_kthread() {
+        Some(info) => info,
+        None => return,
+    };
+
+    loop {
+        let resumed = without_interrupts(|| {
+            let mut state = info.state.lock();
+            if !state.should_park {
+                state.parked = false;
+                return true;
+            }
+
+            state.parked = true;
+            let waiters = core::mem::take(&mut state.waiters);
+            if let Some(task) = get_task_mut(info.id) {
+                task.state = TaskState::Blocked;
+            }
+            drop(state);
+
+            for waiter in waiters {
+                wake_blocked(waiter);
+            }
+            super::schedule();
+            false
+        });
+
+        if resumed {
+            return;
+        }
+    }
+}
+
+/// Sleep for `ns` nanoseconds in a kernel thread
+///
+/// Unlike `hrtimer::sleep_ns`, a `stop` or `park` request can't be missed:
+/// the sleep is skipped if one is pending and cut short if one arrives.
+/// `KThread::wake_up` also ends it early.
+///
+/// # Returns
+/// true if the full time elapsed
+pub fn kthread_sleep_ns(ns: u64) -> bool {
+    let deadline = hrtimer::monotonic_ns() + ns;
+    let info = match current_kthread() {
+        Some(info) => info,
+        None => return hrtimer::sleep_until(deadline),
+    };
+
+    hrtimer::sleep_until_if(deadline, || {
+        let state = info.state.lock();
+        (!state.should_stop && !state.should_park).then_some(state)
+    })
+}
+
+// ============================================================================
+// Queries
+// ============================================================================
+
+/// Check whether a task is a kernel thread bound to one CPU
+pub fn is_bound(task: &Task) -> bool {
+    task.kthread.as_ref().is_some_and(|info| info.cpu.is_some())
+}
+
+/// Write the kernel thread list shown in /proc/kthreads
+pub fn write_list(out: &mut dyn Write) -> core::fmt::Result {
+    let mut threads = Vec::new();
+    super::for_each_task(|task| {
+        if let Some(info) = task.kthread.clone() {
+            threads.push((task.id, task.name, task.state, task.last_cpu, info));
+        }
+    });
+
+    writeln!(out, "  TID  CPU  LAST STATE   NAME")?;
+    for (id, name, task_state, last_cpu, info) in threads {
+        let state = {
+            let flags = info.state.lock();
+            if flags.exited {
+                "exited"
+            } else if flags.parked {
+                "parked"
+            } else if flags.should_stop {
+                "stopping"
+            } else {
+                match task_state {
+                    TaskState::Running => "running",
+                    TaskState::Ready => "ready",
+                    TaskState::Sleeping | TaskState::Blocked => "sleeping",
+                    TaskState::Zombie => "exited",
+                }
+            }
+        };
+
+        match info.cpu {
+            Some(cpu) => write!(out, "{:5} {:4}", id, cpu)?,
+            None => write!(out, "{:5}    *", id)?,
+        }
+        match last_cpu {
+            Some(cpu) => write!(out, " {:5}", cpu)?,
+            None => write!(out, "     -")?,
+        }
+        writeln!(out, " {:8} {}", state, name)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(id: TaskId) -> Arc<KThreadInfo> {
+        Arc::new(KThreadInfo::new(id, None, None))
+    }
+
+    #[test]
+    fn test_exit_wakes_waiters() {
+        let info = info(1001);
+        info.state.lock().waiters.extend([1, 2]);
+
+        assert_eq!(mark_exited(&info, 3), [1, 2]);
+        let state = info.state.lock();
+        assert!(state.exited);
+        assert_eq!(state.exit_code, 3);
+        assert!(state.waiters.is_empty());
+        assert!(!DETACHED.lock().contains(&1001));
+    }
+
+    #[test]
+    fn test_dropped_handle_detaches_running_thread() {
+        let info = info(1002);
+        drop(KThread { info: info.clone() });
+        assert!(info.state.lock().detached);
+
+        // It is queued for reaping when it exits
+        mark_exited(&info, 0);
+        let mut detached = DETACHED.lock();
+        assert!(detached.contains(&1002));
+        detached.retain(|&id| id != 1002);
+    }
+}
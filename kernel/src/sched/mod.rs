@@ -1,17 +1,26 @@
 //! Task Scheduler Module
 //!
-//! This module implements a preemptive multitasking scheduler using Round-Robin algorithm.
+//! This module implements a preemptive multitasking scheduler. Real-time tasks
+//! (SCHED_FIFO / SCHED_RR, see `rt`) run first; everything else is picked by the
+//! fair-share class in `fair` (virtual runtime weighted by nice value).
 //! It manages task creation, context switching, and timer-based preemption.
+//! Sleeps and the scheduler tick itself are driven by the per-CPU timer heaps
+//! in `hrtimer`; the tick is stopped while a CPU is idle. Tasks only run on
+//! CPUs allowed by their affinity mask and cpuset (see `cpuset`), and are
+//! spread across them by `balance`. Kernel background work runs in threads
+//! created through `kthread`. Time spent by tasks and CPUs is accounted in
+//! `cputime`. Released tasks are freed after a grace period (`rcu`).
 //!
 //! # SMP Safety and Lock Ordering
 //!
 //! The scheduler is designed to work correctly in SMP environments with multiple CPUs.
 //! To prevent deadlocks, locks must be acquired in the following order:
 //!
-//! 1. SCHED (global scheduler state)
-//! 2. TASK_TABLE (global task table)
-//! 3. Per-CPU runqueue locks (in ascending CPU ID order)
-//! 4. Per-task state (implicit in get_task_mut)
+//! 1. Task release lock (`release_task`)
+//! 2. Task ID allocator (`pid`, a leaf lock)
+//! 3. TASK_TABLE shard locks (one at a time, see `table`)
+//! 4. Per-CPU runqueue locks (in ascending CPU ID order)
+//! 5. Per-task state (implicit in get_task_mut)
 //!
 //! ## Key SMP Design Decisions
 //!
@@ -22,15 +31,25 @@
 //!
 //! ## Critical Sections
 //!
-//! - Task creation: Holds SCHED and TASK_TABLE briefly, then releases before enqueuing
+//! - Task creation: Holds the ID allocator and one TASK_TABLE shard briefly, then releases before enqueuing
 //! - Task migration: Holds two runqueue locks in CPU ID order
 //! - Context switch: Only accesses current CPU's runqueue (no cross-CPU locks)
 //!
 //! See `kernel/src/sync/lock_ordering.rs` for complete lock ordering documentation.
 
+pub mod balance;
 pub mod context;
+pub mod cpuset;
+pub mod cputime;
+pub mod fair;
+pub mod hrtimer;
+pub mod kthread;
+pub mod pid;
 pub mod priority;
 pub mod process_group;
+pub mod rcu;
+pub mod rt;
+mod table;
 pub mod task;
 pub mod timer;
 
@@ -75,134 +94,21 @@ macro_rules! sched_error {
 }
 
 use crate::arch::x86_64::smp::percpu::{percpu_current, percpu_for};
-use alloc::vec::Vec;
+use crate::user::process::WaitStatus;
+pub use balance::balance_load;
 use context::CpuContext;
+use cpuset::{CpuMask, CpusetError, CpusetId};
 use priority::TaskPriority;
-use spin::Mutex;
 pub use task::Task;
-use task::{SchedulerError, SchedulerResult, TaskId, TaskState};
-
-/// Maximum number of tasks supported
-const MAX_TASKS: usize = 64;
-
-/// Maximum number of tasks per CPU runqueue (from percpu.rs)
-const MAX_RUNQUEUE_SIZE: usize = 64;
-
-/// Wrapper for task pointer that implements Sync
-///
-/// # Safety
-/// This is safe because:
-/// - We only access tasks through the TASK_TABLE mutex
-/// - Each task is only accessed by one context at a time
-/// - Tasks are heap-allocated and don't move
-#[derive(Copy, Clone)]
-struct TaskPtr(*mut Task);
-
-unsafe impl Sync for TaskPtr {}
-unsafe impl Send for TaskPtr {}
-
-impl TaskPtr {
-    const fn null() -> Self {
-        Self(core::ptr::null_mut())
-    }
-
-    fn new(ptr: *mut Task) -> Self {
-        Self(ptr)
-    }
-
-    fn get(&self) -> *mut Task {
-        self.0
-    }
-
-    fn is_null(&self) -> bool {
-        self.0.is_null()
-    }
-}
-
-/// Simple circular queue for task IDs
-struct TaskQueue {
-    tasks: [TaskId; MAX_TASKS],
-    head: usize,
-    tail: usize,
-    count: usize,
-}
-
-impl TaskQueue {
-    const fn new() -> Self {
-        Self {
-            tasks: [0; MAX_TASKS],
-            head: 0,
-            tail: 0,
-            count: 0,
-        }
-    }
-
-    fn push_back(&mut self, task_id: TaskId) -> bool {
-        if self.count >= MAX_TASKS {
-            return false;
-        }
-
-        self.tasks[self.tail] = task_id;
-        self.tail = (self.tail + 1) % MAX_TASKS;
-        self.count += 1;
-        true
-    }
-
-    fn pop_front(&mut self) -> Option<TaskId> {
-        if self.count == 0 {
-            return None;
-        }
-
-        let task_id = self.tasks[self.head];
-        self.head = (self.head + 1) % MAX_TASKS;
-        self.count -= 1;
-        Some(task_id)
-    }
-
-    fn len(&self) -> usize {
-        self.count
-    }
-
-    fn is_empty(&self) -> bool {
-        self.count == 0
-    }
-
-    fn clear(&mut self) {
-        self.head = 0;
-        self.tail = 0;
-        self.count = 0;
-    }
-}
-
-/// Scheduler state containing global task management
-///
-/// Note: Runqueues are now per-CPU (in PerCpu structure)
-struct SchedState {
-    /// Next task ID to assign (incremented for each new task)
-    next_tid: usize,
-}
-
-impl SchedState {
-    /// Create a new empty scheduler state
-    fn new() -> Self {
-        Self {
-            next_tid: 1, // Start at 1, reserve 0 for idle task
-        }
-    }
-}
-
-/// Global scheduler state protected by a mutex
-static SCHED: spin::Once<Mutex<SchedState>> = spin::Once::new();
+use task::{SchedulerError, SchedulerResult, TaskId, TaskState, DEFAULT_STACK_SIZE};
 
 /// Get the number of online CPUs from SMP module
 fn get_cpu_count() -> usize {
     crate::arch::x86_64::smp::get_cpu_count()
 }
 
-/// Task table storing all Task objects
-/// Uses TaskPtr wrapper for heap-allocated tasks
-/// TaskPtr::null() indicates an empty slot
-static TASK_TABLE: Mutex<[TaskPtr; MAX_TASKS]> = Mutex::new([TaskPtr::null(); MAX_TASKS]);
+/// Task table storing all Task objects, keyed by task ID
+static TASK_TABLE: table::TaskTable = table::TaskTable::new();
 
 /// Spawn a new task with the given entry point
 ///
@@ -222,40 +128,54 @@ static TASK_TABLE: Mutex<[TaskPtr; MAX_TASKS]> = Mutex::new([TaskPtr::null(); MA
 /// A Result containing the TaskId of the newly spawned task, or an error if spawning fails
 ///
 /// # Errors
-/// Returns `SchedulerError::TooManyTasks` if the task table is full
+/// Returns `SchedulerError::TooManyTasks` if every ID below `pid_max` is in use
 /// Returns `SchedulerError::OutOfMemory` if memory allocation fails
 /// Returns `SchedulerError::RunqueueFull` if the runqueue is full
 pub fn spawn_task(
     name: &'static str,
     entry_point: fn() -> !,
     priority: TaskPriority,
+) -> SchedulerResult<TaskId> {
+    spawn_task_in(name, entry_point, priority, DEFAULT_STACK_SIZE, |_| {})
+}
+
+/// Spawn a task with a `stack_size`-byte kernel stack
+///
+/// `setup` runs on the new task before it becomes runnable (after the
+/// affinity and cpuset inherited from the creator are applied).
+pub(crate) fn spawn_task_in(
+    name: &'static str,
+    entry_point: fn() -> !,
+    priority: TaskPriority,
+    stack_size: usize,
+    setup: impl FnOnce(&mut Task),
 ) -> SchedulerResult<TaskId> {
     use crate::mm::allocator::kmalloc;
     use core::ptr;
 
-    // Lock both SCHED and TASK_TABLE
-    let mut sched = SCHED.get().expect("Scheduler not initialized").lock();
-    let task_table = TASK_TABLE.lock();
-
-    // 1. Generate unique TaskId
-    let task_id = sched.next_tid;
-
-    if task_id >= MAX_TASKS {
-        sched_error!("Too many tasks! Maximum is {}", MAX_TASKS);
-        return Err(SchedulerError::TooManyTasks);
-    }
-
-    sched.next_tid += 1;
-
-    // Drop locks before creating task (to avoid holding locks during allocation)
-    drop(sched);
-    drop(task_table);
+    // 1. Allocate a unique TaskId
+    let task_id = match pid::alloc_pid() {
+        Some(id) => id,
+        None => {
+            sched_error!("Too many tasks! pid_max is {}", pid::pid_max());
+            return Err(SchedulerError::TooManyTasks);
+        }
+    };
 
     // 2. Create new Task with specified priority
-    let task = match Task::new(task_id, name, entry_point, priority) {
-        Ok(task) => task,
+    let task = match Task::with_stack_size(task_id, name, entry_point, priority, stack_size) {
+        Ok(mut task) => {
+            // Children stay where their creator was confined
+            if let Some(parent) = current_task() {
+                task.cpus_allowed = parent.cpus_allowed;
+                task.cpuset = parent.cpuset;
+            }
+            setup(&mut task);
+            task
+        }
         Err(e) => {
             sched_error!("Failed to create task {}: {:?}", task_id, e);
+            pid::free_pid(task_id);
             return Err(e);
         }
     };
@@ -266,6 +186,7 @@ pub fn spawn_task(
 
     if task_ptr.is_null() {
         sched_error!("Failed to allocate memory for task {} ({})", task_id, name);
+        pid::free_pid(task_id);
         return Err(SchedulerError::OutOfMemory);
     }
 
@@ -273,9 +194,7 @@ pub fn spawn_task(
         ptr::write(task_ptr, task);
     }
 
-    let mut task_table = TASK_TABLE.lock();
-    task_table[task_id] = TaskPtr::new(task_ptr);
-    drop(task_table);
+    TASK_TABLE.insert(task_id, task_ptr);
 
     // 4. Enqueue task to a CPU runqueue (will select CPU with smallest runqueue)
     enqueue_task(task_id, None);
@@ -302,24 +221,15 @@ pub fn spawn_task(
 /// # Safety
 /// This function returns a 'static mutable reference, which is safe because:
 /// - Tasks are allocated on the heap and don't move
-/// - We only access tasks while holding appropriate locks
+/// - A released task is only freed after every CPU has passed a quiescent
+///   state, so the reference stays valid until this CPU next schedules
+///   (see `rcu`)
 /// - Each task is only accessed by one context at a time
 fn get_task(id: TaskId) -> Option<&'static mut Task> {
-    let task_table = TASK_TABLE.lock();
-
-    if id >= MAX_TASKS {
-        return None;
-    }
-
-    // Get the task pointer
-    let task_ptr = task_table[id];
-
-    if task_ptr.is_null() {
-        return None;
-    }
+    let task_ptr = TASK_TABLE.get(id)?;
 
     // Convert to static reference (safe because task is heap-allocated and doesn't move)
-    unsafe { Some(&mut *task_ptr.get()) }
+    unsafe { Some(&mut *task_ptr) }
 }
 
 /// Schedule the next task on a specific CPU core
@@ -349,34 +259,30 @@ fn schedule_on_core(cpu_id: usize) -> Option<(&'static mut Task, &'static mut Ta
     }
 
     // Move current task back to runqueue if it's still ready
-    if let Some(current_id) = old_task_id {
+    // (the idle task is never queued; it runs only when the queue is empty)
+    if let Some(current_id) = old_task_id.filter(|&id| id != percpu.idle_task) {
         if let Some(task) = get_task(current_id) {
             // Only re-enqueue if task is still in Running state
             // (it might have been put to sleep or blocked)
             if task.state == TaskState::Running {
                 task.state = TaskState::Ready;
-                let mut runqueue = percpu.runqueue.lock();
-                if !runqueue.push_back(current_id) {
-                    sched_warn!("CPU {} runqueue full, dropping task {}", cpu_id, current_id);
+                if task_cpus(task).contains(cpu_id) {
+                    percpu.runqueue.lock().enqueue(task, false);
+                } else {
+                    // Its affinity changed; another CPU may only pick it up
+                    // once the switch below has saved its context
+                    percpu.migrate_pending = Some(current_id);
                 }
             }
         }
     }
 
     // Select next task from this CPU's runqueue
-    let next_task_id = {
-        let mut runqueue = percpu.runqueue.lock();
-        match runqueue.pop_front() {
-            Some(id) => id,
-            None => {
-                // Runqueue empty - use idle task
-                percpu.idle_task
-            }
-        }
-    };
+    let next_task_id = pick_next_task(cpu_id);
 
     // Update current task in PerCpu
     percpu.current_task = Some(next_task_id);
+    percpu.prev_task = old_task_id.filter(|&id| id != next_task_id && id != percpu.idle_task);
 
     // Get task references
     let old_task = old_task_id.and_then(|id| get_task(id));
@@ -394,12 +300,143 @@ fn schedule_on_core(cpu_id: usize) -> Option<(&'static mut Task, &'static mut Ta
     }
 }
 
+/// Take the task with the smallest vruntime off a CPU's runqueue
+///
+/// When the runqueue is empty the CPU first tries to steal work from a
+/// busier one. Failing that it falls back to its idle task, in which case
+/// the scheduler tick is stopped until a real task is picked again. The
+/// tick keeps running while real-time tasks wait for their throttled
+/// period to end, since only the tick advances the period.
+fn pick_next_task(cpu_id: usize) -> TaskId {
+    let percpu = percpu_for(cpu_id);
+    let mut next = percpu.runqueue.lock().pick_next();
+
+    if next.is_none() && balance::idle_steal(cpu_id) {
+        next = percpu.runqueue.lock().pick_next();
+    }
+
+    let next = match next {
+        Some(task) => {
+            task.last_cpu = Some(cpu_id);
+            task.on_cpu
+                .store(true, core::sync::atomic::Ordering::Release);
+            task.id
+        }
+        None => percpu.idle_task,
+    };
+
+    let rt_waiting = {
+        let runqueue = percpu.runqueue.lock();
+        runqueue.rt.is_throttled() && !runqueue.rt.is_empty()
+    };
+    hrtimer::update_tick(next == percpu.idle_task && !rt_waiting);
+    next
+}
+
+/// Load the address space of the task about to run
+///
+/// Kernel threads carry the kernel template PML4; user tasks carry their
+/// private PML4. PCID bookkeeping lives in `mm::pcid`.
+fn switch_mm(next: &Task) {
+    crate::mm::pcid::switch_address_space(next.context.cr3 as usize);
+}
+
+/// Load the per-thread CPU state of the task about to run
+///
+/// The FS base (the user TLS pointer) and the FPU state are per thread;
+/// everything else is restored by `context_switch` or on return to user
+/// mode. The outgoing task's FPU state must have been saved first.
+fn load_thread_state(next: &Task) {
+    unsafe {
+        crate::arch::x86_64::tls::write_fs_base(next.context.fs_base);
+    }
+    next.fpu.restore();
+}
+
 /// Global counter for context switches (for logging throttling)
 pub(crate) static SWITCH_COUNT: core::sync::atomic::AtomicUsize =
     core::sync::atomic::AtomicUsize::new(0);
 
+/// Charge the running task for one tick and check whether it must yield
+///
+/// Returns true when the CPU should switch tasks: the running task has
+/// used up its share (or its class was throttled), a higher class has
+/// work waiting, it is no longer runnable or allowed on this core, the idle
+/// task has work waiting behind it, or nothing has run on this core yet.
+fn scheduler_tick(cpu_id: usize) -> bool {
+    let percpu = percpu_for(cpu_id);
+
+    let current_id = match percpu.current_task {
+        Some(id) => id,
+        None => return true,
+    };
+
+    let task = if current_id == percpu.idle_task {
+        None
+    } else {
+        match get_task(current_id) {
+            Some(task) => Some(task),
+            None => return true,
+        }
+    };
+
+    let rt_running = task.as_ref().map_or(false, |t| t.rt.is_rt());
+    let misplaced = task
+        .as_ref()
+        .map_or(false, |t| !task_cpus(t).contains(cpu_id));
+    let running_load = task.as_ref().map_or(0, |t| balance::task_load(t));
+    let mut runqueue = percpu.runqueue.lock();
+    let throttled = runqueue.rt.update_period(fair::TICK_NS, rt_running);
+    percpu
+        .load
+        .update(balance::now_ticks(), runqueue.load() + running_load);
+
+    let task = match task {
+        Some(task) => task,
+        None => return runqueue.has_runnable(),
+    };
+
+    if task.state != TaskState::Running || misplaced {
+        return true;
+    }
+
+    if throttled {
+        sched_warn!("CPU {} real-time runtime exhausted, throttling", cpu_id);
+    }
+
+    if task.rt.is_rt() {
+        task.se.sum_exec_runtime += fair::TICK_NS;
+        runqueue.rt.check_preempt_tick(&mut task.rt, fair::TICK_NS)
+    } else {
+        runqueue.fair.update_curr(&mut task.se, fair::TICK_NS);
+        runqueue.rt.has_runnable() || runqueue.fair.check_preempt_tick(&task.se)
+    }
+}
+
 /// Scheduler tick function - called by timer interrupt
 ///
+/// Accounts the tick to the running task, enforces its CPU time limit, and
+/// switches only when the fair class says it has had its share.
+pub fn tick() {
+    use core::sync::atomic::Ordering;
+
+    // Increment timer_ticks metric
+    crate::sys::METRICS
+        .timer_ticks
+        .fetch_add(1, Ordering::Relaxed);
+
+    cputime::tick();
+    let reschedule = scheduler_tick(percpu_current().id);
+    if let Some(task) = current_task_mut() {
+        crate::user::rlimit::charge_tick(task);
+    }
+    if reschedule {
+        schedule();
+    }
+}
+
+/// Switch to the next task on this CPU
+///
 /// This function:
 /// 1. Determines the current CPU ID
 /// 2. Calls schedule_on_core() to get old and new tasks
@@ -410,16 +447,15 @@ pub(crate) static SWITCH_COUNT: core::sync::atomic::AtomicUsize =
 /// - This function does not return in the traditional sense (tail-switch)
 /// - The next task will continue execution from where it was interrupted
 /// - For new tasks, execution starts at entry_trampoline
-pub fn tick() {
+pub fn schedule() {
     use core::sync::atomic::Ordering;
 
-    // Increment timer_ticks metric
-    crate::sys::METRICS
-        .timer_ticks
-        .fetch_add(1, Ordering::Relaxed);
-
     // Get current CPU ID
     let cpu_id = percpu_current().id;
+    rcu::quiescent(cpu_id);
+
+    // Charge the outgoing task up to the switch
+    cputime::switch_out();
 
     // Get next task to run on this core
     let tasks = schedule_on_core(cpu_id);
@@ -442,11 +478,19 @@ pub fn tick() {
             .fetch_add(1, Ordering::Relaxed);
 
         // Check if this is a preemptive switch (old task was still Running/Ready)
-        if old_task.state == TaskState::Running || old_task.state == TaskState::Ready {
+        let preempted = old_task.state == TaskState::Running || old_task.state == TaskState::Ready;
+        if preempted {
             crate::sys::METRICS
                 .preemptions
                 .fetch_add(1, Ordering::Relaxed);
         }
+        if old_task.id != new_task.id {
+            if preempted {
+                old_task.rusage.nivcsw += 1;
+            } else {
+                old_task.rusage.nvcsw += 1;
+            }
+        }
 
         // Log context switch with throttling
         // First 10 switches: log every switch
@@ -463,6 +507,9 @@ pub fn tick() {
 
         // Perform context switch
         // This is a tail-switch: we don't return to this function
+        old_task.fpu.save();
+        switch_mm(new_task);
+        load_thread_state(new_task);
         unsafe {
             context::context_switch(
                 &mut old_task.context as *mut CpuContext,
@@ -470,24 +517,16 @@ pub fn tick() {
             );
         }
 
-        // Note: We never reach here because context_switch doesn't return
-        // The next task will continue from where it was interrupted
+        // We only get here once this task is switched back in, possibly on
+        // another core; the task that ran before it may need finishing off
+        finish_task_switch();
     } else {
         // First switch on this core - no old task yet
         // We need to manually set up the first task and jump to it
         let percpu = unsafe { crate::arch::x86_64::smp::percpu::percpu_for_mut(cpu_id) };
 
         // Select the first task from this core's runqueue
-        let first_task_id = {
-            let mut runqueue = percpu.runqueue.lock();
-            match runqueue.pop_front() {
-                Some(id) => id,
-                None => {
-                    // No tasks in runqueue - use idle task
-                    percpu.idle_task
-                }
-            }
-        };
+        let first_task_id = pick_next_task(cpu_id);
 
         percpu.current_task = Some(first_task_id);
 
@@ -518,16 +557,10 @@ pub fn tick() {
             // For the first switch, we need to manually jump to the task
             // We'll use a dummy context for the "old" task (which is the kernel boot code)
             // This context will never be used again
-            let mut dummy_context = CpuContext {
-                r15: 0,
-                r14: 0,
-                r13: 0,
-                r12: 0,
-                rbp: 0,
-                rbx: 0,
-                rsp: 0, // Will be filled by context_switch
-                cr3: 0, // Dummy value, will not be used
-            };
+            let mut dummy_context = CpuContext::new();
+
+            switch_mm(first_task);
+            load_thread_state(first_task);
 
             unsafe {
                 context::context_switch(
@@ -544,12 +577,31 @@ pub fn tick() {
     }
 }
 
+/// Complete a context switch on the task that was just switched in
+///
+/// Marks the previous task as off the CPU, so load balancing may move it,
+/// and queues it on another CPU if it had to leave this one. That can only
+/// happen after `context_switch` has saved its registers, which is why it
+/// isn't done in `schedule_on_core`. Also called from `entry_trampoline`
+/// for tasks that run for the first time.
+pub(crate) extern "C" fn finish_task_switch() {
+    let percpu = unsafe { crate::arch::x86_64::smp::percpu::percpu_current_mut() };
+    if let Some(task) = percpu.prev_task.take().and_then(get_task) {
+        task.on_cpu
+            .store(false, core::sync::atomic::Ordering::Release);
+    }
+    if let Some(task_id) = percpu.migrate_pending.take() {
+        enqueue_task(task_id, None);
+    }
+}
+
 /// Idle task entry point
 ///
 /// This task runs when no other tasks are available.
 /// It simply halts the CPU until the next interrupt.
 fn idle_task() -> ! {
     loop {
+        rcu::quiescent(percpu_current().id);
         unsafe {
             core::arch::asm!("hlt");
         }
@@ -586,6 +638,14 @@ pub fn current_task() -> Option<&'static Task> {
     get_task(current_id).map(|t| &*t)
 }
 
+/// Get a mutable reference to the current task
+///
+/// Only the task itself (or code running on its behalf, like a syscall)
+/// should change its fields this way.
+pub fn current_task_mut() -> Option<&'static mut Task> {
+    get_task(percpu_current().current_task?)
+}
+
 /// Get task priority by ID
 ///
 /// Returns the task's ID and priority, or None if task doesn't exist
@@ -608,36 +668,246 @@ pub fn get_task_by_id(task_id: TaskId) -> Option<&'static Task> {
     get_task(task_id).map(|t| &*t)
 }
 
-/// Apply a closure to every task in the specified process group.
+/// Change a task's scheduling parameters
 ///
-/// This helper collects matching tasks while holding the task-table lock
-/// and then invokes the closure without the lock to avoid re-entrancy issues.
-pub fn for_each_task_in_group<F>(pgid: process_group::Pgid, mut f: F)
+/// `f` runs under the runqueue lock of the CPU the task is queued or
+/// running on, so it cannot race with that CPU's tick charging the task.
+/// A queued task is taken off its runqueue while `f` runs so the runqueue's
+/// ordering and load stay consistent. If the change moves the task to a
+/// different class it is placed in the new class like a woken task.
+///
+/// # Returns
+/// false if the task doesn't exist
+fn change_task_sched<F>(task_id: TaskId, f: F) -> bool
 where
-    F: FnMut(&'static mut Task),
+    F: FnOnce(&mut Task),
 {
-    let mut target_ptrs: Vec<*mut Task> = Vec::new();
+    let task = match get_task(task_id) {
+        Some(task) => task,
+        None => return false,
+    };
+
+    loop {
+        for cpu in 0..get_cpu_count() {
+            let percpu = percpu_for(cpu);
+            let mut runqueue = percpu.runqueue.lock();
+            if runqueue.contains(task) {
+                let was_rt = task.rt.is_rt();
+                runqueue.dequeue(task);
+                f(task);
+                runqueue.enqueue(task, was_rt != task.rt.is_rt());
+                return true;
+            }
+            if percpu.current_task == Some(task_id) {
+                // Requeued with its new parameters when it is switched out
+                f(task);
+                return true;
+            }
+        }
+
+        // Not runnable - no runqueue to fix up
+        if !matches!(task.state, TaskState::Ready | TaskState::Running) {
+            f(task);
+            return true;
+        }
+        // Moved between runqueues while we looked; look again
+        core::hint::spin_loop();
+    }
+}
+
+/// Change a task's nice value
+///
+/// # Returns
+/// The clamped nice value actually applied, or None if the task doesn't exist
+pub fn set_task_nice(task_id: TaskId, nice: i8) -> Option<i8> {
+    let nice = nice.clamp(fair::MIN_NICE, fair::MAX_NICE);
+
+    if change_task_sched(task_id, |task| task.se.set_nice(nice)) {
+        Some(nice)
+    } else {
+        None
+    }
+}
+
+/// Change a task's scheduling policy and real-time priority
+///
+/// `rt_priority` must be 1..99 for real-time policies and 0 for SCHED_OTHER;
+/// the caller validates it.
+///
+/// # Returns
+/// false if the task doesn't exist
+pub fn set_task_scheduler(task_id: TaskId, policy: rt::SchedPolicy, rt_priority: u8) -> bool {
+    change_task_sched(task_id, |task| {
+        task.rt.policy = policy;
+        task.rt.rt_priority = rt_priority;
+        task.rt.update_prio();
+        sched_log!(
+            "Task {} policy {:?} priority {} (effective {})",
+            task.id,
+            policy,
+            rt_priority,
+            task.rt.prio
+        );
+    })
+}
+
+/// Set the priority a task inherits through an `RtMutex` (0 removes the boost)
+///
+/// # Returns
+/// false if the task doesn't exist
+pub fn set_task_pi_prio(task_id: TaskId, pi_prio: u8) -> bool {
+    change_task_sched(task_id, |task| {
+        task.rt.pi_prio = pi_prio;
+        task.rt.update_prio();
+    })
+}
+
+/// CPUs a task may run on right now
+///
+/// Its affinity mask within its cpuset, limited to online CPUs.
+pub fn task_cpus(task: &Task) -> CpuMask {
+    let online = CpuMask::first_n(get_cpu_count());
+    cpuset::effective_cpus(task.cpus_allowed, task.cpuset, online)
+}
+
+/// Move a task off a CPU it may no longer use
+///
+/// A queued task is requeued on an allowed CPU. A task running on a
+/// disallowed CPU is preempted there; `schedule_on_core` then hands it over.
+fn enforce_affinity(task_id: TaskId) {
+    let task = match get_task(task_id) {
+        Some(task) => task,
+        None => return,
+    };
+    let allowed = task_cpus(task);
 
-    {
-        let task_table = TASK_TABLE.lock();
-        for task_ptr in task_table.iter() {
-            if task_ptr.is_null() {
-                continue;
+    for cpu in 0..get_cpu_count() {
+        let percpu = percpu_for(cpu);
+
+        if percpu.current_task == Some(task_id) && percpu.idle_task != task_id {
+            if !allowed.contains(cpu) && cpu != percpu_current().id {
+                use crate::arch::x86_64::apic::ipi::send_reschedule_ipi;
+                send_reschedule_ipi(cpu);
             }
+            return;
+        }
 
-            let task = unsafe { &*task_ptr.get() };
-            if task.pgid == pgid {
-                target_ptrs.push(task_ptr.get());
+        let mut runqueue = percpu.runqueue.lock();
+        if runqueue.contains(task) {
+            if !allowed.contains(cpu) {
+                runqueue.dequeue(task);
+                drop(runqueue);
+                enqueue_task(task_id, None);
             }
+            return;
         }
     }
+}
 
-    for ptr in target_ptrs {
-        unsafe {
-            let task = &mut *ptr;
-            f(task);
+/// Set a task's CPU affinity mask
+///
+/// A task running elsewhere is preempted right away; the calling task
+/// itself moves at its next tick or yield.
+///
+/// # Returns
+/// false if the task doesn't exist, is a per-CPU kernel thread, or the mask
+/// has no CPU the task's cpuset allows
+pub fn set_task_affinity(task_id: TaskId, mask: CpuMask) -> bool {
+    let task = match get_task(task_id) {
+        Some(task) => task,
+        None => return false,
+    };
+
+    if kthread::is_bound(task) {
+        return false;
+    }
+
+    let online = CpuMask::first_n(get_cpu_count());
+    if mask.and(cpuset::cpus(task.cpuset)).and(online).is_empty() {
+        return false;
+    }
+
+    task.cpus_allowed = mask;
+    enforce_affinity(task_id);
+    true
+}
+
+/// Move a task into a cpuset
+///
+/// # Returns
+/// false if the task or the cpuset doesn't exist, or the task is a per-CPU
+/// kernel thread
+pub fn set_task_cpuset(task_id: TaskId, cpuset: CpusetId) -> bool {
+    if !cpuset::exists(cpuset) {
+        return false;
+    }
+
+    match get_task(task_id) {
+        Some(task) if !kthread::is_bound(task) => task.cpuset = cpuset,
+        _ => return false,
+    }
+
+    enforce_affinity(task_id);
+    true
+}
+
+/// Change a cpuset's CPUs and move its tasks accordingly
+pub fn set_cpuset_cpus(id: CpusetId, cpus: CpuMask) -> Result<(), CpusetError> {
+    cpuset::set_cpus(id, cpus)?;
+
+    // Tasks in child cpusets are unaffected: their CPUs had to fit already
+    for_each_task(|task| {
+        if task.cpuset == id {
+            enforce_affinity(task.id);
         }
+    });
+    Ok(())
+}
+
+/// Remove an empty cpuset
+///
+/// Fails with `Busy` while tasks or child cpusets remain in it.
+pub fn destroy_cpuset(id: CpusetId) -> Result<(), CpusetError> {
+    let mut in_use = false;
+    for_each_task(|task| in_use |= task.cpuset == id);
+    if in_use {
+        return Err(CpusetError::Busy);
     }
+
+    cpuset::destroy(id)
+}
+
+/// Apply a closure to every task.
+///
+/// Like `for_each_task_in_group`, the closure runs without the task-table
+/// lock, but with interrupts disabled so no task in the snapshot is freed
+/// meanwhile (see `rcu`). It must not sleep.
+pub fn for_each_task<F>(mut f: F)
+where
+    F: FnMut(&'static mut Task),
+{
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        for ptr in TASK_TABLE.snapshot() {
+            unsafe {
+                f(&mut *ptr);
+            }
+        }
+    })
+}
+
+/// Apply a closure to every task in the specified process group.
+///
+/// Like `for_each_task`, this works on a snapshot of the task table and
+/// invokes the closure without any table lock to avoid re-entrancy issues.
+pub fn for_each_task_in_group<F>(pgid: process_group::Pgid, mut f: F)
+where
+    F: FnMut(&'static mut Task),
+{
+    for_each_task(|task| {
+        if task.pgid == pgid {
+            f(task);
+        }
+    });
 }
 
 /// Get a task as an Arc for exec() operations
@@ -655,40 +925,31 @@ where
 /// # Returns
 /// An Arc<Task> wrapping the task, or None if task doesn't exist
 pub fn get_task_arc(task_id: TaskId) -> Option<alloc::sync::Arc<Task>> {
-    let task_table = TASK_TABLE.lock();
+    let task_ptr = TASK_TABLE.get(task_id)?;
 
-    // Find the task in the table
-    for task_ptr in task_table.iter() {
-        if task_ptr.is_null() {
-            continue;
-        }
+    // Create an Arc from the raw pointer
+    // SAFETY: The task is heap-allocated and won't be freed during exec
+    // We use ManuallyDrop to prevent the Arc from freeing the task
+    // when it's dropped, since TASK_TABLE owns the task
+    let arc = unsafe { alloc::sync::Arc::from_raw(task_ptr) };
 
-        let task = unsafe { &*task_ptr.get() };
-        if task.id == task_id {
-            // Create an Arc from the raw pointer
-            // SAFETY: The task is heap-allocated and won't be freed during exec
-            // We use ManuallyDrop to prevent the Arc from freeing the task
-            // when it's dropped, since TASK_TABLE owns the task
-            let task_ptr = task_ptr.get();
-            let arc = unsafe { alloc::sync::Arc::from_raw(task_ptr) };
-
-            // Immediately convert back to raw and forget to prevent deallocation
-            // This gives us an Arc that doesn't own the data
-            let raw_ptr = alloc::sync::Arc::into_raw(arc);
-            let arc = unsafe { alloc::sync::Arc::from_raw(raw_ptr) };
-
-            return Some(arc);
-        }
-    }
+    // Immediately convert back to raw and forget to prevent deallocation
+    // This gives us an Arc that doesn't own the data
+    let raw_ptr = alloc::sync::Arc::into_raw(arc);
+    let arc = unsafe { alloc::sync::Arc::from_raw(raw_ptr) };
 
-    None
+    Some(arc)
 }
 
 /// Enqueue a task to a CPU runqueue
 ///
-/// Assigns the task to the CPU with the smallest runqueue, or to a specific CPU if specified.
+/// Assigns the task to the allowed CPU chosen by `balance::select_cpu`, or to a specific
+/// CPU if specified and allowed by the task's affinity and cpuset.
+/// The task's vruntime is placed relative to the chosen runqueue so that a task
+/// waking from a long sleep cannot monopolise the CPU.
 /// If the task is enqueued to a remote CPU (not the current CPU), sends a RESCHEDULE_IPI
-/// to wake up that CPU and schedule the new task.
+/// to wake up that CPU and schedule the new task. The same IPI is sent to the current
+/// CPU when its tick is stopped, since otherwise nothing would preempt the idle task.
 ///
 /// # Arguments
 /// * `task_id` - The task to enqueue
@@ -696,32 +957,33 @@ pub fn get_task_arc(task_id: TaskId) -> Option<alloc::sync::Arc<Task>> {
 pub fn enqueue_task(task_id: TaskId, target_cpu: Option<usize>) {
     let cpu_count = get_cpu_count();
 
-    // Determine which CPU to enqueue to
-    let cpu_id = if let Some(cpu) = target_cpu {
-        // Use specified CPU
-        if cpu >= cpu_count {
-            sched_warn!("Invalid target CPU {}, using CPU 0", cpu);
-            0
-        } else {
-            cpu
+    let task = match get_task(task_id) {
+        Some(task) => task,
+        None => {
+            sched_warn!("Cannot enqueue task {}: not found", task_id);
+            return;
         }
-    } else {
-        // Find CPU with smallest runqueue
-        let mut min_cpu = 0;
-        let mut min_size = usize::MAX;
+    };
 
-        for i in 0..cpu_count {
-            let percpu = percpu_for(i);
-            let runqueue = percpu.runqueue.lock();
-            let size = runqueue.len();
+    let allowed = task_cpus(task);
 
-            if size < min_size {
-                min_size = size;
-                min_cpu = i;
+    // Determine which CPU to enqueue to
+    let cpu_id = match target_cpu {
+        // Use specified CPU
+        Some(cpu) if allowed.contains(cpu) => cpu,
+        _ => {
+            if let Some(cpu) = target_cpu {
+                sched_warn!(
+                    "Task {} may not run on CPU {}, placing it elsewhere",
+                    task_id,
+                    cpu
+                );
             }
-        }
 
-        min_cpu
+            // Near the waker or the task's previous CPU, or wherever is
+            // least loaded for new tasks
+            balance::select_cpu(task, allowed)
+        }
     };
 
     // Get current CPU ID to check if this is a remote enqueue
@@ -731,12 +993,8 @@ pub fn enqueue_task(task_id: TaskId, target_cpu: Option<usize>) {
     let percpu = percpu_for(cpu_id);
     let mut runqueue = percpu.runqueue.lock();
 
-    if !runqueue.push_back(task_id) {
-        sched_error!(
-            "Failed to enqueue task {} to CPU {} (runqueue full)",
-            task_id,
-            cpu_id
-        );
+    if !runqueue.enqueue(task, true) {
+        sched_warn!("Task {} is already on a runqueue", task_id);
     } else {
         sched_log!(
             "Enqueued task {} to CPU {} (runqueue size: {})",
@@ -748,8 +1006,10 @@ pub fn enqueue_task(task_id: TaskId, target_cpu: Option<usize>) {
         // Drop the runqueue lock before sending IPI
         drop(runqueue);
 
-        // If we enqueued to a remote CPU, send RESCHEDULE_IPI to wake it up
-        if cpu_id != current_cpu && cpu_count > 1 {
+        // If we enqueued to a remote CPU, send RESCHEDULE_IPI to wake it up.
+        // A local CPU with its tick stopped is idle and needs the same nudge.
+        let remote = cpu_id != current_cpu && cpu_count > 1;
+        if remote || (cpu_id == current_cpu && hrtimer::tick_stopped()) {
             use crate::arch::x86_64::apic::ipi::send_reschedule_ipi;
             send_reschedule_ipi(cpu_id);
         }
@@ -758,7 +1018,7 @@ pub fn enqueue_task(task_id: TaskId, target_cpu: Option<usize>) {
 
 /// Dequeue a task from a CPU's runqueue
 ///
-/// Removes and returns the next task from the specified CPU's runqueue.
+/// Removes and returns the task that would run next on the specified CPU.
 ///
 /// # Arguments
 /// * `cpu_id` - The CPU to dequeue from
@@ -769,32 +1029,132 @@ pub fn enqueue_task(task_id: TaskId, target_cpu: Option<usize>) {
 pub fn dequeue_task(cpu_id: usize) -> Option<TaskId> {
     let percpu = percpu_for(cpu_id);
     let mut runqueue = percpu.runqueue.lock();
-    runqueue.pop_front()
+    runqueue.pick_next().map(|task| task.id)
 }
 
 /// Put current task to sleep for specified ticks
 ///
+/// Blocks until the ticks have elapsed (or the sleep is interrupted by a
+/// signal). Sleeping for 0 ticks returns immediately.
+///
 /// Returns true on success, false on error
 pub fn sleep_current_task(ticks: u64, _priority: TaskPriority) -> bool {
-    // Get current CPU and task
-    let percpu = percpu_current();
-    let current_id = match percpu.current_task {
-        Some(id) => id,
-        None => return false,
-    };
-
-    // Update task state to Sleeping
-    if let Some(task) = get_task(current_id) {
-        task.state = TaskState::Sleeping;
-        task.wake_tick = Some(ticks);
+    if percpu_current().current_task.is_none() {
+        return false;
     }
 
-    // Note: Task will not be re-enqueued until wake time
-    // The timer interrupt will check wake_tick and re-enqueue when ready
+    if ticks > 0 {
+        hrtimer::sleep_ns(ticks * hrtimer::TICK_NS);
+    }
 
     true
 }
 
+/// Wake a task that is sleeping or blocked
+///
+/// A blocked task goes back to the CPU it last ran on: it may have marked
+/// itself blocked without having switched away yet, and that CPU is the
+/// only one that won't run it before its context is saved.
+///
+/// # Returns
+/// true if the task was woken
+pub fn wake_task(task_id: TaskId) -> bool {
+    if hrtimer::interrupt_sleep(task_id) {
+        return true;
+    }
+
+    match get_task(task_id) {
+        Some(task) if task.state == TaskState::Blocked => {
+            task.state = TaskState::Ready;
+            enqueue_task(task_id, task.last_cpu);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Interrupt a task running on another CPU
+///
+/// Sends that CPU a RESCHEDULE_IPI, so the task passes through
+/// `interrupt_exit_to_user` and acts on its signals or group exit without
+/// waiting for its next syscall or tick. Does nothing if the task is not
+/// running elsewhere.
+pub fn kick_task(task_id: TaskId) {
+    let this_cpu = percpu_current().id;
+    for cpu in 0..get_cpu_count() {
+        if cpu != this_cpu && percpu_for(cpu).current_task == Some(task_id) {
+            use crate::arch::x86_64::apic::ipi::send_reschedule_ipi;
+            send_reschedule_ipi(cpu);
+            return;
+        }
+    }
+}
+
+/// Serializes releases, so exactly one of the last threads of an address
+/// space finds it unshared
+static RELEASE_LOCK: crate::sync::SpinLock<()> = crate::sync::SpinLock::new(());
+
+/// Free a zombie task and its ID
+///
+/// Waits until the task has been switched out for the last time, then
+/// removes it from the task table and frees its kernel stack. Its address
+/// space goes too once no remaining task runs in it. The `Task` itself is
+/// retired and freed once no CPU can still hold a reference to it (see
+/// `rcu`).
+///
+/// # Returns
+/// How the task ended, or None if it is not a zombie
+pub fn release_task(task_id: TaskId) -> Option<WaitStatus> {
+    use crate::mm::allocator::kfree;
+    use core::sync::atomic::Ordering;
+
+    // Task references stay valid while the tick can't preempt us
+    let status = x86_64::instructions::interrupts::without_interrupts(|| {
+        let task = get_task(task_id).filter(|task| task.state == TaskState::Zombie)?;
+
+        // A zombie runs `schedule` on its own stack until it is switched
+        // out; `finish_task_switch` finds it in the table to clear `on_cpu`
+        while task.on_cpu.load(Ordering::Acquire) {
+            core::hint::spin_loop();
+        }
+
+        let _release = RELEASE_LOCK.lock();
+        let task_ptr = TASK_TABLE.remove_if(task_id, |task| task.state == TaskState::Zombie)?;
+        let task = unsafe { &*task_ptr };
+
+        let status = task.exit_status.unwrap_or(WaitStatus::Exited(0));
+        let cr3 = task.address_space();
+        let private = task.has_private_address_space();
+        let pid = task.pid;
+        unsafe {
+            kfree(task.stack, task.stack_size);
+            rcu::retire(task_ptr);
+        }
+        pid::free_pid(task_id);
+
+        let others = TASK_TABLE.snapshot();
+        let shared = others
+            .iter()
+            .any(|&other| unsafe { (*other).address_space() } == cr3);
+        if private && !shared {
+            let mut pmm_guard = crate::mm::pmm::get_global_pmm();
+            if let Some(pmm) = pmm_guard.as_mut() {
+                crate::mm::paging::free_page_table_hierarchy(cr3, pmm);
+            }
+        }
+
+        // The process's mmap table goes with its last thread
+        if !others.iter().any(|&other| unsafe { (*other).pid } == pid) {
+            crate::mm::mmap::get_mmap_manager().release(pid as u64);
+        }
+        Some(status)
+    })?;
+
+    rcu::reclaim();
+    sched_log!("Released task {} ({:?})", task_id, status);
+    Some(status)
+}
+
 /// Migrate a task from one CPU to another
 ///
 /// This function moves a task from the source CPU's runqueue to the destination CPU's runqueue.
@@ -818,6 +1178,19 @@ pub fn migrate_task(task_id: TaskId, from_cpu: usize, to_cpu: usize) -> bool {
         return false; // Invalid CPU IDs
     }
 
+    let task = match get_task(task_id) {
+        Some(task) => task,
+        None => return false,
+    };
+
+    if !task_cpus(task).contains(to_cpu) {
+        return false; // Not allowed by affinity or cpuset
+    }
+
+    if task.on_cpu.load(core::sync::atomic::Ordering::Acquire) {
+        return false; // Still being switched out
+    }
+
     // Lock ordering: always lock lower CPU ID first to prevent deadlocks
     let (first_cpu, second_cpu) = if from_cpu < to_cpu {
         (from_cpu, to_cpu)
@@ -843,37 +1216,17 @@ pub fn migrate_task(task_id: TaskId, from_cpu: usize, to_cpu: usize) -> bool {
     };
 
     // Remove task from source queue
-    // Note: This is a simple implementation that searches the queue
-    // A more efficient implementation would use a different data structure
-    let mut found = false;
-    let mut temp_tasks = [0; MAX_RUNQUEUE_SIZE];
-    let mut temp_count = 0;
-
-    while let Some(tid) = src_queue.pop_front() {
-        if tid == task_id {
-            found = true;
-            break;
-        } else {
-            temp_tasks[temp_count] = tid;
-            temp_count += 1;
-        }
+    if !src_queue.dequeue(task) {
+        return false; // Task not queued on source CPU
     }
 
-    // Restore tasks we removed
-    for i in 0..temp_count {
-        src_queue.push_back(temp_tasks[i]);
-    }
-
-    if !found {
-        return false; // Task not found in source queue
-    }
-
-    // Add task to destination queue
-    if !dst_queue.push_back(task_id) {
-        // Destination queue full - put task back in source queue
-        src_queue.push_back(task_id);
-        return false;
+    // Keep the task's lag relative to the destination queue
+    if !task.rt.is_rt() {
+        dst_queue
+            .fair
+            .place_migrated(task, src_queue.fair.min_vruntime());
     }
+    dst_queue.enqueue(task, false);
 
     // Drop locks before sending IPI
     drop(runqueue_first);
@@ -893,95 +1246,28 @@ pub fn migrate_task(task_id: TaskId, from_cpu: usize, to_cpu: usize) -> bool {
     true
 }
 
-/// Balance load across CPUs
-///
-/// This function checks runqueue sizes and migrates tasks from busy CPUs to idle CPUs
-/// if the imbalance is greater than 2 tasks.
-///
-/// # Returns
-/// The number of tasks migrated
-pub fn balance_load() -> usize {
-    let cpu_count = get_cpu_count();
-
-    if cpu_count <= 1 {
-        return 0; // No balancing needed for single CPU
-    }
-
-    // Find CPU with largest and smallest runqueue
-    let mut max_cpu = 0;
-    let mut max_size = 0;
-    let mut min_cpu = 0;
-    let mut min_size = usize::MAX;
-
-    for i in 0..cpu_count {
-        let percpu = percpu_for(i);
-        let runqueue = percpu.runqueue.lock();
-        let size = runqueue.len();
-
-        if size > max_size {
-            max_size = size;
-            max_cpu = i;
-        }
-
-        if size < min_size {
-            min_size = size;
-            min_cpu = i;
-        }
-    }
-
-    // Check if imbalance is greater than 2 tasks
-    if max_size <= min_size + 2 {
-        return 0; // No significant imbalance
-    }
-
-    // Migrate one task from busy CPU to idle CPU
-    let task_to_migrate = {
-        let percpu = percpu_for(max_cpu);
-        let mut runqueue = percpu.runqueue.lock();
-        runqueue.pop_front()
-    };
-
-    if let Some(task_id) = task_to_migrate {
-        // Re-enqueue to destination CPU
-        let percpu = percpu_for(min_cpu);
-        let mut runqueue = percpu.runqueue.lock();
-
-        if runqueue.push_back(task_id) {
-            sched_log!(
-                "Load balance: migrated task {} from CPU {} (size {}) to CPU {} (size {})",
-                task_id,
-                max_cpu,
-                max_size,
-                min_cpu,
-                min_size
-            );
-            return 1;
-        } else {
-            // Failed to enqueue - put back in source queue
-            let percpu = percpu_for(max_cpu);
-            let mut runqueue = percpu.runqueue.lock();
-            runqueue.push_back(task_id);
-        }
-    }
-
-    0
-}
-
 /// Yield CPU to next task (voluntary context switch)
 ///
 /// This function triggers the scheduler to select the next task.
 /// It does not return in the traditional sense - execution continues
 /// in the next task, and eventually returns here when this task runs again.
 pub fn yield_now() {
-    // Call the scheduler tick function to perform context switch
-    tick();
+    // A yielding real-time task goes behind its peers at the same priority
+    if let Some(id) = percpu_current().current_task {
+        if let Some(task) = get_task(id) {
+            task.rt.time_slice = 0;
+        }
+    }
+
+    // Switch unconditionally; the fair class only decides on timer ticks
+    schedule();
 }
 
 /// Initialize the scheduler
 ///
 /// This function:
-/// 1. Initializes SCHED and TASK_TABLE
-/// 2. Creates the idle task (task id 0)
+/// 1. Creates the idle task (task id 0)
+/// 2. Adds it to TASK_TABLE
 /// 3. Logs scheduler initialization
 ///
 /// # Notes
@@ -995,16 +1281,6 @@ pub fn init_scheduler() {
 
     sched_info!("Initializing scheduler...");
 
-    // Initialize SCHED state
-    SCHED.call_once(|| Mutex::new(SchedState::new()));
-
-    // Initialize TASK_TABLE (clear all entries)
-    let mut task_table = TASK_TABLE.lock();
-    for i in 0..MAX_TASKS {
-        task_table[i] = TaskPtr::null();
-    }
-    drop(task_table);
-
     // Create idle task (task id 0)
     // We manually create it with id 0 instead of using spawn_task
     let idle = match Task::new(0, "idle", idle_task, TaskPriority::Low) {
@@ -1026,9 +1302,7 @@ pub fn init_scheduler() {
         ptr::write(task_ptr, idle);
     }
 
-    let mut task_table = TASK_TABLE.lock();
-    task_table[0] = TaskPtr::new(task_ptr);
-    drop(task_table);
+    TASK_TABLE.insert(0, task_ptr);
 
     // Set idle task for all CPUs
     let cpu_count = get_cpu_count();
@@ -1043,206 +1317,6 @@ pub fn init_scheduler() {
     sched_info!("Scheduler initialized!");
 }
 
-/// Process-aware context switching
-///
-/// Enhanced version of schedule_on_core that integrates with process management.
-/// This function coordinates between Task and Process state during context switches.
-///
-/// # Arguments
-/// * `cpu_id` - The CPU core to schedule on
-///
-/// # Returns
-/// A tuple of (old_task, new_task) references, or None if no tasks available
-pub fn schedule_with_process_integration(
-    cpu_id: usize,
-) -> Option<(&'static mut Task, &'static mut Task)> {
-    // Use the existing scheduler logic
-    let result = schedule_on_core(cpu_id);
-
-    // If we have a context switch, update process states
-    if let Some((old_task, new_task)) = &result {
-        // Update process states through integration functions
-        use crate::user::process::{get_process_for_task, prepare_process_context_switch};
-
-        let old_process_id = get_process_for_task(old_task.id);
-        let new_process_id = get_process_for_task(new_task.id);
-
-        if let Some(new_pid) = new_process_id {
-            if let Err(e) = prepare_process_context_switch(old_process_id, new_pid) {
-                sched_warn!("Failed to prepare process context switch: {:?}", e);
-            }
-        }
-    }
-
-    result
-}
-
-/// Enhanced tick function with process integration
-///
-/// This version of tick() integrates with the process management system
-/// to ensure consistent state between Tasks and Processes.
-pub fn tick_with_process_integration() {
-    use core::sync::atomic::Ordering;
-
-    // Increment timer_ticks metric
-    crate::sys::METRICS
-        .timer_ticks
-        .fetch_add(1, Ordering::Relaxed);
-
-    // Get current CPU ID
-    let cpu_id = percpu_current().id;
-
-    // Get next task to run on this core (with process integration)
-    let tasks = schedule_with_process_integration(cpu_id);
-
-    if let Some((old_task, new_task)) = tasks {
-        // Validate task pointers before context switch
-        if old_task.context.rsp == 0 {
-            panic!("[SCHED] CRITICAL: Old task has invalid RSP (null stack pointer)");
-        }
-        if new_task.context.rsp == 0 {
-            panic!("[SCHED] CRITICAL: New task has invalid RSP (null stack pointer)");
-        }
-
-        // Increment switch counter
-        let count = SWITCH_COUNT.fetch_add(1, Ordering::Relaxed);
-
-        // Increment ctx_switches metric
-        crate::sys::METRICS
-            .ctx_switches
-            .fetch_add(1, Ordering::Relaxed);
-
-        // Check if this is a preemptive switch (old task was still Running/Ready)
-        if old_task.state == TaskState::Running || old_task.state == TaskState::Ready {
-            crate::sys::METRICS
-                .preemptions
-                .fetch_add(1, Ordering::Relaxed);
-        }
-
-        // Log context switch with throttling
-        if count < 10 || count % 100 == 0 {
-            sched_log!(
-                "[core{}] Switch #{} → Task {} ({})",
-                cpu_id,
-                count,
-                new_task.id,
-                new_task.name
-            );
-        }
-
-        // Perform context switch
-        unsafe {
-            context::context_switch(
-                &mut old_task.context as *mut CpuContext,
-                &new_task.context as *const CpuContext,
-            );
-        }
-    } else {
-        // Use the existing first-switch logic
-        let percpu = unsafe { crate::arch::x86_64::smp::percpu::percpu_for_mut(cpu_id) };
-
-        let first_task_id = {
-            let mut runqueue = percpu.runqueue.lock();
-            match runqueue.pop_front() {
-                Some(id) => id,
-                None => percpu.idle_task,
-            }
-        };
-
-        percpu.current_task = Some(first_task_id);
-
-        if let Some(first_task) = get_task(first_task_id) {
-            first_task.state = TaskState::Running;
-
-            sched_log!(
-                "[core{}] First switch → Task {} ({}) [priority: {:?}]",
-                cpu_id,
-                first_task.id,
-                first_task.name,
-                first_task.priority
-            );
-
-            if first_task.context.rsp == 0 {
-                panic!("[SCHED] CRITICAL: First task has null RSP");
-            }
-
-            let mut dummy_context = CpuContext {
-                r15: 0,
-                r14: 0,
-                r13: 0,
-                r12: 0,
-                rbp: 0,
-                rbx: 0,
-                rsp: 0,
-                cr3: 0, // Dummy value, will not be used
-            };
-
-            unsafe {
-                context::context_switch(
-                    &mut dummy_context as *mut CpuContext,
-                    &first_task.context as *const CpuContext,
-                );
-            }
-
-            panic!("[SCHED] CRITICAL: Returned from first context switch");
-        } else {
-            panic!("[SCHED] CRITICAL: First task not found in task table");
-        }
-    }
-}
-
-/// Create a process-aware task
-///
-/// This function creates both a Process and a Task, linking them together
-/// for integrated management.
-///
-/// # Arguments
-/// * `name` - Process/task name
-/// * `entry_point` - Task entry point function
-/// * `priority` - Task priority
-/// * `parent_pid` - Optional parent process ID
-///
-/// # Returns
-/// Ok((process_id, task_id)) if creation succeeded, or an error
-pub fn spawn_process_task(
-    name: &'static str,
-    entry_point: fn() -> !,
-    priority: TaskPriority,
-    parent_pid: Option<usize>,
-) -> SchedulerResult<(usize, TaskId)> {
-    use crate::user::process::{ProcessError, ProcessManager};
-
-    // Create the process first
-    let process_id = ProcessManager::create_process(parent_pid, name).map_err(|e| match e {
-        ProcessError::ProcessTableFull => SchedulerError::TooManyTasks,
-        ProcessError::OutOfMemory => SchedulerError::OutOfMemory,
-        _ => SchedulerError::OutOfMemory,
-    })?;
-
-    // Create the corresponding task
-    let task_id = spawn_task(name, entry_point, priority)?;
-
-    // Link them together through synchronization
-    use crate::user::process::sync_task_with_process;
-    if let Err(e) = sync_task_with_process(task_id, process_id) {
-        sched_warn!(
-            "Failed to sync task {} with process {}: {:?}",
-            task_id,
-            process_id,
-            e
-        );
-    }
-
-    sched_info!(
-        "Created process {} with task {} ({})",
-        process_id,
-        task_id,
-        name
-    );
-
-    Ok((process_id, task_id))
-}
-
 /// End-to-end integration test for task switching
 ///
 /// This test verifies that:
@@ -0,0 +1,213 @@
+//! Task ID Allocation
+//!
+//! Task IDs (which double as PIDs) come from a bitmap that grows with the
+//! highest ID handed out, so the number of tasks is bounded by memory and
+//! `pid_max` rather than by a table size. Like Linux, IDs are handed out in
+//! increasing order and wrap around at `pid_max`, which keeps a just-freed
+//! ID from being reused right away.
+//!
+//! ID 0 belongs to the idle task and is never allocated.
+//!
+//! # Lock Ordering
+//!
+//! The allocator lock is a leaf lock; nothing else is taken while it is held.
+
+use super::task::TaskId;
+use crate::sync::SpinLock;
+use alloc::vec::Vec;
+
+/// Lowest ID handed out (0 is the idle task)
+const FIRST_PID: TaskId = 1;
+
+/// Smallest accepted `pid_max`
+pub const PID_MAX_MIN: usize = 64;
+
+/// Largest accepted `pid_max`
+pub const PID_MAX_LIMIT: usize = 4 * 1024 * 1024;
+
+/// Bitmap allocator for task IDs
+pub struct PidAllocator {
+    /// One bit per ID; covers only up to the highest ID allocated so far
+    bitmap: Vec<u64>,
+    /// Most recently allocated ID (the next search starts after it)
+    last: TaskId,
+    /// IDs are below this bound
+    pid_max: usize,
+    /// Number of IDs in use
+    count: usize,
+}
+
+impl PidAllocator {
+    /// Create an allocator handing out IDs below `pid_max`
+    pub const fn new(pid_max: usize) -> Self {
+        Self {
+            bitmap: Vec::new(),
+            last: FIRST_PID - 1,
+            pid_max,
+            count: 0,
+        }
+    }
+
+    /// Allocate the next free ID
+    ///
+    /// # Returns
+    /// None if every ID below `pid_max` is in use
+    pub fn alloc(&mut self) -> Option<TaskId> {
+        let id = self
+            .find_free(self.last + 1, self.pid_max)
+            .or_else(|| self.find_free(FIRST_PID, (self.last + 1).min(self.pid_max)))?;
+
+        let word = id / 64;
+        if self.bitmap.len() <= word {
+            self.bitmap.resize(word + 1, 0);
+        }
+        self.bitmap[word] |= 1 << (id % 64);
+
+        self.last = id;
+        self.count += 1;
+        Some(id)
+    }
+
+    /// Return an ID to the pool
+    pub fn free(&mut self, id: TaskId) {
+        if self.is_allocated(id) {
+            self.bitmap[id / 64] &= !(1 << (id % 64));
+            self.count -= 1;
+        }
+    }
+
+    /// Check whether an ID is in use
+    pub fn is_allocated(&self, id: TaskId) -> bool {
+        self.bitmap
+            .get(id / 64)
+            .is_some_and(|word| word & (1 << (id % 64)) != 0)
+    }
+
+    /// Current upper bound for IDs
+    pub fn pid_max(&self) -> usize {
+        self.pid_max
+    }
+
+    /// Change the upper bound for new IDs
+    ///
+    /// IDs already allocated above a lowered bound stay valid.
+    ///
+    /// # Returns
+    /// false if `pid_max` is outside `PID_MAX_MIN..=PID_MAX_LIMIT`
+    pub fn set_pid_max(&mut self, pid_max: usize) -> bool {
+        if !(PID_MAX_MIN..=PID_MAX_LIMIT).contains(&pid_max) {
+            return false;
+        }
+
+        self.pid_max = pid_max;
+        if self.last >= pid_max {
+            self.last = FIRST_PID - 1;
+        }
+        true
+    }
+
+    /// Lowest free ID in `start..end`
+    fn find_free(&self, start: TaskId, end: TaskId) -> Option<TaskId> {
+        let mut id = start;
+        while id < end {
+            let word = id / 64;
+            let used = self.bitmap.get(word).copied().unwrap_or(0);
+            let free = !used & (u64::MAX << (id % 64));
+
+            if free != 0 {
+                let found = word * 64 + free.trailing_zeros() as usize;
+                return (found < end).then_some(found);
+            }
+            id = (word + 1) * 64;
+        }
+        None
+    }
+}
+
+/// System-wide task ID allocator
+static PIDS: SpinLock<PidAllocator> =
+    SpinLock::new(PidAllocator::new(crate::config::PID_MAX_DEFAULT));
+
+/// Allocate a task ID
+///
+/// # Returns
+/// None if all IDs below `pid_max` are in use
+pub fn alloc_pid() -> Option<TaskId> {
+    PIDS.lock().alloc()
+}
+
+/// Release a task ID for reuse
+pub fn free_pid(id: TaskId) {
+    PIDS.lock().free(id);
+}
+
+/// Current `pid_max`
+pub fn pid_max() -> usize {
+    PIDS.lock().pid_max()
+}
+
+/// Set `pid_max`
+///
+/// Written through /proc/sys/kernel/pid_max.
+///
+/// # Returns
+/// false if the value is outside `PID_MAX_MIN..=PID_MAX_LIMIT`
+pub fn set_pid_max(pid_max: usize) -> bool {
+    PIDS.lock().set_pid_max(pid_max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alloc_wraps_and_reuses() {
+        let mut pids = PidAllocator::new(PID_MAX_MIN);
+        for expected in FIRST_PID..PID_MAX_MIN {
+            assert_eq!(pids.alloc(), Some(expected));
+        }
+        assert_eq!(pids.alloc(), None);
+        assert_eq!(pids.count, PID_MAX_MIN - 1);
+
+        // Freed IDs come back only after wrapping around
+        pids.free(10);
+        pids.free(20);
+        assert!(!pids.is_allocated(10));
+        assert_eq!(pids.alloc(), Some(10));
+        assert_eq!(pids.alloc(), Some(20));
+        assert_eq!(pids.alloc(), None);
+    }
+
+    #[test]
+    fn test_search_continues_after_last() {
+        let mut pids = PidAllocator::new(1000);
+        let first = pids.alloc().unwrap();
+        let second = pids.alloc().unwrap();
+        pids.free(first);
+
+        // The freed ID is not reused while higher ones are free
+        assert_eq!(pids.alloc(), Some(second + 1));
+        assert!(pids.is_allocated(second));
+        assert!(!pids.is_allocated(0));
+    }
+
+    #[test]
+    fn test_set_pid_max() {
+        let mut pids = PidAllocator::new(1000);
+        assert!(!pids.set_pid_max(PID_MAX_MIN - 1));
+        assert!(!pids.set_pid_max(PID_MAX_LIMIT + 1));
+
+        for _ in 0..200 {
+            pids.alloc();
+        }
+        assert!(pids.set_pid_max(128));
+        assert_eq!(pids.pid_max(), 128);
+
+        // Everything below the new bound is taken
+        assert_eq!(pids.alloc(), None);
+        pids.free(150);
+        assert_eq!(pids.alloc(), None);
+        pids.free(100);
+        assert_eq!(pids.alloc(), Some(100));
+    }
+}
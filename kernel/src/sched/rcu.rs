@@ -0,0 +1,140 @@
+//! Deferred Task Freeing
+//!
+//! Tasks looked up in the task table (`get_task`, `for_each_task`, table
+//! snapshots) are used through plain references, without a lock or a
+//! reference count. A released task is therefore not freed at once: it is
+//! retired, and freed only after a grace period in which every CPU has
+//! passed a quiescent state, as with RCU.
+//!
+//! # Quiescent States
+//!
+//! A CPU is quiescent when it calls `schedule`, when its idle loop wakes
+//! from `hlt`, and when an interrupt returns to user mode. In between, it
+//! may keep using task references it looked up. So:
+//!
+//! - A task reference must not be kept across a call that may sleep.
+//! - Code that runs with interrupts enabled (kernel threads) must disable
+//!   them while it uses task references, so the tick cannot preempt it.
+//!   `for_each_task` does this itself.
+//!
+//! Retired tasks are freed by later calls to `reclaim`, in process context.
+//! A CPU that has not been quiescent since a task was retired (typically
+//! an idle CPU with its tick stopped) is sent a reschedule IPI so the next
+//! call finds it has been.
+
+use super::task::Task;
+use crate::config::MAX_CPUS;
+use crate::sync::SpinLock;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Quiescent states passed by each CPU
+static QUIESCENT: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+/// A released task waiting for its grace period
+struct Retired {
+    task: *mut Task,
+    /// `QUIESCENT` of every CPU when the task was retired
+    seen: Vec<u64>,
+}
+
+// Retired tasks are unreachable, only the reclaiming CPU touches them
+unsafe impl Send for Retired {}
+
+/// Released tasks, oldest first
+static RETIRED: SpinLock<Vec<Retired>> = SpinLock::new(Vec::new());
+
+/// Record a quiescent state of `cpu`
+pub fn quiescent(cpu: usize) {
+    if let Some(count) = QUIESCENT.get(cpu) {
+        count.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// Quiescent-state counts of the first `cpus` CPUs
+fn counts(cpus: usize) -> Vec<u64> {
+    QUIESCENT[..cpus.min(MAX_CPUS)]
+        .iter()
+        .map(|count| count.load(Ordering::Acquire))
+        .collect()
+}
+
+/// CPUs that have not passed a quiescent state since `seen` was taken
+fn lagging(seen: &[u64], now: &[u64]) -> Vec<usize> {
+    seen.iter()
+        .zip(now)
+        .enumerate()
+        .filter(|(_, (seen, now))| now <= seen)
+        .map(|(cpu, _)| cpu)
+        .collect()
+}
+
+/// Hand a task that was removed from the task table over to be freed once
+/// no CPU can still hold a reference to it
+///
+/// # Safety
+/// `task` must have come from `Task::new`'s allocation and must no longer
+/// be reachable through the task table.
+pub unsafe fn retire(task: *mut Task) {
+    // A CPU counted past these values looks tasks up after the removal
+    core::sync::atomic::fence(Ordering::SeqCst);
+    let seen = counts(super::get_cpu_count());
+    RETIRED.lock().push(Retired { task, seen });
+}
+
+/// Free the retired tasks whose grace period is over
+///
+/// Must be called in process context, outside any task-reference use.
+pub fn reclaim() {
+    use crate::mm::allocator::kfree;
+
+    let now = counts(super::get_cpu_count());
+    let mut done = Vec::new();
+    let holding_up = {
+        let mut retired = RETIRED.lock();
+        retired.retain(|entry| {
+            let over = lagging(&entry.seen, &now).is_empty();
+            if over {
+                done.push(entry.task);
+            }
+            !over
+        });
+        retired
+            .first()
+            .map_or(Vec::new(), |oldest| lagging(&oldest.seen, &now))
+    };
+
+    // Wake CPUs still holding up the rest, so a later call can free them
+    let this_cpu = super::percpu_current().id;
+    for cpu in holding_up {
+        if cpu != this_cpu {
+            crate::arch::x86_64::apic::ipi::send_reschedule_ipi(cpu);
+        }
+    }
+
+    for task in done {
+        unsafe {
+            core::ptr::drop_in_place(task);
+            kfree(task as *mut u8, core::mem::size_of::<Task>());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lagging_cpus() {
+        let seen = [3, 7, 0];
+        assert!(lagging(&seen, &[4, 8, 1]).is_empty());
+        assert_eq!(lagging(&seen, &[4, 7, 1]), [1]);
+        assert_eq!(lagging(&seen, &[3, 7, 0]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_cpus_brought_up_later_are_ignored() {
+        // Only the CPUs online at retire time are waited for
+        assert!(lagging(&[1], &[2, 0]).is_empty());
+    }
+}
@@ -0,0 +1,470 @@
+//! Real-Time Scheduling Classes (SCHED_FIFO / SCHED_RR)
+//!
+//! Real-time tasks sit above the fair class: a CPU only runs fair tasks when
+//! no unthrottled real-time task is runnable. Each of the 99 priority levels
+//! has its own FIFO queue and a bitmap gives O(1) selection of the highest
+//! non-empty level, with the queue links stored inside each task so nothing
+//! is allocated.
+//!
+//! # Policies
+//!
+//! - **SCHED_FIFO**: Runs until it blocks, yields, or a higher priority task
+//!   becomes runnable. A preempted FIFO task goes back to the head of its level.
+//! - **SCHED_RR**: Like FIFO, but rotates to the tail of its level after each
+//!   `SCHED_RR_TIMESLICE_NS` of runtime.
+//!
+//! # Throttling
+//!
+//! Real-time tasks may use at most `SCHED_RT_RUNTIME_NS` of every
+//! `SCHED_RT_PERIOD_NS` on a CPU. Once the budget is spent the CPU's
+//! real-time queue is throttled until the period ends, so a runaway FIFO
+//! task cannot starve the rest of the system.
+//!
+//! # Priority Inheritance
+//!
+//! `prio` is the effective priority. It equals `rt_priority` unless the task
+//! holds an `RtMutex` that a higher-priority task is waiting on, in which
+//! case it is boosted (see `sync::rtmutex`). A boosted fair task temporarily
+//! runs in the real-time class.
+
+use super::task::Task;
+use crate::config::{SCHED_RR_TIMESLICE_NS, SCHED_RT_PERIOD_NS, SCHED_RT_RUNTIME_NS};
+use crate::sync::rtmutex::RtMutexCore;
+use alloc::vec::Vec;
+
+/// Number of real-time priority levels (1..=99 are valid, 0 means "not RT")
+pub const MAX_RT_PRIO: usize = 100;
+
+/// Scheduling policy (values match Linux)
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum SchedPolicy {
+    /// Fair class (SCHED_OTHER)
+    Normal = 0,
+    /// Real-time first-in first-out
+    Fifo = 1,
+    /// Real-time round-robin
+    RoundRobin = 2,
+}
+
+impl SchedPolicy {
+    /// Convert a raw policy number from userspace
+    pub fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(SchedPolicy::Normal),
+            1 => Some(SchedPolicy::Fifo),
+            2 => Some(SchedPolicy::RoundRobin),
+            _ => None,
+        }
+    }
+
+    /// Check whether this is a real-time policy
+    pub fn is_rt(self) -> bool {
+        self != SchedPolicy::Normal
+    }
+}
+
+/// Per-task scheduling state for the real-time classes
+#[derive(Debug)]
+pub struct RtEntity {
+    /// Scheduling policy
+    pub policy: SchedPolicy,
+
+    /// Base real-time priority (1..99, 0 for SCHED_OTHER)
+    pub rt_priority: u8,
+
+    /// Priority inherited from `RtMutex` waiters (0 = not boosted)
+    pub pi_prio: u8,
+
+    /// Effective priority: the higher of `rt_priority` and `pi_prio` (0 = fair class)
+    pub prio: u8,
+
+    /// Remaining round-robin slice (nanoseconds)
+    ///
+    /// Zero means "requeue at the tail" the next time the task is enqueued.
+    pub time_slice: u64,
+
+    /// `RtMutex` the task is blocked on (null if none)
+    pub pi_blocked_on: *const RtMutexCore,
+
+    /// `RtMutex`es the task holds
+    pub pi_held: Vec<*const RtMutexCore>,
+
+    /// Runqueue this entity is queued on (null when not queued)
+    rq: *const RtRunQueue,
+
+    /// Queue links within the priority level
+    next: *mut Task,
+    prev: *mut Task,
+}
+
+impl RtEntity {
+    /// Create the state for a SCHED_OTHER task
+    pub const fn new() -> Self {
+        Self {
+            policy: SchedPolicy::Normal,
+            rt_priority: 0,
+            pi_prio: 0,
+            prio: 0,
+            time_slice: SCHED_RR_TIMESLICE_NS,
+            pi_blocked_on: core::ptr::null(),
+            pi_held: Vec::new(),
+            rq: core::ptr::null(),
+            next: core::ptr::null_mut(),
+            prev: core::ptr::null_mut(),
+        }
+    }
+
+    /// Recompute the effective priority
+    ///
+    /// The caller must make sure the entity is not queued.
+    pub fn update_prio(&mut self) {
+        debug_assert!(self.rq.is_null());
+        self.prio = self.rt_priority.max(self.pi_prio);
+    }
+
+    /// Check whether the task currently runs in a real-time class
+    pub fn is_rt(&self) -> bool {
+        self.prio > 0
+    }
+
+    /// Check whether the entity is on a runqueue
+    pub fn is_queued(&self) -> bool {
+        !self.rq.is_null()
+    }
+}
+
+/// Per-CPU runqueue for the real-time classes
+pub struct RtRunQueue {
+    /// First task of each priority level
+    heads: [*mut Task; MAX_RT_PRIO],
+
+    /// Last task of each priority level
+    tails: [*mut Task; MAX_RT_PRIO],
+
+    /// Bitmap of non-empty priority levels
+    bitmap: [u64; 2],
+
+    /// Number of queued tasks
+    nr_running: usize,
+
+    /// Real-time runtime consumed in the current period (nanoseconds)
+    rt_time: u64,
+
+    /// Time elapsed in the current throttling period (nanoseconds)
+    period_time: u64,
+
+    /// True once the budget for this period is spent
+    throttled: bool,
+}
+
+// Safety: the raw task pointers are only dereferenced while holding the
+// per-CPU runqueue lock, and tasks are heap-allocated and never move.
+unsafe impl Send for RtRunQueue {}
+
+impl RtRunQueue {
+    /// Create a new empty runqueue
+    pub const fn new() -> Self {
+        Self {
+            heads: [core::ptr::null_mut(); MAX_RT_PRIO],
+            tails: [core::ptr::null_mut(); MAX_RT_PRIO],
+            bitmap: [0; 2],
+            nr_running: 0,
+            rt_time: 0,
+            period_time: 0,
+            throttled: false,
+        }
+    }
+
+    /// Get the number of queued tasks
+    pub fn len(&self) -> usize {
+        self.nr_running
+    }
+
+    /// Check if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.nr_running == 0
+    }
+
+    /// Check whether the real-time budget for this period is spent
+    pub fn is_throttled(&self) -> bool {
+        self.throttled
+    }
+
+    /// Check whether a real-time task may run now
+    pub fn has_runnable(&self) -> bool {
+        !self.throttled && self.nr_running > 0
+    }
+
+    /// Check whether the task is queued on this runqueue
+    pub fn contains(&self, task: &Task) -> bool {
+        core::ptr::eq(task.rt.rq, self)
+    }
+
+    /// Highest queued priority (0 if empty)
+    pub fn highest_prio(&self) -> u8 {
+        if self.bitmap[1] != 0 {
+            (127 - self.bitmap[1].leading_zeros()) as u8
+        } else if self.bitmap[0] != 0 {
+            (63 - self.bitmap[0].leading_zeros()) as u8
+        } else {
+            0
+        }
+    }
+
+    /// Insert a task at its effective priority
+    ///
+    /// Tasks that were preempted (`time_slice > 0` and not a wakeup) go back
+    /// to the head of their level; woken, yielding or expired tasks go to the
+    /// tail with a fresh round-robin slice.
+    ///
+    /// Returns false if the task is already queued or not real-time.
+    pub fn enqueue(&mut self, task: &mut Task, wakeup: bool) -> bool {
+        if task.rt.is_queued() || !task.rt.is_rt() {
+            return false;
+        }
+
+        let prio = task.rt.prio as usize;
+        let node = task as *mut Task;
+        let at_head = !wakeup && task.rt.time_slice > 0;
+
+        if task.rt.time_slice == 0 {
+            task.rt.time_slice = SCHED_RR_TIMESLICE_NS;
+        }
+
+        task.rt.rq = self;
+        unsafe {
+            if self.heads[prio].is_null() {
+                task.rt.next = core::ptr::null_mut();
+                task.rt.prev = core::ptr::null_mut();
+                self.heads[prio] = node;
+                self.tails[prio] = node;
+            } else if at_head {
+                let head = self.heads[prio];
+                task.rt.next = head;
+                task.rt.prev = core::ptr::null_mut();
+                (*head).rt.prev = node;
+                self.heads[prio] = node;
+            } else {
+                let tail = self.tails[prio];
+                task.rt.next = core::ptr::null_mut();
+                task.rt.prev = tail;
+                (*tail).rt.next = node;
+                self.tails[prio] = node;
+            }
+        }
+
+        self.bitmap[prio / 64] |= 1 << (prio % 64);
+        self.nr_running += 1;
+        true
+    }
+
+    /// Remove a task queued on this runqueue
+    ///
+    /// Returns false if the task is not queued here.
+    pub fn dequeue(&mut self, task: &mut Task) -> bool {
+        if !self.contains(task) {
+            return false;
+        }
+
+        let prio = task.rt.prio as usize;
+        unsafe {
+            if task.rt.prev.is_null() {
+                self.heads[prio] = task.rt.next;
+            } else {
+                (*task.rt.prev).rt.next = task.rt.next;
+            }
+
+            if task.rt.next.is_null() {
+                self.tails[prio] = task.rt.prev;
+            } else {
+                (*task.rt.next).rt.prev = task.rt.prev;
+            }
+        }
+
+        if self.heads[prio].is_null() {
+            self.bitmap[prio / 64] &= !(1 << (prio % 64));
+        }
+
+        task.rt.rq = core::ptr::null();
+        task.rt.next = core::ptr::null_mut();
+        task.rt.prev = core::ptr::null_mut();
+        self.nr_running -= 1;
+        true
+    }
+
+    /// Pick the highest priority task and take it off the queue
+    ///
+    /// Returns None when the queue is empty or throttled.
+    pub fn pick_next(&mut self) -> Option<&'static mut Task> {
+        if !self.has_runnable() {
+            return None;
+        }
+
+        let node = self.heads[self.highest_prio() as usize];
+        let task = unsafe { &mut *node };
+        self.dequeue(task);
+        Some(task)
+    }
+
+    /// Advance the throttling period by one tick
+    ///
+    /// `rt_running` says whether a real-time task was running during the tick.
+    /// Returns true if the queue just became throttled.
+    pub fn update_period(&mut self, delta: u64, rt_running: bool) -> bool {
+        self.period_time += delta;
+        if self.period_time >= SCHED_RT_PERIOD_NS {
+            self.period_time = 0;
+            self.rt_time = 0;
+            self.throttled = false;
+        }
+
+        if !rt_running || self.throttled {
+            return false;
+        }
+
+        self.rt_time += delta;
+        if self.rt_time >= SCHED_RT_RUNTIME_NS {
+            self.throttled = true;
+            return true;
+        }
+
+        false
+    }
+
+    /// Decide whether the running real-time task must give up the CPU
+    pub fn check_preempt_tick(&self, curr: &mut RtEntity, delta: u64) -> bool {
+        if self.throttled {
+            return true;
+        }
+
+        if self.highest_prio() > curr.prio {
+            return true;
+        }
+
+        if curr.policy != SchedPolicy::RoundRobin {
+            return false;
+        }
+
+        curr.time_slice = curr.time_slice.saturating_sub(delta);
+        if curr.time_slice > 0 {
+            return false;
+        }
+
+        // Slice used up: rotate only if someone at the same level is waiting,
+        // otherwise just start a new slice
+        if self.heads[curr.prio as usize].is_null() {
+            curr.time_slice = SCHED_RR_TIMESLICE_NS;
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_from_raw() {
+        assert_eq!(SchedPolicy::from_raw(0), Some(SchedPolicy::Normal));
+        assert_eq!(SchedPolicy::from_raw(2), Some(SchedPolicy::RoundRobin));
+        assert_eq!(SchedPolicy::from_raw(7), None);
+        assert!(SchedPolicy::Fifo.is_rt());
+        assert!(!SchedPolicy::Normal.is_rt());
+    }
+
+    #[test]
+    fn test_throttling() {
+        let mut rq = RtRunQueue::new();
+        let ticks = SCHED_RT_RUNTIME_NS / 1_000_000;
+
+        for _ in 1..ticks {
+            assert!(!rq.update_period(1_000_000, true));
+        }
+        assert!(rq.update_period(1_000_000, true));
+        assert!(rq.is_throttled());
+
+        // Budget is restored at the start of the next period
+        let rest = (SCHED_RT_PERIOD_NS - SCHED_RT_RUNTIME_NS) / 1_000_000;
+        for _ in 0..rest {
+            rq.update_period(1_000_000, false);
+        }
+        assert!(!rq.is_throttled());
+    }
+
+    #[test]
+    fn test_pi_boost_and_unboost() {
+        let mut rt = RtEntity::new();
+        rt.policy = SchedPolicy::Fifo;
+        rt.rt_priority = 10;
+        rt.update_prio();
+        assert_eq!(rt.prio, 10);
+
+        // A waiter at 50 lends its priority; a lower one changes nothing
+        rt.pi_prio = 50;
+        rt.update_prio();
+        assert_eq!(rt.prio, 50);
+        rt.pi_prio = 5;
+        rt.update_prio();
+        assert_eq!(rt.prio, 10);
+
+        // A fair-class owner runs as real-time only while boosted
+        let mut fair = RtEntity::new();
+        fair.pi_prio = 30;
+        fair.update_prio();
+        assert!(fair.is_rt());
+        fair.pi_prio = 0;
+        fair.update_prio();
+        assert!(!fair.is_rt());
+    }
+
+    fn dummy_entry() -> ! {
+        loop {}
+    }
+
+    fn rt_task(id: usize, prio: u8) -> alloc::boxed::Box<Task> {
+        let mut task = alloc::boxed::Box::new(
+            Task::new(
+                id,
+                "rt",
+                dummy_entry,
+                crate::sched::task::TaskPriority::Normal,
+            )
+            .expect("Failed to create test task"),
+        );
+        task.rt.policy = SchedPolicy::Fifo;
+        task.rt.rt_priority = prio;
+        task.rt.update_prio();
+        task
+    }
+
+    #[test]
+    fn test_runqueue_order() {
+        let mut rq = RtRunQueue::new();
+        let mut low = rt_task(1, 10);
+        let mut high = rt_task(2, 90);
+        let mut low2 = rt_task(3, 10);
+
+        assert!(rq.enqueue(&mut low, true));
+        assert!(rq.enqueue(&mut high, true));
+        assert!(rq.enqueue(&mut low2, true));
+        assert!(!rq.enqueue(&mut low, true));
+        assert_eq!(rq.len(), 3);
+        assert_eq!(rq.highest_prio(), 90);
+
+        // Highest level first, then FIFO within a level
+        assert_eq!(rq.pick_next().map(|t| t.id), Some(2));
+        assert_eq!(rq.pick_next().map(|t| t.id), Some(1));
+
+        // A preempted task goes back to the head of its level
+        low.rt.time_slice = 1;
+        assert!(rq.enqueue(&mut low, false));
+        assert_eq!(rq.pick_next().map(|t| t.id), Some(1));
+
+        assert!(rq.dequeue(&mut low2));
+        assert!(rq.is_empty());
+        assert_eq!(rq.highest_prio(), 0);
+    }
+}
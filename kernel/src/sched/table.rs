@@ -0,0 +1,103 @@
+//! Task Table
+//!
+//! Maps task IDs to their heap-allocated `Task`. The map is split into
+//! shards by ID so that lookups from different CPUs rarely touch the same
+//! lock, and each shard is an ordered map so the table grows and shrinks
+//! with the number of tasks instead of being sized for the worst case.
+//!
+//! Shard locks are IRQ-safe: the timer and IPI handlers look tasks up too.
+//! No shard lock is held while another one is taken.
+
+use super::task::{Task, TaskId};
+use crate::sync::IrqSpinLock;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// Number of shards (a power of two)
+const TASK_TABLE_SHARDS: usize = 16;
+
+/// Wrapper for task pointer that implements Sync
+///
+/// # Safety
+/// This is safe because:
+/// - We only access tasks through the TASK_TABLE shard locks
+/// - Each task is only accessed by one context at a time
+/// - Tasks are heap-allocated and don't move
+#[derive(Copy, Clone)]
+struct TaskPtr(*mut Task);
+
+unsafe impl Sync for TaskPtr {}
+unsafe impl Send for TaskPtr {}
+
+/// Sharded map from task ID to task
+pub struct TaskTable {
+    shards: [IrqSpinLock<BTreeMap<TaskId, TaskPtr>>; TASK_TABLE_SHARDS],
+}
+
+impl TaskTable {
+    /// Create an empty table
+    pub const fn new() -> Self {
+        Self {
+            shards: [const { IrqSpinLock::new(BTreeMap::new()) }; TASK_TABLE_SHARDS],
+        }
+    }
+
+    fn shard(&self, id: TaskId) -> &IrqSpinLock<BTreeMap<TaskId, TaskPtr>> {
+        &self.shards[id % TASK_TABLE_SHARDS]
+    }
+
+    /// Add a task
+    ///
+    /// # Returns
+    /// false if the ID is already in use
+    pub fn insert(&self, id: TaskId, task: *mut Task) -> bool {
+        let mut shard = self.shard(id).lock();
+        if shard.contains_key(&id) {
+            return false;
+        }
+        shard.insert(id, TaskPtr(task));
+        true
+    }
+
+    /// Look up a task
+    pub fn get(&self, id: TaskId) -> Option<*mut Task> {
+        self.shard(id).lock().get(&id).map(|ptr| ptr.0)
+    }
+
+    /// Remove a task, returning its pointer
+    pub fn remove(&self, id: TaskId) -> Option<*mut Task> {
+        self.shard(id).lock().remove(&id).map(|ptr| ptr.0)
+    }
+
+    /// Remove a task if `pred` accepts it
+    ///
+    /// `pred` runs under the shard lock, so no one else can remove the
+    /// task between the check and the removal.
+    pub fn remove_if(&self, id: TaskId, pred: impl FnOnce(&Task) -> bool) -> Option<*mut Task> {
+        let mut shard = self.shard(id).lock();
+        let ptr = shard.get(&id)?.0;
+        if !pred(unsafe { &*ptr }) {
+            return None;
+        }
+        shard.remove(&id).map(|ptr| ptr.0)
+    }
+
+    /// Pointers to every task, in ID order within each shard
+    ///
+    /// Only one shard is locked at a time, so tasks created or removed
+    /// meanwhile may or may not be included. Removed tasks are only freed
+    /// after a grace period (see `rcu`), so the pointers stay valid until
+    /// this CPU next schedules.
+    pub fn snapshot(&self) -> Vec<*mut Task> {
+        let mut tasks = Vec::with_capacity(self.count());
+        for shard in &self.shards {
+            tasks.extend(shard.lock().values().map(|ptr| ptr.0));
+        }
+        tasks
+    }
+
+    /// Number of tasks
+    pub fn count(&self) -> usize {
+        self.shards.iter().map(|shard| shard.lock().len()).sum()
+    }
+}
@@ -4,11 +4,27 @@
 //! It handles task creation, state management, and stack allocation.
 
 use super::context::CpuContext;
+use super::cpuset::{CpuMask, CpusetId, ROOT_CPUSET};
+use super::fair::SchedEntity;
+use super::hrtimer::TimerHandle;
+use super::kthread::KThreadInfo;
 use super::priority::TaskPriority;
 use super::process_group::{DeviceId, Pgid, Pid, Sid};
+use super::rt::RtEntity;
+use crate::arch::x86_64::fpu::FpuState;
 use crate::mm::paging::PageTableFlags;
-use crate::signal::{signals, PendingSignalFrame, SigAction};
-use core::sync::atomic::{AtomicU64, Ordering};
+use crate::signal::frame::SignalStack;
+use crate::signal::queue::SigQueue;
+use crate::signal::{lowest_signal, sigbit, SigAction, SigSource};
+use crate::sys::syscall::SyscallFrame;
+use crate::user::capability::{CapSets, Capabilities};
+use crate::user::exec::ProcessImage;
+use crate::user::process::WaitStatus;
+use crate::user::ptrace::TraceState;
+use crate::user::rlimit::ResourceLimits;
+use crate::user::seccomp::Filter;
+use crate::user::thread::ThreadGroup;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 /// Task identifier type
 pub type TaskId = usize;
@@ -97,6 +113,11 @@ pub enum SchedulerError {
     InvalidUserAddress,
     /// Too many memory regions
     TooManyRegions,
+    /// CPU is not online
+    InvalidCpu,
+    /// A memory region would exceed `RLIMIT_AS`, `RLIMIT_DATA` or
+    /// `RLIMIT_STACK`
+    ResourceLimit,
 }
 
 /// Result type for scheduler operations
@@ -116,24 +137,52 @@ pub enum TaskState {
 
     /// Task is blocked on IPC
     Blocked,
+
+    /// Task has exited and waits for its parent to collect the exit code
+    Zombie,
 }
 
+/// Kernel stack size for tasks created with `Task::new`
+pub const DEFAULT_STACK_SIZE: usize = 8192;
+
 /// Maximum number of memory regions per task
 const MAX_MEMORY_REGIONS: usize = 16;
 
-/// Maximum number of signals (64 signals, 0-63)
-const MAX_SIGNALS: usize = 64;
+/// Size of per-signal tables (signals 1-64, indexed by signal number)
+const MAX_SIGNALS: usize = 65;
 
 /// User space address limit (512GB)
 pub const USER_LIMIT: usize = 0x0000_8000_0000_0000;
 
+/// Maximum number of supplementary groups per task
+pub const NGROUPS_MAX: usize = 32;
+
 /// Task credential descriptor
+///
+/// `uid` and `gid` are the effective IDs used for file permission
+/// classes; the real and saved IDs only matter when the IDs are changed
+/// (see `user::creds`). Privileged operations check `caps` (see
+/// `user::capability`).
 #[derive(Debug, Clone, Copy)]
 pub struct Credentials {
     /// Effective user identifier
     pub uid: u32,
     /// Effective group identifier
     pub gid: u32,
+    /// Real user identifier
+    pub ruid: u32,
+    /// Real group identifier
+    pub rgid: u32,
+    /// Saved set-user-ID
+    pub suid: u32,
+    /// Saved set-group-ID
+    pub sgid: u32,
+    /// Supplementary groups (first `ngroups` entries are valid)
+    pub groups: [u32; NGROUPS_MAX],
+    /// Number of supplementary groups
+    pub ngroups: usize,
+    /// Capability sets
+    pub caps: CapSets,
     /// Whether this task represents a kernel thread
     pub is_kernel_thread: bool,
 }
@@ -142,20 +191,79 @@ impl Credentials {
     /// Create credentials for kernel threads (root privileges, kernel flag)
     pub const fn kernel() -> Self {
         Self {
-            uid: 0,
-            gid: 0,
             is_kernel_thread: true,
+            ..Self::user(0, 0)
         }
     }
 
-    /// Create user-space credentials
+    /// Create user-space credentials with all IDs set to `uid`/`gid`
+    ///
+    /// Root starts with every capability, anyone else with none.
     pub const fn user(uid: u32, gid: u32) -> Self {
         Self {
             uid,
             gid,
+            ruid: uid,
+            rgid: gid,
+            suid: uid,
+            sgid: gid,
+            groups: [0; NGROUPS_MAX],
+            ngroups: 0,
+            caps: if uid == 0 {
+                CapSets::full()
+            } else {
+                CapSets::empty()
+            },
             is_kernel_thread: false,
         }
     }
+
+    /// Whether `cap` is in the effective capability set
+    pub fn capable(&self, cap: Capabilities) -> bool {
+        self.caps.effective.contains(cap)
+    }
+
+    /// Supplementary group list
+    pub fn groups(&self) -> &[u32] {
+        &self.groups[..self.ngroups]
+    }
+
+    /// Whether `gid` is the effective group or a supplementary group
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups().contains(&gid)
+    }
+}
+
+/// Resources a task has used, as reported by `wait4`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Time spent running in user mode (nanoseconds)
+    pub utime_ns: u64,
+    /// Time spent running in the kernel (nanoseconds)
+    pub stime_ns: u64,
+    /// Largest memory footprint seen (bytes)
+    pub maxrss: usize,
+    /// Page faults served without reading a file
+    pub minflt: u64,
+    /// Page faults that read from a file
+    pub majflt: u64,
+    /// Times the task gave up the CPU to wait for something
+    pub nvcsw: u64,
+    /// Times the task was preempted or yielded
+    pub nivcsw: u64,
+}
+
+impl ResourceUsage {
+    /// Add `other`'s usage; the footprint is the larger of the two
+    pub fn add(&mut self, other: &ResourceUsage) {
+        self.utime_ns += other.utime_ns;
+        self.stime_ns += other.stime_ns;
+        self.maxrss = self.maxrss.max(other.maxrss);
+        self.minflt += other.minflt;
+        self.majflt += other.majflt;
+        self.nvcsw += other.nvcsw;
+        self.nivcsw += other.nivcsw;
+    }
 }
 
 /// Task Control Block (TCB)
@@ -185,9 +293,31 @@ pub struct Task {
     /// Task priority level
     pub priority: TaskPriority,
 
+    /// Fair-class scheduling state (nice, weight, vruntime)
+    pub se: SchedEntity,
+
+    /// Real-time scheduling state (policy, RT priority, inheritance)
+    pub rt: RtEntity,
+
+    /// CPUs the task asked to run on (sched_setaffinity)
+    pub cpus_allowed: CpuMask,
+
+    /// Cpuset the task belongs to
+    pub cpuset: CpusetId,
+
+    /// CPU the task last ran on (None until it first runs)
+    pub last_cpu: Option<usize>,
+
+    /// True from when the task is picked to run until its context has been
+    /// saved after it is switched out; load balancing leaves it alone meanwhile
+    pub on_cpu: AtomicBool,
+
     /// Tick at which to wake the task (if sleeping)
     pub wake_tick: Option<u64>,
 
+    /// Pending wakeup timer (if sleeping in `hrtimer::sleep_until`)
+    pub sleep_timer: Option<TimerHandle>,
+
     /// Port ID the task is blocked on (if blocked on IPC)
     pub blocked_on_port: Option<usize>,
 
@@ -198,17 +328,39 @@ pub struct Task {
     pub region_count: usize,
 
     /// Signal handlers for each signal (indexed by signal number)
-    pub signal_handlers: [SigAction; MAX_SIGNALS],
+    ///
+    /// Shared between tasks created with `CLONE_SIGHAND`.
+    pub signal_handlers: alloc::sync::Arc<crate::sync::IrqSpinLock<[SigAction; MAX_SIGNALS]>>,
 
-    /// Pending signals bitset (bit N = signal N is pending)
+    /// Pending signals bitset (bit N - 1 = signal N is pending)
     /// Uses atomic operations for race-free signal delivery in SMP
     pub pending_signals: AtomicU64,
 
-    /// Signal mask (bit N = signal N is blocked)
+    /// Signal mask (bit N - 1 = signal N is blocked)
     /// Uses atomic operations for race-free mask updates
     pub signal_mask: AtomicU64,
 
-    /// Process ID (same as task ID for now)
+    /// Where each pending signal came from (indexed by signal number)
+    pub signal_info: crate::sync::IrqSpinLock<[SigSource; MAX_SIGNALS]>,
+
+    /// Pending real-time signals, each with its own source
+    pub signal_queue: crate::sync::IrqSpinLock<SigQueue>,
+
+    /// Signals the task is waiting for in `rt_sigtimedwait` or a signalfd
+    /// read; they wake it even while blocked
+    pub waited_signals: AtomicU64,
+
+    /// Mask to put back once `rt_sigsuspend` returns (the handler frame
+    /// saves it instead of the temporary mask)
+    pub saved_sigmask: Option<u64>,
+
+    /// Alternate signal stack for `SA_ONSTACK` handlers (`sigaltstack`)
+    pub alt_stack: SignalStack,
+
+    /// User FPU/SSE state, saved here while the task is switched out
+    pub fpu: FpuState,
+
+    /// Process ID (thread-group ID: the ID of the group's first task)
     pub pid: Pid,
 
     /// Parent process ID
@@ -227,7 +379,7 @@ pub struct Task {
     pub last_syscall: Option<usize>,
 
     /// File descriptor table (per-process)
-    pub fd_table: alloc::sync::Arc<crate::sync::SpinLock<crate::fs::vfs::file::FdTable>>,
+    pub fd_table: alloc::sync::Arc<crate::sync::SpinLock<crate::sys::syscall::FdTable>>,
 
     /// Heap start address (for brk/sbrk syscalls)
     /// This marks the beginning of the process's heap region
@@ -247,8 +399,60 @@ pub struct Task {
     /// Cached user stack pointer used for signal delivery bookkeeping
     pub user_stack_pointer: u64,
 
-    /// Pending signal frame metadata (if a handler is prepared)
-    pub pending_signal_frame: Option<PendingSignalFrame>,
+    /// Kernel thread control block (None for ordinary tasks)
+    pub kthread: Option<alloc::sync::Arc<KThreadInfo>>,
+
+    /// Registers saved on entry to the syscall in progress (null otherwise)
+    pub syscall_frame: *mut SyscallFrame,
+
+    /// Thread group (None until the task first creates a thread)
+    pub thread_group: Option<alloc::sync::Arc<ThreadGroup>>,
+
+    /// User address zeroed and futex-woken when the task exits
+    /// (`CLONE_CHILD_CLEARTID`, 0 = none)
+    pub clear_child_tid: usize,
+
+    /// How the task ended, set when it becomes a zombie
+    pub exit_status: Option<WaitStatus>,
+
+    /// Stop or continue of the process that `wait` has not reported yet
+    /// (kept on the leader)
+    pub wait_report: Option<WaitStatus>,
+
+    /// The process is stopped by a signal (kept on the leader)
+    pub stopped: bool,
+
+    /// Blocked in a wait that a deliverable signal cuts short
+    pub interruptible: bool,
+
+    /// CPU time, page faults and context switches of this task
+    pub rusage: ResourceUsage,
+
+    /// Monotonic time the task was created (nanoseconds since boot)
+    pub start_ns: u64,
+
+    /// Usage of the children collected by `wait`, and of their children
+    pub child_rusage: ResourceUsage,
+
+    /// Program the process is running (None until its first exec)
+    pub image: Option<alloc::sync::Arc<ProcessImage>>,
+
+    /// Tracing state while a tracer is attached (see `user::ptrace`)
+    pub ptrace: Option<TraceState>,
+
+    /// Syscall filters (see `user::seccomp`), inherited by children
+    pub seccomp: Option<alloc::sync::Arc<Filter>>,
+
+    /// `exec` may not grant privileges (`PR_SET_NO_NEW_PRIVS`), inherited
+    pub no_new_privs: bool,
+
+    /// Core dumps and `ptrace` attach by the process's own user are
+    /// allowed; cleared when its credentials change to another user (see
+    /// `user::creds::keeps_dumpable`) or by `PR_SET_DUMPABLE`
+    pub dumpable: bool,
+
+    /// Resource limits (see `user::rlimit`), inherited by children
+    pub rlimits: ResourceLimits,
 }
 
 impl Task {
@@ -273,19 +477,34 @@ impl Task {
         name: &'static str,
         entry_point: fn() -> !,
         priority: TaskPriority,
+    ) -> SchedulerResult<Self> {
+        Self::with_stack_size(id, name, entry_point, priority, DEFAULT_STACK_SIZE)
+    }
+
+    /// Create a new task with a kernel stack of `stack_size` bytes
+    ///
+    /// Same as `new` otherwise; kernel threads use this for their larger stack.
+    pub fn with_stack_size(
+        id: TaskId,
+        name: &'static str,
+        entry_point: fn() -> !,
+        priority: TaskPriority,
+        stack_size: usize,
     ) -> SchedulerResult<Self> {
         use crate::mm::allocator::kmalloc;
 
-        // 1. Allocate 8KB stack
-        const STACK_SIZE: usize = 8192;
-        let stack = kmalloc(STACK_SIZE);
+        // 1. Allocate the stack
+        let stack = kmalloc(stack_size);
 
         if stack.is_null() {
             return Err(SchedulerError::OutOfMemory);
         }
 
+        // Live locals on the stack reference heap objects
+        crate::mm::kmemleak::mark_root(stack);
+
         // 2. Calculate stack top (stack grows downward)
-        let stack_top = (stack as usize) + STACK_SIZE;
+        let stack_top = (stack as usize) + stack_size;
 
         // 3. Prepare initial stack frame
         // The stack will be set up so that when context_switch does 'ret',
@@ -327,15 +546,21 @@ impl Task {
             r13: 0,
             r14: 0,
             r15: 0,
-            cr3: 0, // Will be set when process page table is allocated
+            // Kernel threads run on the kernel template; exec/fork replace
+            // this with the process's private PML4
+            cr3: crate::mm::paging::get_kernel_template()
+                .unwrap_or_else(crate::mm::paging::get_current_cr3) as u64,
+            fs_base: 0,
         };
 
         // Initialize signal handlers with defaults
-        let signal_handlers = Self::init_default_signal_handlers();
+        let signal_handlers = alloc::sync::Arc::new(crate::sync::IrqSpinLock::new(
+            Self::init_default_signal_handlers(),
+        ));
 
         // Create empty FD table
-        use crate::fs::vfs::file::FdTable;
         use crate::sync::SpinLock;
+        use crate::sys::syscall::FdTable;
         use alloc::sync::Arc;
         let fd_table = Arc::new(SpinLock::new(FdTable::new()));
 
@@ -343,17 +568,30 @@ impl Task {
             id,
             name,
             stack,
-            stack_size: STACK_SIZE,
+            stack_size,
             state: TaskState::Ready,
             context,
             priority,
+            se: SchedEntity::new(priority.nice()),
+            rt: RtEntity::new(),
+            cpus_allowed: CpuMask::all(),
+            cpuset: ROOT_CPUSET,
+            last_cpu: None,
+            on_cpu: AtomicBool::new(false),
             wake_tick: None,
+            sleep_timer: None,
             blocked_on_port: None,
             memory_regions: [const { None }; MAX_MEMORY_REGIONS],
             region_count: 0,
             signal_handlers,
             pending_signals: AtomicU64::new(0),
             signal_mask: AtomicU64::new(0),
+            signal_info: crate::sync::IrqSpinLock::new([SigSource::Kernel; MAX_SIGNALS]),
+            signal_queue: crate::sync::IrqSpinLock::new(SigQueue::new()),
+            waited_signals: AtomicU64::new(0),
+            saved_sigmask: None,
+            alt_stack: SignalStack::disabled(),
+            fpu: FpuState::new(),
             pid: id,                          // PID = task ID
             ppid: 0,                          // Will be set by parent
             pgid: id,                         // Initially, pgid = pid
@@ -366,14 +604,32 @@ impl Task {
             children: alloc::vec::Vec::new(), // Empty children list initially
             creds: Credentials::kernel(),
             user_stack_pointer: 0,
-            pending_signal_frame: None,
+            kthread: None,
+            syscall_frame: core::ptr::null_mut(),
+            thread_group: None,
+            clear_child_tid: 0,
+            exit_status: None,
+            wait_report: None,
+            stopped: false,
+            interruptible: false,
+            rusage: ResourceUsage::default(),
+            start_ns: crate::sched::hrtimer::monotonic_ns(),
+            child_rusage: ResourceUsage::default(),
+            image: None,
+            ptrace: None,
+            seccomp: None,
+            no_new_privs: false,
+            dumpable: true,
+            rlimits: ResourceLimits::new(),
         })
     }
 
     /// Add a memory region to this task
     ///
     /// Validates the region and ensures no overlaps with existing regions.
-    /// All regions must be within user space limits.
+    /// All regions must be within user space limits, and the task's
+    /// `RLIMIT_AS`, `RLIMIT_DATA` (data, BSS and heap) and `RLIMIT_STACK`
+    /// must allow them.
     /// Automatically ensures USER flag is set for user space regions.
     ///
     /// # Arguments
@@ -412,6 +668,27 @@ impl Task {
             return Err(SchedulerError::TooManyRegions);
         }
 
+        if !self.creds.is_kernel_thread {
+            let usage = crate::mm::mmap::memory_usage(self);
+            let size = region.end - region.start;
+            let grown = crate::user::rlimit::MemoryUsage {
+                address_space: usage.address_space + size,
+                data: match region.region_type {
+                    MemoryRegionType::Data | MemoryRegionType::Bss | MemoryRegionType::Heap => {
+                        usage.data + size
+                    }
+                    _ => 0,
+                },
+                stack: match region.region_type {
+                    MemoryRegionType::Stack => size,
+                    _ => 0,
+                },
+            };
+            if self.rlimits.memory_over_limit(&grown).is_some() {
+                return Err(SchedulerError::ResourceLimit);
+            }
+        }
+
         // Add the region
         self.memory_regions[self.region_count] = Some(region);
         self.region_count += 1;
@@ -521,6 +798,27 @@ impl Task {
             .sum()
     }
 
+    /// Bytes of user memory resident in the task's page tables
+    ///
+    /// Threads sharing an address space all report the same figure. The
+    /// walk holds the PMM lock, under which page tables are freed: if the
+    /// task is still in the task table at that point, its address space
+    /// can't be freed before the walk is done (see `release_task`).
+    pub fn resident_memory_usage(&self) -> usize {
+        if !self.has_private_address_space() {
+            return 0;
+        }
+
+        let _pmm = crate::mm::pmm::get_global_pmm();
+        let cr3 = self.address_space();
+        match super::get_task_by_id(self.id) {
+            Some(task) if task.address_space() == cr3 => {
+                crate::mm::paging::user_resident_bytes(cr3)
+            }
+            _ => 0,
+        }
+    }
+
     /// Clear all memory regions (used during exec)
     pub fn clear_memory_regions(&mut self) {
         for region in &mut self.memory_regions {
@@ -529,42 +827,89 @@ impl Task {
         self.region_count = 0;
     }
 
+    /// Physical address of the PML4 this task runs on
+    pub fn address_space(&self) -> crate::mm::PhysAddr {
+        self.context.cr3 as crate::mm::PhysAddr
+    }
+
+    /// Check whether the task owns a private page table
+    ///
+    /// Kernel threads share the kernel template and must never get user
+    /// mappings installed into it.
+    pub fn has_private_address_space(&self) -> bool {
+        let cr3 = self.address_space();
+        cr3 != 0 && Some(cr3) != crate::mm::paging::get_kernel_template()
+    }
+
+    /// Top of the task's kernel stack (16-byte aligned)
+    pub fn kernel_stack_top(&self) -> u64 {
+        (self.stack as u64 + self.stack_size as u64) & !0xF
+    }
+
+    /// Make the task's first run return to user mode with `frame`'s registers
+    ///
+    /// Used for threads created by `clone`: instead of entering a kernel
+    /// function, the new task's first `context_switch` returns into
+    /// `ret_to_user`, which restores `frame` from the top of the kernel stack.
+    pub fn set_user_entry(&mut self, frame: &SyscallFrame) {
+        let frame_size = core::mem::size_of::<SyscallFrame>() as u64;
+        let frame_addr = self.kernel_stack_top() - frame_size;
+
+        unsafe {
+            core::ptr::write(frame_addr as *mut SyscallFrame, *frame);
+
+            // Return address, then the callee-saved registers popped by
+            // context_switch
+            let mut rsp = frame_addr as *mut u64;
+            rsp = rsp.offset(-1);
+            *rsp = crate::sys::syscall::ret_to_user as *const () as u64;
+            for _ in 0..6 {
+                rsp = rsp.offset(-1);
+                *rsp = 0;
+            }
+            self.context.rsp = rsp as u64;
+        }
+
+        self.context.r12 = 0;
+        self.syscall_frame = core::ptr::null_mut();
+    }
+
     /// Initialize default signal handlers for a new task
     ///
-    /// Sets up the default signal actions according to POSIX semantics:
+    /// Every signal starts with its default action (see
+    /// `signal::default_action`):
     /// - Most signals terminate the process
     /// - Some signals are ignored by default (SIGCHLD, SIGURG, SIGWINCH)
     /// - Some signals stop the process (SIGSTOP, SIGTSTP, SIGTTIN, SIGTTOU)
     /// - SIGCONT continues a stopped process
     ///
+    /// SIGCHLD must not start out explicitly ignored: that would ask for
+    /// children to be freed without becoming zombies.
+    ///
     /// # Returns
     /// Array of SigAction structures with default handlers
     fn init_default_signal_handlers() -> [SigAction; MAX_SIGNALS] {
-        let mut handlers = [SigAction::default(); MAX_SIGNALS];
-
-        // Set ignored signals
-        handlers[signals::SIGCHLD as usize] = SigAction::ignore();
-        handlers[signals::SIGURG as usize] = SigAction::ignore();
-        handlers[signals::SIGWINCH as usize] = SigAction::ignore();
-
-        // All other signals use default action (handled by kernel)
         // SIGKILL and SIGSTOP cannot be caught or ignored (enforced elsewhere)
-
-        handlers
+        [SigAction::default(); MAX_SIGNALS]
     }
 
     /// Reset signal handlers to default (used during exec)
     ///
     /// After exec, all signal handlers are reset to their default actions,
     /// except for signals that were set to SIG_IGN which remain ignored.
+    ///
+    /// The task stops sharing its handlers with any other thread.
     pub fn reset_signal_handlers(&mut self) {
-        for (_sig_num, handler) in self.signal_handlers.iter_mut().enumerate() {
+        let mut handlers = *self.signal_handlers.lock();
+        for handler in handlers.iter_mut() {
             // Keep ignored signals ignored, reset everything else to default
             if !matches!(handler.handler, crate::signal::SigHandler::Ignore) {
                 *handler = SigAction::default();
             }
         }
+        self.signal_handlers = alloc::sync::Arc::new(crate::sync::IrqSpinLock::new(handlers));
         // Clear pending signals atomically
+        self.signal_queue.lock().clear();
         self.pending_signals.store(0, Ordering::Release);
         // Keep signal mask (inherited across exec)
     }
@@ -577,10 +922,10 @@ impl Task {
     /// # Returns
     /// true if the signal is pending and not blocked
     pub fn has_pending_signal(&self, signal: u32) -> bool {
-        if signal >= MAX_SIGNALS as u32 {
+        if signal == 0 || signal >= MAX_SIGNALS as u32 {
             return false;
         }
-        let mask = 1u64 << signal;
+        let mask = sigbit(signal);
         let pending = self.pending_signals.load(Ordering::Acquire);
         let blocked = self.signal_mask.load(Ordering::Acquire);
         (pending & mask) != 0 && (blocked & mask) == 0
@@ -598,7 +943,7 @@ impl Task {
             return None;
         }
         // Find the lowest set bit (lowest signal number)
-        Some(unblocked_pending.trailing_zeros())
+        Some(lowest_signal(unblocked_pending))
     }
 
     /// Clear a pending signal (atomically)
@@ -606,8 +951,8 @@ impl Task {
     /// # Arguments
     /// * `signal` - Signal number to clear
     pub fn clear_pending_signal(&self, signal: u32) {
-        if signal < MAX_SIGNALS as u32 {
-            let mask = 1u64 << signal;
+        if signal != 0 && signal < MAX_SIGNALS as u32 {
+            let mask = sigbit(signal);
             // Use fetch_and with inverted mask to clear the bit atomically
             self.pending_signals.fetch_and(!mask, Ordering::Release);
         }
@@ -624,10 +969,10 @@ impl Task {
     /// # Returns
     /// true if the signal was added, false if invalid signal number
     pub fn add_pending_signal(&self, signal: u32) -> bool {
-        if signal >= MAX_SIGNALS as u32 {
+        if signal == 0 || signal >= MAX_SIGNALS as u32 {
             return false;
         }
-        let mask = 1u64 << signal;
+        let mask = sigbit(signal);
         // Use fetch_or to set the bit atomically
         self.pending_signals.fetch_or(mask, Ordering::Release);
         true
@@ -652,7 +997,7 @@ impl Task {
     /// Block signals (add to mask atomically)
     ///
     /// # Arguments
-    /// * `mask` - Signals to block (bit N = block signal N)
+    /// * `mask` - Signals to block (bit N - 1 = block signal N)
     pub fn block_signals(&self, mask: u64) {
         self.signal_mask.fetch_or(mask, Ordering::Release);
     }
@@ -660,7 +1005,7 @@ impl Task {
     /// Unblock signals (remove from mask atomically)
     ///
     /// # Arguments
-    /// * `mask` - Signals to unblock (bit N = unblock signal N)
+    /// * `mask` - Signals to unblock (bit N - 1 = unblock signal N)
     pub fn unblock_signals(&self, mask: u64) {
         self.signal_mask.fetch_and(!mask, Ordering::Release);
     }
@@ -685,14 +1030,18 @@ pub extern "C" fn entry_trampoline() -> ! {
         // Save entry_point in a callee-saved register
         "mov r12, rax",
 
-        // Enable interrupts before calling the task
-        // (they were disabled during the interrupt handler)
-        "sti",
-
         // Align stack to 16 bytes (required by System V ABI)
         // The stack should be 16-byte aligned before a call instruction
         "and rsp, -16",
 
+        // Finish the switch away from the previous task (a first run skips
+        // the code after context_switch in schedule())
+        "call {finish_task_switch}",
+
+        // Enable interrupts before calling the task
+        // (they were disabled during the interrupt handler)
+        "sti",
+
         // R12 contains the entry_point function pointer
         // Call the entry point
         "call r12",
@@ -707,6 +1056,7 @@ pub extern "C" fn entry_trampoline() -> ! {
         "hlt",
         "jmp 2b",
 
+        finish_task_switch = sym super::finish_task_switch,
         task_returned_panic = sym task_returned_panic,
     )
 }
@@ -102,7 +102,8 @@ static TIMER_TICKS: AtomicUsize = AtomicUsize::new(0);
 ///
 /// This function:
 /// 1. Creates a new IDT
-/// 2. Registers the timer interrupt handler at vector 32 (IRQ0)
+/// 2. Registers the timer interrupt handler at vector 32 (IRQ0), the syscall
+///    gate at 0x80 and the debug (1) and breakpoint (3) traps
 /// 3. Loads the IDT into the CPU
 ///
 /// # Safety
@@ -151,6 +152,16 @@ pub unsafe fn init_idt() {
 
     serial_println!("[TIMER] Syscall handler registered at vector 0x80 (DPL=3)");
 
+    // Debug and breakpoint traps; `int3` is allowed from user mode
+    IDT.entries[1].set_handler(
+        crate::arch::x86_64::fault::debug_trap_wrapper as *const () as usize,
+        code_selector,
+    );
+    IDT.entries[3].set_handler_user(
+        crate::arch::x86_64::fault::breakpoint_wrapper as *const () as usize,
+        code_selector,
+    );
+
     // Create IDT pointer
     let idt_ptr = IdtPointer {
         limit: (core::mem::size_of::<IdtTable>() - 1) as u16,
@@ -338,11 +349,12 @@ unsafe fn send_eoi() {
 ///
 /// This is a naked function that saves/restores registers and calls the actual handler.
 /// We use a naked function because we're manually managing the IDT.
+/// Before returning it passes the saved registers to `interrupt_exit_to_user`.
 #[unsafe(naked)]
 extern "C" fn timer_interrupt_handler_wrapper() {
     core::arch::naked_asm!(
         // The CPU has already pushed SS, RSP, RFLAGS, CS, RIP
-        // We need to save all other registers
+        // We need to save all other registers (in SyscallFrame order)
 
         "push rax",
         "push rcx",
@@ -353,11 +365,27 @@ extern "C" fn timer_interrupt_handler_wrapper() {
         "push r9",
         "push r10",
         "push r11",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
 
         // Call the actual handler
         "call {handler}",
 
+        // Act on exits and signals before returning to user mode
+        "mov rdi, rsp",
+        "call {exit_to_user}",
+
         // Restore registers
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
         "pop r11",
         "pop r10",
         "pop r9",
@@ -372,6 +400,7 @@ extern "C" fn timer_interrupt_handler_wrapper() {
         "iretq",
 
         handler = sym timer_interrupt_handler,
+        exit_to_user = sym crate::sys::syscall::interrupt_exit_to_user,
     )
 }
 
@@ -385,22 +414,21 @@ extern "C" fn timer_interrupt_handler_wrapper() {
 ///
 /// # Notes
 /// - The CPU automatically disables interrupts (IF=0) when entering this handler
-/// - The scheduler tick() function performs a context switch and doesn't return
-/// - This is a "tail-switch" - we don't return to this handler
+/// - The scheduler tick() function may switch to another task; this task
+///   returns here when it next runs
 extern "C" fn timer_interrupt_handler() {
     // Increment tick counter (for testing and debugging)
     TIMER_TICKS.fetch_add(1, Ordering::Relaxed);
+    crate::sched::cputime::irq_enter();
 
     // Send EOI to PIC first (so it can send next interrupt)
     unsafe {
         send_eoi();
     }
+    crate::sched::cputime::irq_exit();
 
-    // Call scheduler tick (this performs context switch and doesn't return)
+    // Call scheduler tick (switches tasks once the current one has had its share)
     crate::sched::tick();
-
-    // Note: We never reach here because tick() does a tail-switch
-    // The next task will continue from where it was interrupted
 }
 
 /// Initialize the timer interrupt system
@@ -443,9 +471,19 @@ pub unsafe fn init_timer(frequency: u32) {
 
 /// Get the current tick count
 ///
-/// Returns the number of timer interrupts that have occurred since boot.
-/// Useful for testing and debugging.
+/// Returns the number of `SCHED_HZ` ticks since boot. Once the TSC is
+/// calibrated this is derived from the monotonic clock, since idle CPUs no
+/// longer take a timer interrupt every tick.
 pub fn get_tick_count() -> usize {
+    if crate::arch::x86_64::tsc::is_calibrated() {
+        (super::hrtimer::monotonic_ns() / super::hrtimer::TICK_NS) as usize
+    } else {
+        get_interrupt_count()
+    }
+}
+
+/// Get the number of scheduler tick interrupts taken since boot
+pub fn get_interrupt_count() -> usize {
     TIMER_TICKS.load(Ordering::Relaxed)
 }
 
@@ -457,11 +495,12 @@ pub fn get_tick_count() -> usize {
 ///
 /// This is a naked function that saves/restores registers and calls the actual handler.
 /// This handler is used for APIC timer interrupts (vector 0x20) in SMP mode.
+/// Before returning it passes the saved registers to `interrupt_exit_to_user`.
 #[unsafe(naked)]
 extern "C" fn apic_timer_interrupt_handler_wrapper() {
     core::arch::naked_asm!(
         // The CPU has already pushed SS, RSP, RFLAGS, CS, RIP
-        // We need to save all other registers
+        // We need to save all other registers (in SyscallFrame order)
 
         "push rax",
         "push rcx",
@@ -472,11 +511,27 @@ extern "C" fn apic_timer_interrupt_handler_wrapper() {
         "push r9",
         "push r10",
         "push r11",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
 
         // Call the actual handler
         "call {handler}",
 
+        // Act on exits and signals before returning to user mode
+        "mov rdi, rsp",
+        "call {exit_to_user}",
+
         // Restore registers
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
         "pop r11",
         "pop r10",
         "pop r9",
@@ -491,6 +546,7 @@ extern "C" fn apic_timer_interrupt_handler_wrapper() {
         "iretq",
 
         handler = sym apic_timer_interrupt_handler,
+        exit_to_user = sym crate::sys::syscall::interrupt_exit_to_user,
     )
 }
 
@@ -501,26 +557,43 @@ static BALANCE_COUNTER: AtomicUsize = AtomicUsize::new(0);
 ///
 /// This function is called when an APIC timer interrupt (vector 0x20) occurs.
 /// It:
-/// 1. Increments the per-CPU tick counter
-/// 2. Sends EOI to the Local APIC
-/// 3. Performs load balancing every 100ms (2 ticks at 20Hz)
-/// 4. Calls the scheduler tick function for the current core
+/// 1. Sends EOI to the Local APIC
+/// 2. Runs expired hrtimers and reprograms the LAPIC (see `hrtimer`)
+/// 3. Returns early unless the scheduler tick is due
+/// 4. Increments the per-CPU tick counter
+/// 5. Balances load every `SCHED_BALANCE_INTERVAL_TICKS` ticks
+/// 6. Calls the scheduler tick function for the current core
 ///
 /// # Notes
 /// - The CPU automatically disables interrupts (IF=0) when entering this handler
-/// - The scheduler tick() function performs a context switch and doesn't return
-/// - This is a "tail-switch" - we don't return to this handler
+/// - The scheduler tick() function may switch to another task; this task
+///   returns here when it next runs
 extern "C" fn apic_timer_interrupt_handler() {
     use crate::arch::x86_64::acpi::get_madt_info;
     use crate::arch::x86_64::apic::LocalApic;
     use crate::arch::x86_64::smp::percpu::percpu_current_mut;
     use core::sync::atomic::Ordering;
 
+    crate::sched::cputime::irq_enter();
+
+    // Send EOI to Local APIC
+    unsafe {
+        let madt_info = get_madt_info().expect("MADT info not available");
+        let mut lapic = LocalApic::new(madt_info.lapic_address);
+        lapic.eoi();
+    }
+
+    // Run expired timers; in one-shot modes most interrupts are not ticks
+    if !crate::sched::hrtimer::timer_interrupt() {
+        crate::sched::cputime::irq_exit();
+        return;
+    }
+
     // Get current CPU's per-CPU data
     let percpu = unsafe { percpu_current_mut() };
 
     // Increment per-CPU tick counter
-    percpu.ticks.fetch_add(1, Ordering::Relaxed);
+    let cpu_ticks = percpu.ticks.fetch_add(1, Ordering::Relaxed) + 1;
 
     // Also increment global tick counter for compatibility
     let global_ticks = TIMER_TICKS.fetch_add(1, Ordering::Relaxed);
@@ -534,23 +607,16 @@ extern "C" fn apic_timer_interrupt_handler() {
         );
     }
 
-    // Send EOI to Local APIC
-    unsafe {
-        let madt_info = get_madt_info().expect("MADT info not available");
-        let mut lapic = LocalApic::new(madt_info.lapic_address);
-        lapic.eoi();
-    }
-
-    // Perform load balancing every 100ms (2 ticks at 20Hz)
-    // Only CPU 0 performs load balancing to avoid conflicts
-    if percpu.id == 0 && global_ticks % 2 == 0 {
+    // Each busy CPU balances itself periodically (idle CPUs steal instead);
+    // offset by CPU ID so they don't all lock runqueues on the same tick
+    let interval = crate::config::SCHED_BALANCE_INTERVAL_TICKS;
+    if (cpu_ticks + percpu.id as u64) % interval == 0 {
         crate::sched::balance_load();
     }
+    crate::sched::cputime::irq_exit();
 
-    // Call scheduler tick (this performs context switch and doesn't return)
+    // Call scheduler tick (switches tasks once the current one has had its share)
     crate::sched::tick();
-
-    // Note: We never reach here because tick() does a tail-switch
 }
 
 /// Initialize APIC timer interrupt handler in IDT
@@ -598,11 +664,12 @@ pub unsafe fn init_apic_timer_handler() {
 ///
 /// This is a naked function that saves/restores registers and calls the actual handler.
 /// This handler is used for RESCHEDULE_IPI interrupts (vector 0x30) in SMP mode.
+/// Before returning it passes the saved registers to `interrupt_exit_to_user`.
 #[unsafe(naked)]
 extern "C" fn reschedule_ipi_handler_wrapper() {
     core::arch::naked_asm!(
         // The CPU has already pushed SS, RSP, RFLAGS, CS, RIP
-        // We need to save all other registers
+        // We need to save all other registers (in SyscallFrame order)
 
         "push rax",
         "push rcx",
@@ -613,11 +680,27 @@ extern "C" fn reschedule_ipi_handler_wrapper() {
         "push r9",
         "push r10",
         "push r11",
+        "push rbx",
+        "push rbp",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
 
         // Call the actual handler
         "call {handler}",
 
+        // Act on exits and signals before returning to user mode
+        "mov rdi, rsp",
+        "call {exit_to_user}",
+
         // Restore registers
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
         "pop r11",
         "pop r10",
         "pop r9",
@@ -632,6 +715,7 @@ extern "C" fn reschedule_ipi_handler_wrapper() {
         "iretq",
 
         handler = sym reschedule_ipi_handler,
+        exit_to_user = sym crate::sys::syscall::interrupt_exit_to_user,
     )
 }
 
@@ -645,27 +729,28 @@ extern "C" fn reschedule_ipi_handler_wrapper() {
 /// - A new task is enqueued to this CPU
 /// - A task is migrated to this CPU during load balancing
 /// - A task on this CPU needs to be woken up
+/// - A task running on this CPU must act on a signal or group exit
 ///
 /// # Notes
 /// - The CPU automatically disables interrupts (IF=0) when entering this handler
-/// - The scheduler tick() function performs a context switch and doesn't return
-/// - This is a "tail-switch" - we don't return to this handler
+/// - schedule() may switch to another task; this task returns here when it
+///   next runs
 extern "C" fn reschedule_ipi_handler() {
     use crate::arch::x86_64::acpi::get_madt_info;
     use crate::arch::x86_64::apic::LocalApic;
 
+    crate::sched::cputime::irq_enter();
+
     // Send EOI to Local APIC
     unsafe {
         let madt_info = get_madt_info().expect("MADT info not available");
         let mut lapic = LocalApic::new(madt_info.lapic_address);
         lapic.eoi();
     }
+    crate::sched::cputime::irq_exit();
 
-    // Call scheduler tick to perform context switch
-    // Note: This doesn't return - it performs a tail-switch
-    crate::sched::tick();
-
-    // Note: We never reach here because tick() does a tail-switch
+    // Pick up the newly enqueued task
+    crate::sched::schedule();
 }
 
 /// Initialize RESCHEDULE_IPI interrupt handler in IDT
@@ -768,6 +853,8 @@ extern "C" fn tlb_shootdown_ipi_handler() {
     use crate::arch::x86_64::acpi::get_madt_info;
     use crate::arch::x86_64::apic::LocalApic;
 
+    crate::sched::cputime::irq_enter();
+
     // Handle the TLB shootdown request
     unsafe {
         crate::mm::tlb::handle_tlb_shootdown_ipi();
@@ -779,6 +866,8 @@ extern "C" fn tlb_shootdown_ipi_handler() {
         let mut lapic = LocalApic::new(madt_info.lapic_address);
         lapic.eoi();
     }
+
+    crate::sched::cputime::irq_exit();
 }
 
 /// Initialize TLB_SHOOTDOWN_IPI interrupt handler in IDT
@@ -0,0 +1,585 @@
+//! Signal Frames
+//!
+//! A signal with a handler is delivered by pushing a frame on the user
+//! stack and pointing the saved registers at the handler. The frame uses
+//! the Linux x86_64 `rt_sigframe` layout: the address of the restorer (the
+//! handler's return address), a `ucontext` holding the interrupted
+//! registers and signal mask, and the `siginfo`. A copy of the task's
+//! FPU/SSE state (`Task::fpu`) goes above it, 64-byte aligned, and
+//! `sigcontext.fpstate` points to it; `rt_sigreturn` makes the saved copy
+//! the task's state again.
+//!
+//! The restorer calls `rt_sigreturn`, which checks the frame and puts
+//! everything back. Frames are only ever read back through user memory,
+//! so a handler may edit the saved context before it returns.
+//!
+//! Handlers with `SA_ONSTACK` get their frame on the task's alternate
+//! signal stack, if it has one and is not already running on it, so that
+//! a SIGSEGV from a stack overflow can still be handled.
+
+use super::sa_flags::*;
+use super::{sigbit, SigAction, SigHandler, SigInfo, SigSource, Signal, UNBLOCKABLE};
+use crate::arch::x86_64::fpu::{FpuState, STATE_ALIGN};
+use crate::sched::task::{Task, USER_LIMIT};
+use crate::sys::syscall::{SyscallFrame, ERESTARTNOHAND, ERESTARTSYS};
+use core::mem::size_of;
+
+/// Bytes below the user stack pointer that leaf functions may use
+const RED_ZONE: u64 = 128;
+
+/// `ss_flags`: the task is running on its alternate signal stack
+pub const SS_ONSTACK: i32 = 1;
+
+/// `ss_flags`: no alternate signal stack
+pub const SS_DISABLE: i32 = 2;
+
+/// Smallest alternate signal stack `sigaltstack` accepts
+pub const MINSIGSTKSZ: u64 = 2048;
+
+/// RFLAGS.TF - single-step trap
+pub const RFLAGS_TF: u64 = 1 << 8;
+
+/// RFLAGS.DF - string direction
+const RFLAGS_DF: u64 = 1 << 10;
+
+/// RFLAGS bits user space may set, through `rt_sigreturn` or a ptrace
+/// tracer (arithmetic flags, TF, DF, RF and AC)
+pub const USER_RFLAGS: u64 = 0x50DD5;
+
+/// Exception vector of a page fault, reported in `sigcontext.trapno`
+const TRAP_PAGE_FAULT: u64 = 14;
+
+/// Saved general-purpose registers (Linux `sigcontext` layout)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SigContext {
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub rdx: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rsp: u64,
+    pub rip: u64,
+    pub eflags: u64,
+    pub cs: u16,
+    pub gs: u16,
+    pub fs: u16,
+    pub ss: u16,
+    /// Page fault error code (0 otherwise)
+    pub err: u64,
+    /// Exception vector (0 for signals that are not faults)
+    pub trapno: u64,
+    /// Signal mask of the interrupted code
+    pub oldmask: u64,
+    /// Faulting address
+    pub cr2: u64,
+    /// User address of the saved FPU/SSE state (0 if none)
+    pub fpstate: u64,
+    _reserved: [u64; 8],
+}
+
+impl SigContext {
+    /// Snapshot the user registers in `regs`
+    fn from_frame(regs: &SyscallFrame) -> Self {
+        Self {
+            r8: regs.r8,
+            r9: regs.r9,
+            r10: regs.r10,
+            r11: regs.r11,
+            r12: regs.r12,
+            r13: regs.r13,
+            r14: regs.r14,
+            r15: regs.r15,
+            rdi: regs.rdi,
+            rsi: regs.rsi,
+            rbp: regs.rbp,
+            rbx: regs.rbx,
+            rdx: regs.rdx,
+            rax: regs.rax,
+            rcx: regs.rcx,
+            rsp: regs.rsp,
+            rip: regs.rip,
+            eflags: regs.rflags,
+            cs: regs.cs as u16,
+            ss: regs.ss as u16,
+            ..Self::default()
+        }
+    }
+
+    /// Load the general-purpose registers into `regs`
+    ///
+    /// Segments stay as they are and only `USER_RFLAGS` come from the
+    /// context, so the result is always a valid user-mode frame.
+    fn restore_to(&self, regs: &mut SyscallFrame) {
+        regs.r8 = self.r8;
+        regs.r9 = self.r9;
+        regs.r10 = self.r10;
+        regs.r11 = self.r11;
+        regs.r12 = self.r12;
+        regs.r13 = self.r13;
+        regs.r14 = self.r14;
+        regs.r15 = self.r15;
+        regs.rdi = self.rdi;
+        regs.rsi = self.rsi;
+        regs.rbp = self.rbp;
+        regs.rbx = self.rbx;
+        regs.rdx = self.rdx;
+        regs.rax = self.rax;
+        regs.rcx = self.rcx;
+        regs.rsp = self.rsp;
+        regs.rip = self.rip;
+        regs.rflags = (regs.rflags & !USER_RFLAGS) | (self.eflags & USER_RFLAGS);
+    }
+}
+
+/// Signal stack description (Linux `stack_t` layout)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalStack {
+    /// Base of the stack
+    pub ss_sp: u64,
+    /// `SS_*` flags
+    pub ss_flags: i32,
+    _pad: i32,
+    /// Size of the stack in bytes
+    pub ss_size: u64,
+}
+
+impl SignalStack {
+    /// No alternate stack
+    pub const fn disabled() -> Self {
+        Self {
+            ss_sp: 0,
+            ss_flags: SS_DISABLE,
+            _pad: 0,
+            ss_size: 0,
+        }
+    }
+
+    /// Whether there is an alternate stack
+    pub fn is_enabled(&self) -> bool {
+        self.ss_flags & SS_DISABLE == 0
+    }
+
+    /// Whether the stack pointer `sp` is on this stack
+    pub fn contains(&self, sp: u64) -> bool {
+        self.is_enabled() && sp > self.ss_sp && sp - self.ss_sp <= self.ss_size
+    }
+
+    /// The stack as reported to code whose stack pointer is `sp`
+    pub fn report(&self, sp: u64) -> Self {
+        let ss_flags = if !self.is_enabled() {
+            SS_DISABLE
+        } else if self.contains(sp) {
+            SS_ONSTACK
+        } else {
+            0
+        };
+        Self { ss_flags, ..*self }
+    }
+}
+
+/// Set a task's alternate signal stack (`sigaltstack`)
+///
+/// # Arguments
+/// * `task` - The task
+/// * `new` - The new stack; `SS_DISABLE` in `ss_flags` removes it
+/// * `sp` - The task's user stack pointer
+///
+/// # Returns
+/// Err if the task is running on its current alternate stack, the flags
+/// are not valid or the stack is smaller than `MINSIGSTKSZ`
+pub fn set_alt_stack(task: &mut Task, new: &SignalStack, sp: u64) -> Result<(), ()> {
+    if task.alt_stack.contains(sp) {
+        return Err(()); // EPERM
+    }
+    task.alt_stack = match new.ss_flags {
+        SS_DISABLE => SignalStack::disabled(),
+        // SS_ONSTACK is accepted and ignored, as on Linux
+        0 | SS_ONSTACK if new.ss_size < MINSIGSTKSZ => return Err(()), // ENOMEM
+        0 | SS_ONSTACK => SignalStack {
+            ss_sp: new.ss_sp,
+            ss_flags: 0,
+            _pad: 0,
+            ss_size: new.ss_size,
+        },
+        _ => return Err(()), // EINVAL
+    };
+    Ok(())
+}
+
+/// Interrupted user context (Linux `ucontext` layout)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UContext {
+    pub uc_flags: u64,
+    /// Unused (0)
+    pub uc_link: u64,
+    /// Signal stack in use when the signal arrived
+    pub uc_stack: SignalStack,
+    /// Registers
+    pub uc_mcontext: SigContext,
+    /// Signal mask restored by `rt_sigreturn`
+    pub uc_sigmask: u64,
+}
+
+/// Frame pushed on the user stack for a handler (Linux `rt_sigframe`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RtSigFrame {
+    /// Return address of the handler: the restorer
+    pub pretcode: u64,
+    pub uc: UContext,
+    pub info: SigInfo,
+}
+
+/// View a frame as bytes to copy out
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// Push a signal frame and point `regs` at the handler
+///
+/// # Arguments
+/// * `task` - Task the signal is delivered to (the current one)
+/// * `regs` - User registers to return to, changed to enter the handler
+/// * `signal` - Signal being delivered
+/// * `action` - Its action, a custom handler
+/// * `source` - Where the signal came from
+/// * `saved_mask` - Signal mask for `rt_sigreturn` to restore
+///
+/// # Returns
+/// Err if the action has no usable handler or restorer, or the frame does
+/// not fit on the user stack
+fn setup_signal_frame(
+    task: &mut Task,
+    regs: &mut SyscallFrame,
+    signal: Signal,
+    action: &SigAction,
+    source: SigSource,
+    saved_mask: u64,
+) -> Result<(), ()> {
+    let handler = match action.handler {
+        SigHandler::Custom(addr) if addr != 0 && addr < USER_LIMIT => addr as u64,
+        _ => return Err(()),
+    };
+    // Handlers return through the restorer, which calls rt_sigreturn
+    if action.flags & SA_RESTORER == 0 || action.restorer == 0 || action.restorer >= USER_LIMIT {
+        return Err(());
+    }
+
+    // The CPU holds the running task's FPU state; bring the copy up to date
+    task.fpu.save();
+
+    // Switch to the alternate stack unless already on it
+    let alt_stack = &task.alt_stack;
+    let on_alt_stack = alt_stack.contains(regs.rsp);
+    let use_alt_stack = action.flags & SA_ONSTACK != 0 && alt_stack.is_enabled();
+    let top = if use_alt_stack && !on_alt_stack {
+        alt_stack.ss_sp + alt_stack.ss_size
+    } else {
+        regs.rsp.checked_sub(RED_ZONE).ok_or(())?
+    };
+
+    // FPU state on top, then the frame, aligned as if the handler had
+    // been called
+    let fpu_len = task.fpu.as_bytes().len() as u64;
+    let fp_addr = top.checked_sub(fpu_len).ok_or(())? & !(STATE_ALIGN as u64 - 1);
+    let frame_addr = (fp_addr
+        .checked_sub(size_of::<RtSigFrame>() as u64)
+        .ok_or(())?
+        & !0xF)
+        .checked_sub(8)
+        .ok_or(())?;
+
+    // Overflowing the alternate stack would write over whatever is below it
+    if (use_alt_stack || on_alt_stack) && !alt_stack.contains(frame_addr) {
+        return Err(());
+    }
+
+    let mut mcontext = SigContext::from_frame(regs);
+    mcontext.oldmask = saved_mask;
+    mcontext.fpstate = fp_addr;
+    if let SigSource::Fault { addr, .. } = source {
+        mcontext.trapno = TRAP_PAGE_FAULT;
+        mcontext.cr2 = addr;
+    }
+    let frame = RtSigFrame {
+        pretcode: action.restorer as u64,
+        uc: UContext {
+            uc_flags: 0,
+            uc_link: 0,
+            uc_stack: alt_stack.report(regs.rsp),
+            uc_mcontext: mcontext,
+            uc_sigmask: saved_mask,
+        },
+        info: SigInfo::new(signal, source),
+    };
+
+    crate::user::process::copy_to_user(fp_addr as usize, task.fpu.as_bytes()).map_err(|_| ())?;
+    crate::user::process::copy_to_user(frame_addr as usize, as_bytes(&frame)).map_err(|_| ())?;
+
+    // Block the action's mask, and the signal itself, while the handler runs
+    let mut mask = task.get_signal_mask() | action.mask;
+    if action.flags & (SA_NODEFER | SA_RESETHAND) == 0 {
+        mask |= sigbit(signal);
+    }
+    task.set_signal_mask(mask & !UNBLOCKABLE);
+    if action.flags & SA_RESETHAND != 0 {
+        task.signal_handlers.lock()[signal as usize] = SigAction::default();
+    }
+
+    regs.rip = handler;
+    regs.rsp = frame_addr;
+    regs.rdi = signal as u64;
+    if action.flags & SA_SIGINFO != 0 {
+        regs.rsi = frame_addr + core::mem::offset_of!(RtSigFrame, info) as u64;
+        regs.rdx = frame_addr + core::mem::offset_of!(RtSigFrame, uc) as u64;
+    } else {
+        regs.rsi = 0;
+        regs.rdx = 0;
+    }
+    regs.rax = 0;
+    regs.rflags &= !(RFLAGS_DF | RFLAGS_TF);
+    Ok(())
+}
+
+/// Run the handler of the current task's next pending signal
+///
+/// Called on the way back to user mode from a syscall, after the default
+/// actions have been taken. A syscall that returned `ERESTARTSYS` is
+/// restarted if no handler runs or the handler has `SA_RESTART`, and
+/// fails with EINTR otherwise; one that returned `ERESTARTNOHAND` is only
+/// restarted if no handler runs. A handler that cannot be entered kills
+/// the process with SIGSEGV.
+///
+/// After `rt_sigsuspend` the mask it replaced is restored, by
+/// `rt_sigreturn` if a handler runs and right away otherwise.
+///
+/// # Arguments
+/// * `regs` - User registers saved on syscall entry
+/// * `syscall_id` - Number of the syscall
+/// * `result` - Its return value
+///
+/// # Returns
+/// The value for RAX on return to user mode
+pub fn handle_signal(regs: &mut SyscallFrame, syscall_id: usize, result: isize) -> isize {
+    let task = match crate::sched::current_task_mut() {
+        Some(task) => task,
+        None => return result,
+    };
+    let next = super::next_deliverable(task, |action| {
+        matches!(action.handler, SigHandler::Custom(_))
+    });
+    let saved_mask = task.saved_sigmask.take();
+
+    // `int 0x80` is two bytes long; going back over it runs the syscall again
+    let restart = |regs: &mut SyscallFrame| {
+        regs.rip -= 2;
+        syscall_id as isize
+    };
+    let (signal, action) = match next {
+        Some(next) => next,
+        None => {
+            if let Some(mask) = saved_mask {
+                task.set_signal_mask(mask);
+            }
+            return match result {
+                ERESTARTSYS | ERESTARTNOHAND => restart(regs),
+                result => result,
+            };
+        }
+    };
+
+    let result = match result {
+        ERESTARTSYS if action.flags & SA_RESTART != 0 => restart(regs),
+        ERESTARTSYS | ERESTARTNOHAND => -1, // EINTR
+        result => result,
+    };
+    regs.rax = result as u64;
+
+    let saved_mask = saved_mask.unwrap_or(task.get_signal_mask());
+    enter_handler(task, regs, signal, &action, saved_mask);
+    0
+}
+
+/// Run the handler of the current task's next pending signal on the way
+/// back to user mode from an interrupt
+///
+/// Like `handle_signal`, but the interrupted code was not in a syscall, so
+/// there is nothing to restart and RAX is left alone.
+///
+/// # Arguments
+/// * `regs` - User registers saved on interrupt entry
+pub fn handle_interrupt_signal(regs: &mut SyscallFrame) {
+    let task = match crate::sched::current_task_mut() {
+        Some(task) => task,
+        None => return,
+    };
+    let next = super::next_deliverable(task, |action| {
+        matches!(action.handler, SigHandler::Custom(_))
+    });
+    if let Some((signal, action)) = next {
+        let saved_mask = task.get_signal_mask();
+        enter_handler(task, regs, signal, &action, saved_mask);
+    }
+}
+
+/// Dequeue `signal` and set up the frame for its handler, killing the
+/// process with SIGSEGV if the handler cannot be entered
+fn enter_handler(
+    task: &mut Task,
+    regs: &mut SyscallFrame,
+    signal: Signal,
+    action: &SigAction,
+    saved_mask: u64,
+) {
+    let source = super::dequeue_signal(task, signal);
+    if setup_signal_frame(task, regs, signal, action, source, saved_mask).is_err() {
+        let signal = super::signals::SIGSEGV;
+        crate::user::thread::exit_group(crate::user::process::WaitStatus::Signaled {
+            signal,
+            core_dumped: crate::user::coredump::dump_core(signal, regs),
+        });
+    }
+}
+
+/// Run the handler of a fault signal right away
+///
+/// For faults, which return to user mode without passing through the
+/// syscall path.
+///
+/// # Arguments
+/// * `regs` - User registers at the fault, changed to enter the handler
+/// * `signal` - Signal raised by the fault
+/// * `source` - The fault
+///
+/// # Returns
+/// false if the signal is blocked, has no handler or the handler cannot
+/// be entered; the caller then kills the process
+pub fn force_fault_signal(regs: &mut SyscallFrame, signal: Signal, source: SigSource) -> bool {
+    let task = match crate::sched::current_task_mut() {
+        Some(task) => task,
+        None => return false,
+    };
+    let action = task.signal_handlers.lock()[signal as usize];
+    if task.get_signal_mask() & sigbit(signal) != 0 {
+        return false;
+    }
+    let saved_mask = task.get_signal_mask();
+    matches!(action.handler, SigHandler::Custom(_))
+        && setup_signal_frame(task, regs, signal, &action, source, saved_mask).is_ok()
+}
+
+/// Undo a signal frame (`rt_sigreturn`)
+///
+/// The handler's `ret` into the restorer popped `pretcode`, so the frame
+/// starts 8 bytes below the current user stack pointer. The saved
+/// alternate stack is set again if the handler did not run on it.
+///
+/// # Arguments
+/// * `regs` - User registers saved on syscall entry, replaced by the
+///   saved context
+///
+/// # Returns
+/// The saved RAX, or Err if the frame cannot be read or is not valid
+pub fn restore_signal_frame(regs: &mut SyscallFrame) -> Result<u64, ()> {
+    let task = crate::sched::current_task_mut().ok_or(())?;
+    let frame_addr = regs.rsp.checked_sub(8).ok_or(())? as usize;
+
+    let mut buf = [0u8; size_of::<RtSigFrame>()];
+    crate::user::process::copy_from_user(&mut buf, frame_addr, size_of::<RtSigFrame>())
+        .map_err(|_| ())?;
+    let frame = unsafe { core::ptr::read_unaligned(buf.as_ptr() as *const RtSigFrame) };
+    let context = &frame.uc.uc_mcontext;
+
+    if context.rip >= USER_LIMIT as u64 || context.rsp >= USER_LIMIT as u64 {
+        return Err(());
+    }
+
+    if context.fpstate != 0 {
+        if context.fpstate % STATE_ALIGN as u64 != 0 {
+            return Err(());
+        }
+        let mut fpu = FpuState::new();
+        let len = fpu.as_bytes().len();
+        crate::user::process::copy_from_user(fpu.as_bytes_mut(), context.fpstate as usize, len)
+            .map_err(|_| ())?;
+        if !fpu.is_valid() {
+            return Err(());
+        }
+        fpu.restore();
+        task.fpu = fpu;
+    }
+
+    task.set_signal_mask(frame.uc.uc_sigmask & !UNBLOCKABLE);
+    // Refused while still on the alternate stack, which is not an error
+    let _ = set_alt_stack(task, &frame.uc.uc_stack, regs.rsp);
+    context.restore_to(regs);
+    Ok(context.rax)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal::{SEGV_MAPERR, SI_USER};
+
+    #[test]
+    fn test_frame_layout() {
+        // Sizes and offsets user space expects from the Linux ABI
+        assert_eq!(size_of::<SigInfo>(), 128);
+        assert_eq!(size_of::<SigContext>(), 256);
+        assert_eq!(size_of::<SignalStack>(), 24);
+        assert_eq!(size_of::<UContext>(), 304);
+        assert_eq!(core::mem::offset_of!(SigContext, fpstate), 184);
+        assert_eq!(core::mem::offset_of!(UContext, uc_mcontext), 40);
+        assert_eq!(core::mem::offset_of!(RtSigFrame, info), 312);
+    }
+
+    #[test]
+    fn test_siginfo_fields() {
+        let info = SigInfo::new(10, SigSource::User { pid: 42, uid: 1000 });
+        let bytes = as_bytes(&info);
+        assert_eq!(info.si_code, SI_USER);
+        assert_eq!(&bytes[16..20], &42i32.to_le_bytes());
+        assert_eq!(&bytes[20..24], &1000u32.to_le_bytes());
+
+        let fault = SigSource::Fault {
+            code: SEGV_MAPERR,
+            addr: 0xdead_b000,
+        };
+        let info = SigInfo::new(11, fault);
+        assert_eq!(&as_bytes(&info)[16..24], &0xdead_b000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_alt_stack_bounds() {
+        let stack = SignalStack {
+            ss_sp: 0x1000,
+            ss_flags: 0,
+            _pad: 0,
+            ss_size: 0x2000,
+        };
+        // The stack grows down from ss_sp + ss_size, which is on it
+        assert!(stack.contains(0x3000));
+        assert!(stack.contains(0x1001));
+        assert!(!stack.contains(0x1000));
+        assert!(!stack.contains(0x3001));
+        assert_eq!(stack.report(0x2000).ss_flags, SS_ONSTACK);
+        assert_eq!(stack.report(0x8000).ss_flags, 0);
+
+        let disabled = SignalStack::disabled();
+        assert!(!disabled.contains(0));
+        assert_eq!(disabled.report(0).ss_flags, SS_DISABLE);
+    }
+}
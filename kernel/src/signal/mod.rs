@@ -3,7 +3,10 @@
 //! This module implements POSIX-like signal handling for process management
 //! and job control.
 
+pub mod frame;
+pub mod queue;
 pub mod security;
+pub mod signalfd;
 
 /// Signal number type
 pub type Signal = u32;
@@ -87,8 +90,13 @@ pub mod signals {
     /// Bad system call
     pub const SIGSYS: Signal = 31;
 
-    /// Maximum signal number
-    pub const MAX_SIGNAL: Signal = 64;
+    /// First real-time signal
+    pub const SIGRTMIN: Signal = 34;
+    /// Last real-time signal
+    pub const SIGRTMAX: Signal = 64;
+
+    /// One past the highest signal number
+    pub const MAX_SIGNAL: Signal = 65;
 }
 
 /// Signal handler type
@@ -118,6 +126,10 @@ pub mod sa_flags {
     pub const SA_ONSTACK: u32 = 0x0800_0000;
     /// Restart syscalls if possible
     pub const SA_RESTART: u32 = 0x1000_0000;
+    /// Pass `siginfo` and `ucontext` to a three-argument handler
+    pub const SA_SIGINFO: u32 = 0x0000_0004;
+    /// `SigAction::restorer` is set (required for handlers)
+    pub const SA_RESTORER: u32 = 0x0400_0000;
 }
 
 /// Signal action structure
@@ -133,6 +145,8 @@ pub struct SigAction {
     /// Signal action flags
     #[allow(dead_code)]
     pub flags: u32,
+    /// Code the handler returns to, which calls `rt_sigreturn`
+    pub restorer: usize,
 }
 
 impl SigAction {
@@ -142,49 +156,136 @@ impl SigAction {
             handler: SigHandler::Default,
             mask: 0,
             flags: 0,
-        }
-    }
-
-    /// Create a signal action that ignores the signal
-    pub fn ignore() -> Self {
-        Self {
-            handler: SigHandler::Ignore,
-            mask: 0,
-            flags: 0,
+            restorer: 0,
         }
     }
 
     /// Create a signal action with a custom handler
     #[allow(dead_code)]
-    pub fn custom(handler_addr: usize, mask: u64, flags: u32) -> Self {
+    pub fn custom(handler_addr: usize, mask: u64, flags: u32, restorer: usize) -> Self {
         Self {
             handler: SigHandler::Custom(handler_addr),
             mask,
-            flags,
+            flags: flags | sa_flags::SA_RESTORER,
+            restorer,
         }
     }
 }
 
-/// Minimal representation of a signal frame that would be placed on the user stack.
-#[derive(Debug, Clone, Copy)]
-pub struct SignalStackFrame {
-    /// Signal being delivered
-    pub signal: Signal,
-    /// Address of the handler
-    pub handler: u64,
-    /// Stored user return address (placeholder until sigreturn is implemented)
-    pub return_address: u64,
-    /// Previous user stack pointer
-    pub saved_rsp: u64,
+/// `si_code`: sent by `kill`
+pub const SI_USER: i32 = 0;
+
+/// `si_code`: sent by `sigqueue`
+pub const SI_QUEUE: i32 = -1;
+
+/// `si_code`: sent by the kernel
+pub const SI_KERNEL: i32 = 0x80;
+
+/// `si_code` of SIGSEGV: address not mapped
+pub const SEGV_MAPERR: i32 = 1;
+
+/// `si_code` of SIGSEGV: access not permitted by the mapping
+pub const SEGV_ACCERR: i32 = 2;
+
+/// `si_code` of SIGTRAP: breakpoint (`int3`)
+pub const TRAP_BRKPT: i32 = 1;
+
+/// `si_code` of SIGTRAP: single step
+pub const TRAP_TRACE: i32 = 2;
+
+/// `si_code` of SIGSYS: syscall refused by a syscall filter
+pub const SYS_SECCOMP: i32 = 1;
+
+/// `si_arch` of SIGSYS (`AUDIT_ARCH_X86_64`)
+pub const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+
+/// Where a pending signal came from
+///
+/// Kept per task and signal until delivery, in a fixed table so signals
+/// can be sent from interrupt context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigSource {
+    /// Raised by the kernel
+    Kernel,
+    /// Sent with `kill` by process `pid` of user `uid`
+    User { pid: usize, uid: u32 },
+    /// Sent with `sigqueue` by process `pid` of user `uid`, carrying `value`
+    Queue { pid: usize, uid: u32, value: u64 },
+    /// A memory access at `addr` faulted (`SEGV_*` code)
+    Fault { code: i32, addr: u64 },
+    /// Child `pid` changed state (`CLD_*` code and wait status)
+    Child {
+        code: i32,
+        pid: usize,
+        uid: u32,
+        status: i32,
+    },
+    /// Syscall `nr` made at `addr` was refused by a syscall filter, which
+    /// returned `data`
+    Syscall { nr: usize, addr: u64, data: u16 },
 }
 
-/// Metadata tracked by the kernel while a custom handler is pending.
+/// Signal information for handlers (Linux `siginfo_t` layout)
+#[repr(C)]
 #[derive(Debug, Clone, Copy)]
-pub struct PendingSignalFrame {
-    /// Virtual address on the user stack where the frame would reside
-    pub frame_ptr: u64,
-    /// Frame contents (context snapshot)
-    pub frame: SignalStackFrame,
+pub struct SigInfo {
+    /// Signal number
+    pub si_signo: i32,
+    /// Error number (the filter's data for `SYS_SECCOMP`, otherwise 0)
+    pub si_errno: i32,
+    /// `SI_*`, `SEGV_*`, `CLD_*` or `SYS_*` code
+    pub si_code: i32,
+    _pad: i32,
+    /// `si_pid`/`si_uid` then `si_status` or `si_value`, `si_addr` for
+    /// faults, or `si_call_addr` then `si_syscall`/`si_arch` for SIGSYS
+    fields: [u64; 14],
+}
+
+impl SigInfo {
+    /// Build the information a handler of `signal` sees
+    pub fn new(signal: Signal, source: SigSource) -> Self {
+        let ids = |pid: usize, uid: u32| pid as u32 as u64 | (uid as u64) << 32;
+        let mut info = Self {
+            si_signo: signal as i32,
+            si_errno: 0,
+            si_code: SI_KERNEL,
+            _pad: 0,
+            fields: [0; 14],
+        };
+        match source {
+            SigSource::Kernel => {}
+            SigSource::User { pid, uid } => {
+                info.si_code = SI_USER;
+                info.fields[0] = ids(pid, uid);
+            }
+            SigSource::Queue { pid, uid, value } => {
+                info.si_code = SI_QUEUE;
+                info.fields[0] = ids(pid, uid);
+                info.fields[1] = value;
+            }
+            SigSource::Fault { code, addr } => {
+                info.si_code = code;
+                info.fields[0] = addr;
+            }
+            SigSource::Child {
+                code,
+                pid,
+                uid,
+                status,
+            } => {
+                info.si_code = code;
+                info.fields[0] = ids(pid, uid);
+                info.fields[1] = status as u32 as u64;
+            }
+            SigSource::Syscall { nr, addr, data } => {
+                info.si_errno = data as i32;
+                info.si_code = SYS_SECCOMP;
+                info.fields[0] = addr;
+                info.fields[1] = nr as u32 as u64 | (AUDIT_ARCH_X86_64 as u64) << 32;
+            }
+        }
+        info
+    }
 }
 
 /// Default signal actions
@@ -212,9 +313,8 @@ pub fn default_action(signal: Signal) -> DefaultAction {
         SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
         SIGCONT => DefaultAction::Continue,
         SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
-        SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV | SIGSYS => {
-            DefaultAction::Core
-        }
+        SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV | SIGSYS | SIGXCPU
+        | SIGXFSZ => DefaultAction::Core,
         _ => DefaultAction::Terminate,
     }
 }
@@ -224,8 +324,26 @@ pub fn is_catchable(signal: Signal) -> bool {
     signal != signals::SIGKILL && signal != signals::SIGSTOP
 }
 
+/// Check if a signal is a real-time signal, which is queued rather than
+/// merged with one already pending
+pub fn is_realtime(signal: Signal) -> bool {
+    (signals::SIGRTMIN..=signals::SIGRTMAX).contains(&signal)
+}
+
+/// Bit of a signal in a signal set (bit 0 is signal 1, as in Linux)
+pub const fn sigbit(signal: Signal) -> u64 {
+    1 << (signal - 1)
+}
+
+/// Lowest signal in a non-empty signal set
+pub fn lowest_signal(set: u64) -> Signal {
+    set.trailing_zeros() + 1
+}
+
+/// Signals that no mask can hold back
+pub const UNBLOCKABLE: u64 = sigbit(signals::SIGKILL) | sigbit(signals::SIGSTOP);
+
 /// Signal mask operations
-#[allow(dead_code)]
 pub mod sigmask {
     /// Block signals (add to mask)
     pub const SIG_BLOCK: i32 = 0;
@@ -302,8 +420,7 @@ pub fn send_signal_to_group(pgid: usize, signal: Signal) -> usize {
 
 /// Legacy function for compatibility
 ///
-/// Adds the signal to the task's pending signal set atomically.
-/// The signal will be delivered when the task returns to userspace.
+/// Sends a signal raised by the kernel; see `send_signal_info`.
 ///
 /// # Arguments
 /// * `task` - The task to send the signal to
@@ -312,6 +429,31 @@ pub fn send_signal_to_group(pgid: usize, signal: Signal) -> usize {
 /// # Returns
 /// Ok(()) if the signal was queued, Err if invalid signal number
 pub fn send_signal(task: &mut crate::sched::task::Task, signal: Signal) -> Result<(), ()> {
+    send_signal_info(task, signal, SigSource::Kernel)
+}
+
+/// Send a signal to a task, recording where it came from
+///
+/// Adds the signal to the task's pending signal set atomically. It is
+/// delivered when the task returns to userspace; a task blocked in an
+/// interruptible wait is woken so that it returns. A standard signal that
+/// is already pending keeps the source it was first sent with; real-time
+/// signals are queued, each with its own source.
+///
+/// # Arguments
+/// * `task` - The task to send the signal to
+/// * `signal` - The signal number to send
+/// * `source` - Sender or cause, reported to `SA_SIGINFO` handlers
+///
+/// # Returns
+/// Ok(()) if the signal was queued or discarded, Err if invalid signal number
+/// or the task's real-time signal queue is full
+pub fn send_signal_info(
+    task: &mut crate::sched::task::Task,
+    signal: Signal,
+    source: SigSource,
+) -> Result<(), ()> {
+    use core::sync::atomic::Ordering;
     use signals::*;
 
     // Validate signal number
@@ -319,168 +461,242 @@ pub fn send_signal(task: &mut crate::sched::task::Task, signal: Signal) -> Resul
         return Err(());
     }
 
+    // Job control acts when the signal is sent, even if it is ignored
+    match signal {
+        SIGCONT => {
+            for stop in [SIGSTOP, SIGTSTP, SIGTTIN, SIGTTOU] {
+                task.clear_pending_signal(stop);
+            }
+            crate::user::process::continue_process(task.pid, true);
+        }
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => task.clear_pending_signal(SIGCONT),
+        SIGKILL => crate::user::process::continue_process(task.pid, false),
+        _ => {}
+    }
+
     // SIGKILL and SIGSTOP cannot be blocked or ignored
-    if signal == SIGKILL || signal == SIGSTOP {
-        // These signals are always delivered immediately
-        task.add_pending_signal(signal);
+    let blocked =
+        is_catchable(signal) && task.signal_mask.load(Ordering::Acquire) & sigbit(signal) != 0;
+    let waited = task.waited_signals.load(Ordering::Acquire) & sigbit(signal) != 0;
+    if is_catchable(signal) && !blocked && !waited && is_ignored(task, signal) {
         return Ok(());
     }
 
-    // Check if signal is ignored
-    if signal < 64 {
-        let handler = &task.signal_handlers[signal as usize];
-        if matches!(handler.handler, SigHandler::Ignore) {
-            // Signal is ignored, don't queue it
-            return Ok(());
+    // Add signal to pending set
+    if is_realtime(signal) {
+        let mut queue = task.signal_queue.lock();
+        queue.push(signal, source)?;
+        task.add_pending_signal(signal);
+    } else {
+        let mut sources = task.signal_info.lock();
+        if task.pending_signals.load(Ordering::Acquire) & sigbit(signal) == 0 {
+            sources[signal as usize] = source;
         }
+        task.add_pending_signal(signal);
     }
 
-    // Add signal to pending set
-    if task.add_pending_signal(signal) {
-        Ok(())
-    } else {
-        Err(())
+    // Cut short a wait the signal can be delivered in, or is waited for
+    if (!blocked || waited) && task.kthread.is_none() {
+        if task.interruptible {
+            crate::sched::wake_task(task.id);
+        } else {
+            crate::sched::hrtimer::interrupt_sleep(task.id);
+        }
     }
+    Ok(())
 }
 
-/// Deliver pending signals to the current task
-///
-/// This function is called when returning to userspace from a syscall or interrupt.
-/// It checks for pending unblocked signals and delivers them according to their
-/// configured actions.
-///
-/// # Arguments
-/// * `task` - The task to deliver signals to
+/// Whether the action of a signal is to ignore it
 ///
-/// # Returns
-/// Some(signal_number) if a signal was delivered and needs special handling,
-/// None if no signals were delivered or they were handled normally
-#[allow(dead_code)]
-pub fn deliver_pending_signals(task: &mut crate::sched::task::Task) -> Option<Signal> {
-    use signals::*;
-
-    // Get the next pending unblocked signal
-    let signal = task.next_pending_signal()?;
-
-    // Clear the signal from pending set
-    task.clear_pending_signal(signal);
-
-    // SIGKILL and SIGSTOP have special handling
-    if signal == SIGKILL {
-        // SIGKILL always terminates (handled by caller)
-        return Some(SIGKILL);
+/// Such signals are discarded when sent unless the task blocks them.
+fn is_ignored(task: &crate::sched::task::Task, signal: Signal) -> bool {
+    match task.signal_handlers.lock()[signal as usize].handler {
+        SigHandler::Ignore => true,
+        SigHandler::Default => matches!(
+            default_action(signal),
+            DefaultAction::Ignore | DefaultAction::Continue
+        ),
+        SigHandler::Custom(_) => false,
     }
+}
 
-    if signal == SIGSTOP || signal == SIGTSTP || signal == SIGTTIN || signal == SIGTTOU {
-        // Stop signals (handled by caller)
-        return Some(signal);
+/// Block the current task in a wait that signals cut short
+///
+/// Follows the usual pattern: the caller has queued itself where its
+/// wakers look, under `guard`, with interrupts disabled. The guard is
+/// dropped before switching away. If a deliverable signal is already
+/// pending the task does not stay blocked.
+pub fn block_interruptible<G>(guard: G) {
+    use core::sync::atomic::{fence, Ordering};
+
+    let task = match crate::sched::current_task_mut() {
+        Some(task) => task,
+        None => return,
+    };
+    let task_id = task.id;
+    task.state = crate::sched::task::TaskState::Blocked;
+    task.interruptible = true;
+
+    // Either the sender sees `interruptible` or we see its pending bit
+    fence(Ordering::SeqCst);
+    let interrupted = has_wakeup_signal(task);
+    drop(guard);
+    if interrupted {
+        crate::sched::wake_task(task_id);
     }
+    crate::sched::schedule();
 
-    if signal == SIGCONT {
-        // Continue signal (handled by caller)
-        return Some(SIGCONT);
+    if let Some(task) = crate::sched::current_task_mut() {
+        task.interruptible = false;
     }
+}
 
-    // Get the signal handler
-    if signal >= 64 {
-        return None;
-    }
+/// Whether a pending signal ends an interruptible wait of `task`: one it
+/// can be delivered, or one it is waiting for
+pub(crate) fn has_wakeup_signal(task: &crate::sched::task::Task) -> bool {
+    use core::sync::atomic::Ordering;
 
-    let handler = task.signal_handlers[signal as usize];
-
-    match handler.handler {
-        SigHandler::Default => {
-            // Use default action
-            match default_action(signal) {
-                DefaultAction::Terminate | DefaultAction::Core => {
-                    // Terminate the process (handled by caller)
-                    return Some(signal);
-                }
-                DefaultAction::Ignore => {
-                    // Ignore the signal
-                    return None;
-                }
-                DefaultAction::Stop => {
-                    // Stop the process (handled by caller)
-                    return Some(signal);
-                }
-                DefaultAction::Continue => {
-                    // Continue the process (handled by caller)
-                    return Some(signal);
-                }
-            }
+    let waited = task.waited_signals.load(Ordering::Acquire);
+    task.next_pending_signal().is_some()
+        || task.pending_signals.load(Ordering::Acquire) & waited != 0
+}
+
+/// Wait for one of the signals in `set` to become pending
+///
+/// Used by `rt_sigtimedwait` and signalfd reads, for signals the caller
+/// normally blocks. The wait also ends when a deliverable signal arrives
+/// or `deadline` (monotonic ns) passes; without a deadline it can last
+/// forever.
+///
+/// # Returns
+/// The lowest such signal, taken off the pending set, with its source, or
+/// None if the wait ended without one
+pub fn wait_for_signal(set: u64, deadline: Option<u64>) -> Option<(Signal, SigSource)> {
+    use crate::sched::hrtimer;
+    use core::sync::atomic::Ordering;
+
+    let task = crate::sched::current_task()?;
+    let set = set & !UNBLOCKABLE;
+    task.waited_signals.store(set, Ordering::Release);
+
+    let found = loop {
+        let pending = task.pending_signals.load(Ordering::Acquire) & set;
+        if pending != 0 {
+            let signal = lowest_signal(pending);
+            break Some((signal, dequeue_signal(task, signal)));
         }
-        SigHandler::Ignore => {
-            // Signal is ignored
-            return None;
+        if task.next_pending_signal().is_some() {
+            break None;
         }
-        SigHandler::Custom(_handler_addr) => {
-            // Custom handler - needs to be invoked in userspace
-            // This requires setting up a signal frame on the user stack
-            // For now, return the signal so the caller can handle it
-            return Some(signal);
+        match deadline {
+            Some(deadline) if hrtimer::monotonic_ns() >= deadline => break None,
+            Some(deadline) => {
+                hrtimer::sleep_until_if(deadline, || (!has_wakeup_signal(task)).then_some(()));
+            }
+            None => x86_64::instructions::interrupts::without_interrupts(|| {
+                block_interruptible(());
+            }),
         }
-    }
+    };
+
+    task.waited_signals.store(0, Ordering::Release);
+    found
 }
 
-/// Setup signal handler frame on user stack
+/// Take a pending signal off a task
 ///
-/// This function prepares the user stack to invoke a signal handler.
-/// It saves the current context and sets up the stack so that when
-/// the task returns to userspace, it will execute the signal handler.
-///
-/// # Arguments
-/// * `task` - The task to setup the signal frame for
-/// * `signal` - The signal number being delivered
-/// * `handler_addr` - Address of the signal handler in userspace
+/// A real-time signal stays pending while more of it are queued.
 ///
 /// # Returns
-/// Ok(()) if the frame was setup successfully, Err if stack setup failed
-#[allow(dead_code)]
-pub fn setup_signal_frame(
-    task: &mut crate::sched::task::Task,
-    signal: Signal,
-    handler_addr: usize,
-) -> Result<(), ()> {
-    use crate::sched::task::MemoryRegionType;
-
-    if handler_addr == 0 || handler_addr >= crate::sched::task::USER_LIMIT {
-        return Err(());
-    }
-
-    // Prevent nested handler setup until we support multiple frames
-    if task.pending_signal_frame.is_some() {
-        return Err(());
+/// Where the signal came from
+pub fn dequeue_signal(task: &crate::sched::task::Task, signal: Signal) -> SigSource {
+    if is_realtime(signal) {
+        let mut queue = task.signal_queue.lock();
+        let (source, more) = queue.pop(signal).unwrap_or((SigSource::Kernel, false));
+        if !more {
+            task.clear_pending_signal(signal);
+        }
+        return source;
     }
 
-    // Resolve the effective user stack pointer
-    let current_rsp = if task.user_stack_pointer != 0 {
-        task.user_stack_pointer
-    } else {
-        task.memory_regions[..task.region_count]
-            .iter()
-            .filter_map(|region_opt| region_opt.as_ref())
-            .find(|region| region.region_type == MemoryRegionType::Stack)
-            .map(|region| region.end as u64)
-            .ok_or(())?
-    };
+    let sources = task.signal_info.lock();
+    task.clear_pending_signal(signal);
+    sources[signal as usize]
+}
 
-    let frame_size = core::mem::size_of::<SignalStackFrame>() as u64;
-    let mut new_rsp = current_rsp.checked_sub(frame_size).ok_or(())?;
-    new_rsp &= !0xF;
+/// Lowest deliverable pending signal whose action passes `select`
+///
+/// # Returns
+/// The signal and its action (the default one for SIGKILL and SIGSTOP)
+pub(crate) fn next_deliverable(
+    task: &crate::sched::task::Task,
+    select: impl Fn(&SigAction) -> bool,
+) -> Option<(Signal, SigAction)> {
+    use core::sync::atomic::Ordering;
+
+    let blocked = task.signal_mask.load(Ordering::Acquire) & !UNBLOCKABLE;
+    let mut deliverable = task.pending_signals.load(Ordering::Acquire) & !blocked;
+    while deliverable != 0 {
+        let signal = lowest_signal(deliverable);
+        deliverable &= deliverable - 1;
+
+        let action = if is_catchable(signal) {
+            task.signal_handlers.lock()[signal as usize]
+        } else {
+            SigAction::default()
+        };
+        if select(&action) {
+            return Some((signal, action));
+        }
+    }
+    None
+}
 
-    let frame = SignalStackFrame {
-        signal,
-        handler: handler_addr as u64,
-        return_address: current_rsp,
-        saved_rsp: current_rsp,
+/// Carry out the default action of the current task's pending signals
+///
+/// Called on the way back to user mode from a syscall or interrupt. Ignored
+/// signals are dropped, stop signals stop the whole process until SIGCONT
+/// and fatal signals end it. Signals with a handler stay pending. A thread
+/// whose process was stopped by another thread stops here too. `regs` are
+/// the registers saved on entry to the kernel, for a core dump.
+pub fn handle_default_actions(regs: &crate::sys::syscall::SyscallFrame) {
+    let task = match crate::sched::current_task() {
+        Some(task) => task,
+        None => return,
     };
 
-    task.pending_signal_frame = Some(PendingSignalFrame {
-        frame_ptr: new_rsp,
-        frame,
-    });
-    task.user_stack_pointer = new_rsp;
+    loop {
+        crate::user::process::park_while_stopped();
+
+        // Lowest deliverable signal that has no handler
+        let next = next_deliverable(task, |action| {
+            !matches!(action.handler, SigHandler::Custom(_))
+        });
+        let (signal, action) = match next {
+            Some(next) => next,
+            None => return,
+        };
+        dequeue_signal(task, signal);
+        if action.handler == SigHandler::Ignore {
+            continue;
+        }
 
-    Ok(())
+        match default_action(signal) {
+            DefaultAction::Ignore | DefaultAction::Continue => {}
+            DefaultAction::Stop => crate::user::process::stop_process(signal),
+            DefaultAction::Terminate => {
+                crate::user::thread::exit_group(crate::user::process::WaitStatus::Signaled {
+                    signal,
+                    core_dumped: false,
+                })
+            }
+            DefaultAction::Core => {
+                let core_dumped = crate::user::coredump::dump_core(signal, regs);
+                crate::user::thread::exit_group(crate::user::process::WaitStatus::Signaled {
+                    signal,
+                    core_dumped,
+                })
+            }
+        }
+    }
 }
@@ -0,0 +1,118 @@
+//! Queued Real-Time Signals
+//!
+//! Standard signals are a pending bit each, so a second one sent before
+//! the first is delivered is merged into it. Real-time signals
+//! (`SIGRTMIN..=SIGRTMAX`) are queued instead: every one sent is kept with
+//! its own source and value and delivered in the order it was sent. The
+//! pending bit of a real-time signal stays set while any of it is queued.
+//!
+//! Each task has a fixed queue so signals can be queued from interrupt
+//! context. Its size, `SIGQUEUE_MAX` (32), is the only limit on queued
+//! signals: it applies to each task separately and cannot be changed.
+//! There is no per-user `RLIMIT_SIGPENDING`.
+
+use super::{SigSource, Signal};
+
+/// Most real-time signals a task can have queued (fixed, per task)
+pub const SIGQUEUE_MAX: usize = 32;
+
+/// A queued real-time signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct QueuedSignal {
+    signal: Signal,
+    source: SigSource,
+}
+
+/// Real-time signals queued on a task, oldest first
+#[derive(Debug)]
+pub struct SigQueue {
+    entries: [QueuedSignal; SIGQUEUE_MAX],
+    len: usize,
+}
+
+impl SigQueue {
+    /// Create an empty queue
+    pub const fn new() -> Self {
+        Self {
+            entries: [QueuedSignal {
+                signal: 0,
+                source: SigSource::Kernel,
+            }; SIGQUEUE_MAX],
+            len: 0,
+        }
+    }
+
+    /// Queue `signal` behind any already queued
+    ///
+    /// # Returns
+    /// Err if the queue is full
+    pub fn push(&mut self, signal: Signal, source: SigSource) -> Result<(), ()> {
+        if self.len == SIGQUEUE_MAX {
+            return Err(());
+        }
+        self.entries[self.len] = QueuedSignal { signal, source };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Take the oldest queued instance of `signal`
+    ///
+    /// # Returns
+    /// Its source, and whether more of the signal remain queued; None if
+    /// none is queued
+    pub fn pop(&mut self, signal: Signal) -> Option<(SigSource, bool)> {
+        let index = self.entries[..self.len]
+            .iter()
+            .position(|entry| entry.signal == signal)?;
+        let source = self.entries[index].source;
+        self.entries.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+        let more = self.entries[index..self.len]
+            .iter()
+            .any(|entry| entry.signal == signal);
+        Some((source, more))
+    }
+
+    /// Drop every queued signal
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(value: u64) -> SigSource {
+        SigSource::Queue {
+            pid: 2,
+            uid: 0,
+            value,
+        }
+    }
+
+    #[test]
+    fn test_sigqueue_order() {
+        let mut queue = SigQueue::new();
+        queue.push(40, value(1)).unwrap();
+        queue.push(35, value(2)).unwrap();
+        queue.push(40, value(3)).unwrap();
+
+        assert_eq!(queue.pop(40), Some((value(1), true)));
+        assert_eq!(queue.pop(40), Some((value(3), false)));
+        assert_eq!(queue.pop(40), None);
+        assert_eq!(queue.pop(35), Some((value(2), false)));
+        assert_eq!(queue.pop(35), None);
+    }
+
+    #[test]
+    fn test_sigqueue_limit() {
+        let mut queue = SigQueue::new();
+        for n in 0..SIGQUEUE_MAX as u64 {
+            queue.push(34, value(n)).unwrap();
+        }
+        assert!(queue.push(34, value(0)).is_err());
+        queue.pop(34);
+        assert!(queue.push(34, value(0)).is_ok());
+    }
+}
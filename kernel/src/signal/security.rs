@@ -7,7 +7,8 @@
 use super::{signals, SigHandler, Signal};
 use crate::mm::paging::PageTableFlags;
 use crate::sched;
-use crate::sched::task::{Task, USER_LIMIT};
+use crate::sched::task::{Credentials, Task, USER_LIMIT};
+use crate::user::capability::Capabilities;
 
 /// Error types for signal security operations
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -22,8 +23,6 @@ pub enum SignalSecurityError {
     ProtectedProcess,
     /// Signal handler address is invalid
     InvalidHandler,
-    /// Session mismatch
-    SessionMismatch,
 }
 
 /// Result type for signal security operations
@@ -48,8 +47,8 @@ pub fn validate_signal_number(signal: Signal) -> SignalSecurityResult<()> {
 ///
 /// Permission rules:
 /// 1. Process can send signals to itself
-/// 2. Process can send signals to processes in same session (for job control)
-/// 3. TODO: Add UID-based permission checks when user management is implemented
+/// 2. Process can send job control signals within its own session
+/// 3. Otherwise the sender's credentials must allow it (see `creds_may_signal`)
 ///
 /// # Arguments
 /// * `sender` - Sending task
@@ -68,33 +67,32 @@ pub fn check_signal_permission(
         return Ok(());
     }
 
-    // For job control signals, check session membership
-    if is_job_control_signal(signal) {
-        if sender.sid == target.sid {
-            return Ok(());
-        }
-        return Err(SignalSecurityError::SessionMismatch);
-    }
-
-    // Allow root to signal anyone
-    if sender.creds.uid == 0 {
+    // Job control signals may cross UID boundaries within a session
+    if is_job_control_signal(signal) && sender.sid == target.sid {
         return Ok(());
     }
 
-    // Allow signaling between processes that share the same effective UID
-    if sender.creds.uid == target.creds.uid {
+    if creds_may_signal(&sender.creds, &target.creds) {
         return Ok(());
     }
 
-    // Allow signals within the same session (for compat with early userland)
-    if sender.sid == target.sid {
-        return Ok(());
-    }
-
-    // No permission
     Err(SignalSecurityError::PermissionDenied)
 }
 
+/// Whether a task with `sender` credentials may signal one with `target`
+///
+/// `CAP_KILL` may signal anyone. Otherwise the sender's real or effective UID
+/// must match the target's real or saved UID, so a setuid program that
+/// still runs on behalf of the user can be signalled by them.
+pub fn creds_may_signal(sender: &Credentials, target: &Credentials) -> bool {
+    if sender.capable(Capabilities::CAP_KILL) {
+        return true;
+    }
+    [sender.ruid, sender.uid]
+        .iter()
+        .any(|uid| *uid == target.ruid || *uid == target.suid)
+}
+
 /// Check if signal is a job control signal
 ///
 /// Job control signals can be sent across UID boundaries within the same session.
@@ -256,9 +254,8 @@ pub fn should_deliver_signal(task: &Task, signal: Signal) -> bool {
     }
 
     // Check if signal is blocked
-    let signal_bit = 1u64 << (signal - 1);
     let mask = task.signal_mask.load(Ordering::Relaxed);
-    (mask & signal_bit) == 0
+    (mask & super::sigbit(signal)) == 0
 }
 
 /// Audit log for signal operations
@@ -356,4 +353,24 @@ mod tests {
         assert!(!is_job_control_signal(signals::SIGTERM));
         assert!(!is_job_control_signal(signals::SIGHUP));
     }
+
+    #[test]
+    fn test_creds_may_signal() {
+        let root = Credentials::user(0, 0);
+        let alice = Credentials::user(1000, 1000);
+        let bob = Credentials::user(1001, 1001);
+        assert!(creds_may_signal(&root, &alice));
+        assert!(creds_may_signal(&alice, &alice));
+        assert!(!creds_may_signal(&alice, &bob));
+        assert!(!creds_may_signal(&alice, &root));
+
+        // A setuid-root program run by alice keeps her as real UID
+        let setuid = Credentials {
+            uid: 0,
+            suid: 0,
+            ..alice
+        };
+        assert!(creds_may_signal(&alice, &setuid));
+        assert!(!creds_may_signal(&bob, &setuid));
+    }
 }
@@ -0,0 +1,201 @@
+//! Signal File Descriptors
+//!
+//! A signalfd turns signals into data: reading it takes pending signals
+//! from a set (which the reader normally blocks) off the calling task and
+//! returns one `SignalfdSiginfo` record for each. It is readable whenever
+//! one of those signals is pending, so it can be polled next to other
+//! file descriptors.
+
+use super::{dequeue_signal, lowest_signal, SigSource, Signal, UNBLOCKABLE};
+use core::mem::size_of;
+use core::sync::atomic::Ordering;
+
+/// Record returned by a signalfd read (Linux `signalfd_siginfo` layout)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalfdSiginfo {
+    pub ssi_signo: u32,
+    pub ssi_errno: i32,
+    /// `SI_*`, `SEGV_*`, `CLD_*` or `SYS_*` code
+    pub ssi_code: i32,
+    /// Sending process, or child that changed state
+    pub ssi_pid: u32,
+    pub ssi_uid: u32,
+    pub ssi_fd: i32,
+    pub ssi_tid: u32,
+    pub ssi_band: u32,
+    pub ssi_overrun: u32,
+    pub ssi_trapno: u32,
+    /// Exit status or signal of a child
+    pub ssi_status: i32,
+    /// Value sent with `sigqueue`, as an integer and as a pointer
+    pub ssi_int: i32,
+    pub ssi_ptr: u64,
+    pub ssi_utime: u64,
+    pub ssi_stime: u64,
+    /// Faulting address
+    pub ssi_addr: u64,
+    pub ssi_addr_lsb: u16,
+    _pad2: u16,
+    /// Syscall refused by a syscall filter, its address and architecture
+    pub ssi_syscall: i32,
+    pub ssi_call_addr: u64,
+    pub ssi_arch: u32,
+    _pad: [u8; 28],
+}
+
+impl SignalfdSiginfo {
+    /// Describe `signal`, sent by `source`
+    pub fn new(signal: Signal, source: SigSource) -> Self {
+        let mut info = Self {
+            ssi_signo: signal,
+            ssi_errno: 0,
+            ssi_code: super::SI_KERNEL,
+            ssi_pid: 0,
+            ssi_uid: 0,
+            ssi_fd: 0,
+            ssi_tid: 0,
+            ssi_band: 0,
+            ssi_overrun: 0,
+            ssi_trapno: 0,
+            ssi_status: 0,
+            ssi_int: 0,
+            ssi_ptr: 0,
+            ssi_utime: 0,
+            ssi_stime: 0,
+            ssi_addr: 0,
+            ssi_addr_lsb: 0,
+            _pad2: 0,
+            ssi_syscall: 0,
+            ssi_call_addr: 0,
+            ssi_arch: 0,
+            _pad: [0; 28],
+        };
+        match source {
+            SigSource::Kernel => {}
+            SigSource::User { pid, uid } => {
+                info.ssi_code = super::SI_USER;
+                info.ssi_pid = pid as u32;
+                info.ssi_uid = uid;
+            }
+            SigSource::Queue { pid, uid, value } => {
+                info.ssi_code = super::SI_QUEUE;
+                info.ssi_pid = pid as u32;
+                info.ssi_uid = uid;
+                info.ssi_int = value as i32;
+                info.ssi_ptr = value;
+            }
+            SigSource::Fault { code, addr } => {
+                info.ssi_code = code;
+                info.ssi_addr = addr;
+            }
+            SigSource::Child {
+                code,
+                pid,
+                uid,
+                status,
+            } => {
+                info.ssi_code = code;
+                info.ssi_pid = pid as u32;
+                info.ssi_uid = uid;
+                info.ssi_status = status;
+            }
+            SigSource::Syscall { nr, addr, data } => {
+                info.ssi_errno = data as i32;
+                info.ssi_code = super::SYS_SECCOMP;
+                info.ssi_syscall = nr as i32;
+                info.ssi_call_addr = addr;
+                info.ssi_arch = super::AUDIT_ARCH_X86_64;
+            }
+        }
+        info
+    }
+}
+
+/// Errors from reading a signalfd
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalfdError {
+    /// The buffer cannot hold a single record
+    BufferTooSmall,
+    /// No signal is pending and the fd is non-blocking
+    WouldBlock,
+    /// Another signal arrived while waiting
+    Interrupted,
+}
+
+/// Signals of `mask` pending on the current task
+fn pending_in(mask: u64) -> u64 {
+    crate::sched::current_task().map_or(0, |task| {
+        task.pending_signals.load(Ordering::Acquire) & mask & !UNBLOCKABLE
+    })
+}
+
+/// Whether a read of a signalfd for `mask` would return a record now
+pub fn is_readable(mask: u64) -> bool {
+    pending_in(mask) != 0
+}
+
+/// Read the current task's pending signals of `mask`
+///
+/// Fills `buffer` with as many records as there are signals pending and
+/// room for. If none is pending, waits for one unless `nonblock` is set.
+///
+/// # Returns
+/// The number of bytes written
+pub fn read(mask: u64, buffer: &mut [u8], nonblock: bool) -> Result<usize, SignalfdError> {
+    let record = size_of::<SignalfdSiginfo>();
+    if buffer.len() < record {
+        return Err(SignalfdError::BufferTooSmall);
+    }
+    let task = crate::sched::current_task().ok_or(SignalfdError::Interrupted)?;
+
+    let mut count = 0;
+    while count + record <= buffer.len() {
+        let pending = pending_in(mask);
+        let (signal, source) = if pending != 0 {
+            let signal = lowest_signal(pending);
+            (signal, dequeue_signal(task, signal))
+        } else if count > 0 {
+            break;
+        } else if nonblock {
+            return Err(SignalfdError::WouldBlock);
+        } else {
+            super::wait_for_signal(mask, None).ok_or(SignalfdError::Interrupted)?
+        };
+
+        let info = SignalfdSiginfo::new(signal, source);
+        let bytes = unsafe {
+            core::slice::from_raw_parts(&info as *const SignalfdSiginfo as *const u8, record)
+        };
+        buffer[count..count + record].copy_from_slice(bytes);
+        count += record;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signalfd_siginfo_layout() {
+        assert_eq!(size_of::<SignalfdSiginfo>(), 128);
+        assert_eq!(core::mem::offset_of!(SignalfdSiginfo, ssi_status), 40);
+        assert_eq!(core::mem::offset_of!(SignalfdSiginfo, ssi_addr), 72);
+    }
+
+    #[test]
+    fn test_signalfd_siginfo_child() {
+        let source = SigSource::Child {
+            code: 1,
+            pid: 7,
+            uid: 1000,
+            status: 3,
+        };
+        let info = SignalfdSiginfo::new(17, source);
+        assert_eq!(info.ssi_signo, 17);
+        assert_eq!(info.ssi_code, 1);
+        assert_eq!(info.ssi_pid, 7);
+        assert_eq!(info.ssi_status, 3);
+    }
+}
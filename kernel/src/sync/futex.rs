@@ -0,0 +1,337 @@
+//! Futexes
+//!
+//! A futex is a 32-bit word in user memory that threads wait on. The fast
+//! path (an uncontended lock or an unneeded wake-up) stays in user space;
+//! the kernel only keeps a queue of waiters per word.
+//!
+//! Waiters are keyed by address space and user address, so threads sharing
+//! a VM meet on the same queue while unrelated processes using the same
+//! address do not.
+//!
+//! # Protocol
+//!
+//! - `futex_wait` reads the word under the futex lock and only queues the
+//!   caller if it still holds the expected value. A waker that changes the
+//!   word and then calls `futex_wake` takes the same lock, so the wake-up
+//!   cannot fall between the check and the sleep.
+//! - Wakers dequeue the waiters they wake. A waiter that finds itself still
+//!   queued after waking up timed out or was interrupted, and removes itself.
+//!
+//! # Lock Ordering
+//!
+//! The futex lock is taken before TASK_TABLE and the per-CPU runqueue
+//! locks. Tasks are only woken after it is released.
+
+use super::SpinLock;
+use crate::sched::hrtimer;
+use crate::sched::task::TaskId;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+
+/// Sleep while the word holds a value
+pub const FUTEX_WAIT: usize = 0;
+
+/// Wake waiters
+pub const FUTEX_WAKE: usize = 1;
+
+/// Wake waiters and move others to a second futex
+pub const FUTEX_REQUEUE: usize = 3;
+
+/// `FUTEX_REQUEUE` that first checks the word's value
+pub const FUTEX_CMP_REQUEUE: usize = 4;
+
+/// The futex is private to the process (accepted and ignored: keys always
+/// include the address space)
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// Futex operation errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FutexError {
+    /// The word did not hold the expected value (EAGAIN)
+    WouldBlock,
+    /// The timeout expired (ETIMEDOUT)
+    TimedOut,
+    /// The wait was cut short without a wake-up (EINTR)
+    Interrupted,
+    /// The word could not be read (EFAULT)
+    Fault,
+}
+
+/// Identifies a futex: address space and user address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct FutexKey {
+    /// PML4 of the address space
+    mm: u64,
+    /// User address of the word
+    addr: usize,
+}
+
+impl FutexKey {
+    /// Key of `addr` in the current address space
+    fn current(addr: usize) -> Self {
+        Self {
+            mm: crate::user::process::current_address_space() as u64,
+            addr,
+        }
+    }
+}
+
+/// Waiter queues, in FIFO order, of every futex with at least one waiter
+type FutexMap = BTreeMap<FutexKey, VecDeque<TaskId>>;
+
+/// Waiters of every futex with at least one
+static FUTEXES: SpinLock<FutexMap> = SpinLock::new(BTreeMap::new());
+
+/// Read a futex word of the current task
+fn read_word(addr: usize) -> Result<u32, FutexError> {
+    let mut bytes = [0u8; 4];
+    crate::user::process::copy_from_user(&mut bytes, addr, 4).map_err(|_| FutexError::Fault)?;
+    Ok(u32::from_ne_bytes(bytes))
+}
+
+/// Remove `task_id` from whatever queue it is on
+///
+/// # Returns
+/// true if it was still queued
+fn dequeue_waiter(task_id: TaskId) -> bool {
+    remove_waiter(&mut FUTEXES.lock(), task_id)
+}
+
+/// Remove `task_id` from whatever queue of `futexes` it is on
+fn remove_waiter(futexes: &mut FutexMap, task_id: TaskId) -> bool {
+    // Requeueing may have moved the task to another key
+    let key = futexes
+        .iter()
+        .find(|(_, waiters)| waiters.contains(&task_id))
+        .map(|(key, _)| *key);
+
+    match key {
+        Some(key) => {
+            if let Some(waiters) = futexes.get_mut(&key) {
+                waiters.retain(|&id| id != task_id);
+                if waiters.is_empty() {
+                    futexes.remove(&key);
+                }
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Take up to `count` waiters off a queue
+fn take_waiters(futexes: &mut FutexMap, key: FutexKey, count: usize) -> Vec<TaskId> {
+    let waiters = match futexes.get_mut(&key) {
+        Some(waiters) => waiters,
+        None => return Vec::new(),
+    };
+
+    let taken: Vec<TaskId> = waiters.drain(..count.min(waiters.len())).collect();
+    if waiters.is_empty() {
+        futexes.remove(&key);
+    }
+    taken
+}
+
+/// Take up to `wake` waiters off `key` and move up to `requeue` of the
+/// rest to the back of `key2`
+///
+/// # Returns
+/// The waiters to wake and the number moved
+fn requeue_waiters(
+    futexes: &mut FutexMap,
+    key: FutexKey,
+    wake: usize,
+    key2: FutexKey,
+    requeue: usize,
+) -> (Vec<TaskId>, usize) {
+    let woken = take_waiters(futexes, key, wake);
+    let moved = take_waiters(futexes, key, requeue);
+    if !moved.is_empty() {
+        futexes
+            .entry(key2)
+            .or_default()
+            .extend(moved.iter().copied());
+    }
+    (woken, moved.len())
+}
+
+/// Wait on the futex at `addr` as long as it holds `expected`
+///
+/// The caller must have checked that `addr` is an aligned user address.
+///
+/// # Arguments
+/// * `addr` - User address of the futex word
+/// * `expected` - Value the word must hold for the caller to sleep
+/// * `deadline` - Monotonic time (ns) to give up at, or None to wait forever
+///
+/// # Returns
+/// Ok(()) once woken by `futex_wake` or `futex_requeue`
+pub fn futex_wait(addr: usize, expected: u32, deadline: Option<u64>) -> Result<(), FutexError> {
+    let task_id = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => return Err(FutexError::Interrupted),
+    };
+    let key = FutexKey::current(addr);
+
+    // Queues the caller if the word still holds the expected value
+    let mut outcome = Ok(());
+    let mut enqueue = || {
+        let mut futexes = FUTEXES.lock();
+        match read_word(addr) {
+            Ok(value) if value == expected => {
+                futexes.entry(key).or_default().push_back(task_id);
+                Some(futexes)
+            }
+            Ok(_) => {
+                outcome = Err(FutexError::WouldBlock);
+                None
+            }
+            Err(e) => {
+                outcome = Err(e);
+                None
+            }
+        }
+    };
+
+    match deadline {
+        Some(deadline) => {
+            hrtimer::sleep_until_if(deadline, enqueue);
+        }
+        None => x86_64::instructions::interrupts::without_interrupts(|| {
+            if let Some(futexes) = enqueue() {
+                crate::signal::block_interruptible(futexes);
+            }
+        }),
+    }
+    outcome?;
+
+    if !dequeue_waiter(task_id) {
+        return Ok(());
+    }
+
+    match deadline {
+        Some(deadline) if hrtimer::monotonic_ns() >= deadline => Err(FutexError::TimedOut),
+        _ => Err(FutexError::Interrupted),
+    }
+}
+
+/// Wake up to `count` waiters of the futex at `addr`
+///
+/// # Returns
+/// Number of tasks woken
+pub fn futex_wake(addr: usize, count: usize) -> usize {
+    let woken = take_waiters(&mut FUTEXES.lock(), FutexKey::current(addr), count);
+    for &task_id in &woken {
+        crate::sched::wake_task(task_id);
+    }
+    woken.len()
+}
+
+/// Wake up to `wake` waiters of `addr` and move up to `requeue` of the rest
+/// to `addr2`
+///
+/// # Arguments
+/// * `addr` - User address of the futex to wake
+/// * `wake` - Maximum number of waiters to wake
+/// * `addr2` - User address of the futex to move waiters to
+/// * `requeue` - Maximum number of waiters to move
+/// * `expected` - For `FUTEX_CMP_REQUEUE`: value `addr` must hold
+///
+/// # Returns
+/// Number of tasks woken or moved
+pub fn futex_requeue(
+    addr: usize,
+    wake: usize,
+    addr2: usize,
+    requeue: usize,
+    expected: Option<u32>,
+) -> Result<usize, FutexError> {
+    let key = FutexKey::current(addr);
+    let key2 = FutexKey::current(addr2);
+
+    let (woken, moved) = {
+        let mut futexes = FUTEXES.lock();
+        if let Some(expected) = expected {
+            if read_word(addr)? != expected {
+                return Err(FutexError::WouldBlock);
+            }
+        }
+
+        requeue_waiters(&mut futexes, key, wake, key2, requeue)
+    };
+
+    for &task_id in &woken {
+        crate::sched::wake_task(task_id);
+    }
+    Ok(woken.len() + moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(addr: usize) -> FutexKey {
+        FutexKey { mm: 0x1000, addr }
+    }
+
+    /// Queue waiters the way `futex_wait` does
+    fn wait(futexes: &mut FutexMap, key: FutexKey, ids: &[TaskId]) {
+        for &id in ids {
+            futexes.entry(key).or_default().push_back(id);
+        }
+    }
+
+    #[test]
+    fn test_wake_in_fifo_order() {
+        let mut futexes = FutexMap::new();
+        wait(&mut futexes, key(0x10), &[1, 2, 3]);
+
+        assert_eq!(take_waiters(&mut futexes, key(0x10), 2), [1, 2]);
+        assert!(take_waiters(&mut futexes, key(0x20), 1).is_empty());
+        assert_eq!(take_waiters(&mut futexes, key(0x10), 5), [3]);
+        // Empty queues are dropped
+        assert!(futexes.is_empty());
+    }
+
+    #[test]
+    fn test_keys_include_address_space() {
+        let mut futexes = FutexMap::new();
+        let other = FutexKey {
+            mm: 0x2000,
+            addr: 0x10,
+        };
+        wait(&mut futexes, key(0x10), &[1]);
+        wait(&mut futexes, other, &[2]);
+
+        assert_eq!(take_waiters(&mut futexes, key(0x10), usize::MAX), [1]);
+        assert_eq!(take_waiters(&mut futexes, other, usize::MAX), [2]);
+    }
+
+    #[test]
+    fn test_requeue() {
+        let mut futexes = FutexMap::new();
+        wait(&mut futexes, key(0x10), &[1, 2, 3, 4]);
+        wait(&mut futexes, key(0x20), &[5]);
+
+        let (woken, moved) = requeue_waiters(&mut futexes, key(0x10), 1, key(0x20), 2);
+        assert_eq!(woken, [1]);
+        assert_eq!(moved, 2);
+        assert_eq!(futexes[&key(0x10)], [4]);
+        assert_eq!(futexes[&key(0x20)], [5, 2, 3]);
+    }
+
+    #[test]
+    fn test_timed_out_waiter_removes_itself() {
+        let mut futexes = FutexMap::new();
+        wait(&mut futexes, key(0x10), &[1, 2]);
+        requeue_waiters(&mut futexes, key(0x10), 0, key(0x20), 1);
+
+        // A waiter that was requeued is found under its new key
+        assert!(remove_waiter(&mut futexes, 1));
+        assert!(!futexes.contains_key(&key(0x20)));
+        // A waiter that was woken is no longer queued
+        assert_eq!(take_waiters(&mut futexes, key(0x10), 1), [2]);
+        assert!(!remove_waiter(&mut futexes, 2));
+    }
+}
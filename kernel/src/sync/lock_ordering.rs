@@ -11,21 +11,24 @@
 //! 2. **SESSION_TABLE** - Global session table
 //! 3. **PROCESS_GROUP_TABLE** - Global process group table
 //! 4. **PORT_MANAGER.table_lock** - Port creation/deletion
-//! 5. **TASK_TABLE** - Task table access
-//! 6. **SCHED** - Scheduler state
-//! 7. **Per-CPU runqueue locks** - Must be acquired in CPU ID order (lower ID first)
-//! 8. **Session locks** - Individual session state
-//! 9. **Process group locks** - Individual process group state
-//! 10. **Task locks** - Individual task state (implicit in get_task_mut)
-//! 11. **PTY pair locks** - Individual PTY pair operations
-//! 12. **Per-port locks** - Individual port operations
+//! 5. **FUTEXES** - Futex wait queues (`sync::futex`)
+//! 6. **CHILD_WAITERS** - Tasks blocked in wait, process stop and exit state (`user::process`)
+//! 7. **PIDS** - Task ID allocator
+//! 8. **TASK_TABLE** - Task table shards (one at a time)
+//! 9. **Per-CPU runqueue locks** - Must be acquired in CPU ID order (lower ID first)
+//! 10. **Session locks** - Individual session state
+//! 11. **Process group locks** - Individual process group state
+//! 12. **Task locks** - Individual task state (implicit in get_task_mut)
+//! 13. **PTY pair locks** - Individual PTY pair operations
+//! 14. **Per-port locks** - Individual port operations
 //!
 //! # Lock Ordering Rules
 //!
 //! ## Rule 1: Global before Per-Object
 //! Always acquire global locks (PTY_TABLE, SESSION_TABLE, PROCESS_GROUP_TABLE,
-//! PORT_MANAGER, TASK_TABLE, SCHED) before per-object locks (session locks,
-//! process group locks, task locks, PTY pair locks, port locks).
+//! PORT_MANAGER, FUTEXES, CHILD_WAITERS, PIDS, TASK_TABLE) before per-object
+//! locks (session locks, process group locks, task locks, PTY pair locks, port
+//! locks).
 //!
 //! ## Rule 2: CPU ID Ordering
 //! When acquiring multiple per-CPU runqueue locks (e.g., during task migration),
@@ -66,11 +69,9 @@
 //!
 //! ## Pattern 1: Task Creation
 //! ```rust,ignore
-//! let mut sched = SCHED.lock();
-//! let mut task_table = TASK_TABLE.lock();
+//! let task_id = pid::alloc_pid()?; // PIDS held only inside the call
 //! // ... create task ...
-//! drop(task_table);
-//! drop(sched);
+//! TASK_TABLE.insert(task_id, task_ptr); // Locks one shard
 //! enqueue_task(task_id, None); // Acquires per-CPU runqueue lock
 //! ```
 //!
@@ -135,7 +136,7 @@
 //! for pid in pg.iter() {
 //!     let task_table = TASK_TABLE.lock();
 //!     if let Some(task) = task_table.get_mut(pid) {
-//!         task.pending_signals.fetch_or(1 << signal, Ordering::SeqCst);
+//!         task.pending_signals.fetch_or(sigbit(signal), Ordering::SeqCst);
 //!     }
 //!     drop(task_table);
 //! }
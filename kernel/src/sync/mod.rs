@@ -1,8 +1,11 @@
+pub mod futex;
 pub mod lock_ordering;
+pub mod rtmutex;
 pub mod seqlock;
+pub mod waitqueue;
 /// Synchronization primitives for multi-core support
 /// This module provides spinlocks and other synchronization mechanisms
 /// required for safe concurrent access to shared data structures.
 mod spin;
 
-pub use spin::{SpinLock, SpinLockGuard};
+pub use spin::{IrqSpinLock, SpinLock, SpinLockGuard};
@@ -0,0 +1,330 @@
+//! Sleeping Mutex with Priority Inheritance
+//!
+//! `RtMutex` blocks the calling task instead of spinning, and lends the
+//! priority of its highest waiter to the current owner. Without that, a
+//! SCHED_FIFO task waiting on a lock held by a fair-class task could be
+//! stalled indefinitely by unrelated medium-priority work (priority
+//! inversion).
+//!
+//! # Protocol
+//!
+//! - Waiters are kept sorted by effective priority (FIFO within a level).
+//! - When a task blocks, its priority is propagated along the chain of
+//!   owners: the owner is boosted, and if that owner is itself blocked on
+//!   another `RtMutex`, so is that lock's owner, and so on.
+//! - A waiter sleeps with `hrtimer::sleep_until_if`, checking under the
+//!   wait-list lock that it still doesn't own the mutex.
+//! - Unlock hands the mutex directly to the top waiter, wakes it under the
+//!   wait-list lock, and drops the old owner's boost back to what its
+//!   remaining locks justify.
+//!
+//! # Lock Ordering
+//!
+//! A mutex's wait-list lock is taken before TASK_TABLE and the per-CPU
+//! runqueue locks, and never while holding them. Only one wait-list lock is
+//! held at a time.
+//!
+//! Must only be used from task context: blocking calls into the scheduler.
+
+use super::SpinLock;
+use crate::sched::task::TaskId;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+
+/// Maximum number of owners walked when propagating a boost
+const MAX_PI_CHAIN: usize = 16;
+
+/// Owner and wait list of an `RtMutex`
+struct RtMutexState {
+    /// Task holding the mutex
+    owner: Option<TaskId>,
+
+    /// Blocked tasks, highest effective priority first
+    waiters: Vec<TaskId>,
+}
+
+/// Type-independent part of an `RtMutex`
+///
+/// Tasks refer to the mutex they are blocked on, and the ones they hold,
+/// through this type.
+pub struct RtMutexCore {
+    state: SpinLock<RtMutexState>,
+}
+
+impl RtMutexCore {
+    const fn new() -> Self {
+        Self {
+            state: SpinLock::new(RtMutexState {
+                owner: None,
+                waiters: Vec::new(),
+            }),
+        }
+    }
+
+    /// Effective priority of the highest waiter (0 if none)
+    fn top_waiter_prio(&self) -> u8 {
+        let state = self.state.lock();
+        state.waiters.first().map_or(0, |&id| task_prio(id))
+    }
+}
+
+/// Effective priority of a task (0 if it doesn't exist or isn't real-time)
+fn task_prio(id: TaskId) -> u8 {
+    crate::sched::get_task_by_id(id).map_or(0, |task| task.rt.prio)
+}
+
+/// Insert a waiter behind every waiter of equal or higher priority
+fn insert_waiter(waiters: &mut Vec<TaskId>, id: TaskId) {
+    insert_by_prio(waiters, id, task_prio);
+}
+
+/// Insert `id` behind every entry of equal or higher priority by `prio`
+fn insert_by_prio(waiters: &mut Vec<TaskId>, id: TaskId, prio: impl Fn(TaskId) -> u8) {
+    let own = prio(id);
+    let pos = waiters
+        .iter()
+        .position(|&w| prio(w) < own)
+        .unwrap_or(waiters.len());
+    waiters.insert(pos, id);
+}
+
+/// Priority a task should inherit from the mutexes it holds
+fn inherited_prio(id: TaskId) -> u8 {
+    let task = match crate::sched::get_task_by_id(id) {
+        Some(task) => task,
+        None => return 0,
+    };
+
+    task.rt
+        .pi_held
+        .iter()
+        .map(|&core| unsafe { (*core).top_waiter_prio() })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Boost the owner chain starting at `owner` to at least `prio`
+fn propagate_boost(mut owner: TaskId, prio: u8) {
+    for _ in 0..MAX_PI_CHAIN {
+        let task = match crate::sched::get_task_mut(owner) {
+            Some(task) => task,
+            None => return,
+        };
+
+        if task.rt.prio >= prio {
+            return;
+        }
+
+        crate::sched::set_task_pi_prio(owner, prio);
+
+        // Follow the chain if the owner is itself waiting
+        let core = task.rt.pi_blocked_on;
+        if core.is_null() {
+            return;
+        }
+
+        let mut state = unsafe { (*core).state.lock() };
+
+        // Its position in that wait list changed with its priority
+        state.waiters.retain(|&w| w != owner);
+        insert_waiter(&mut state.waiters, owner);
+
+        owner = match state.owner {
+            Some(next) => next,
+            None => return,
+        };
+    }
+}
+
+/// A sleeping mutual exclusion lock with priority inheritance
+///
+/// # Example
+///
+/// ```rust,ignore
+/// static CAPTURE: RtMutex<Ring> = RtMutex::new(Ring::new());
+///
+/// let mut ring = CAPTURE.lock(); // may block; owner is boosted meanwhile
+/// ring.push(sample);
+/// ```
+pub struct RtMutex<T> {
+    core: RtMutexCore,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for RtMutex<T> {}
+unsafe impl<T: Send> Send for RtMutex<T> {}
+
+/// A guard that releases the `RtMutex` (and any boost it caused) on drop
+pub struct RtMutexGuard<'a, T> {
+    mutex: &'a RtMutex<T>,
+}
+
+impl<T> RtMutex<T> {
+    /// Create a new unlocked mutex
+    pub const fn new(data: T) -> Self {
+        Self {
+            core: RtMutexCore::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Acquire the mutex, blocking the current task until it is available
+    ///
+    /// Before the scheduler is running (no current task) this spins instead.
+    pub fn lock(&self) -> RtMutexGuard<'_, T> {
+        let core = &self.core as *const RtMutexCore;
+
+        let me = match crate::sched::get_current_task_info() {
+            Some((id, _)) => id,
+            None => {
+                while self.try_acquire(usize::MAX).is_err() {
+                    core::hint::spin_loop();
+                }
+                return RtMutexGuard { mutex: self };
+            }
+        };
+
+        // Take the mutex if it is free, or queue up for it
+        let owner = {
+            let mut state = self.core.state.lock();
+            match state.owner {
+                None => {
+                    state.owner = Some(me);
+                    drop(state);
+                    if let Some(task) = crate::sched::get_task_mut(me) {
+                        task.rt.pi_held.push(core);
+                    }
+                    return RtMutexGuard { mutex: self };
+                }
+                Some(owner) => {
+                    insert_waiter(&mut state.waiters, me);
+                    if let Some(task) = crate::sched::get_task_mut(me) {
+                        task.rt.pi_blocked_on = core;
+                    }
+                    owner
+                }
+            }
+        };
+
+        propagate_boost(owner, task_prio(me));
+
+        // Sleep until unlock hands the mutex over. The wait-list lock is
+        // held until the task is marked sleeping, so the handover's wakeup
+        // either finds it asleep or comes before the check; a wakeup for
+        // anything else (a signal) just sleeps again.
+        loop {
+            crate::sched::hrtimer::sleep_until_if(u64::MAX, || {
+                let state = self.core.state.lock();
+                (state.owner != Some(me)).then_some(state)
+            });
+            if self.core.state.lock().owner == Some(me) {
+                break;
+            }
+        }
+
+        if let Some(task) = crate::sched::get_task_mut(me) {
+            task.rt.pi_blocked_on = core::ptr::null();
+        }
+        RtMutexGuard { mutex: self }
+    }
+
+    /// Try to acquire the mutex without blocking
+    pub fn try_lock(&self) -> Option<RtMutexGuard<'_, T>> {
+        let me = crate::sched::get_current_task_info().map_or(usize::MAX, |(id, _)| id);
+        self.try_acquire(me).ok()?;
+
+        if let Some(task) = crate::sched::get_task_mut(me) {
+            task.rt.pi_held.push(&self.core);
+        }
+        Some(RtMutexGuard { mutex: self })
+    }
+
+    fn try_acquire(&self, me: TaskId) -> Result<(), ()> {
+        let mut state = self.core.state.lock();
+        if state.owner.is_some() {
+            return Err(());
+        }
+        state.owner = Some(me);
+        Ok(())
+    }
+
+    /// Check whether the mutex is currently held
+    pub fn is_locked(&self) -> bool {
+        self.core.state.lock().owner.is_some()
+    }
+
+    fn unlock(&self) {
+        let core = &self.core as *const RtMutexCore;
+
+        // Hand over to the top waiter; it inherits from whoever still
+        // waits. It is woken with the wait-list lock held, so the wakeup
+        // can't land on a later, unrelated sleep of that task.
+        let me = {
+            let mut state = self.core.state.lock();
+            let me = state.owner.take();
+            if !state.waiters.is_empty() {
+                let next = state.waiters.remove(0);
+                state.owner = Some(next);
+                let boost = state.waiters.first().map_or(0, |&id| task_prio(id));
+                if let Some(task) = crate::sched::get_task_mut(next) {
+                    task.rt.pi_held.push(core);
+                    if boost > task.rt.pi_prio {
+                        crate::sched::set_task_pi_prio(next, boost);
+                    }
+                }
+                crate::sched::wake_task(next);
+            }
+            me
+        };
+
+        // Drop the boost this mutex lent us
+        if let Some(me) = me {
+            if let Some(task) = crate::sched::get_task_mut(me) {
+                task.rt.pi_held.retain(|&held| held != core);
+                if task.rt.pi_prio > 0 {
+                    crate::sched::set_task_pi_prio(me, inherited_prio(me));
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T> Deref for RtMutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T> DerefMut for RtMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T> Drop for RtMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waiters_sorted_by_prio() {
+        let prio = |id: TaskId| [0, 10, 50, 10, 90][id];
+        let mut waiters = Vec::new();
+        for id in 1..5 {
+            insert_by_prio(&mut waiters, id, prio);
+        }
+        // Highest first, FIFO among equals
+        assert_eq!(waiters, [4, 2, 1, 3]);
+
+        insert_by_prio(&mut waiters, 0, prio);
+        assert_eq!(waiters.last(), Some(&0));
+    }
+}
@@ -0,0 +1,150 @@
+//! Wait Queues
+//!
+//! A wait queue lists the tasks waiting for something to change on an
+//! object (data arriving in a pipe, a PTY being hung up, ...). The code
+//! that changes the object calls `wake_all` afterwards.
+//!
+//! A task can wait on several queues at once, as `poll` does, through a
+//! single `Waiter` added to each of them.
+//!
+//! # Protocol
+//!
+//! - The waiter is added to its queues before it checks the condition,
+//!   and calls `Waiter::reset` before each check.
+//! - `Waiter::wait` only sleeps if no wake-up came in since the reset. A
+//!   waker marks the waiter woken under the waiter's lock, which is held
+//!   until the task is asleep, so the wake-up cannot be lost.
+//!
+//! # Lock Ordering
+//!
+//! A waiter's lock is taken after the queue locks and before TASK_TABLE
+//! and the per-CPU runqueue locks.
+
+use super::SpinLock;
+use crate::sched::hrtimer;
+use crate::sched::task::TaskId;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// A task waiting on one or more wait queues
+pub struct Waiter {
+    /// The waiting task
+    task: TaskId,
+    /// Set by a wake-up since the last `reset`
+    woken: SpinLock<bool>,
+}
+
+impl Waiter {
+    /// Create a waiter for `task`
+    pub fn new(task: TaskId) -> Arc<Self> {
+        Arc::new(Self {
+            task,
+            woken: SpinLock::new(false),
+        })
+    }
+
+    /// Forget earlier wake-ups, before checking the condition again
+    pub fn reset(&self) {
+        *self.woken.lock() = false;
+    }
+
+    /// Sleep until woken, `deadline` (monotonic ns) passes or a signal
+    /// arrives
+    ///
+    /// Returns at once if a wake-up already came in since the last `reset`.
+    /// Must be called by the waiting task.
+    pub fn wait(&self, deadline: Option<u64>) {
+        let prepare = || {
+            let woken = self.woken.lock();
+            (!*woken).then_some(woken)
+        };
+        match deadline {
+            Some(deadline) => {
+                // `block_interruptible` makes the same check for untimed waits
+                hrtimer::sleep_until_if(deadline, || {
+                    let woken = prepare()?;
+                    let task = crate::sched::current_task()?;
+                    (!crate::signal::has_wakeup_signal(task)).then_some(woken)
+                });
+            }
+            None => x86_64::instructions::interrupts::without_interrupts(|| {
+                if let Some(woken) = prepare() {
+                    crate::signal::block_interruptible(woken);
+                }
+            }),
+        }
+    }
+
+    /// Mark the waiter woken and wake its task
+    fn wake(&self) {
+        let mut woken = self.woken.lock();
+        *woken = true;
+        crate::sched::wake_task(self.task);
+    }
+}
+
+/// Tasks waiting for an object to change
+pub struct WaitQueue {
+    waiters: SpinLock<Vec<Arc<Waiter>>>,
+}
+
+impl WaitQueue {
+    /// Create an empty wait queue
+    pub const fn new() -> Self {
+        Self {
+            waiters: SpinLock::new(Vec::new()),
+        }
+    }
+
+    /// Add `waiter` to the queue
+    pub fn add(&self, waiter: &Arc<Waiter>) {
+        self.waiters.lock().push(waiter.clone());
+    }
+
+    /// Remove `waiter` from the queue, if it is still there
+    pub fn remove(&self, waiter: &Arc<Waiter>) {
+        self.waiters.lock().retain(|w| !Arc::ptr_eq(w, waiter));
+    }
+
+    /// Wake every waiter
+    ///
+    /// Waiters stay queued until they remove themselves, so they also see
+    /// later wake-ups.
+    pub fn wake_all(&self) {
+        for waiter in self.waiters.lock().iter() {
+            waiter.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_and_remove() {
+        let queue = WaitQueue::new();
+        let first = Waiter::new(1);
+        let second = Waiter::new(1);
+        queue.add(&first);
+        queue.add(&second);
+        assert_eq!(queue.waiters.lock().len(), 2);
+
+        // Waiters are told apart by identity, not by task
+        queue.remove(&first);
+        assert!(Arc::ptr_eq(&queue.waiters.lock()[0], &second));
+        queue.remove(&first);
+        assert_eq!(queue.waiters.lock().len(), 1);
+        queue.remove(&second);
+        assert!(queue.waiters.lock().is_empty());
+    }
+
+    #[test]
+    fn test_reset_forgets_wake_up() {
+        let waiter = Waiter::new(1);
+        assert!(!*waiter.woken.lock());
+        *waiter.woken.lock() = true;
+        waiter.reset();
+        assert!(!*waiter.woken.lock());
+    }
+}
@@ -148,7 +148,7 @@ pub struct Port {
     pub queue: MessageQueue,
 
     /// Tasks blocked waiting for messages (FIFO wake policy)
-    pub blocked_tasks: TaskQueue,
+    blocked_tasks: TaskQueue,
 
     /// Spinlock protecting port operations
     pub lock: Mutex<()>,
@@ -4,8 +4,11 @@
 //! It provides syscall entry point, dispatcher, and handler functions.
 
 use crate::sched::task::USER_LIMIT;
+use crate::sync::waitqueue::WaitQueue;
 use crate::sync::SpinLock;
 use crate::sys::METRICS;
+use crate::user::capability::Capabilities;
+use crate::user::process::{ProcessError, WaitStatus};
 use crate::{serial_print, serial_println};
 use core::sync::atomic::AtomicUsize;
 
@@ -74,6 +77,7 @@ pub extern "C" fn syscall_entry() {
         "mov rsi, [rsp + 80]",    // arg1 (original RDI)
         "mov rdx, [rsp + 88]",    // arg2 (original RSI)
         "mov rcx, [rsp + 96]",    // arg3 (original RDX)
+        "mov r8, rsp",            // saved registers (SyscallFrame)
 
         // Call the dispatcher
         "call {dispatcher}",
@@ -111,17 +115,178 @@ pub extern "C" fn syscall_entry() {
     )
 }
 
+/// User registers saved by `syscall_entry`
+///
+/// Fields are in stack order (lowest address first), so a pointer to the
+/// top of the saved registers is a pointer to this struct. The last five
+/// words are the frame pushed by the CPU on entry.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub rflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+}
+
+/// Internal return value of a syscall cut short by a signal
+///
+/// Never seen by user space: the syscall is restarted, or fails with
+/// EINTR if a handler without `SA_RESTART` runs.
+pub const ERESTARTSYS: isize = -512;
+
+/// Like `ERESTARTSYS`, but the syscall fails with EINTR whenever a handler
+/// runs, `SA_RESTART` or not
+pub const ERESTARTNOHAND: isize = -514;
+
 /// Wrapper for syscall_dispatcher to match calling convention
 ///
 /// This function converts the register arguments to Rust function arguments.
+/// The saved registers are published in `Task::syscall_frame` while the
+/// syscall runs (for `clone` and the fourth to sixth arguments). A thread
+/// whose group is exiting leaves here instead of returning to user mode,
+/// and pending signals are acted on before the return (interrupts make the
+/// same checks, see `interrupt_exit_to_user`). A traced task may
+/// stop for its tracer on the way in and out (see `user::ptrace`). The
+/// time before and inside the syscall is charged as user and system time.
 #[no_mangle]
 extern "C" fn syscall_dispatcher_wrapper(
     syscall_id: usize,
     arg1: usize,
     arg2: usize,
     arg3: usize,
+    frame: *mut SyscallFrame,
 ) -> isize {
-    syscall_dispatcher(syscall_id, arg1, arg2, arg3)
+    crate::sched::cputime::syscall_enter();
+    if let Some(task) = crate::sched::current_task_mut() {
+        task.syscall_frame = frame;
+    }
+
+    let regs = unsafe { &mut *frame };
+    let (syscall_id, arg1, arg2, arg3) = match crate::user::ptrace::syscall_entry(regs, syscall_id)
+    {
+        // The tracer may have changed the syscall and its arguments
+        Some(id) => (id, regs.rdi as usize, regs.rsi as usize, regs.rdx as usize),
+        None => (syscall_id, arg1, arg2, arg3),
+    };
+
+    let result = syscall_dispatcher(syscall_id, arg1, arg2, arg3);
+
+    if let Some(task) = crate::sched::current_task_mut() {
+        task.syscall_frame = core::ptr::null_mut();
+    }
+    exit_if_group_exiting();
+    let result = crate::user::ptrace::syscall_exit(regs, result);
+    crate::user::ptrace::report_signals(regs);
+    crate::signal::handle_default_actions(regs);
+
+    let result = crate::signal::frame::handle_signal(regs, syscall_id, result);
+    crate::sched::cputime::syscall_exit();
+    result
+}
+
+/// End the current thread if its thread group is exiting
+fn exit_if_group_exiting() {
+    let status = crate::sched::current_task()
+        .and_then(|task| task.thread_group.as_ref())
+        .and_then(|group| group.exit_status());
+    if let Some(status) = status {
+        crate::user::thread::exit_thread(status);
+    }
+}
+
+/// Checks on the way back to user mode from an interrupt
+///
+/// The timer and reschedule interrupt entries call this after their
+/// handler, with the interrupted registers in `SyscallFrame` order. If
+/// they return to user mode, the thread does what it would on return from
+/// a syscall: it exits with its group, stops for its tracer, carries out
+/// default signal actions and enters a signal handler. So a thread that
+/// never makes a syscall still sees SIGKILL, SIGSTOP, handlers and
+/// `exit_group` within a tick, or at once when kicked with an IPI.
+pub(crate) extern "C" fn interrupt_exit_to_user(frame: *mut SyscallFrame) {
+    let regs = unsafe { &mut *frame };
+    if regs.cs & 3 != 3 {
+        return;
+    }
+
+    // User code holds no task references
+    crate::sched::rcu::quiescent(crate::arch::x86_64::smp::percpu::percpu_current().id);
+    exit_if_group_exiting();
+    crate::user::ptrace::report_signals(regs);
+    crate::signal::handle_default_actions(regs);
+    crate::signal::frame::handle_interrupt_signal(regs);
+}
+
+/// Return to user mode from a newly created thread
+///
+/// `Task::set_user_entry` leaves this as the return address of the new
+/// task's first `context_switch`, with a `SyscallFrame` right above it.
+#[unsafe(naked)]
+pub(crate) extern "C" fn ret_to_user() -> ! {
+    core::arch::naked_asm!(
+        "call {finish_task_switch}",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbp",
+        "pop rbx",
+        "pop r11",
+        "pop r10",
+        "pop r9",
+        "pop r8",
+        "pop rdi",
+        "pop rsi",
+        "pop rdx",
+        "pop rcx",
+        "pop rax",
+        "iretq",
+        finish_task_switch = sym crate::sched::finish_task_switch,
+    )
+}
+
+/// Apply the current task's syscall filter
+///
+/// # Returns
+/// None if the syscall may go ahead, or the result it fails with
+#[inline]
+fn filter_syscall(syscall_id: usize, arg1: usize, arg2: usize, arg3: usize) -> Option<isize> {
+    if crate::sched::current_task()?.seccomp.is_none() {
+        return None;
+    }
+    let (arg4, arg5, arg6) = extra_syscall_args().unwrap_or_default();
+    let args = [arg1, arg2, arg3, arg4, arg5, arg6].map(|arg| arg as u64);
+    crate::user::seccomp::filter_syscall(syscall_id, &args)
+}
+
+/// Fourth to sixth syscall arguments (R10, R8, R9) of the current syscall
+///
+/// # Returns
+/// None outside of an `int 0x80` syscall
+fn extra_syscall_args() -> Option<(usize, usize, usize)> {
+    let frame = crate::sched::current_task()?.syscall_frame;
+    if frame.is_null() {
+        return None;
+    }
+    let frame = unsafe { &*frame };
+    Some((frame.r10 as usize, frame.r8 as usize, frame.r9 as usize))
 }
 
 /// Syscall numbers
@@ -175,12 +340,76 @@ pub const SYS_FDATASYNC: usize = 46;
 pub const SYS_MOUNT: usize = 47;
 pub const SYS_UMOUNT: usize = 48;
 pub const SYS_GET_MOUNT_INFO: usize = 49;
+pub const SYS_NICE: usize = 50;
+pub const SYS_SETPRIORITY: usize = 51;
+pub const SYS_GETPRIORITY: usize = 52;
+pub const SYS_SCHED_SETSCHEDULER: usize = 53;
+pub const SYS_SCHED_GETSCHEDULER: usize = 54;
+pub const SYS_SCHED_GETPARAM: usize = 55;
+pub const SYS_NANOSLEEP: usize = 56;
+pub const SYS_CLOCK_GETTIME: usize = 57;
+pub const SYS_TIMER_CREATE: usize = 58;
+pub const SYS_TIMER_SETTIME: usize = 59;
+pub const SYS_TIMER_GETTIME: usize = 60;
+pub const SYS_TIMER_DELETE: usize = 61;
+pub const SYS_TIMER_GETOVERRUN: usize = 62;
+pub const SYS_SCHED_SETAFFINITY: usize = 63;
+pub const SYS_SCHED_GETAFFINITY: usize = 64;
+pub const SYS_CPUSET_CREATE: usize = 65;
+pub const SYS_CPUSET_SETCPUS: usize = 66;
+pub const SYS_CPUSET_ATTACH: usize = 67;
+pub const SYS_CPUSET_DESTROY: usize = 68;
+pub const SYS_CLONE: usize = 69;
+pub const SYS_ARCH_PRCTL: usize = 70;
+pub const SYS_FUTEX: usize = 71;
+pub const SYS_GETTID: usize = 72;
+pub const SYS_EXIT_GROUP: usize = 73;
+pub const SYS_WAIT4: usize = 74;
+pub const SYS_WAITID: usize = 75;
+pub const SYS_RT_SIGRETURN: usize = 76;
+pub const SYS_RT_SIGPROCMASK: usize = 77;
+pub const SYS_RT_SIGPENDING: usize = 78;
+pub const SYS_RT_SIGSUSPEND: usize = 79;
+pub const SYS_RT_SIGTIMEDWAIT: usize = 80;
+pub const SYS_SIGALTSTACK: usize = 81;
+pub const SYS_SIGNALFD: usize = 82;
+pub const SYS_POLL: usize = 83;
+pub const SYS_SIGQUEUE: usize = 84;
+pub const SYS_PTRACE: usize = 85;
+pub const SYS_GETUID: usize = 86;
+pub const SYS_GETEUID: usize = 87;
+pub const SYS_GETGID: usize = 88;
+pub const SYS_GETEGID: usize = 89;
+pub const SYS_SETUID: usize = 90;
+pub const SYS_SETGID: usize = 91;
+pub const SYS_SETREUID: usize = 92;
+pub const SYS_SETREGID: usize = 93;
+pub const SYS_SETRESUID: usize = 94;
+pub const SYS_SETRESGID: usize = 95;
+pub const SYS_GETRESUID: usize = 96;
+pub const SYS_GETRESGID: usize = 97;
+pub const SYS_GETGROUPS: usize = 98;
+pub const SYS_SETGROUPS: usize = 99;
+pub const SYS_CAPGET: usize = 100;
+pub const SYS_CAPSET: usize = 101;
+pub const SYS_PRCTL: usize = 102;
+pub const SYS_GETRLIMIT: usize = 103;
+pub const SYS_SETRLIMIT: usize = 104;
+pub const SYS_PRLIMIT: usize = 105;
+pub const SYS_MMAP: usize = 106;
+pub const SYS_MUNMAP: usize = 107;
+pub const SYS_MPROTECT: usize = 108;
+pub const SYS_MKDIR: usize = 109;
+pub const SYS_UNLINK: usize = 110;
+pub const SYS_SYMLINK: usize = 111;
 
 static NEXT_FAKE_PID: AtomicUsize = AtomicUsize::new(2000);
 
 /// Syscall dispatcher
 ///
-/// Routes syscall ID to appropriate handler and increments metrics.
+/// Routes syscall ID to appropriate handler and increments metrics. A
+/// task's syscall filter, if it has one, is applied first (see
+/// `user::seccomp`).
 ///
 /// # Arguments
 /// * `syscall_id` - Syscall number (from RAX)
@@ -204,6 +433,10 @@ pub extern "C" fn syscall_dispatcher(
     arg2: usize,
     arg3: usize,
 ) -> isize {
+    if let Some(result) = filter_syscall(syscall_id, arg1, arg2, arg3) {
+        return result;
+    }
+
     // Get current task ID for logging
     let task_id = match crate::sched::get_current_task_info() {
         Some((id, _)) => id,
@@ -262,6 +495,68 @@ pub extern "C" fn syscall_dispatcher(
         SYS_MOUNT => "SYS_MOUNT",
         SYS_UMOUNT => "SYS_UMOUNT",
         SYS_GET_MOUNT_INFO => "SYS_GET_MOUNT_INFO",
+        SYS_NICE => "SYS_NICE",
+        SYS_SETPRIORITY => "SYS_SETPRIORITY",
+        SYS_GETPRIORITY => "SYS_GETPRIORITY",
+        SYS_SCHED_SETSCHEDULER => "SYS_SCHED_SETSCHEDULER",
+        SYS_SCHED_GETSCHEDULER => "SYS_SCHED_GETSCHEDULER",
+        SYS_SCHED_GETPARAM => "SYS_SCHED_GETPARAM",
+        SYS_NANOSLEEP => "SYS_NANOSLEEP",
+        SYS_CLOCK_GETTIME => "SYS_CLOCK_GETTIME",
+        SYS_TIMER_CREATE => "SYS_TIMER_CREATE",
+        SYS_TIMER_SETTIME => "SYS_TIMER_SETTIME",
+        SYS_TIMER_GETTIME => "SYS_TIMER_GETTIME",
+        SYS_TIMER_DELETE => "SYS_TIMER_DELETE",
+        SYS_TIMER_GETOVERRUN => "SYS_TIMER_GETOVERRUN",
+        SYS_SCHED_SETAFFINITY => "SYS_SCHED_SETAFFINITY",
+        SYS_SCHED_GETAFFINITY => "SYS_SCHED_GETAFFINITY",
+        SYS_CPUSET_CREATE => "SYS_CPUSET_CREATE",
+        SYS_CPUSET_SETCPUS => "SYS_CPUSET_SETCPUS",
+        SYS_CPUSET_ATTACH => "SYS_CPUSET_ATTACH",
+        SYS_CPUSET_DESTROY => "SYS_CPUSET_DESTROY",
+        SYS_CLONE => "SYS_CLONE",
+        SYS_ARCH_PRCTL => "SYS_ARCH_PRCTL",
+        SYS_FUTEX => "SYS_FUTEX",
+        SYS_GETTID => "SYS_GETTID",
+        SYS_EXIT_GROUP => "SYS_EXIT_GROUP",
+        SYS_WAIT4 => "SYS_WAIT4",
+        SYS_WAITID => "SYS_WAITID",
+        SYS_RT_SIGRETURN => "SYS_RT_SIGRETURN",
+        SYS_RT_SIGPROCMASK => "SYS_RT_SIGPROCMASK",
+        SYS_RT_SIGPENDING => "SYS_RT_SIGPENDING",
+        SYS_RT_SIGSUSPEND => "SYS_RT_SIGSUSPEND",
+        SYS_RT_SIGTIMEDWAIT => "SYS_RT_SIGTIMEDWAIT",
+        SYS_SIGALTSTACK => "SYS_SIGALTSTACK",
+        SYS_SIGNALFD => "SYS_SIGNALFD",
+        SYS_POLL => "SYS_POLL",
+        SYS_SIGQUEUE => "SYS_SIGQUEUE",
+        SYS_PTRACE => "SYS_PTRACE",
+        SYS_GETUID => "SYS_GETUID",
+        SYS_GETEUID => "SYS_GETEUID",
+        SYS_GETGID => "SYS_GETGID",
+        SYS_GETEGID => "SYS_GETEGID",
+        SYS_SETUID => "SYS_SETUID",
+        SYS_SETGID => "SYS_SETGID",
+        SYS_SETREUID => "SYS_SETREUID",
+        SYS_SETREGID => "SYS_SETREGID",
+        SYS_SETRESUID => "SYS_SETRESUID",
+        SYS_SETRESGID => "SYS_SETRESGID",
+        SYS_GETRESUID => "SYS_GETRESUID",
+        SYS_GETRESGID => "SYS_GETRESGID",
+        SYS_GETGROUPS => "SYS_GETGROUPS",
+        SYS_SETGROUPS => "SYS_SETGROUPS",
+        SYS_CAPGET => "SYS_CAPGET",
+        SYS_CAPSET => "SYS_CAPSET",
+        SYS_PRCTL => "SYS_PRCTL",
+        SYS_GETRLIMIT => "SYS_GETRLIMIT",
+        SYS_SETRLIMIT => "SYS_SETRLIMIT",
+        SYS_PRLIMIT => "SYS_PRLIMIT",
+        SYS_MMAP => "SYS_MMAP",
+        SYS_MUNMAP => "SYS_MUNMAP",
+        SYS_MPROTECT => "SYS_MPROTECT",
+        SYS_MKDIR => "SYS_MKDIR",
+        SYS_UNLINK => "SYS_UNLINK",
+        SYS_SYMLINK => "SYS_SYMLINK",
         _ => "INVALID",
     };
 
@@ -293,7 +588,7 @@ pub extern "C" fn syscall_dispatcher(
         SYS_GETPID => sys_getpid(),
         SYS_YIELD => sys_yield(),
         SYS_FORK => sys_fork(),
-        SYS_WAIT => sys_wait_impl(arg1),
+        SYS_WAIT => sys_wait(arg1),
         SYS_EXEC => sys_exec(arg1, arg2),
         SYS_OPEN => sys_open(arg1, arg2),
         SYS_READ => sys_read(arg1, arg2, arg3),
@@ -335,6 +630,74 @@ pub extern "C" fn syscall_dispatcher(
         SYS_MOUNT => sys_mount(arg1, arg2, arg3),
         SYS_UMOUNT => sys_umount(arg1, arg2),
         SYS_GET_MOUNT_INFO => sys_get_mount_info(arg1, arg2),
+        SYS_NICE => sys_nice(arg1),
+        SYS_SETPRIORITY => sys_setpriority(arg1, arg2, arg3),
+        SYS_GETPRIORITY => sys_getpriority(arg1, arg2),
+        SYS_SCHED_SETSCHEDULER => sys_sched_setscheduler(arg1, arg2, arg3),
+        SYS_SCHED_GETSCHEDULER => sys_sched_getscheduler(arg1),
+        SYS_SCHED_GETPARAM => sys_sched_getparam(arg1, arg2),
+        SYS_NANOSLEEP => sys_nanosleep(arg1, arg2),
+        SYS_CLOCK_GETTIME => sys_clock_gettime(arg1, arg2),
+        SYS_TIMER_CREATE => sys_timer_create(arg1, arg2),
+        SYS_TIMER_SETTIME => sys_timer_settime(arg1, arg2, arg3),
+        SYS_TIMER_GETTIME => sys_timer_gettime(arg1, arg2),
+        SYS_TIMER_DELETE => sys_timer_delete(arg1),
+        SYS_TIMER_GETOVERRUN => sys_timer_getoverrun(arg1),
+        SYS_SCHED_SETAFFINITY => sys_sched_setaffinity(arg1, arg2, arg3),
+        SYS_SCHED_GETAFFINITY => sys_sched_getaffinity(arg1, arg2, arg3),
+        SYS_CPUSET_CREATE => sys_cpuset_create(arg1, arg2),
+        SYS_CPUSET_SETCPUS => sys_cpuset_setcpus(arg1, arg2),
+        SYS_CPUSET_ATTACH => sys_cpuset_attach(arg1, arg2),
+        SYS_CPUSET_DESTROY => sys_cpuset_destroy(arg1),
+        SYS_CLONE => sys_clone(arg1, arg2, arg3),
+        SYS_ARCH_PRCTL => sys_arch_prctl(arg1, arg2),
+        SYS_FUTEX => sys_futex(arg1, arg2, arg3),
+        SYS_GETTID => sys_gettid(),
+        SYS_EXIT_GROUP => sys_exit_group(arg1),
+        SYS_WAIT4 => sys_wait4(arg1, arg2, arg3),
+        SYS_WAITID => sys_waitid(arg1, arg2, arg3),
+        SYS_RT_SIGRETURN => sys_rt_sigreturn(),
+        SYS_RT_SIGPROCMASK => sys_rt_sigprocmask(arg1, arg2, arg3),
+        SYS_RT_SIGPENDING => sys_rt_sigpending(arg1),
+        SYS_RT_SIGSUSPEND => sys_rt_sigsuspend(arg1),
+        SYS_RT_SIGTIMEDWAIT => sys_rt_sigtimedwait(arg1, arg2, arg3),
+        SYS_SIGALTSTACK => sys_sigaltstack(arg1, arg2),
+        SYS_SIGNALFD => sys_signalfd(arg1, arg2, arg3),
+        SYS_POLL => sys_poll(arg1, arg2, arg3),
+        SYS_SIGQUEUE => sys_sigqueue(arg1, arg2, arg3),
+        SYS_PTRACE => sys_ptrace(arg1, arg2, arg3),
+        SYS_GETUID => sys_getuid(),
+        SYS_GETEUID => sys_geteuid(),
+        SYS_GETGID => sys_getgid(),
+        SYS_GETEGID => sys_getegid(),
+        SYS_SETUID => sys_setuid(arg1),
+        SYS_SETGID => sys_setgid(arg1),
+        SYS_SETREUID => sys_setreuid(arg1, arg2),
+        SYS_SETREGID => sys_setregid(arg1, arg2),
+        SYS_SETRESUID => sys_setresuid(arg1, arg2, arg3),
+        SYS_SETRESGID => sys_setresgid(arg1, arg2, arg3),
+        SYS_GETRESUID => sys_getresuid(arg1, arg2, arg3),
+        SYS_GETRESGID => sys_getresgid(arg1, arg2, arg3),
+        SYS_GETGROUPS => sys_getgroups(arg1, arg2),
+        SYS_SETGROUPS => sys_setgroups(arg1, arg2),
+        SYS_CAPGET => sys_capget(arg1, arg2),
+        SYS_CAPSET => sys_capset(arg1, arg2),
+        SYS_PRCTL => sys_prctl(arg1, arg2, arg3),
+        SYS_GETRLIMIT => sys_prlimit(0, arg1, 0, arg2),
+        SYS_SETRLIMIT => sys_prlimit(0, arg1, arg2, 0),
+        SYS_PRLIMIT => {
+            let old_ptr = extra_syscall_args().map_or(0, |(old_ptr, _, _)| old_ptr);
+            sys_prlimit(arg1, arg2, arg3, old_ptr)
+        }
+        SYS_MMAP => {
+            let (flags, fd, offset) = extra_syscall_args().unwrap_or((0, usize::MAX, 0));
+            sys_mmap(arg1, arg2, arg3, flags, fd, offset)
+        }
+        SYS_MUNMAP => sys_munmap(arg1, arg2),
+        SYS_MPROTECT => sys_mprotect(arg1, arg2, arg3),
+        SYS_MKDIR => sys_mkdir(arg1, arg2),
+        SYS_UNLINK => sys_unlink(arg1),
+        SYS_SYMLINK => sys_symlink(arg1, arg2),
         _ => {
             serial_println!("[SYSCALL] ERROR: Invalid syscall ID: {}", syscall_id);
             -1 // Invalid syscall
@@ -387,6 +750,15 @@ fn kernel_buffer_allowed() -> bool {
     }
 }
 
+/// Check that the caller has `cap` for a privileged syscall
+fn require_capability(name: &str, cap: Capabilities) -> bool {
+    if crate::user::capability::capable(cap) {
+        return true;
+    }
+    serial_println!("[SYSCALL] {}: {:?} required", name, cap);
+    false
+}
+
 /// sys_write handler - Write data to file descriptor
 ///
 /// # Arguments
@@ -421,15 +793,13 @@ fn sys_write(fd: usize, buf_ptr: usize, len: usize) -> isize {
     }
 
     // Look up file descriptor
-    let fd_table = FD_TABLE.lock();
-    let fd_entry = match fd_table.get(fd) {
+    let fd_entry = match get_fd(fd) {
         Some(entry) => entry,
         None => {
             serial_println!("[SYSCALL] sys_write: invalid FD {}", fd);
             return -1; // EBADF
         }
     };
-    drop(fd_table);
 
     // Handle based on FD type
     match fd_entry.fd_type {
@@ -454,6 +824,10 @@ fn sys_write(fd: usize, buf_ptr: usize, len: usize) -> isize {
                         return -1; // EPIPE
                     }
                     let bytes_written = pipe.write(buffer);
+                    drop(pipe_table);
+                    if bytes_written > 0 {
+                        wake_pipe_pollers(pipe_id);
+                    }
                     bytes_written as isize
                 }
                 None => {
@@ -490,7 +864,17 @@ fn sys_write(fd: usize, buf_ptr: usize, len: usize) -> isize {
                 offset.load(Ordering::SeqCst)
             };
 
-            match inode.write_at(current_offset, buffer) {
+            // A regular file may not grow past RLIMIT_FSIZE
+            let allowed = if inode.mode().is_file() {
+                match crate::user::rlimit::file_write_allowance(current_offset, len) {
+                    Some(allowed) => allowed,
+                    None => return -1, // EFBIG
+                }
+            } else {
+                len
+            };
+
+            match inode.write_at(current_offset, &buffer[..allowed]) {
                 Ok(bytes_written) => {
                     // Update offset
                     offset.store(current_offset + bytes_written as u64, Ordering::SeqCst);
@@ -509,6 +893,10 @@ fn sys_write(fd: usize, buf_ptr: usize, len: usize) -> isize {
                 }
             }
         }
+        FdType::SignalFd(_) => {
+            serial_println!("[SYSCALL] sys_write: cannot write to a signalfd");
+            -1 // EINVAL
+        }
         FdType::Invalid => {
             serial_println!("[SYSCALL] sys_write: invalid FD type");
             -1 // EBADF
@@ -526,11 +914,8 @@ fn sys_write(fd: usize, buf_ptr: usize, len: usize) -> isize {
 fn sys_exit(code: usize) -> ! {
     serial_println!("[SYSCALL] Task exiting with code {}", code);
 
-    loop {
-        unsafe {
-            core::arch::asm!("hlt");
-        }
-    }
+    // Only the calling thread ends; see sys_exit_group
+    crate::user::thread::exit_thread(WaitStatus::Exited(code as i32))
 }
 
 /// sys_sleep handler - Put task to sleep for specified ticks
@@ -545,7 +930,7 @@ fn sys_exit(code: usize) -> ! {
 /// This function is SMP-safe because:
 /// - Task state modifications are protected by per-task locks (implicit in get_task_mut)
 /// - Uses current core's context via percpu_current()
-/// - yield_now() operates on current core's runqueue
+/// - The wakeup timer is armed on the current core's timer queue
 fn sys_sleep(ticks: usize) -> isize {
     // Validate tick count
     if ticks == 0 {
@@ -570,11 +955,7 @@ fn sys_sleep(ticks: usize) -> isize {
     use core::sync::atomic::Ordering;
     METRICS.sleep_count.fetch_add(1, Ordering::Relaxed);
 
-    // Trigger scheduler to select next task on current core
-    // This will context switch away from the current task
-    crate::sched::yield_now();
-
-    // When we wake up, we return here
+    // sleep_current_task only returns once we have woken up
     0
 }
 
@@ -671,7 +1052,21 @@ fn sys_ipc_recv(port_id: usize, buf_ptr: usize, len: usize) -> isize {
     }
 }
 
+/// sys_getpid handler - Get the process (thread-group) ID
+///
+/// # Returns
+/// The PID shared by all threads of the caller's group
 fn sys_getpid() -> isize {
+    crate::sched::current_task()
+        .map(|task| task.pid as isize)
+        .unwrap_or(1)
+}
+
+/// sys_gettid handler - Get the calling thread's ID
+///
+/// # Returns
+/// The caller's task ID
+fn sys_gettid() -> isize {
     crate::sched::get_current_task_info()
         .map(|(id, _)| id as isize)
         .unwrap_or(1)
@@ -684,379 +1079,55 @@ fn sys_yield() -> isize {
 
 /// sys_fork handler - Create a child process
 ///
-/// Creates a new child process that is a copy of the parent process.
-/// The child gets its own page table with copied user space mappings
-/// and shared kernel space mappings.
+/// The child is a copy of the caller with its own copy-on-write address
+/// space and file table, and returns from the same syscall with 0.
 ///
 /// # Returns
 /// * Child PID in parent process
 /// * 0 in child process
 /// * -1 on error
-///
-/// # Requirements
-/// Implements R2.7, R2.3:
-/// - R2.7: Clone parent's page table structure for child
-/// - R2.3: Share kernel space mappings between parent and child
 fn sys_fork() -> isize {
-    use crate::mm::paging::{clone_page_table_hierarchy, get_current_cr3};
-    use crate::mm::pmm::get_global_pmm;
-    use crate::sched;
-    use crate::user::process::{ProcessManager, ProcessState};
-
-    serial_println!("[SYSCALL] sys_fork: Starting fork operation");
-
-    // Get current task/process info
-    let parent_pid = match sched::get_current_task_info() {
-        Some((id, _)) => id,
-        None => {
-            serial_println!("[SYSCALL] sys_fork: No current task");
-            return -1; // ESRCH
-        }
-    };
-
-    serial_println!("[SYSCALL] sys_fork: Parent PID = {}", parent_pid);
-
-    // Get parent process
-    let parent_guard = match ProcessManager::get_process(parent_pid) {
-        Some(guard) => guard,
-        None => {
-            serial_println!("[SYSCALL] sys_fork: Parent process not found");
-            return -1; // ESRCH
-        }
-    };
-
-    let parent_process = match parent_guard.get() {
-        Some(p) => p,
-        None => {
-            serial_println!("[SYSCALL] sys_fork: Parent process slot empty");
-            return -1; // ESRCH
-        }
-    };
-
-    // Get parent's page table (CR3)
-    let parent_cr3 = if parent_process.cr3 != 0 {
-        parent_process.cr3
-    } else {
-        // Parent doesn't have a dedicated page table yet, use current CR3
-        get_current_cr3()
-    };
-
-    serial_println!("[SYSCALL] sys_fork: Parent CR3 = {:#x}", parent_cr3);
-
-    // Drop parent guard before allocating to avoid holding locks
-    drop(parent_guard);
-
-    // Allocate new page table for child by cloning parent's page table hierarchy
-    // This copies user space mappings (lower half) and shares kernel mappings (upper half)
-    let mut pmm_guard = get_global_pmm();
-    let pmm = match pmm_guard.as_mut() {
-        Some(p) => p,
-        None => {
-            serial_println!("[SYSCALL] sys_fork: PMM not initialized");
-            return -1; // EIO
-        }
-    };
-
-    let child_cr3 = match clone_page_table_hierarchy(parent_cr3, pmm) {
-        Ok(cr3) => cr3,
-        Err(e) => {
-            serial_println!("[SYSCALL] sys_fork: Failed to clone page table: {}", e);
-            return -1; // ENOMEM
-        }
-    };
-
-    serial_println!(
-        "[SYSCALL] sys_fork: Child CR3 = {:#x} (cloned from parent)",
-        child_cr3
-    );
-
-    // Drop PMM guard before marking COW pages
-    drop(pmm_guard);
-
-    // Mark all writable user pages as COW in both parent and child
-    // This implements Requirement 1.1: fork() marks all writable pages as COW
-    use crate::mm::paging::mark_user_pages_cow;
-
-    match mark_user_pages_cow(parent_cr3) {
-        Ok(count) => {
-            serial_println!(
-                "[SYSCALL] sys_fork: Marked {} pages as COW in parent",
-                count
-            );
-        }
-        Err(e) => {
-            serial_println!(
-                "[SYSCALL] sys_fork: Failed to mark parent pages as COW: {}",
-                e
-            );
-            // Continue anyway - COW is an optimization
-        }
-    }
-
-    match mark_user_pages_cow(child_cr3) {
-        Ok(count) => {
-            serial_println!("[SYSCALL] sys_fork: Marked {} pages as COW in child", count);
-        }
-        Err(e) => {
-            serial_println!(
-                "[SYSCALL] sys_fork: Failed to mark child pages as COW: {}",
-                e
-            );
-            // Continue anyway - COW is an optimization
-        }
-    }
-
-    // Create child process
-    let child_pid = match ProcessManager::create_process(Some(parent_pid), "forked_child") {
-        Ok(pid) => pid,
-        Err(e) => {
-            serial_println!(
-                "[SYSCALL] sys_fork: Failed to create child process: {:?}",
-                e
-            );
-
-            // Clean up: Free the allocated page table hierarchy
-            use crate::mm::paging::free_page_table_hierarchy;
-            let mut pmm_guard = get_global_pmm();
-            if let Some(pmm) = pmm_guard.as_mut() {
-                free_page_table_hierarchy(child_cr3, pmm);
-                serial_println!(
-                    "[SYSCALL] sys_fork: Freed child page table at CR3={:#x}",
-                    child_cr3
-                );
-            } else {
-                serial_println!(
-                    "[SYSCALL] sys_fork: WARNING: Could not free child page table - PMM unavailable"
-                );
-            }
-
-            return -1; // EAGAIN or ENOMEM
-        }
-    };
-
-    serial_println!("[SYSCALL] sys_fork: Child PID = {}", child_pid);
-
-    // Get child process and set its CR3
-    let mut child_guard = match ProcessManager::get_process(child_pid) {
-        Some(guard) => guard,
-        None => {
-            serial_println!("[SYSCALL] sys_fork: Failed to get child process");
-
-            // Clean up: Free the allocated page table and remove process
-            use crate::mm::paging::free_page_table_hierarchy;
-            let mut pmm_guard = get_global_pmm();
-            if let Some(pmm) = pmm_guard.as_mut() {
-                free_page_table_hierarchy(child_cr3, pmm);
-            }
-            let _ = ProcessManager::remove_process(child_pid);
-
-            return -1; // EIO
-        }
-    };
-
-    let child_process = match child_guard.get_mut() {
-        Some(p) => p,
-        None => {
-            serial_println!("[SYSCALL] sys_fork: Child process slot empty");
-
-            // Clean up: Free the allocated page table and remove process
-            drop(child_guard);
-            use crate::mm::paging::free_page_table_hierarchy;
-            let mut pmm_guard = get_global_pmm();
-            if let Some(pmm) = pmm_guard.as_mut() {
-                free_page_table_hierarchy(child_cr3, pmm);
-            }
-            let _ = ProcessManager::remove_process(child_pid);
-
-            return -1; // EIO
-        }
-    };
-
-    // Set child's CR3 to the cloned page table
-    child_process.cr3 = child_cr3;
-
-    // Copy parent's memory regions to child
-    // Get parent process again to copy regions
-    let parent_guard = match ProcessManager::get_process(parent_pid) {
-        Some(guard) => guard,
-        None => {
-            serial_println!("[SYSCALL] sys_fork: Parent process disappeared");
-
-            // Clean up: Free the allocated page table and remove child process
-            drop(child_guard);
-            use crate::mm::paging::free_page_table_hierarchy;
-            let mut pmm_guard = get_global_pmm();
-            if let Some(pmm) = pmm_guard.as_mut() {
-                free_page_table_hierarchy(child_cr3, pmm);
-            }
-            let _ = ProcessManager::remove_process(child_pid);
-
-            return -1; // ESRCH
-        }
-    };
-
-    let parent_process = match parent_guard.get() {
-        Some(p) => p,
-        None => {
-            serial_println!("[SYSCALL] sys_fork: Parent process slot empty");
-
-            // Clean up: Free the allocated page table and remove child process
-            drop(parent_guard);
-            drop(child_guard);
-            use crate::mm::paging::free_page_table_hierarchy;
-            let mut pmm_guard = get_global_pmm();
-            if let Some(pmm) = pmm_guard.as_mut() {
-                free_page_table_hierarchy(child_cr3, pmm);
-            }
-            let _ = ProcessManager::remove_process(child_pid);
-
-            return -1; // ESRCH
-        }
-    };
-
-    // Copy memory regions
-    for i in 0..parent_process.region_count {
-        if let Some(region) = &parent_process.memory_regions[i] {
-            if let Err(e) = child_process.add_memory_region(region.clone()) {
-                serial_println!("[SYSCALL] sys_fork: Failed to copy memory region: {:?}", e);
-                // Continue anyway - partial copy is better than nothing
-            }
-        }
-    }
-
-    // Copy parent's context to child
-    child_process.context = parent_process.context.clone();
-    child_process.priority = parent_process.priority;
-
-    serial_println!(
-        "[SYSCALL] sys_fork: Copied {} memory regions to child",
-        child_process.region_count
-    );
-
-    // Set child's state to Ready
-    child_process.state = ProcessState::Ready;
-
-    drop(parent_guard);
-    drop(child_guard);
-
-    // Create a task for the child process
-    // For now, we'll use a dummy entry point - in a full implementation,
-    // the child would resume from the fork point
-    fn child_entry() -> ! {
-        // Child process entry point
-        // In a full implementation, this would return 0 to indicate child process
-        loop {
-            unsafe {
-                core::arch::asm!("hlt");
-            }
-        }
-    }
-
-    match sched::spawn_task(
-        "forked_child",
-        child_entry,
-        crate::sched::priority::TaskPriority::Normal,
-    ) {
-        Ok(child_task_id) => {
-            serial_println!(
-                "[SYSCALL] sys_fork: Created task {} for child process {}",
-                child_task_id,
-                child_pid
-            );
-
-            // Sync task with process
-            use crate::user::process::sync_task_with_process;
-            if let Err(e) = sync_task_with_process(child_task_id, child_pid) {
-                serial_println!(
-                    "[SYSCALL] sys_fork: Failed to sync task with process: {:?}",
-                    e
-                );
-            }
-        }
+    match crate::user::thread::clone(0, 0, 0, 0, 0) {
+        Ok(child) => child as isize,
         Err(e) => {
-            serial_println!(
-                "[SYSCALL] sys_fork: Failed to create task for child: {:?}",
-                e
-            );
-
-            // Clean up: Free the allocated page table and remove child process
-            use crate::mm::paging::free_page_table_hierarchy;
-            let mut pmm_guard = get_global_pmm();
-            if let Some(pmm) = pmm_guard.as_mut() {
-                free_page_table_hierarchy(child_cr3, pmm);
-            }
-            let _ = ProcessManager::remove_process(child_pid);
-
-            return -1; // EAGAIN
+            serial_println!("[SYSCALL] sys_fork: {:?}", e);
+            -1 // ENOMEM / EAGAIN
         }
     }
-
-    serial_println!(
-        "[SYSCALL] sys_fork: Fork completed successfully - parent={}, child={}",
-        parent_pid,
-        child_pid
-    );
-
-    // Return child PID to parent
-    // In a full implementation, the child would get 0 as return value
-    child_pid as isize
 }
 
-/// sys_wait implementation - Wait for child process to terminate
+/// sys_wait handler - Wait for a child process to exit
 ///
-/// This is the actual implementation that was previously in sys_wait_stub.
-/// It handles waiting for zombie children and cleaning them up.
-fn sys_wait_impl(child_pid: usize) -> isize {
-    use crate::sched;
-    use crate::user::process::ProcessManager;
-
-    let parent_pid = match sched::get_current_task_info() {
-        Some((id, _)) => id,
-        None => return -1, // ESRCH
+/// Blocks until a matching child has exited, then frees it.
+///
+/// # Arguments
+/// * `child_pid` - Child to wait for, or 0 for any child
+///
+/// # Returns
+/// `(pid << 8) | (exit_code & 0xFF)` of the child, or -1 on error. A child
+/// killed by a signal reports `128 + signal`.
+fn sys_wait(child_pid: usize) -> isize {
+    use crate::user::process::{wait_child, WaitTarget, WEXITED};
+
+    let target = match child_pid {
+        0 => WaitTarget::Any,
+        pid => WaitTarget::Pid(pid),
     };
-
-    // Look for zombie children
-    let zombie_child = if child_pid == 0 {
-        // Wait for any child
-        ProcessManager::find_zombie_child(parent_pid)
-    } else {
-        // Wait for specific child
-        if let Some(child_guard) = ProcessManager::get_process(child_pid) {
-            if let Some(child_process) = child_guard.get() {
-                if child_process.is_child_of(parent_pid)
-                    && child_process.state == crate::user::process::ProcessState::Zombie
-                {
-                    child_process.exit_code.map(|code| (child_pid, code))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
+    match wait_child(target, WEXITED) {
+        Ok(Some(event)) => {
+            let code = match event.status {
+                WaitStatus::Signaled { signal, .. } => 128 + signal as usize,
+                status => status.si_status() as usize,
+            };
+            (((event.pid & 0xFF_FFFF) << 8) | (code & 0xFF)) as isize
         }
-    };
-
-    if let Some((dead_child_pid, exit_code)) = zombie_child {
-        // Found a zombie child - clean it up and return
-        match ProcessManager::remove_process(dead_child_pid) {
-            Ok(_) => {
-                // Return child PID and exit code
-                // Encode as (child_pid << 8) | (exit_code & 0xFF)
-                let result = ((dead_child_pid & 0xFFFFFF) << 8) | ((exit_code as usize) & 0xFF);
-                return result as isize;
-            }
-            Err(_) => {
-                return -1; // EIO
-            }
+        Ok(None) => -1,
+        Err(ProcessError::Interrupted) => ERESTARTSYS,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_wait: {:?}", e);
+            -1 // ECHILD
         }
     }
-
-    // No zombie children found
-    // In a full implementation with blocking, we would block here
-    // For now, return EAGAIN (try again later)
-    -11 // EAGAIN
 }
 
 /// sys_exec handler - Execute a new program
@@ -1218,6 +1289,8 @@ pub enum FdType {
         offset: core::sync::atomic::AtomicU64,
         flags: u32, // O_RDONLY, O_WRONLY, O_RDWR, O_APPEND, etc.
     },
+    /// Signalfd, reading the signals in the mask (shared by duplicates)
+    SignalFd(alloc::sync::Arc<core::sync::atomic::AtomicU64>),
 }
 
 // Manual Clone implementation since AtomicU64 doesn't implement Clone
@@ -1241,6 +1314,7 @@ impl Clone for FdType {
                     flags: *flags,
                 }
             }
+            FdType::SignalFd(mask) => FdType::SignalFd(mask.clone()),
         }
     }
 }
@@ -1268,6 +1342,10 @@ impl core::fmt::Debug for FdType {
                     flags
                 )
             }
+            FdType::SignalFd(mask) => {
+                use core::sync::atomic::Ordering;
+                write!(f, "SignalFd({:#x})", mask.load(Ordering::SeqCst))
+            }
         }
     }
 }
@@ -1284,6 +1362,7 @@ impl PartialEq for FdType {
             (FdType::VfsFile { inode: a, .. }, FdType::VfsFile { inode: b, .. }) => {
                 alloc::sync::Arc::ptr_eq(a, b)
             }
+            (FdType::SignalFd(a), FdType::SignalFd(b)) => alloc::sync::Arc::ptr_eq(a, b),
             _ => false,
         }
     }
@@ -1298,6 +1377,9 @@ const FD_CLOEXEC: u32 = 1;
 const O_NONBLOCK: u32 = 0x800;
 const O_APPEND: u32 = 0x400;
 
+/// Open flag: set FD_CLOEXEC on the new descriptor
+const O_CLOEXEC: u32 = 0x80000;
+
 /// File descriptor table entry
 #[derive(Debug, Clone)]
 pub struct FileDescriptor {
@@ -1318,14 +1400,6 @@ impl FileDescriptor {
         }
     }
 
-    fn with_type(fd_type: FdType) -> Self {
-        Self {
-            fd_type,
-            fd_flags: 0,
-            status_flags: 0,
-        }
-    }
-
     fn with_flags(fd_type: FdType, fd_flags: u32, status_flags: u32) -> Self {
         Self {
             fd_type,
@@ -1340,9 +1414,6 @@ impl FileDescriptor {
     }
 }
 
-/// Maximum number of file descriptors per process
-const MAX_FDS: usize = 256;
-
 /// Maximum number of pipes
 const MAX_PIPES: usize = 64;
 
@@ -1474,120 +1545,89 @@ impl PipeTable {
 
 static PIPE_TABLE: SpinLock<PipeTable> = SpinLock::new(PipeTable::new());
 
-/// Close all file descriptors with FD_CLOEXEC flag set
-///
-/// This is called during exec to close file descriptors that should not
-/// be inherited by the new program.
-pub fn close_fds_with_cloexec() {
-    let mut fd_table = FD_TABLE.lock();
-
-    // Scan all file descriptors
-    for fd in 0..MAX_FDS {
-        if let Some(fd_entry) = fd_table.get(fd) {
-            // Check if FD_CLOEXEC flag is set
-            if (fd_entry.fd_flags & FD_CLOEXEC) != 0 {
-                serial_println!("[SYSCALL] Closing FD {} (FD_CLOEXEC set)", fd);
-
-                // Get the FD type before closing
-                let fd_type = fd_entry.fd_type;
+/// Tasks polling each pipe, woken when data moves or an end closes
+static PIPE_POLLERS: [WaitQueue; MAX_PIPES] = [const { WaitQueue::new() }; MAX_PIPES];
 
-                // Close the FD
-                fd_table.close(fd);
+/// Wake the tasks polling a pipe
+fn wake_pipe_pollers(pipe_id: u32) {
+    if let Some(queue) = PIPE_POLLERS.get(pipe_id as usize) {
+        queue.wake_all();
+    }
+}
 
-                // Handle cleanup based on FD type
-                match fd_type {
-                    FdType::PtyMaster(pty_num) => {
-                        crate::dev::pty::deallocate_pty(pty_num);
-                    }
-                    FdType::PipeRead(pipe_id) => {
-                        let mut pipe_table = PIPE_TABLE.lock();
-                        pipe_table.close_reader(pipe_id);
-                    }
-                    FdType::PipeWrite(pipe_id) => {
-                        let mut pipe_table = PIPE_TABLE.lock();
-                        pipe_table.close_writer(pipe_id);
-                    }
-                    _ => {}
+impl FdType {
+    /// Take another reference to the pipe end or terminal behind a copied
+    /// descriptor (dup or fork)
+    fn acquire(&self) {
+        match *self {
+            FdType::PtyMaster(pty_num) => crate::dev::pty::hold_master(pty_num),
+            FdType::PipeRead(pipe_id) => {
+                if let Some(pipe) = PIPE_TABLE.lock().get_mut(pipe_id) {
+                    pipe.readers += 1;
                 }
             }
+            FdType::PipeWrite(pipe_id) => {
+                if let Some(pipe) = PIPE_TABLE.lock().get_mut(pipe_id) {
+                    pipe.writers += 1;
+                }
+            }
+            _ => {}
         }
     }
-}
-
-/// Close all file descriptors for process exit
-///
-/// This is called during process termination to clean up all open file
-/// descriptors and associated resources.
-pub fn close_all_fds_on_exit() {
-    let mut fd_table = FD_TABLE.lock();
-
-    // Scan all file descriptors and close them
-    for fd in 0..MAX_FDS {
-        if let Some(fd_entry) = fd_table.get(fd) {
-            crate::serial_println!("[SYSCALL] Closing FD {} on exit", fd);
-
-            // Get the FD type before closing
-            let fd_type = fd_entry.fd_type;
-
-            // Close the FD
-            fd_table.close(fd);
 
-            // Handle cleanup based on FD type
-            match fd_type {
-                FdType::PtyMaster(pty_num) => {
-                    crate::dev::pty::deallocate_pty(pty_num);
-                    crate::serial_println!("[SYSCALL] Deallocated PTY {}", pty_num);
-                }
-                FdType::PtySlave(pty_num) => {
-                    crate::serial_println!("[SYSCALL] Closed PTY slave {}", pty_num);
-                }
-                FdType::PipeRead(pipe_id) => {
-                    let mut pipe_table = PIPE_TABLE.lock();
-                    pipe_table.close_reader(pipe_id);
-                    crate::serial_println!("[SYSCALL] Closed pipe reader {}", pipe_id);
-                }
-                FdType::PipeWrite(pipe_id) => {
-                    let mut pipe_table = PIPE_TABLE.lock();
-                    pipe_table.close_writer(pipe_id);
-                    crate::serial_println!("[SYSCALL] Closed pipe writer {}", pipe_id);
-                }
-                FdType::VfsFile { inode, .. } => {
-                    crate::serial_println!("[SYSCALL] Closed VFS file (inode {})", inode.ino());
-                    // Arc will handle reference counting automatically
-                }
-                FdType::Invalid => {
-                    crate::serial_println!("[SYSCALL] Encountered invalid FD {}", fd);
-                }
+    /// Drop the reference of a closed descriptor; the last one closes the
+    /// pipe end or deallocates the PTY pair
+    fn release(&self) {
+        match *self {
+            FdType::PtyMaster(pty_num) => crate::dev::pty::release_master(pty_num),
+            FdType::PipeRead(pipe_id) => {
+                PIPE_TABLE.lock().close_reader(pipe_id);
+                wake_pipe_pollers(pipe_id);
+            }
+            FdType::PipeWrite(pipe_id) => {
+                PIPE_TABLE.lock().close_writer(pipe_id);
+                wake_pipe_pollers(pipe_id);
             }
+            // VFS files and signalfds are freed with their last Arc
+            _ => {}
         }
     }
-
-    crate::serial_println!("[SYSCALL] All file descriptors closed on exit");
 }
 
-/// Per-task file descriptor table
+/// Per-process file descriptor table
+///
+/// Each task holds its table in `Task::fd_table`: fork copies it, threads
+/// created with `CLONE_FILES` share it. Every entry holds a reference to its
+/// pipe end or PTY master, so a descriptor closed in one table stays open in
+/// the others.
 ///
-/// this would be per-task.
-pub(crate) struct FdTable {
-    fds: [Option<FileDescriptor>; MAX_FDS],
+/// The table grows as descriptors are opened. New descriptors must be
+/// below `limit`, the caller's `RLIMIT_NOFILE` (see `rlimit::fd_limit`).
+#[derive(Debug, Default)]
+pub struct FdTable {
+    fds: alloc::vec::Vec<Option<FileDescriptor>>,
 }
 
 impl FdTable {
-    const fn new() -> Self {
+    /// Create an empty table
+    pub const fn new() -> Self {
         Self {
-            fds: [const { None }; MAX_FDS],
+            fds: alloc::vec::Vec::new(),
         }
     }
 
-    fn allocate(&mut self, fd_type: FdType) -> Option<usize> {
-        // Start from FD 3 (after stdin/stdout/stderr)
-        for i in 3..MAX_FDS {
-            if self.fds[i].is_none() {
-                self.fds[i] = Some(FileDescriptor::with_type(fd_type));
-                return Some(i);
-            }
+    /// Lowest free descriptor from 3 up (after stdin/stdout/stderr) that is
+    /// below `limit`; the table grows to hold it
+    fn free_slot(&mut self, limit: usize) -> Option<usize> {
+        let fd = (3..limit).find(|&i| self.fds.get(i).is_none_or(|slot| slot.is_none()))?;
+        if fd >= self.fds.len() {
+            self.fds.resize(fd + 1, None);
         }
-        None
+        Some(fd)
+    }
+
+    pub(crate) fn allocate(&mut self, fd_type: FdType, limit: usize) -> Option<usize> {
+        self.allocate_with_flags(fd_type, 0, 0, limit)
     }
 
     fn allocate_with_flags(
@@ -1595,61 +1635,100 @@ impl FdTable {
         fd_type: FdType,
         fd_flags: u32,
         status_flags: u32,
+        limit: usize,
     ) -> Option<usize> {
-        // Start from FD 3 (after stdin/stdout/stderr)
-        for i in 3..MAX_FDS {
-            if self.fds[i].is_none() {
-                self.fds[i] = Some(FileDescriptor::with_flags(fd_type, fd_flags, status_flags));
-                return Some(i);
-            }
-        }
-        None
+        let fd = self.free_slot(limit)?;
+        self.fds[fd] = Some(FileDescriptor::with_flags(fd_type, fd_flags, status_flags));
+        Some(fd)
     }
 
+    /// Install a descriptor as `fd`, closing what was there
     fn allocate_at(
         &mut self,
         fd: usize,
         fd_type: FdType,
         fd_flags: u32,
         status_flags: u32,
+        limit: usize,
     ) -> bool {
-        if fd >= MAX_FDS {
+        if fd >= limit {
             return false;
         }
-        // Close existing FD if open
-        if self.fds[fd].is_some() {
-            self.close(fd);
+        if fd >= self.fds.len() {
+            self.fds.resize(fd + 1, None);
+        }
+        if let Some(old) =
+            self.fds[fd].replace(FileDescriptor::with_flags(fd_type, fd_flags, status_flags))
+        {
+            old.fd_type.release();
         }
-        self.fds[fd] = Some(FileDescriptor::with_flags(fd_type, fd_flags, status_flags));
         true
     }
 
     fn get_mut(&mut self, fd: usize) -> Option<&mut FileDescriptor> {
-        if fd < MAX_FDS {
-            self.fds[fd].as_mut()
-        } else {
-            None
-        }
+        self.fds.get_mut(fd)?.as_mut()
     }
 
     pub(crate) fn get(&self, fd: usize) -> Option<FileDescriptor> {
-        if fd < MAX_FDS {
-            self.fds[fd].clone()
-        } else {
-            None
+        self.fds.get(fd)?.clone()
+    }
+
+    /// Close `fd`, returning what it referred to
+    pub(crate) fn close(&mut self, fd: usize) -> Option<FileDescriptor> {
+        let entry = self.fds.get_mut(fd)?.take()?;
+        entry.fd_type.release();
+        Some(entry)
+    }
+
+    /// Copy the table for a forked child
+    pub fn clone_for_fork(&self) -> Self {
+        for entry in self.fds.iter().flatten() {
+            entry.fd_type.acquire();
+        }
+        Self {
+            fds: self.fds.clone(),
         }
     }
 
-    fn close(&mut self, fd: usize) -> Option<FileDescriptor> {
-        if fd < MAX_FDS {
-            self.fds[fd].take()
-        } else {
-            None
+    /// Close every descriptor marked `FD_CLOEXEC`, for exec
+    pub fn close_cloexec(&mut self) {
+        for slot in self.fds.iter_mut() {
+            if let Some(entry) = slot.take_if(|entry| entry.fd_flags & FD_CLOEXEC != 0) {
+                entry.fd_type.release();
+            }
+        }
+    }
+
+    /// Number of open descriptors
+    pub fn count(&self) -> usize {
+        self.fds.iter().flatten().count()
+    }
+}
+
+impl Drop for FdTable {
+    fn drop(&mut self) {
+        for entry in self.fds.iter().flatten() {
+            entry.fd_type.release();
         }
     }
 }
 
-pub(crate) static FD_TABLE: SpinLock<FdTable> = SpinLock::new(FdTable::new());
+/// The calling task's file descriptor table
+fn current_files() -> Option<alloc::sync::Arc<SpinLock<FdTable>>> {
+    crate::sched::current_task().map(|task| task.fd_table.clone())
+}
+
+/// Look up one of the calling task's file descriptors
+pub(crate) fn get_fd(fd: usize) -> Option<FileDescriptor> {
+    current_files()?.lock().get(fd)
+}
+
+/// Open `fd_type` as the calling task's lowest free file descriptor
+fn install_fd(fd_type: FdType) -> Option<usize> {
+    current_files()?
+        .lock()
+        .allocate(fd_type, crate::user::rlimit::fd_limit())
+}
 
 /// sys_open handler - Open a device or file
 ///
@@ -1684,8 +1763,7 @@ fn sys_open(path_ptr: usize, flags: usize) -> isize {
         match crate::dev::pty::allocate_pty() {
             Some(pty_num) => {
                 // Allocate a file descriptor
-                let mut fd_table = FD_TABLE.lock();
-                match fd_table.allocate(FdType::PtyMaster(pty_num)) {
+                match install_fd(FdType::PtyMaster(pty_num)) {
                     Some(fd) => {
                         serial_println!(
                             "[SYSCALL] sys_open: allocated PTY {} as FD {}",
@@ -1714,8 +1792,7 @@ fn sys_open(path_ptr: usize, flags: usize) -> isize {
             // Verify PTY exists
             if crate::dev::pty::get_pty_slave_number(pty_num).is_some() {
                 // Allocate a file descriptor
-                let mut fd_table = FD_TABLE.lock();
-                match fd_table.allocate(FdType::PtySlave(pty_num)) {
+                match install_fd(FdType::PtySlave(pty_num)) {
                     Some(fd) => {
                         serial_println!(
                             "[SYSCALL] sys_open: opened PTY slave {} as FD {}",
@@ -1737,23 +1814,48 @@ fn sys_open(path_ptr: usize, flags: usize) -> isize {
             serial_println!("[SYSCALL] sys_open: invalid PTY number in path");
             -1 // EINVAL
         }
+    } else if path.starts_with("/proc/") {
+        // Generated files (not in VFS either)
+        match crate::fs::proc::open(path) {
+            Ok(inode) => {
+                let fd_type = FdType::VfsFile {
+                    inode,
+                    offset: core::sync::atomic::AtomicU64::new(0),
+                    flags: flags as u32,
+                };
+                match install_fd(fd_type) {
+                    Some(fd) => fd as isize,
+                    None => {
+                        serial_println!("[SYSCALL] sys_open: no FDs available");
+                        -1 // EMFILE - too many open files
+                    }
+                }
+            }
+            Err(err) => err as isize,
+        }
     } else {
         // Use VFS path resolution for regular files
         use crate::fs::vfs::path;
         use core::sync::atomic::AtomicU64;
 
-        // Resolve path to inode
-        match path::resolve_path(path, None) {
+        use crate::fs::vfs::permission;
+
+        // Resolve path to inode and check the caller may open it that way
+        let creds = crate::user::creds::current();
+        let resolved = path::resolve_path(path, None).and_then(|inode| {
+            let mask = permission::open_mask(flags as u32);
+            permission::permission(&*inode, &creds, mask).map(|()| inode)
+        });
+        match resolved {
             Ok(inode) => {
                 // Allocate file descriptor
-                let mut fd_table = FD_TABLE.lock();
                 let fd_type = FdType::VfsFile {
                     inode,
                     offset: AtomicU64::new(0),
                     flags: flags as u32,
                 };
 
-                match fd_table.allocate(fd_type) {
+                match install_fd(fd_type) {
                     Some(fd) => {
                         serial_println!("[SYSCALL] sys_open: opened {} as FD {}", path, fd);
                         fd as isize
@@ -1801,15 +1903,13 @@ fn sys_read(fd: usize, buf_ptr: usize, len: usize) -> isize {
     }
 
     // Look up file descriptor
-    let fd_table = FD_TABLE.lock();
-    let fd_entry = match fd_table.get(fd) {
+    let fd_entry = match get_fd(fd) {
         Some(entry) => entry,
         None => {
             serial_println!("[SYSCALL] sys_read: invalid FD {}", fd);
             return -1; // EBADF
         }
     };
-    drop(fd_table);
 
     // Convert pointer to mutable slice
     let buffer = unsafe { core::slice::from_raw_parts_mut(buf_ptr as *mut u8, len) };
@@ -1836,6 +1936,10 @@ fn sys_read(fd: usize, buf_ptr: usize, len: usize) -> isize {
                         return 0; // EOF
                     }
                     let bytes_read = pipe.read(buffer);
+                    drop(pipe_table);
+                    if bytes_read > 0 {
+                        wake_pipe_pollers(pipe_id);
+                    }
                     bytes_read as isize
                 }
                 None => {
@@ -1874,6 +1978,17 @@ fn sys_read(fd: usize, buf_ptr: usize, len: usize) -> isize {
                 }
             }
         }
+        FdType::SignalFd(ref mask) => {
+            use crate::signal::signalfd::{self, SignalfdError};
+            use core::sync::atomic::Ordering;
+
+            let nonblock = fd_entry.status_flags & O_NONBLOCK != 0;
+            match signalfd::read(mask.load(Ordering::Acquire), buffer, nonblock) {
+                Ok(bytes_read) => bytes_read as isize,
+                Err(SignalfdError::Interrupted) => ERESTARTSYS,
+                Err(_) => -1, // EINVAL / EAGAIN
+            }
+        }
         FdType::Invalid => {
             serial_println!("[SYSCALL] sys_read: invalid FD type");
             -1 // EBADF
@@ -1889,41 +2004,11 @@ fn sys_read(fd: usize, buf_ptr: usize, len: usize) -> isize {
 /// # Returns
 /// 0 on success, or -1 on error
 fn sys_close(fd: usize) -> isize {
-    let mut fd_table = FD_TABLE.lock();
-
-    match fd_table.close(fd) {
-        Some(fd_entry) => {
+    // Closing drops this table's reference to the pipe end or terminal
+    let closed = current_files().and_then(|files| files.lock().close(fd));
+    match closed {
+        Some(_) => {
             serial_println!("[SYSCALL] sys_close: closed FD {}", fd);
-
-            // Handle cleanup based on FD type
-            match fd_entry.fd_type {
-                FdType::PtyMaster(pty_num) => {
-                    // If this was a PTY master, deallocate the PTY pair
-                    // (In a full implementation, we'd track open counts)
-                    crate::dev::pty::deallocate_pty(pty_num);
-                }
-                FdType::PtySlave(_) => {
-                    // Slave close doesn't deallocate
-                }
-                FdType::PipeRead(pipe_id) => {
-                    // Close pipe read end
-                    let mut pipe_table = PIPE_TABLE.lock();
-                    pipe_table.close_reader(pipe_id);
-                }
-                FdType::PipeWrite(pipe_id) => {
-                    // Close pipe write end
-                    let mut pipe_table = PIPE_TABLE.lock();
-                    pipe_table.close_writer(pipe_id);
-                }
-                FdType::VfsFile { .. } => {
-                    // VFS file - Arc will handle cleanup automatically
-                    // No explicit cleanup needed
-                }
-                FdType::Invalid => {
-                    // Should never happen
-                }
-            }
-
             0
         }
         None => {
@@ -1954,15 +2039,13 @@ const TIOCSCTTY: usize = 0x540E; // Make this TTY the controlling terminal
 /// 0 on success, or -1 on error
 fn sys_ioctl(fd: usize, cmd: usize, arg: usize) -> isize {
     // Look up file descriptor
-    let fd_table = FD_TABLE.lock();
-    let fd_entry = match fd_table.get(fd) {
+    let fd_entry = match get_fd(fd) {
         Some(entry) => entry,
         None => {
             serial_println!("[SYSCALL] sys_ioctl: invalid FD {}", fd);
             return -1; // EBADF
         }
     };
-    drop(fd_table);
 
     serial_println!(
         "[SYSCALL] sys_ioctl: FD={}, cmd={:#x}, arg={:#x}",
@@ -2271,7 +2354,7 @@ fn sys_sigaction(signal: usize, act_ptr: usize, oldact_ptr: usize) -> isize {
             return -1;
         }
 
-        let old_action = task.signal_handlers[signal];
+        let old_action = task.signal_handlers.lock()[signal];
         unsafe {
             *(oldact_ptr as *mut SigAction) = old_action;
         }
@@ -2294,7 +2377,7 @@ fn sys_sigaction(signal: usize, act_ptr: usize, oldact_ptr: usize) -> isize {
             }
         }
 
-        task.signal_handlers[signal] = new_action;
+        task.signal_handlers.lock()[signal] = new_action;
         serial_println!("[SYSCALL] sys_sigaction: set handler for signal {}", signal);
     }
 
@@ -2315,8 +2398,11 @@ fn sys_sigaction(signal: usize, act_ptr: usize, oldact_ptr: usize) -> isize {
 /// * pid == 0: Send to all processes in current process group
 /// * pid == -1: Send to all processes (except init)
 /// * pid < -1: Send to all processes in process group |pid|
+///
+/// Processes the caller may not signal (see `may_signal`) are skipped; a
+/// single target the caller may not signal gives EPERM.
 fn sys_kill(pid: usize, signal: usize) -> isize {
-    use crate::signal::{send_signal, signals};
+    use crate::signal::{send_signal_info, signals, SigSource};
 
     // Convert pid to isize for negative value handling
     let pid_signed = pid as isize;
@@ -2332,6 +2418,9 @@ fn sys_kill(pid: usize, signal: usize) -> isize {
         // Check if target process exists without sending a signal
         if pid > 0 && pid < 0x8000_0000 {
             match crate::sched::get_task_mut(pid) {
+                Some(target) if !may_signal(target, signal) => {
+                    return -1; // EPERM
+                }
                 Some(_) => {
                     serial_println!(
                         "[SYSCALL] sys_kill: process {} exists (signal 0 check)",
@@ -2353,9 +2442,12 @@ fn sys_kill(pid: usize, signal: usize) -> isize {
         }
     }
 
-    // Get current task for permission checks
-    let _sender_id = match crate::sched::get_current_task_info() {
-        Some((id, _)) => id,
+    // The sender, as reported in the target's siginfo
+    let source = match crate::sched::current_task() {
+        Some(task) => SigSource::User {
+            pid: task.pid,
+            uid: task.creds.uid,
+        },
         None => {
             serial_println!("[SYSCALL] sys_kill: no current task");
             return -1;
@@ -2379,8 +2471,13 @@ fn sys_kill(pid: usize, signal: usize) -> isize {
             }
         };
 
+        if !may_signal(target, signal) {
+            serial_println!("[SYSCALL] sys_kill: not permitted to signal {}", pid);
+            return -1; // EPERM
+        }
+
         // Send the signal
-        match send_signal(target, signal as u32) {
+        match send_signal_info(target, signal as u32, source) {
             Ok(()) => {
                 serial_println!(
                     "[SYSCALL] sys_kill: sent signal {} to process {}",
@@ -2412,15 +2509,14 @@ fn sys_kill(pid: usize, signal: usize) -> isize {
 
         // Send signal to all tasks in the same process group
         let mut sent_count = 0;
-        for task_id in 1..1024 {
-            if let Some(task) = crate::sched::get_task_mut(task_id) {
-                if task.pgid == current_pgid {
-                    if let Ok(()) = send_signal(task, signal as u32) {
-                        sent_count += 1;
-                    }
-                }
+        crate::sched::for_each_task_in_group(current_pgid, |task| {
+            if !may_signal(task, signal) {
+                return;
             }
-        }
+            if let Ok(()) = send_signal_info(task, signal as u32, source) {
+                sent_count += 1;
+            }
+        });
 
         serial_println!(
             "[SYSCALL] sys_kill: sent signal to {} processes in group",
@@ -2444,24 +2540,16 @@ fn sys_kill(pid: usize, signal: usize) -> isize {
         };
 
         let mut sent_count = 0;
-        for task_id in 2..1024 {
-            if task_id == current_pid {
-                continue; // Skip current process
+        crate::sched::for_each_task(|task| {
+            // Skip the idle task, init and the current process
+            if task.id < 2 || task.id == current_pid || !may_signal(task, signal) {
+                return;
             }
 
-            if let Some(task) = crate::sched::get_task_mut(task_id) {
-                // Skip init process for SIGKILL/SIGSTOP
-                if task_id == 1
-                    && (signal == signals::SIGKILL as usize || signal == signals::SIGSTOP as usize)
-                {
-                    continue;
-                }
-
-                if let Ok(()) = send_signal(task, signal as u32) {
-                    sent_count += 1;
-                }
+            if let Ok(()) = send_signal_info(task, signal as u32, source) {
+                sent_count += 1;
             }
-        }
+        });
 
         serial_println!(
             "[SYSCALL] sys_kill: sent signal to {} processes",
@@ -2482,15 +2570,14 @@ fn sys_kill(pid: usize, signal: usize) -> isize {
         );
 
         let mut sent_count = 0;
-        for task_id in 1..1024 {
-            if let Some(task) = crate::sched::get_task_mut(task_id) {
-                if task.pgid == target_pgid {
-                    if let Ok(()) = send_signal(task, signal as u32) {
-                        sent_count += 1;
-                    }
-                }
+        crate::sched::for_each_task_in_group(target_pgid, |task| {
+            if !may_signal(task, signal) {
+                return;
             }
-        }
+            if let Ok(()) = send_signal_info(task, signal as u32, source) {
+                sent_count += 1;
+            }
+        });
 
         serial_println!(
             "[SYSCALL] sys_kill: sent signal to {} processes in group {}",
@@ -2508,198 +2595,2250 @@ fn sys_kill(pid: usize, signal: usize) -> isize {
     }
 }
 
-/// sys_setpgid handler - Set process group ID
+/// sys_sigqueue handler - Send a signal carrying a value to a process
+///
+/// Real-time signals are queued, up to a fixed `SIGQUEUE_MAX` (32) per
+/// target task rather than a per-user `RLIMIT_SIGPENDING`; standard
+/// signals merge as with `kill`. The handler (`si_value`) or
+/// signalfd reader (`ssi_int`/`ssi_ptr`) receives the value.
 ///
 /// # Arguments
-/// * `pid` - Process ID to modify (0 = current process)
-/// * `pgid` - New process group ID (0 = use pid)
+/// * `pid` - Target process ID
+/// * `signal` - Signal number to send (0 only checks that `pid` exists)
+/// * `value` - Integer or pointer passed to the receiver
 ///
 /// # Returns
-/// 0 on success, or -1 on error
-///
-/// # Validation
-/// - Can only set pgid for self or children
-/// - Must be in same session
-/// - Cannot move process to different session
-fn sys_setpgid(pid: usize, pgid: usize) -> isize {
-    use crate::sched::process_group::{Pgid, Pid};
-
-    // Get current task
-    let current_id = match crate::sched::get_current_task_info() {
-        Some((id, _)) => id,
-        None => {
-            serial_println!("[SYSCALL] sys_setpgid: no current task");
-            return -1;
-        }
-    };
-
-    // Determine target PID (0 means current process)
-    let target_pid: Pid = if pid == 0 { current_id } else { pid };
+/// 0 on success, or -1 on error (EAGAIN if the target's queue is full)
+fn sys_sigqueue(pid: usize, signal: usize, value: usize) -> isize {
+    use crate::signal::{send_signal_info, signals, SigSource};
 
-    // Determine target PGID (0 means use target's PID)
-    let target_pgid: Pgid = if pgid == 0 { target_pid } else { pgid };
+    if signal >= signals::MAX_SIGNAL as usize || pid == 0 || pid >= 0x8000_0000 {
+        return -1; // EINVAL
+    }
+    if pid == 1 && (signal == signals::SIGKILL as usize || signal == signals::SIGSTOP as usize) {
+        return -1; // EPERM
+    }
 
-    // Get current task info
-    let current_task = match crate::sched::get_task_mut(current_id) {
-        Some(t) => t,
-        None => {
-            serial_println!("[SYSCALL] sys_setpgid: current task not found");
-            return -1;
-        }
+    let source = match crate::sched::current_task() {
+        Some(task) => SigSource::Queue {
+            pid: task.pid,
+            uid: task.creds.uid,
+            value: value as u64,
+        },
+        None => return -1,
     };
-
-    let current_sid = current_task.sid;
-
-    // Get target task
-    let target_task = match crate::sched::get_task_mut(target_pid) {
-        Some(t) => t,
-        None => {
-            serial_println!(
-                "[SYSCALL] sys_setpgid: target process {} not found",
-                target_pid
-            );
-            return -1; // ESRCH - no such process
-        }
+    let target = match crate::sched::get_task_mut(pid) {
+        Some(target) => target,
+        None => return -1, // ESRCH
     };
-
-    // Validation: can only set pgid for self or children
-    if target_pid != current_id && target_task.ppid != current_id {
-        serial_println!("[SYSCALL] sys_setpgid: not self or child");
+    if !may_signal(target, signal) {
         return -1; // EPERM
     }
-
-    // Validation: must be in same session
-    if target_task.sid != current_sid {
-        serial_println!("[SYSCALL] sys_setpgid: not in same session");
-        return -1; // EPERM
+    if signal == 0 {
+        return 0;
     }
 
-    // Set the process group
-    let old_pgid = target_task.pgid;
-    target_task.pgid = target_pgid;
+    match send_signal_info(target, signal as u32, source) {
+        Ok(()) => 0,
+        Err(()) => -1, // EAGAIN
+    }
+}
 
-    serial_println!(
-        "[SYSCALL] sys_setpgid: set PID {} PGID from {} to {}",
-        target_pid,
-        old_pgid,
-        target_pgid
-    );
+/// Whether the caller may send `signal` to `target`
+///
+/// See `signal::security::check_signal_permission`: the sender needs a
+/// real or effective UID matching the target's real or saved UID, unless
+/// it is root or sends a job control signal within its session.
+fn may_signal(target: &crate::sched::task::Task, signal: usize) -> bool {
+    use crate::signal::security::check_signal_permission;
 
-    0
+    match crate::sched::current_task() {
+        Some(sender) => check_signal_permission(sender, target, signal as u32).is_ok(),
+        None => true,
+    }
 }
 
-/// sys_getpgrp handler - Get current process group ID
+/// sys_ptrace handler - Trace and control another task
+///
+/// See `user::ptrace`. Except for `PTRACE_TRACEME` and `PTRACE_ATTACH`,
+/// `tid` must be a tracee of the caller, stopped for all but
+/// `PTRACE_KILL`.
+///
+/// # Arguments
+/// * `request` - `PTRACE_*` request
+/// * `tid` - Task to act on
+/// * `addr` - Address in the tracee (PEEK/POKE)
+/// * `data` (R10) - Pointer to the result (PEEK), the word to write
+///   (POKE), a pointer to `user_regs_struct` (GETREGS/SETREGS), the
+///   signal to deliver (CONT/SYSCALL/SINGLESTEP/DETACH) or the options
+///   (SETOPTIONS)
 ///
 /// # Returns
-/// Process group ID of current process
-fn sys_getpgrp() -> isize {
-    // Get current task
-    let current_id = match crate::sched::get_current_task_info() {
-        Some((id, _)) => id,
-        None => {
-            serial_println!("[SYSCALL] sys_getpgrp: no current task");
-            return -1;
+/// 0 on success, or -1 on error
+fn sys_ptrace(request: usize, tid: usize, addr: usize) -> isize {
+    use crate::user::ptrace::{self, Resume, UserRegs};
+
+    let data = extra_syscall_args().map_or(0, |(data, _, _)| data);
+    let regs_size = core::mem::size_of::<UserRegs>();
+    let result = match request {
+        ptrace::PTRACE_TRACEME => ptrace::trace_me(),
+        ptrace::PTRACE_ATTACH => ptrace::attach(tid),
+        ptrace::PTRACE_PEEKTEXT | ptrace::PTRACE_PEEKDATA => {
+            if !validate_user_buffer(data, core::mem::size_of::<u64>()) {
+                return -1; // EFAULT
+            }
+            ptrace::peek(tid, addr).map(|word| unsafe { *(data as *mut u64) = word })
+        }
+        ptrace::PTRACE_POKETEXT | ptrace::PTRACE_POKEDATA => ptrace::poke(tid, addr, data as u64),
+        ptrace::PTRACE_CONT => ptrace::resume(tid, Resume::Continue, data as u32),
+        ptrace::PTRACE_SYSCALL => ptrace::resume(tid, Resume::Syscall, data as u32),
+        ptrace::PTRACE_SINGLESTEP => ptrace::resume(tid, Resume::SingleStep, data as u32),
+        ptrace::PTRACE_DETACH => ptrace::detach(tid, data as u32),
+        ptrace::PTRACE_KILL => ptrace::kill(tid),
+        ptrace::PTRACE_SETOPTIONS => ptrace::set_options(tid, data as u64),
+        ptrace::PTRACE_GETREGS => {
+            if !validate_user_buffer(data, regs_size) {
+                return -1; // EFAULT
+            }
+            ptrace::get_regs(tid).map(|regs| unsafe { *(data as *mut UserRegs) = regs })
+        }
+        ptrace::PTRACE_SETREGS => {
+            if !validate_user_buffer(data, regs_size) {
+                return -1; // EFAULT
+            }
+            ptrace::set_regs(tid, unsafe { &*(data as *const UserRegs) })
         }
+        _ => return -1, // EIO
     };
 
-    let task = match crate::sched::get_task_mut(current_id) {
-        Some(t) => t,
-        None => {
-            serial_println!("[SYSCALL] sys_getpgrp: task not found");
-            return -1;
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!(
+                "[SYSCALL] sys_ptrace: request {} on {}: {:?}",
+                request,
+                tid,
+                e
+            );
+            -1
         }
-    };
+    }
+}
 
-    let pgid = task.pgid;
-    serial_println!("[SYSCALL] sys_getpgrp: returning PGID {}", pgid);
-    pgid as isize
+/// sys_getuid handler - Get the real user ID
+///
+/// # Returns
+/// The caller's real UID
+fn sys_getuid() -> isize {
+    crate::user::creds::current().ruid as isize
 }
 
-/// sys_setsid handler - Create a new session
+/// sys_geteuid handler - Get the effective user ID
 ///
 /// # Returns
-/// New session ID on success, or -1 on error
+/// The caller's effective UID
+fn sys_geteuid() -> isize {
+    crate::user::creds::current().uid as isize
+}
+
+/// sys_getgid handler - Get the real group ID
 ///
-/// # Behavior
-/// - Creates new session with sid = pid
-/// - Creates new process group with pgid = pid
-/// - Detaches from controlling terminal
-/// - Fails if caller is already a process group leader
-fn sys_setsid() -> isize {
-    // Get current task
-    let current_id = match crate::sched::get_current_task_info() {
-        Some((id, _)) => id,
-        None => {
-            serial_println!("[SYSCALL] sys_setsid: no current task");
-            return -1;
-        }
-    };
+/// # Returns
+/// The caller's real GID
+fn sys_getgid() -> isize {
+    crate::user::creds::current().rgid as isize
+}
 
-    let task = match crate::sched::get_task_mut(current_id) {
-        Some(t) => t,
-        None => {
-            serial_println!("[SYSCALL] sys_setsid: task not found");
-            return -1;
+/// sys_getegid handler - Get the effective group ID
+///
+/// # Returns
+/// The caller's effective GID
+fn sys_getegid() -> isize {
+    crate::user::creds::current().gid as isize
+}
+
+/// Result of a credential change
+fn creds_result(name: &str, result: Result<(), ProcessError>) -> isize {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] {}: {:?}", name, e);
+            -1 // EPERM or EINVAL
         }
-    };
+    }
+}
 
-    // Cannot create session if already a process group leader
+/// sys_setuid handler - Set the user ID
+///
+/// With `CAP_SETUID` the real, effective and saved UIDs are all set;
+/// otherwise only the effective UID may be set, to the real or saved UID
+/// (see `user::creds`). The change applies to all threads of the process.
+///
+/// # Arguments
+/// * `uid` - New user ID
+///
+/// # Returns
+/// 0 on success, or -1 on error (EPERM)
+fn sys_setuid(uid: usize) -> isize {
+    use crate::user::creds;
+    creds_result(
+        "sys_setuid",
+        creds::update(|current| creds::setuid(current, uid as u32)),
+    )
+}
+
+/// sys_setgid handler - Set the group ID
+///
+/// Like `sys_setuid`, for the group IDs.
+///
+/// # Arguments
+/// * `gid` - New group ID
+///
+/// # Returns
+/// 0 on success, or -1 on error (EPERM)
+fn sys_setgid(gid: usize) -> isize {
+    use crate::user::creds;
+    creds_result(
+        "sys_setgid",
+        creds::update(|current| creds::setgid(current, gid as u32)),
+    )
+}
+
+/// sys_setreuid handler - Set the real and effective user IDs
+///
+/// # Arguments
+/// * `ruid` - New real UID (-1 to leave unchanged)
+/// * `euid` - New effective UID (-1 to leave unchanged)
+///
+/// # Returns
+/// 0 on success, or -1 on error (EPERM)
+fn sys_setreuid(ruid: usize, euid: usize) -> isize {
+    use crate::user::creds;
+    creds_result(
+        "sys_setreuid",
+        creds::update(|current| creds::setreuid(current, optional_id(ruid), optional_id(euid))),
+    )
+}
+
+/// sys_setregid handler - Set the real and effective group IDs
+///
+/// # Arguments
+/// * `rgid` - New real GID (-1 to leave unchanged)
+/// * `egid` - New effective GID (-1 to leave unchanged)
+///
+/// # Returns
+/// 0 on success, or -1 on error (EPERM)
+fn sys_setregid(rgid: usize, egid: usize) -> isize {
+    use crate::user::creds;
+    creds_result(
+        "sys_setregid",
+        creds::update(|current| creds::setregid(current, optional_id(rgid), optional_id(egid))),
+    )
+}
+
+/// sys_setresuid handler - Set the real, effective and saved user IDs
+///
+/// # Arguments
+/// * `ruid` - New real UID (-1 to leave unchanged)
+/// * `euid` - New effective UID (-1 to leave unchanged)
+/// * `suid` - New saved UID (-1 to leave unchanged)
+///
+/// # Returns
+/// 0 on success, or -1 on error (EPERM)
+fn sys_setresuid(ruid: usize, euid: usize, suid: usize) -> isize {
+    use crate::user::creds;
+    let (ruid, euid, suid) = (optional_id(ruid), optional_id(euid), optional_id(suid));
+    creds_result(
+        "sys_setresuid",
+        creds::update(|current| creds::setresuid(current, ruid, euid, suid)),
+    )
+}
+
+/// sys_setresgid handler - Set the real, effective and saved group IDs
+///
+/// # Arguments
+/// * `rgid` - New real GID (-1 to leave unchanged)
+/// * `egid` - New effective GID (-1 to leave unchanged)
+/// * `sgid` - New saved GID (-1 to leave unchanged)
+///
+/// # Returns
+/// 0 on success, or -1 on error (EPERM)
+fn sys_setresgid(rgid: usize, egid: usize, sgid: usize) -> isize {
+    use crate::user::creds;
+    let (rgid, egid, sgid) = (optional_id(rgid), optional_id(egid), optional_id(sgid));
+    creds_result(
+        "sys_setresgid",
+        creds::update(|current| creds::setresgid(current, rgid, egid, sgid)),
+    )
+}
+
+/// Store three IDs at user pointers
+fn write_ids(ptrs: [usize; 3], ids: [u32; 3]) -> isize {
+    if ptrs
+        .iter()
+        .any(|&ptr| !validate_user_buffer(ptr, core::mem::size_of::<u32>()))
+    {
+        return -1; // EFAULT
+    }
+    for (ptr, id) in ptrs.iter().zip(ids) {
+        unsafe { *(*ptr as *mut u32) = id };
+    }
+    0
+}
+
+/// sys_getresuid handler - Get the real, effective and saved user IDs
+///
+/// # Arguments
+/// * `ruid_ptr` - Where to store the real UID
+/// * `euid_ptr` - Where to store the effective UID
+/// * `suid_ptr` - Where to store the saved UID
+///
+/// # Returns
+/// 0 on success, or -1 on error (EFAULT)
+fn sys_getresuid(ruid_ptr: usize, euid_ptr: usize, suid_ptr: usize) -> isize {
+    let creds = crate::user::creds::current();
+    write_ids(
+        [ruid_ptr, euid_ptr, suid_ptr],
+        [creds.ruid, creds.uid, creds.suid],
+    )
+}
+
+/// sys_getresgid handler - Get the real, effective and saved group IDs
+///
+/// # Arguments
+/// * `rgid_ptr` - Where to store the real GID
+/// * `egid_ptr` - Where to store the effective GID
+/// * `sgid_ptr` - Where to store the saved GID
+///
+/// # Returns
+/// 0 on success, or -1 on error (EFAULT)
+fn sys_getresgid(rgid_ptr: usize, egid_ptr: usize, sgid_ptr: usize) -> isize {
+    let creds = crate::user::creds::current();
+    write_ids(
+        [rgid_ptr, egid_ptr, sgid_ptr],
+        [creds.rgid, creds.gid, creds.sgid],
+    )
+}
+
+/// sys_getgroups handler - Get the supplementary group IDs
+///
+/// # Arguments
+/// * `size` - Capacity of `list` in entries; 0 only asks for the count
+/// * `list` - Array of `u32` to fill
+///
+/// # Returns
+/// Number of supplementary groups, or -1 on error (EINVAL if `size` is
+/// too small, EFAULT)
+fn sys_getgroups(size: usize, list: usize) -> isize {
+    let creds = crate::user::creds::current();
+    let groups = creds.groups();
+    if size == 0 {
+        return groups.len() as isize;
+    }
+    if size < groups.len() {
+        return -1; // EINVAL
+    }
+    if !groups.is_empty() && !validate_user_buffer(list, core::mem::size_of_val(groups)) {
+        return -1; // EFAULT
+    }
+    for (i, gid) in groups.iter().enumerate() {
+        unsafe { *(list as *mut u32).add(i) = *gid };
+    }
+    groups.len() as isize
+}
+
+/// sys_setgroups handler - Set the supplementary group IDs
+///
+/// Needs `CAP_SETGID`.
+///
+/// # Arguments
+/// * `size` - Number of entries in `list` (at most `NGROUPS_MAX`)
+/// * `list` - Array of `u32` group IDs
+///
+/// # Returns
+/// 0 on success, or -1 on error (EPERM, EINVAL, EFAULT)
+fn sys_setgroups(size: usize, list: usize) -> isize {
+    use crate::sched::task::NGROUPS_MAX;
+    use crate::user::creds;
+
+    if size > NGROUPS_MAX {
+        return -1; // EINVAL
+    }
+    let mut groups = [0u32; NGROUPS_MAX];
+    if size > 0 {
+        if !validate_user_buffer(list, size * core::mem::size_of::<u32>()) {
+            return -1; // EFAULT
+        }
+        for (i, gid) in groups[..size].iter_mut().enumerate() {
+            *gid = unsafe { *(list as *const u32).add(i) };
+        }
+    }
+    creds_result(
+        "sys_setgroups",
+        creds::update(|current| creds::setgroups(current, &groups[..size])),
+    )
+}
+
+/// Read a `capget`/`capset` header, checking its version
+///
+/// An unknown version is answered by storing the supported one in the
+/// header, as Linux does, so user space can retry.
+fn read_cap_header(header_ptr: usize) -> Option<crate::user::capability::CapUserHeader> {
+    use crate::user::capability::{CapUserHeader, LINUX_CAPABILITY_VERSION_3};
+
+    if !validate_user_buffer(header_ptr, core::mem::size_of::<CapUserHeader>()) {
+        return None; // EFAULT
+    }
+    let header = unsafe { &mut *(header_ptr as *mut CapUserHeader) };
+    if header.version != LINUX_CAPABILITY_VERSION_3 {
+        header.version = LINUX_CAPABILITY_VERSION_3;
+        return None; // EINVAL
+    }
+    Some(*header)
+}
+
+/// sys_capget handler - Get the capability sets of a process
+///
+/// # Arguments
+/// * `header_ptr` - `CapUserHeader` with the version and target pid (0 for
+///   the caller)
+/// * `data_ptr` - Two `CapUserData` to fill (low and high 32 bits), or 0 to
+///   only check the version
+///
+/// # Returns
+/// 0 on success, or -1 on error (EINVAL for an unknown version, ESRCH,
+/// EFAULT)
+fn sys_capget(header_ptr: usize, data_ptr: usize) -> isize {
+    use crate::user::capability::CapUserData;
+
+    let header = match read_cap_header(header_ptr) {
+        Some(header) => header,
+        None => return -1, // EINVAL or EFAULT
+    };
+    if data_ptr == 0 {
+        return 0;
+    }
+
+    let caps = if header.pid == 0 {
+        crate::user::creds::current().caps
+    } else {
+        match crate::sched::get_task_by_id(header.pid as usize) {
+            Some(task) => task.creds.caps,
+            None => return -1, // ESRCH
+        }
+    };
+    let data = caps.to_user();
+    if !validate_user_buffer(data_ptr, core::mem::size_of_val(&data)) {
+        return -1; // EFAULT
+    }
+    unsafe { *(data_ptr as *mut [CapUserData; 2]) = data };
+    0
+}
+
+/// sys_capset handler - Set the caller's capability sets
+///
+/// The permitted set may only shrink, the effective set must be within it,
+/// and the inheritable set may only gain permitted capabilities (see
+/// `user::capability::capset`). The change applies to all threads of the
+/// process.
+///
+/// # Arguments
+/// * `header_ptr` - `CapUserHeader` with the version and pid (0 or the
+///   caller's own)
+/// * `data_ptr` - Two `CapUserData` (low and high 32 bits)
+///
+/// # Returns
+/// 0 on success, or -1 on error (EPERM, EINVAL, EFAULT)
+fn sys_capset(header_ptr: usize, data_ptr: usize) -> isize {
+    use crate::user::capability::{self, CapSets, CapUserData};
+    use crate::user::creds;
+
+    let header = match read_cap_header(header_ptr) {
+        Some(header) => header,
+        None => return -1, // EINVAL or EFAULT
+    };
+    let own = crate::sched::current_task().map_or(false, |task| {
+        header.pid as usize == task.pid || header.pid as usize == task.id
+    });
+    if header.pid != 0 && !own {
+        return -1; // EPERM
+    }
+    if !validate_user_buffer(data_ptr, core::mem::size_of::<[CapUserData; 2]>()) {
+        return -1; // EFAULT
+    }
+    let data = unsafe { *(data_ptr as *const [CapUserData; 2]) };
+    creds_result(
+        "sys_capset",
+        creds::update(|current| {
+            capability::capset(current, CapSets::from_user(&data, current.caps.bounding))
+        }),
+    )
+}
+
+// prctl options
+const PR_GET_DUMPABLE: usize = 3;
+const PR_SET_DUMPABLE: usize = 4; // allow core dumps and ptrace attach
+const PR_SET_SECCOMP: usize = 22; // install a syscall filter
+const PR_CAPBSET_READ: usize = 23; // is a capability in the bounding set
+const PR_CAPBSET_DROP: usize = 24; // drop a capability from the bounding set
+const PR_SET_NO_NEW_PRIVS: usize = 38; // exec may not grant privileges
+const PR_GET_NO_NEW_PRIVS: usize = 39;
+
+/// sys_prctl handler - Process control operations
+///
+/// # Arguments
+/// * `option` - `PR_*` option
+/// * `arg2` - Capability number (`PR_CAPBSET_*`), `SECCOMP_MODE_FILTER`
+///   (`PR_SET_SECCOMP`), 1 (`PR_SET_NO_NEW_PRIVS`) or 0 or 1
+///   (`PR_SET_DUMPABLE`)
+/// * `arg3` - `SeccompProg` pointer (`PR_SET_SECCOMP`)
+///
+/// # Returns
+/// 1 or 0 for `PR_CAPBSET_READ`, `PR_GET_NO_NEW_PRIVS` and
+/// `PR_GET_DUMPABLE`, 0 for the
+/// others, or -1 on error (EINVAL for an unknown option, capability or
+/// filter, EPERM without `CAP_SETPCAP` or, for a filter, without
+/// `no_new_privs` or `CAP_SYS_ADMIN`, EFAULT)
+fn sys_prctl(option: usize, arg2: usize, arg3: usize) -> isize {
+    use crate::user::capability;
+    use crate::user::creds;
+
+    match option {
+        PR_SET_SECCOMP => {
+            if arg2 != crate::user::seccomp::SECCOMP_MODE_FILTER {
+                return -1; // EINVAL
+            }
+            set_seccomp(arg3)
+        }
+        PR_SET_NO_NEW_PRIVS => {
+            if arg2 != 1 {
+                return -1; // EINVAL
+            }
+            match crate::sched::current_task_mut() {
+                Some(task) => {
+                    task.no_new_privs = true;
+                    0
+                }
+                None => -1,
+            }
+        }
+        PR_GET_NO_NEW_PRIVS => {
+            crate::sched::current_task().map_or(-1, |task| task.no_new_privs as isize)
+        }
+        PR_SET_DUMPABLE => {
+            if arg2 > 1 {
+                return -1; // EINVAL
+            }
+            match crate::sched::current_task_mut() {
+                Some(task) => {
+                    task.dumpable = arg2 == 1;
+                    0
+                }
+                None => -1,
+            }
+        }
+        PR_GET_DUMPABLE => crate::sched::current_task().map_or(-1, |task| task.dumpable as isize),
+        PR_CAPBSET_READ | PR_CAPBSET_DROP => {
+            let cap = match capability::from_number(arg2) {
+                Some(cap) => cap,
+                None => return -1, // EINVAL
+            };
+            if option == PR_CAPBSET_READ {
+                return creds::current().caps.bounding.contains(cap) as isize;
+            }
+            creds_result(
+                "sys_prctl",
+                creds::update(|current| capability::drop_bounding(current, cap)),
+            )
+        }
+        _ => {
+            serial_println!("[SYSCALL] sys_prctl: unsupported option {}", option);
+            -1 // EINVAL
+        }
+    }
+}
+
+/// Install the syscall filter described by the `SeccompProg` at `prog_ptr`
+fn set_seccomp(prog_ptr: usize) -> isize {
+    use crate::user::seccomp::{self, SeccompProg, SeccompRule, MAX_RULES};
+
+    if !validate_user_buffer(prog_ptr, core::mem::size_of::<SeccompProg>()) {
+        return -1; // EFAULT
+    }
+    let prog = unsafe { *(prog_ptr as *const SeccompProg) };
+    let count = prog.count as usize;
+    if count > MAX_RULES {
+        return -1; // EINVAL
+    }
+    let size = count * core::mem::size_of::<SeccompRule>();
+    if count > 0 && !validate_user_buffer(prog.rules as usize, size) {
+        return -1; // EFAULT
+    }
+    let rules = if count > 0 {
+        unsafe { core::slice::from_raw_parts(prog.rules as *const SeccompRule, count) }
+    } else {
+        &[]
+    };
+    match seccomp::install(prog.default_action, rules) {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_prctl: PR_SET_SECCOMP: {:?}", e);
+            -1 // EPERM or EINVAL
+        }
+    }
+}
+
+/// sys_prlimit handler - Get and set the resource limits of a process
+///
+/// Also serves `getrlimit` (no new limits) and `setrlimit` (no old ones)
+/// for the caller. Raising a hard limit needs `CAP_SYS_RESOURCE`; another
+/// process's limits need it too, unless the caller's real IDs match all of
+/// the target's (see `user::rlimit`).
+///
+/// # Arguments
+/// * `pid` - Target process, or 0 for the caller
+/// * `resource` - `RLIMIT_*` number
+/// * `new_ptr` - `RLimit` to install, or 0
+/// * `old_ptr` - `RLimit` to fill with the previous limits, or 0
+///
+/// # Returns
+/// 0 on success, or -1 on error (EINVAL for an unknown resource or a soft
+/// limit over the hard one, EPERM, ESRCH, EFAULT)
+fn sys_prlimit(pid: usize, resource: usize, new_ptr: usize, old_ptr: usize) -> isize {
+    use crate::user::rlimit::{self, RLimit};
+
+    let size = core::mem::size_of::<RLimit>();
+    if (new_ptr != 0 && !validate_user_buffer(new_ptr, size))
+        || (old_ptr != 0 && !validate_user_buffer(old_ptr, size))
+    {
+        return -1; // EFAULT
+    }
+    let new = (new_ptr != 0).then(|| unsafe { *(new_ptr as *const RLimit) });
+
+    match rlimit::prlimit(pid, resource, new) {
+        Ok(old) => {
+            if old_ptr != 0 {
+                unsafe { *(old_ptr as *mut RLimit) = old };
+            }
+            0
+        }
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_prlimit: {:?}", e);
+            -1 // EINVAL, EPERM or ESRCH
+        }
+    }
+}
+
+/// sys_mmap handler - Map memory into the caller's address space
+///
+/// Pages are populated on first access; large anonymous mappings are 2MB
+/// aligned so that they can be backed by huge pages.
+///
+/// # Arguments
+/// * `addr` - Requested address (0 = let the kernel choose)
+/// * `length` - Length in bytes
+/// * `prot` - `PROT_*` flags
+/// * `flags` - `MAP_*` flags
+/// * `fd` - File to map, unless `MAP_ANONYMOUS`
+/// * `offset` - Offset in the file
+///
+/// # Returns
+/// Start of the mapping, or -1 on error (EINVAL, EBADF, ENOMEM)
+fn sys_mmap(
+    addr: usize,
+    length: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    use crate::mm::mmap::{self, MmapFlags, ProtFlags};
+
+    let flags = MmapFlags::from_bits(flags as u32);
+    let fd = if flags.is_anonymous() {
+        -1
+    } else if get_fd(fd).is_some() {
+        fd as i32
+    } else {
+        return -1; // EBADF
+    };
+
+    let prot = ProtFlags::from_bits(prot as u8);
+    match mmap::sys_mmap(addr as u64, length, prot, flags, fd, offset as u64) {
+        Ok(vaddr) => vaddr as isize,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_mmap: {}", e);
+            -1 // EINVAL or ENOMEM
+        }
+    }
+}
+
+/// sys_munmap handler - Remove mappings and free the pages behind them
+///
+/// # Arguments
+/// * `addr` - Start of the range (page aligned)
+/// * `length` - Length in bytes
+///
+/// # Returns
+/// 0 on success, or -1 on error (EINVAL)
+fn sys_munmap(addr: usize, length: usize) -> isize {
+    match crate::mm::mmap::sys_munmap(addr as u64, length) {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_munmap: {}", e);
+            -1 // EINVAL
+        }
+    }
+}
+
+/// sys_mprotect handler - Change the protection of mapped pages
+///
+/// # Arguments
+/// * `addr` - Start of the range (page aligned)
+/// * `length` - Length in bytes
+/// * `prot` - New `PROT_*` flags
+///
+/// # Returns
+/// 0 on success, or -1 on error (EINVAL, ENOMEM)
+fn sys_mprotect(addr: usize, length: usize, prot: usize) -> isize {
+    use crate::mm::mmap::ProtFlags;
+
+    match crate::mm::mmap::sys_mprotect(addr as u64, length, ProtFlags::from_bits(prot as u8)) {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_mprotect: {}", e);
+            -1 // EINVAL or ENOMEM
+        }
+    }
+}
+
+/// sys_rt_sigreturn handler - Return from a signal handler
+///
+/// Called by the handler's restorer. Restores the registers, FPU/SSE state
+/// and signal mask saved in the signal frame when the signal was
+/// delivered. A frame that does not pass validation kills the process
+/// with SIGSEGV.
+///
+/// # Returns
+/// The saved RAX, so the interrupted code carries on unchanged
+fn sys_rt_sigreturn() -> isize {
+    let frame = match crate::sched::current_task() {
+        Some(task) if !task.syscall_frame.is_null() => task.syscall_frame,
+        _ => return -1, // ENOSYS
+    };
+
+    match crate::signal::frame::restore_signal_frame(unsafe { &mut *frame }) {
+        Ok(rax) => rax as isize,
+        Err(()) => {
+            serial_println!("[SYSCALL] sys_rt_sigreturn: bad signal frame");
+            let signal = crate::signal::signals::SIGSEGV;
+            crate::user::thread::exit_group(WaitStatus::Signaled {
+                signal,
+                core_dumped: crate::user::coredump::dump_core(signal, unsafe { &*frame }),
+            })
+        }
+    }
+}
+
+/// Read a signal set from user memory
+fn read_user_sigset(ptr: usize) -> Option<u64> {
+    if !validate_user_buffer(ptr, core::mem::size_of::<u64>()) {
+        return None;
+    }
+    Some(unsafe { *(ptr as *const u64) })
+}
+
+/// sys_rt_sigprocmask handler - Examine and change the signal mask
+///
+/// SIGKILL and SIGSTOP are left out of any set silently.
+///
+/// # Arguments
+/// * `how` - `SIG_BLOCK`, `SIG_UNBLOCK` or `SIG_SETMASK`
+/// * `set_ptr` - Pointer to the signal set to apply (or 0 to only query)
+/// * `oldset_ptr` - Pointer to store the previous mask (or 0 to ignore)
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_rt_sigprocmask(how: usize, set_ptr: usize, oldset_ptr: usize) -> isize {
+    use crate::signal::sigmask::{SIG_BLOCK, SIG_SETMASK, SIG_UNBLOCK};
+    use crate::signal::UNBLOCKABLE;
+
+    let set = match set_ptr {
+        0 => None,
+        ptr => match read_user_sigset(ptr) {
+            Some(set) => Some(set & !UNBLOCKABLE),
+            None => return -1, // EFAULT
+        },
+    };
+    if oldset_ptr != 0 && !validate_user_buffer(oldset_ptr, core::mem::size_of::<u64>()) {
+        return -1; // EFAULT
+    }
+    let task = match crate::sched::current_task() {
+        Some(task) => task,
+        None => return -1,
+    };
+
+    let old_mask = task.get_signal_mask();
+    if let Some(set) = set {
+        match i32::try_from(how) {
+            Ok(SIG_BLOCK) => task.block_signals(set),
+            Ok(SIG_UNBLOCK) => task.unblock_signals(set),
+            Ok(SIG_SETMASK) => task.set_signal_mask(set),
+            _ => return -1, // EINVAL
+        }
+    }
+    if oldset_ptr != 0 {
+        unsafe {
+            *(oldset_ptr as *mut u64) = old_mask;
+        }
+    }
+    0
+}
+
+/// sys_rt_sigpending handler - Get the signals pending while blocked
+///
+/// # Arguments
+/// * `set_ptr` - Pointer to store the signal set
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_rt_sigpending(set_ptr: usize) -> isize {
+    use core::sync::atomic::Ordering;
+
+    if !validate_user_buffer(set_ptr, core::mem::size_of::<u64>()) {
+        return -1; // EFAULT
+    }
+    let task = match crate::sched::current_task() {
+        Some(task) => task,
+        None => return -1,
+    };
+
+    let pending = task.pending_signals.load(Ordering::Acquire) & task.get_signal_mask();
+    unsafe {
+        *(set_ptr as *mut u64) = pending;
+    }
+    0
+}
+
+/// sys_rt_sigsuspend handler - Wait for a signal with a temporary mask
+///
+/// Replaces the signal mask until a signal is delivered, so unblocking a
+/// signal and waiting for it cannot race. A handler sees, and returns to,
+/// the original mask.
+///
+/// # Arguments
+/// * `mask_ptr` - Pointer to the temporary signal mask
+///
+/// # Returns
+/// -1 (EINTR) once a handler has run
+fn sys_rt_sigsuspend(mask_ptr: usize) -> isize {
+    let mask = match read_user_sigset(mask_ptr) {
+        Some(mask) => mask & !crate::signal::UNBLOCKABLE,
+        None => return -1, // EFAULT
+    };
+    let task = match crate::sched::current_task_mut() {
+        Some(task) => task,
+        None => return -1,
+    };
+
+    task.saved_sigmask = Some(task.get_signal_mask());
+    task.set_signal_mask(mask);
+    while task.next_pending_signal().is_none() {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            crate::signal::block_interruptible(());
+        });
+    }
+
+    // Restarted if the signal had no handler (a stop, say)
+    ERESTARTNOHAND
+}
+
+/// sys_rt_sigtimedwait handler - Wait for one of a set of signals
+///
+/// The signals are normally blocked by the caller; the one returned is
+/// taken off the pending set without running its handler.
+///
+/// # Arguments
+/// * `set_ptr` - Pointer to the set of signals to wait for
+/// * `info_ptr` - Pointer to a `SigInfo` to fill in (or 0 to ignore)
+/// * `timeout_ptr` - Pointer to the longest Timespec to wait (or 0 to
+///   wait without limit)
+///
+/// # Returns
+/// The signal number, or -1 on error, timeout (EAGAIN) or interruption
+/// by another signal (EINTR)
+fn sys_rt_sigtimedwait(set_ptr: usize, info_ptr: usize, timeout_ptr: usize) -> isize {
+    use crate::sched::hrtimer;
+    use crate::signal::SigInfo;
+
+    let set = match read_user_sigset(set_ptr) {
+        Some(set) => set,
+        None => return -1, // EFAULT
+    };
+    if info_ptr != 0 && !validate_user_buffer(info_ptr, core::mem::size_of::<SigInfo>()) {
+        return -1; // EFAULT
+    }
+    let deadline = match timeout_ptr {
+        0 => None,
+        ptr => match read_user_timespec(ptr) {
+            Some(ns) => Some(hrtimer::monotonic_ns().saturating_add(ns)),
+            None => return -1, // EINVAL / EFAULT
+        },
+    };
+
+    match crate::signal::wait_for_signal(set, deadline) {
+        Some((signal, source)) => {
+            if info_ptr != 0 {
+                unsafe {
+                    *(info_ptr as *mut SigInfo) = SigInfo::new(signal, source);
+                }
+            }
+            signal as isize
+        }
+        None => -1, // EAGAIN / EINTR
+    }
+}
+
+/// sys_sigaltstack handler - Set or query the alternate signal stack
+///
+/// Handlers registered with `SA_ONSTACK` run on this stack, so they can
+/// run even after the normal stack has overflowed. The stack cannot be
+/// changed while a handler is running on it.
+///
+/// # Arguments
+/// * `ss_ptr` - Pointer to the new `SignalStack` (or 0 to only query);
+///   `SS_DISABLE` in `ss_flags` removes the stack
+/// * `old_ss_ptr` - Pointer to store the current one (or 0 to ignore);
+///   `ss_flags` reports `SS_ONSTACK` while running on it
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_sigaltstack(ss_ptr: usize, old_ss_ptr: usize) -> isize {
+    use crate::signal::frame::{set_alt_stack, SignalStack};
+
+    let size = core::mem::size_of::<SignalStack>();
+    if (ss_ptr != 0 && !validate_user_buffer(ss_ptr, size))
+        || (old_ss_ptr != 0 && !validate_user_buffer(old_ss_ptr, size))
+    {
+        return -1; // EFAULT
+    }
+    let task = match crate::sched::current_task_mut() {
+        Some(task) if !task.syscall_frame.is_null() => task,
+        _ => return -1,
+    };
+    let sp = unsafe { (*task.syscall_frame).rsp };
+
+    let old = task.alt_stack.report(sp);
+    if ss_ptr != 0 {
+        let new = unsafe { *(ss_ptr as *const SignalStack) };
+        if set_alt_stack(task, &new, sp).is_err() {
+            serial_println!("[SYSCALL] sys_sigaltstack: cannot set stack");
+            return -1; // EPERM / EINVAL / ENOMEM
+        }
+    }
+    if old_ss_ptr != 0 {
+        unsafe {
+            *(old_ss_ptr as *mut SignalStack) = old;
+        }
+    }
+    0
+}
+
+/// sys_setpgid handler - Set process group ID
+///
+/// # Arguments
+/// * `pid` - Process ID to modify (0 = current process)
+/// * `pgid` - New process group ID (0 = use pid)
+///
+/// # Returns
+/// 0 on success, or -1 on error
+///
+/// # Validation
+/// - Can only set pgid for self or children
+/// - Must be in same session
+/// - Cannot move process to different session
+fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    use crate::sched::process_group::{Pgid, Pid};
+
+    // Get current task
+    let current_id = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => {
+            serial_println!("[SYSCALL] sys_setpgid: no current task");
+            return -1;
+        }
+    };
+
+    // Determine target PID (0 means current process)
+    let target_pid: Pid = if pid == 0 { current_id } else { pid };
+
+    // Determine target PGID (0 means use target's PID)
+    let target_pgid: Pgid = if pgid == 0 { target_pid } else { pgid };
+
+    // Get current task info
+    let current_task = match crate::sched::get_task_mut(current_id) {
+        Some(t) => t,
+        None => {
+            serial_println!("[SYSCALL] sys_setpgid: current task not found");
+            return -1;
+        }
+    };
+
+    let current_sid = current_task.sid;
+
+    // Get target task
+    let target_task = match crate::sched::get_task_mut(target_pid) {
+        Some(t) => t,
+        None => {
+            serial_println!(
+                "[SYSCALL] sys_setpgid: target process {} not found",
+                target_pid
+            );
+            return -1; // ESRCH - no such process
+        }
+    };
+
+    // Validation: can only set pgid for self or children
+    if target_pid != current_id && target_task.ppid != current_id {
+        serial_println!("[SYSCALL] sys_setpgid: not self or child");
+        return -1; // EPERM
+    }
+
+    // Validation: must be in same session
+    if target_task.sid != current_sid {
+        serial_println!("[SYSCALL] sys_setpgid: not in same session");
+        return -1; // EPERM
+    }
+
+    // Set the process group
+    let old_pgid = target_task.pgid;
+    target_task.pgid = target_pgid;
+
+    serial_println!(
+        "[SYSCALL] sys_setpgid: set PID {} PGID from {} to {}",
+        target_pid,
+        old_pgid,
+        target_pgid
+    );
+
+    0
+}
+
+/// sys_getpgrp handler - Get current process group ID
+///
+/// # Returns
+/// Process group ID of current process
+fn sys_getpgrp() -> isize {
+    // Get current task
+    let current_id = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => {
+            serial_println!("[SYSCALL] sys_getpgrp: no current task");
+            return -1;
+        }
+    };
+
+    let task = match crate::sched::get_task_mut(current_id) {
+        Some(t) => t,
+        None => {
+            serial_println!("[SYSCALL] sys_getpgrp: task not found");
+            return -1;
+        }
+    };
+
+    let pgid = task.pgid;
+    serial_println!("[SYSCALL] sys_getpgrp: returning PGID {}", pgid);
+    pgid as isize
+}
+
+/// sys_setsid handler - Create a new session
+///
+/// # Returns
+/// New session ID on success, or -1 on error
+///
+/// # Behavior
+/// - Creates new session with sid = pid
+/// - Creates new process group with pgid = pid
+/// - Detaches from controlling terminal
+/// - Fails if caller is already a process group leader
+fn sys_setsid() -> isize {
+    // Get current task
+    let current_id = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => {
+            serial_println!("[SYSCALL] sys_setsid: no current task");
+            return -1;
+        }
+    };
+
+    let task = match crate::sched::get_task_mut(current_id) {
+        Some(t) => t,
+        None => {
+            serial_println!("[SYSCALL] sys_setsid: task not found");
+            return -1;
+        }
+    };
+
+    // Cannot create session if already a process group leader
     if task.pgid == task.pid {
         serial_println!("[SYSCALL] sys_setsid: already a process group leader");
         return -1; // EPERM
     }
 
-    // Create new session
-    let new_sid = task.pid;
-    let new_pgid = task.pid;
+    // Create new session
+    let new_sid = task.pid;
+    let new_pgid = task.pid;
+
+    task.sid = new_sid;
+    task.pgid = new_pgid;
+    task.tty = None; // Detach from controlling terminal
+
+    serial_println!(
+        "[SYSCALL] sys_setsid: created new session {} for PID {}",
+        new_sid,
+        current_id
+    );
+
+    new_sid as isize
+}
+
+/// sys_getsid handler - Get session ID of a process
+///
+/// # Arguments
+/// * `pid` - Process ID to query (0 = current process)
+///
+/// # Returns
+/// Session ID on success, or -1 on error
+fn sys_getsid(pid: usize) -> isize {
+    use crate::sched::process_group::Pid;
+
+    // Get current task
+    let current_id = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => {
+            serial_println!("[SYSCALL] sys_getsid: no current task");
+            return -1;
+        }
+    };
+
+    // Determine target PID (0 means current process)
+    let target_pid: Pid = if pid == 0 { current_id } else { pid };
+
+    // Get target task
+    let task = match crate::sched::get_task_mut(target_pid) {
+        Some(t) => t,
+        None => {
+            serial_println!("[SYSCALL] sys_getsid: process {} not found", target_pid);
+            return -1; // ESRCH - no such process
+        }
+    };
+
+    let sid = task.sid;
+    serial_println!("[SYSCALL] sys_getsid: PID {} has SID {}", target_pid, sid);
+    sid as isize
+}
+
+// setpriority/getpriority "which" values
+const PRIO_PROCESS: usize = 0; // who = PID
+const PRIO_PGRP: usize = 1; // who = process group ID
+const PRIO_USER: usize = 2; // who = user ID
+
+/// Check whether `caller` may set `target`'s nice value to `nice`
+///
+/// `CAP_SYS_NICE` may do anything; other users may only raise (weaken) the
+/// nice value of their own tasks.
+fn may_renice(caller: &crate::sched::Task, target: &crate::sched::Task, nice: i8) -> bool {
+    caller.creds.capable(Capabilities::CAP_SYS_NICE)
+        || (caller.creds.uid == target.creds.uid && nice >= target.se.nice)
+}
+
+/// Collect the IDs of the tasks selected by a (which, who) pair
+///
+/// `who == 0` selects the caller's own process, group or user.
+fn priority_targets(
+    which: usize,
+    who: usize,
+    caller: &crate::sched::Task,
+) -> Option<alloc::vec::Vec<usize>> {
+    let mut targets = alloc::vec::Vec::new();
+
+    match which {
+        PRIO_PROCESS => {
+            let pid = if who == 0 { caller.id } else { who };
+            crate::sched::get_task_by_id(pid)?;
+            targets.push(pid);
+        }
+        PRIO_PGRP => {
+            let pgid = if who == 0 { caller.pgid } else { who };
+            crate::sched::for_each_task_in_group(pgid, |task| targets.push(task.id));
+        }
+        PRIO_USER => {
+            let uid = if who == 0 {
+                caller.creds.uid
+            } else {
+                who as u32
+            };
+            crate::sched::for_each_task(|task| {
+                if task.creds.uid == uid {
+                    targets.push(task.id);
+                }
+            });
+        }
+        _ => return None,
+    }
+
+    Some(targets)
+}
+
+/// sys_nice handler - Adjust the calling task's nice value
+///
+/// # Arguments
+/// * `inc` - Signed increment added to the current nice value (clamped to -20..19)
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_nice(inc: usize) -> isize {
+    let task = match crate::sched::current_task() {
+        Some(t) => t,
+        None => return -1,
+    };
+
+    let inc = inc as isize;
+    let nice = (task.se.nice as isize + inc).clamp(-20, 19) as i8;
+
+    if !may_renice(task, task, nice) {
+        serial_println!(
+            "[SYSCALL] sys_nice: task {} may not lower nice to {}",
+            task.id,
+            nice
+        );
+        return -1; // EPERM
+    }
+
+    crate::sched::set_task_nice(task.id, nice);
+    0
+}
+
+/// sys_setpriority handler - Set the nice value of processes
+///
+/// # Arguments
+/// * `which` - PRIO_PROCESS, PRIO_PGRP or PRIO_USER
+/// * `who` - PID, process group ID or user ID (0 = the caller's)
+/// * `prio` - New nice value (signed, clamped to -20..19)
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_setpriority(which: usize, who: usize, prio: usize) -> isize {
+    let caller = match crate::sched::current_task() {
+        Some(t) => t,
+        None => return -1,
+    };
+
+    let nice = (prio as isize).clamp(-20, 19) as i8;
+
+    let targets = match priority_targets(which, who, caller) {
+        Some(targets) if !targets.is_empty() => targets,
+        Some(_) => return -1, // ESRCH
+        None => return -1,    // EINVAL
+    };
+
+    let mut denied = false;
+    for id in targets {
+        let target = match crate::sched::get_task_by_id(id) {
+            Some(t) => t,
+            None => continue,
+        };
+
+        if !may_renice(caller, target, nice) {
+            denied = true;
+            continue;
+        }
+
+        crate::sched::set_task_nice(id, nice);
+    }
+
+    if denied {
+        return -1; // EPERM
+    }
+
+    0
+}
+
+/// sys_getpriority handler - Get the nice value of processes
+///
+/// # Arguments
+/// * `which` - PRIO_PROCESS, PRIO_PGRP or PRIO_USER
+/// * `who` - PID, process group ID or user ID (0 = the caller's)
+///
+/// # Returns
+/// 20 - nice (1..40) of the highest-priority matching task, or -1 on error
+fn sys_getpriority(which: usize, who: usize) -> isize {
+    let caller = match crate::sched::current_task() {
+        Some(t) => t,
+        None => return -1,
+    };
+
+    let targets = match priority_targets(which, who, caller) {
+        Some(targets) => targets,
+        None => return -1, // EINVAL
+    };
+
+    let best = targets
+        .into_iter()
+        .filter_map(crate::sched::get_task_by_id)
+        .map(|task| task.se.nice)
+        .min();
+
+    match best {
+        Some(nice) => 20 - nice as isize,
+        None => -1, // ESRCH
+    }
+}
+
+/// Scheduling parameters passed to sched_setscheduler/sched_getparam
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SchedParam {
+    /// Real-time priority (1..99 for SCHED_FIFO/SCHED_RR, 0 for SCHED_OTHER)
+    pub sched_priority: i32,
+}
+
+/// sys_sched_setscheduler handler - Set scheduling policy and RT priority
+///
+/// # Arguments
+/// * `pid` - Target process (0 = current process)
+/// * `policy` - 0 (SCHED_OTHER), 1 (SCHED_FIFO) or 2 (SCHED_RR)
+/// * `param_ptr` - Pointer to SchedParam
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_sched_setscheduler(pid: usize, policy: usize, param_ptr: usize) -> isize {
+    use crate::sched::rt::{SchedPolicy, MAX_RT_PRIO};
+
+    let policy = match SchedPolicy::from_raw(policy) {
+        Some(p) => p,
+        None => return -1, // EINVAL
+    };
+
+    if !validate_user_buffer(param_ptr, core::mem::size_of::<SchedParam>()) {
+        return -1; // EFAULT
+    }
+    let param = unsafe { *(param_ptr as *const SchedParam) };
+
+    // Real-time policies need 1..99, SCHED_OTHER needs 0
+    let valid = if policy.is_rt() {
+        param.sched_priority >= 1 && (param.sched_priority as usize) < MAX_RT_PRIO
+    } else {
+        param.sched_priority == 0
+    };
+    if !valid {
+        return -1; // EINVAL
+    }
+
+    let caller = match crate::sched::current_task() {
+        Some(t) => t,
+        None => return -1,
+    };
+    let target_id = if pid == 0 { caller.id } else { pid };
+    let target = match crate::sched::get_task_by_id(target_id) {
+        Some(t) => t,
+        None => return -1, // ESRCH
+    };
+
+    // Only CAP_SYS_NICE may enter a real-time class or touch other users' tasks
+    if !caller.creds.capable(Capabilities::CAP_SYS_NICE)
+        && (policy.is_rt() || caller.creds.uid != target.creds.uid)
+    {
+        serial_println!(
+            "[SYSCALL] sys_sched_setscheduler: task {} may not set {:?} on {}",
+            caller.id,
+            policy,
+            target_id
+        );
+        return -1; // EPERM
+    }
+
+    crate::sched::set_task_scheduler(target_id, policy, param.sched_priority as u8);
+    0
+}
+
+/// sys_sched_getscheduler handler - Get scheduling policy
+///
+/// # Arguments
+/// * `pid` - Target process (0 = current process)
+///
+/// # Returns
+/// Policy number on success, or -1 on error
+fn sys_sched_getscheduler(pid: usize) -> isize {
+    let target_id = if pid == 0 {
+        match crate::sched::get_current_task_info() {
+            Some((id, _)) => id,
+            None => return -1,
+        }
+    } else {
+        pid
+    };
+
+    match crate::sched::get_task_by_id(target_id) {
+        Some(task) => task.rt.policy as isize,
+        None => -1, // ESRCH
+    }
+}
+
+/// sys_sched_getparam handler - Get real-time priority
+///
+/// # Arguments
+/// * `pid` - Target process (0 = current process)
+/// * `param_ptr` - Pointer to SchedParam to fill
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_sched_getparam(pid: usize, param_ptr: usize) -> isize {
+    if !validate_user_buffer(param_ptr, core::mem::size_of::<SchedParam>()) {
+        return -1; // EFAULT
+    }
+
+    let target_id = if pid == 0 {
+        match crate::sched::get_current_task_info() {
+            Some((id, _)) => id,
+            None => return -1,
+        }
+    } else {
+        pid
+    };
+
+    let task = match crate::sched::get_task_by_id(target_id) {
+        Some(t) => t,
+        None => return -1, // ESRCH
+    };
+
+    // Report the base priority, not one inherited through an RtMutex
+    let param = SchedParam {
+        sched_priority: task.rt.rt_priority as i32,
+    };
+    unsafe {
+        *(param_ptr as *mut SchedParam) = param;
+    }
+
+    0
+}
+
+/// Interval timer setting used by timer_settime/timer_gettime
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ItimerSpec {
+    /// Reload period (zero for a one-shot timer)
+    pub it_interval: Timespec,
+    /// Time until the next expiry (zero disarms the timer)
+    pub it_value: Timespec,
+}
+
+/// Read a Timespec from user memory
+fn read_user_timespec(ptr: usize) -> Option<u64> {
+    if !validate_user_buffer(ptr, core::mem::size_of::<Timespec>()) {
+        return None;
+    }
+    unsafe { *(ptr as *const Timespec) }.to_ns()
+}
+
+/// sys_nanosleep handler - Sleep with nanosecond resolution
+///
+/// # Arguments
+/// * `req_ptr` - Pointer to the requested Timespec
+/// * `rem_ptr` - Pointer to a Timespec receiving the unslept time if the
+///   sleep is interrupted (may be 0)
+///
+/// # Returns
+/// 0 on success, or -1 on error or interruption
+fn sys_nanosleep(req_ptr: usize, rem_ptr: usize) -> isize {
+    use crate::sched::hrtimer;
+    use core::sync::atomic::Ordering;
+
+    let ns = match read_user_timespec(req_ptr) {
+        Some(ns) => ns,
+        None => return -1, // EINVAL / EFAULT
+    };
+
+    if rem_ptr != 0 && !validate_user_buffer(rem_ptr, core::mem::size_of::<Timespec>()) {
+        return -1; // EFAULT
+    }
+
+    if ns == 0 {
+        return 0;
+    }
+
+    let deadline = hrtimer::monotonic_ns().saturating_add(ns);
+    if hrtimer::sleep_until(deadline) {
+        METRICS.sleep_count.fetch_add(1, Ordering::Relaxed);
+        return 0;
+    }
+
+    if rem_ptr != 0 {
+        let remaining = deadline.saturating_sub(hrtimer::monotonic_ns());
+        unsafe {
+            *(rem_ptr as *mut Timespec) = Timespec::from_ns(remaining);
+        }
+    }
+
+    -1 // EINTR
+}
+
+/// sys_clock_gettime handler - Read a clock
+///
+/// # Arguments
+/// * `clock_id` - 0 (CLOCK_REALTIME) or 1 (CLOCK_MONOTONIC)
+/// * `tp_ptr` - Pointer to Timespec to fill
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_clock_gettime(clock_id: usize, tp_ptr: usize) -> isize {
+    if !validate_user_buffer(tp_ptr, core::mem::size_of::<Timespec>()) {
+        return -1; // EFAULT
+    }
+
+    let ns = match crate::sched::hrtimer::clock_ns(clock_id) {
+        Some(ns) => ns,
+        None => return -1, // EINVAL
+    };
+
+    unsafe {
+        *(tp_ptr as *mut Timespec) = Timespec::from_ns(ns);
+    }
+
+    0
+}
+
+/// sys_timer_create handler - Create an interval timer
+///
+/// # Arguments
+/// * `clock_id` - Clock the timer measures (CLOCK_REALTIME or CLOCK_MONOTONIC)
+/// * `signal` - Signal sent on each expiry (0 = SIGALRM)
+///
+/// # Returns
+/// Timer ID on success, or -1 on error
+fn sys_timer_create(clock_id: usize, signal: usize) -> isize {
+    use crate::signal::signals;
+
+    let signal = if signal == 0 {
+        signals::SIGALRM
+    } else if signal < signals::MAX_SIGNAL as usize {
+        signal as u32
+    } else {
+        return -1; // EINVAL
+    };
+
+    let owner = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => return -1,
+    };
+
+    match crate::sched::hrtimer::timer_create(owner, clock_id, signal) {
+        Some(id) => id as isize,
+        None => -1, // EINVAL / EAGAIN
+    }
+}
+
+/// sys_timer_settime handler - Arm or disarm an interval timer
+///
+/// With TIMER_ABSTIME in `flags`, `it_value` is an absolute time on the
+/// timer's clock. The previous setting is written back to `spec_ptr`.
+///
+/// # Arguments
+/// * `timer_id` - Timer returned by timer_create
+/// * `flags` - 0 or TIMER_ABSTIME (1)
+/// * `spec_ptr` - Pointer to ItimerSpec (in: new setting, out: old setting)
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_timer_settime(timer_id: usize, flags: usize, spec_ptr: usize) -> isize {
+    if !validate_user_buffer(spec_ptr, core::mem::size_of::<ItimerSpec>()) {
+        return -1; // EFAULT
+    }
+    let spec = unsafe { *(spec_ptr as *const ItimerSpec) };
+
+    let (value, interval) = match (spec.it_value.to_ns(), spec.it_interval.to_ns()) {
+        (Some(value), Some(interval)) => (value, interval),
+        _ => return -1, // EINVAL
+    };
+
+    let owner = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => return -1,
+    };
+
+    let (old_value, old_interval) =
+        match crate::sched::hrtimer::timer_settime(timer_id, owner, flags, value, interval) {
+            Some(old) => old,
+            None => return -1, // EINVAL
+        };
+
+    unsafe {
+        *(spec_ptr as *mut ItimerSpec) = ItimerSpec {
+            it_interval: Timespec::from_ns(old_interval),
+            it_value: Timespec::from_ns(old_value),
+        };
+    }
+
+    0
+}
+
+/// sys_timer_gettime handler - Read an interval timer
+///
+/// # Arguments
+/// * `timer_id` - Timer returned by timer_create
+/// * `spec_ptr` - Pointer to ItimerSpec to fill (relative time to expiry)
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_timer_gettime(timer_id: usize, spec_ptr: usize) -> isize {
+    if !validate_user_buffer(spec_ptr, core::mem::size_of::<ItimerSpec>()) {
+        return -1; // EFAULT
+    }
+
+    let owner = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => return -1,
+    };
+
+    let (value, interval) = match crate::sched::hrtimer::timer_gettime(timer_id, owner) {
+        Some(setting) => setting,
+        None => return -1, // EINVAL
+    };
+
+    unsafe {
+        *(spec_ptr as *mut ItimerSpec) = ItimerSpec {
+            it_interval: Timespec::from_ns(interval),
+            it_value: Timespec::from_ns(value),
+        };
+    }
+
+    0
+}
+
+/// sys_timer_delete handler - Delete an interval timer
+///
+/// # Arguments
+/// * `timer_id` - Timer returned by timer_create
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_timer_delete(timer_id: usize) -> isize {
+    let owner = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => return -1,
+    };
+
+    if crate::sched::hrtimer::timer_delete(timer_id, owner) {
+        0
+    } else {
+        -1 // EINVAL
+    }
+}
+
+/// sys_timer_getoverrun handler - Get the number of missed expirations
+///
+/// # Arguments
+/// * `timer_id` - Timer returned by timer_create
+///
+/// # Returns
+/// Expirations missed since the last signal, or -1 on error
+fn sys_timer_getoverrun(timer_id: usize) -> isize {
+    let owner = match crate::sched::get_current_task_info() {
+        Some((id, _)) => id,
+        None => return -1,
+    };
+
+    match crate::sched::hrtimer::timer_getoverrun(timer_id, owner) {
+        Some(overrun) => overrun as isize,
+        None => -1, // EINVAL
+    }
+}
+
+/// sys_sched_setaffinity handler - Restrict the CPUs a task may run on
+///
+/// # Arguments
+/// * `pid` - Target process (0 = current process)
+/// * `len` - Size of the mask buffer in bytes
+/// * `mask_ptr` - Pointer to the CPU mask (bit N = CPU N)
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_sched_setaffinity(pid: usize, len: usize, mask_ptr: usize) -> isize {
+    use crate::sched::cpuset::CpuMask;
+
+    if len == 0 || !validate_user_buffer(mask_ptr, len) {
+        return -1; // EFAULT
+    }
+
+    // Only the first 64 CPUs can be named; longer masks are truncated
+    let mut bits = [0u8; 8];
+    let count = len.min(bits.len());
+    unsafe {
+        core::ptr::copy_nonoverlapping(mask_ptr as *const u8, bits.as_mut_ptr(), count);
+    }
+    let mask = CpuMask::from_bits(u64::from_le_bytes(bits));
+
+    let caller = match crate::sched::current_task() {
+        Some(t) => t,
+        None => return -1,
+    };
+    let target_id = if pid == 0 { caller.id } else { pid };
+    let target = match crate::sched::get_task_by_id(target_id) {
+        Some(t) => t,
+        None => return -1, // ESRCH
+    };
+
+    if !caller.creds.capable(Capabilities::CAP_SYS_NICE) && caller.creds.uid != target.creds.uid {
+        return -1; // EPERM
+    }
+
+    if !crate::sched::set_task_affinity(target_id, mask) {
+        return -1; // EINVAL
+    }
 
-    task.sid = new_sid;
-    task.pgid = new_pgid;
-    task.tty = None; // Detach from controlling terminal
+    // Leave this CPU straight away if it's no longer allowed
+    if target_id == caller.id {
+        let cpu = crate::arch::x86_64::smp::percpu::percpu_current().id;
+        if !crate::sched::task_cpus(caller).contains(cpu) {
+            crate::sched::yield_now();
+        }
+    }
 
-    serial_println!(
-        "[SYSCALL] sys_setsid: created new session {} for PID {}",
-        new_sid,
-        current_id
-    );
+    0
+}
 
-    new_sid as isize
+/// sys_sched_getaffinity handler - Get the CPUs a task may run on
+///
+/// # Arguments
+/// * `pid` - Target process (0 = current process)
+/// * `len` - Size of the mask buffer in bytes (at least 8)
+/// * `mask_ptr` - Pointer to the buffer that receives the CPU mask
+///
+/// # Returns
+/// Number of bytes written on success, or -1 on error
+fn sys_sched_getaffinity(pid: usize, len: usize, mask_ptr: usize) -> isize {
+    const MASK_SIZE: usize = core::mem::size_of::<u64>();
+
+    if len < MASK_SIZE {
+        return -1; // EINVAL
+    }
+    if !validate_user_buffer(mask_ptr, MASK_SIZE) {
+        return -1; // EFAULT
+    }
+
+    let target_id = if pid == 0 {
+        match crate::sched::get_current_task_info() {
+            Some((id, _)) => id,
+            None => return -1,
+        }
+    } else {
+        pid
+    };
+
+    let mask = match crate::sched::get_task_by_id(target_id) {
+        Some(task) => crate::sched::task_cpus(task),
+        None => return -1, // ESRCH
+    };
+
+    unsafe {
+        *(mask_ptr as *mut u64) = mask.bits();
+    }
+    MASK_SIZE as isize
 }
 
-/// sys_getsid handler - Get session ID of a process
+/// Check that the current task may manage cpusets (CAP_SYS_ADMIN)
+fn may_manage_cpusets() -> bool {
+    match crate::sched::current_task() {
+        Some(task) => task.creds.capable(Capabilities::CAP_SYS_ADMIN),
+        None => false,
+    }
+}
+
+/// sys_cpuset_create handler - Create a cpuset
 ///
 /// # Arguments
-/// * `pid` - Process ID to query (0 = current process)
+/// * `parent` - Parent cpuset ID (0 = root cpuset)
+/// * `mask` - CPUs of the new cpuset; must be a subset of the parent's
 ///
 /// # Returns
-/// Session ID on success, or -1 on error
-fn sys_getsid(pid: usize) -> isize {
-    use crate::sched::process_group::Pid;
+/// New cpuset ID on success, or -1 on error
+fn sys_cpuset_create(parent: usize, mask: usize) -> isize {
+    use crate::sched::cpuset::{self, CpuMask};
 
-    // Get current task
-    let current_id = match crate::sched::get_current_task_info() {
-        Some((id, _)) => id,
-        None => {
-            serial_println!("[SYSCALL] sys_getsid: no current task");
-            return -1;
+    if !may_manage_cpusets() {
+        return -1; // EPERM
+    }
+
+    match cpuset::create(parent, CpuMask::from_bits(mask as u64)) {
+        Ok(id) => id as isize,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_cpuset_create: {:?}", e);
+            -1
         }
+    }
+}
+
+/// sys_cpuset_setcpus handler - Change the CPUs of a cpuset
+///
+/// # Arguments
+/// * `id` - Cpuset ID (the root cpuset cannot be changed)
+/// * `mask` - New CPUs; must fit the parent and cover every child
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_cpuset_setcpus(id: usize, mask: usize) -> isize {
+    use crate::sched::cpuset::CpuMask;
+
+    if !may_manage_cpusets() {
+        return -1; // EPERM
+    }
+
+    match crate::sched::set_cpuset_cpus(id, CpuMask::from_bits(mask as u64)) {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_cpuset_setcpus: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// sys_cpuset_attach handler - Move a task into a cpuset
+///
+/// # Arguments
+/// * `id` - Cpuset ID
+/// * `pid` - Target process (0 = current process)
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_cpuset_attach(id: usize, pid: usize) -> isize {
+    if !may_manage_cpusets() {
+        return -1; // EPERM
+    }
+
+    let caller = match crate::sched::current_task() {
+        Some(t) => t,
+        None => return -1,
     };
+    let target_id = if pid == 0 { caller.id } else { pid };
 
-    // Determine target PID (0 means current process)
-    let target_pid: Pid = if pid == 0 { current_id } else { pid };
+    if !crate::sched::set_task_cpuset(target_id, id) {
+        return -1; // ESRCH / EINVAL
+    }
+
+    if target_id == caller.id {
+        let cpu = crate::arch::x86_64::smp::percpu::percpu_current().id;
+        if !crate::sched::task_cpus(caller).contains(cpu) {
+            crate::sched::yield_now();
+        }
+    }
+
+    0
+}
+
+/// sys_cpuset_destroy handler - Remove an empty cpuset
+///
+/// # Arguments
+/// * `id` - Cpuset ID; must have no tasks and no children
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_cpuset_destroy(id: usize) -> isize {
+    if !may_manage_cpusets() {
+        return -1; // EPERM
+    }
+
+    match crate::sched::destroy_cpuset(id) {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_cpuset_destroy: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// sys_clone handler - Create a thread or process
+///
+/// # Arguments
+/// * `flags` - `CLONE_*` flags (see `user::thread`)
+/// * `stack` - Child's stack pointer (0 = the caller's)
+/// * `parent_tid` - Where to store the child's ID for `CLONE_PARENT_SETTID`
+///
+/// R10 holds `child_tid` (for `CLONE_CHILD_SETTID`/`CLONE_CHILD_CLEARTID`)
+/// and R8 the TLS pointer (for `CLONE_SETTLS`).
+///
+/// # Returns
+/// The child's task ID in the caller, 0 in the child, or -1 on error
+fn sys_clone(flags: usize, stack: usize, parent_tid: usize) -> isize {
+    use crate::user::thread::{CLONE_CHILD_CLEARTID, CLONE_CHILD_SETTID, CLONE_PARENT_SETTID};
+
+    let (child_tid, tls, _) = match extra_syscall_args() {
+        Some(args) => args,
+        None => return -1, // ENOSYS
+    };
+
+    if stack >= USER_LIMIT || tls >= USER_LIMIT {
+        return -1; // EINVAL
+    }
+    if flags & CLONE_PARENT_SETTID != 0 && !validate_user_buffer(parent_tid, 4) {
+        return -1; // EFAULT
+    }
+    if flags & (CLONE_CHILD_SETTID | CLONE_CHILD_CLEARTID) != 0
+        && !validate_user_buffer(child_tid, 4)
+    {
+        return -1; // EFAULT
+    }
+
+    match crate::user::thread::clone(flags, stack, parent_tid, child_tid, tls) {
+        Ok(child) => child as isize,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_clone: {:?}", e);
+            -1
+        }
+    }
+}
+
+/// sys_arch_prctl handler - Get or set the thread's FS base
+///
+/// # Arguments
+/// * `code` - `ARCH_SET_FS` or `ARCH_GET_FS`
+/// * `addr` - New FS base, or where to store the current one
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_arch_prctl(code: usize, addr: usize) -> isize {
+    use crate::arch::x86_64::tls::{self, ARCH_GET_FS, ARCH_SET_FS};
+
+    let task = match crate::sched::current_task_mut() {
+        Some(task) => task,
+        None => return -1, // ESRCH
+    };
+
+    match code {
+        ARCH_SET_FS => {
+            // A non-canonical base would fault on the next switch
+            if addr >= USER_LIMIT {
+                return -1; // EPERM
+            }
+            task.context.fs_base = addr as u64;
+            unsafe {
+                tls::write_fs_base(addr as u64);
+            }
+            0
+        }
+        ARCH_GET_FS => {
+            if !validate_user_buffer(addr, core::mem::size_of::<u64>()) {
+                return -1; // EFAULT
+            }
+            unsafe {
+                *(addr as *mut u64) = tls::read_fs_base();
+            }
+            0
+        }
+        _ => -1, // EINVAL
+    }
+}
+
+/// sys_futex handler - Wait on or wake a user-space futex
+///
+/// # Arguments
+/// * `uaddr` - Address of the futex word (4-byte aligned)
+/// * `op` - `FUTEX_WAIT`, `FUTEX_WAKE`, `FUTEX_REQUEUE` or
+///   `FUTEX_CMP_REQUEUE`, optionally with `FUTEX_PRIVATE_FLAG`
+/// * `val` - Expected value (WAIT) or number of tasks to wake
+///
+/// R10 holds the timeout Timespec pointer (WAIT, 0 = none) or the number
+/// of tasks to requeue, R8 the second futex address and R9 the value
+/// compared by `FUTEX_CMP_REQUEUE`.
+///
+/// # Returns
+/// 0 after a wait, the number of tasks woken (or woken and requeued), or
+/// -1 on error, timeout or interruption (a wait without a timeout is
+/// restarted instead)
+fn sys_futex(uaddr: usize, op: usize, val: usize) -> isize {
+    use crate::sched::hrtimer;
+    use crate::sync::futex::{
+        self, FUTEX_CMP_REQUEUE, FUTEX_PRIVATE_FLAG, FUTEX_REQUEUE, FUTEX_WAIT, FUTEX_WAKE,
+    };
+
+    let (arg4, uaddr2, val3) = match extra_syscall_args() {
+        Some(args) => args,
+        None => return -1, // ENOSYS
+    };
+
+    if uaddr % 4 != 0 || !validate_user_buffer(uaddr, 4) {
+        return -1; // EINVAL / EFAULT
+    }
+
+    let result = match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            let deadline = if arg4 != 0 {
+                match read_user_timespec(arg4) {
+                    Some(ns) => Some(hrtimer::monotonic_ns().saturating_add(ns)),
+                    None => return -1, // EINVAL / EFAULT
+                }
+            } else {
+                None
+            };
+            futex::futex_wait(uaddr, val as u32, deadline).map(|()| 0)
+        }
+        FUTEX_WAKE => Ok(futex::futex_wake(uaddr, val)),
+        FUTEX_REQUEUE | FUTEX_CMP_REQUEUE => {
+            if uaddr2 % 4 != 0 || !validate_user_buffer(uaddr2, 4) {
+                return -1; // EINVAL / EFAULT
+            }
+            let expected = (op & !FUTEX_PRIVATE_FLAG == FUTEX_CMP_REQUEUE).then_some(val3 as u32);
+            futex::futex_requeue(uaddr, val, uaddr2, arg4, expected)
+        }
+        _ => return -1, // ENOSYS
+    };
+
+    match result {
+        Ok(count) => count as isize,
+        // A wait without a timeout is restarted
+        Err(futex::FutexError::Interrupted) if arg4 == 0 => ERESTARTSYS,
+        Err(_) => -1, // EAGAIN / ETIMEDOUT / EINTR / EFAULT
+    }
+}
+
+/// sys_exit_group handler - Terminate every thread of the process
+///
+/// # Arguments
+/// * `code` - Exit code
+///
+/// # Returns
+/// Never returns
+fn sys_exit_group(code: usize) -> ! {
+    serial_println!("[SYSCALL] Thread group exiting with code {}", code);
+
+    crate::user::thread::exit_group(WaitStatus::Exited(code as i32))
+}
+
+/// Resource usage reported by wait4 and waitid (Linux `struct rusage`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rusage {
+    /// Time spent in user mode
+    pub ru_utime: Timeval,
+    /// Time spent in the kernel
+    pub ru_stime: Timeval,
+    /// Largest memory footprint (KiB)
+    pub ru_maxrss: i64,
+    /// Shared memory size integral (not tracked)
+    pub ru_ixrss: i64,
+    /// Unshared data size integral (not tracked)
+    pub ru_idrss: i64,
+    /// Unshared stack size integral (not tracked)
+    pub ru_isrss: i64,
+    /// Page faults served without reading a file
+    pub ru_minflt: i64,
+    /// Page faults that read from a file
+    pub ru_majflt: i64,
+    /// Swaps (not tracked)
+    pub ru_nswap: i64,
+    /// Block input operations (not tracked)
+    pub ru_inblock: i64,
+    /// Block output operations (not tracked)
+    pub ru_oublock: i64,
+    /// IPC messages sent (not tracked)
+    pub ru_msgsnd: i64,
+    /// IPC messages received (not tracked)
+    pub ru_msgrcv: i64,
+    /// Signals received (not tracked)
+    pub ru_nsignals: i64,
+    /// Voluntary context switches
+    pub ru_nvcsw: i64,
+    /// Involuntary context switches
+    pub ru_nivcsw: i64,
+}
+
+impl Rusage {
+    /// Convert the kernel's accounting to the user layout
+    fn from_usage(usage: &crate::sched::task::ResourceUsage) -> Self {
+        Self {
+            ru_utime: Timeval::from_ns(usage.utime_ns),
+            ru_stime: Timeval::from_ns(usage.stime_ns),
+            ru_maxrss: (usage.maxrss / 1024) as i64,
+            ru_minflt: usage.minflt as i64,
+            ru_majflt: usage.majflt as i64,
+            ru_nvcsw: usage.nvcsw as i64,
+            ru_nivcsw: usage.nivcsw as i64,
+            ..Self::default()
+        }
+    }
+}
+
+/// SIGCHLD `siginfo` filled in by waitid (Linux `siginfo_t` layout)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ChildSigInfo {
+    /// Signal number (SIGCHLD)
+    pub si_signo: i32,
+    /// Error number (always 0)
+    pub si_errno: i32,
+    /// `CLD_*` code
+    pub si_code: i32,
+    _pad: i32,
+    /// PID of the child
+    pub si_pid: i32,
+    /// User ID of the child
+    pub si_uid: u32,
+    /// Exit code, or the signal that caused the change
+    pub si_status: i32,
+    _pad2: i32,
+    /// User time of the child (clock ticks)
+    pub si_utime: i64,
+    /// System time of the child (clock ticks)
+    pub si_stime: i64,
+    _reserved: [u8; 80],
+}
+
+/// Children selected by a wait4 `pid` argument
+///
+/// # Returns
+/// None if the caller has no process group to look in
+fn wait_target(pid: isize) -> Option<crate::user::process::WaitTarget> {
+    use crate::user::process::WaitTarget;
+
+    match pid {
+        -1 => Some(WaitTarget::Any),
+        0 => crate::sched::current_task().map(|task| WaitTarget::Group(task.pgid)),
+        pid if pid < 0 => Some(WaitTarget::Group(pid.unsigned_abs())),
+        pid => Some(WaitTarget::Pid(pid as usize)),
+    }
+}
+
+/// sys_wait4 handler - Wait for a child to change state
+///
+/// # Arguments
+/// * `pid` - Children to wait for: a PID if positive, -1 for any child, 0
+///   for any child in the caller's process group and `-pgid` for any child
+///   in process group `pgid`
+/// * `status_ptr` - Where to store the status word (may be 0)
+/// * `options` - `WNOHANG`, `WUNTRACED` and `WCONTINUED`
+///
+/// R10 points to a `Rusage` receiving the usage of an exited child and of
+/// the children it collected (may be 0).
+///
+/// # Returns
+/// The child's PID, 0 with `WNOHANG` if no child has changed state yet, or
+/// -1 on error
+fn sys_wait4(pid: usize, status_ptr: usize, options: usize) -> isize {
+    use crate::user::process::{wait_child, WCONTINUED, WEXITED, WNOHANG, WUNTRACED};
+
+    let rusage_ptr = extra_syscall_args().map_or(0, |(r10, _, _)| r10);
+
+    if options & !(WNOHANG | WUNTRACED | WCONTINUED) != 0 {
+        return -1; // EINVAL
+    }
+    if status_ptr != 0 && !validate_user_buffer(status_ptr, 4) {
+        return -1; // EFAULT
+    }
+    if rusage_ptr != 0 && !validate_user_buffer(rusage_ptr, core::mem::size_of::<Rusage>()) {
+        return -1; // EFAULT
+    }
+    let target = match wait_target(pid as isize) {
+        Some(target) => target,
+        None => return -1, // ESRCH
+    };
+
+    match wait_child(target, options | WEXITED) {
+        Ok(Some(event)) => {
+            unsafe {
+                if status_ptr != 0 {
+                    *(status_ptr as *mut i32) = event.status.encode();
+                }
+                if rusage_ptr != 0 {
+                    *(rusage_ptr as *mut Rusage) = Rusage::from_usage(&event.rusage);
+                }
+            }
+            event.pid as isize
+        }
+        Ok(None) => 0,
+        Err(ProcessError::Interrupted) => ERESTARTSYS,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_wait4: {:?}", e);
+            -1 // ECHILD
+        }
+    }
+}
+
+/// sys_waitid handler - Wait for a child to change state, with siginfo
+///
+/// # Arguments
+/// * `idtype` - 0 (P_ALL), 1 (P_PID) or 2 (P_PGID)
+/// * `id` - PID or process group for P_PID/P_PGID (P_PGID 0 means the
+///   caller's group)
+/// * `info_ptr` - Pointer to a `ChildSigInfo` to fill in
+///
+/// R10 holds the options: at least one of `WEXITED`, `WSTOPPED` and
+/// `WCONTINUED`, optionally with `WNOHANG` and `WNOWAIT`. R8 points to a
+/// `Rusage` (may be 0).
+///
+/// # Returns
+/// 0 on success (with `WNOHANG` and no change, `si_pid` is 0), or -1 on
+/// error
+fn sys_waitid(idtype: usize, id: usize, info_ptr: usize) -> isize {
+    use crate::user::process::{
+        wait_child, WaitTarget, WCONTINUED, WEXITED, WNOHANG, WNOWAIT, WUNTRACED,
+    };
+
+    let (options, rusage_ptr) = match extra_syscall_args() {
+        Some((options, rusage, _)) => (options, rusage),
+        None => return -1, // ENOSYS
+    };
+
+    let events = WEXITED | WUNTRACED | WCONTINUED;
+    if options & events == 0 || options & !(events | WNOHANG | WNOWAIT) != 0 {
+        return -1; // EINVAL
+    }
+    if !validate_user_buffer(info_ptr, core::mem::size_of::<ChildSigInfo>()) {
+        return -1; // EFAULT
+    }
+    if rusage_ptr != 0 && !validate_user_buffer(rusage_ptr, core::mem::size_of::<Rusage>()) {
+        return -1; // EFAULT
+    }
+    let target = match idtype {
+        0 => WaitTarget::Any,
+        1 if id > 0 => WaitTarget::Pid(id),
+        2 if id > 0 => WaitTarget::Group(id),
+        2 => match wait_target(0) {
+            Some(target) => target,
+            None => return -1, // ESRCH
+        },
+        _ => return -1, // EINVAL
+    };
 
-    // Get target task
-    let task = match crate::sched::get_task_mut(target_pid) {
-        Some(t) => t,
-        None => {
-            serial_println!("[SYSCALL] sys_getsid: process {} not found", target_pid);
-            return -1; // ESRCH - no such process
+    let event = match wait_child(target, options) {
+        Ok(event) => event,
+        Err(ProcessError::Interrupted) => return ERESTARTSYS,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_waitid: {:?}", e);
+            return -1; // ECHILD
         }
     };
 
-    let sid = task.sid;
-    serial_println!("[SYSCALL] sys_getsid: PID {} has SID {}", target_pid, sid);
-    sid as isize
+    let ticks = |ns: u64| (ns / crate::sched::fair::TICK_NS) as i64;
+    let info = ChildSigInfo {
+        si_signo: event.map_or(0, |_| crate::signal::signals::SIGCHLD as i32),
+        si_errno: 0,
+        si_code: event.map_or(0, |e| e.status.si_code()),
+        _pad: 0,
+        si_pid: event.map_or(0, |e| e.pid as i32),
+        si_uid: event.map_or(0, |e| e.uid),
+        si_status: event.map_or(0, |e| e.status.si_status()),
+        _pad2: 0,
+        si_utime: event.map_or(0, |e| ticks(e.rusage.utime_ns)),
+        si_stime: event.map_or(0, |e| ticks(e.rusage.stime_ns)),
+        _reserved: [0; 80],
+    };
+    unsafe {
+        *(info_ptr as *mut ChildSigInfo) = info;
+        if rusage_ptr != 0 {
+            let usage = event.map(|e| e.rusage).unwrap_or_default();
+            *(rusage_ptr as *mut Rusage) = Rusage::from_usage(&usage);
+        }
+    }
+    0
 }
 
 /// sys_tcsetpgrp handler - Set foreground process group of terminal
@@ -2733,15 +4872,13 @@ fn sys_tcsetpgrp(fd: usize, pgid: usize) -> isize {
     let _current_sid = current_task.sid;
 
     // Look up file descriptor
-    let fd_table = FD_TABLE.lock();
-    let fd_entry = match fd_table.get(fd) {
+    let fd_entry = match get_fd(fd) {
         Some(entry) => entry,
         None => {
             serial_println!("[SYSCALL] sys_tcsetpgrp: invalid FD {}", fd);
             return -1; // EBADF
         }
     };
-    drop(fd_table);
 
     // Get PTY number from FD
     let pty_num = match fd_entry.fd_type {
@@ -2777,15 +4914,13 @@ fn sys_tcsetpgrp(fd: usize, pgid: usize) -> isize {
 /// Foreground process group ID on success, or -1 on error
 fn sys_tcgetpgrp(fd: usize) -> isize {
     // Look up file descriptor
-    let fd_table = FD_TABLE.lock();
-    let fd_entry = match fd_table.get(fd) {
+    let fd_entry = match get_fd(fd) {
         Some(entry) => entry,
         None => {
             serial_println!("[SYSCALL] sys_tcgetpgrp: invalid FD {}", fd);
             return -1; // EBADF
         }
     };
-    drop(fd_table);
 
     // Get PTY number from FD
     let pty_num = match fd_entry.fd_type {
@@ -2831,7 +4966,11 @@ const F_SETFL: usize = 4; // Set file status flags
 fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
     serial_println!("[SYSCALL] sys_fcntl: FD={}, cmd={}, arg={}", fd, cmd, arg);
 
-    let mut fd_table = FD_TABLE.lock();
+    let files = match current_files() {
+        Some(files) => files,
+        None => return -1, // EBADF
+    };
+    let mut fd_table = files.lock();
     let fd_entry = match fd_table.get_mut(fd) {
         Some(entry) => entry,
         None => {
@@ -2905,6 +5044,11 @@ fn sys_pipe2(pipefd_ptr: usize, flags: usize) -> isize {
         0
     }; // O_CLOEXEC = 0x80000
     let status_flags = (flags as u32) & O_NONBLOCK;
+    let limit = crate::user::rlimit::fd_limit();
+    let files = match current_files() {
+        Some(files) => files,
+        None => return -1, // EBADF
+    };
 
     // Allocate a pipe
     let mut pipe_table = PIPE_TABLE.lock();
@@ -2918,36 +5062,43 @@ fn sys_pipe2(pipefd_ptr: usize, flags: usize) -> isize {
     drop(pipe_table);
 
     // Allocate file descriptors
-    let mut fd_table = FD_TABLE.lock();
+    let mut fd_table = files.lock();
 
     // Allocate read end
-    let read_fd =
-        match fd_table.allocate_with_flags(FdType::PipeRead(pipe_id), fd_flags, status_flags) {
-            Some(fd) => fd,
-            None => {
-                // Failed to allocate read FD, deallocate pipe
-                let mut pipe_table = PIPE_TABLE.lock();
-                pipe_table.close_reader(pipe_id);
-                pipe_table.close_writer(pipe_id);
-                serial_println!("[SYSCALL] sys_pipe2: no FDs available for read end");
-                return -1; // EMFILE
-            }
-        };
+    let read_fd = match fd_table.allocate_with_flags(
+        FdType::PipeRead(pipe_id),
+        fd_flags,
+        status_flags,
+        limit,
+    ) {
+        Some(fd) => fd,
+        None => {
+            // Failed to allocate read FD, deallocate pipe
+            let mut pipe_table = PIPE_TABLE.lock();
+            pipe_table.close_reader(pipe_id);
+            pipe_table.close_writer(pipe_id);
+            serial_println!("[SYSCALL] sys_pipe2: no FDs available for read end");
+            return -1; // EMFILE
+        }
+    };
 
     // Allocate write end
-    let write_fd =
-        match fd_table.allocate_with_flags(FdType::PipeWrite(pipe_id), fd_flags, status_flags) {
-            Some(fd) => fd,
-            None => {
-                // Failed to allocate write FD, clean up
-                fd_table.close(read_fd);
-                let mut pipe_table = PIPE_TABLE.lock();
-                pipe_table.close_reader(pipe_id);
-                pipe_table.close_writer(pipe_id);
-                serial_println!("[SYSCALL] sys_pipe2: no FDs available for write end");
-                return -1; // EMFILE
-            }
-        };
+    let write_fd = match fd_table.allocate_with_flags(
+        FdType::PipeWrite(pipe_id),
+        fd_flags,
+        status_flags,
+        limit,
+    ) {
+        Some(fd) => fd,
+        None => {
+            // Failed to allocate write FD, clean up (closing the read FD
+            // closes the read end)
+            fd_table.close(read_fd);
+            PIPE_TABLE.lock().close_writer(pipe_id);
+            serial_println!("[SYSCALL] sys_pipe2: no FDs available for write end");
+            return -1; // EMFILE
+        }
+    };
 
     drop(fd_table);
 
@@ -2979,15 +5130,15 @@ fn sys_dup2(oldfd: usize, newfd: usize) -> isize {
     serial_println!("[SYSCALL] sys_dup2: oldfd={}, newfd={}", oldfd, newfd);
 
     // Validate FD numbers
-    if oldfd >= MAX_FDS || newfd >= MAX_FDS {
+    let limit = crate::user::rlimit::fd_limit();
+    if newfd >= limit {
         serial_println!("[SYSCALL] sys_dup2: FD out of range");
         return -1; // EBADF
     }
 
     // If oldfd == newfd, just validate oldfd and return it
     if oldfd == newfd {
-        let fd_table = FD_TABLE.lock();
-        if fd_table.get(oldfd).is_some() {
+        if get_fd(oldfd).is_some() {
             serial_println!("[SYSCALL] sys_dup2: oldfd == newfd, returning {}", newfd);
             return newfd as isize;
         } else {
@@ -2997,7 +5148,11 @@ fn sys_dup2(oldfd: usize, newfd: usize) -> isize {
     }
 
     // Get old FD entry
-    let mut fd_table = FD_TABLE.lock();
+    let files = match current_files() {
+        Some(files) => files,
+        None => return -1, // EBADF
+    };
+    let mut fd_table = files.lock();
     let old_entry = match fd_table.get(oldfd) {
         Some(entry) => entry,
         None => {
@@ -3013,29 +5168,17 @@ fn sys_dup2(oldfd: usize, newfd: usize) -> isize {
         status_flags: old_entry.status_flags,
     };
 
-    // Increment reference count for pipes
-    match new_entry.fd_type {
-        FdType::PipeRead(pipe_id) => {
-            let mut pipe_table = PIPE_TABLE.lock();
-            if let Some(pipe) = pipe_table.get_mut(pipe_id) {
-                pipe.readers += 1;
-            }
-        }
-        FdType::PipeWrite(pipe_id) => {
-            let mut pipe_table = PIPE_TABLE.lock();
-            if let Some(pipe) = pipe_table.get_mut(pipe_id) {
-                pipe.writers += 1;
-            }
-        }
-        _ => {}
-    }
+    // Take a reference to the pipe end or terminal before closing newfd,
+    // which may hold the last other one
+    new_entry.fd_type.acquire();
 
     // Close newfd if it's open, then allocate at that position
     if fd_table.allocate_at(
         newfd,
-        new_entry.fd_type,
+        new_entry.fd_type.clone(),
         new_entry.fd_flags,
         new_entry.status_flags,
+        limit,
     ) {
         serial_println!(
             "[SYSCALL] sys_dup2: duplicated FD {} to FD {}",
@@ -3045,10 +5188,225 @@ fn sys_dup2(oldfd: usize, newfd: usize) -> isize {
         newfd as isize
     } else {
         serial_println!("[SYSCALL] sys_dup2: failed to allocate at FD {}", newfd);
+        new_entry.fd_type.release();
         -1
     }
 }
 
+/// sys_signalfd handler - Create a signalfd, or change its signals
+///
+/// Reads return a 128-byte `SignalfdSiginfo` for each pending signal of
+/// the set, taking it off the reader's pending set.
+///
+/// # Arguments
+/// * `fd` - -1 to create a signalfd, or an existing one to update
+/// * `mask_ptr` - Pointer to the set of signals to read
+/// * `flags` - `O_NONBLOCK` and `O_CLOEXEC` (for a new signalfd)
+///
+/// # Returns
+/// The file descriptor, or -1 on error
+fn sys_signalfd(fd: usize, mask_ptr: usize, flags: usize) -> isize {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    if flags as u32 as usize != flags || flags as u32 & !(O_NONBLOCK | O_CLOEXEC) != 0 {
+        return -1; // EINVAL
+    }
+    let mask = match read_user_sigset(mask_ptr) {
+        Some(mask) => mask & !crate::signal::UNBLOCKABLE,
+        None => return -1, // EFAULT
+    };
+
+    let files = match current_files() {
+        Some(files) => files,
+        None => return -1, // EBADF
+    };
+    let mut fd_table = files.lock();
+    if fd as isize != -1 {
+        return match fd_table.get(fd).map(|entry| entry.fd_type) {
+            Some(FdType::SignalFd(current)) => {
+                current.store(mask, Ordering::Release);
+                fd as isize
+            }
+            Some(_) => -1, // EINVAL
+            None => -1,    // EBADF
+        };
+    }
+
+    let fd_flags = if flags as u32 & O_CLOEXEC != 0 {
+        FD_CLOEXEC
+    } else {
+        0
+    };
+    let fd_type = FdType::SignalFd(alloc::sync::Arc::new(AtomicU64::new(mask)));
+    let limit = crate::user::rlimit::fd_limit();
+    match fd_table.allocate_with_flags(fd_type, fd_flags, flags as u32 & O_NONBLOCK, limit) {
+        Some(fd) => {
+            serial_println!("[SYSCALL] sys_signalfd: created FD {} for {:#x}", fd, mask);
+            fd as isize
+        }
+        None => -1, // EMFILE
+    }
+}
+
+/// poll events
+const POLLIN: u16 = 0x01;
+const POLLOUT: u16 = 0x04;
+const POLLERR: u16 = 0x08;
+const POLLHUP: u16 = 0x10;
+const POLLNVAL: u16 = 0x20;
+
+/// Entry of the array passed to `poll`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PollFd {
+    /// File descriptor (negative entries are skipped)
+    pub fd: i32,
+    /// Events to wait for
+    pub events: u16,
+    /// Events that occurred
+    pub revents: u16,
+}
+
+/// Events a file descriptor is ready for now
+fn poll_events(fd: usize) -> u16 {
+    use core::sync::atomic::Ordering;
+
+    let fd_entry = match get_fd(fd) {
+        Some(entry) => entry,
+        None => return POLLNVAL,
+    };
+    match fd_entry.fd_type {
+        FdType::PtyMaster(pty_num) if crate::dev::pty::has_input(pty_num, true) => POLLIN | POLLOUT,
+        FdType::PtySlave(pty_num) if crate::dev::pty::has_input(pty_num, false) => POLLIN | POLLOUT,
+        FdType::PtyMaster(_) | FdType::PtySlave(_) => POLLOUT,
+        FdType::PipeRead(pipe_id) => match PIPE_TABLE.lock().get(pipe_id) {
+            Some(pipe) if pipe.writers == 0 => POLLIN | POLLHUP,
+            Some(pipe) if !pipe.is_empty() => POLLIN,
+            Some(_) => 0,
+            None => POLLNVAL,
+        },
+        FdType::PipeWrite(pipe_id) => match PIPE_TABLE.lock().get(pipe_id) {
+            Some(pipe) if pipe.readers == 0 => POLLERR,
+            Some(pipe) if !pipe.is_full() => POLLOUT,
+            Some(_) => 0,
+            None => POLLNVAL,
+        },
+        FdType::VfsFile { .. } => POLLIN | POLLOUT,
+        FdType::SignalFd(mask) => {
+            if crate::signal::signalfd::is_readable(mask.load(Ordering::Acquire)) {
+                POLLIN
+            } else {
+                0
+            }
+        }
+        FdType::Invalid => POLLNVAL,
+    }
+}
+
+/// Wait queue announcing readiness changes of a descriptor, if it has one
+fn poll_queue(fd_type: &FdType) -> Option<&'static WaitQueue> {
+    match *fd_type {
+        FdType::PtyMaster(pty_num) | FdType::PtySlave(pty_num) => {
+            crate::dev::pty::poll_queue(pty_num)
+        }
+        FdType::PipeRead(pipe_id) | FdType::PipeWrite(pipe_id) => {
+            PIPE_POLLERS.get(pipe_id as usize)
+        }
+        _ => None,
+    }
+}
+
+/// sys_poll handler - Wait for file descriptors to become ready
+///
+/// The caller waits on the wait queues of the polled pipes and PTYs, and
+/// for the signals of the polled signalfds, until something is ready, the
+/// timeout passes or a signal arrives.
+///
+/// # Arguments
+/// * `fds_ptr` - Pointer to an array of `PollFd`
+/// * `nfds` - Number of entries
+/// * `timeout_ms` - Longest wait in milliseconds, an `int` (negative: no
+///   limit)
+///
+/// # Returns
+/// Number of entries with events, 0 on timeout, or -1 on error
+fn sys_poll(fds_ptr: usize, nfds: usize, timeout_ms: usize) -> isize {
+    use crate::sched::hrtimer;
+    use crate::sync::waitqueue::Waiter;
+    use core::sync::atomic::Ordering;
+
+    if nfds as u64 > crate::user::rlimit::current(crate::user::rlimit::RLIMIT_NOFILE) {
+        return -1; // EINVAL
+    }
+    if nfds > 0 && !validate_user_buffer(fds_ptr, nfds * core::mem::size_of::<PollFd>()) {
+        return -1; // EFAULT
+    }
+    let fds = unsafe { core::slice::from_raw_parts_mut(fds_ptr as *mut PollFd, nfds) };
+    // Only the low 32 bits count, so a zero-extended -1 still means no limit
+    let deadline = match timeout_ms as i32 {
+        ms if ms < 0 => None,
+        ms => Some(hrtimer::monotonic_ns().saturating_add(ms as u64 * 1_000_000)),
+    };
+    let task = match crate::sched::current_task() {
+        Some(task) => task,
+        None => return -1,
+    };
+
+    // Wait where readiness changes are announced; `send_signal` wakes the
+    // task for waited signals even though it blocks them
+    let waiter = Waiter::new(task.id);
+    let mut queues = alloc::vec::Vec::new();
+    let mut signals = 0;
+    for pollfd in fds.iter() {
+        let fd_entry = match usize::try_from(pollfd.fd).ok().and_then(get_fd) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if let FdType::SignalFd(mask) = &fd_entry.fd_type {
+            signals |= mask.load(Ordering::Acquire);
+        }
+        if let Some(queue) = poll_queue(&fd_entry.fd_type) {
+            queue.add(&waiter);
+            queues.push(queue);
+        }
+    }
+    task.waited_signals
+        .store(signals & !crate::signal::UNBLOCKABLE, Ordering::Release);
+
+    let result = loop {
+        waiter.reset();
+        let mut ready = 0;
+        for pollfd in fds.iter_mut() {
+            pollfd.revents = if pollfd.fd < 0 {
+                0
+            } else {
+                // Errors are reported whether asked for or not
+                poll_events(pollfd.fd as usize) & (pollfd.events | POLLERR | POLLHUP | POLLNVAL)
+            };
+            if pollfd.revents != 0 {
+                ready += 1;
+            }
+        }
+        if ready > 0 {
+            break ready;
+        }
+
+        if deadline.is_some_and(|deadline| hrtimer::monotonic_ns() >= deadline) {
+            break 0;
+        }
+        if task.next_pending_signal().is_some() {
+            break ERESTARTNOHAND;
+        }
+        waiter.wait(deadline);
+    };
+
+    task.waited_signals.store(0, Ordering::Release);
+    for queue in queues {
+        queue.remove(&waiter);
+    }
+    result
+}
+
 /// sys_read_stdin handler - Read keyboard input from stdin
 ///
 /// # Arguments
@@ -3183,6 +5541,9 @@ fn sys_serial_read(buf_ptr: usize, len: usize) -> isize {
 /// Reads the kernel log buffer and copies it to userspace.
 /// The log buffer contains formatted log messages from the kernel.
 fn sys_read_kernel_log(buf_ptr: usize, len: usize) -> isize {
+    if !require_capability("sys_read_kernel_log", Capabilities::CAP_SYSLOG) {
+        return -1; // EPERM
+    }
     if len == 0 {
         return 0;
     }
@@ -3210,8 +5571,11 @@ fn sys_read_kernel_log(buf_ptr: usize, len: usize) -> isize {
 /// * `count` - Number of blocks to read
 ///
 /// # Returns
-/// Number of blocks read, or -1 on error
+/// Number of blocks read, or -1 on error (EPERM without `CAP_SYS_RAWIO`)
 fn sys_block_read(lba: usize, buf_ptr: usize, count: usize) -> isize {
+    if !require_capability("sys_block_read", Capabilities::CAP_SYS_RAWIO) {
+        return -1; // EPERM
+    }
     if count == 0 {
         return 0;
     }
@@ -3264,8 +5628,11 @@ fn sys_block_read(lba: usize, buf_ptr: usize, count: usize) -> isize {
 /// * `count` - Number of blocks to write
 ///
 /// # Returns
-/// Number of blocks written, or -1 on error
+/// Number of blocks written, or -1 on error (EPERM without `CAP_SYS_RAWIO`)
 fn sys_block_write(lba: usize, buf_ptr: usize, count: usize) -> isize {
+    if !require_capability("sys_block_write", Capabilities::CAP_SYS_RAWIO) {
+        return -1; // EPERM
+    }
     if count == 0 {
         return 0;
     }
@@ -3783,6 +6150,8 @@ fn sys_lstat(path_ptr: usize, stat_ptr: usize) -> isize {
 
 /// sys_chmod handler - Change file permissions
 ///
+/// Only the owner of the file (or root) may change its mode.
+///
 /// # Arguments
 /// * `path_ptr` - Pointer to null-terminated path string
 /// * `mode` - New permission bits
@@ -3820,8 +6189,24 @@ fn sys_chmod(path_ptr: usize, mode: usize) -> isize {
 
     serial_println!("[SYSCALL] sys_chmod: path={}, mode={:#o}", path, mode);
 
-    serial_println!("[SYSCALL] sys_chmod: {} mode={:o}", path, mode);
-    0 // Success
+    let creds = crate::user::creds::current();
+    let result = crate::fs::vfs::path::resolve_path(path, None)
+        .and_then(|inode| crate::fs::vfs::permission::chmod(&*inode, &creds, mode as u16));
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_chmod: {}: {:?}", path, e);
+            -1 // ENOENT, EACCES or EPERM
+        }
+    }
+}
+
+/// A user or group ID argument, or None for -1 ("leave unchanged")
+fn optional_id(id: usize) -> Option<u32> {
+    match id as u32 {
+        u32::MAX => None,
+        id => Some(id),
+    }
 }
 
 /// sys_chown handler - Change file ownership
@@ -3831,6 +6216,9 @@ fn sys_chmod(path_ptr: usize, mode: usize) -> isize {
 /// * `uid` - New user ID (-1 to leave unchanged)
 /// * `gid` - New group ID (-1 to leave unchanged)
 ///
+/// Only root may change the owner; the owner may change the group to one
+/// of its own groups (see `fs::vfs::permission::chown_attr`).
+///
 /// # Returns
 /// 0 on success, or -1 on error
 fn sys_chown(path_ptr: usize, uid: usize, gid: usize) -> isize {
@@ -3869,16 +6257,191 @@ fn sys_chown(path_ptr: usize, uid: usize, gid: usize) -> isize {
         gid
     );
 
-    serial_println!("[SYSCALL] sys_chown: {} uid={} gid={}", path, uid, gid);
-    0 // Success
+    let creds = crate::user::creds::current();
+    let result = crate::fs::vfs::path::resolve_path(path, None).and_then(|inode| {
+        crate::fs::vfs::permission::chown(&*inode, &creds, optional_id(uid), optional_id(gid))
+    });
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_chown: {}: {:?}", path, e);
+            -1 // ENOENT, EACCES or EPERM
+        }
+    }
+}
+
+/// Read a null-terminated path argument for `syscall`
+///
+/// Returns None (after logging why) if the pointer is bad, the path is
+/// 4096 bytes or longer, or it is not UTF-8.
+fn read_user_path(syscall: &str, path_ptr: usize) -> Option<&'static str> {
+    if !validate_user_buffer(path_ptr, 1) {
+        serial_println!("[SYSCALL] {}: invalid path pointer", syscall);
+        return None; // EFAULT
+    }
+
+    let path_bytes = unsafe {
+        let mut len = 0;
+        let ptr = path_ptr as *const u8;
+        while len < 4096 && *ptr.add(len) != 0 {
+            len += 1;
+        }
+        if len >= 4096 {
+            serial_println!("[SYSCALL] {}: path too long", syscall);
+            return None; // ENAMETOOLONG
+        }
+        core::slice::from_raw_parts(ptr, len)
+    };
+
+    match core::str::from_utf8(path_bytes) {
+        Ok(path) => Some(path),
+        Err(_) => {
+            serial_println!("[SYSCALL] {}: invalid UTF-8 in path", syscall);
+            None // EINVAL
+        }
+    }
+}
+
+/// sys_mkdir handler - Create a directory
+///
+/// The caller needs write and search permission on the parent directory;
+/// the new directory belongs to it (see `fs::vfs::permission::create`).
+///
+/// # Arguments
+/// * `path_ptr` - Pointer to null-terminated path string
+/// * `mode` - Permission bits of the new directory
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_mkdir(path_ptr: usize, mode: usize) -> isize {
+    use crate::fs::vfs::inode::FileMode;
+
+    let Some(path) = read_user_path("sys_mkdir", path_ptr) else {
+        return -1;
+    };
+    serial_println!("[SYSCALL] sys_mkdir: path={}, mode={:#o}", path, mode);
+
+    let creds = crate::user::creds::current();
+    let dir_mode = FileMode::new(FileMode::S_IFDIR | (mode as u16 & 0o7777));
+    let result = crate::fs::vfs::path::resolve_parent(path, None).and_then(|(parent, name)| {
+        crate::fs::vfs::permission::create(&*parent, &creds, &name, dir_mode)
+    });
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_mkdir: {}: {:?}", path, e);
+            -1 // ENOENT, EEXIST or EACCES
+        }
+    }
+}
+
+/// sys_unlink handler - Remove a directory entry
+///
+/// The caller needs write and search permission on the parent directory,
+/// and in a sticky directory must own the entry or the directory (see
+/// `fs::vfs::permission::may_delete`).
+///
+/// # Arguments
+/// * `path_ptr` - Pointer to null-terminated path string
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_unlink(path_ptr: usize) -> isize {
+    let Some(path) = read_user_path("sys_unlink", path_ptr) else {
+        return -1;
+    };
+    serial_println!("[SYSCALL] sys_unlink: path={}", path);
+
+    let creds = crate::user::creds::current();
+    let result = crate::fs::vfs::path::resolve_parent(path, None)
+        .and_then(|(parent, name)| crate::fs::vfs::permission::unlink(&*parent, &creds, &name));
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_unlink: {}: {:?}", path, e);
+            -1 // ENOENT, EISDIR, EACCES or EPERM
+        }
+    }
+}
+
+/// sys_symlink handler - Create a symbolic link
+///
+/// # Arguments
+/// * `target_ptr` - Pointer to null-terminated link target
+/// * `path_ptr` - Pointer to null-terminated path of the new link
+///
+/// # Returns
+/// 0 on success, or -1 on error
+fn sys_symlink(target_ptr: usize, path_ptr: usize) -> isize {
+    let Some(target) = read_user_path("sys_symlink", target_ptr) else {
+        return -1;
+    };
+    let Some(path) = read_user_path("sys_symlink", path_ptr) else {
+        return -1;
+    };
+    serial_println!("[SYSCALL] sys_symlink: target={}, path={}", target, path);
+
+    let creds = crate::user::creds::current();
+    let result = crate::fs::vfs::path::resolve_parent(path, None).and_then(|(parent, name)| {
+        crate::fs::vfs::permission::symlink(&*parent, &creds, &name, target)
+    });
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            serial_println!("[SYSCALL] sys_symlink: {}: {:?}", path, e);
+            -1 // ENOENT, EEXIST or EACCES
+        }
+    }
 }
 
-/// Timespec structure for utimensat
+/// Time value used by utimensat, nanosleep and clock_gettime
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
-struct Timespec {
-    tv_sec: i64,
-    tv_nsec: i64,
+pub struct Timespec {
+    /// Seconds
+    pub tv_sec: i64,
+    /// Nanoseconds (0..999_999_999, or UTIME_NOW/UTIME_OMIT for utimensat)
+    pub tv_nsec: i64,
+}
+
+impl Timespec {
+    /// Split a nanosecond count into seconds and nanoseconds
+    fn from_ns(ns: u64) -> Self {
+        Self {
+            tv_sec: (ns / 1_000_000_000) as i64,
+            tv_nsec: (ns % 1_000_000_000) as i64,
+        }
+    }
+
+    /// Convert to nanoseconds (None if out of range)
+    fn to_ns(self) -> Option<u64> {
+        if self.tv_sec < 0 || !(0..1_000_000_000).contains(&self.tv_nsec) {
+            return None;
+        }
+        (self.tv_sec as u64)
+            .checked_mul(1_000_000_000)?
+            .checked_add(self.tv_nsec as u64)
+    }
+}
+
+/// Time value with microsecond resolution, used by `Rusage`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timeval {
+    /// Seconds
+    pub tv_sec: i64,
+    /// Microseconds (0..999_999)
+    pub tv_usec: i64,
+}
+
+impl Timeval {
+    /// Split a nanosecond count into seconds and microseconds
+    fn from_ns(ns: u64) -> Self {
+        Self {
+            tv_sec: (ns / 1_000_000_000) as i64,
+            tv_usec: (ns % 1_000_000_000 / 1_000) as i64,
+        }
+    }
 }
 
 /// sys_utimensat handler - Change file timestamps with nanosecond precision
@@ -4199,7 +6762,8 @@ fn sys_listxattr(path_ptr: usize, _list_info: usize) -> isize {
 /// * `dev` - Device number (major << 32 | minor) for device nodes
 ///
 /// # Returns
-/// 0 on success, or -1 on error
+/// 0 on success, or -1 on error (EPERM for a device node without
+/// `CAP_MKNOD`)
 ///
 /// # Supported types
 /// * S_IFREG (0o100000): Regular file
@@ -4262,6 +6826,9 @@ fn sys_mknod(path_ptr: usize, mode: usize, dev: usize) -> isize {
         }
         FileMode::S_IFCHR => {
             // Character device
+            if !require_capability("sys_mknod", Capabilities::CAP_MKNOD) {
+                return -1; // EPERM
+            }
             serial_println!(
                 "[SYSCALL] sys_mknod: creating character device {}:{}",
                 major,
@@ -4270,6 +6837,9 @@ fn sys_mknod(path_ptr: usize, mode: usize, dev: usize) -> isize {
         }
         FileMode::S_IFBLK => {
             // Block device
+            if !require_capability("sys_mknod", Capabilities::CAP_MKNOD) {
+                return -1; // EPERM
+            }
             serial_println!(
                 "[SYSCALL] sys_mknod: creating block device {}:{}",
                 major,
@@ -4334,15 +6904,13 @@ fn sys_fsync(fd: usize) -> isize {
     serial_println!("[SYSCALL] sys_fsync: fd={}", fd);
 
     // Validate file descriptor
-    let fd_table = FD_TABLE.lock();
-    let _fd_entry = match fd_table.get(fd) {
+    let _fd_entry = match get_fd(fd) {
         Some(entry) => entry,
         None => {
             serial_println!("[SYSCALL] sys_fsync: invalid FD {}", fd);
             return -1; // EBADF
         }
     };
-    drop(fd_table);
 
     // Sync the file through VFS
     use crate::fs::vfs::file::sync_file;
@@ -4374,15 +6942,13 @@ fn sys_fdatasync(fd: usize) -> isize {
     serial_println!("[SYSCALL] sys_fdatasync: fd={}", fd);
 
     // Validate file descriptor
-    let fd_table = FD_TABLE.lock();
-    let _fd_entry = match fd_table.get(fd) {
+    let _fd_entry = match get_fd(fd) {
         Some(entry) => entry,
         None => {
             serial_println!("[SYSCALL] sys_fdatasync: invalid FD {}", fd);
             return -1; // EBADF
         }
     };
-    drop(fd_table);
 
     // fdatasync is like fsync but doesn't sync metadata
     // For now, we'll just call fsync (conservative approach)
@@ -4409,7 +6975,7 @@ fn sys_fdatasync(fd: usize) -> isize {
 /// * `data_ptr` - Pointer to filesystem-specific options string
 ///
 /// # Returns
-/// 0 on success, or -1 on error
+/// 0 on success, or -1 on error (EPERM without `CAP_SYS_ADMIN`)
 ///
 /// # Note
 /// This is a simplified version that takes 3 args instead of 5.
@@ -4423,6 +6989,10 @@ fn sys_fdatasync(fd: usize) -> isize {
 /// * cow: Enable copy-on-write
 /// * trim: Enable TRIM support
 fn sys_mount(source_ptr: usize, target_ptr: usize, fstype_ptr: usize) -> isize {
+    if !require_capability("sys_mount", Capabilities::CAP_SYS_ADMIN) {
+        return -1; // EPERM
+    }
+
     // Validate pointers
     if !validate_user_buffer(source_ptr, 1) {
         serial_println!("[SYSCALL] sys_mount: invalid source pointer");
@@ -4532,13 +7102,17 @@ fn sys_mount(source_ptr: usize, target_ptr: usize, fstype_ptr: usize) -> isize {
 /// * `flags` - Unmount flags (MNT_FORCE, MNT_DETACH, etc.)
 ///
 /// # Returns
-/// 0 on success, or -1 on error
+/// 0 on success, or -1 on error (EPERM without `CAP_SYS_ADMIN`)
 ///
 /// # Flags
 /// * MNT_FORCE (0x1): Force unmount even if busy
 /// * MNT_DETACH (0x2): Lazy unmount (detach from namespace)
 /// * MNT_EXPIRE (0x4): Mark for expiration
 fn sys_umount(target_ptr: usize, flags: usize) -> isize {
+    if !require_capability("sys_umount", Capabilities::CAP_SYS_ADMIN) {
+        return -1; // EPERM
+    }
+
     // Validate pointer
     if !validate_user_buffer(target_ptr, 1) {
         serial_println!("[SYSCALL] sys_umount: invalid target pointer");
@@ -4586,3 +7160,30 @@ fn sys_umount(target_ptr: usize, flags: usize) -> isize {
     serial_println!("[SYSCALL] sys_umount: complete");
     0 // Success
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signalfd() -> FdType {
+        FdType::SignalFd(alloc::sync::Arc::new(core::sync::atomic::AtomicU64::new(0)))
+    }
+
+    #[test]
+    fn test_fd_limit_applies_per_table() {
+        let mut first = FdTable::new();
+        let mut second = FdTable::new();
+        for fd in 3..8 {
+            assert_eq!(first.allocate(signalfd(), 8), Some(fd));
+        }
+        assert_eq!(first.allocate(signalfd(), 8), None);
+
+        // Another process's descriptors don't count against this limit
+        assert_eq!(second.allocate(signalfd(), 8), Some(3));
+
+        // dup2 may replace a descriptor below the limit, not go past it
+        assert!(first.allocate_at(7, signalfd(), 0, 0, 8));
+        assert!(!first.allocate_at(8, signalfd(), 0, 0, 8));
+        assert_eq!(first.count(), 5);
+    }
+}
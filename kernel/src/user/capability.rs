@@ -0,0 +1,330 @@
+//! Capabilities
+//!
+//! Root's power is split into capabilities, each guarding one class of
+//! privileged operation (`CAP_SYS_RAWIO` for raw disk access, `CAP_KILL`
+//! for signalling other users' processes, ...). The kernel checks the
+//! capability instead of the user ID, so a service can drop root and keep
+//! only what it needs. Bit numbers follow Linux, so `capget`/`capset`
+//! data is laid out the same way.
+//!
+//! A task has four sets (`CapSets`, part of `Credentials`):
+//!
+//! - effective: what the kernel checks;
+//! - permitted: the most the effective set may hold;
+//! - inheritable: what survives a change away from root (`setuid` to a
+//!   non-root user) and an `exec` of an ordinary program, like Linux's
+//!   ambient set;
+//! - bounding: the most any `exec` can grant; only ever shrinks.
+//!
+//! Root (real or effective UID 0) gets the whole bounding set as permitted
+//! on `exec`, and as effective when its effective UID is 0.
+
+use super::process::{ProcessError, ProcessResult};
+use crate::sched::task::Credentials;
+
+bitflags::bitflags! {
+    /// A set of capabilities
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Capabilities: u64 {
+        /// Change file owners and groups freely
+        const CAP_CHOWN = 1 << 0;
+        /// Bypass file read, write and execute permission checks
+        const CAP_DAC_OVERRIDE = 1 << 1;
+        /// Bypass file read and directory search permission checks
+        const CAP_DAC_READ_SEARCH = 1 << 2;
+        /// Act as the owner of any file (chmod, sticky directories)
+        const CAP_FOWNER = 1 << 3;
+        /// Keep set-group-ID bits the caller could not otherwise set
+        const CAP_FSETID = 1 << 4;
+        /// Signal processes of other users
+        const CAP_KILL = 1 << 5;
+        /// Set any group ID and the supplementary groups
+        const CAP_SETGID = 1 << 6;
+        /// Set any user ID
+        const CAP_SETUID = 1 << 7;
+        /// Add bounding-set capabilities to the inheritable set, and drop
+        /// capabilities from the bounding set
+        const CAP_SETPCAP = 1 << 8;
+        /// Bind to ports below 1024
+        const CAP_NET_BIND_SERVICE = 1 << 10;
+        /// Configure network interfaces
+        const CAP_NET_ADMIN = 1 << 12;
+        /// Use raw sockets
+        const CAP_NET_RAW = 1 << 13;
+        /// Lock memory
+        const CAP_IPC_LOCK = 1 << 14;
+        /// Raw block device and I/O port access
+        const CAP_SYS_RAWIO = 1 << 17;
+        /// Change the root directory
+        const CAP_SYS_CHROOT = 1 << 18;
+        /// Trace any process
+        const CAP_SYS_PTRACE = 1 << 19;
+        /// System administration (mount, cpusets, ...)
+        const CAP_SYS_ADMIN = 1 << 21;
+        /// Reboot the system
+        const CAP_SYS_BOOT = 1 << 22;
+        /// Raise priority, use real-time policies, set other users' affinity
+        const CAP_SYS_NICE = 1 << 23;
+        /// Override resource limits
+        const CAP_SYS_RESOURCE = 1 << 24;
+        /// Set the system clock
+        const CAP_SYS_TIME = 1 << 25;
+        /// Create device nodes
+        const CAP_MKNOD = 1 << 27;
+        /// Read the kernel log
+        const CAP_SYSLOG = 1 << 34;
+    }
+}
+
+/// Capability sets of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapSets {
+    /// Capabilities in force
+    pub effective: Capabilities,
+    /// Capabilities the task may make effective
+    pub permitted: Capabilities,
+    /// Capabilities kept when giving up root and across `exec`
+    pub inheritable: Capabilities,
+    /// Limit on the capabilities `exec` grants
+    pub bounding: Capabilities,
+}
+
+impl CapSets {
+    /// Sets of root: everything permitted and effective
+    pub const fn full() -> Self {
+        Self {
+            effective: Capabilities::all(),
+            permitted: Capabilities::all(),
+            inheritable: Capabilities::empty(),
+            bounding: Capabilities::all(),
+        }
+    }
+
+    /// Sets of an ordinary user: nothing, with a full bounding set
+    pub const fn empty() -> Self {
+        Self {
+            effective: Capabilities::empty(),
+            permitted: Capabilities::empty(),
+            inheritable: Capabilities::empty(),
+            bounding: Capabilities::all(),
+        }
+    }
+}
+
+/// Whether the calling task has `cap` in its effective set
+pub fn capable(cap: Capabilities) -> bool {
+    super::creds::current().capable(cap)
+}
+
+/// Capability sets after a change of user IDs from `old` to `new`
+///
+/// - Leaving root entirely (no real, effective or saved UID 0 any more)
+///   keeps only the inheritable capabilities.
+/// - Otherwise an effective UID leaving 0 clears the effective set, and
+///   one becoming 0 makes the permitted set effective.
+pub fn after_setuid(old: &Credentials, new: &Credentials) -> CapSets {
+    let had_root = [old.ruid, old.uid, old.suid].contains(&0);
+    let has_root = [new.ruid, new.uid, new.suid].contains(&0);
+
+    let mut caps = new.caps;
+    if had_root && !has_root {
+        caps.permitted &= caps.inheritable;
+        caps.effective &= caps.inheritable;
+    } else if old.uid == 0 && new.uid != 0 {
+        caps.effective = Capabilities::empty();
+    } else if old.uid != 0 && new.uid == 0 {
+        caps.effective = caps.permitted;
+    }
+    caps
+}
+
+/// Capability sets for the program `exec` starts with `creds` (IDs already
+/// updated for set-user-ID files)
+pub fn after_exec(creds: &Credentials) -> CapSets {
+    let mut caps = creds.caps;
+    if creds.ruid == 0 || creds.uid == 0 {
+        caps.permitted = caps.bounding;
+    } else {
+        caps.permitted &= caps.inheritable & caps.bounding;
+    }
+    caps.effective = if creds.uid == 0 || creds.uid == creds.ruid {
+        caps.permitted
+    } else {
+        // A set-user-ID program starts without capabilities in force
+        Capabilities::empty()
+    };
+    caps
+}
+
+/// Credentials after `capset` to `sets` (the bounding set is not changed)
+///
+/// The permitted set can only shrink, the effective set must be within
+/// the new permitted set, and the inheritable set may only gain
+/// capabilities that are permitted (or, with `CAP_SETPCAP`, in the
+/// bounding set).
+pub fn capset(creds: &Credentials, sets: CapSets) -> ProcessResult<Credentials> {
+    let old = creds.caps;
+    let mut inheritable_limit = old.inheritable | (old.permitted & old.bounding);
+    if creds.capable(Capabilities::CAP_SETPCAP) {
+        inheritable_limit |= old.bounding;
+    }
+    if !old.permitted.contains(sets.permitted)
+        || !sets.permitted.contains(sets.effective)
+        || !inheritable_limit.contains(sets.inheritable)
+    {
+        return Err(ProcessError::PermissionDenied);
+    }
+
+    let mut new = *creds;
+    new.caps = CapSets {
+        bounding: old.bounding,
+        ..sets
+    };
+    Ok(new)
+}
+
+/// Credentials after dropping `cap` from the bounding set (`CAP_SETPCAP`)
+pub fn drop_bounding(creds: &Credentials, cap: Capabilities) -> ProcessResult<Credentials> {
+    if !creds.capable(Capabilities::CAP_SETPCAP) {
+        return Err(ProcessError::PermissionDenied);
+    }
+    let mut new = *creds;
+    new.caps.bounding.remove(cap);
+    Ok(new)
+}
+
+/// The capability with bit number `number`, if the kernel knows it
+pub fn from_number(number: usize) -> Option<Capabilities> {
+    if number >= 64 {
+        return None;
+    }
+    Capabilities::from_bits(1 << number)
+}
+
+/// `capget`/`capset` header version (64-bit sets in two data structs)
+pub const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// `capget`/`capset` header (`struct __user_cap_header_struct`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CapUserHeader {
+    /// Must be `LINUX_CAPABILITY_VERSION_3`
+    pub version: u32,
+    /// Target process (0 for the caller)
+    pub pid: i32,
+}
+
+/// One 32-bit half of the sets (`struct __user_cap_data_struct`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CapUserData {
+    pub effective: u32,
+    pub permitted: u32,
+    pub inheritable: u32,
+}
+
+impl CapSets {
+    /// Low and high halves of the effective, permitted and inheritable sets
+    pub fn to_user(&self) -> [CapUserData; 2] {
+        let half = |shift: u32| CapUserData {
+            effective: (self.effective.bits() >> shift) as u32,
+            permitted: (self.permitted.bits() >> shift) as u32,
+            inheritable: (self.inheritable.bits() >> shift) as u32,
+        };
+        [half(0), half(32)]
+    }
+
+    /// Sets from `capset` data; unknown bits are ignored
+    pub fn from_user(data: &[CapUserData; 2], bounding: Capabilities) -> Self {
+        let join = |low: u32, high: u32| {
+            Capabilities::from_bits_truncate(low as u64 | (high as u64) << 32)
+        };
+        Self {
+            effective: join(data[0].effective, data[1].effective),
+            permitted: join(data[0].permitted, data[1].permitted),
+            inheritable: join(data[0].inheritable, data[1].inheritable),
+            bounding,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVICE: u32 = 100;
+
+    #[test]
+    fn test_user_creds() {
+        assert!(Credentials::user(0, 0).capable(Capabilities::CAP_SYS_ADMIN));
+        assert!(!Credentials::user(SERVICE, SERVICE).capable(Capabilities::CAP_KILL));
+    }
+
+    #[test]
+    fn test_drop_root_keeps_inheritable() {
+        let root = Credentials::user(0, 0);
+        let mut sets = root.caps;
+        sets.inheritable = Capabilities::CAP_NET_BIND_SERVICE;
+        let root = capset(&root, sets).unwrap();
+
+        // setuid(SERVICE) from root leaves only the inheritable capability
+        let service = super::super::creds::setuid(&root, SERVICE).unwrap();
+        assert_eq!(service.caps.permitted, Capabilities::CAP_NET_BIND_SERVICE);
+        assert!(service.capable(Capabilities::CAP_NET_BIND_SERVICE));
+        assert!(!service.capable(Capabilities::CAP_SETUID));
+
+        // ... and keeps it across exec
+        let caps = after_exec(&service);
+        assert_eq!(caps.effective, Capabilities::CAP_NET_BIND_SERVICE);
+    }
+
+    #[test]
+    fn test_effective_follows_euid() {
+        let root = Credentials::user(0, 0);
+        let dropped = super::super::creds::setresuid(&root, None, Some(SERVICE), None).unwrap();
+        assert!(dropped.caps.effective.is_empty());
+        assert_eq!(dropped.caps.permitted, Capabilities::all());
+        let regained = super::super::creds::setresuid(&dropped, None, Some(0), None).unwrap();
+        assert_eq!(regained.caps.effective, Capabilities::all());
+    }
+
+    #[test]
+    fn test_capset_limits() {
+        let user = Credentials::user(SERVICE, SERVICE);
+        let mut sets = user.caps;
+        sets.permitted = Capabilities::CAP_KILL;
+        assert!(capset(&user, sets).is_err());
+
+        let root = Credentials::user(0, 0);
+        let mut sets = root.caps;
+        sets.permitted = Capabilities::CAP_KILL;
+        sets.effective = Capabilities::CAP_KILL | Capabilities::CAP_CHOWN;
+        assert!(capset(&root, sets).is_err());
+        sets.effective = Capabilities::CAP_KILL;
+        let reduced = capset(&root, sets).unwrap();
+        assert!(!reduced.capable(Capabilities::CAP_CHOWN));
+    }
+
+    #[test]
+    fn test_bounding_limits_exec() {
+        let root = Credentials::user(0, 0);
+        let limited = drop_bounding(&root, Capabilities::CAP_SYS_RAWIO).unwrap();
+        assert!(!after_exec(&limited)
+            .effective
+            .contains(Capabilities::CAP_SYS_RAWIO));
+        assert!(
+            drop_bounding(&Credentials::user(SERVICE, SERVICE), Capabilities::CAP_KILL).is_err()
+        );
+    }
+
+    #[test]
+    fn test_user_layout() {
+        let sets = CapSets::full();
+        let data = sets.to_user();
+        assert_eq!(
+            data[1].effective,
+            (Capabilities::CAP_SYSLOG.bits() >> 32) as u32
+        );
+        assert_eq!(CapSets::from_user(&data, sets.bounding), sets);
+    }
+}
@@ -0,0 +1,588 @@
+//! Core Dumps
+//!
+//! A process killed by a signal whose default action is to dump core
+//! (SIGSEGV, SIGABRT, SIGQUIT, ...) leaves an ELF core file behind, which
+//! gdb on the host can load together with the program's binary:
+//!
+//! - a `PT_NOTE` segment with the crashing thread's registers
+//!   (`NT_PRSTATUS`), the process description (`NT_PRPSINFO`), the
+//!   auxiliary vector the program was started with (`NT_AUXV`) and where
+//!   its executable is mapped (`NT_FILE`)
+//! - one `PT_LOAD` segment for each memory region and mapping, holding its
+//!   contents (pages that were never touched read as zero)
+//!
+//! The file is written to the path the core pattern names, on whichever
+//! mount holds it. A segment that would take the file past the process's
+//! `RLIMIT_CORE` is left out (its `p_filesz` is 0); a limit of 0 disables
+//! core dumps. A process that is not dumpable (`Task::dumpable`) leaves no
+//! core file.
+
+use super::elf::{
+    Elf64Header, Elf64ProgramHeader, ELFCLASS64, ELFDATA2LSB, ELF_MAGIC, EM_X86_64, EV_CURRENT,
+    PF_R, PF_W, PF_X, PT_LOAD,
+};
+use super::ptrace::UserRegs;
+use crate::fs::vfs::inode::{FileMode, Inode};
+use crate::fs::vfs::superblock::FsError;
+use crate::mm::mmap::ProtFlags;
+use crate::mm::paging::PageTableFlags;
+use crate::sched::task::Task;
+use crate::serial_println;
+use crate::signal::Signal;
+use crate::sync::SpinLock;
+use crate::sys::syscall::SyscallFrame;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::sync::atomic::Ordering;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// ELF file type of a core dump
+const ET_CORE: u16 = 4;
+
+/// Program header type of the notes
+const PT_NOTE: u32 = 4;
+
+/// Note types
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+const NT_FILE: u32 = 0x4649_4c45;
+
+/// Auxiliary vector entry types
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+
+/// Longest core pattern, in bytes (Linux `CORENAME_MAX_SIZE`)
+pub const CORE_PATTERN_MAX: usize = 128;
+
+/// Where core dumps go (None = `CORE_PATTERN_DEFAULT`)
+static CORE_PATTERN: SpinLock<Option<String>> = SpinLock::new(None);
+
+/// Current core pattern
+pub fn core_pattern() -> String {
+    CORE_PATTERN
+        .lock()
+        .clone()
+        .unwrap_or_else(|| String::from(crate::config::CORE_PATTERN_DEFAULT))
+}
+
+/// Set where core dumps go (`%e`, `%p` and `%s` are expanded)
+///
+/// Written through /proc/sys/kernel/core_pattern.
+pub fn set_core_pattern(pattern: &str) {
+    *CORE_PATTERN.lock() = Some(String::from(pattern));
+}
+
+/// Create the directory of the default core pattern
+///
+/// Called at boot, once the root filesystem is mounted.
+pub fn init() {
+    let pattern = crate::config::CORE_PATTERN_DEFAULT;
+    let dir = &pattern[..pattern.rfind('/').unwrap_or(0)];
+
+    let mut path = String::new();
+    for name in dir.split('/').filter(|name| !name.is_empty()) {
+        let parent = if path.is_empty() { "/" } else { path.as_str() };
+        let parent = match crate::fs::vfs::path::resolve_path(parent, None) {
+            Ok(inode) => inode,
+            Err(_) => return,
+        };
+        if let Err(e) = parent.create(name, FileMode::new(FileMode::S_IFDIR | 0o755), 0, 0) {
+            if e != FsError::AlreadyExists {
+                serial_println!(
+                    "[CORE] Warning: could not create {}/{}: {:?}",
+                    path,
+                    name,
+                    e
+                );
+                return;
+            }
+        }
+        path.push('/');
+        path.push_str(name);
+    }
+}
+
+/// Dump the current process's core, as it dies from `signal`
+///
+/// # Arguments
+/// * `signal` - The fatal signal
+/// * `regs` - User registers of the current thread when it was killed
+///
+/// # Returns
+/// true if a core file was written (reported to the parent by `wait`)
+pub fn dump_core(signal: Signal, regs: &SyscallFrame) -> bool {
+    let task = match crate::sched::current_task() {
+        Some(task) if !task.creds.is_kernel_thread && task.dumpable => task,
+        _ => return false,
+    };
+    let limit = task.rlimits.soft(super::rlimit::RLIMIT_CORE);
+    if limit == 0 {
+        return false;
+    }
+
+    let comm = command_name(task);
+    let path = expand_pattern(&core_pattern(), comm, task.pid, signal);
+    let segments = memory_segments(task);
+
+    let mut notes = Vec::new();
+    push_note(
+        &mut notes,
+        NT_PRSTATUS,
+        as_bytes(&prstatus(task, signal, regs)),
+    );
+    push_note(&mut notes, NT_PRPSINFO, as_bytes(&prpsinfo(task, comm)));
+    push_note(&mut notes, NT_AUXV, &auxv(task));
+    if let Some(files) = file_note(task) {
+        push_note(&mut notes, NT_FILE, &files);
+    }
+
+    let phdrs = match program_headers(&segments, notes.len(), limit) {
+        Some(phdrs) => phdrs,
+        None => {
+            serial_println!("[CORE] Core of process {} is over the limit", task.pid);
+            return false;
+        }
+    };
+
+    match write_core(&path, task, &phdrs, &notes) {
+        Ok(()) => {
+            serial_println!("[CORE] Process {} dumped core to {}", task.pid, path);
+            true
+        }
+        Err(e) => {
+            serial_println!("[CORE] Could not write {}: {:?}", path, e);
+            false
+        }
+    }
+}
+
+/// Name of the running program: the file it was loaded from, cut to 15
+/// bytes as in `comm`
+fn command_name(task: &Task) -> &str {
+    let name = match &task.image {
+        Some(image) => image.path.rsplit('/').next().unwrap_or(&image.path),
+        None => task.name,
+    };
+    let mut end = name.len().min(15);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+/// Fill in a core pattern
+fn expand_pattern(pattern: &str, comm: &str, pid: usize, signal: Signal) -> String {
+    use core::fmt::Write;
+
+    let mut path = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+        let _ = match chars.next() {
+            Some('e') => write!(path, "{}", comm),
+            Some('p') => write!(path, "{}", pid),
+            Some('s') => write!(path, "{}", signal),
+            Some('%') => write!(path, "%"),
+            _ => Ok(()),
+        };
+    }
+    path
+}
+
+/// A range of process memory, as dumped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    start: u64,
+    end: u64,
+    /// `PF_*` permissions
+    flags: u32,
+}
+
+/// The process's memory: its regions and mappings, by address
+fn memory_segments(task: &Task) -> Vec<Segment> {
+    let mut segments = Vec::new();
+
+    for region in task.memory_regions.iter().flatten() {
+        let mut flags = PF_R;
+        if region.flags.bits() & PageTableFlags::WRITABLE.bits() != 0 {
+            flags |= PF_W;
+        }
+        if region.flags.bits() & PageTableFlags::NO_EXECUTE.bits() == 0 {
+            flags |= PF_X;
+        }
+        segments.push(Segment {
+            start: region.start as u64,
+            end: region.end as u64,
+            flags,
+        });
+    }
+
+    if let Some(table) = crate::mm::mmap::get_mmap_manager().find_table(task.pid as u64) {
+        for lock in &table.mappings {
+            let mapping = lock.read();
+            if !mapping.valid || mapping.prot == ProtFlags::PROT_NONE {
+                continue;
+            }
+            let mut flags = 0;
+            if mapping.prot.is_readable() {
+                flags |= PF_R;
+            }
+            if mapping.prot.is_writable() {
+                flags |= PF_W;
+            }
+            if mapping.prot.is_executable() {
+                flags |= PF_X;
+            }
+            segments.push(Segment {
+                start: mapping.vaddr,
+                end: mapping.vaddr + mapping.length as u64,
+                flags,
+            });
+        }
+    }
+
+    segments.sort_by_key(|segment| segment.start);
+    segments
+}
+
+/// Lay the file out: ELF header, program headers, notes, then the memory
+/// of each segment at a page-aligned offset
+///
+/// # Returns
+/// The program headers (the notes first), or None if not even the notes
+/// fit under `limit`
+fn program_headers(
+    segments: &[Segment],
+    notes_len: usize,
+    limit: u64,
+) -> Option<Vec<Elf64ProgramHeader>> {
+    let notes_offset =
+        (size_of::<Elf64Header>() + (segments.len() + 1) * size_of::<Elf64ProgramHeader>()) as u64;
+    let notes_end = notes_offset + notes_len as u64;
+    if notes_end > limit {
+        return None;
+    }
+
+    let mut phdrs = Vec::with_capacity(segments.len() + 1);
+    phdrs.push(Elf64ProgramHeader {
+        p_type: PT_NOTE,
+        p_flags: 0,
+        p_offset: notes_offset,
+        p_vaddr: 0,
+        p_paddr: 0,
+        p_filesz: notes_len as u64,
+        p_memsz: 0,
+        p_align: 4,
+    });
+
+    let mut offset = notes_end.next_multiple_of(PAGE_SIZE);
+    for segment in segments {
+        let size = segment.end - segment.start;
+        let filesz = if offset + size <= limit { size } else { 0 };
+        phdrs.push(Elf64ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags: segment.flags,
+            p_offset: offset,
+            p_vaddr: segment.start,
+            p_paddr: 0,
+            p_filesz: filesz,
+            p_memsz: size,
+            p_align: PAGE_SIZE,
+        });
+        offset += filesz;
+    }
+    Some(phdrs)
+}
+
+/// Write the core file
+fn write_core(
+    path: &str,
+    task: &Task,
+    phdrs: &[Elf64ProgramHeader],
+    notes: &[u8],
+) -> Result<(), FsError> {
+    let file = create_file(path, task.creds.uid, task.creds.gid)?;
+    let write = |offset: u64, data: &[u8]| match file.write_at(offset, data)? {
+        written if written == data.len() => Ok(()),
+        _ => Err(FsError::NoSpace),
+    };
+
+    let mut ident = [0u8; 16];
+    ident[..4].copy_from_slice(&ELF_MAGIC);
+    ident[4] = ELFCLASS64;
+    ident[5] = ELFDATA2LSB;
+    ident[6] = EV_CURRENT;
+    let header = Elf64Header {
+        e_ident: ident,
+        e_type: ET_CORE,
+        e_machine: EM_X86_64,
+        e_version: EV_CURRENT as u32,
+        e_entry: 0,
+        e_phoff: size_of::<Elf64Header>() as u64,
+        e_shoff: 0,
+        e_flags: 0,
+        e_ehsize: size_of::<Elf64Header>() as u16,
+        e_phentsize: size_of::<Elf64ProgramHeader>() as u16,
+        e_phnum: phdrs.len() as u16,
+        e_shentsize: 0,
+        e_shnum: 0,
+        e_shstrndx: 0,
+    };
+
+    let mut offset = 0;
+    write(offset, as_bytes(&header))?;
+    offset += size_of::<Elf64Header>() as u64;
+    for phdr in phdrs {
+        write(offset, as_bytes(phdr))?;
+        offset += size_of::<Elf64ProgramHeader>() as u64;
+    }
+    write(phdrs[0].p_offset, notes)?;
+
+    // Memory is copied a page at a time; pages never mapped are zeros
+    let mut page = alloc::vec![0u8; PAGE_SIZE as usize];
+    for phdr in &phdrs[1..] {
+        let mut done = 0;
+        while done < phdr.p_filesz {
+            let addr = (phdr.p_vaddr + done) as usize;
+            if super::process::copy_from_user(&mut page, addr, PAGE_SIZE as usize).is_err() {
+                page.fill(0);
+            }
+            write(phdr.p_offset + done, &page)?;
+            done += PAGE_SIZE;
+        }
+    }
+    Ok(())
+}
+
+/// Create `path`, or empty it if it exists
+fn create_file(path: &str, uid: u32, gid: u32) -> Result<Arc<dyn Inode>, FsError> {
+    let (parent, name) = crate::fs::vfs::path::resolve_parent(path, None)?;
+    match parent.lookup(&name) {
+        Ok(inode) if inode.mode().is_file() => {
+            inode.truncate(0)?;
+            Ok(inode)
+        }
+        Ok(_) => Err(FsError::AlreadyExists),
+        Err(FsError::NotFound) => {
+            parent.create(&name, FileMode::new(FileMode::S_IFREG | 0o600), uid, gid)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Append a note owned by "CORE"
+fn push_note(notes: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+    const NAME: &[u8; 8] = b"CORE\0\0\0\0";
+    notes.extend_from_slice(&5u32.to_le_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&note_type.to_le_bytes());
+    notes.extend_from_slice(NAME);
+    notes.extend_from_slice(desc);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+}
+
+/// View a record as bytes to write out
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// Thread status note (Linux `elf_prstatus` layout)
+#[repr(C)]
+struct Prstatus {
+    /// `si_signo`, `si_code`, `si_errno`
+    pr_info: [i32; 3],
+    pr_cursig: u16,
+    pr_sigpend: u64,
+    pr_sighold: u64,
+    pr_pid: i32,
+    pr_ppid: i32,
+    pr_pgrp: i32,
+    pr_sid: i32,
+    /// User, system and children's times as `timeval`s
+    pr_times: [[i64; 2]; 4],
+    /// Registers in `user_regs_struct` order
+    pr_reg: UserRegs,
+    pr_fpvalid: i32,
+}
+
+/// Process description note (Linux `elf_prpsinfo` layout)
+#[repr(C)]
+struct Prpsinfo {
+    pr_state: u8,
+    pr_sname: u8,
+    pr_zomb: u8,
+    pr_nice: i8,
+    pr_flag: u64,
+    pr_uid: u32,
+    pr_gid: u32,
+    pr_pid: i32,
+    pr_ppid: i32,
+    pr_pgrp: i32,
+    pr_sid: i32,
+    pr_fname: [u8; 16],
+    pr_psargs: [u8; 80],
+}
+
+/// Nanoseconds as a `timeval`
+fn timeval(ns: u64) -> [i64; 2] {
+    [
+        (ns / 1_000_000_000) as i64,
+        (ns % 1_000_000_000 / 1000) as i64,
+    ]
+}
+
+fn prstatus(task: &Task, signal: Signal, regs: &SyscallFrame) -> Prstatus {
+    let fs_base = crate::arch::x86_64::tls::read_fs_base();
+    Prstatus {
+        pr_info: [signal as i32, 0, 0],
+        pr_cursig: signal as u16,
+        pr_sigpend: task.pending_signals.load(Ordering::Acquire),
+        pr_sighold: task.get_signal_mask(),
+        pr_pid: task.id as i32,
+        pr_ppid: task.ppid as i32,
+        pr_pgrp: task.pgid as i32,
+        pr_sid: task.sid as i32,
+        pr_times: [
+            timeval(task.rusage.utime_ns),
+            timeval(task.rusage.stime_ns),
+            timeval(task.child_rusage.utime_ns),
+            timeval(task.child_rusage.stime_ns),
+        ],
+        // Not in a syscall, so no ORIG_RAX
+        pr_reg: UserRegs::new(regs, u64::MAX, fs_base),
+        pr_fpvalid: 0,
+    }
+}
+
+fn prpsinfo(task: &Task, comm: &str) -> Prpsinfo {
+    let mut fname = [0u8; 16];
+    fname[..comm.len()].copy_from_slice(comm.as_bytes());
+    let args = task
+        .image
+        .as_ref()
+        .map_or(comm, |image| image.args.as_str());
+    let mut psargs = [0u8; 80];
+    let len = args.len().min(psargs.len() - 1);
+    psargs[..len].copy_from_slice(&args.as_bytes()[..len]);
+
+    Prpsinfo {
+        pr_state: 0,
+        pr_sname: b'R',
+        pr_zomb: 0,
+        pr_nice: 0,
+        pr_flag: 0,
+        pr_uid: task.creds.uid,
+        pr_gid: task.creds.gid,
+        pr_pid: task.pid as i32,
+        pr_ppid: task.ppid as i32,
+        pr_pgrp: task.pgid as i32,
+        pr_sid: task.sid as i32,
+        pr_fname: fname,
+        pr_psargs: psargs,
+    }
+}
+
+/// The auxiliary vector describing the program, as `(type, value)` words
+fn auxv(task: &Task) -> Vec<u8> {
+    let mut entries = Vec::new();
+    if let Some(image) = &task.image {
+        if let Some(phdr) = image.phdr {
+            entries.push((AT_PHDR, phdr));
+            entries.push((AT_PHENT, size_of::<Elf64ProgramHeader>() as u64));
+            entries.push((AT_PHNUM, image.phnum as u64));
+        }
+        entries.push((AT_ENTRY, image.entry));
+    }
+    entries.push((AT_PAGESZ, PAGE_SIZE));
+    entries.push((AT_NULL, 0));
+
+    entries
+        .iter()
+        .flat_map(|&(key, value)| [key.to_le_bytes(), value.to_le_bytes()])
+        .flatten()
+        .collect()
+}
+
+/// Where the executable is mapped: a count and the page size, a
+/// `(start, end, page offset)` triple per range, then the file names
+fn file_note(task: &Task) -> Option<Vec<u8>> {
+    let image = task.image.as_ref()?;
+    let mut note = Vec::new();
+    note.extend_from_slice(&(image.file_ranges.len() as u64).to_le_bytes());
+    note.extend_from_slice(&PAGE_SIZE.to_le_bytes());
+    for &(start, end, offset) in &image.file_ranges {
+        note.extend_from_slice(&start.to_le_bytes());
+        note.extend_from_slice(&end.to_le_bytes());
+        note.extend_from_slice(&(offset / PAGE_SIZE).to_le_bytes());
+    }
+    for _ in &image.file_ranges {
+        note.extend_from_slice(image.path.as_bytes());
+        note.push(0);
+    }
+    Some(note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_layouts() {
+        assert_eq!(size_of::<Prstatus>(), 336);
+        assert_eq!(core::mem::offset_of!(Prstatus, pr_pid), 32);
+        assert_eq!(core::mem::offset_of!(Prstatus, pr_reg), 112);
+        assert_eq!(size_of::<Prpsinfo>(), 136);
+        assert_eq!(core::mem::offset_of!(Prpsinfo, pr_fname), 40);
+
+        let mut notes = Vec::new();
+        push_note(&mut notes, NT_AUXV, &[1, 2, 3]);
+        assert_eq!(notes.len(), 12 + 8 + 4);
+    }
+
+    #[test]
+    fn test_expand_pattern() {
+        assert_eq!(
+            expand_pattern("/var/core/core.%e.%p", "sh", 42, 11),
+            "/var/core/core.sh.42"
+        );
+        assert_eq!(expand_pattern("/tmp/%s%%", "sh", 42, 6), "/tmp/6%");
+    }
+
+    #[test]
+    fn test_program_headers_limit() {
+        let segment = |start: u64, pages: u64| Segment {
+            start,
+            end: start + pages * PAGE_SIZE,
+            flags: PF_R | PF_W,
+        };
+        let segments = [
+            segment(0x400000, 2),
+            segment(0x600000, 8),
+            segment(0x800000, 1),
+        ];
+
+        let phdrs = program_headers(&segments, 100, 4 * PAGE_SIZE).unwrap();
+        assert_eq!(phdrs.len(), 4);
+        assert_eq!(phdrs[0].p_type, PT_NOTE);
+        assert_eq!(phdrs[1].p_offset, PAGE_SIZE);
+        assert_eq!(phdrs[1].p_filesz, 2 * PAGE_SIZE);
+        // Too big for what is left; the next one still fits
+        assert_eq!(phdrs[2].p_filesz, 0);
+        assert_eq!(phdrs[2].p_memsz, 8 * PAGE_SIZE);
+        assert_eq!(phdrs[3].p_offset, 3 * PAGE_SIZE);
+        assert_eq!(phdrs[3].p_filesz, PAGE_SIZE);
+
+        assert!(program_headers(&segments, 100, 64).is_none());
+    }
+}
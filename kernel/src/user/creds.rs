@@ -0,0 +1,414 @@
+//! User and Group Credentials
+//!
+//! Each task carries a real, effective and saved user ID and group ID and
+//! a list of supplementary groups (`sched::task::Credentials`). Permission
+//! checks use the effective IDs and the supplementary groups; the real ID
+//! is who started the process, and the saved ID remembers the effective ID
+//! a setuid program started with, so it can drop privileges and take them
+//! back later.
+//!
+//! The rules for changing IDs follow POSIX. A privileged caller
+//! (`CAP_SETUID` for user IDs, `CAP_SETGID` for group IDs) may set any
+//! ID; any other caller may only switch between its current real,
+//! effective and saved IDs. Changing user IDs also updates the capability
+//! sets (`capability::after_setuid`). Credentials are shared by all
+//! threads of a process, so a change applies to the whole thread group.
+
+use super::capability::{self, Capabilities};
+use super::process::{ProcessError, ProcessResult};
+use crate::sched::task::{Credentials, NGROUPS_MAX};
+
+/// The real, effective and saved values of one kind of ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ids {
+    real: u32,
+    effective: u32,
+    saved: u32,
+}
+
+impl Ids {
+    fn user(creds: &Credentials) -> Self {
+        Self {
+            real: creds.ruid,
+            effective: creds.uid,
+            saved: creds.suid,
+        }
+    }
+
+    fn group(creds: &Credentials) -> Self {
+        Self {
+            real: creds.rgid,
+            effective: creds.gid,
+            saved: creds.sgid,
+        }
+    }
+
+    fn contains(&self, id: u32) -> bool {
+        id == self.real || id == self.effective || id == self.saved
+    }
+
+    /// `setuid`: a privileged caller sets all three IDs, anyone else may
+    /// only set the effective ID to the real or saved ID
+    fn set(self, privileged: bool, id: u32) -> ProcessResult<Self> {
+        if privileged {
+            return Ok(Self {
+                real: id,
+                effective: id,
+                saved: id,
+            });
+        }
+        if id != self.real && id != self.saved {
+            return Err(ProcessError::PermissionDenied);
+        }
+        Ok(Self {
+            effective: id,
+            ..self
+        })
+    }
+
+    /// `setreuid`: the real ID may become the effective ID, the effective
+    /// ID any of the three. The saved ID follows the new effective ID when
+    /// the real ID is set or the effective ID moves off the real ID.
+    fn set_re(
+        self,
+        privileged: bool,
+        real: Option<u32>,
+        effective: Option<u32>,
+    ) -> ProcessResult<Self> {
+        if !privileged {
+            if real.is_some_and(|id| id != self.real && id != self.effective) {
+                return Err(ProcessError::PermissionDenied);
+            }
+            if effective.is_some_and(|id| !self.contains(id)) {
+                return Err(ProcessError::PermissionDenied);
+            }
+        }
+        let mut ids = self;
+        if let Some(id) = real {
+            ids.real = id;
+        }
+        if let Some(id) = effective {
+            ids.effective = id;
+        }
+        if real.is_some() || effective.is_some_and(|id| id != self.real) {
+            ids.saved = ids.effective;
+        }
+        Ok(ids)
+    }
+
+    /// `setresuid`: each ID may become any of the current three
+    fn set_res(
+        self,
+        privileged: bool,
+        real: Option<u32>,
+        effective: Option<u32>,
+        saved: Option<u32>,
+    ) -> ProcessResult<Self> {
+        let allowed = |id: Option<u32>| id.map_or(true, |id| self.contains(id));
+        if !privileged && !(allowed(real) && allowed(effective) && allowed(saved)) {
+            return Err(ProcessError::PermissionDenied);
+        }
+        Ok(Self {
+            real: real.unwrap_or(self.real),
+            effective: effective.unwrap_or(self.effective),
+            saved: saved.unwrap_or(self.saved),
+        })
+    }
+}
+
+fn with_user_ids(creds: &Credentials, ids: Ids) -> Credentials {
+    let mut new = Credentials {
+        ruid: ids.real,
+        uid: ids.effective,
+        suid: ids.saved,
+        ..*creds
+    };
+    new.caps = capability::after_setuid(creds, &new);
+    new
+}
+
+fn may_set_uids(creds: &Credentials) -> bool {
+    creds.capable(Capabilities::CAP_SETUID)
+}
+
+fn may_set_gids(creds: &Credentials) -> bool {
+    creds.capable(Capabilities::CAP_SETGID)
+}
+
+fn with_group_ids(creds: &Credentials, ids: Ids) -> Credentials {
+    Credentials {
+        rgid: ids.real,
+        gid: ids.effective,
+        sgid: ids.saved,
+        ..*creds
+    }
+}
+
+/// Credentials after `setuid(uid)`
+pub fn setuid(creds: &Credentials, uid: u32) -> ProcessResult<Credentials> {
+    let ids = Ids::user(creds).set(may_set_uids(creds), uid)?;
+    Ok(with_user_ids(creds, ids))
+}
+
+/// Credentials after `setgid(gid)`
+pub fn setgid(creds: &Credentials, gid: u32) -> ProcessResult<Credentials> {
+    let ids = Ids::group(creds).set(may_set_gids(creds), gid)?;
+    Ok(with_group_ids(creds, ids))
+}
+
+/// Credentials after `setreuid(ruid, euid)`; `None` leaves an ID as is
+pub fn setreuid(
+    creds: &Credentials,
+    ruid: Option<u32>,
+    euid: Option<u32>,
+) -> ProcessResult<Credentials> {
+    let ids = Ids::user(creds).set_re(may_set_uids(creds), ruid, euid)?;
+    Ok(with_user_ids(creds, ids))
+}
+
+/// Credentials after `setregid(rgid, egid)`; `None` leaves an ID as is
+pub fn setregid(
+    creds: &Credentials,
+    rgid: Option<u32>,
+    egid: Option<u32>,
+) -> ProcessResult<Credentials> {
+    let ids = Ids::group(creds).set_re(may_set_gids(creds), rgid, egid)?;
+    Ok(with_group_ids(creds, ids))
+}
+
+/// Credentials after `setresuid(ruid, euid, suid)`; `None` leaves an ID as is
+pub fn setresuid(
+    creds: &Credentials,
+    ruid: Option<u32>,
+    euid: Option<u32>,
+    suid: Option<u32>,
+) -> ProcessResult<Credentials> {
+    let ids = Ids::user(creds).set_res(may_set_uids(creds), ruid, euid, suid)?;
+    Ok(with_user_ids(creds, ids))
+}
+
+/// Credentials after `setresgid(rgid, egid, sgid)`; `None` leaves an ID as is
+pub fn setresgid(
+    creds: &Credentials,
+    rgid: Option<u32>,
+    egid: Option<u32>,
+    sgid: Option<u32>,
+) -> ProcessResult<Credentials> {
+    let ids = Ids::group(creds).set_res(may_set_gids(creds), rgid, egid, sgid)?;
+    Ok(with_group_ids(creds, ids))
+}
+
+/// Credentials after `setgroups(groups)`; needs `CAP_SETGID`
+pub fn setgroups(creds: &Credentials, groups: &[u32]) -> ProcessResult<Credentials> {
+    if !may_set_gids(creds) {
+        return Err(ProcessError::PermissionDenied);
+    }
+    if groups.len() > NGROUPS_MAX {
+        return Err(ProcessError::InvalidArgument);
+    }
+    let mut new = *creds;
+    new.groups = [0; NGROUPS_MAX];
+    new.groups[..groups.len()].copy_from_slice(groups);
+    new.ngroups = groups.len();
+    Ok(new)
+}
+
+/// Credentials after exec'ing a file owned by `owner` with `mode`
+///
+/// A set-user-ID file makes its owner the effective user ID, and a
+/// set-group-ID file (with group execute permission) its group the
+/// effective group ID, unless `nosuid` says the bits must be ignored. The
+/// saved IDs then take the effective IDs, and the capability sets are
+/// recomputed for the new program (`capability::after_exec`).
+pub fn exec(creds: &Credentials, mode: u16, owner: (u32, u32), nosuid: bool) -> Credentials {
+    use crate::fs::vfs::inode::FileMode;
+
+    let mut new = *creds;
+    if !nosuid {
+        if mode & FileMode::S_ISUID != 0 {
+            new.uid = owner.0;
+        }
+        if mode & FileMode::S_ISGID != 0 && mode & FileMode::S_IXGRP != 0 {
+            new.gid = owner.1;
+        }
+    }
+    new.suid = new.uid;
+    new.sgid = new.gid;
+    new.caps = capability::after_exec(&new);
+    new
+}
+
+/// Whether `caller` acts as the same user as `target`
+///
+/// The caller's real user and group IDs must equal each of the target's
+/// real, effective and saved IDs, so a set-ID program that has only
+/// dropped its effective ID for now still belongs to another user.
+pub fn same_user(caller: &Credentials, target: &Credentials) -> bool {
+    [target.ruid, target.uid, target.suid]
+        .iter()
+        .all(|&id| id == caller.ruid)
+        && [target.rgid, target.gid, target.sgid]
+            .iter()
+            .all(|&id| id == caller.rgid)
+}
+
+/// Whether a process stays dumpable when its credentials go from `old`
+/// to `new`
+///
+/// A process that takes on other effective IDs or more permitted
+/// capabilities may hold data its real user must not see, so it stops
+/// being dumpable (see `Task::dumpable`).
+pub fn keeps_dumpable(old: &Credentials, new: &Credentials) -> bool {
+    new.uid == old.uid && new.gid == old.gid && old.caps.permitted.contains(new.caps.permitted)
+}
+
+/// Credentials of the calling task
+pub fn current() -> Credentials {
+    crate::sched::current_task().map_or(Credentials::kernel(), |task| task.creds)
+}
+
+/// Replace the caller's credentials with `change(current)`
+///
+/// The new credentials are installed in every thread of the process, which
+/// stops being dumpable if `keeps_dumpable` says so.
+pub fn update<F>(change: F) -> ProcessResult<()>
+where
+    F: FnOnce(&Credentials) -> ProcessResult<Credentials>,
+{
+    let task = crate::sched::current_task_mut().ok_or(ProcessError::ProcessNotFound)?;
+    let new = change(&task.creds)?;
+    let dumpable = task.dumpable && keeps_dumpable(&task.creds, &new);
+    match task.thread_group.clone() {
+        Some(group) => {
+            for id in group.members() {
+                if let Some(member) = crate::sched::get_task_mut(id) {
+                    member.creds = new;
+                    member.dumpable = dumpable;
+                }
+            }
+        }
+        None => {
+            task.creds = new;
+            task.dumpable = dumpable;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: u32 = 1000;
+    const BOB: u32 = 1001;
+
+    #[test]
+    fn test_same_user_and_dumpable() {
+        use crate::fs::vfs::inode::FileMode;
+
+        let alice = Credentials::user(ALICE, ALICE);
+        assert!(same_user(&alice, &alice));
+        assert!(!same_user(&alice, &Credentials::user(BOB, BOB)));
+
+        // Running a setuid-root program, even after seteuid(getuid())
+        let setuid_root = exec(&alice, FileMode::S_ISUID | 0o755, (0, 0), false);
+        assert!(!keeps_dumpable(&alice, &setuid_root));
+        let dropped = setresuid(&setuid_root, None, Some(ALICE), None).unwrap();
+        assert_eq!(dropped.uid, ALICE);
+        assert!(!same_user(&alice, &dropped));
+
+        // A plain exec, and root giving up its privileges
+        assert!(keeps_dumpable(
+            &alice,
+            &exec(&alice, 0o755, (BOB, BOB), false)
+        ));
+        let root = Credentials::user(0, 0);
+        assert!(!keeps_dumpable(&root, &setuid(&root, ALICE).unwrap()));
+    }
+
+    #[test]
+    fn test_setuid() {
+        // Root drops all privileges at once
+        let creds = setuid(&Credentials::user(0, 0), ALICE).unwrap();
+        assert_eq!((creds.ruid, creds.uid, creds.suid), (ALICE, ALICE, ALICE));
+        assert!(setuid(&creds, 0).is_err());
+
+        // A setuid program can switch between the real and saved IDs
+        let setuid_bob = Credentials {
+            uid: BOB,
+            suid: BOB,
+            ..Credentials::user(ALICE, ALICE)
+        };
+        let dropped = setuid(&setuid_bob, ALICE).unwrap();
+        assert_eq!(
+            (dropped.ruid, dropped.uid, dropped.suid),
+            (ALICE, ALICE, BOB)
+        );
+        let regained = setuid(&dropped, BOB).unwrap();
+        assert_eq!(regained.uid, BOB);
+    }
+
+    #[test]
+    fn test_setreuid() {
+        let creds = Credentials::user(ALICE, ALICE);
+        assert!(setreuid(&creds, None, Some(BOB)).is_err());
+        assert!(setreuid(&creds, Some(BOB), None).is_err());
+
+        // Swapping real and effective IDs updates the saved ID
+        let setuid_bob = Credentials {
+            uid: BOB,
+            suid: BOB,
+            ..creds
+        };
+        let swapped = setreuid(&setuid_bob, Some(BOB), Some(ALICE)).unwrap();
+        assert_eq!(
+            (swapped.ruid, swapped.uid, swapped.suid),
+            (BOB, ALICE, ALICE)
+        );
+    }
+
+    #[test]
+    fn test_setresuid() {
+        let setuid_root = Credentials {
+            uid: 0,
+            suid: 0,
+            ..Credentials::user(ALICE, ALICE)
+        };
+        // Root may set anything; others only shuffle their current IDs
+        let dropped = setresuid(&setuid_root, None, Some(ALICE), None).unwrap();
+        assert_eq!((dropped.ruid, dropped.uid, dropped.suid), (ALICE, ALICE, 0));
+        assert!(setresuid(&dropped, None, Some(BOB), None).is_err());
+        assert_eq!(setresuid(&dropped, None, Some(0), None).unwrap().uid, 0);
+
+        let groups = setresgid(&Credentials::user(0, 0), Some(BOB), Some(BOB), Some(BOB)).unwrap();
+        assert_eq!((groups.rgid, groups.gid, groups.sgid), (BOB, BOB, BOB));
+    }
+
+    #[test]
+    fn test_setgroups() {
+        let root = setgroups(&Credentials::user(0, 0), &[10, 20]).unwrap();
+        assert_eq!(root.groups(), &[10, 20]);
+        assert!(root.in_group(20));
+        assert!(!root.in_group(30));
+        assert!(setgroups(&Credentials::user(ALICE, ALICE), &[10]).is_err());
+        assert!(setgroups(&root, &[0; NGROUPS_MAX + 1]).is_err());
+    }
+
+    #[test]
+    fn test_exec_setuid() {
+        use crate::fs::vfs::inode::FileMode;
+
+        let creds = Credentials::user(ALICE, ALICE);
+        let mode = FileMode::S_ISUID | FileMode::S_ISGID | 0o755;
+        let new = exec(&creds, mode, (0, 0), false);
+        assert_eq!((new.ruid, new.uid, new.suid), (ALICE, 0, 0));
+        assert_eq!((new.rgid, new.gid, new.sgid), (ALICE, 0, 0));
+
+        // Ignored on nosuid mounts, and setgid needs group execute
+        assert_eq!(exec(&creds, mode, (0, 0), true).uid, ALICE);
+        assert_eq!(
+            exec(&creds, FileMode::S_ISGID | 0o744, (0, 0), false).gid,
+            ALICE
+        );
+    }
+}
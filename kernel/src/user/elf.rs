@@ -10,68 +10,67 @@ use crate::serial_println;
 use core::mem;
 
 /// ELF identification bytes
-const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+pub(crate) const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
 
 /// ELF class (64-bit)
-const ELFCLASS64: u8 = 2;
+pub(crate) const ELFCLASS64: u8 = 2;
 
 /// ELF data encoding (little-endian)
-const ELFDATA2LSB: u8 = 1;
+pub(crate) const ELFDATA2LSB: u8 = 1;
 
 /// ELF version (current)
-const EV_CURRENT: u8 = 1;
+pub(crate) const EV_CURRENT: u8 = 1;
 
 /// ELF file types
 const ET_EXEC: u16 = 2; // Executable file
 
 /// ELF machine types
-const EM_X86_64: u16 = 62; // AMD x86-64 architecture
+pub(crate) const EM_X86_64: u16 = 62; // AMD x86-64 architecture
 
 /// Program header types
-const PT_LOAD: u32 = 1; // Loadable segment
+pub(crate) const PT_LOAD: u32 = 1; // Loadable segment
 const PT_GNU_STACK: u32 = 0x6474e551; // GNU stack segment
 
 /// Program header flags
-const PF_X: u32 = 1; // Execute
-const PF_W: u32 = 2; // Write
-const PF_R: u32 = 4; // Read
+pub(crate) const PF_X: u32 = 1; // Execute
+pub(crate) const PF_W: u32 = 2; // Write
+pub(crate) const PF_R: u32 = 4; // Read
 
-/// User stack configuration
-const USER_STACK_TOP: usize = 0x0000_7FFF_FFFF_0000;
+/// User stack configuration (the top is randomized, see `mm::aslr::stack_top`)
 const USER_STACK_SIZE: usize = 8192; // 8KB
 
 /// ELF64 Header structure
 #[repr(C)]
 #[derive(Debug)]
-struct Elf64Header {
-    e_ident: [u8; 16], // ELF identification
-    e_type: u16,       // Object file type (ET_EXEC = 2)
-    e_machine: u16,    // Machine type (EM_X86_64 = 62)
-    e_version: u32,    // Object file version
-    e_entry: u64,      // Entry point address
-    e_phoff: u64,      // Program header offset
-    e_shoff: u64,      // Section header offset
-    e_flags: u32,      // Processor-specific flags
-    e_ehsize: u16,     // ELF header size
-    e_phentsize: u16,  // Program header entry size
-    e_phnum: u16,      // Number of program header entries
-    e_shentsize: u16,  // Section header entry size
-    e_shnum: u16,      // Number of section header entries
-    e_shstrndx: u16,   // Section header string table index
+pub(crate) struct Elf64Header {
+    pub(crate) e_ident: [u8; 16], // ELF identification
+    pub(crate) e_type: u16,       // Object file type (ET_EXEC = 2)
+    pub(crate) e_machine: u16,    // Machine type (EM_X86_64 = 62)
+    pub(crate) e_version: u32,    // Object file version
+    pub(crate) e_entry: u64,      // Entry point address
+    pub(crate) e_phoff: u64,      // Program header offset
+    pub(crate) e_shoff: u64,      // Section header offset
+    pub(crate) e_flags: u32,      // Processor-specific flags
+    pub(crate) e_ehsize: u16,     // ELF header size
+    pub(crate) e_phentsize: u16,  // Program header entry size
+    pub(crate) e_phnum: u16,      // Number of program header entries
+    pub(crate) e_shentsize: u16,  // Section header entry size
+    pub(crate) e_shnum: u16,      // Number of section header entries
+    pub(crate) e_shstrndx: u16,   // Section header string table index
 }
 
 /// ELF64 Program Header structure
 #[repr(C)]
 #[derive(Debug)]
-struct Elf64ProgramHeader {
-    p_type: u32,   // Segment type (PT_LOAD = 1)
-    p_flags: u32,  // Segment flags (PF_X=1, PF_W=2, PF_R=4)
-    p_offset: u64, // Segment file offset
-    p_vaddr: u64,  // Segment virtual address
-    p_paddr: u64,  // Segment physical address
-    p_filesz: u64, // Segment size in file
-    p_memsz: u64,  // Segment size in memory
-    p_align: u64,  // Segment alignment
+pub(crate) struct Elf64ProgramHeader {
+    pub(crate) p_type: u32,   // Segment type (PT_LOAD = 1)
+    pub(crate) p_flags: u32,  // Segment flags (PF_X=1, PF_W=2, PF_R=4)
+    pub(crate) p_offset: u64, // Segment file offset
+    pub(crate) p_vaddr: u64,  // Segment virtual address
+    pub(crate) p_paddr: u64,  // Segment physical address
+    pub(crate) p_filesz: u64, // Segment size in file
+    pub(crate) p_memsz: u64,  // Segment size in memory
+    pub(crate) p_align: u64,  // Segment alignment
 }
 
 /// ELF loader error types
@@ -119,18 +118,18 @@ impl<'a> ElfLoader<'a> {
     ///
     /// # Arguments
     /// * `elf_data` - Raw ELF binary data
-    /// * `process` - Process to load the binary into (uses process-specific page table)
+    /// * `task` - Task to load the binary into (uses the task's own page table)
     ///
     /// # Returns
     /// Entry point address and stack top on success, or ElfError on failure
     pub fn load_elf_for_process(
         &mut self,
         elf_data: &[u8],
-        process: &mut crate::user::process::Process,
+        task: &mut Task,
     ) -> Result<(u64, u64), ElfError> {
         serial_println!(
             "[ELF] Loading ELF binary for process {} ({} bytes)",
-            process.pid,
+            task.pid,
             elf_data.len()
         );
 
@@ -146,7 +145,7 @@ impl<'a> ElfLoader<'a> {
         serial_println!("[ELF] Found {} program headers", program_headers.len());
 
         // 4. Clear existing memory regions
-        process.clear_memory_regions();
+        task.clear_memory_regions();
 
         // 5. Map PT_LOAD segments into process page table
         for (i, phdr) in program_headers.iter().enumerate() {
@@ -158,7 +157,7 @@ impl<'a> ElfLoader<'a> {
                     phdr.p_vaddr + phdr.p_memsz,
                     phdr.p_flags
                 );
-                self.map_segment_for_process(elf_data, phdr, process)?;
+                self.map_segment_for_process(elf_data, phdr, task)?;
             } else if phdr.p_type == PT_GNU_STACK {
                 serial_println!(
                     "[ELF] Found GNU_STACK segment (flags: 0x{:x})",
@@ -168,11 +167,11 @@ impl<'a> ElfLoader<'a> {
         }
 
         // 6. Set up user stack in process page table
-        let user_stack_top = self.setup_user_stack_for_process(process)?;
+        let user_stack_top = self.setup_user_stack_for_process(task)?;
 
         serial_println!(
             "[ELF] ELF loading completed for process {} (entry=0x{:x}, stack_top=0x{:x})",
-            process.pid,
+            task.pid,
             header.e_entry,
             user_stack_top
         );
@@ -481,7 +480,7 @@ impl<'a> ElfLoader<'a> {
         &mut self,
         elf_data: &[u8],
         phdr: &Elf64ProgramHeader,
-        process: &mut crate::user::process::Process,
+        task: &mut Task,
     ) -> Result<(), ElfError> {
         let vaddr = phdr.p_vaddr as usize;
         let size = phdr.p_memsz as usize;
@@ -524,14 +523,13 @@ impl<'a> ElfLoader<'a> {
             MemoryRegionType::Data // Read-only data
         };
 
-        // Map pages and copy data into process page table
-        // TODO: When process has its own page table, use process.page_table
-        // For now, we use the global mapper (shared address space)
+        // Map pages and copy data into the process's own page table
+        let mut mapper = self.process_mapper(task)?;
         for page_addr in (start_page..end_page).step_by(4096) {
             let phys_frame = self.pmm.alloc_frame().ok_or(ElfError::OutOfMemory)?;
 
             // Map page in user space with USER flag
-            self.mapper
+            mapper
                 .map_page(page_addr, phys_frame, flags, self.pmm)
                 .map_err(|_| ElfError::MappingFailed)?;
 
@@ -578,22 +576,16 @@ impl<'a> ElfLoader<'a> {
                     dst.copy_from_slice(src);
                 }
             }
-
-            // Flush TLB for this page to ensure visibility
-            unsafe {
-                core::arch::asm!("invlpg [{}]", in(reg) page_addr);
-            }
         }
 
         // Add memory region to process
         let region = MemoryRegion::new(start_page, end_page, flags, region_type);
-        process
-            .add_memory_region(region)
+        task.add_memory_region(region)
             .map_err(|_| ElfError::MappingFailed)?;
 
         serial_println!(
             "[ELF] Mapped segment for process {}: 0x{:x}-0x{:x} ({:?})",
-            process.pid,
+            task.pid,
             start_page,
             end_page,
             region_type
@@ -602,31 +594,42 @@ impl<'a> ElfLoader<'a> {
         Ok(())
     }
 
+    /// Get a mapper for the process's own page table
+    ///
+    /// The PML4 (with the kernel half copied from the template) is allocated
+    /// on first use so user mappings never land in a shared table.
+    fn process_mapper(&mut self, task: &mut Task) -> Result<PageMapper, ElfError> {
+        if task.context.cr3 == 0 {
+            task.context.cr3 = crate::mm::paging::alloc_page_table_with_kernel_mappings(self.pmm)
+                .map_err(|_| ElfError::OutOfMemory)? as u64;
+        }
+
+        Ok(PageMapper::for_address_space(task.address_space()))
+    }
+
     /// Set up user stack with guard pages (process-specific page table)
-    fn setup_user_stack_for_process(
-        &mut self,
-        process: &mut crate::user::process::Process,
-    ) -> Result<u64, ElfError> {
-        let stack_top = crate::user::process::USER_STACK_TOP;
-        let stack_size = crate::user::process::USER_STACK_SIZE;
+    ///
+    /// The stack top is randomized like on exec.
+    fn setup_user_stack_for_process(&mut self, task: &mut Task) -> Result<u64, ElfError> {
+        let stack_top = crate::mm::aslr::stack_top() as usize;
+        let stack_size = USER_STACK_SIZE;
         let stack_bottom = stack_top - stack_size;
         let guard_page = stack_bottom - 4096;
 
         serial_println!(
             "[ELF] Setting up user stack for process {}: 0x{:x}-0x{:x} (guard @ 0x{:x})",
-            process.pid,
+            task.pid,
             stack_bottom,
             stack_top,
             guard_page
         );
 
         // Map stack pages (RW + NX + USER) into process page table
-        // TODO: When process has its own page table, use process.page_table
-        // For now, we use the global mapper (shared address space)
+        let mut mapper = self.process_mapper(task)?;
         for addr in (stack_bottom..stack_top).step_by(4096) {
             let phys_frame = self.pmm.alloc_frame().ok_or(ElfError::OutOfMemory)?;
 
-            self.mapper
+            mapper
                 .map_page(
                     addr,
                     phys_frame,
@@ -660,27 +663,28 @@ impl<'a> ElfLoader<'a> {
             MemoryRegionType::Stack,
         );
 
-        process
-            .add_memory_region(stack_region)
+        task.add_memory_region(stack_region)
             .map_err(|_| ElfError::MappingFailed)?;
 
         let aligned_top = (stack_top & !0xF) as u64;
         serial_println!(
             "[ELF] User stack top aligned to 0x{:x} for process {}",
             aligned_top,
-            process.pid
+            task.pid
         );
 
         serial_println!(
             "[ELF] User stack set up successfully for process {}",
-            process.pid
+            task.pid
         );
         Ok(aligned_top)
     }
 
     /// Set up user stack with guard pages
+    ///
+    /// The stack top is randomized like on exec.
     fn setup_user_stack(&mut self, task: &mut Task) -> Result<u64, ElfError> {
-        let stack_top = USER_STACK_TOP;
+        let stack_top = crate::mm::aslr::stack_top() as usize;
         let stack_size = USER_STACK_SIZE;
         let stack_bottom = stack_top - stack_size;
         let guard_page = stack_bottom - 4096;
@@ -819,6 +823,51 @@ mod tests {
         assert!(task.find_memory_region(0x500).is_none());
     }
 
+    /// Test that memory regions are held to the task's memory limits
+    #[test]
+    fn test_memory_region_limits() {
+        use crate::sched::task::SchedulerError;
+        use crate::user::rlimit::{RLimit, RLIMIT_AS, RLIMIT_DATA, RLIMIT_STACK};
+
+        let mut task = Task::new(1, "test", dummy_test_entry, TaskPriority::Normal)
+            .expect("Failed to create test task");
+        task.creds = crate::sched::task::Credentials::user(1000, 1000);
+        task.rlimits = task
+            .rlimits
+            .set(RLIMIT_AS, RLimit::new(0x10000, 0x10000), false)
+            .unwrap()
+            .set(RLIMIT_DATA, RLimit::new(0x2000, 0x2000), false)
+            .unwrap()
+            .set(RLIMIT_STACK, RLimit::new(0x4000, 0x4000), false)
+            .unwrap();
+        let flags = PageTableFlags::PRESENT | PageTableFlags::USER;
+        let region = |start, end, region_type| MemoryRegion::new(start, end, flags, region_type);
+
+        assert!(task
+            .add_memory_region(region(0x1000, 0x3000, MemoryRegionType::Data))
+            .is_ok());
+        assert_eq!(
+            task.add_memory_region(region(0x3000, 0x4000, MemoryRegionType::Bss)),
+            Err(SchedulerError::ResourceLimit)
+        );
+        assert_eq!(
+            task.add_memory_region(region(0x8000, 0xd000, MemoryRegionType::Stack)),
+            Err(SchedulerError::ResourceLimit)
+        );
+        assert!(task
+            .add_memory_region(region(0x8000, 0xc000, MemoryRegionType::Stack))
+            .is_ok());
+
+        // Code counts against the address space only
+        assert_eq!(
+            task.add_memory_region(region(0x20000, 0x2b000, MemoryRegionType::Code)),
+            Err(SchedulerError::ResourceLimit)
+        );
+        assert!(task
+            .add_memory_region(region(0x20000, 0x2a000, MemoryRegionType::Code))
+            .is_ok());
+    }
+
     /// Test ELF segment mapping flags
     #[test]
     fn test_segment_flag_mapping() {
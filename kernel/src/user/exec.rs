@@ -32,7 +32,9 @@
 //! - Buffer overflows from excessively long strings
 //! - Resource exhaustion from too many arguments
 
-use crate::sched::task::Task;
+use crate::arch::x86_64::fpu::FpuState;
+use crate::fs::vfs::inode::Inode;
+use crate::sched::task::{Credentials, Task};
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -120,7 +122,7 @@ impl ExecContext {
     /// 5. Load new program segments
     /// 6. Setup new stack with argc/argv/envp
     /// 7. Close O_CLOEXEC file descriptors
-    /// 8. Update task state
+    /// 8. Update task state (including set-user-ID credentials)
     /// 9. Jump to userspace (never returns)
     ///
     /// # Returns
@@ -178,7 +180,7 @@ impl ExecContext {
 
         // Step 1: Load ELF binary from filesystem
         serial_println!("[EXEC] Step 1: Loading ELF from filesystem...");
-        let elf_data = self.load_elf_from_fs().map_err(|e| {
+        let (elf_data, creds) = self.load_elf_from_fs().map_err(|e| {
             serial_println!("[EXEC] Failed to load ELF: {:?}", e);
             e
         })?;
@@ -186,7 +188,7 @@ impl ExecContext {
 
         // Step 2: Parse and validate ELF
         serial_println!("[EXEC] Step 2: Parsing ELF...");
-        let elf_info = self.parse_elf(&elf_data).map_err(|e| {
+        let mut elf_info = self.parse_elf(&elf_data).map_err(|e| {
             serial_println!("[EXEC] Failed to parse ELF: {:?}", e);
             e
         })?;
@@ -196,6 +198,19 @@ impl ExecContext {
             elf_info.segments.len()
         );
 
+        // Position-independent executables are loaded at a random base (ASLR)
+        if elf_info.is_pie {
+            elf_info.load_bias = crate::mm::aslr::pie_load_base();
+            serial_println!("[EXEC] PIE load base: 0x{:016x}", elf_info.load_bias);
+        }
+
+        // Kernel threads (e.g. init before its first exec) still run on the
+        // shared kernel template; give them a private address space first
+        self.ensure_private_address_space(pmm).map_err(|e| {
+            serial_println!("[EXEC] Failed to create address space: {:?}", e);
+            e
+        })?;
+
         // Step 3: Save current memory state for rollback
         serial_println!("[EXEC] Step 3: Saving current memory state...");
         let saved_state = self.clear_old_image().map_err(|e| {
@@ -204,6 +219,10 @@ impl ExecContext {
         })?;
         serial_println!("[EXEC] Saved {} memory regions", saved_state.regions.len());
 
+        // The old image's mmap()s go with it; unlike its segments they are
+        // not restored if the exec fails
+        crate::mm::mmap::release_mappings(self.task.pid as u64, self.task.address_space(), pmm);
+
         // From this point on, if we encounter an error, we need to rollback
         // We'll use a closure to handle rollback on error
         let rollback_on_error = |error: ExecError| -> ExecError {
@@ -254,13 +273,75 @@ impl ExecContext {
         // For exec(), we directly jump to userspace with sysretq, so we don't
         // need to update the CpuContext. Instead, we'll set up the registers
         // in jump_to_userspace().
+        let image = ProcessImage::new(&self.path, &self.argv, &elf_info);
+        unsafe {
+            let task_ptr = Arc::as_ptr(&self.task) as *mut Task;
+            (*task_ptr).image = Some(Arc::new(image));
+            (*task_ptr).dumpable = super::creds::keeps_dumpable(&(*task_ptr).creds, &creds);
+            (*task_ptr).creds = creds;
+            // The new program starts with a clean FPU state
+            (*task_ptr).fpu = FpuState::new();
+            (*task_ptr).fpu.restore();
+
+            // A tracer hears of the new program through SIGTRAP, reported
+            // on the first return from a syscall (see `user::ptrace`)
+            if (*task_ptr).ptrace.is_some() {
+                let _ = crate::signal::send_signal(&mut *task_ptr, crate::signal::signals::SIGTRAP);
+            }
+        }
         serial_println!("[EXEC] Step 7: Task state ready for userspace transition");
         serial_println!("[EXEC] Step 8: Jumping to userspace...");
 
         // Step 8: Jump to userspace
         // This never returns on success
         // The new program starts executing at the entry point
-        self.jump_to_userspace(elf_info.entry, stack_pointer)
+        self.jump_to_userspace(elf_info.entry_point(), stack_pointer)
+    }
+
+    /// Make sure the task owns a private PML4
+    ///
+    /// User mappings must never be installed into the kernel template, which
+    /// every kernel thread shares. If the task does not have its own page
+    /// table yet, a fresh one with the kernel half copied in is allocated,
+    /// attached to the task and loaded on this CPU.
+    ///
+    /// # Arguments
+    /// * `pmm` - Physical memory manager for the PML4 allocation
+    ///
+    /// # Errors
+    /// * OutOfMemory - No frame available for the new PML4
+    pub fn ensure_private_address_space(
+        &self,
+        pmm: &mut crate::mm::pmm::PhysicalMemoryManager,
+    ) -> Result<(), ExecError> {
+        use crate::mm::paging::alloc_page_table_with_kernel_mappings;
+
+        if self.task.has_private_address_space() {
+            return Ok(());
+        }
+
+        let pml4_phys =
+            alloc_page_table_with_kernel_mappings(pmm).map_err(|_| ExecError::OutOfMemory)?;
+
+        unsafe {
+            let task_ptr = Arc::as_ptr(&self.task) as *mut Task;
+            (*task_ptr).context.cr3 = pml4_phys as u64;
+        }
+
+        let is_current = crate::sched::current_task()
+            .map(|t| t.id == self.task.id)
+            .unwrap_or(false);
+        if is_current {
+            crate::mm::pcid::switch_address_space(pml4_phys);
+        }
+
+        crate::serial_println!(
+            "[EXEC] Task {} now has private address space at PML4={:#x}",
+            self.task.id,
+            pml4_phys
+        );
+
+        Ok(())
     }
 
     /// Clear the old process image
@@ -305,7 +386,7 @@ impl ExecContext {
         // Clone the memory regions for rollback
         // Note: In a full implementation, we would also save the actual page table
         // entries and their contents. For now, we just save the region descriptors.
-        let mut mapper = PageMapper::new();
+        let mut mapper = PageMapper::for_address_space(self.task.address_space());
 
         for i in 0..self.task.region_count {
             if let Some(region) = &self.task.memory_regions[i] {
@@ -436,7 +517,7 @@ impl ExecContext {
         // They remain allocated but unmapped. So restoration is simpler.
 
         // Remap each saved page back into the address space
-        let mut mapper = PageMapper::new();
+        let mut mapper = PageMapper::for_address_space(self.task.address_space());
         let mut pmm_guard = crate::mm::pmm::get_global_pmm();
         let pmm = pmm_guard.as_mut().ok_or(ExecError::OutOfMemory)?;
 
@@ -477,15 +558,18 @@ impl ExecContext {
     ///
     /// This method:
     /// 1. Resolves the path using VFS
-    /// 2. Opens the file and reads its contents
-    /// 3. Returns the file data as a Vec<u8>
+    /// 2. Checks the caller may execute the file
+    /// 3. Opens the file and reads its contents
+    /// 4. Returns the file data as a Vec<u8>, with the credentials the
+    ///    program runs with (see `exec_credentials`)
     ///
     /// # Returns
-    /// Ok(Vec<u8>) containing the ELF file data, Err on failure
+    /// Ok((Vec<u8>, Credentials)) containing the ELF file data, Err on failure
     ///
     /// # Errors
     /// * FileNotFound - File does not exist (ENOENT)
-    /// * PermissionDenied - File is not readable (EACCES)
+    /// * PermissionDenied - File is not a regular file or not executable
+    ///   by the caller (EACCES)
     /// * IoError - I/O error reading file (EIO)
     ///
     /// # Requirements
@@ -495,9 +579,9 @@ impl ExecContext {
     /// - Handle file not found error (ENOENT)
     /// - Handle permission denied error (EACCES)
     /// - Close file descriptor after reading (automatic via RAII)
-    pub fn load_elf_from_fs(&self) -> Result<Vec<u8>, ExecError> {
-        use crate::fs::vfs::path;
+    pub fn load_elf_from_fs(&self) -> Result<(Vec<u8>, Credentials), ExecError> {
         use crate::fs::vfs::superblock::FsError;
+        use crate::fs::vfs::{path, permission};
 
         // Resolve the path to an inode
         // Use None for current_dir to force absolute path resolution from root
@@ -514,6 +598,10 @@ impl ExecContext {
             return Err(ExecError::PermissionDenied);
         }
 
+        let creds = self.task.creds;
+        permission::permission(&*inode, &creds, permission::MAY_EXEC)
+            .map_err(|_| ExecError::PermissionDenied)?;
+
         // Get file size
         let file_size = inode.size();
 
@@ -545,7 +633,24 @@ impl ExecContext {
         }
 
         // File descriptor is automatically closed (inode is dropped here)
-        Ok(buffer)
+        Ok((buffer, self.exec_credentials(&creds, &*inode)))
+    }
+
+    /// Credentials the new program runs with
+    ///
+    /// A set-user-ID or set-group-ID file gives the program its owner or
+    /// group as effective ID, unless it lives on a filesystem mounted with
+    /// `MS_NOSUID`, the task is being traced (the tracer could otherwise
+    /// control a program running with someone else's rights) or has set
+    /// `no_new_privs`.
+    fn exec_credentials(&self, creds: &Credentials, inode: &dyn Inode) -> Credentials {
+        use crate::fs::vfs::mount;
+        use crate::fs::vfs::superblock::MountFlags;
+
+        let nosuid = mount::lookup_mount(&self.path)
+            .map_or(false, |mount| mount.flags.contains(MountFlags::MS_NOSUID));
+        let ignore = nosuid || self.task.ptrace.is_some() || self.task.no_new_privs;
+        super::creds::exec(creds, inode.mode().0, inode.uid_gid(), ignore)
     }
 
     /// Parse and validate ELF binary
@@ -649,6 +754,7 @@ impl ExecContext {
 
         // Parse program headers
         let mut segments = Vec::new();
+        let mut dynamic = None;
 
         for i in 0..phnum {
             let ph_offset = (phoff + (i as u64 * phentsize as u64)) as usize;
@@ -770,6 +876,21 @@ impl ExecContext {
                     offset,
                     align,
                 });
+            } else if seg_type == PT_DYNAMIC {
+                // Needed to apply relocations when a PIE is loaded at a random base
+                if offset.checked_add(filesz).is_none() || (offset + filesz) as usize > data.len() {
+                    return Err(ExecError::InvalidFormat);
+                }
+
+                dynamic = Some(ProgramSegment {
+                    seg_type,
+                    vaddr,
+                    filesz,
+                    memsz,
+                    flags,
+                    offset,
+                    align,
+                });
             }
         }
 
@@ -778,7 +899,15 @@ impl ExecContext {
             return Err(ExecError::InvalidFormat);
         }
 
-        Ok(ElfInfo { entry, segments })
+        Ok(ElfInfo {
+            entry,
+            segments,
+            dynamic,
+            is_pie: elf_type == ET_DYN,
+            load_bias: 0,
+            phoff,
+            phnum,
+        })
     }
 }
 
@@ -854,6 +983,87 @@ pub struct ElfInfo {
 
     /// Program segments to be loaded into memory
     pub segments: Vec<ProgramSegment>,
+
+    /// PT_DYNAMIC segment, if present
+    pub dynamic: Option<ProgramSegment>,
+
+    /// Whether the binary is position independent (ET_DYN)
+    pub is_pie: bool,
+
+    /// Offset added to every virtual address of the binary (0 for ET_EXEC)
+    pub load_bias: u64,
+
+    /// File offset of the program header table
+    pub phoff: u64,
+
+    /// Number of program headers
+    pub phnum: u16,
+}
+
+impl ElfInfo {
+    /// Entry point after applying the load bias
+    pub fn entry_point(&self) -> u64 {
+        self.entry + self.load_bias
+    }
+}
+
+/// What exec loaded into a process
+///
+/// Kept on the task for core dumps, which describe the executable to the
+/// debugger (`NT_AUXV`, `NT_FILE`, `NT_PRPSINFO`).
+#[derive(Debug)]
+pub struct ProcessImage {
+    /// Path the executable was loaded from
+    pub path: String,
+
+    /// Command line, arguments separated by spaces
+    pub args: String,
+
+    /// Entry point (after the load bias)
+    pub entry: u64,
+
+    /// Address of the program headers, if a segment loads them
+    pub phdr: Option<u64>,
+
+    /// Number of program headers
+    pub phnum: u16,
+
+    /// Ranges loaded from the file: (start, end, file offset), page aligned
+    pub file_ranges: Vec<(u64, u64, u64)>,
+}
+
+impl ProcessImage {
+    /// Describe the program `elf_info` was parsed from, as loaded
+    pub fn new(path: &str, argv: &[String], elf_info: &ElfInfo) -> Self {
+        const PAGE_MASK: u64 = 0xFFF;
+        let bias = elf_info.load_bias;
+
+        let phdr = elf_info
+            .segments
+            .iter()
+            .find(|seg| seg.offset <= elf_info.phoff && elf_info.phoff < seg.offset + seg.filesz)
+            .map(|seg| seg.vaddr + bias + (elf_info.phoff - seg.offset));
+
+        let file_ranges = elf_info
+            .segments
+            .iter()
+            .filter(|seg| seg.filesz > 0)
+            .map(|seg| {
+                let start = (seg.vaddr + bias) & !PAGE_MASK;
+                let end = (seg.vaddr + bias + seg.filesz + PAGE_MASK) & !PAGE_MASK;
+                (start, end, seg.offset & !PAGE_MASK)
+            })
+            .collect();
+
+        Self {
+            path: String::from(path),
+            args: argv.join(" "),
+            entry: elf_info.entry_point(),
+            phdr,
+            phnum: elf_info.phnum,
+            file_ranges,
+        }
+    }
 }
 
 /// A single program segment from an ELF file
@@ -906,6 +1116,7 @@ impl ProgramSegment {
 
 // ELF constants
 pub const PT_LOAD: u32 = 1;
+pub const PT_DYNAMIC: u32 = 2;
 pub const PF_X: u32 = 0x1;
 pub const PF_W: u32 = 0x2;
 pub const PF_R: u32 = 0x4;
@@ -1150,9 +1361,11 @@ impl ExecContext {
     /// This method creates a new user stack and populates it with the program's
     /// arguments and environment variables according to the System V ABI specification.
     ///
+    /// The stack top is randomized on every exec (see `mm::aslr::stack_top`).
+    ///
     /// Stack layout (from high to low addresses):
     /// ```text
-    /// 0x7FFF_FFFF_0000 (STACK_TOP)
+    /// stack_top (at most 0x7FFF_FFFF_0000)
     /// |  [environment strings]  |
     /// |  [argument strings]     |
     /// |  [padding for alignment]|
@@ -1179,7 +1392,7 @@ impl ExecContext {
     ///
     /// # Requirements
     /// Implements R3.4, R3.5, R5.1-R5.8:
-    /// - Allocate 8MB user stack at 0x7FFF_FFFF_0000
+    /// - Allocate 8MB user stack below a randomized top
     /// - Copy environment strings to stack
     /// - Copy argument strings to stack
     /// - Build envp pointer array (NULL-terminated)
@@ -1202,13 +1415,13 @@ impl ExecContext {
 
         // Stack configuration
         const STACK_SIZE: usize = 8 * 1024 * 1024; // 8MB
-        const STACK_TOP: u64 = 0x0000_7FFF_FFFF_0000;
+        let stack_top = crate::mm::aslr::stack_top();
 
         // Calculate stack bottom (start address)
-        let stack_bottom = STACK_TOP - STACK_SIZE as u64;
+        let stack_bottom = stack_top - STACK_SIZE as u64;
 
         // Allocate and map stack pages
-        let mut mapper = PageMapper::new();
+        let mut mapper = PageMapper::for_address_space(self.task.address_space());
         let stack_flags = PageTableFlags::PRESENT
             | PageTableFlags::WRITABLE
             | PageTableFlags::USER
@@ -1216,7 +1429,7 @@ impl ExecContext {
 
         // Map all pages for the stack
         let mut current_addr = stack_bottom as usize;
-        let stack_end = STACK_TOP as usize;
+        let stack_end = stack_top as usize;
 
         while current_addr < stack_end {
             // Allocate a physical frame
@@ -1239,7 +1452,7 @@ impl ExecContext {
         // Track stack region in task's memory regions
         let stack_region = MemoryRegion::new(
             stack_bottom as usize,
-            STACK_TOP as usize,
+            stack_top as usize,
             stack_flags,
             MemoryRegionType::Stack,
         );
@@ -1253,7 +1466,7 @@ impl ExecContext {
 
         // Now build the stack contents
         // We'll work from the top down, pushing data onto the stack
-        let mut sp = STACK_TOP;
+        let mut sp = stack_top;
 
         // Step 1: Copy environment strings to stack and collect their addresses
         let mut envp_addrs = Vec::with_capacity(self.envp.len());
@@ -1424,8 +1637,8 @@ impl ExecContext {
         use crate::mm::phys_to_virt;
         use crate::sched::task::{MemoryRegion, USER_LIMIT};
 
-        // Get the page mapper for the current address space
-        let mut mapper = PageMapper::new();
+        // Get the page mapper for the task's address space
+        let mut mapper = PageMapper::for_address_space(self.task.address_space());
 
         // Iterate through all PT_LOAD segments
         for segment in &elf_info.segments {
@@ -1434,14 +1647,19 @@ impl ExecContext {
                 continue;
             }
 
+            // Apply the load bias (non-zero only for PIE binaries)
+            let vaddr = segment
+                .vaddr
+                .checked_add(elf_info.load_bias)
+                .ok_or(ExecError::InvalidArgument)?;
+
             // Validate segment is in user space
-            if segment.vaddr >= USER_LIMIT as u64 {
+            if vaddr >= USER_LIMIT as u64 {
                 return Err(ExecError::InvalidArgument);
             }
 
             // Validate segment end is in user space
-            let segment_end = segment
-                .vaddr
+            let segment_end = vaddr
                 .checked_add(segment.memsz)
                 .ok_or(ExecError::InvalidArgument)?;
 
@@ -1453,8 +1671,8 @@ impl ExecContext {
             let page_flags = self.segment_flags_to_page_flags(segment.flags)?;
 
             // Calculate page-aligned start and end addresses
-            let start_page = (segment.vaddr as usize) & !0xFFF; // Align down to page boundary
-            let end_page = ((segment.vaddr as usize + segment.memsz as usize) + 0xFFF) & !0xFFF; // Align up
+            let start_page = (vaddr as usize) & !0xFFF; // Align down to page boundary
+            let end_page = ((vaddr as usize + segment.memsz as usize) + 0xFFF) & !0xFFF; // Align up
 
             // Allocate and map pages for the entire segment
             let mut current_addr = start_page;
@@ -1494,7 +1712,7 @@ impl ExecContext {
 
                 // Copy data to the mapped virtual address
                 // We need to copy byte by byte because the segment might not be page-aligned
-                let dest_addr = segment.vaddr as usize;
+                let dest_addr = vaddr as usize;
                 unsafe {
                     core::ptr::copy_nonoverlapping(
                         src_data.as_ptr(),
@@ -1507,7 +1725,7 @@ impl ExecContext {
             // Zero BSS section (memsz > filesz)
             // BSS is the uninitialized data section that should be zeroed
             if segment.memsz > segment.filesz {
-                let bss_start = (segment.vaddr + segment.filesz) as usize;
+                let bss_start = (vaddr + segment.filesz) as usize;
                 let bss_size = (segment.memsz - segment.filesz) as usize;
 
                 // Zero the BSS section
@@ -1519,8 +1737,8 @@ impl ExecContext {
             // Track this memory region in the task
             // This allows us to manage and clean up the memory later
             let region = MemoryRegion::new(
-                segment.vaddr as usize,
-                (segment.vaddr + segment.memsz) as usize,
+                vaddr as usize,
+                (vaddr + segment.memsz) as usize,
                 page_flags,
                 self.segment_type_from_flags(segment.flags),
             );
@@ -1535,6 +1753,129 @@ impl ExecContext {
             }
         }
 
+        if elf_info.load_bias != 0 {
+            self.apply_relocations(elf_info, elf_data, &mapper)?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the dynamic relocations of a PIE loaded at a random base
+    ///
+    /// Userspace has no dynamic linker, so only static PIEs are supported:
+    /// every entry in DT_RELA must be R_X86_64_RELATIVE (or R_X86_64_NONE).
+    /// Values are written through the direct map because relocated data may
+    /// live in read-only segments.
+    ///
+    /// # Arguments
+    /// * `elf_info` - Parsed ELF information with the chosen load bias
+    /// * `elf_data` - Raw ELF file data
+    /// * `mapper` - Page mapper of the task's address space
+    ///
+    /// # Errors
+    /// * InvalidFormat - Malformed dynamic section or unsupported relocation
+    fn apply_relocations(
+        &self,
+        elf_info: &ElfInfo,
+        elf_data: &[u8],
+        mapper: &crate::mm::paging::PageMapper,
+    ) -> Result<(), ExecError> {
+        use crate::mm::phys_to_virt;
+
+        const DT_NULL: u64 = 0;
+        const DT_RELA: u64 = 7;
+        const DT_RELASZ: u64 = 8;
+        const DT_RELAENT: u64 = 9;
+        const R_X86_64_NONE: u32 = 0;
+        const R_X86_64_RELATIVE: u32 = 8;
+
+        let read_u64 = |off: usize| -> Result<u64, ExecError> {
+            let bytes = elf_data.get(off..off + 8).ok_or(ExecError::InvalidFormat)?;
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(bytes);
+            Ok(u64::from_le_bytes(buf))
+        };
+
+        let dynamic = match &elf_info.dynamic {
+            Some(d) => d,
+            None => return Ok(()), // Nothing to relocate
+        };
+
+        // Walk the dynamic section for the RELA table
+        let mut rela_vaddr = 0u64;
+        let mut rela_size = 0u64;
+        let mut rela_ent = 24u64;
+        let dyn_start = dynamic.offset as usize;
+        let dyn_end = dyn_start + dynamic.filesz as usize;
+        let mut off = dyn_start;
+        while off + 16 <= dyn_end {
+            let tag = read_u64(off)?;
+            let val = read_u64(off + 8)?;
+            match tag {
+                DT_NULL => break,
+                DT_RELA => rela_vaddr = val,
+                DT_RELASZ => rela_size = val,
+                DT_RELAENT => rela_ent = val,
+                _ => {}
+            }
+            off += 16;
+        }
+
+        if rela_size == 0 {
+            return Ok(());
+        }
+        if rela_ent < 24 {
+            return Err(ExecError::InvalidFormat);
+        }
+
+        // Locate the table in the file through the segment that contains it
+        let rela_offset = elf_info
+            .segments
+            .iter()
+            .find(|seg| rela_vaddr >= seg.vaddr && rela_vaddr + rela_size <= seg.vaddr + seg.filesz)
+            .map(|seg| (seg.offset + (rela_vaddr - seg.vaddr)) as usize)
+            .ok_or(ExecError::InvalidFormat)?;
+
+        let mut applied = 0usize;
+        let mut entry = rela_offset;
+        while entry + 24 <= rela_offset + rela_size as usize {
+            let r_offset = read_u64(entry)?;
+            let r_info = read_u64(entry + 8)?;
+            let r_addend = read_u64(entry + 16)?;
+
+            match r_info as u32 {
+                R_X86_64_NONE => {}
+                R_X86_64_RELATIVE => {
+                    // Only slots inside the loaded image may be patched
+                    let in_image = elf_info
+                        .segments
+                        .iter()
+                        .any(|seg| r_offset >= seg.vaddr && r_offset + 8 <= seg.vaddr + seg.memsz);
+                    if !in_image {
+                        return Err(ExecError::InvalidFormat);
+                    }
+
+                    let target = (r_offset + elf_info.load_bias) as usize;
+                    // The 8-byte slot must not straddle a page boundary
+                    if target & 0xFFF > 0xFF8 {
+                        return Err(ExecError::InvalidFormat);
+                    }
+                    let phys = mapper.translate(target).ok_or(ExecError::InvalidFormat)?;
+                    unsafe {
+                        core::ptr::write_unaligned(
+                            phys_to_virt(phys) as *mut u64,
+                            r_addend.wrapping_add(elf_info.load_bias),
+                        );
+                    }
+                    applied += 1;
+                }
+                _ => return Err(ExecError::InvalidFormat),
+            }
+
+            entry += rela_ent as usize;
+        }
+
+        crate::serial_println!("[EXEC] Applied {} relocations", applied);
         Ok(())
     }
 
@@ -1670,10 +2011,8 @@ impl ExecContext {
         // The FdTable is wrapped in Arc<SpinLock<...>> for thread-safety
         let mut fd_table = self.task.fd_table.lock();
 
-        // Close all file descriptors with O_CLOEXEC flag set
-        // The FdTable::close_cloexec_fds() method handles the iteration
-        // and closing logic, preserving all FDs without the flag
-        fd_table.close_cloexec_fds();
+        // Close all file descriptors with FD_CLOEXEC set, preserving the rest
+        fd_table.close_cloexec();
 
         // Log the operation for debugging
         crate::serial_println!(
@@ -1842,3 +2181,60 @@ impl ExecContext {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(vaddr: u64, offset: u64, filesz: u64, memsz: u64, flags: u32) -> ProgramSegment {
+        ProgramSegment {
+            seg_type: PT_LOAD,
+            vaddr,
+            filesz,
+            memsz,
+            flags,
+            offset,
+            align: 0x1000,
+        }
+    }
+
+    fn pie(load_bias: u64) -> ElfInfo {
+        ElfInfo {
+            entry: 0x1040,
+            segments: alloc::vec![
+                segment(0, 0, 0x1800, 0x1800, PF_R | PF_X),
+                segment(0x3000, 0x2000, 0x100, 0x2000, PF_R | PF_W),
+            ],
+            dynamic: None,
+            is_pie: true,
+            load_bias,
+            phoff: 0x40,
+            phnum: 2,
+        }
+    }
+
+    #[test]
+    fn test_pie_load_bias() {
+        let bias = crate::mm::aslr::pie_load_base();
+        let elf_info = pie(bias);
+        assert_eq!(elf_info.entry_point(), bias + 0x1040);
+
+        let image = ProcessImage::new("/bin/pie", &[String::from("pie")], &elf_info);
+        assert_eq!(image.entry, bias + 0x1040);
+        assert_eq!(image.phdr, Some(bias + 0x40));
+        assert_eq!(
+            image.file_ranges,
+            [
+                (bias, bias + 0x2000, 0),
+                (bias + 0x3000, bias + 0x4000, 0x2000)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unbiased_image() {
+        let image = ProcessImage::new("/bin/pie", &[], &pie(0));
+        assert_eq!(image.entry, 0x1040);
+        assert_eq!(image.phdr, Some(0x40));
+    }
+}
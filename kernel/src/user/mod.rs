@@ -5,8 +5,15 @@
 /// - Process management
 /// - User-kernel memory management
 /// - exec() system call implementation
+pub mod capability;
+pub mod coredump;
+pub mod creds;
 pub mod elf;
 pub mod exec;
 pub mod integration_tests;
 pub mod launch;
 pub mod process;
+pub mod ptrace;
+pub mod rlimit;
+pub mod seccomp;
+pub mod thread;
@@ -1,97 +1,81 @@
-//! Process Management
+//! Processes
 //!
-//! This module implements the process control block (PCB) and process table
-//! for user-mode process management. It provides fine-grained locking for
-//! SMP safety and atomic PID allocation.
+//! A process is not a separate object: it is a `Task` (its leader, whose ID
+//! is the PID) together with the threads that share its thread group (see
+//! `user::thread`). Each `Task` owns its kernel stack and, through shared
+//! handles, its address space, file descriptor table and signal handlers.
+//!
+//! # Lifecycle
+//!
+//! - `fork` is `clone` without sharing flags; `exec` replaces the task's
+//!   image in place (see `user::exec`).
+//! - A thread that exits becomes a zombie. When the last thread of a
+//!   process exits, the process's status is recorded on its leader, the
+//!   parent gets SIGCHLD and its waiters are woken.
+//! - Stop signals stop every thread of a process until SIGCONT; both are
+//!   reported to the parent the same way.
+//! - `wait_child` collects a state change of a child. Collecting an exit
+//!   frees all of the child's tasks, which also frees the address space
+//!   once no task runs in it, and adds the child's resource usage to the
+//!   parent's.
+//! - Children that outlive their parent are handed to the child reaper
+//!   (init). Processes nobody will wait for (orphans without a reaper, and
+//!   children of a parent that ignores SIGCHLD or set SA_NOCLDWAIT) are
+//!   freed by the next `wait_child` or process exit, since a task cannot
+//!   free itself.
+//!
+//! - A traced task is also reported to its tracer, which `wait`s for it
+//!   like for a child (see `user::ptrace`).
+//!
+//! Stop state and pending reports live on the leader and are only changed
+//! with `CHILD_WAITERS` held, so a waiter can't miss one between looking
+//! for children and going to sleep.
+//!
+//! This module also holds the helpers the kernel uses to reach user memory.
 
-use crate::mm::paging::PageTable;
 use crate::mm::PhysAddr;
-use crate::sched::context::CpuContext;
-use crate::sched::priority::TaskPriority;
-use crate::sched::task::{MemoryRegion, MemoryRegionType};
-use crate::sync::{SpinLock, SpinLockGuard};
-use core::cell::UnsafeCell;
+use crate::sched::process_group::{Pgid, Pid};
+use crate::sched::task::{ResourceUsage, Task, TaskId, TaskState};
+use crate::signal::{sa_flags, signals, SigHandler, SigSource, Signal};
+use crate::sync::SpinLock;
+use alloc::vec::Vec;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-/// Process identifier type
-pub type ProcessId = usize;
-
-/// Maximum number of processes in the system
-pub const MAX_PROCESSES: usize = 1024;
-
-/// Maximum number of file descriptors per process
-pub const MAX_FDS: usize = 256;
-
-/// Maximum number of memory regions per process
-const MAX_MEMORY_REGIONS: usize = 16;
-
 /// User space address limit (512GB)
 pub const USER_LIMIT: usize = 0x0000_8000_0000_0000;
 
-/// User stack configuration
-pub const USER_STACK_TOP: usize = 0x0000_7FFF_FFFF_0000;
-pub const USER_STACK_SIZE: usize = 8192; // 8KB
-
-/// Process state enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ProcessState {
-    /// Process is ready to run and waiting in the runqueue
-    Ready,
-
-    /// Process is currently running on the CPU
-    Running,
+/// `wait` option: return at once if no child has changed state
+pub const WNOHANG: usize = 0x0000_0001;
 
-    /// Process is sleeping (waiting for wake_tick)
-    Sleeping,
+/// `wait` option: report children stopped by a signal
+pub const WUNTRACED: usize = 0x0000_0002;
 
-    /// Process is blocked on I/O or IPC
-    Blocked,
+/// `wait` option: report children that exited (implied by `wait4`)
+pub const WEXITED: usize = 0x0000_0004;
 
-    /// Process has terminated, waiting for parent to collect exit code
-    Zombie,
+/// `wait` option: report stopped children continued by SIGCONT
+pub const WCONTINUED: usize = 0x0000_0008;
 
-    /// Process has been fully cleaned up and can be reused
-    Terminated,
-}
-
-/// Block reason for blocked processes
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum BlockReason {
-    /// Blocked waiting for child process to exit
-    WaitingForChild,
-
-    /// Blocked on IPC receive
-    IpcReceive(usize), // port_id
-
-    /// Blocked on I/O operation
-    IoOperation,
-}
+/// `wait` option: leave the child waitable
+pub const WNOWAIT: usize = 0x0100_0000;
 
-/// File descriptor placeholder (for future implementation)
-#[derive(Debug, Clone)]
-pub struct FileDescriptor {
-    /// File descriptor number
-    pub fd: usize,
+/// `si_code` of a child that exited
+pub const CLD_EXITED: i32 = 1;
 
-    /// File type (placeholder)
-    pub file_type: FileType,
-}
+/// `si_code` of a child killed by a signal
+pub const CLD_KILLED: i32 = 2;
 
-/// File type enumeration (placeholder)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum FileType {
-    /// Standard input/output/error
-    StandardStream,
+/// `si_code` of a child killed by a signal that dumped core
+pub const CLD_DUMPED: i32 = 3;
 
-    /// Regular file
-    RegularFile,
+/// `si_code` of a traced child in a trace stop
+pub const CLD_TRAPPED: i32 = 4;
 
-    /// Directory
-    Directory,
+/// `si_code` of a child stopped by a signal
+pub const CLD_STOPPED: i32 = 5;
 
-    /// Device file
-    Device,
-}
+/// `si_code` of a stopped child that was continued
+pub const CLD_CONTINUED: i32 = 6;
 
 /// Process error types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -99,11 +83,11 @@ pub enum ProcessError {
     /// Process not found
     ProcessNotFound,
 
-    /// Process table is full
-    ProcessTableFull,
+    /// The caller has no child to wait for (ECHILD)
+    NoChildren,
 
-    /// Invalid process ID
-    InvalidProcessId,
+    /// The wait was cut short without a child changing state (EINTR)
+    Interrupted,
 
     /// Out of memory
     OutOfMemory,
@@ -114,601 +98,556 @@ pub enum ProcessError {
     /// Invalid memory region
     InvalidMemoryRegion,
 
-    /// Memory region overlap
-    RegionOverlap,
-
-    /// Too many memory regions
-    TooManyRegions,
-
     /// Invalid user address
     InvalidUserAddress,
 
-    /// Process is in wrong state for operation
-    InvalidState,
-
-    /// Would block (for non-blocking operations)
-    WouldBlock,
+    /// An argument is not valid (EINVAL)
+    InvalidArgument,
 }
 
 /// Result type for process operations
 pub type ProcessResult<T> = Result<T, ProcessError>;
 
-/// Process Control Block (PCB)
-///
-/// Contains all information needed to manage a process, including its
-/// execution context, memory layout, and state.
-pub struct Process {
-    /// Unique process identifier
-    pub pid: ProcessId,
-
-    /// Parent process ID (None for init process)
-    pub parent_pid: Option<ProcessId>,
-
-    /// Effective user identifier
-    pub uid: u32,
-
-    /// Effective group identifier
-    pub gid: u32,
-
-    /// Current state of the process
-    pub state: ProcessState,
-
-    /// Exit code (set when process terminates)
-    pub exit_code: Option<i32>,
-
-    /// CPU context (saved registers)
-    pub context: CpuContext,
-
-    /// Process priority level
-    pub priority: TaskPriority,
-
-    /// Tick at which to wake the process (if sleeping)
-    pub wake_tick: Option<u64>,
-
-    /// Block reason (if blocked)
-    pub block_reason: Option<BlockReason>,
-
-    /// Physical address of process page table root (CR3 value)
-    pub cr3: PhysAddr,
-
-    /// Process page table (for memory isolation)
-    pub page_table: Option<PageTable>,
-
-    /// Memory regions for this process (Code, Data, BSS, Stack, Heap)
-    pub memory_regions: [Option<MemoryRegion>; MAX_MEMORY_REGIONS],
-
-    /// Number of active memory regions
-    pub region_count: usize,
-
-    /// File descriptor table (for future use)
-    pub fd_table: [Option<FileDescriptor>; MAX_FDS],
-
-    /// Process name (for debugging)
-    pub name: [u8; 16],
-
-    /// Process creation time (in ticks)
-    pub creation_time: u64,
-
-    /// Total CPU time used (in ticks)
-    pub cpu_time: u64,
+/// How a process ended or changed state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitStatus {
+    /// Exited with a code (only the low 8 bits are reported)
+    Exited(i32),
+    /// Killed by a signal
+    Signaled { signal: Signal, core_dumped: bool },
+    /// Stopped by a signal
+    Stopped(Signal),
+    /// Continued by SIGCONT
+    Continued,
+    /// Traced and stopped for the tracer (see `user::ptrace`)
+    Trapped(Signal),
 }
 
-impl Process {
-    /// Create a new process with the given PID and parent
-    ///
-    /// # Arguments
-    /// * `pid` - Unique process identifier
-    /// * `parent_pid` - Parent process ID (None for init process)
-    ///
-    /// # Returns
-    /// A new Process with Ready state and empty memory layout
-    pub fn new(pid: ProcessId, parent_pid: Option<ProcessId>) -> Self {
-        Self {
-            pid,
-            parent_pid,
-            uid: 0,
-            gid: 0,
-            state: ProcessState::Ready,
-            exit_code: None,
-            context: CpuContext::new(),
-            priority: TaskPriority::Normal,
-            wake_tick: None,
-            block_reason: None,
-            cr3: 0, // Will be set when page table is allocated
-            page_table: None,
-            memory_regions: [const { None }; MAX_MEMORY_REGIONS],
-            region_count: 0,
-            fd_table: [const { None }; MAX_FDS],
-            name: [0; 16],
-            creation_time: 0, // TODO: Get current tick count
-            cpu_time: 0,
-        }
-    }
-
-    /// Set the process name (for debugging)
-    ///
-    /// # Arguments
-    /// * `name` - Process name (will be truncated to 15 chars + null terminator)
-    pub fn set_name(&mut self, name: &str) {
-        let bytes = name.as_bytes();
-        let len = core::cmp::min(bytes.len(), 15);
-        self.name[..len].copy_from_slice(&bytes[..len]);
-        self.name[len] = 0; // Null terminator
-    }
-
-    /// Get the process name as a string
-    ///
-    /// # Returns
-    /// Process name as a string slice
-    pub fn get_name(&self) -> &str {
-        // Find null terminator
-        let len = self.name.iter().position(|&b| b == 0).unwrap_or(16);
-        core::str::from_utf8(&self.name[..len]).unwrap_or("<invalid>")
-    }
-
-    /// Add a memory region to this process
-    ///
-    /// Validates the region and ensures no overlaps with existing regions.
-    /// All regions must be within user space limits.
-    /// Automatically ensures USER flag is set for user space regions.
-    ///
-    /// # Arguments
-    /// * `region` - The memory region to add
-    ///
-    /// # Returns
-    /// Ok(()) if the region was added successfully, or an error if validation fails
-    pub fn add_memory_region(&mut self, mut region: MemoryRegion) -> ProcessResult<()> {
-        // Validate region bounds
-        if region.start >= region.end {
-            return Err(ProcessError::InvalidMemoryRegion);
-        }
-
-        // Ensure region is in user space
-        if region.start >= USER_LIMIT || region.end > USER_LIMIT {
-            return Err(ProcessError::InvalidUserAddress);
-        }
-
-        // Ensure USER flag is set for user space regions (Requirement 2.8)
-        // This is critical for per-process page tables to work correctly
-        use crate::mm::paging::PageTableFlags;
-        if region.flags.bits() & PageTableFlags::USER.bits() == 0 {
-            region.flags |= PageTableFlags::USER;
-        }
-
-        // Check for overlaps with existing regions
-        for existing_region in &self.memory_regions[..self.region_count] {
-            if let Some(existing) = existing_region {
-                if region.overlaps_with(existing) {
-                    return Err(ProcessError::RegionOverlap);
-                }
+impl WaitStatus {
+    /// Whether the process is gone (as opposed to stopped or continued)
+    pub fn is_exit(self) -> bool {
+        matches!(self, WaitStatus::Exited(_) | WaitStatus::Signaled { .. })
+    }
+
+    /// The status word `wait4` stores
+    pub fn encode(self) -> i32 {
+        match self {
+            WaitStatus::Exited(code) => (code & 0xff) << 8,
+            WaitStatus::Signaled {
+                signal,
+                core_dumped,
+            } => (signal as i32 & 0x7f) | if core_dumped { 0x80 } else { 0 },
+            WaitStatus::Stopped(signal) | WaitStatus::Trapped(signal) => {
+                ((signal as i32 & 0xff) << 8) | 0x7f
             }
+            WaitStatus::Continued => 0xffff,
         }
-
-        // Check if we have space for another region
-        if self.region_count >= MAX_MEMORY_REGIONS {
-            return Err(ProcessError::TooManyRegions);
-        }
-
-        // Add the region
-        self.memory_regions[self.region_count] = Some(region);
-        self.region_count += 1;
-
-        Ok(())
     }
 
-    /// Find the memory region containing the given address
-    ///
-    /// # Arguments
-    /// * `addr` - Virtual address to look up
-    ///
-    /// # Returns
-    /// Some(region) if found, None if the address is not in any region
-    pub fn find_memory_region(&self, addr: usize) -> Option<&MemoryRegion> {
-        for region_opt in &self.memory_regions[..self.region_count] {
-            if let Some(region) = region_opt {
-                if region.contains(addr) {
-                    return Some(region);
-                }
-            }
+    /// `si_code` of the SIGCHLD `siginfo` `waitid` fills in
+    pub fn si_code(self) -> i32 {
+        match self {
+            WaitStatus::Exited(_) => CLD_EXITED,
+            WaitStatus::Signaled {
+                core_dumped: false, ..
+            } => CLD_KILLED,
+            WaitStatus::Signaled {
+                core_dumped: true, ..
+            } => CLD_DUMPED,
+            WaitStatus::Stopped(_) => CLD_STOPPED,
+            WaitStatus::Continued => CLD_CONTINUED,
+            WaitStatus::Trapped(_) => CLD_TRAPPED,
         }
-        None
     }
 
-    /// Get all memory regions of a specific type
-    ///
-    /// # Arguments
-    /// * `region_type` - The type of regions to find
-    ///
-    /// # Returns
-    /// Iterator over regions of the specified type
-    pub fn get_regions_by_type(
-        &self,
-        region_type: MemoryRegionType,
-    ) -> impl Iterator<Item = &MemoryRegion> {
-        self.memory_regions[..self.region_count]
-            .iter()
-            .filter_map(|region_opt| region_opt.as_ref())
-            .filter(move |region| region.region_type == region_type)
-    }
-
-    /// Remove a memory region by address range
-    ///
-    /// # Arguments
-    /// * `start` - Start address of the region to remove
-    /// * `end` - End address of the region to remove
-    ///
-    /// # Returns
-    /// Ok(()) if the region was removed, or an error if not found
-    pub fn remove_memory_region(&mut self, start: usize, end: usize) -> ProcessResult<()> {
-        for i in 0..self.region_count {
-            if let Some(region) = &self.memory_regions[i] {
-                if region.start == start && region.end == end {
-                    // Remove this region by shifting others down
-                    for j in i..self.region_count - 1 {
-                        self.memory_regions[j] = self.memory_regions[j + 1].take();
-                    }
-                    self.memory_regions[self.region_count - 1] = None;
-                    self.region_count -= 1;
-                    return Ok(());
-                }
-            }
+    /// `si_status`: the exit code, or the signal that caused the change
+    pub fn si_status(self) -> i32 {
+        match self {
+            WaitStatus::Exited(code) => code & 0xff,
+            WaitStatus::Signaled { signal, .. }
+            | WaitStatus::Stopped(signal)
+            | WaitStatus::Trapped(signal) => signal as i32,
+            WaitStatus::Continued => signals::SIGCONT as i32,
         }
-        Err(ProcessError::InvalidMemoryRegion)
     }
 
-    /// Clear all memory regions (used during exec)
-    pub fn clear_memory_regions(&mut self) {
-        for region in &mut self.memory_regions {
-            *region = None;
-        }
-        self.region_count = 0;
+    /// Whether a `wait` with `options` reports this change (trace stops
+    /// always are)
+    fn wanted_by(self, options: usize) -> bool {
+        let option = match self {
+            WaitStatus::Exited(_) | WaitStatus::Signaled { .. } => WEXITED,
+            WaitStatus::Stopped(_) => WUNTRACED,
+            WaitStatus::Continued => WCONTINUED,
+            WaitStatus::Trapped(_) => return true,
+        };
+        options & option != 0
     }
+}
 
-    /// Validate that an address range is within a valid memory region
-    ///
-    /// Used for page fault handling and memory access validation.
-    ///
-    /// # Arguments
-    /// * `addr` - Start address
-    /// * `size` - Size of the access
-    ///
-    /// # Returns
-    /// Ok(region) if the entire range is within a valid region, or an error
-    pub fn validate_memory_access(&self, addr: usize, size: usize) -> ProcessResult<&MemoryRegion> {
-        let end_addr = addr.saturating_add(size);
-
-        // Find region containing the start address
-        let region = self
-            .find_memory_region(addr)
-            .ok_or(ProcessError::InvalidUserAddress)?;
+/// Children a `wait` accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitTarget {
+    /// Any child
+    Any,
+    /// The child with this PID (or traced task with this ID)
+    Pid(Pid),
+    /// Any child in this process group
+    Group(Pgid),
+}
 
-        // Ensure the entire range is within this region
-        if end_addr > region.end {
-            return Err(ProcessError::InvalidUserAddress);
+impl WaitTarget {
+    fn matches(self, task: &Task) -> bool {
+        match self {
+            WaitTarget::Any => true,
+            WaitTarget::Pid(pid) => task.id == pid,
+            WaitTarget::Group(pgid) => task.pgid == pgid,
         }
-
-        Ok(region)
     }
+}
 
-    /// Get total memory usage for this process
-    ///
-    /// # Returns
-    /// Total size in bytes of all memory regions
-    pub fn total_memory_usage(&self) -> usize {
-        self.memory_regions[..self.region_count]
-            .iter()
-            .filter_map(|region_opt| region_opt.as_ref())
-            .map(|region| region.size())
-            .sum()
-    }
+/// A state change collected by `wait_child`
+#[derive(Debug, Clone, Copy)]
+pub struct ChildEvent {
+    /// PID of the child (task ID of a traced thread)
+    pub pid: Pid,
+    /// User ID of the child
+    pub uid: u32,
+    /// What happened
+    pub status: WaitStatus,
+    /// Resources used by the child and the children it collected
+    pub rusage: ResourceUsage,
+}
 
-    /// Check if this process is a child of the given parent
-    ///
-    /// # Arguments
-    /// * `parent_pid` - Potential parent process ID
-    ///
-    /// # Returns
-    /// true if this process is a child of the given parent
-    pub fn is_child_of(&self, parent_pid: ProcessId) -> bool {
-        self.parent_pid == Some(parent_pid)
-    }
+/// Tasks blocked in `wait_child`, with the PID whose children they wait for
+pub(super) static CHILD_WAITERS: SpinLock<Vec<(TaskId, Pid)>> = SpinLock::new(Vec::new());
 
-    /// Mark process as zombie with exit code
-    ///
-    /// # Arguments
-    /// * `exit_code` - Process exit code
-    pub fn mark_zombie(&mut self, exit_code: i32) {
-        self.state = ProcessState::Zombie;
-        self.exit_code = Some(exit_code);
-    }
+/// Process that adopts orphans (0 = none)
+static CHILD_REAPER: AtomicUsize = AtomicUsize::new(0);
 
-    /// Mark process as terminated (fully cleaned up)
-    pub fn mark_terminated(&mut self) {
-        self.state = ProcessState::Terminated;
-        self.exit_code = None;
-        self.clear_memory_regions();
-    }
+/// Make `pid` the process that adopts orphans
+///
+/// Called once init has been started.
+pub fn set_child_reaper(pid: Pid) {
+    CHILD_REAPER.store(pid, Ordering::Release);
 }
 
-impl core::fmt::Debug for Process {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.debug_struct("Process")
-            .field("pid", &self.pid)
-            .field("parent_pid", &self.parent_pid)
-            .field("state", &self.state)
-            .field("exit_code", &self.exit_code)
-            .field("priority", &self.priority)
-            .field("wake_tick", &self.wake_tick)
-            .field("block_reason", &self.block_reason)
-            .field("region_count", &self.region_count)
-            .field("name", &self.get_name())
-            .field("creation_time", &self.creation_time)
-            .field("cpu_time", &self.cpu_time)
-            .finish()
-    }
+/// Whether every thread of a process has exited
+fn has_exited(leader: &Task) -> bool {
+    leader.wait_report.is_some_and(WaitStatus::is_exit)
 }
 
-/// Process table entry with fine-grained locking
-///
-/// Each process slot has its own lock to minimize contention in SMP systems.
-/// This allows multiple cores to operate on different processes simultaneously.
-struct ProcessTableEntry {
-    /// Process data (None if slot is free) - wrapped in UnsafeCell for interior mutability
-    process: UnsafeCell<Option<Process>>,
-
-    /// Per-process lock for SMP safety
-    lock: SpinLock<()>,
+/// Whether `parent` asked for its children not to become zombies
+fn ignores_children(parent: Pid) -> bool {
+    crate::sched::get_task_by_id(parent).is_some_and(|task| {
+        let action = task.signal_handlers.lock()[signals::SIGCHLD as usize];
+        action.handler == SigHandler::Ignore || action.flags & sa_flags::SA_NOCLDWAIT != 0
+    })
 }
 
-// Safety: ProcessTableEntry is safe to share between threads because:
-// - All access to the UnsafeCell is protected by the SpinLock
-// - The lock ensures exclusive access to the process data
-// - No data races can occur as long as the lock is held
-unsafe impl Sync for ProcessTableEntry {}
-
-impl ProcessTableEntry {
-    /// Create a new empty process table entry
-    const fn new() -> Self {
-        Self {
-            process: UnsafeCell::new(None),
-            lock: SpinLock::new(()),
+/// Look for a child of `parent` with a state change `options` asks for
+///
+/// Tasks `parent` traces count as children, and their trace stops are
+/// reported first.
+///
+/// # Arguments
+/// * `parent` - PID of the waiting process
+/// * `target` - Children to look at
+/// * `options` - `W*` options
+///
+/// # Returns
+/// The child to collect (if any), and whether a matching child exists at all
+fn find_waitable_child(parent: Pid, target: WaitTarget, options: usize) -> (Option<Pid>, bool) {
+    let mut waitable = None;
+    let mut found = false;
+
+    crate::sched::for_each_task(|task| {
+        let is_child = task.id == task.pid && task.ppid == parent && task.pid != parent;
+        let trace_report = super::ptrace::trace_report(task, parent);
+        let traced = task
+            .ptrace
+            .as_ref()
+            .is_some_and(|state| state.tracer == parent);
+        if !(is_child || traced) || !target.matches(task) {
+            return;
         }
-    }
+        found = true;
+        if trace_report.is_some() {
+            waitable = Some(task.id);
+        } else if waitable.is_none()
+            && is_child
+            && task.wait_report.is_some_and(|s| s.wanted_by(options))
+        {
+            waitable = Some(task.pid);
+        }
+    });
 
-    /// Check if this slot is free (requires lock for safe access)
-    fn is_free(&self) -> bool {
-        let _guard = self.lock.lock();
-        unsafe { (*self.process.get()).is_none() }
-    }
+    (waitable, found)
 }
 
-/// Process table guard for safe access to a process slot
-///
-/// This guard ensures that the process slot remains locked while it's being accessed.
-/// The lock is automatically released when the guard is dropped.
-pub struct ProcessGuard<'a> {
-    /// Reference to the process (if present)
-    process: &'a mut Option<Process>,
-
-    /// Lock guard to ensure exclusive access
-    _lock_guard: SpinLockGuard<'a, ()>,
+/// Resources used by every thread of a process and by its collected
+/// children
+fn process_usage(leader: &Task) -> ResourceUsage {
+    let mut usage = thread_group_usage(leader);
+    usage.add(&leader.child_rusage);
+    usage
 }
 
-impl<'a> ProcessGuard<'a> {
-    /// Get a reference to the process (if present)
-    pub fn get(&self) -> Option<&Process> {
-        self.process.as_ref()
+/// Resources used so far by every thread of a process, live or exited
+pub fn thread_group_usage(leader: &Task) -> ResourceUsage {
+    let mut usage = leader.rusage;
+
+    let threads = leader
+        .thread_group
+        .as_ref()
+        .map(|group| {
+            let mut threads = group.members();
+            threads.extend(group.exited_threads());
+            threads
+        })
+        .unwrap_or_default();
+    for id in threads.into_iter().filter(|&id| id != leader.id) {
+        if let Some(thread) = crate::sched::get_task_by_id(id) {
+            usage.add(&thread.rusage);
+        }
     }
+    usage
+}
 
-    /// Get a mutable reference to the process (if present)
-    pub fn get_mut(&mut self) -> Option<&mut Process> {
-        self.process.as_mut()
-    }
+/// Free every task of an exited process
+///
+/// The caller must hold `CHILD_WAITERS`, so that no one else frees the
+/// process meanwhile.
+fn release_process(pid: Pid) {
+    let threads = match crate::sched::get_task_by_id(pid) {
+        Some(leader) => leader
+            .thread_group
+            .as_ref()
+            .map(|group| group.take_exited())
+            .unwrap_or_default(),
+        None => return,
+    };
 
-    /// Take the process out of the slot (leaving it empty)
-    pub fn take(&mut self) -> Option<Process> {
-        self.process.take()
+    for id in threads {
+        crate::sched::release_task(id);
     }
+    crate::sched::release_task(pid);
+}
 
-    /// Insert a process into the slot (replacing any existing process)
-    pub fn insert(&mut self, process: Process) -> Option<Process> {
-        self.process.replace(process)
+/// Free exited processes that have no parent, except `current`
+///
+/// The caller must hold `CHILD_WAITERS`.
+fn release_orphans(current: Pid) {
+    let mut orphans = Vec::new();
+    crate::sched::for_each_task(|task| {
+        if task.id == task.pid && task.ppid == 0 && task.pid != current && has_exited(task) {
+            orphans.push(task.pid);
+        }
+    });
+    for pid in orphans {
+        release_process(pid);
     }
+}
 
-    /// Check if the slot contains a process
-    pub fn is_some(&self) -> bool {
-        self.process.is_some()
-    }
+/// Take the state change of a child of `parent`, freeing the child if it
+/// exited
+///
+/// The caller must hold `CHILD_WAITERS`.
+fn collect_child(parent: Pid, pid: Pid, options: usize) -> Option<ChildEvent> {
+    let leader = crate::sched::get_task_mut(pid)?;
+    if let Some(status) = super::ptrace::trace_report(leader, parent) {
+        if options & WNOWAIT == 0 {
+            super::ptrace::mark_reported(leader);
+        }
+        return Some(ChildEvent {
+            pid,
+            uid: leader.creds.uid,
+            status,
+            rusage: ResourceUsage::default(),
+        });
+    }
+
+    let status = leader.wait_report?;
+    let event = ChildEvent {
+        pid,
+        uid: leader.creds.uid,
+        status,
+        rusage: if status.is_exit() {
+            process_usage(leader)
+        } else {
+            ResourceUsage::default()
+        },
+    };
 
-    /// Check if the slot is empty
-    pub fn is_none(&self) -> bool {
-        self.process.is_none()
+    if options & WNOWAIT == 0 {
+        if status.is_exit() {
+            release_process(pid);
+        } else {
+            leader.wait_report = None;
+        }
     }
+    Some(event)
 }
 
-/// Global process table with fine-grained locking
+/// Wait for a child to change state
 ///
-/// Each process slot has its own lock to enable concurrent access from multiple cores.
-/// This design minimizes lock contention and improves SMP scalability.
-static PROCESS_TABLE: [ProcessTableEntry; MAX_PROCESSES] =
-    [const { ProcessTableEntry::new() }; MAX_PROCESSES];
-
-/// Atomic PID counter for unique process ID allocation
-static NEXT_PID: AtomicUsize = AtomicUsize::new(1);
-
-/// Process table manager
+/// # Arguments
+/// * `target` - Children to wait for
+/// * `options` - `W*` options; `WEXITED`, `WUNTRACED` and `WCONTINUED`
+///   select the changes to report
 ///
-/// Provides high-level operations for process management with proper locking.
-pub struct ProcessManager;
-
-impl ProcessManager {
-    /// Allocate a new unique process ID
-    ///
-    /// Uses atomic increment to ensure uniqueness across all cores.
-    ///
-    /// # Returns
-    /// A new unique process ID
-    pub fn alloc_pid() -> ProcessId {
-        NEXT_PID.fetch_add(1, Ordering::Relaxed)
-    }
+/// # Returns
+/// The change, or None with `WNOHANG` if no matching child has changed
+/// state yet
+///
+/// # Errors
+/// * NoChildren - The caller has no matching child
+/// * Interrupted - A signal, or another wake-up, cut the wait short
+pub fn wait_child(target: WaitTarget, options: usize) -> ProcessResult<Option<ChildEvent>> {
+    let (task_id, parent) = crate::sched::current_task()
+        .map(|task| (task.id, task.pid))
+        .ok_or(ProcessError::ProcessNotFound)?;
 
-    /// Find a free process slot and lock it
-    ///
-    /// Searches the process table for an empty slot and returns a guard
-    /// that provides exclusive access to that slot.
-    ///
-    /// # Returns
-    /// Some(ProcessGuard) if a free slot was found, None if table is full
-    pub fn alloc_process_slot() -> Option<ProcessGuard<'static>> {
-        for entry in &PROCESS_TABLE {
-            // Try to acquire the lock
-            if let Some(lock_guard) = entry.lock.try_lock() {
-                // Check if the slot is free while we have the lock
-                let process_ref = unsafe { &mut *entry.process.get() };
-                if process_ref.is_none() {
-                    return Some(ProcessGuard {
-                        process: process_ref,
-                        _lock_guard: lock_guard,
-                    });
+    loop {
+        let mut outcome = None;
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let mut waiters = CHILD_WAITERS.lock();
+            release_orphans(parent);
+            match find_waitable_child(parent, target, options) {
+                (Some(child), _) => {
+                    outcome = collect_child(parent, child, options).map(|e| Ok(Some(e)))
+                }
+                (None, false) => outcome = Some(Err(ProcessError::NoChildren)),
+                (None, true) if options & WNOHANG != 0 => outcome = Some(Ok(None)),
+                (None, true) => {
+                    waiters.push((task_id, parent));
+                    crate::signal::block_interruptible(waiters);
                 }
-                // Slot was taken by another core, continue searching
             }
-        }
-        None // Process table is full
-    }
-
-    /// Get a process by PID with exclusive access
-    ///
-    /// Searches the process table for the given PID and returns a guard
-    /// that provides exclusive access to that process.
-    ///
-    /// # Arguments
-    /// * `pid` - Process ID to search for
-    ///
-    /// # Returns
-    /// Some(ProcessGuard) if the process was found, None if not found
-    pub fn get_process(pid: ProcessId) -> Option<ProcessGuard<'static>> {
-        for entry in &PROCESS_TABLE {
-            // Try to acquire the lock
-            let lock_guard = entry.lock.lock();
-
-            // Check if this slot contains the process we're looking for
-            let process_ref = unsafe { &mut *entry.process.get() };
-            if let Some(ref process) = process_ref {
-                if process.pid == pid {
-                    return Some(ProcessGuard {
-                        process: process_ref,
-                        _lock_guard: lock_guard,
-                    });
+        });
+
+        match outcome {
+            Some(Ok(Some(event))) => {
+                if event.status.is_exit() && options & WNOWAIT == 0 {
+                    if let Some(task) = crate::sched::current_task_mut() {
+                        task.children.retain(|&id| id != event.pid);
+                        task.child_rusage.add(&event.rusage);
+                    }
                 }
+                return Ok(Some(event));
             }
-            // Not the process we're looking for, lock will be released automatically
+            Some(result) => return result,
+            None => {}
         }
-        None // Process not found
-    }
 
-    /// Create a new process and add it to the process table
-    ///
-    /// Allocates a new PID, finds a free slot, and initializes the process.
-    ///
-    /// # Arguments
-    /// * `parent_pid` - Parent process ID (None for init process)
-    /// * `name` - Process name for debugging
-    ///
-    /// # Returns
-    /// Ok(pid) if the process was created successfully, or an error
-    pub fn create_process(parent_pid: Option<ProcessId>, name: &str) -> ProcessResult<ProcessId> {
-        // Allocate new PID
-        let pid = Self::alloc_pid();
-
-        // Find free process slot
-        let mut slot = Self::alloc_process_slot().ok_or(ProcessError::ProcessTableFull)?;
-
-        // Create new process
-        let mut process = Process::new(pid, parent_pid);
-        process.set_name(name);
-
-        // Insert into slot
-        slot.insert(process);
-
-        Ok(pid)
+        // Children dequeue the waiters they wake; still being queued means
+        // something else woke us
+        let mut waiters = CHILD_WAITERS.lock();
+        if waiters.iter().any(|&(id, _)| id == task_id) {
+            waiters.retain(|&(id, _)| id != task_id);
+            return Err(ProcessError::Interrupted);
+        }
     }
+}
 
-    /// Remove a process from the process table
-    ///
-    /// Marks the process slot as free so it can be reused.
-    ///
-    /// # Arguments
-    /// * `pid` - Process ID to remove
-    ///
-    /// # Returns
-    /// Ok(process) if the process was removed, or an error if not found
-    pub fn remove_process(pid: ProcessId) -> ProcessResult<Process> {
-        let mut slot = Self::get_process(pid).ok_or(ProcessError::ProcessNotFound)?;
+/// Dequeue the tasks waiting for children of `parent`
+pub(super) fn take_waiters(waiters: &mut Vec<(TaskId, Pid)>, parent: Pid) -> Vec<TaskId> {
+    let woken = waiters
+        .iter()
+        .filter(|&&(_, pid)| pid == parent)
+        .map(|&(id, _)| id)
+        .collect();
+    waiters.retain(|&(_, pid)| pid != parent);
+    woken
+}
 
-        let process = slot.take().ok_or(ProcessError::ProcessNotFound)?;
+/// Record a state change of the process led by `leader` for its parent
+///
+/// Sends the parent SIGCHLD, unless the change is a stop or continue and
+/// the parent set SA_NOCLDSTOP. The caller holds `CHILD_WAITERS` (as
+/// `waiters`) and wakes the returned tasks once it has released it.
+fn notify_parent(
+    waiters: &mut Vec<(TaskId, Pid)>,
+    leader: &mut Task,
+    status: WaitStatus,
+) -> Vec<TaskId> {
+    leader.wait_report = Some(status);
+
+    if let Some(parent) = crate::sched::get_task_mut(leader.ppid) {
+        let flags = parent.signal_handlers.lock()[signals::SIGCHLD as usize].flags;
+        if status.is_exit() || flags & sa_flags::SA_NOCLDSTOP == 0 {
+            let source = SigSource::Child {
+                code: status.si_code(),
+                pid: leader.pid,
+                uid: leader.creds.uid,
+                status: status.si_status(),
+            };
+            let _ = crate::signal::send_signal_info(parent, signals::SIGCHLD, source);
+        }
+    }
 
-        // Clear TLB tracking for this process on all CPUs
-        crate::mm::tlb::clear_process_tracking(pid);
+    take_waiters(waiters, leader.ppid)
+}
 
-        Ok(process)
-    }
+/// Finish the exit of a process whose last thread is `task`
+///
+/// Called by the exiting thread, already a zombie, with interrupts
+/// disabled. The process's status is the leader's, unless `exit_group`
+/// set one. Exited children are freed (no one can wait for them any
+/// more), the others are handed to the child reaper, and the parent is
+/// notified. Tasks the process traced are let go.
+pub(crate) fn exit_notify(task: &Task) {
+    let pid = task.pid;
+    super::ptrace::release_tracees(pid);
+
+    let group_status = task
+        .thread_group
+        .as_ref()
+        .and_then(|group| group.exit_status());
+
+    let reaper = CHILD_REAPER.load(Ordering::Acquire);
+    let new_parent = if reaper != pid && crate::sched::get_task_by_id(reaper).is_some() {
+        reaper
+    } else {
+        0
+    };
 
-    /// Find the first zombie child of a parent process
-    ///
-    /// Searches the process table for a zombie process that is a child
-    /// of the given parent.
-    ///
-    /// # Arguments
-    /// * `parent_pid` - Parent process ID
-    ///
-    /// # Returns
-    /// Some((child_pid, exit_code)) if a zombie child is found, None otherwise
-    pub fn find_zombie_child(parent_pid: ProcessId) -> Option<(ProcessId, i32)> {
-        for entry in &PROCESS_TABLE {
-            let _lock_guard = entry.lock.lock();
-
-            let process_ref = unsafe { &*entry.process.get() };
-            if let Some(ref process) = process_ref {
-                if process.is_child_of(parent_pid) && process.state == ProcessState::Zombie {
-                    if let Some(exit_code) = process.exit_code {
-                        return Some((process.pid, exit_code));
-                    }
-                }
+    let woken = {
+        let mut waiters = CHILD_WAITERS.lock();
+        let leader = match crate::sched::get_task_mut(pid) {
+            Some(leader) => leader,
+            None => return,
+        };
+        let status = group_status
+            .or(leader.exit_status)
+            .unwrap_or(WaitStatus::Exited(0));
+        leader.exit_status = Some(status);
+        leader.stopped = false;
+
+        let mut exited = Vec::new();
+        let mut adopted = Vec::new();
+        crate::sched::for_each_task(|child| {
+            if child.ppid != pid || child.pid == pid {
+                return;
             }
+            if child.id == child.pid && has_exited(child) {
+                exited.push(child.pid);
+                return;
+            }
+            child.ppid = new_parent;
+            if child.id == child.pid {
+                adopted.push(child.pid);
+            }
+        });
+        for child in exited {
+            release_process(child);
+        }
+        if let Some(reaper) = crate::sched::get_task_mut(new_parent) {
+            reaper.children.extend(adopted);
         }
 
-        None
+        let woken = if ignores_children(leader.ppid) {
+            // Nobody collects the process; waiters recheck for ECHILD
+            let parent = core::mem::replace(&mut leader.ppid, 0);
+            leader.wait_report = Some(status);
+            take_waiters(&mut waiters, parent)
+        } else {
+            notify_parent(&mut waiters, leader, status)
+        };
+
+        release_orphans(pid);
+        woken
+    };
+
+    for id in woken {
+        crate::sched::wake_task(id);
     }
+}
+
+/// Stop the current process for `signal` until it is continued
+///
+/// Every other thread stops once it next returns from a syscall (see
+/// `park_while_stopped`).
+pub(crate) fn stop_process(signal: Signal) {
+    let pid = match crate::sched::current_task() {
+        Some(task) => task.pid,
+        None => return,
+    };
 
-    /// Count the number of active processes
-    ///
-    /// # Returns
-    /// Number of processes in the table (excluding free slots)
-    pub fn count_processes() -> usize {
-        let mut count = 0;
-
-        for entry in &PROCESS_TABLE {
-            let _lock_guard = entry.lock.lock();
-            let process_ref = unsafe { &*entry.process.get() };
-            if process_ref.is_some() {
-                count += 1;
+    let woken = x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut waiters = CHILD_WAITERS.lock();
+        match crate::sched::get_task_mut(pid) {
+            Some(leader) if !leader.stopped && !has_exited(leader) => {
+                leader.stopped = true;
+                notify_parent(&mut waiters, leader, WaitStatus::Stopped(signal))
             }
+            _ => Vec::new(),
         }
+    });
+    for id in woken {
+        crate::sched::wake_task(id);
+    }
+
+    park_while_stopped();
+}
+
+/// Block the current thread while its process is stopped
+///
+/// Returns early if SIGKILL is pending.
+pub(crate) fn park_while_stopped() {
+    let (task_id, pid) = match crate::sched::current_task() {
+        Some(task) => (task.id, task.pid),
+        None => return,
+    };
 
-        count
+    loop {
+        let mut parked = false;
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let waiters = CHILD_WAITERS.lock();
+            let stopped = crate::sched::get_task_by_id(pid).is_some_and(|leader| leader.stopped);
+            let task = match crate::sched::get_task_mut(task_id) {
+                Some(task) if stopped && !task.has_pending_signal(signals::SIGKILL) => task,
+                _ => return,
+            };
+            task.state = TaskState::Blocked;
+            drop(waiters);
+            parked = true;
+            crate::sched::schedule();
+        });
+        if !parked {
+            return;
+        }
     }
+}
 
-    /// Get process table statistics
-    ///
-    /// # Returns
-    /// (total_slots, used_slots, free_slots)
-    pub fn get_stats() -> (usize, usize, usize) {
-        let used = Self::count_processes();
-        let total = MAX_PROCESSES;
-        let free = total - used;
+/// Resume a stopped process
+///
+/// # Arguments
+/// * `pid` - Process to resume
+/// * `report` - Whether to tell the parent (false when resuming a process
+///   so it can die of SIGKILL)
+pub(crate) fn continue_process(pid: Pid, report: bool) {
+    let (woken, threads) = x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut waiters = CHILD_WAITERS.lock();
+        let leader = match crate::sched::get_task_mut(pid) {
+            Some(leader) if leader.stopped => leader,
+            _ => return (Vec::new(), Vec::new()),
+        };
+        leader.stopped = false;
+
+        let threads = match leader.thread_group.as_ref() {
+            Some(group) => group.members(),
+            None => alloc::vec![pid],
+        };
+        let woken = if report {
+            notify_parent(&mut waiters, leader, WaitStatus::Continued)
+        } else {
+            Vec::new()
+        };
+        (woken, threads)
+    });
 
-        (total, used, free)
+    for id in woken.into_iter().chain(threads) {
+        crate::sched::wake_task(id);
     }
 }
 
@@ -728,78 +667,141 @@ pub fn is_user_pointer_valid(ptr: usize) -> bool {
     ptr != 0 && ptr < USER_LIMIT
 }
 
-/// Temporarily map a user page into kernel space for safe access
+/// Physical address of the PML4 user copies should walk
 ///
-/// This function creates a temporary kernel mapping of a user page,
-/// allowing the kernel to safely access user memory even when using
-/// separate page tables. The mapping is automatically unmapped when
-/// the returned guard is dropped.
+/// This is the running task's own page table. Before the scheduler is up (or
+/// for tasks without a recorded CR3) the currently loaded table is used.
+pub fn current_address_space() -> PhysAddr {
+    crate::sched::current_task()
+        .map(|task| task.address_space())
+        .filter(|&cr3| cr3 != 0)
+        .unwrap_or_else(crate::mm::paging::get_current_cr3)
+}
+
+/// Map a user page of the current task into kernel space for safe access
+///
+/// See [`kmap_user_page_in`].
 ///
 /// # Arguments
-/// * `user_addr` - User space address to map (will be page-aligned)
-/// * `writable` - Whether the mapping should be writable
+/// * `user_addr` - User space address to access
+/// * `writable` - Whether the mapping will be written through
 ///
 /// # Returns
 /// Ok(KernelMapping) with the kernel virtual address, or an error
-///
-/// # Safety
-/// This function is safe because:
-/// - It validates the user address is in user space
-/// - It creates a temporary mapping that doesn't affect user page tables
-/// - The mapping is automatically cleaned up when dropped
-/// - It uses a dedicated kernel mapping region
 #[allow(dead_code)]
 pub fn kmap_user_page(user_addr: usize, writable: bool) -> ProcessResult<KernelMapping> {
+    kmap_user_page_in(current_address_space(), user_addr, writable)
+}
+
+/// Process whose mmap table describes the address space `pml4_phys`
+fn address_space_owner(pml4_phys: PhysAddr) -> Option<Pid> {
+    if pml4_phys == current_address_space() {
+        return Some(crate::mm::mmap::current_pid() as Pid);
+    }
+
+    let mut owner = None;
+    crate::sched::for_each_task(|task| {
+        if owner.is_none() && task.address_space() == pml4_phys {
+            owner = Some(task.pid);
+        }
+    });
+    owner
+}
+
+/// Map a user page of an arbitrary address space into kernel space
+///
+/// Walks the page table rooted at `pml4_phys` to find the backing frame and
+/// returns its address in the higher-half direct map. This works whether or
+/// not the target address space is loaded in CR3, so the kernel can read or
+/// write another process's memory (exec, fork, ptrace). A page of an mmap
+/// region that was not touched yet is populated first, as the fault on a
+/// user access would (see `mm::mmap::fault_in_page`; file-backed pages
+/// only in the current address space).
+///
+/// # Arguments
+/// * `pml4_phys` - Physical address of the target PML4
+/// * `user_addr` - User space address to access
+/// * `writable` - Whether the mapping will be written through
+///
+/// # Returns
+/// Ok(KernelMapping) with the kernel virtual address, or an error
+///
+/// # Errors
+/// * InvalidUserAddress - Address is outside user space, unmapped, not a user page
+///   or could not be populated
+/// * PermissionDenied - `writable` was requested for a read-only page
+/// * OutOfMemory - Breaking copy-on-write failed
+///
+/// # Notes
+/// The kernel writes through the direct map, which ignores the user PTE's
+/// write bit. Copy-on-write pages are therefore split here before a writable
+/// mapping is handed out, or the write would leak into the other sharers.
+pub fn kmap_user_page_in(
+    pml4_phys: PhysAddr,
+    user_addr: usize,
+    writable: bool,
+) -> ProcessResult<KernelMapping> {
+    use crate::mm::paging::{PageMapper, PageTableFlags};
+
     // Validate user address
     if !is_user_pointer_valid(user_addr) {
         return Err(ProcessError::InvalidUserAddress);
     }
 
-    // Page-align the address
     const PAGE_SIZE: usize = 4096;
     let page_addr = user_addr & !(PAGE_SIZE - 1);
-    let offset = user_addr & (PAGE_SIZE - 1);
 
-    // Allocate a temporary kernel virtual address for the mapping
-    // Use a dedicated region: 0xFFFF_FF00_0000_0000 - 0xFFFF_FF00_1000_0000 (256 MB)
-    const KMAP_BASE: usize = 0xFFFF_FF00_0000_0000;
-    const KMAP_SIZE: usize = 256 * 1024 * 1024; // 256 MB
+    let mapper = PageMapper::for_address_space(pml4_phys);
+    let mut flags = match mapper.get_page_flags(page_addr) {
+        Some(flags) => flags,
+        None => {
+            let pid = address_space_owner(pml4_phys).ok_or(ProcessError::InvalidUserAddress)?;
+            crate::mm::mmap::fault_in_page(pml4_phys, pid as u64, page_addr as u64)
+                .map_err(|_| ProcessError::InvalidUserAddress)?;
+            mapper
+                .get_page_flags(page_addr)
+                .ok_or(ProcessError::InvalidUserAddress)?
+        }
+    };
 
-    // For now, use a simple rotating allocator
-    // In production: Implement proper per-CPU kmap regions to avoid contention
-    use core::sync::atomic::{AtomicUsize, Ordering};
-    static NEXT_KMAP_SLOT: AtomicUsize = AtomicUsize::new(0);
+    if flags.bits() & PageTableFlags::USER.bits() == 0 {
+        return Err(ProcessError::InvalidUserAddress);
+    }
 
-    let _slot = NEXT_KMAP_SLOT.fetch_add(1, Ordering::Relaxed) % (KMAP_SIZE / PAGE_SIZE);
+    if writable && flags.bits() & PageTableFlags::WRITABLE.bits() == 0 {
+        if flags.bits() & PageTableFlags::COW.bits() == 0 {
+            return Err(ProcessError::PermissionDenied);
+        }
+
+        crate::arch::x86_64::fault::handle_cow_fault_in(pml4_phys, page_addr as u64)
+            .map_err(|_| ProcessError::OutOfMemory)?;
 
-    // Get the current page table
-    // When we have per-process page tables, we need to:
-    // 1. Get the physical address from the user's page table
-    // 2. Map it into the kernel page table
-    // For now, with shared address space, we can just use the address directly
+        flags = mapper
+            .get_page_flags(page_addr)
+            .ok_or(ProcessError::InvalidUserAddress)?;
+        if flags.bits() & PageTableFlags::WRITABLE.bits() == 0 {
+            return Err(ProcessError::PermissionDenied);
+        }
+    }
 
-    // In a full implementation, we would:
-    // 1. Walk the user page table to get the physical address
-    // 2. Create a temporary kernel mapping to that physical address
-    // 3. Return a guard that unmaps on drop
+    let phys = mapper
+        .translate(user_addr)
+        .ok_or(ProcessError::InvalidUserAddress)?;
 
-    // Current implementation (shared address space):
-    // Just return the user address since we share the same address space
     Ok(KernelMapping {
-        kernel_addr: page_addr + offset,
-        user_addr: page_addr,
+        kernel_addr: crate::mm::phys_to_virt(phys),
         _writable: writable,
     })
 }
 
-/// Guard for a temporary kernel mapping of a user page
+/// Kernel view of a user page
 ///
-/// Automatically unmaps the page when dropped.
+/// Points into the higher-half direct map, which covers all physical memory,
+/// so nothing has to be torn down when the guard is dropped. Only the bytes
+/// up to the end of the user page are valid.
 pub struct KernelMapping {
     /// Kernel virtual address of the mapping
     kernel_addr: usize,
-    /// Original user address (page-aligned)
-    user_addr: usize,
     /// Whether the mapping is writable
     _writable: bool,
 }
@@ -821,23 +823,16 @@ impl KernelMapping {
     }
 }
 
-impl Drop for KernelMapping {
-    fn drop(&mut self) {
-        // TODO: Unmap the temporary kernel mapping
-        // For now, with shared address space, nothing to do
-
-        // In a full implementation:
-        // 1. Remove the mapping from the kernel page table
-        // 2. Flush TLB for this address
-        // 3. Mark the kmap slot as free
-    }
+/// Number of bytes from `addr` to the end of its page, capped at `remaining`
+fn bytes_in_page(addr: usize, remaining: usize) -> usize {
+    const PAGE_SIZE: usize = 4096;
+    core::cmp::min(PAGE_SIZE - (addr & (PAGE_SIZE - 1)), remaining)
 }
 
 /// Copy data from user space to kernel space
 ///
-/// Validates the source pointer and performs a safe copy operation.
-/// Uses temporary kernel mappings (kmap_user_page) for safety when
-/// separate page tables are implemented.
+/// Validates the source pointer and copies page by page through the current
+/// task's page tables.
 ///
 /// # Arguments
 /// * `dst` - Destination buffer in kernel space
@@ -847,43 +842,18 @@ impl Drop for KernelMapping {
 /// # Returns
 /// Ok(()) if the copy succeeded, or an error if validation failed
 pub fn copy_from_user(dst: &mut [u8], src_ptr: usize, len: usize) -> ProcessResult<()> {
-    // Validate source pointer is in user space
-    if !is_user_pointer_valid(src_ptr) || !is_user_pointer_valid(src_ptr + len) {
-        return Err(ProcessError::InvalidUserAddress);
-    }
-
     // Check destination buffer size
     if len > dst.len() {
         return Err(ProcessError::InvalidMemoryRegion);
     }
 
-    // Current implementation (shared address space):
-    // Direct copy since we share the same address space
-    unsafe {
-        let src = core::slice::from_raw_parts(src_ptr as *const u8, len);
-        dst[..len].copy_from_slice(src);
-    }
-
-    // TODO: When implementing full page table separation, use kmap_user_page:
-    // let mut copied = 0;
-    // while copied < len {
-    //     let mapping = kmap_user_page(src_ptr + copied, false)?;
-    //     let to_copy = core::cmp::min(4096, len - copied);
-    //     unsafe {
-    //         let src = core::slice::from_raw_parts(mapping.as_ptr(), to_copy);
-    //         dst[copied..copied + to_copy].copy_from_slice(src);
-    //     }
-    //     copied += to_copy;
-    // }
-
-    Ok(())
+    copy_from_address_space(current_address_space(), &mut dst[..len], src_ptr)
 }
 
 /// Copy data from kernel space to user space
 ///
-/// Validates the destination pointer and performs a safe copy operation.
-/// Uses temporary kernel mappings (kmap_user_page) for safety when
-/// separate page tables are implemented.
+/// Validates the destination pointer and copies page by page through the
+/// current task's page tables, breaking copy-on-write as needed.
 ///
 /// # Arguments
 /// * `dst_ptr` - Destination pointer in user space
@@ -892,776 +862,112 @@ pub fn copy_from_user(dst: &mut [u8], src_ptr: usize, len: usize) -> ProcessResu
 /// # Returns
 /// Ok(()) if the copy succeeded, or an error if validation failed
 pub fn copy_to_user(dst_ptr: usize, src: &[u8]) -> ProcessResult<()> {
-    // Validate destination pointer is in user space
-    if !is_user_pointer_valid(dst_ptr) || !is_user_pointer_valid(dst_ptr + src.len()) {
-        return Err(ProcessError::InvalidUserAddress);
-    }
-
-    // Current implementation (shared address space):
-    // Direct copy since we share the same address space
-    unsafe {
-        let dst = core::slice::from_raw_parts_mut(dst_ptr as *mut u8, src.len());
-        dst.copy_from_slice(src);
-    }
-
-    // TODO: When implementing full page table separation, use kmap_user_page:
-    // let mut copied = 0;
-    // while copied < src.len() {
-    //     let mut mapping = kmap_user_page(dst_ptr + copied, true)?;
-    //     let to_copy = core::cmp::min(4096, src.len() - copied);
-    //     unsafe {
-    //         let dst = core::slice::from_raw_parts_mut(mapping.as_mut_ptr(), to_copy);
-    //         dst.copy_from_slice(&src[copied..copied + to_copy]);
-    //     }
-    //     copied += to_copy;
-    // }
-
-    Ok(())
+    copy_to_address_space(current_address_space(), dst_ptr, src)
 }
 
-/// Process-Scheduler Integration Functions
-///
-/// These functions provide integration between the Process management system
-/// and the existing Task-based scheduler. They allow both systems to work
-/// together during the transition to full process management.
-
-/// Synchronize process state with task state
-///
-/// Updates the process state to match the corresponding task state.
-/// This ensures consistency between the two management systems.
+/// Copy data out of an arbitrary address space
 ///
 /// # Arguments
-/// * `process_id` - Process ID to synchronize
-/// * `task_id` - Corresponding task ID
+/// * `pml4_phys` - Physical address of the source PML4
+/// * `dst` - Destination buffer in kernel space (its length is the copy size)
+/// * `src_ptr` - Source pointer in the target's user space
 ///
 /// # Returns
-/// Ok(()) if synchronization succeeded, or an error
-pub fn sync_process_with_task(
-    process_id: ProcessId,
-    task_id: crate::sched::task::TaskId,
+/// Ok(()) if every byte was copied, or an error if any page is not accessible
+pub fn copy_from_address_space(
+    pml4_phys: PhysAddr,
+    dst: &mut [u8],
+    src_ptr: usize,
 ) -> ProcessResult<()> {
-    use crate::sched;
-
-    // Get the task state
-    let task_state = match sched::get_task_mut(task_id) {
-        Some(task) => task.state,
-        None => return Err(ProcessError::ProcessNotFound),
-    };
-
-    // Get the process and update its state
-    let mut process_guard =
-        ProcessManager::get_process(process_id).ok_or(ProcessError::ProcessNotFound)?;
-
-    let process = process_guard
-        .get_mut()
-        .ok_or(ProcessError::ProcessNotFound)?;
-
-    // Map task state to process state
-    process.state = match task_state {
-        crate::sched::task::TaskState::Ready => ProcessState::Ready,
-        crate::sched::task::TaskState::Running => ProcessState::Running,
-        crate::sched::task::TaskState::Sleeping => ProcessState::Sleeping,
-        crate::sched::task::TaskState::Blocked => ProcessState::Blocked,
-    };
-
-    // Sync other fields
-    if let Some(task) = sched::get_task_mut(task_id) {
-        process.context = task.context.clone();
-        process.priority = task.priority;
-        process.wake_tick = task.wake_tick;
-
-        // Sync memory regions if they differ
-        if process.region_count != task.region_count {
-            process.clear_memory_regions();
-            for i in 0..task.region_count {
-                if let Some(region) = &task.memory_regions[i] {
-                    let _ = process.add_memory_region(region.clone());
-                }
-            }
-        }
+    let len = dst.len();
+    let end = src_ptr
+        .checked_add(len)
+        .ok_or(ProcessError::InvalidUserAddress)?;
+    if !is_user_pointer_valid(src_ptr) || !is_user_pointer_valid(end) {
+        return Err(ProcessError::InvalidUserAddress);
     }
 
-    Ok(())
-}
-
-/// Synchronize task state with process state
-///
-/// Updates the task state to match the corresponding process state.
-/// This is the reverse of sync_process_with_task.
-///
-/// # Arguments
-/// * `task_id` - Task ID to synchronize
-/// * `process_id` - Corresponding process ID
-///
-/// # Returns
-/// Ok(()) if synchronization succeeded, or an error
-pub fn sync_task_with_process(
-    task_id: crate::sched::task::TaskId,
-    process_id: ProcessId,
-) -> ProcessResult<()> {
-    use crate::sched;
-
-    // Get the process state
-    let process_guard =
-        ProcessManager::get_process(process_id).ok_or(ProcessError::ProcessNotFound)?;
-
-    let process = process_guard.get().ok_or(ProcessError::ProcessNotFound)?;
-
-    // Get the task and update its state
-    let task = sched::get_task_mut(task_id).ok_or(ProcessError::ProcessNotFound)?;
-
-    // Map process state to task state
-    task.state = match process.state {
-        ProcessState::Ready => crate::sched::task::TaskState::Ready,
-        ProcessState::Running => crate::sched::task::TaskState::Running,
-        ProcessState::Sleeping => crate::sched::task::TaskState::Sleeping,
-        ProcessState::Blocked => crate::sched::task::TaskState::Blocked,
-        ProcessState::Zombie => crate::sched::task::TaskState::Ready, // Will be cleaned up
-        ProcessState::Terminated => crate::sched::task::TaskState::Ready, // Will be cleaned up
-    };
-
-    // Sync other fields
-    task.context = process.context.clone();
-    task.priority = process.priority;
-    task.wake_tick = process.wake_tick;
-    task.creds.uid = process.uid;
-    task.creds.gid = process.gid;
-    task.creds.is_kernel_thread = false;
-    task.user_stack_pointer = process.context.rsp;
-
-    // Sync memory regions if they differ
-    if task.region_count != process.region_count {
-        task.clear_memory_regions();
-        for i in 0..process.region_count {
-            if let Some(region) = &process.memory_regions[i] {
-                let _ = task.add_memory_region(region.clone());
-            }
+    let mut copied = 0;
+    while copied < len {
+        let addr = src_ptr + copied;
+        let chunk = bytes_in_page(addr, len - copied);
+        let mapping = kmap_user_page_in(pml4_phys, addr, false)?;
+        unsafe {
+            let src = core::slice::from_raw_parts(mapping.as_ptr(), chunk);
+            dst[copied..copied + chunk].copy_from_slice(src);
         }
+        copied += chunk;
     }
 
     Ok(())
 }
 
-/// Create a task for an existing process
-///
-/// Creates a corresponding Task in the scheduler for a Process.
-/// This allows the process to be scheduled and executed.
-///
-/// # Arguments
-/// * `process_id` - Process ID to create task for
-/// * `entry_point` - Task entry point function
-///
-/// # Returns
-/// Ok(task_id) if task was created successfully, or an error
-pub fn create_task_for_process(
-    process_id: ProcessId,
-    entry_point: fn() -> !,
-) -> ProcessResult<crate::sched::task::TaskId> {
-    use crate::sched;
-
-    // Get the process
-    let process_guard =
-        ProcessManager::get_process(process_id).ok_or(ProcessError::ProcessNotFound)?;
-
-    let process = process_guard.get().ok_or(ProcessError::ProcessNotFound)?;
-
-    // Get process info before creating task
-    let process_name = "process_task"; // Use a static name for now
-    let process_priority = process.priority;
-
-    // Drop the guard before creating task to avoid lifetime issues
-    drop(process_guard);
-
-    // Create task with same priority as process
-    let task_id = sched::spawn_task(process_name, entry_point, process_priority)
-        .map_err(|_| ProcessError::OutOfMemory)?;
-
-    // Synchronize the task with the process
-    sync_task_with_process(task_id, process_id)?;
-
-    Ok(task_id)
-}
-
-/// Get the process ID for a task ID
-///
-/// Finds the process that corresponds to a given task.
-/// This is a simple mapping for now - in a full implementation,
-/// we would maintain a proper task->process mapping table.
-///
-/// # Arguments
-/// * `task_id` - Task ID to look up
-///
-/// # Returns
-/// Some(process_id) if found, None if no corresponding process
-pub fn get_process_for_task(task_id: crate::sched::task::TaskId) -> Option<ProcessId> {
-    // For now, we assume task_id == process_id
-    // In a full implementation, we would maintain a mapping table
-    Some(task_id)
-}
-
-/// Get the task ID for a process ID
-///
-/// Finds the task that corresponds to a given process.
-/// This is a simple mapping for now - in a full implementation,
-/// we would maintain a proper process->task mapping table.
-///
-/// # Arguments
-/// * `process_id` - Process ID to look up
-///
-/// # Returns
-/// Some(task_id) if found, None if no corresponding task
-pub fn get_task_for_process(process_id: ProcessId) -> Option<crate::sched::task::TaskId> {
-    // For now, we assume process_id == task_id
-    // In a full implementation, we would maintain a mapping table
-    Some(process_id)
-}
-
-/// Enhanced process state management
-///
-/// Updates both process and task states when a process changes state.
-/// This ensures consistency between the two management systems.
+/// Copy data into an arbitrary address space
 ///
 /// # Arguments
-/// * `process_id` - Process ID to update
-/// * `new_state` - New process state
+/// * `pml4_phys` - Physical address of the destination PML4
+/// * `dst_ptr` - Destination pointer in the target's user space
+/// * `src` - Source buffer in kernel space
 ///
 /// # Returns
-/// Ok(()) if state was updated successfully, or an error
-pub fn set_process_state(process_id: ProcessId, new_state: ProcessState) -> ProcessResult<()> {
-    // Update process state
-    let mut process_guard =
-        ProcessManager::get_process(process_id).ok_or(ProcessError::ProcessNotFound)?;
-
-    let process = process_guard
-        .get_mut()
-        .ok_or(ProcessError::ProcessNotFound)?;
-
-    let old_state = process.state;
-    process.state = new_state;
-
-    // Update corresponding task state if it exists
-    if let Some(task_id) = get_task_for_process(process_id) {
-        sync_task_with_process(task_id, process_id)?;
+/// Ok(()) if every byte was copied, or an error if any page is not writable
+pub fn copy_to_address_space(pml4_phys: PhysAddr, dst_ptr: usize, src: &[u8]) -> ProcessResult<()> {
+    let len = src.len();
+    let end = dst_ptr
+        .checked_add(len)
+        .ok_or(ProcessError::InvalidUserAddress)?;
+    if !is_user_pointer_valid(dst_ptr) || !is_user_pointer_valid(end) {
+        return Err(ProcessError::InvalidUserAddress);
     }
 
-    crate::serial_println!(
-        "[PROCESS] Process {} state changed: {:?} -> {:?}",
-        process_id,
-        old_state,
-        new_state
-    );
-
-    Ok(())
-}
-
-/// Process context switching support
-///
-/// Handles process-specific context switching operations like
-/// page table switching and TLB flushing.
-///
-/// # Arguments
-/// * `old_process_id` - Process being switched away from
-/// * `new_process_id` - Process being switched to
-///
-/// # Returns
-/// Ok(()) if context switch preparation succeeded, or an error
-pub fn prepare_process_context_switch(
-    old_process_id: Option<ProcessId>,
-    new_process_id: ProcessId,
-) -> ProcessResult<()> {
-    // Get the new process
-    let new_process_guard =
-        ProcessManager::get_process(new_process_id).ok_or(ProcessError::ProcessNotFound)?;
-
-    let _new_process = new_process_guard
-        .get()
-        .ok_or(ProcessError::ProcessNotFound)?;
-
-    // TODO: Switch page tables when we have per-process page tables
-    // For now, we're using a shared address space
-
-    // TODO: Flush TLB if switching between different address spaces
-    // unsafe {
-    //     core::arch::asm!("mov rax, cr3; mov cr3, rax", out("rax") _);
-    // }
-
-    // Mark that this CPU has accessed the new process
-    // This is used for optimized TLB shootdowns
-    crate::mm::tlb::mark_process_accessed(new_process_id);
-
-    // Update process state to Running
-    drop(new_process_guard);
-    set_process_state(new_process_id, ProcessState::Running)?;
-
-    // Update old process state to Ready (if it was Running)
-    if let Some(old_pid) = old_process_id {
-        let old_process_guard = ProcessManager::get_process(old_pid);
-        if let Some(guard) = old_process_guard {
-            if let Some(old_process) = guard.get() {
-                if old_process.state == ProcessState::Running {
-                    drop(guard);
-                    set_process_state(old_pid, ProcessState::Ready)?;
-                }
-            }
+    let mut copied = 0;
+    while copied < len {
+        let addr = dst_ptr + copied;
+        let chunk = bytes_in_page(addr, len - copied);
+        let mut mapping = kmap_user_page_in(pml4_phys, addr, true)?;
+        unsafe {
+            let dst = core::slice::from_raw_parts_mut(mapping.as_mut_ptr(), chunk);
+            dst.copy_from_slice(&src[copied..copied + chunk]);
         }
+        copied += chunk;
     }
 
     Ok(())
 }
 
-/// Process Management Tests
-///
-/// These tests verify the functionality of the process management system
-/// including process creation, state management, and cleanup.
-
-#[cfg(not(test))]
-pub mod tests {
+#[cfg(test)]
+mod tests {
     use super::*;
-    use crate::sched::priority::TaskPriority;
-    use crate::sched::{self};
-    use crate::serial_println;
-
-    /// Test process creation and basic operations
-    pub fn test_process_creation() {
-        serial_println!("[TEST] Testing process creation...");
-
-        // Test creating a process
-        match ProcessManager::create_process(None, "test_process") {
-            Ok(pid) => {
-                serial_println!("[TEST] ✓ Created process with PID {}", pid);
-
-                // Test getting the process
-                if let Some(process_guard) = ProcessManager::get_process(pid) {
-                    if let Some(process) = process_guard.get() {
-                        serial_println!(
-                            "[TEST] ✓ Retrieved process: PID={}, name='{}'",
-                            process.pid,
-                            process.get_name()
-                        );
-
-                        // Test process state
-                        if process.state == ProcessState::Ready {
-                            serial_println!("[TEST] ✓ Process has correct initial state: Ready");
-                        } else {
-                            serial_println!(
-                                "[TEST] ✗ Process has incorrect state: {:?}",
-                                process.state
-                            );
-                        }
-                    } else {
-                        serial_println!("[TEST] ✗ Process slot is empty");
-                    }
-                } else {
-                    serial_println!("[TEST] ✗ Failed to retrieve created process");
-                }
-
-                // Test removing the process
-                match ProcessManager::remove_process(pid) {
-                    Ok(removed) => {
-                        serial_println!(
-                            "[TEST] ✓ Removed process {} ({})",
-                            removed.pid,
-                            removed.get_name()
-                        );
-                    }
-                    Err(e) => {
-                        serial_println!("[TEST] ✗ Failed to remove process: {:?}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                serial_println!("[TEST] ✗ Failed to create process: {:?}", e);
-            }
-        }
-    }
-
-    /// Test process memory region management
-    pub fn test_memory_regions() {
-        serial_println!("[TEST] Testing memory region management...");
-
-        match ProcessManager::create_process(None, "memory_test") {
-            Ok(pid) => {
-                if let Some(mut process_guard) = ProcessManager::get_process(pid) {
-                    if let Some(process) = process_guard.get_mut() {
-                        // Test adding memory regions
-                        use crate::mm::paging::PageTableFlags;
-                        use crate::sched::task::{MemoryRegion, MemoryRegionType};
-
-                        let code_region = MemoryRegion::new(
-                            0x400000,
-                            0x401000,
-                            PageTableFlags::PRESENT | PageTableFlags::USER,
-                            MemoryRegionType::Code,
-                        );
-
-                        match process.add_memory_region(code_region) {
-                            Ok(()) => {
-                                serial_println!("[TEST] ✓ Added code region");
-
-                                // Test finding the region
-                                if let Some(found_region) = process.find_memory_region(0x400500) {
-                                    if found_region.region_type == MemoryRegionType::Code {
-                                        serial_println!("[TEST] ✓ Found code region correctly");
-                                    } else {
-                                        serial_println!("[TEST] ✗ Found region has wrong type");
-                                    }
-                                } else {
-                                    serial_println!("[TEST] ✗ Failed to find added region");
-                                }
-
-                                // Test memory usage calculation
-                                let usage = process.total_memory_usage();
-                                if usage == 4096 {
-                                    serial_println!(
-                                        "[TEST] ✓ Memory usage calculated correctly: {} bytes",
-                                        usage
-                                    );
-                                } else {
-                                    serial_println!(
-                                        "[TEST] ✗ Memory usage incorrect: {} bytes",
-                                        usage
-                                    );
-                                }
-                            }
-                            Err(e) => {
-                                serial_println!("[TEST] ✗ Failed to add memory region: {:?}", e);
-                            }
-                        }
-
-                        // Test overlapping region detection
-                        let overlapping_region = MemoryRegion::new(
-                            0x400800,
-                            0x401800,
-                            PageTableFlags::PRESENT | PageTableFlags::USER,
-                            MemoryRegionType::Data,
-                        );
-
-                        match process.add_memory_region(overlapping_region) {
-                            Err(ProcessError::RegionOverlap) => {
-                                serial_println!("[TEST] ✓ Correctly detected overlapping region");
-                            }
-                            Ok(()) => {
-                                serial_println!("[TEST] ✗ Failed to detect overlapping region");
-                            }
-                            Err(e) => {
-                                serial_println!(
-                                    "[TEST] ✗ Unexpected error for overlapping region: {:?}",
-                                    e
-                                );
-                            }
-                        }
-                    }
-                }
-
-                // Clean up
-                let _ = ProcessManager::remove_process(pid);
-            }
-            Err(e) => {
-                serial_println!("[TEST] ✗ Failed to create process for memory test: {:?}", e);
-            }
-        }
-    }
-
-    /// Test process state transitions
-    pub fn test_process_states() {
-        serial_println!("[TEST] Testing process state transitions...");
-
-        match ProcessManager::create_process(None, "state_test") {
-            Ok(pid) => {
-                // Test initial state
-                if let Some(process_guard) = ProcessManager::get_process(pid) {
-                    if let Some(process) = process_guard.get() {
-                        if process.state == ProcessState::Ready {
-                            serial_println!("[TEST] ✓ Initial state is Ready");
-                        } else {
-                            serial_println!(
-                                "[TEST] ✗ Initial state is not Ready: {:?}",
-                                process.state
-                            );
-                        }
-                    }
-                }
-
-                // Test state changes
-                match set_process_state(pid, ProcessState::Running) {
-                    Ok(()) => {
-                        serial_println!("[TEST] ✓ Changed state to Running");
-
-                        // Verify state change
-                        if let Some(process_guard) = ProcessManager::get_process(pid) {
-                            if let Some(process) = process_guard.get() {
-                                if process.state == ProcessState::Running {
-                                    serial_println!("[TEST] ✓ State change verified");
-                                } else {
-                                    serial_println!(
-                                        "[TEST] ✗ State change not applied: {:?}",
-                                        process.state
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        serial_println!("[TEST] ✗ Failed to change state: {:?}", e);
-                    }
-                }
-
-                // Test zombie state
-                if let Some(mut process_guard) = ProcessManager::get_process(pid) {
-                    if let Some(process) = process_guard.get_mut() {
-                        process.mark_zombie(42);
-
-                        if process.state == ProcessState::Zombie && process.exit_code == Some(42) {
-                            serial_println!("[TEST] ✓ Zombie state set correctly");
-                        } else {
-                            serial_println!("[TEST] ✗ Zombie state not set correctly");
-                        }
-                    }
-                }
-
-                // Clean up
-                let _ = ProcessManager::remove_process(pid);
-            }
-            Err(e) => {
-                serial_println!("[TEST] ✗ Failed to create process for state test: {:?}", e);
-            }
-        }
-    }
-
-    /// Test process table management and PID allocation
-    pub fn test_process_table() {
-        serial_println!("[TEST] Testing process table management...");
-
-        // Test PID allocation
-        let pid1 = ProcessManager::alloc_pid();
-        let pid2 = ProcessManager::alloc_pid();
-        let pid3 = ProcessManager::alloc_pid();
-
-        if pid2 == pid1 + 1 && pid3 == pid2 + 1 {
-            serial_println!(
-                "[TEST] ✓ PID allocation is sequential: {}, {}, {}",
-                pid1,
-                pid2,
-                pid3
-            );
-        } else {
-            serial_println!(
-                "[TEST] ✗ PID allocation is not sequential: {}, {}, {}",
-                pid1,
-                pid2,
-                pid3
-            );
-        }
-
-        // Test process table statistics
-        let (total, used_before, free_before) = ProcessManager::get_stats();
-        serial_println!(
-            "[TEST] Process table before: {}/{} used, {} free",
-            used_before,
-            total,
-            free_before
-        );
-
-        // Create multiple processes
-        let mut created_pids = [0usize; 5];
-        let mut created_count = 0;
-        for i in 0..5 {
-            match ProcessManager::create_process(None, "table_test") {
-                Ok(pid) => {
-                    created_pids[created_count] = pid;
-                    created_count += 1;
-                    serial_println!("[TEST] Created process {} (iteration {})", pid, i);
-                }
-                Err(e) => {
-                    serial_println!("[TEST] ✗ Failed to create process {}: {:?}", i, e);
-                    break;
-                }
-            }
-        }
-
-        let (_, used_after, free_after) = ProcessManager::get_stats();
-        serial_println!(
-            "[TEST] Process table after creation: {} used, {} free",
-            used_after,
-            free_after
-        );
-
-        if used_after == used_before + created_count {
-            serial_println!("[TEST] ✓ Process table statistics are correct");
-        } else {
-            serial_println!("[TEST] ✗ Process table statistics are incorrect");
-        }
-
-        // Clean up created processes
-        for i in 0..created_count {
-            let pid = created_pids[i];
-            match ProcessManager::remove_process(pid) {
-                Ok(_) => {
-                    serial_println!("[TEST] Cleaned up process {}", pid);
-                }
-                Err(e) => {
-                    serial_println!("[TEST] ✗ Failed to clean up process {}: {:?}", pid, e);
-                }
-            }
-        }
-
-        let (_, used_final, free_final) = ProcessManager::get_stats();
-        serial_println!(
-            "[TEST] Process table after cleanup: {} used, {} free",
-            used_final,
-            free_final
-        );
-
-        if used_final == used_before {
-            serial_println!("[TEST] ✓ Process table cleanup successful");
-        } else {
-            serial_println!("[TEST] ✗ Process table cleanup incomplete");
-        }
-    }
-
-    /// Test zombie child detection
-    pub fn test_zombie_children() {
-        serial_println!("[TEST] Testing zombie child detection...");
-
-        // Create parent process
-        match ProcessManager::create_process(None, "parent") {
-            Ok(parent_pid) => {
-                // Create child process
-                match ProcessManager::create_process(Some(parent_pid), "child") {
-                    Ok(child_pid) => {
-                        serial_println!(
-                            "[TEST] Created parent {} and child {}",
-                            parent_pid,
-                            child_pid
-                        );
-
-                        // Mark child as zombie
-                        if let Some(mut child_guard) = ProcessManager::get_process(child_pid) {
-                            if let Some(child) = child_guard.get_mut() {
-                                child.mark_zombie(123);
-                                serial_println!("[TEST] Marked child {} as zombie", child_pid);
-                            }
-                        }
-
-                        // Test finding zombie child
-                        match ProcessManager::find_zombie_child(parent_pid) {
-                            Some((found_pid, exit_code)) => {
-                                if found_pid == child_pid && exit_code == 123 {
-                                    serial_println!("[TEST] ✓ Found zombie child correctly: PID={}, exit_code={}", 
-                                                   found_pid, exit_code);
-                                } else {
-                                    serial_println!(
-                                        "[TEST] ✗ Found wrong zombie child: PID={}, exit_code={}",
-                                        found_pid,
-                                        exit_code
-                                    );
-                                }
-                            }
-                            None => {
-                                serial_println!("[TEST] ✗ Failed to find zombie child");
-                            }
-                        }
-
-                        // Clean up
-                        let _ = ProcessManager::remove_process(child_pid);
-                    }
-                    Err(e) => {
-                        serial_println!("[TEST] ✗ Failed to create child process: {:?}", e);
-                    }
-                }
-
-                // Clean up parent
-                let _ = ProcessManager::remove_process(parent_pid);
-            }
-            Err(e) => {
-                serial_println!("[TEST] ✗ Failed to create parent process: {:?}", e);
-            }
-        }
-    }
-
-    /// Test integration with scheduler
-    pub fn test_scheduler_integration() {
-        serial_println!("[TEST] Testing scheduler integration...");
-
-        // Test creating a process-task pair
-        fn dummy_task() -> ! {
-            loop {
-                unsafe {
-                    core::arch::asm!("hlt");
-                }
-            }
-        }
-
-        match sched::spawn_process_task("integration_test", dummy_task, TaskPriority::Normal, None)
-        {
-            Ok((process_id, task_id)) => {
-                serial_println!(
-                    "[TEST] ✓ Created process {} with task {}",
-                    process_id,
-                    task_id
-                );
-
-                // Test synchronization
-                match sync_process_with_task(process_id, task_id) {
-                    Ok(()) => {
-                        serial_println!("[TEST] ✓ Process-task synchronization successful");
-                    }
-                    Err(e) => {
-                        serial_println!("[TEST] ✗ Process-task synchronization failed: {:?}", e);
-                    }
-                }
-
-                // Test mapping functions
-                if let Some(found_process_id) = get_process_for_task(task_id) {
-                    if found_process_id == process_id {
-                        serial_println!("[TEST] ✓ Task->Process mapping works");
-                    } else {
-                        serial_println!(
-                            "[TEST] ✗ Task->Process mapping incorrect: {} != {}",
-                            found_process_id,
-                            process_id
-                        );
-                    }
-                }
-
-                if let Some(found_task_id) = get_task_for_process(process_id) {
-                    if found_task_id == task_id {
-                        serial_println!("[TEST] ✓ Process->Task mapping works");
-                    } else {
-                        serial_println!(
-                            "[TEST] ✗ Process->Task mapping incorrect: {} != {}",
-                            found_task_id,
-                            task_id
-                        );
-                    }
-                }
-
-                // Clean up (process will be cleaned up when task is removed)
-                serial_println!(
-                    "[TEST] Integration test cleanup (task will be cleaned up by scheduler)"
-                );
-            }
-            Err(e) => {
-                serial_println!("[TEST] ✗ Failed to create process-task pair: {:?}", e);
-            }
-        }
-    }
 
-    /// Run all process management tests
-    pub fn run_all_tests() {
-        serial_println!("[TEST] ========================================");
-        serial_println!("[TEST] Running Process Management Tests");
-        serial_println!("[TEST] ========================================");
-
-        test_process_creation();
-        test_memory_regions();
-        test_process_states();
-        test_process_table();
-        test_zombie_children();
-        test_scheduler_integration();
-
-        serial_println!("[TEST] ========================================");
-        serial_println!("[TEST] Process Management Tests Completed!");
-        serial_println!("[TEST] ========================================");
+    #[test]
+    fn test_wait_status_encoding() {
+        // The bit layout user space decodes with WIFEXITED and friends
+        assert_eq!(WaitStatus::Exited(3).encode(), 0x0300);
+        assert_eq!(WaitStatus::Exited(0x1ff).encode(), 0xff00);
+        let killed = WaitStatus::Signaled {
+            signal: signals::SIGKILL,
+            core_dumped: false,
+        };
+        assert_eq!(killed.encode(), 9);
+        let dumped = WaitStatus::Signaled {
+            signal: signals::SIGSEGV,
+            core_dumped: true,
+        };
+        assert_eq!(dumped.encode(), 0x8b);
+        assert_eq!(WaitStatus::Stopped(signals::SIGTSTP).encode(), 0x147f);
+        assert_eq!(WaitStatus::Continued.encode(), 0xffff);
+
+        assert_eq!(dumped.si_code(), CLD_DUMPED);
+        assert_eq!(dumped.si_status(), signals::SIGSEGV as i32);
+        assert_eq!(WaitStatus::Continued.si_code(), CLD_CONTINUED);
+    }
+
+    #[test]
+    fn test_wait_options_select_changes() {
+        assert!(WaitStatus::Exited(0).wanted_by(WEXITED));
+        assert!(!WaitStatus::Stopped(signals::SIGSTOP).wanted_by(WEXITED));
+        assert!(WaitStatus::Stopped(signals::SIGSTOP).wanted_by(WEXITED | WUNTRACED));
+        assert!(!WaitStatus::Continued.wanted_by(WEXITED | WUNTRACED));
+        assert!(WaitStatus::Continued.wanted_by(WCONTINUED));
     }
 }
@@ -0,0 +1,887 @@
+//! Process Tracing
+//!
+//! `ptrace` lets a process, the tracer, control a task of another process,
+//! the tracee: read and write its memory and registers and stop it at
+//! events. A task becomes a tracee with `PTRACE_TRACEME` (traced by its
+//! parent) or `PTRACE_ATTACH` (which also sends it SIGSTOP). Tracing is
+//! not inherited by `fork` and ends when either side exits.
+//!
+//! A tracee stops for its tracer (a trace stop):
+//!
+//! - before a signal is delivered to it (signal-delivery-stop). The tracer
+//!   chooses the signal to deliver, if any, when it resumes the tracee;
+//! - at entry to and exit from each syscall while resumed with
+//!   `PTRACE_SYSCALL` (syscall-stop), reported as SIGTRAP, or as
+//!   `SIGTRAP | 0x80` with `PTRACE_O_TRACESYSGOOD`. At entry RAX is
+//!   `-ENOSYS` and ORIG_RAX is the syscall number, which the tracer can
+//!   change along with the arguments;
+//! - on SIGTRAP from an `int3` breakpoint, or after one instruction when
+//!   resumed with `PTRACE_SINGLESTEP` (using the RFLAGS trap flag).
+//!
+//! A successful `exec` sends the tracee SIGTRAP. `exec` enters the new
+//! program directly, so the stop comes on the first return from a syscall.
+//!
+//! The tracer learns of trace stops through `wait`, as if the tracee were
+//! a stopped child (`WaitStatus::Trapped`, reported without `WUNTRACED`).
+//! Trace state is only changed with `CHILD_WAITERS` held, like stop state,
+//! and the tracer only touches a tracee while it is stopped. SIGKILL
+//! always ends a trace stop.
+//!
+//! Syscall-stops are only seen on the `int 0x80` gate. Signals are
+//! delivered there and on return to user mode from timer and reschedule
+//! interrupts.
+
+use super::capability::Capabilities;
+use super::process::{
+    copy_from_address_space, copy_to_address_space, take_waiters, ProcessError, ProcessResult,
+    WaitStatus, CHILD_WAITERS, CLD_TRAPPED,
+};
+use crate::sched::process_group::Pid;
+use crate::sched::task::{Credentials, Task, TaskId, TaskState};
+use crate::signal::frame::{RFLAGS_TF, USER_RFLAGS};
+use crate::signal::{sigbit, signals, SigSource, Signal, UNBLOCKABLE};
+use crate::sys::syscall::SyscallFrame;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+/// Become traced by the parent
+pub const PTRACE_TRACEME: usize = 0;
+/// Read a word of the tracee's code
+pub const PTRACE_PEEKTEXT: usize = 1;
+/// Read a word of the tracee's data
+pub const PTRACE_PEEKDATA: usize = 2;
+/// Write a word of the tracee's code
+pub const PTRACE_POKETEXT: usize = 4;
+/// Write a word of the tracee's data
+pub const PTRACE_POKEDATA: usize = 5;
+/// Resume the tracee
+pub const PTRACE_CONT: usize = 7;
+/// Kill the tracee
+pub const PTRACE_KILL: usize = 8;
+/// Resume the tracee for one instruction
+pub const PTRACE_SINGLESTEP: usize = 9;
+/// Read the tracee's registers
+pub const PTRACE_GETREGS: usize = 12;
+/// Set the tracee's registers
+pub const PTRACE_SETREGS: usize = 13;
+/// Start tracing a task
+pub const PTRACE_ATTACH: usize = 16;
+/// Stop tracing a task, resuming it
+pub const PTRACE_DETACH: usize = 17;
+/// Resume the tracee up to its next syscall entry or exit
+pub const PTRACE_SYSCALL: usize = 24;
+/// Set `PTRACE_O_*` options
+pub const PTRACE_SETOPTIONS: usize = 0x4200;
+
+/// Option: report syscall-stops as `SIGTRAP | 0x80`
+pub const PTRACE_O_TRACESYSGOOD: u64 = 1;
+
+/// RAX at a syscall-entry-stop (`-ENOSYS`)
+pub const ENTRY_RAX: u64 = -38i64 as u64;
+
+/// How a tracee runs until its next stop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    /// Until a signal arrives
+    Continue,
+    /// Until a signal arrives or the next syscall entry or exit
+    Syscall,
+    /// For one instruction
+    SingleStep,
+}
+
+/// Tracing state of a task (`Task::ptrace`)
+#[derive(Debug)]
+pub struct TraceState {
+    /// Process tracing the task
+    pub tracer: Pid,
+    /// `PTRACE_O_*` options
+    options: u64,
+    /// How the task was last resumed
+    resume: Resume,
+    /// Signal the task is stopped for (None while it runs)
+    stop: Option<Signal>,
+    /// Whether `wait` has reported the current stop
+    reported: bool,
+    /// Registers the task returns to user mode with, while stopped
+    regs: *mut SyscallFrame,
+    /// Number of the syscall in progress (ORIG_RAX)
+    syscall: Option<u64>,
+    /// Signal the tracer gave on resuming the task
+    inject: Signal,
+    /// Signals the tracer let through, not to be reported again
+    passed: u64,
+    /// Read-only pages the tracer wrote to, which the task has its own
+    /// copies of (address space and page)
+    copied: Vec<(crate::mm::PhysAddr, usize)>,
+}
+
+impl TraceState {
+    fn new(tracer: Pid) -> Self {
+        Self {
+            tracer,
+            options: 0,
+            resume: Resume::Continue,
+            stop: None,
+            reported: false,
+            regs: core::ptr::null_mut(),
+            syscall: None,
+            inject: 0,
+            passed: 0,
+            copied: Vec::new(),
+        }
+    }
+}
+
+/// Registers as `PTRACE_GETREGS` returns them (Linux `user_regs_struct`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserRegs {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64,
+}
+
+impl UserRegs {
+    /// Registers of a task that returns to user mode with `regs`
+    ///
+    /// # Arguments
+    /// * `regs` - Saved user registers
+    /// * `orig_rax` - Number of the syscall in progress (-1 if none)
+    /// * `fs_base` - FS base of the task
+    pub fn new(regs: &SyscallFrame, orig_rax: u64, fs_base: u64) -> Self {
+        Self {
+            r15: regs.r15,
+            r14: regs.r14,
+            r13: regs.r13,
+            r12: regs.r12,
+            rbp: regs.rbp,
+            rbx: regs.rbx,
+            r11: regs.r11,
+            r10: regs.r10,
+            r9: regs.r9,
+            r8: regs.r8,
+            rax: regs.rax,
+            rcx: regs.rcx,
+            rdx: regs.rdx,
+            rsi: regs.rsi,
+            rdi: regs.rdi,
+            orig_rax,
+            rip: regs.rip,
+            cs: regs.cs,
+            eflags: regs.rflags,
+            rsp: regs.rsp,
+            ss: regs.ss,
+            fs_base,
+            ..Self::default()
+        }
+    }
+
+    /// Copy the registers a tracer may set into `regs`
+    ///
+    /// Segments stay as they are, and of RFLAGS only the bits in
+    /// `USER_RFLAGS` change.
+    ///
+    /// # Returns
+    /// Err if RIP, RSP or the FS base is outside user space
+    fn apply(&self, regs: &mut SyscallFrame) -> Result<(), ()> {
+        use super::process::USER_LIMIT;
+
+        if [self.rip, self.rsp, self.fs_base]
+            .iter()
+            .any(|&addr| addr >= USER_LIMIT as u64)
+        {
+            return Err(());
+        }
+        regs.r15 = self.r15;
+        regs.r14 = self.r14;
+        regs.r13 = self.r13;
+        regs.r12 = self.r12;
+        regs.rbp = self.rbp;
+        regs.rbx = self.rbx;
+        regs.r11 = self.r11;
+        regs.r10 = self.r10;
+        regs.r9 = self.r9;
+        regs.r8 = self.r8;
+        regs.rax = self.rax;
+        regs.rcx = self.rcx;
+        regs.rdx = self.rdx;
+        regs.rsi = self.rsi;
+        regs.rdi = self.rdi;
+        regs.rip = self.rip;
+        regs.rflags = (regs.rflags & !USER_RFLAGS) | (self.eflags & USER_RFLAGS);
+        regs.rsp = self.rsp;
+        Ok(())
+    }
+}
+
+/// Status a trace stop is reported with
+fn syscall_stop_signal(state: &TraceState) -> Signal {
+    if state.options & PTRACE_O_TRACESYSGOOD != 0 {
+        signals::SIGTRAP | 0x80
+    } else {
+        signals::SIGTRAP
+    }
+}
+
+/// Trace stop of `task` that its tracer `tracer` has yet to collect
+///
+/// The caller holds `CHILD_WAITERS`.
+pub(super) fn trace_report(task: &Task, tracer: Pid) -> Option<WaitStatus> {
+    let state = task
+        .ptrace
+        .as_ref()
+        .filter(|state| state.tracer == tracer)?;
+    state
+        .stop
+        .filter(|_| !state.reported)
+        .map(WaitStatus::Trapped)
+}
+
+/// Note that `wait` reported the trace stop of `task`
+///
+/// The caller holds `CHILD_WAITERS`.
+pub(super) fn mark_reported(task: &mut Task) {
+    if let Some(state) = task.ptrace.as_mut() {
+        state.reported = true;
+    }
+}
+
+/// Stop the current task for its tracer
+///
+/// The tracer gets SIGCHLD and its waiters are woken. Returns once the
+/// tracer resumes the task, or detaches, or SIGKILL arrives.
+///
+/// # Arguments
+/// * `regs` - Registers the task returns to user mode with; the tracer
+///   reads and changes them while the task is stopped
+/// * `signal` - What the stop is reported as
+///
+/// # Returns
+/// The signal the tracer resumed the task with (0 for none, or if the
+/// task is not traced, was detached or was killed)
+fn trace_stop(regs: &mut SyscallFrame, signal: Signal) -> Signal {
+    let task_id = match crate::sched::current_task() {
+        Some(task) => task.id,
+        None => return 0,
+    };
+
+    let woken = x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut waiters = CHILD_WAITERS.lock();
+        let task = crate::sched::get_task_mut(task_id)?;
+        let (id, uid) = (task.id, task.creds.uid);
+        let state = task.ptrace.as_mut()?;
+        state.stop = Some(signal);
+        state.reported = false;
+        state.regs = regs;
+        state.inject = 0;
+
+        let tracer = state.tracer;
+        if let Some(tracer) = crate::sched::get_task_mut(tracer) {
+            let source = SigSource::Child {
+                code: CLD_TRAPPED,
+                pid: id,
+                uid,
+                status: signal as i32,
+            };
+            let _ = crate::signal::send_signal_info(tracer, signals::SIGCHLD, source);
+        }
+        Some(take_waiters(&mut waiters, tracer))
+    });
+    match woken {
+        Some(woken) => woken.into_iter().for_each(|id| {
+            crate::sched::wake_task(id);
+        }),
+        None => return 0,
+    }
+
+    loop {
+        let mut parked = false;
+        let mut inject = 0;
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let waiters = CHILD_WAITERS.lock();
+            let task = match crate::sched::get_task_mut(task_id) {
+                Some(task) => task,
+                None => return,
+            };
+            let killed = task.has_pending_signal(signals::SIGKILL);
+            let state = match task.ptrace.as_mut() {
+                Some(state) => state,
+                None => return,
+            };
+            if state.stop.is_none() || killed {
+                // The tracer may no longer touch the registers
+                state.stop = None;
+                state.regs = core::ptr::null_mut();
+                inject = core::mem::take(&mut state.inject);
+                return;
+            }
+
+            // SIGKILL wakes an interruptible task: either its sender sees
+            // `interruptible` or we see its pending bit
+            task.state = TaskState::Blocked;
+            task.interruptible = true;
+            core::sync::atomic::fence(Ordering::SeqCst);
+            let killed = task.has_pending_signal(signals::SIGKILL);
+            drop(waiters);
+            if killed {
+                crate::sched::wake_task(task_id);
+            }
+            parked = true;
+            crate::sched::schedule();
+        });
+        if let Some(task) = crate::sched::current_task_mut() {
+            task.interruptible = false;
+        }
+        if !parked {
+            return inject;
+        }
+    }
+}
+
+/// Signal-delivery-stop of the current task, if it is traced
+///
+/// # Arguments
+/// * `regs` - Registers the task returns to user mode with
+/// * `signal` - Signal about to be delivered
+/// * `source` - Where it came from
+///
+/// # Returns
+/// The signal to deliver instead and its source, or None if the tracer
+/// cancelled it; untraced tasks get `signal` back
+pub fn signal_stop(
+    regs: &mut SyscallFrame,
+    signal: Signal,
+    source: SigSource,
+) -> Option<(Signal, SigSource)> {
+    let traced = crate::sched::current_task().is_some_and(|task| task.ptrace.is_some());
+    if !traced {
+        return Some((signal, source));
+    }
+    match trace_stop(regs, signal) {
+        0 => None,
+        inject if inject == signal => Some((signal, source)),
+        inject => Some((inject, SigSource::Kernel)),
+    }
+}
+
+/// Syscall-entry-stop of the current task, if its tracer asked for one
+///
+/// # Arguments
+/// * `regs` - Registers saved on entry to the syscall
+/// * `syscall_id` - Number of the syscall
+///
+/// # Returns
+/// The syscall to run, if the task stopped (its arguments are in `regs`,
+/// where the tracer may have changed them)
+pub fn syscall_entry(regs: &mut SyscallFrame, syscall_id: usize) -> Option<usize> {
+    let signal = {
+        let state = crate::sched::current_task_mut()?.ptrace.as_mut()?;
+        state.syscall = Some(syscall_id as u64);
+        if state.resume != Resume::Syscall {
+            return None;
+        }
+        syscall_stop_signal(state)
+    };
+
+    regs.rax = ENTRY_RAX;
+    let inject = trace_stop(regs, signal);
+    let task = crate::sched::current_task_mut()?;
+    if inject != 0 {
+        let _ = crate::signal::send_signal(task, inject);
+    }
+    let syscall = task.ptrace.as_ref().and_then(|state| state.syscall);
+    Some(syscall.map_or(syscall_id, |syscall| syscall as usize))
+}
+
+/// Syscall-exit-stop of the current task, if its tracer asked for one
+///
+/// # Arguments
+/// * `regs` - Registers the task returns to user mode with
+/// * `result` - Return value of the syscall
+///
+/// # Returns
+/// The return value, which the tracer may have changed
+pub fn syscall_exit(regs: &mut SyscallFrame, result: isize) -> isize {
+    let signal = match crate::sched::current_task().and_then(|task| task.ptrace.as_ref()) {
+        Some(state) if state.resume == Resume::Syscall => syscall_stop_signal(state),
+        _ => return result,
+    };
+
+    regs.rax = result as u64;
+    let inject = trace_stop(regs, signal);
+    if inject != 0 {
+        if let Some(task) = crate::sched::current_task_mut() {
+            let _ = crate::signal::send_signal(task, inject);
+        }
+    }
+    regs.rax as isize
+}
+
+/// Stop the current task for each deliverable signal, if it is traced
+///
+/// Called on the way back to user mode from a syscall or interrupt, before
+/// any signal is acted on. Each signal is taken off the pending set and sent again
+/// if the tracer lets it through (possibly as another signal), marked so
+/// it is not reported twice. SIGKILL is never reported.
+pub fn report_signals(regs: &mut SyscallFrame) {
+    loop {
+        let task = match crate::sched::current_task_mut() {
+            Some(task) => task,
+            None => return,
+        };
+        let pending = task.pending_signals.load(Ordering::Acquire);
+        let blocked = task.signal_mask.load(Ordering::Acquire) & !UNBLOCKABLE;
+        let state = match task.ptrace.as_mut() {
+            Some(state) => state,
+            None => return,
+        };
+        state.passed &= pending;
+        let reportable = pending & !blocked & !state.passed & !sigbit(signals::SIGKILL);
+        if reportable == 0 {
+            state.syscall = None;
+            return;
+        }
+
+        let signal = crate::signal::lowest_signal(reportable);
+        let source = crate::signal::dequeue_signal(task, signal);
+        if let Some((signal, source)) = signal_stop(regs, signal, source) {
+            let task = match crate::sched::current_task_mut() {
+                Some(task) => task,
+                None => return,
+            };
+            if let Some(state) = task.ptrace.as_mut() {
+                state.passed |= sigbit(signal);
+            }
+            let _ = crate::signal::send_signal_info(task, signal, source);
+        }
+    }
+}
+
+/// Stop tracing an exiting task
+///
+/// Its tracer's waiters are woken to notice. Called by the task itself,
+/// already a zombie, with interrupts disabled.
+pub(crate) fn exit_tracee(task: &mut Task) {
+    let woken = {
+        let mut waiters = CHILD_WAITERS.lock();
+        match task.ptrace.take() {
+            Some(state) => take_waiters(&mut waiters, state.tracer),
+            None => Vec::new(),
+        }
+    };
+    woken.into_iter().for_each(|id| {
+        crate::sched::wake_task(id);
+    });
+}
+
+/// Let go of every task traced by the exiting process `tracer`
+///
+/// Stopped tracees resume. Called with interrupts disabled.
+pub(crate) fn release_tracees(tracer: Pid) {
+    let mut stopped = Vec::new();
+    {
+        let _waiters = CHILD_WAITERS.lock();
+        crate::sched::for_each_task(|task| {
+            if let Some(state) = task.ptrace.as_ref().filter(|state| state.tracer == tracer) {
+                if state.stop.is_some() {
+                    stopped.push(task.id);
+                }
+                if state.resume == Resume::SingleStep && !state.regs.is_null() {
+                    unsafe { (*state.regs).rflags &= !RFLAGS_TF };
+                }
+                task.ptrace = None;
+            }
+        });
+    }
+    stopped.into_iter().for_each(|id| {
+        crate::sched::wake_task(id);
+    });
+}
+
+/// Make the calling task traced by its parent (`PTRACE_TRACEME`)
+///
+/// # Errors
+/// * PermissionDenied - The task is already traced, or has no parent
+pub fn trace_me() -> ProcessResult<()> {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let _waiters = CHILD_WAITERS.lock();
+        let task = crate::sched::current_task_mut().ok_or(ProcessError::ProcessNotFound)?;
+        if task.ptrace.is_some() || task.ppid == 0 {
+            return Err(ProcessError::PermissionDenied);
+        }
+        task.ptrace = Some(TraceState::new(task.ppid));
+        Ok(())
+    })
+}
+
+/// Whether a caller with `creds` may attach to a task running with
+/// `target`
+///
+/// Without `CAP_SYS_PTRACE` the task must run as the caller's own user
+/// (`creds::same_user`, so a set-ID program that only dropped its
+/// effective ID is out of reach) and be dumpable.
+fn may_attach(creds: &Credentials, target: &Credentials, dumpable: bool) -> bool {
+    creds.capable(Capabilities::CAP_SYS_PTRACE)
+        || (dumpable && super::creds::same_user(creds, target))
+}
+
+/// Start tracing task `tid` (`PTRACE_ATTACH`)
+///
+/// The task is sent SIGSTOP, so it soon stops for the tracer.
+///
+/// # Errors
+/// * ProcessNotFound - No such task
+/// * PermissionDenied - The task is in the caller's process, a kernel
+///   thread, init, already traced, or `may_attach` refuses it
+pub fn attach(tid: TaskId) -> ProcessResult<()> {
+    let (tracer, creds) = crate::sched::current_task()
+        .map(|task| (task.pid, task.creds))
+        .ok_or(ProcessError::ProcessNotFound)?;
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let _waiters = CHILD_WAITERS.lock();
+        let task = crate::sched::get_task_mut(tid).ok_or(ProcessError::ProcessNotFound)?;
+        if task.state == TaskState::Zombie {
+            return Err(ProcessError::ProcessNotFound);
+        }
+        if task.pid == tracer
+            || task.pid == 1
+            || task.creds.is_kernel_thread
+            || task.ptrace.is_some()
+            || !may_attach(&creds, &task.creds, task.dumpable)
+        {
+            return Err(ProcessError::PermissionDenied);
+        }
+        task.ptrace = Some(TraceState::new(tracer));
+        Ok(())
+    })?;
+
+    if let Some(task) = crate::sched::get_task_mut(tid) {
+        let _ = crate::signal::send_signal(task, signals::SIGSTOP);
+    }
+    Ok(())
+}
+
+/// Run `f` on task `tid` and its trace state, if it is stopped and traced
+/// by the calling process
+///
+/// `f` runs with `CHILD_WAITERS` held, so the task stays stopped.
+///
+/// # Errors
+/// * ProcessNotFound - No such task, or it is not a stopped tracee of the
+///   caller
+fn with_tracee<T>(tid: TaskId, f: impl FnOnce(&mut Task) -> ProcessResult<T>) -> ProcessResult<T> {
+    let tracer = crate::sched::current_task()
+        .map(|task| task.pid)
+        .ok_or(ProcessError::ProcessNotFound)?;
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let _waiters = CHILD_WAITERS.lock();
+        let task = crate::sched::get_task_mut(tid).ok_or(ProcessError::ProcessNotFound)?;
+        match task.ptrace.as_ref() {
+            Some(state) if state.tracer == tracer && state.stop.is_some() => f(task),
+            _ => Err(ProcessError::ProcessNotFound),
+        }
+    })
+}
+
+/// Trace state of a task `with_tracee` handed out
+fn state_of(task: &mut Task) -> &mut TraceState {
+    task.ptrace.as_mut().expect("tracee without trace state")
+}
+
+/// Resume the stopped tracee `tid`
+///
+/// # Arguments
+/// * `tid` - Tracee
+/// * `resume` - How it runs until its next stop
+/// * `signal` - Signal to deliver (0 for none); at a signal-delivery-stop
+///   it replaces the signal the task stopped for
+///
+/// # Errors
+/// * ProcessNotFound - `tid` is not a stopped tracee of the caller
+/// * InvalidArgument - Bad signal number
+pub fn resume(tid: TaskId, resume: Resume, signal: Signal) -> ProcessResult<()> {
+    if signal >= signals::MAX_SIGNAL {
+        return Err(ProcessError::InvalidArgument);
+    }
+    with_tracee(tid, |task| {
+        let state = state_of(task);
+        let regs = unsafe { &mut *state.regs };
+        if resume == Resume::SingleStep {
+            regs.rflags |= RFLAGS_TF;
+        } else if state.resume == Resume::SingleStep {
+            regs.rflags &= !RFLAGS_TF;
+        }
+        state.resume = resume;
+        state.inject = signal;
+        state.stop = None;
+        Ok(())
+    })?;
+    crate::sched::wake_task(tid);
+    Ok(())
+}
+
+/// Stop tracing the stopped tracee `tid` and resume it (`PTRACE_DETACH`)
+///
+/// # Arguments
+/// * `tid` - Tracee
+/// * `signal` - Signal to send it (0 for none)
+///
+/// # Errors
+/// * ProcessNotFound - `tid` is not a stopped tracee of the caller
+/// * InvalidArgument - Bad signal number
+pub fn detach(tid: TaskId, signal: Signal) -> ProcessResult<()> {
+    if signal >= signals::MAX_SIGNAL {
+        return Err(ProcessError::InvalidArgument);
+    }
+    with_tracee(tid, |task| {
+        let state = state_of(task);
+        if state.resume == Resume::SingleStep {
+            unsafe { (*state.regs).rflags &= !RFLAGS_TF };
+        }
+        task.ptrace = None;
+        Ok(())
+    })?;
+
+    if let Some(task) = crate::sched::get_task_mut(tid) {
+        if signal != 0 {
+            let _ = crate::signal::send_signal(task, signal);
+        }
+    }
+    crate::sched::wake_task(tid);
+    Ok(())
+}
+
+/// Kill the tracee `tid` (`PTRACE_KILL`)
+///
+/// # Errors
+/// * ProcessNotFound - `tid` is not traced by the caller
+pub fn kill(tid: TaskId) -> ProcessResult<()> {
+    let tracer = crate::sched::current_task()
+        .map(|task| task.pid)
+        .ok_or(ProcessError::ProcessNotFound)?;
+    let task = crate::sched::get_task_mut(tid)
+        .filter(|task| {
+            task.ptrace
+                .as_ref()
+                .is_some_and(|state| state.tracer == tracer)
+        })
+        .ok_or(ProcessError::ProcessNotFound)?;
+    let _ = crate::signal::send_signal(task, signals::SIGKILL);
+    Ok(())
+}
+
+/// Set the `PTRACE_O_*` options of the stopped tracee `tid`
+///
+/// # Errors
+/// * ProcessNotFound - `tid` is not a stopped tracee of the caller
+/// * InvalidArgument - Unsupported option
+pub fn set_options(tid: TaskId, options: u64) -> ProcessResult<()> {
+    if options & !PTRACE_O_TRACESYSGOOD != 0 {
+        return Err(ProcessError::InvalidArgument);
+    }
+    with_tracee(tid, |task| {
+        state_of(task).options = options;
+        Ok(())
+    })
+}
+
+/// Registers of the stopped tracee `tid` (`PTRACE_GETREGS`)
+///
+/// # Errors
+/// * ProcessNotFound - `tid` is not a stopped tracee of the caller
+pub fn get_regs(tid: TaskId) -> ProcessResult<UserRegs> {
+    with_tracee(tid, |task| {
+        let fs_base = task.context.fs_base;
+        let state = state_of(task);
+        let orig_rax = state.syscall.unwrap_or(u64::MAX);
+        Ok(UserRegs::new(unsafe { &*state.regs }, orig_rax, fs_base))
+    })
+}
+
+/// Set the registers of the stopped tracee `tid` (`PTRACE_SETREGS`)
+///
+/// Changing ORIG_RAX at a syscall-entry-stop changes the syscall that
+/// runs.
+///
+/// # Errors
+/// * ProcessNotFound - `tid` is not a stopped tracee of the caller
+/// * InvalidArgument - An address is outside user space
+pub fn set_regs(tid: TaskId, regs: &UserRegs) -> ProcessResult<()> {
+    with_tracee(tid, |task| {
+        let state = state_of(task);
+        regs.apply(unsafe { &mut *state.regs })
+            .map_err(|_| ProcessError::InvalidArgument)?;
+        if state.syscall.is_some() {
+            state.syscall = Some(regs.orig_rax);
+        }
+        task.context.fs_base = regs.fs_base;
+        Ok(())
+    })
+}
+
+/// Read a word of the stopped tracee `tid` (`PTRACE_PEEKDATA`)
+///
+/// # Errors
+/// * ProcessNotFound - `tid` is not a stopped tracee of the caller
+/// * InvalidUserAddress - `addr` is not mapped in the tracee
+pub fn peek(tid: TaskId, addr: usize) -> ProcessResult<u64> {
+    with_tracee(tid, |task| {
+        let mut word = [0u8; 8];
+        copy_from_address_space(task.address_space(), &mut word, addr)?;
+        Ok(u64::from_ne_bytes(word))
+    })
+}
+
+/// Write a word of the stopped tracee `tid` (`PTRACE_POKEDATA`)
+///
+/// Read-only pages, such as code, are written too. The tracee gets its own
+/// copy of such a page first, as it may be shared with other processes
+/// running the same program, which should not see a breakpoint.
+///
+/// # Errors
+/// * ProcessNotFound - `tid` is not a stopped tracee of the caller
+/// * InvalidUserAddress - `addr` is not mapped in the tracee
+/// * OutOfMemory - No memory for the copy of a page
+pub fn poke(tid: TaskId, addr: usize, value: u64) -> ProcessResult<()> {
+    with_tracee(tid, |task| {
+        let pml4 = task.address_space();
+        let bytes = value.to_ne_bytes();
+        match copy_to_address_space(pml4, addr, &bytes) {
+            Err(ProcessError::PermissionDenied) => {}
+            result => return result,
+        }
+
+        // The word may straddle two pages
+        let state = state_of(task);
+        for (offset, byte) in bytes.iter().enumerate() {
+            let addr = addr + offset;
+            let page = private_page(state, pml4, addr & !0xfff)?;
+            unsafe { *(crate::mm::phys_to_virt(page + (addr & 0xfff)) as *mut u8) = *byte };
+        }
+        Ok(())
+    })
+}
+
+/// Frame of a user page that only the tracee's address space maps
+///
+/// Read-only pages are copied to a new frame, mapped with the same flags,
+/// the first time they are written; copy-on-write pages are split.
+fn private_page(
+    state: &mut TraceState,
+    pml4: crate::mm::PhysAddr,
+    page: usize,
+) -> ProcessResult<crate::mm::PhysAddr> {
+    use crate::mm::paging::{PageMapper, PageTableFlags};
+
+    let mut mapper = PageMapper::for_address_space(pml4);
+    let flags = mapper
+        .get_page_flags(page)
+        .ok_or(ProcessError::InvalidUserAddress)?;
+    if flags.bits() & PageTableFlags::USER.bits() == 0 {
+        return Err(ProcessError::InvalidUserAddress);
+    }
+    if flags.bits() & PageTableFlags::COW.bits() != 0 {
+        crate::arch::x86_64::fault::handle_cow_fault_in(pml4, page as u64)
+            .map_err(|_| ProcessError::OutOfMemory)?;
+    }
+    let old = mapper
+        .translate(page)
+        .ok_or(ProcessError::InvalidUserAddress)?;
+    if flags.bits() & (PageTableFlags::WRITABLE.bits() | PageTableFlags::COW.bits()) != 0
+        || state.copied.contains(&(pml4, page))
+    {
+        return Ok(old);
+    }
+
+    let mut pmm_guard = crate::mm::pmm::get_global_pmm();
+    let pmm = pmm_guard.as_mut().ok_or(ProcessError::OutOfMemory)?;
+    let new = pmm.alloc_frame().ok_or(ProcessError::OutOfMemory)?;
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            crate::mm::phys_to_virt(old) as *const u8,
+            crate::mm::phys_to_virt(new) as *mut u8,
+            4096,
+        );
+    }
+    mapper
+        .unmap_page(page)
+        .and_then(|_| mapper.map_page(page, new, flags, pmm))
+        .map_err(|_| ProcessError::OutOfMemory)?;
+    state.copied.push((pml4, page));
+    Ok(new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_regs_layout() {
+        // `user_regs_struct` is 27 words, with RIP after ORIG_RAX
+        assert_eq!(core::mem::size_of::<UserRegs>(), 27 * 8);
+        assert_eq!(core::mem::offset_of!(UserRegs, orig_rax), 15 * 8);
+        assert_eq!(core::mem::offset_of!(UserRegs, fs_base), 21 * 8);
+    }
+
+    #[test]
+    fn test_set_regs_keeps_privileged_state() {
+        let mut frame: SyscallFrame = unsafe { core::mem::zeroed() };
+        frame.cs = 0x23;
+        frame.rflags = 0x202;
+
+        let mut regs = UserRegs::new(&frame, u64::MAX, 0);
+        regs.rip = 0x40_1000;
+        regs.cs = 0x08;
+        regs.eflags = 0x3000 | RFLAGS_TF | 1; // IOPL 3, TF, CF
+        regs.apply(&mut frame).unwrap();
+        assert_eq!(frame.rip, 0x40_1000);
+        assert_eq!(frame.cs, 0x23);
+        assert_eq!(frame.rflags, 0x202 | RFLAGS_TF | 1);
+
+        regs.rip = 0xffff_8000_0000_0000;
+        assert!(regs.apply(&mut frame).is_err());
+    }
+
+    #[test]
+    fn test_may_attach() {
+        let alice = Credentials::user(1000, 1000);
+        assert!(may_attach(&alice, &alice, true));
+        assert!(!may_attach(&alice, &Credentials::user(1001, 1001), true));
+        assert!(!may_attach(&alice, &alice, false));
+
+        // A setuid-root program that ran seteuid(getuid()) keeps its saved
+        // ID, so its invoker still may not attach
+        let dropped = Credentials { suid: 0, ..alice };
+        assert!(!may_attach(&alice, &dropped, true));
+
+        let root = Credentials::user(0, 0);
+        assert!(may_attach(&root, &dropped, false));
+    }
+}
@@ -0,0 +1,403 @@
+//! Resource limits
+//!
+//! Every process has a soft and a hard limit for each resource, like Linux
+//! `getrlimit`/`setrlimit`. The kernel enforces the soft limit. A process
+//! may move its soft limit anywhere up to the hard limit and may lower the
+//! hard limit, but only `CAP_SYS_RESOURCE` raises a hard limit. Resource
+//! numbers and `struct rlimit` follow Linux. Limits are inherited by `fork`
+//! and kept across `exec`.
+//!
+//! | Resource | Enforced in |
+//! |----------|-------------|
+//! | `RLIMIT_CPU` | the scheduler tick: SIGXCPU every second past the soft limit, SIGKILL at the hard limit |
+//! | `RLIMIT_FSIZE` | `write` to a file: cut short at the limit, EFBIG and SIGXFSZ beyond it |
+//! | `RLIMIT_DATA` | private writable `mmap`s and the data and BSS segments `exec` loads |
+//! | `RLIMIT_STACK` | the stack `exec` sets up and growth of `MAP_GROWSDOWN` stacks |
+//! | `RLIMIT_CORE` | core dumps (`user::coredump`) |
+//! | `RLIMIT_NPROC` | `fork`, counting the processes of the caller's real user |
+//! | `RLIMIT_NOFILE` | the file descriptor table: new descriptors stay below it |
+//! | `RLIMIT_AS` | `mmap`, stack growth and the segments `exec` loads |
+//!
+//! The other Linux resources can be set and read but are not enforced.
+
+use super::capability::Capabilities;
+use super::process::{ProcessError, ProcessResult};
+use crate::config::{CORE_SIZE_LIMIT_DEFAULT, NOFILE_LIMIT_DEFAULT, NR_OPEN, STACK_LIMIT_DEFAULT};
+use crate::sched::task::{Credentials, Task};
+use crate::signal::signals;
+
+/// CPU time in seconds
+pub const RLIMIT_CPU: usize = 0;
+/// Largest file the process may write, in bytes
+pub const RLIMIT_FSIZE: usize = 1;
+/// Data segments and private writable mappings, in bytes
+pub const RLIMIT_DATA: usize = 2;
+/// Size of a growing stack, in bytes
+pub const RLIMIT_STACK: usize = 3;
+/// Size of a core dump, in bytes
+pub const RLIMIT_CORE: usize = 4;
+/// Processes of the real user
+pub const RLIMIT_NPROC: usize = 6;
+/// One more than the highest file descriptor number
+pub const RLIMIT_NOFILE: usize = 7;
+/// Address space, in bytes
+pub const RLIMIT_AS: usize = 9;
+/// Number of resource kinds
+pub const RLIM_NLIMITS: usize = 16;
+
+/// No limit
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+/// A soft and a hard limit (Linux `struct rlimit`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RLimit {
+    /// Soft limit, the one enforced
+    pub rlim_cur: u64,
+    /// Hard limit, the ceiling for the soft limit
+    pub rlim_max: u64,
+}
+
+impl RLimit {
+    /// No soft or hard limit
+    pub const UNLIMITED: Self = Self::new(RLIM_INFINITY, RLIM_INFINITY);
+
+    pub const fn new(rlim_cur: u64, rlim_max: u64) -> Self {
+        Self { rlim_cur, rlim_max }
+    }
+}
+
+/// The limits of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    limits: [RLimit; RLIM_NLIMITS],
+}
+
+impl ResourceLimits {
+    /// Limits of the first process, which every other one inherits
+    pub const fn new() -> Self {
+        let mut limits = [RLimit::UNLIMITED; RLIM_NLIMITS];
+        limits[RLIMIT_STACK] = RLimit::new(STACK_LIMIT_DEFAULT, RLIM_INFINITY);
+        limits[RLIMIT_CORE] = RLimit::new(CORE_SIZE_LIMIT_DEFAULT, RLIM_INFINITY);
+        limits[RLIMIT_NOFILE] = RLimit::new(NOFILE_LIMIT_DEFAULT as u64, NR_OPEN as u64);
+        Self { limits }
+    }
+
+    /// Both limits of `resource` (which must be below `RLIM_NLIMITS`)
+    pub fn get(&self, resource: usize) -> RLimit {
+        self.limits[resource]
+    }
+
+    /// The enforced limit of `resource`
+    pub fn soft(&self, resource: usize) -> u64 {
+        self.limits[resource].rlim_cur
+    }
+
+    /// These limits with `resource` set to `new`
+    ///
+    /// # Arguments
+    /// * `resource` - `RLIMIT_*` number
+    /// * `new` - New soft and hard limits
+    /// * `privileged` - The caller has `CAP_SYS_RESOURCE`
+    ///
+    /// # Returns
+    /// The new limits, InvalidArgument for an unknown resource or a soft
+    /// limit over the hard one, or PermissionDenied for raising the hard
+    /// limit without privilege or `RLIMIT_NOFILE` past `NR_OPEN`
+    pub fn set(&self, resource: usize, new: RLimit, privileged: bool) -> ProcessResult<Self> {
+        if resource >= RLIM_NLIMITS || new.rlim_cur > new.rlim_max {
+            return Err(ProcessError::InvalidArgument);
+        }
+        if new.rlim_max > self.limits[resource].rlim_max && !privileged {
+            return Err(ProcessError::PermissionDenied);
+        }
+        if resource == RLIMIT_NOFILE && new.rlim_max > NR_OPEN as u64 {
+            return Err(ProcessError::PermissionDenied);
+        }
+        let mut limits = *self;
+        limits.limits[resource] = new;
+        Ok(limits)
+    }
+}
+
+/// Memory a process would use after growing, as the memory limits count it
+///
+/// A caller fills in only what it grows, leaving the rest 0: lowering a
+/// limit below what a process already uses does not stop it from growing
+/// something else.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Whole address space (`RLIMIT_AS`)
+    pub address_space: usize,
+    /// Data, BSS and heap segments and private writable mappings
+    /// (`RLIMIT_DATA`)
+    pub data: usize,
+    /// The stack being grown (`RLIMIT_STACK`)
+    pub stack: usize,
+}
+
+impl ResourceLimits {
+    /// The first of `RLIMIT_AS`, `RLIMIT_DATA` and `RLIMIT_STACK` that
+    /// `usage` is over, if any
+    pub fn memory_over_limit(&self, usage: &MemoryUsage) -> Option<usize> {
+        [
+            (RLIMIT_AS, usage.address_space),
+            (RLIMIT_DATA, usage.data),
+            (RLIMIT_STACK, usage.stack),
+        ]
+        .into_iter()
+        .find(|&(resource, bytes)| bytes as u64 > self.soft(resource))
+        .map(|(resource, _)| resource)
+    }
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The calling task's soft limit of `resource` (no limit for kernel code)
+pub fn current(resource: usize) -> u64 {
+    crate::sched::current_task().map_or(RLIM_INFINITY, |task| task.rlimits.soft(resource))
+}
+
+/// Read and optionally change the limits of a process, like Linux `prlimit`
+///
+/// Changing another process needs `CAP_SYS_RESOURCE` or a caller whose real
+/// user and group IDs match all of the target's IDs. New limits apply to
+/// every thread of the process.
+///
+/// # Arguments
+/// * `pid` - Target process, or 0 for the caller
+/// * `resource` - `RLIMIT_*` number
+/// * `new` - New limits, or None to only read them
+///
+/// # Returns
+/// The limits before the change
+pub fn prlimit(pid: usize, resource: usize, new: Option<RLimit>) -> ProcessResult<RLimit> {
+    if resource >= RLIM_NLIMITS {
+        return Err(ProcessError::InvalidArgument);
+    }
+    let caller = crate::sched::current_task().ok_or(ProcessError::ProcessNotFound)?;
+    let target = if pid == 0 || pid == caller.pid {
+        crate::sched::current_task_mut().ok_or(ProcessError::ProcessNotFound)?
+    } else {
+        let task = crate::sched::get_task_mut(pid).ok_or(ProcessError::ProcessNotFound)?;
+        if task.creds.is_kernel_thread {
+            return Err(ProcessError::ProcessNotFound);
+        }
+        if !may_change(&caller.creds, &task.creds) {
+            return Err(ProcessError::PermissionDenied);
+        }
+        task
+    };
+
+    let old = target.rlimits.get(resource);
+    let new = match new {
+        Some(new) => new,
+        None => return Ok(old),
+    };
+    let privileged = caller.creds.capable(Capabilities::CAP_SYS_RESOURCE);
+    let limits = target.rlimits.set(resource, new, privileged)?;
+    match target.thread_group.clone() {
+        Some(group) => {
+            for id in group.members() {
+                if let Some(member) = crate::sched::get_task_mut(id) {
+                    member.rlimits = limits;
+                }
+            }
+        }
+        None => target.rlimits = limits,
+    }
+    Ok(old)
+}
+
+/// Whether `caller` may read and change the limits of a process running
+/// with `target`
+fn may_change(caller: &Credentials, target: &Credentials) -> bool {
+    caller.capable(Capabilities::CAP_SYS_RESOURCE) || super::creds::same_user(caller, target)
+}
+
+/// Whether `parent` may create another process under `RLIMIT_NPROC`
+///
+/// Processes are counted per real user ID. `CAP_SYS_RESOURCE` and
+/// `CAP_SYS_ADMIN` are exempt.
+pub fn may_fork(parent: &Task) -> bool {
+    let limit = parent.rlimits.soft(RLIMIT_NPROC);
+    if limit == RLIM_INFINITY
+        || parent.creds.capable(Capabilities::CAP_SYS_RESOURCE)
+        || parent.creds.capable(Capabilities::CAP_SYS_ADMIN)
+    {
+        return true;
+    }
+
+    let uid = parent.creds.ruid;
+    let mut processes = 0u64;
+    crate::sched::for_each_task(|task| {
+        if task.id == task.pid && !task.creds.is_kernel_thread && task.creds.ruid == uid {
+            processes += 1;
+        }
+    });
+    processes < limit
+}
+
+/// Enforce `RLIMIT_CPU` on the running task at a tick
+///
+/// Past the soft limit the task gets SIGXCPU once per second of CPU time
+/// (it is charged at most a tick's worth between two ticks); at the hard
+/// limit it gets SIGKILL.
+pub fn charge_tick(task: &mut Task) {
+    let limit = task.rlimits.get(RLIMIT_CPU);
+    if limit.rlim_cur == RLIM_INFINITY || task.creds.is_kernel_thread {
+        return;
+    }
+
+    let used = task.rusage.utime_ns + task.rusage.stime_ns;
+    let soft = limit.rlim_cur.saturating_mul(NS_PER_SEC);
+    let hard = limit.rlim_max.saturating_mul(NS_PER_SEC);
+    if used >= hard {
+        let _ = crate::signal::send_signal(task, signals::SIGKILL);
+    } else if used >= soft && (used - soft) % NS_PER_SEC < crate::sched::fair::TICK_NS {
+        let _ = crate::signal::send_signal(task, signals::SIGXCPU);
+    }
+}
+
+/// How many of `len` bytes may be written at `offset` under `RLIMIT_FSIZE`
+///
+/// Returns None, after sending the caller SIGXFSZ, when the write starts at
+/// or past the limit; it then fails with EFBIG.
+pub fn file_write_allowance(offset: u64, len: usize) -> Option<usize> {
+    let limit = current(RLIMIT_FSIZE);
+    if offset < limit {
+        return Some(len.min((limit - offset).min(usize::MAX as u64) as usize));
+    }
+    if let Some(task) = crate::sched::current_task_mut() {
+        let _ = crate::signal::send_signal(task, signals::SIGXFSZ);
+    }
+    None
+}
+
+/// One more than the highest file descriptor the caller may open: its
+/// `RLIMIT_NOFILE`, at most `NR_OPEN`
+pub fn fd_limit() -> usize {
+    current(RLIMIT_NOFILE).min(NR_OPEN as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let limits = ResourceLimits::new();
+        assert_eq!(limits.soft(RLIMIT_NOFILE), NOFILE_LIMIT_DEFAULT as u64);
+        assert_eq!(limits.get(RLIMIT_NOFILE).rlim_max, NR_OPEN as u64);
+        assert_eq!(limits.soft(RLIMIT_CORE), CORE_SIZE_LIMIT_DEFAULT);
+        assert_eq!(limits.get(RLIMIT_CPU), RLimit::UNLIMITED);
+    }
+
+    #[test]
+    fn test_set() {
+        let limits = ResourceLimits::new();
+
+        // The soft limit moves freely under the hard one
+        let lowered = limits
+            .set(RLIMIT_NOFILE, RLimit::new(16, 64), false)
+            .unwrap();
+        assert_eq!(lowered.get(RLIMIT_NOFILE), RLimit::new(16, 64));
+        let raised = lowered
+            .set(RLIMIT_NOFILE, RLimit::new(64, 64), false)
+            .unwrap();
+        assert_eq!(raised.soft(RLIMIT_NOFILE), 64);
+
+        // Only privilege raises the hard limit, and never past NR_OPEN
+        assert_eq!(
+            lowered.set(RLIMIT_NOFILE, RLimit::new(16, 128), false),
+            Err(ProcessError::PermissionDenied)
+        );
+        assert!(lowered
+            .set(RLIMIT_NOFILE, RLimit::new(16, 128), true)
+            .is_ok());
+        assert_eq!(
+            limits.set(RLIMIT_NOFILE, RLimit::new(16, NR_OPEN as u64 + 1), true),
+            Err(ProcessError::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn test_set_invalid() {
+        let limits = ResourceLimits::new();
+        assert_eq!(
+            limits.set(RLIMIT_CPU, RLimit::new(10, 5), true),
+            Err(ProcessError::InvalidArgument)
+        );
+        assert_eq!(
+            limits.set(RLIM_NLIMITS, RLimit::UNLIMITED, true),
+            Err(ProcessError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_memory_over_limit() {
+        let limits = ResourceLimits::new()
+            .set(RLIMIT_AS, RLimit::new(1 << 20, RLIM_INFINITY), false)
+            .unwrap()
+            .set(RLIMIT_DATA, RLimit::new(1 << 16, RLIM_INFINITY), false)
+            .unwrap();
+
+        let fits = MemoryUsage {
+            address_space: 1 << 20,
+            data: 1 << 16,
+            stack: STACK_LIMIT_DEFAULT as usize,
+        };
+        assert_eq!(limits.memory_over_limit(&fits), None);
+
+        let big_space = MemoryUsage {
+            address_space: (1 << 20) + 1,
+            ..fits
+        };
+        assert_eq!(limits.memory_over_limit(&big_space), Some(RLIMIT_AS));
+
+        let big_data = MemoryUsage {
+            data: (1 << 16) + 1,
+            ..fits
+        };
+        assert_eq!(limits.memory_over_limit(&big_data), Some(RLIMIT_DATA));
+
+        let big_stack = MemoryUsage {
+            stack: STACK_LIMIT_DEFAULT as usize + 4096,
+            ..fits
+        };
+        assert_eq!(limits.memory_over_limit(&big_stack), Some(RLIMIT_STACK));
+    }
+
+    #[test]
+    fn test_memory_unlimited_by_default() {
+        let usage = MemoryUsage {
+            address_space: usize::MAX,
+            data: usize::MAX,
+            stack: 0,
+        };
+        assert_eq!(ResourceLimits::new().memory_over_limit(&usage), None);
+    }
+
+    #[test]
+    fn test_may_change() {
+        let alice = Credentials::user(1000, 1000);
+        let bob = Credentials::user(1001, 1001);
+        assert!(may_change(&alice, &alice));
+        assert!(!may_change(&alice, &bob));
+        assert!(may_change(&Credentials::user(0, 0), &bob));
+
+        // A setuid program run by alice is not hers to change
+        let setuid = Credentials {
+            uid: 1001,
+            suid: 1001,
+            ..alice
+        };
+        assert!(!may_change(&alice, &setuid));
+    }
+}
@@ -0,0 +1,474 @@
+//! Syscall Filtering
+//!
+//! A task can restrict the syscalls it may make, to run an untrusted
+//! program with a small syscall surface. A filter is a list of rules, each
+//! matching a syscall number and optionally comparing its arguments, and a
+//! default action for syscalls no rule matches. It is installed with
+//! `prctl(PR_SET_SECCOMP, SECCOMP_MODE_FILTER, prog)` and checked at the
+//! top of the syscall dispatcher.
+//!
+//! Filters can only be added, never removed: a new filter is stacked on
+//! the ones already installed, every filter in the stack is consulted and
+//! the most restrictive action wins. The stack is inherited by `fork`,
+//! `clone` and `exec`. Installing a filter needs `no_new_privs` (set with
+//! `PR_SET_NO_NEW_PRIVS`, which makes `exec` ignore set-user-ID bits) or
+//! `CAP_SYS_ADMIN`, so a filter cannot be used to trip up a privileged
+//! program.
+//!
+//! The actions follow Linux's `SECCOMP_RET_*` values, from most to least
+//! restrictive:
+//!
+//! - kill: the process dies of SIGSYS, leaving a core file;
+//! - trap: the syscall fails with ENOSYS and SIGSYS is sent, with the
+//!   syscall number, its address and the rule's data in the siginfo;
+//! - errno: the syscall fails with the rule's error number;
+//! - log: the syscall is logged and allowed;
+//! - allow.
+
+use super::capability::Capabilities;
+use super::process::{ProcessError, ProcessResult};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// `prctl(PR_SET_SECCOMP)` mode: install a filter
+pub const SECCOMP_MODE_FILTER: usize = 2;
+
+/// Kill the process
+pub const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+/// Send SIGSYS (low 16 bits: `si_errno`)
+pub const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+/// Fail with an error number (low 16 bits)
+pub const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+/// Log and allow
+pub const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+/// Allow
+pub const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+const SECCOMP_RET_ACTION: u32 = 0xffff_0000;
+const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+
+/// Argument comparisons (`SeccompArg::op`), as in libseccomp
+pub const SCMP_CMP_NE: u32 = 1;
+pub const SCMP_CMP_LT: u32 = 2;
+pub const SCMP_CMP_LE: u32 = 3;
+pub const SCMP_CMP_EQ: u32 = 4;
+pub const SCMP_CMP_GE: u32 = 5;
+pub const SCMP_CMP_GT: u32 = 6;
+/// `(arg & mask) == value`
+pub const SCMP_CMP_MASKED_EQ: u32 = 7;
+
+/// Most rules in one filter
+pub const MAX_RULES: usize = 256;
+
+/// Most filters stacked on a task
+pub const MAX_FILTERS: usize = 16;
+
+/// Most argument comparisons in one rule (one per argument register)
+pub const MAX_ARG_CHECKS: usize = 6;
+
+/// What happens to a syscall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Kill,
+    Trap(u16),
+    Errno(u16),
+    Log,
+    Allow,
+}
+
+impl Action {
+    /// Decode a `SECCOMP_RET_*` value
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        let data = (raw & SECCOMP_RET_DATA) as u16;
+        match raw & SECCOMP_RET_ACTION {
+            SECCOMP_RET_KILL_PROCESS => Some(Self::Kill),
+            SECCOMP_RET_TRAP => Some(Self::Trap(data)),
+            SECCOMP_RET_ERRNO => Some(Self::Errno(data)),
+            SECCOMP_RET_LOG => Some(Self::Log),
+            SECCOMP_RET_ALLOW => Some(Self::Allow),
+            _ => None,
+        }
+    }
+
+    /// Rank of the action; lower wins when filters disagree
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Kill => 0,
+            Self::Trap(_) => 1,
+            Self::Errno(_) => 2,
+            Self::Log => 3,
+            Self::Allow => 4,
+        }
+    }
+}
+
+/// Comparison of a syscall argument with a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Ne,
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+    MaskedEq(u64),
+}
+
+/// Check of one syscall argument (unsigned 64-bit comparison)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgCheck {
+    /// Argument index (0-5)
+    pub arg: usize,
+    pub op: CmpOp,
+    pub value: u64,
+}
+
+impl ArgCheck {
+    fn matches(&self, args: &[u64; 6]) -> bool {
+        let arg = args[self.arg];
+        match self.op {
+            CmpOp::Ne => arg != self.value,
+            CmpOp::Lt => arg < self.value,
+            CmpOp::Le => arg <= self.value,
+            CmpOp::Eq => arg == self.value,
+            CmpOp::Ge => arg >= self.value,
+            CmpOp::Gt => arg > self.value,
+            CmpOp::MaskedEq(mask) => arg & mask == self.value,
+        }
+    }
+}
+
+/// Action for a syscall whose arguments pass all checks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub syscall: usize,
+    pub checks: Vec<ArgCheck>,
+    pub action: Action,
+}
+
+impl Rule {
+    fn matches(&self, syscall: usize, args: &[u64; 6]) -> bool {
+        self.syscall == syscall && self.checks.iter().all(|check| check.matches(args))
+    }
+}
+
+/// One installed filter, linked to the filters installed before it
+#[derive(Debug)]
+pub struct Filter {
+    default: Action,
+    rules: Vec<Rule>,
+    prev: Option<Arc<Filter>>,
+}
+
+impl Filter {
+    /// Action of this filter alone: the first matching rule's, or the
+    /// default
+    fn own_action(&self, syscall: usize, args: &[u64; 6]) -> Action {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(syscall, args))
+            .map_or(self.default, |rule| rule.action)
+    }
+
+    /// Action of the whole stack: the most restrictive of all filters
+    pub fn action(&self, syscall: usize, args: &[u64; 6]) -> Action {
+        let mut action = self.own_action(syscall, args);
+        let mut prev = self.prev.as_deref();
+        while let Some(filter) = prev {
+            let other = filter.own_action(syscall, args);
+            if other.precedence() < action.precedence() {
+                action = other;
+            }
+            prev = filter.prev.as_deref();
+        }
+        action
+    }
+
+    /// Number of filters in the stack
+    fn depth(&self) -> usize {
+        let mut depth = 1;
+        let mut prev = self.prev.as_deref();
+        while let Some(filter) = prev {
+            depth += 1;
+            prev = filter.prev.as_deref();
+        }
+        depth
+    }
+}
+
+/// Filter program passed to `prctl(PR_SET_SECCOMP)`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SeccompProg {
+    /// `SECCOMP_RET_*` for syscalls no rule matches
+    pub default_action: u32,
+    /// Number of rules
+    pub count: u32,
+    /// User pointer to `count` `SeccompRule`s
+    pub rules: u64,
+}
+
+/// Rule of a `SeccompProg`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeccompRule {
+    /// Syscall number
+    pub nr: u32,
+    /// `SECCOMP_RET_*` when the rule matches
+    pub action: u32,
+    /// Number of valid entries in `args`
+    pub arg_count: u32,
+    pub _pad: u32,
+    pub args: [SeccompArg; MAX_ARG_CHECKS],
+}
+
+/// Argument check of a `SeccompRule`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeccompArg {
+    /// Argument index (0-5)
+    pub index: u32,
+    /// `SCMP_CMP_*`
+    pub op: u32,
+    pub value: u64,
+    /// Mask for `SCMP_CMP_MASKED_EQ`
+    pub mask: u64,
+}
+
+impl SeccompArg {
+    fn decode(&self) -> ProcessResult<ArgCheck> {
+        let op = match self.op {
+            SCMP_CMP_NE => CmpOp::Ne,
+            SCMP_CMP_LT => CmpOp::Lt,
+            SCMP_CMP_LE => CmpOp::Le,
+            SCMP_CMP_EQ => CmpOp::Eq,
+            SCMP_CMP_GE => CmpOp::Ge,
+            SCMP_CMP_GT => CmpOp::Gt,
+            SCMP_CMP_MASKED_EQ => CmpOp::MaskedEq(self.mask),
+            _ => return Err(ProcessError::InvalidArgument),
+        };
+        if self.index as usize >= MAX_ARG_CHECKS {
+            return Err(ProcessError::InvalidArgument);
+        }
+        Ok(ArgCheck {
+            arg: self.index as usize,
+            op,
+            value: self.value,
+        })
+    }
+}
+
+impl SeccompRule {
+    fn decode(&self) -> ProcessResult<Rule> {
+        let action = Action::from_raw(self.action).ok_or(ProcessError::InvalidArgument)?;
+        let count = self.arg_count as usize;
+        if count > MAX_ARG_CHECKS {
+            return Err(ProcessError::InvalidArgument);
+        }
+        let checks = self.args[..count]
+            .iter()
+            .map(SeccompArg::decode)
+            .collect::<ProcessResult<Vec<_>>>()?;
+        Ok(Rule {
+            syscall: self.nr as usize,
+            checks,
+            action,
+        })
+    }
+}
+
+/// Build the filter a program describes, on top of `prev`
+///
+/// # Errors
+/// * InvalidArgument - Unknown action or comparison, bad argument index,
+///   too many rules or filters
+pub fn build(
+    default_action: u32,
+    rules: &[SeccompRule],
+    prev: Option<Arc<Filter>>,
+) -> ProcessResult<Filter> {
+    let default = Action::from_raw(default_action).ok_or(ProcessError::InvalidArgument)?;
+    if rules.len() > MAX_RULES || prev.as_ref().map_or(0, |prev| prev.depth()) >= MAX_FILTERS {
+        return Err(ProcessError::InvalidArgument);
+    }
+    let rules = rules
+        .iter()
+        .map(SeccompRule::decode)
+        .collect::<ProcessResult<Vec<_>>>()?;
+    Ok(Filter {
+        default,
+        rules,
+        prev,
+    })
+}
+
+/// Install a filter on the calling task (`PR_SET_SECCOMP`)
+///
+/// # Errors
+/// * PermissionDenied - Neither `no_new_privs` nor `CAP_SYS_ADMIN`
+/// * InvalidArgument - See `build`
+pub fn install(default_action: u32, rules: &[SeccompRule]) -> ProcessResult<()> {
+    let task = crate::sched::current_task_mut().ok_or(ProcessError::ProcessNotFound)?;
+    if !task.no_new_privs && !task.creds.capable(Capabilities::CAP_SYS_ADMIN) {
+        return Err(ProcessError::PermissionDenied);
+    }
+    let filter = build(default_action, rules, task.seccomp.clone())?;
+    task.seccomp = Some(Arc::new(filter));
+    Ok(())
+}
+
+/// Apply the calling task's filter to a syscall
+///
+/// Called at the top of the syscall dispatcher; a task without a filter
+/// only pays for the lookup of its `seccomp` field.
+///
+/// # Returns
+/// None if the syscall may go ahead, or the result it fails with
+pub fn filter_syscall(syscall: usize, args: &[u64; 6]) -> Option<isize> {
+    use crate::signal::signals::SIGSYS;
+
+    let task = crate::sched::current_task_mut()?;
+    let action = task.seccomp.as_ref()?.action(syscall, args);
+    match action {
+        Action::Allow => return None,
+        Action::Log => {
+            crate::serial_println!(
+                "[SECCOMP] task {} syscall {} allowed by log rule",
+                task.id,
+                syscall
+            );
+            return None;
+        }
+        _ => {}
+    }
+
+    crate::metrics::metrics().inc_syscall_denied(syscall);
+    match action {
+        Action::Errno(errno) => Some(-(errno as isize)),
+        Action::Trap(data) => {
+            // The instruction after `int 0x80`
+            let addr = if task.syscall_frame.is_null() {
+                0
+            } else {
+                unsafe { (*task.syscall_frame).rip }
+            };
+            force_signal(
+                task,
+                SIGSYS,
+                crate::signal::SigSource::Syscall {
+                    nr: syscall,
+                    addr,
+                    data,
+                },
+            );
+            Some(-38) // ENOSYS
+        }
+        _ => {
+            crate::serial_println!("[SECCOMP] task {} killed by syscall {}", task.id, syscall);
+            let core_dumped = !task.syscall_frame.is_null()
+                && super::coredump::dump_core(SIGSYS, unsafe { &*task.syscall_frame });
+            super::thread::exit_group(super::process::WaitStatus::Signaled {
+                signal: SIGSYS,
+                core_dumped,
+            })
+        }
+    }
+}
+
+/// Send `signal` so that it cannot be blocked or ignored away
+fn force_signal(
+    task: &mut crate::sched::task::Task,
+    signal: crate::signal::Signal,
+    source: crate::signal::SigSource,
+) {
+    use core::sync::atomic::Ordering;
+
+    task.signal_mask
+        .fetch_and(!crate::signal::sigbit(signal), Ordering::AcqRel);
+    {
+        let mut handlers = task.signal_handlers.lock();
+        if handlers[signal as usize].handler == crate::signal::SigHandler::Ignore {
+            handlers[signal as usize] = crate::signal::SigAction::default();
+        }
+    }
+    let _ = crate::signal::send_signal_info(task, signal, source);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYS_WRITE: u32 = 0;
+    const SYS_OPEN: u32 = 10;
+
+    fn rule(nr: u32, action: u32, args: &[SeccompArg]) -> SeccompRule {
+        let mut rule = SeccompRule {
+            nr,
+            action,
+            arg_count: args.len() as u32,
+            ..SeccompRule::default()
+        };
+        rule.args[..args.len()].copy_from_slice(args);
+        rule
+    }
+
+    #[test]
+    fn test_rules_and_default() {
+        // Allow writes to stdout and stderr only, refuse everything else
+        let rules = [rule(
+            SYS_WRITE,
+            SECCOMP_RET_ALLOW,
+            &[SeccompArg {
+                index: 0,
+                op: SCMP_CMP_LE,
+                value: 2,
+                mask: 0,
+            }],
+        )];
+        let filter = build(SECCOMP_RET_ERRNO | 1, &rules, None).unwrap();
+        assert_eq!(filter.action(0, &[1, 0, 0, 0, 0, 0]), Action::Allow);
+        assert_eq!(filter.action(0, &[3, 0, 0, 0, 0, 0]), Action::Errno(1));
+        assert_eq!(filter.action(10, &[0; 6]), Action::Errno(1));
+    }
+
+    #[test]
+    fn test_masked_eq() {
+        // open without O_WRONLY or O_RDWR
+        let check = SeccompArg {
+            index: 1,
+            op: SCMP_CMP_MASKED_EQ,
+            value: 0,
+            mask: 0x3,
+        };
+        let rules = [rule(SYS_OPEN, SECCOMP_RET_ALLOW, &[check])];
+        let filter = build(SECCOMP_RET_TRAP | 7, &rules, None).unwrap();
+        assert_eq!(filter.action(10, &[0, 0x40, 0, 0, 0, 0]), Action::Allow);
+        assert_eq!(filter.action(10, &[0, 0x1, 0, 0, 0, 0]), Action::Trap(7));
+    }
+
+    #[test]
+    fn test_stacked_filters() {
+        let first = build(
+            SECCOMP_RET_ALLOW,
+            &[rule(SYS_OPEN, SECCOMP_RET_KILL_PROCESS, &[])],
+            None,
+        );
+        let first = Arc::new(first.unwrap());
+        // A later, looser filter cannot undo an earlier one
+        let second = build(SECCOMP_RET_LOG, &[], Some(first)).unwrap();
+        assert_eq!(second.action(10, &[0; 6]), Action::Kill);
+        assert_eq!(second.action(0, &[0; 6]), Action::Log);
+    }
+
+    #[test]
+    fn test_invalid_programs() {
+        assert!(build(0x1234_0000, &[], None).is_err());
+        let mut bad = rule(SYS_WRITE, SECCOMP_RET_ALLOW, &[SeccompArg::default()]);
+        assert!(build(SECCOMP_RET_ALLOW, &[bad], None).is_err());
+        bad.args[0].op = SCMP_CMP_EQ;
+        bad.args[0].index = 6;
+        assert!(build(SECCOMP_RET_ALLOW, &[bad], None).is_err());
+        bad.arg_count = 7;
+        assert!(build(SECCOMP_RET_ALLOW, &[bad], None).is_err());
+    }
+}
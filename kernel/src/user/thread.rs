@@ -0,0 +1,510 @@
+//! User Threads
+//!
+//! `clone` creates a task that returns to user mode next to its creator,
+//! sharing as much of the creator's state as the flags ask for:
+//!
+//! - `CLONE_VM`: the address space (otherwise it is copied-on-write, as
+//!   with fork)
+//! - `CLONE_FILES`: the file descriptor table
+//! - `CLONE_SIGHAND`: the signal handler table (requires `CLONE_VM`)
+//! - `CLONE_THREAD`: the thread group, i.e. the PID (requires
+//!   `CLONE_SIGHAND`)
+//!
+//! A thread group is what user space calls a process: `getpid` returns its
+//! ID (the ID of its first task) and `gettid` the task's own. The group
+//! lives as long as one of its threads does. `exit_group` ends every
+//! thread: the others are woken and leave at their next return from a
+//! syscall.
+
+use crate::sched::process_group::Pid;
+use crate::sched::task::{SchedulerError, Task, TaskId, TaskState};
+use crate::sync::SpinLock;
+use crate::sys::syscall::FdTable;
+use crate::user::process::WaitStatus;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// Share the address space
+pub const CLONE_VM: usize = 0x0000_0100;
+
+/// Share the file descriptor table
+pub const CLONE_FILES: usize = 0x0000_0400;
+
+/// Share the signal handler table
+pub const CLONE_SIGHAND: usize = 0x0000_0800;
+
+/// Join the creator's thread group
+pub const CLONE_THREAD: usize = 0x0001_0000;
+
+/// Set the child's TLS pointer (FS base) to the `tls` argument
+pub const CLONE_SETTLS: usize = 0x0008_0000;
+
+/// Store the child's thread ID at `parent_tid` in the creator's memory
+pub const CLONE_PARENT_SETTID: usize = 0x0010_0000;
+
+/// Zero `child_tid` and wake a futex on it when the child exits
+pub const CLONE_CHILD_CLEARTID: usize = 0x0020_0000;
+
+/// Store the child's thread ID at `child_tid` in the child's memory
+pub const CLONE_CHILD_SETTID: usize = 0x0100_0000;
+
+/// Flags `clone` understands (the low byte is the exit signal)
+const CLONE_SUPPORTED: usize = 0xFF
+    | CLONE_VM
+    | CLONE_FILES
+    | CLONE_SIGHAND
+    | CLONE_THREAD
+    | CLONE_SETTLS
+    | CLONE_PARENT_SETTID
+    | CLONE_CHILD_CLEARTID
+    | CLONE_CHILD_SETTID;
+
+/// `clone` errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloneError {
+    /// Unknown or inconsistent flags (EINVAL)
+    InvalidFlags,
+    /// Not called from a syscall of a user task
+    NoUserContext,
+    /// The address space could not be copied (ENOMEM)
+    OutOfMemory,
+    /// The caller's user has reached `RLIMIT_NPROC` (EAGAIN)
+    ProcessLimit,
+    /// The task could not be created
+    Sched(SchedulerError),
+}
+
+/// Members of a thread group
+struct GroupState {
+    /// Live tasks in the group, in creation order
+    members: Vec<TaskId>,
+    /// Exited tasks other than the leader, freed with the process
+    exited: Vec<TaskId>,
+    /// Set by `exit_group`; the remaining threads exit with it
+    exit_status: Option<WaitStatus>,
+}
+
+/// Tasks sharing a PID
+pub struct ThreadGroup {
+    /// Thread-group ID (the PID)
+    tgid: Pid,
+    state: SpinLock<GroupState>,
+}
+
+impl core::fmt::Debug for ThreadGroup {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ThreadGroup")
+            .field("tgid", &self.tgid)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ThreadGroup {
+    /// Create a group whose only member is `leader`
+    fn new(tgid: Pid, leader: TaskId) -> Self {
+        let mut members = Vec::new();
+        members.push(leader);
+        Self {
+            tgid,
+            state: SpinLock::new(GroupState {
+                members,
+                exited: Vec::new(),
+                exit_status: None,
+            }),
+        }
+    }
+
+    /// Status passed to `exit_group`, once the group is exiting
+    pub fn exit_status(&self) -> Option<WaitStatus> {
+        self.state.lock().exit_status
+    }
+
+    /// Live tasks in the group
+    pub fn members(&self) -> Vec<TaskId> {
+        self.state.lock().members.clone()
+    }
+
+    /// Exited threads (other than the leader) not freed yet
+    pub(crate) fn exited_threads(&self) -> Vec<TaskId> {
+        self.state.lock().exited.clone()
+    }
+
+    /// Take the exited threads (other than the leader) to free them
+    pub(crate) fn take_exited(&self) -> Vec<TaskId> {
+        core::mem::take(&mut self.state.lock().exited)
+    }
+
+    fn join(&self, id: TaskId) {
+        self.state.lock().members.push(id);
+    }
+
+    /// Remove an exiting thread
+    ///
+    /// # Returns
+    /// true if it was the last one
+    fn leave(&self, id: TaskId) -> bool {
+        let mut state = self.state.lock();
+        state.members.retain(|&member| member != id);
+        if id != self.tgid {
+            state.exited.push(id);
+        }
+        state.members.is_empty()
+    }
+}
+
+/// Check that a set of `clone` flags is consistent
+///
+/// Sharing signal handlers only makes sense with a shared address space
+/// (handlers are user addresses), and threads of one group must share
+/// their handlers.
+pub fn validate_clone_flags(flags: usize) -> Result<(), CloneError> {
+    if flags & !CLONE_SUPPORTED != 0 {
+        return Err(CloneError::InvalidFlags);
+    }
+    if flags & CLONE_THREAD != 0 && flags & CLONE_SIGHAND == 0 {
+        return Err(CloneError::InvalidFlags);
+    }
+    if flags & CLONE_SIGHAND != 0 && flags & CLONE_VM == 0 {
+        return Err(CloneError::InvalidFlags);
+    }
+    Ok(())
+}
+
+/// The child's file descriptor table: the creator's own with
+/// `CLONE_FILES`, otherwise a copy whose descriptors close independently
+fn copy_files(files: &Arc<SpinLock<FdTable>>, flags: usize) -> Arc<SpinLock<FdTable>> {
+    if flags & CLONE_FILES != 0 {
+        files.clone()
+    } else {
+        Arc::new(SpinLock::new(files.lock().clone_for_fork()))
+    }
+}
+
+/// Kernel entry point of cloned tasks (never runs: `set_user_entry`
+/// replaces it before the task is first scheduled)
+fn clone_entry() -> ! {
+    panic!("[THREAD] Cloned task entered its kernel entry point");
+}
+
+/// Copy the current address space copy-on-write
+fn fork_address_space(parent_cr3: crate::mm::PhysAddr) -> Result<crate::mm::PhysAddr, CloneError> {
+    use crate::mm::paging::{clone_page_table_hierarchy, mark_user_pages_cow};
+
+    let child_cr3 = {
+        let mut pmm_guard = crate::mm::pmm::get_global_pmm();
+        let pmm = pmm_guard.as_mut().ok_or(CloneError::OutOfMemory)?;
+        clone_page_table_hierarchy(parent_cr3, pmm).map_err(|_| CloneError::OutOfMemory)?
+    };
+
+    // Failing to share pages only costs memory; both copies stay valid
+    let _ = mark_user_pages_cow(parent_cr3);
+    let _ = mark_user_pages_cow(child_cr3);
+    Ok(child_cr3)
+}
+
+/// Create a task from the current syscall, like Linux `clone`
+///
+/// The child returns to user mode at the same instruction as the creator,
+/// with 0 in RAX.
+///
+/// # Arguments
+/// * `flags` - `CLONE_*` flags
+/// * `stack` - Child's user stack pointer (0 = same as the creator's)
+/// * `parent_tid` - Address for `CLONE_PARENT_SETTID`
+/// * `child_tid` - Address for `CLONE_CHILD_SETTID` / `CLONE_CHILD_CLEARTID`
+/// * `tls` - FS base for `CLONE_SETTLS`
+///
+/// # Returns
+/// The child's task ID
+pub fn clone(
+    flags: usize,
+    stack: usize,
+    parent_tid: usize,
+    child_tid: usize,
+    tls: usize,
+) -> Result<TaskId, CloneError> {
+    validate_clone_flags(flags)?;
+
+    let parent = crate::sched::current_task_mut().ok_or(CloneError::NoUserContext)?;
+    if parent.syscall_frame.is_null() || parent.creds.is_kernel_thread {
+        return Err(CloneError::NoUserContext);
+    }
+    if flags & CLONE_THREAD == 0 && !crate::user::rlimit::may_fork(parent) {
+        return Err(CloneError::ProcessLimit);
+    }
+
+    let mut frame = unsafe { *parent.syscall_frame };
+    frame.rax = 0;
+    if stack != 0 {
+        frame.rsp = stack as u64;
+    }
+
+    let parent_cr3 = crate::user::process::current_address_space();
+    let child_cr3 = if flags & CLONE_VM != 0 {
+        parent_cr3
+    } else {
+        fork_address_space(parent_cr3)?
+    };
+
+    let group = if flags & CLONE_THREAD != 0 {
+        let (pid, id) = (parent.pid, parent.id);
+        Some(
+            parent
+                .thread_group
+                .get_or_insert_with(|| Arc::new(ThreadGroup::new(pid, id)))
+                .clone(),
+        )
+    } else {
+        None
+    };
+
+    let parent: &Task = parent;
+    let spawned = crate::sched::spawn_task_in(
+        parent.name,
+        clone_entry,
+        parent.priority,
+        crate::config::CLONE_STACK_SIZE,
+        |task| {
+            task.context.cr3 = child_cr3 as u64;
+            task.context.fs_base = if flags & CLONE_SETTLS != 0 {
+                tls as u64
+            } else {
+                parent.context.fs_base
+            };
+
+            task.memory_regions = parent.memory_regions.clone();
+            task.region_count = parent.region_count;
+            task.heap_start = parent.heap_start;
+            task.heap_end = parent.heap_end;
+            task.creds = parent.creds;
+            task.seccomp = parent.seccomp.clone();
+            task.no_new_privs = parent.no_new_privs;
+            task.dumpable = parent.dumpable;
+            task.rlimits = parent.rlimits;
+            task.image = parent.image.clone();
+            task.tty = parent.tty;
+            task.pgid = parent.pgid;
+            task.sid = parent.sid;
+            task.signal_mask.store(
+                parent
+                    .signal_mask
+                    .load(core::sync::atomic::Ordering::Acquire),
+                core::sync::atomic::Ordering::Release,
+            );
+            // The child starts with the parent's FPU state, still live on
+            // this CPU
+            task.fpu.save();
+            // A thread sharing the address space must not share the stack too
+            if flags & CLONE_VM == 0 {
+                task.alt_stack = parent.alt_stack;
+            }
+
+            task.fd_table = copy_files(&parent.fd_table, flags);
+            task.signal_handlers = if flags & CLONE_SIGHAND != 0 {
+                parent.signal_handlers.clone()
+            } else {
+                Arc::new(crate::sync::IrqSpinLock::new(
+                    *parent.signal_handlers.lock(),
+                ))
+            };
+
+            match &group {
+                Some(group) => {
+                    task.pid = parent.pid;
+                    task.ppid = parent.ppid;
+                    group.join(task.id);
+                    task.thread_group = Some(group.clone());
+                }
+                None => {
+                    task.pid = task.id;
+                    task.ppid = parent.pid;
+                }
+            }
+
+            if flags & CLONE_CHILD_SETTID != 0 {
+                let tid = (task.id as u32).to_ne_bytes();
+                let _ = crate::user::process::copy_to_address_space(child_cr3, child_tid, &tid);
+            }
+            if flags & CLONE_CHILD_CLEARTID != 0 {
+                task.clear_child_tid = child_tid;
+            }
+
+            task.set_user_entry(&frame);
+        },
+    );
+
+    let child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            if child_cr3 != parent_cr3 {
+                let mut pmm_guard = crate::mm::pmm::get_global_pmm();
+                if let Some(pmm) = pmm_guard.as_mut() {
+                    crate::mm::paging::free_page_table_hierarchy(child_cr3, pmm);
+                }
+            }
+            return Err(CloneError::Sched(e));
+        }
+    };
+
+    if group.is_none() {
+        if let Some(parent) = crate::sched::current_task_mut() {
+            parent.children.push(child);
+        }
+        // Failing leaves the child's mapped pages usable, only unmapped
+        // parts of its mappings fault
+        let _ = crate::mm::mmap::get_mmap_manager().copy_table(parent.pid as u64, child as u64);
+    }
+
+    if flags & CLONE_PARENT_SETTID != 0 {
+        let _ = crate::user::process::copy_to_user(parent_tid, &(child as u32).to_ne_bytes());
+    }
+
+    Ok(child)
+}
+
+/// End the current task
+///
+/// Clears and futex-wakes the `CLONE_CHILD_CLEARTID` address (so a joiner
+/// sees the thread is gone), closes the task's files and leaves it a
+/// zombie. The last thread of a process also ends the process (see
+/// `process::exit_notify`).
+pub fn exit_thread(status: WaitStatus) -> ! {
+    let task = match crate::sched::current_task_mut() {
+        Some(task) => task,
+        None => panic!("[THREAD] exit_thread called without a current task"),
+    };
+    let id = task.id;
+
+    let clear_child_tid = core::mem::replace(&mut task.clear_child_tid, 0);
+    if clear_child_tid != 0
+        && crate::user::process::copy_to_user(clear_child_tid, &0u32.to_ne_bytes()).is_ok()
+    {
+        crate::sync::futex::futex_wake(clear_child_tid, 1);
+    }
+
+    // Interval timers would otherwise keep signalling a dead task
+    crate::sched::hrtimer::timer_delete_all(id);
+
+    // The footprint is largest just before the image goes away
+    task.rusage.maxrss = task.rusage.maxrss.max(task.resident_memory_usage());
+
+    // Files close with the last task holding the table, not at reaping
+    task.fd_table = Arc::new(SpinLock::new(FdTable::new()));
+
+    crate::serial_println!("[THREAD] Task {} exited: {:?}", id, status);
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        task.exit_status = Some(status);
+        task.state = TaskState::Zombie;
+        crate::user::ptrace::exit_tracee(task);
+
+        // Leaving after becoming a zombie: once the group is empty, all of
+        // its tasks can be freed
+        let last = task
+            .thread_group
+            .as_ref()
+            .is_none_or(|group| group.leave(id));
+        if last {
+            crate::user::process::exit_notify(task);
+        }
+
+        crate::sched::schedule();
+    });
+
+    panic!("[THREAD] Exited task {} was scheduled again", id);
+}
+
+/// End every thread in the current thread group
+///
+/// The other threads are woken from any sleep, and those running on other
+/// CPUs are kicked with an IPI. Each exits with `status` on its next return
+/// towards user mode.
+pub fn exit_group(status: WaitStatus) -> ! {
+    let current = crate::sched::current_task().map(|task| (task.id, task.thread_group.clone()));
+
+    if let Some((id, Some(group))) = current {
+        let others: Vec<TaskId> = {
+            let mut state = group.state.lock();
+            state.exit_status.get_or_insert(status);
+            state
+                .members
+                .iter()
+                .copied()
+                .filter(|&member| member != id)
+                .collect()
+        };
+        for member in others {
+            crate::sched::wake_task(member);
+            crate::sched::kick_task(member);
+        }
+    }
+
+    exit_thread(status);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys::syscall::FdType;
+    use core::sync::atomic::AtomicU64;
+
+    #[test]
+    fn test_validate_clone_flags() {
+        // fork-like and pthread_create-like flag sets
+        assert_eq!(validate_clone_flags(0), Ok(()));
+        let thread = CLONE_VM
+            | CLONE_FILES
+            | CLONE_SIGHAND
+            | CLONE_THREAD
+            | CLONE_SETTLS
+            | CLONE_PARENT_SETTID
+            | CLONE_CHILD_CLEARTID;
+        assert_eq!(validate_clone_flags(thread), Ok(()));
+
+        assert_eq!(
+            validate_clone_flags(CLONE_VM | CLONE_THREAD),
+            Err(CloneError::InvalidFlags)
+        );
+        assert_eq!(
+            validate_clone_flags(CLONE_SIGHAND),
+            Err(CloneError::InvalidFlags)
+        );
+        assert_eq!(
+            validate_clone_flags(0x0200_0000),
+            Err(CloneError::InvalidFlags)
+        );
+    }
+
+    fn signalfd() -> FdType {
+        FdType::SignalFd(Arc::new(AtomicU64::new(0)))
+    }
+
+    #[test]
+    fn test_fork_child_close_keeps_parent_fd() {
+        let parent = Arc::new(SpinLock::new(FdTable::new()));
+        let fd = parent.lock().allocate(signalfd(), 16).unwrap();
+
+        let child = copy_files(&parent, 0);
+        assert!(!Arc::ptr_eq(&parent, &child));
+        assert!(child.lock().close(fd).is_some());
+        assert!(child.lock().get(fd).is_none());
+        assert!(parent.lock().get(fd).is_some());
+
+        // Descriptors opened afterwards stay in their own table
+        let parent_fd = parent.lock().allocate(signalfd(), 16).unwrap();
+        assert!(child.lock().get(parent_fd).is_none());
+    }
+
+    #[test]
+    fn test_clone_files_shares_table() {
+        let parent = Arc::new(SpinLock::new(FdTable::new()));
+        let fd = parent.lock().allocate(signalfd(), 16).unwrap();
+
+        let thread = copy_files(&parent, CLONE_FILES);
+        assert!(Arc::ptr_eq(&parent, &thread));
+        let thread_fd = thread.lock().allocate(signalfd(), 16).unwrap();
+        assert!(parent.lock().get(thread_fd).is_some());
+        assert!(thread.lock().close(fd).is_some());
+        assert!(parent.lock().get(fd).is_none());
+    }
+}
@@ -2,7 +2,7 @@
 
 use alloc::string::String;
 use alloc::format;
-use crate::{Shell, syscalls, jobs::JobState};
+use crate::{Shell, syscalls, jobs::{JobState, SigchldBlock}};
 
 /// Execute a built-in command
 /// Returns Some(status) if command was a built-in, None otherwise
@@ -18,6 +18,7 @@ pub fn execute(shell: &mut Shell, cmd: &str, args: &[String]) -> Option<i32> {
         "bg" => Some(builtin_bg(shell, args)),
         "exit" => Some(builtin_exit(shell, args)),
         "which" => Some(builtin_which(shell, args)),
+        "ulimit" => Some(builtin_ulimit(args)),
         "debug-pty" => Some(builtin_debug_pty()),
         "debug-jobs" => Some(builtin_debug_jobs(shell)),
         "debug-signals" => Some(builtin_debug_signals()),
@@ -262,6 +263,7 @@ fn builtin_fg(shell: &mut Shell, args: &[String]) -> i32 {
     }
 
     // Update job state
+    let _sigchld = SigchldBlock::new();
     if let Some(job) = shell.jobs_mut().get_job_mut(job_id) {
         if status & 0x7f == 0 {
             let exit_status = (status >> 8) & 0xff;
@@ -307,6 +309,7 @@ fn builtin_bg(shell: &mut Shell, args: &[String]) -> i32 {
     syscalls::kill(job.pgid, syscalls::SIGCONT);
 
     // Update job state
+    let _sigchld = SigchldBlock::new();
     if let Some(job) = shell.jobs_mut().get_job_mut(job_id) {
         job.state = JobState::Running;
         syscalls::write(1, format!("[{}]+ {}    {}\n", job.id, "Running", job.command).as_bytes());
@@ -383,6 +386,127 @@ fn builtin_which(shell: &Shell, args: &[String]) -> i32 {
     if found_all { 0 } else { 1 }
 }
 
+/// Limits `ulimit` knows: option, resource, unit in bytes and description
+const ULIMITS: &[(char, usize, u64, &str)] = &[
+    ('c', syscalls::RLIMIT_CORE, 1024, "core file size (kbytes)"),
+    ('d', syscalls::RLIMIT_DATA, 1024, "data seg size (kbytes)"),
+    ('f', syscalls::RLIMIT_FSIZE, 1024, "file size (kbytes)"),
+    ('n', syscalls::RLIMIT_NOFILE, 1, "open files"),
+    ('s', syscalls::RLIMIT_STACK, 1024, "stack size (kbytes)"),
+    ('t', syscalls::RLIMIT_CPU, 1, "cpu time (seconds)"),
+    ('u', syscalls::RLIMIT_NPROC, 1, "max user processes"),
+    ('v', syscalls::RLIMIT_AS, 1024, "virtual memory (kbytes)"),
+];
+
+/// ulimit - show or set resource limits
+///
+/// `ulimit [-S|-H] [-a | -c|-d|-f|-n|-s|-t|-u|-v [LIMIT]]`. The default
+/// resource is -f. A new limit (a number or "unlimited") sets the soft
+/// limit with -S, the hard limit with -H, and both otherwise; it applies
+/// to the shell and the commands it starts.
+fn builtin_ulimit(args: &[String]) -> i32 {
+    let mut soft = false;
+    let mut hard = false;
+    let mut all = false;
+    let mut option = 'f';
+    let mut value = None;
+
+    for arg in args {
+        if let Some(flags) = arg.strip_prefix('-') {
+            for flag in flags.chars() {
+                match flag {
+                    'S' => soft = true,
+                    'H' => hard = true,
+                    'a' => all = true,
+                    _ if ULIMITS.iter().any(|limit| limit.0 == flag) => option = flag,
+                    _ => {
+                        syscalls::write(2, format!("ulimit: -{}: invalid option\n", flag).as_bytes());
+                        return 2;
+                    }
+                }
+            }
+        } else {
+            value = Some(arg.as_str());
+        }
+    }
+
+    if all {
+        for &(flag, resource, unit, description) in ULIMITS {
+            let shown = match get_limit(resource, hard) {
+                Some(limit) => format_limit(limit, unit),
+                None => String::from("?"),
+            };
+            let line = format!("{:<26}(-{}) {}\n", description, flag, shown);
+            syscalls::write(1, line.as_bytes());
+        }
+        return 0;
+    }
+
+    let &(_, resource, unit, _) = ULIMITS.iter().find(|limit| limit.0 == option).unwrap();
+    let value = match value {
+        Some(value) => value,
+        None => {
+            return match get_limit(resource, hard) {
+                Some(limit) => {
+                    syscalls::write(1, format!("{}\n", format_limit(limit, unit)).as_bytes());
+                    0
+                }
+                None => {
+                    syscalls::write(2, b"ulimit: cannot get limit\n");
+                    1
+                }
+            };
+        }
+    };
+
+    let new = if value == "unlimited" {
+        syscalls::RLIM_INFINITY
+    } else {
+        match value.parse::<u64>() {
+            Ok(number) => number.saturating_mul(unit),
+            Err(_) => {
+                syscalls::write(2, format!("ulimit: {}: invalid number\n", value).as_bytes());
+                return 1;
+            }
+        }
+    };
+
+    let mut limit = syscalls::RLimit::default();
+    if syscalls::getrlimit(resource, &mut limit) < 0 {
+        syscalls::write(2, b"ulimit: cannot get limit\n");
+        return 1;
+    }
+    if soft || !hard {
+        limit.rlim_cur = new;
+    }
+    if hard || !soft {
+        limit.rlim_max = new;
+    }
+    if syscalls::setrlimit(resource, &limit) < 0 {
+        syscalls::write(2, b"ulimit: cannot modify limit: Operation not permitted\n");
+        return 1;
+    }
+    0
+}
+
+/// Soft (or with `hard`, hard) limit of a resource
+fn get_limit(resource: usize, hard: bool) -> Option<u64> {
+    let mut limit = syscalls::RLimit::default();
+    if syscalls::getrlimit(resource, &mut limit) < 0 {
+        return None;
+    }
+    Some(if hard { limit.rlim_max } else { limit.rlim_cur })
+}
+
+/// A limit in `unit`s, or "unlimited"
+fn format_limit(limit: u64, unit: u64) -> String {
+    if limit == syscalls::RLIM_INFINITY {
+        String::from("unlimited")
+    } else {
+        format!("{}", limit / unit)
+    }
+}
+
 /// debug-pty - show PTY state from /proc/debug/pty
 fn builtin_debug_pty() -> i32 {
     syscalls::write(1, b"=== PTY Debug Information ===\n");
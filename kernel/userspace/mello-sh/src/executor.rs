@@ -3,7 +3,7 @@
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use alloc::format;
-use crate::{Shell, parser::{Command, Redirect, RedirectKind}, syscalls, builtins};
+use crate::{Shell, parser::{Command, Redirect, RedirectKind}, syscalls, builtins, jobs::SigchldBlock};
 
 /// Execute a command
 pub fn execute(shell: &mut Shell, command: Command) -> Result<i32, String> {
@@ -35,7 +35,9 @@ fn execute_simple(
         return Ok(status);
     }
 
-    // External command - fork and exec
+    // External command - fork and exec, with SIGCHLD held back until the
+    // job is recorded
+    let sigchld = SigchldBlock::new();
     let pid = syscalls::fork();
     
     if pid < 0 {
@@ -44,6 +46,7 @@ fn execute_simple(
 
     if pid == 0 {
         // Child process
+        drop(sigchld);
         
         // Set process group
         syscalls::setpgid(0, 0);
@@ -151,7 +154,8 @@ fn execute_pipeline(
         pipes.push(pipe_fds);
     }
 
-    // Fork children
+    // Fork children, with SIGCHLD held back until the job is recorded
+    let sigchld = SigchldBlock::new();
     for (i, cmd) in commands.iter().enumerate() {
         let pid = syscalls::fork();
         
@@ -161,6 +165,7 @@ fn execute_pipeline(
 
         if pid == 0 {
             // Child process
+            drop(sigchld);
             
             // Set up stdin from previous pipe
             if i > 0 {
@@ -24,6 +24,32 @@ pub struct Job {
     pub processes: Vec<i32>,
 }
 
+/// Keeps SIGCHLD blocked while alive
+///
+/// Job-table updates run with SIGCHLD blocked, so a child cannot change
+/// state between being forked and having its job recorded; the signal
+/// stays pending until the guard is dropped. A forked child drops its copy
+/// before running anything.
+pub struct SigchldBlock {
+    old_mask: u64,
+}
+
+impl SigchldBlock {
+    /// Block SIGCHLD
+    pub fn new() -> Self {
+        let mut old_mask = 0;
+        let set = syscalls::sigmask(syscalls::SIGCHLD);
+        syscalls::sigprocmask(syscalls::SIG_BLOCK, Some(&set), Some(&mut old_mask));
+        Self { old_mask }
+    }
+}
+
+impl Drop for SigchldBlock {
+    fn drop(&mut self) {
+        syscalls::sigprocmask(syscalls::SIG_SETMASK, Some(&self.old_mask), None);
+    }
+}
+
 /// Job table
 pub struct JobTable {
     jobs: Vec<Job>,
@@ -95,6 +121,7 @@ impl JobTable {
 
     /// Check for completed or stopped jobs
     pub fn check_jobs(&mut self) {
+        let _sigchld = SigchldBlock::new();
         let mut status = 0;
         
         loop {
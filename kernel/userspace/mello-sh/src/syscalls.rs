@@ -19,6 +19,7 @@ const SYS_TCSETPGRP: usize = 136;
 const SYS_TCGETPGRP: usize = 137;
 const SYS_KILL: usize = 62;
 const SYS_SIGACTION: usize = 13;
+const SYS_RT_SIGPROCMASK: usize = 14;
 const SYS_GETCWD: usize = 79;
 const SYS_CHDIR: usize = 80;
 
@@ -189,6 +190,16 @@ pub fn kill(pid: i32, sig: i32) -> isize {
     unsafe { syscall2(SYS_KILL, pid as usize, sig as usize) }
 }
 
+/// Examine and change the signal mask
+///
+/// Applies `set` according to `how` (if given) and stores the previous mask
+/// in `old` (if given).
+pub fn sigprocmask(how: i32, set: Option<&u64>, old: Option<&mut u64>) -> isize {
+    let set_ptr = set.map_or(0, |set| set as *const u64 as usize);
+    let old_ptr = old.map_or(0, |old| old as *mut u64 as usize);
+    unsafe { syscall4(SYS_RT_SIGPROCMASK, how as usize, set_ptr, old_ptr, 8) }
+}
+
 /// Get current working directory
 pub fn getcwd(buf: &mut [u8]) -> isize {
     unsafe { syscall2(SYS_GETCWD, buf.as_mut_ptr() as usize, buf.len()) }
@@ -225,6 +236,15 @@ pub const SIGTSTP: i32 = 20;
 pub const SIGCONT: i32 = 18;
 pub const SIGCHLD: i32 = 17;
 
+// sigprocmask operations
+pub const SIG_BLOCK: i32 = 0;
+pub const SIG_SETMASK: i32 = 2;
+
+/// Signal set holding just `sig` (bit 0 is signal 1)
+pub const fn sigmask(sig: i32) -> u64 {
+    1 << (sig - 1)
+}
+
 // Open flags
 pub const O_RDONLY: i32 = 0;
 pub const O_WRONLY: i32 = 1;
@@ -288,3 +308,52 @@ pub struct BlockDeviceInfo {
 pub fn get_block_device_info(info: &mut BlockDeviceInfo) -> isize {
     unsafe { syscall1(SYS_GET_BLOCK_DEVICE_INFO, info as *mut _ as usize) }
 }
+
+// Resource limits go through the `int 0x80` gate, with the kernel's own
+// syscall numbers
+const KSYS_GETRLIMIT: usize = 103;
+const KSYS_SETRLIMIT: usize = 104;
+
+/// Raw `int 0x80` system call with 2 arguments
+#[inline]
+unsafe fn int80_syscall2(n: usize, arg1: usize, arg2: usize) -> isize {
+    let ret: isize;
+    asm!(
+        "int 0x80",
+        in("rax") n,
+        in("rdi") arg1,
+        in("rsi") arg2,
+        lateout("rax") ret,
+        options(nostack)
+    );
+    ret
+}
+
+/// Soft and hard limit of a resource (must match kernel definition)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RLimit {
+    pub rlim_cur: u64,
+    pub rlim_max: u64,
+}
+
+// Resources
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_FSIZE: usize = 1;
+pub const RLIMIT_DATA: usize = 2;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// Get the limits of a resource
+pub fn getrlimit(resource: usize, limit: &mut RLimit) -> isize {
+    unsafe { int80_syscall2(KSYS_GETRLIMIT, resource, limit as *mut RLimit as usize) }
+}
+
+/// Set the limits of a resource
+pub fn setrlimit(resource: usize, limit: &RLimit) -> isize {
+    unsafe { int80_syscall2(KSYS_SETRLIMIT, resource, limit as *const RLimit as usize) }
+}
@@ -0,0 +1,39 @@
+//! id - print user and group IDs
+
+use crate::args::Args;
+use crate::error::Result;
+use crate::syscalls;
+use alloc::format;
+use alloc::string::String;
+
+/// Most supplementary groups a task can have (the kernel's NGROUPS_MAX)
+const NGROUPS_MAX: usize = 32;
+
+pub fn main(argv: &'static [&'static str]) -> Result<i32> {
+    let _args = Args::parse(argv, "")?;
+
+    let [ruid, euid, _] = syscalls::getresuid();
+    let [rgid, egid, _] = syscalls::getresgid();
+    let mut groups = [0u32; NGROUPS_MAX];
+    let count = syscalls::getgroups(&mut groups).max(0) as usize;
+
+    let mut line = format!("uid={} gid={}", ruid, rgid);
+    if euid != ruid {
+        line.push_str(&format!(" euid={}", euid));
+    }
+    if egid != rgid {
+        line.push_str(&format!(" egid={}", egid));
+    }
+    if count > 0 {
+        let list: String = groups[..count]
+            .iter()
+            .map(|gid| format!("{}", gid))
+            .collect::<alloc::vec::Vec<_>>()
+            .join(",");
+        line.push_str(&format!(" groups={}", list));
+    }
+    line.push('\n');
+    syscalls::write(1, line.as_bytes());
+
+    Ok(0)
+}
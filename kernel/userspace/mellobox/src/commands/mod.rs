@@ -6,6 +6,7 @@ pub mod df;
 pub mod echo;
 pub mod false_cmd;
 pub mod grep;
+pub mod id;
 pub mod kill;
 pub mod ls;
 pub mod mkdir;
@@ -14,7 +15,9 @@ pub mod mv;
 pub mod ps;
 pub mod pwd;
 pub mod rm;
+pub mod sandbox;
 pub mod stat;
+pub mod strace;
 pub mod touch;
 pub mod true_cmd;
 pub mod umount;
@@ -6,6 +6,12 @@ use crate::syscalls;
 use alloc::string::String;
 use alloc::vec::Vec;
 
+/// Clock ticks per second in /proc times
+const CLOCK_TICKS_PER_SEC: usize = 100;
+
+/// Size of a page in kB (/proc reports RSS in pages)
+const PAGE_SIZE_KB: usize = 4;
+
 // Directory entry structure (must match kernel's getdents)
 #[repr(C)]
 struct DirEnt {
@@ -26,7 +32,7 @@ pub fn main(argv: &'static [&'static str]) -> Result<i32> {
     if user_format {
         syscalls::write(
             1,
-            b"USER       PID  PPID  PGID   SID TTY      STAT   TIME COMMAND\n",
+            b"USER       PID  PPID  PGID   SID %CPU    VSZ   RSS TTY      STAT   TIME COMMAND\n",
         );
     } else {
         syscalls::write(1, b"  PID TTY          TIME CMD\n");
@@ -91,11 +97,14 @@ pub fn main(argv: &'static [&'static str]) -> Result<i32> {
     // Sort processes by PID
     processes.sort();
 
+    // %CPU is the share of its lifetime a process spent on a CPU
+    let uptime = read_uptime_ticks();
+
     // Display each process
     for pid in processes {
         if let Ok(info) = read_proc_stat(pid) {
             if user_format {
-                print_process_user_format(&info);
+                print_process_user_format(&info, uptime);
             } else {
                 print_process_simple(&info);
             }
@@ -112,13 +121,51 @@ struct ProcessInfo {
     sid: usize,
     tty_nr: usize,
     state: char,
-    #[allow(dead_code)]
     utime: usize,
-    #[allow(dead_code)]
     stime: usize,
+    starttime: usize,
+    vsize: usize,
+    rss: usize,
     comm: String,
 }
 
+impl ProcessInfo {
+    /// CPU time used, in clock ticks
+    fn cpu_ticks(&self) -> usize {
+        self.utime + self.stime
+    }
+
+    /// CPU usage over the process's lifetime, in tenths of a percent
+    fn cpu_permille(&self, uptime: usize) -> usize {
+        let elapsed = uptime.saturating_sub(self.starttime);
+        if elapsed == 0 {
+            return 0;
+        }
+        self.cpu_ticks() * 1000 / elapsed
+    }
+}
+
+/// Read the system uptime from /proc/uptime, in clock ticks
+fn read_uptime_ticks() -> usize {
+    let fd = syscalls::openat(syscalls::AT_FDCWD, b"/proc/uptime\0", syscalls::O_RDONLY, 0);
+    if fd < 0 {
+        return 0;
+    }
+
+    let mut buf = [0u8; 128];
+    let nread = syscalls::read(fd as i32, &mut buf);
+    syscalls::close(fd as i32);
+    if nread <= 0 {
+        return 0;
+    }
+
+    // Format: seconds.hundredths idle_seconds.hundredths
+    let text = core::str::from_utf8(&buf[..nread as usize]).unwrap_or("");
+    let uptime = text.split_whitespace().next().unwrap_or("0");
+    let (secs, hundredths) = uptime.split_once('.').unwrap_or((uptime, "0"));
+    secs.parse::<usize>().unwrap_or(0) * CLOCK_TICKS_PER_SEC + hundredths.parse().unwrap_or(0)
+}
+
 fn read_proc_stat(pid: usize) -> Result<ProcessInfo> {
     // Build path: /proc/<pid>/stat
     let mut path = String::from("/proc/");
@@ -173,6 +220,10 @@ fn parse_stat(stat: &str) -> Result<ProcessInfo> {
     let tty_nr = parts[6].parse().map_err(|_| Error::InvalidArgument)?;
     let utime = parts[13].parse().unwrap_or(0);
     let stime = parts[14].parse().unwrap_or(0);
+    let field = |index: usize| parts.get(index).and_then(|s| s.parse().ok()).unwrap_or(0);
+    let starttime = field(21);
+    let vsize = field(22);
+    let rss = field(23);
 
     Ok(ProcessInfo {
         pid,
@@ -183,6 +234,9 @@ fn parse_stat(stat: &str) -> Result<ProcessInfo> {
         state,
         utime,
         stime,
+        starttime,
+        vsize,
+        rss,
         comm: String::from(comm),
     })
 }
@@ -222,11 +276,15 @@ fn print_process_simple(info: &ProcessInfo) {
         }
     }
 
-    // TIME (simplified - just show 00:00:00)
-    for &b in b"00:00:00 " {
-        buf[pos] = b;
-        pos += 1;
+    // TIME (HH:MM:SS)
+    let secs = info.cpu_ticks() / CLOCK_TICKS_PER_SEC;
+    for part in [secs / 3600, secs / 60 % 60, secs % 60] {
+        buf[pos] = b'0' + (part / 10 % 10) as u8;
+        buf[pos + 1] = b'0' + (part % 10) as u8;
+        buf[pos + 2] = b':';
+        pos += 3;
     }
+    buf[pos - 1] = b' ';
 
     // Command
     for &b in info.comm.as_bytes() {
@@ -243,7 +301,7 @@ fn print_process_simple(info: &ProcessInfo) {
     syscalls::write(1, &buf[..pos]);
 }
 
-fn print_process_user_format(info: &ProcessInfo) {
+fn print_process_user_format(info: &ProcessInfo, uptime: usize) {
     let mut buf = [0u8; 256];
     let mut pos = 0;
 
@@ -325,6 +383,22 @@ fn print_process_user_format(info: &ProcessInfo) {
     buf[pos] = b' ';
     pos += 1;
 
+    // %CPU (4 chars, one decimal)
+    let permille = info.cpu_permille(uptime);
+    let mut cpu_str = format_number(permille / 10);
+    cpu_str.push('.');
+    cpu_str.push((b'0' + (permille % 10) as u8) as char);
+    write_right_aligned(&mut buf, &mut pos, &cpu_str, 4);
+
+    // VSZ and RSS in kB (6 and 5 chars)
+    write_right_aligned(&mut buf, &mut pos, &format_number(info.vsize / 1024), 6);
+    write_right_aligned(
+        &mut buf,
+        &mut pos,
+        &format_number(info.rss * PAGE_SIZE_KB),
+        5,
+    );
+
     // TTY
     if info.tty_nr == 0 {
         for &b in b"?        " {
@@ -346,11 +420,13 @@ fn print_process_user_format(info: &ProcessInfo) {
         pos += 1;
     }
 
-    // TIME
-    for &b in b"0:00 " {
-        buf[pos] = b;
-        pos += 1;
-    }
+    // TIME (M:SS)
+    let secs = info.cpu_ticks() / CLOCK_TICKS_PER_SEC;
+    let mut time_str = format_number(secs / 60);
+    time_str.push(':');
+    time_str.push((b'0' + (secs % 60 / 10) as u8) as char);
+    time_str.push((b'0' + (secs % 10) as u8) as char);
+    write_right_aligned(&mut buf, &mut pos, &time_str, 4);
 
     // COMMAND
     for &b in info.comm.as_bytes() {
@@ -367,6 +443,20 @@ fn print_process_user_format(info: &ProcessInfo) {
     syscalls::write(1, &buf[..pos]);
 }
 
+/// Write `s` right-aligned in `width` columns, followed by a space
+fn write_right_aligned(buf: &mut [u8], pos: &mut usize, s: &str, width: usize) {
+    for _ in s.len()..width {
+        buf[*pos] = b' ';
+        *pos += 1;
+    }
+    for &b in s.as_bytes() {
+        buf[*pos] = b;
+        *pos += 1;
+    }
+    buf[*pos] = b' ';
+    *pos += 1;
+}
+
 fn format_number(n: usize) -> String {
     if n == 0 {
         return String::from("0");
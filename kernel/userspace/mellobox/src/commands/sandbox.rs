@@ -0,0 +1,113 @@
+//! sandbox - run a program with some system calls refused
+//!
+//! `sandbox [-k] SYSCALL[,SYSCALL...] PROGRAM [ARGS...]` installs a syscall
+//! filter that makes the listed syscalls (by name or number) fail with
+//! EPERM, or kill the program with `-k`, and then runs the program. The
+//! filter stays with the program and everything it starts.
+
+use super::strace::SYSCALL_NAMES;
+use crate::error::{Error, Result};
+use crate::syscalls;
+use alloc::format;
+use alloc::vec::Vec;
+
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const EPERM: u32 = 1;
+
+/// Filter program for `PR_SET_SECCOMP`
+#[repr(C)]
+struct SeccompProg {
+    default_action: u32,
+    count: u32,
+    rules: u64,
+}
+
+/// One filter rule (argument checks are not used here)
+#[repr(C)]
+#[derive(Default)]
+struct SeccompRule {
+    nr: u32,
+    action: u32,
+    arg_count: u32,
+    _pad: u32,
+    args: [[u64; 3]; 6],
+}
+
+pub fn main(argv: &'static [&'static str]) -> Result<i32> {
+    let (kill, rest) = match argv.get(1) {
+        Some(&"-k") => (true, &argv[2..]),
+        _ => (false, &argv[1..]),
+    };
+    if rest.len() < 2 {
+        return Err(Error::MissingArgument);
+    }
+
+    let action = if kill {
+        SECCOMP_RET_KILL_PROCESS
+    } else {
+        SECCOMP_RET_ERRNO | EPERM
+    };
+    let mut rules = Vec::new();
+    for name in rest[0].split(',') {
+        let nr = syscall_number(name).ok_or(Error::InvalidOptionArgument)?;
+        rules.push(SeccompRule {
+            nr,
+            action,
+            ..SeccompRule::default()
+        });
+    }
+    let prog = SeccompProg {
+        default_action: SECCOMP_RET_ALLOW,
+        count: rules.len() as u32,
+        rules: rules.as_ptr() as u64,
+    };
+
+    // Without no_new_privs only a privileged task may install a filter
+    let result = syscalls::prctl(syscalls::PR_SET_NO_NEW_PRIVS, 1, 0);
+    if result < 0 {
+        return Err(Error::SyscallFailed(result));
+    }
+    let result = syscalls::prctl(
+        syscalls::PR_SET_SECCOMP,
+        syscalls::SECCOMP_MODE_FILTER,
+        &prog as *const SeccompProg as usize,
+    );
+    if result < 0 {
+        return Err(Error::SyscallFailed(result));
+    }
+
+    exec(&rest[1..])
+}
+
+/// Syscall number for a name from strace's table, or a plain number
+fn syscall_number(name: &str) -> Option<u32> {
+    if let Ok(nr) = name.parse() {
+        return Some(nr);
+    }
+    SYSCALL_NAMES
+        .iter()
+        .position(|known| *known == name)
+        .map(|nr| nr as u32)
+}
+
+/// Replace this process with the program
+fn exec(argv: &[&str]) -> Result<i32> {
+    let args: Vec<Vec<u8>> = argv
+        .iter()
+        .map(|arg| {
+            let mut bytes = Vec::from(arg.as_bytes());
+            bytes.push(0);
+            bytes
+        })
+        .collect();
+    let mut pointers: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+    pointers.push(core::ptr::null());
+
+    syscalls::execv(args[0].as_ptr(), pointers.as_ptr());
+
+    let msg = format!("sandbox: cannot run '{}'\n", argv[0]);
+    syscalls::write(2, msg.as_bytes());
+    Ok(127)
+}
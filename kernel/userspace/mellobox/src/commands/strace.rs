@@ -0,0 +1,253 @@
+//! strace - trace the system calls and signals of a program
+//!
+//! Runs the program as a traced child and prints each syscall it makes
+//! through the `int 0x80` gate, with its first three arguments and result,
+//! and each signal it receives, on stderr.
+
+use crate::error::{Error, Result};
+use crate::syscalls::{self, UserRegs};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const SIGTRAP: i32 = 5;
+const SIGSTOP: i32 = 19;
+
+/// RAX at a syscall-entry-stop (`-ENOSYS`)
+const ENTRY_RAX: u64 = -38i64 as u64;
+
+/// Kernel syscall names, by number
+pub const SYSCALL_NAMES: &[&str] = &[
+    "write",
+    "exit",
+    "sleep",
+    "ipc_send",
+    "ipc_recv",
+    "getpid",
+    "yield",
+    "fork",
+    "wait",
+    "exec",
+    "open",
+    "read",
+    "close",
+    "ioctl",
+    "sigaction",
+    "kill",
+    "setpgid",
+    "getpgrp",
+    "setsid",
+    "getsid",
+    "tcsetpgrp",
+    "tcgetpgrp",
+    "fcntl",
+    "pipe2",
+    "dup2",
+    "read_stdin",
+    "serial_write",
+    "serial_read",
+    "block_read",
+    "block_write",
+    "get_device_list",
+    "get_block_device_info",
+    "read_kernel_log",
+    "get_irq_stats",
+    "stat",
+    "fstat",
+    "lstat",
+    "chmod",
+    "chown",
+    "utimensat",
+    "setxattr",
+    "getxattr",
+    "listxattr",
+    "mknod",
+    "sync",
+    "fsync",
+    "fdatasync",
+    "mount",
+    "umount",
+    "get_mount_info",
+    "nice",
+    "setpriority",
+    "getpriority",
+    "sched_setscheduler",
+    "sched_getscheduler",
+    "sched_getparam",
+    "nanosleep",
+    "clock_gettime",
+    "timer_create",
+    "timer_settime",
+    "timer_gettime",
+    "timer_delete",
+    "timer_getoverrun",
+    "sched_setaffinity",
+    "sched_getaffinity",
+    "cpuset_create",
+    "cpuset_setcpus",
+    "cpuset_attach",
+    "cpuset_destroy",
+    "clone",
+    "arch_prctl",
+    "futex",
+    "gettid",
+    "exit_group",
+    "wait4",
+    "waitid",
+    "rt_sigreturn",
+    "rt_sigprocmask",
+    "rt_sigpending",
+    "rt_sigsuspend",
+    "rt_sigtimedwait",
+    "sigaltstack",
+    "signalfd",
+    "poll",
+    "sigqueue",
+    "ptrace",
+    "getuid",
+    "geteuid",
+    "getgid",
+    "getegid",
+    "setuid",
+    "setgid",
+    "setreuid",
+    "setregid",
+    "setresuid",
+    "setresgid",
+    "getresuid",
+    "getresgid",
+    "getgroups",
+    "setgroups",
+    "capget",
+    "capset",
+    "prctl",
+    "getrlimit",
+    "setrlimit",
+    "prlimit",
+];
+
+pub fn main(argv: &'static [&'static str]) -> Result<i32> {
+    if argv.len() < 2 {
+        return Err(Error::MissingArgument);
+    }
+
+    let pid = syscalls::fork();
+    if pid < 0 {
+        return Err(Error::from_errno(pid));
+    }
+    if pid == 0 {
+        run_tracee(&argv[1..]);
+    }
+    trace(pid as i32)
+}
+
+/// Become traced and run the program (in the child)
+fn run_tracee(argv: &[&str]) -> ! {
+    let args: Vec<Vec<u8>> = argv
+        .iter()
+        .map(|arg| {
+            let mut bytes = Vec::from(arg.as_bytes());
+            bytes.push(0);
+            bytes
+        })
+        .collect();
+    let mut pointers: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+    pointers.push(core::ptr::null());
+
+    if syscalls::ptrace(syscalls::PTRACE_TRACEME, 0, 0, 0) < 0 {
+        syscalls::write(2, b"strace: PTRACE_TRACEME failed\n");
+        syscalls::exit_group(127);
+    }
+    // Let the tracer set its options before the program starts
+    syscalls::raise(SIGSTOP);
+    syscalls::execv(args[0].as_ptr(), pointers.as_ptr());
+
+    let msg = format!("strace: cannot run '{}'\n", argv[0]);
+    syscalls::write(2, msg.as_bytes());
+    syscalls::exit_group(127)
+}
+
+/// Report the tracee's syscalls and signals until it ends
+///
+/// Returns the tracee's exit code (128 + signal if it was killed).
+fn trace(pid: i32) -> Result<i32> {
+    let mut started = false;
+    let mut in_syscall = false;
+
+    loop {
+        let mut status = 0;
+        let result = syscalls::wait4(pid, &mut status, 0);
+        if result < 0 {
+            return Err(Error::SyscallFailed(result));
+        }
+
+        // A syscall that never returned (exit, exec) ends without a result
+        let unfinished = if in_syscall { " = ?\n" } else { "" };
+        if status & 0x7f == 0 {
+            let code = (status >> 8) & 0xff;
+            print(&format!("{}+++ exited with {} +++\n", unfinished, code));
+            return Ok(code);
+        }
+        if status & 0xff != 0x7f {
+            let signal = status & 0x7f;
+            print(&format!(
+                "{}+++ killed by signal {} +++\n",
+                unfinished, signal
+            ));
+            return Ok(128 + signal);
+        }
+
+        let signal = (status >> 8) & 0xff;
+        let mut inject = 0;
+        if !started {
+            // The SIGSTOP the child raised before exec
+            started = true;
+            syscalls::ptrace(
+                syscalls::PTRACE_SETOPTIONS,
+                pid,
+                0,
+                syscalls::PTRACE_O_TRACESYSGOOD,
+            );
+        } else if signal == SIGTRAP | 0x80 {
+            let mut regs = UserRegs::default();
+            syscalls::ptrace(
+                syscalls::PTRACE_GETREGS,
+                pid,
+                0,
+                &mut regs as *mut UserRegs as usize,
+            );
+            if regs.rax == ENTRY_RAX {
+                print(&format!("{}{}", unfinished, syscall_entry(&regs)));
+                in_syscall = true;
+            } else {
+                print(&format!(") = {}\n", regs.rax as i64));
+                in_syscall = false;
+            }
+        } else {
+            print(&format!("{}--- signal {} ---\n", unfinished, signal));
+            in_syscall = false;
+            // The SIGTRAP of exec is for the tracer only
+            if signal != SIGTRAP && signal != SIGSTOP {
+                inject = signal as usize;
+            }
+        }
+
+        if syscalls::ptrace(syscalls::PTRACE_SYSCALL, pid, 0, inject) < 0 {
+            return Err(Error::SyscallFailed(-1));
+        }
+    }
+}
+
+/// Start of a syscall line: the name and first three arguments
+fn syscall_entry(regs: &UserRegs) -> String {
+    let number = regs.orig_rax as usize;
+    let name = match SYSCALL_NAMES.get(number) {
+        Some(name) => String::from(*name),
+        None => format!("syscall_{}", number),
+    };
+    format!("{}({:#x}, {:#x}, {:#x}", name, regs.rdi, regs.rsi, regs.rdx)
+}
+
+fn print(text: &str) {
+    syscalls::write(2, text.as_bytes());
+}
@@ -99,6 +99,18 @@ const APPLETS: &[Applet] = &[
         name: "umount",
         func: commands::umount::main,
     },
+    Applet {
+        name: "strace",
+        func: commands::strace::main,
+    },
+    Applet {
+        name: "id",
+        func: commands::id::main,
+    },
+    Applet {
+        name: "sandbox",
+        func: commands::sandbox::main,
+    },
 ];
 
 /// Extract program name from argv[0]
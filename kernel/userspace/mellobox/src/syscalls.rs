@@ -212,6 +212,170 @@ pub fn kill(pid: i32, sig: i32) -> isize {
     unsafe { syscall2(SYS_KILL, pid as usize, sig as usize) }
 }
 
+// Process control and tracing go through the `int 0x80` gate, with the
+// kernel's own syscall numbers: it is the gate that reports ptrace stops
+const KSYS_GETPID: usize = 5;
+const KSYS_FORK: usize = 7;
+const KSYS_EXEC: usize = 9;
+const KSYS_KILL: usize = 15;
+const KSYS_EXIT_GROUP: usize = 73;
+const KSYS_WAIT4: usize = 74;
+const KSYS_PTRACE: usize = 85;
+const KSYS_GETRESUID: usize = 96;
+const KSYS_GETRESGID: usize = 97;
+const KSYS_GETGROUPS: usize = 98;
+const KSYS_PRCTL: usize = 102;
+
+/// Raw `int 0x80` system call with up to 4 arguments
+#[inline]
+unsafe fn int80_syscall4(n: usize, arg1: usize, arg2: usize, arg3: usize, arg4: usize) -> isize {
+    let ret: isize;
+    asm!(
+        "int 0x80",
+        in("rax") n,
+        in("rdi") arg1,
+        in("rsi") arg2,
+        in("rdx") arg3,
+        in("r10") arg4,
+        lateout("rax") ret,
+        options(nostack)
+    );
+    ret
+}
+
+/// Get the process ID
+pub fn getpid() -> isize {
+    unsafe { int80_syscall4(KSYS_GETPID, 0, 0, 0, 0) }
+}
+
+/// Create a child process (returns 0 in the child)
+pub fn fork() -> isize {
+    unsafe { int80_syscall4(KSYS_FORK, 0, 0, 0, 0) }
+}
+
+/// Replace the process image (`path` and `argv` entries are NUL-terminated,
+/// `argv` ends with a null pointer)
+pub fn execv(path: *const u8, argv: *const *const u8) -> isize {
+    unsafe { int80_syscall4(KSYS_EXEC, path as usize, argv as usize, 0, 0) }
+}
+
+/// Send a signal to the calling process
+pub fn raise(sig: i32) -> isize {
+    unsafe { int80_syscall4(KSYS_KILL, getpid() as usize, sig as usize, 0, 0) }
+}
+
+/// Exit all threads of the process
+pub fn exit_group(code: i32) -> ! {
+    unsafe {
+        int80_syscall4(KSYS_EXIT_GROUP, code as usize, 0, 0, 0);
+    }
+    loop {}
+}
+
+/// Wait for a child (or tracee) to change state
+pub fn wait4(pid: i32, status: &mut i32, options: i32) -> isize {
+    unsafe {
+        int80_syscall4(
+            KSYS_WAIT4,
+            pid as usize,
+            status as *mut i32 as usize,
+            options as usize,
+            0,
+        )
+    }
+}
+
+/// Trace another process
+pub fn ptrace(request: usize, pid: i32, addr: usize, data: usize) -> isize {
+    unsafe { int80_syscall4(KSYS_PTRACE, request, pid as usize, addr, data) }
+}
+
+/// Get the real, effective and saved user IDs
+pub fn getresuid() -> [u32; 3] {
+    let mut ids = [0u32; 3];
+    unsafe {
+        int80_syscall4(
+            KSYS_GETRESUID,
+            &mut ids[0] as *mut u32 as usize,
+            &mut ids[1] as *mut u32 as usize,
+            &mut ids[2] as *mut u32 as usize,
+            0,
+        );
+    }
+    ids
+}
+
+/// Get the real, effective and saved group IDs
+pub fn getresgid() -> [u32; 3] {
+    let mut ids = [0u32; 3];
+    unsafe {
+        int80_syscall4(
+            KSYS_GETRESGID,
+            &mut ids[0] as *mut u32 as usize,
+            &mut ids[1] as *mut u32 as usize,
+            &mut ids[2] as *mut u32 as usize,
+            0,
+        );
+    }
+    ids
+}
+
+/// Get the supplementary groups (returns how many were stored)
+pub fn getgroups(list: &mut [u32]) -> isize {
+    unsafe { int80_syscall4(KSYS_GETGROUPS, list.len(), list.as_mut_ptr() as usize, 0, 0) }
+}
+
+/// Process control operations
+pub fn prctl(option: usize, arg2: usize, arg3: usize) -> isize {
+    unsafe { int80_syscall4(KSYS_PRCTL, option, arg2, arg3, 0) }
+}
+
+// prctl options
+pub const PR_SET_SECCOMP: usize = 22;
+pub const PR_SET_NO_NEW_PRIVS: usize = 38;
+pub const SECCOMP_MODE_FILTER: usize = 2;
+
+// ptrace requests
+pub const PTRACE_TRACEME: usize = 0;
+pub const PTRACE_CONT: usize = 7;
+pub const PTRACE_GETREGS: usize = 12;
+pub const PTRACE_SYSCALL: usize = 24;
+pub const PTRACE_SETOPTIONS: usize = 0x4200;
+pub const PTRACE_O_TRACESYSGOOD: usize = 1;
+
+/// Registers as `PTRACE_GETREGS` returns them (`user_regs_struct`)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UserRegs {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64,
+}
+
 // Open flags
 pub const O_RDONLY: i32 = 0;
 pub const O_WRONLY: i32 = 1;